@@ -0,0 +1,62 @@
+//! Throughput of parsing results, run with `cargo bench --bench parse`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use vosk::ResultParser;
+
+/// A result as libvosk formats it, with `words` words.
+fn result_json(words: usize) -> String {
+    let mut entries = Vec::new();
+    let mut text = Vec::new();
+    for i in 0..words {
+        let word = ["turn", "on", "the", "kitchen", "lights", "please"][i % 6];
+        entries.push(format!(
+            "{{\n      \"conf\" : {:.6},\n      \"end\" : {:.6},\n      \"start\" : {:.6},\n      \"word\" : \"{}\"\n    }}",
+            0.5 + (i % 50) as f32 / 100.0,
+            i as f32 * 0.3 + 0.25,
+            i as f32 * 0.3,
+            word
+        ));
+        text.push(word);
+    }
+    format!(
+        "{{\n  \"result\" : [{}],\n  \"text\" : \"{}\"\n}}",
+        entries.join(", "),
+        text.join(" ")
+    )
+}
+
+/// Parses with `parse` for about a second, returning the time per call.
+fn measure<F: FnMut()>(mut parse: F) -> Duration {
+    let started = Instant::now();
+    let mut calls = 0u32;
+    while started.elapsed() < Duration::from_secs(1) {
+        for _ in 0..100 {
+            parse();
+        }
+        calls += 100;
+    }
+    started.elapsed() / calls
+}
+
+fn main() {
+    let mut parser = ResultParser::new();
+    let partial = r#"{
+  "partial" : "turn on the kitchen"
+}"#;
+    let per_call = measure(|| {
+        black_box(parser.partial(black_box(partial)).unwrap());
+    });
+    println!("partial         {:>8.2?}", per_call);
+    for &words in &[5, 50, 500] {
+        let json = result_json(words);
+        let per_call = measure(|| {
+            black_box(parser.result(black_box(&json)).unwrap());
+        });
+        let mb_per_s = json.len() as f64 / per_call.as_secs_f64() / 1e6;
+        println!(
+            "{:>3} words {:>8.2?} {:>7.1} MB/s",
+            words, per_call, mb_per_s
+        );
+    }
+}
@@ -0,0 +1,142 @@
+//! Recognizing speech captured through ALSA, for headless Linux devices
+//! such as a Raspberry Pi where there is no sound server.
+//!
+//! The capture device is opened directly, as 16-bit mono at the rate of the model
+//! if the hardware allows it. Otherwise the recognizer is created at the rate the
+//! device runs at and libvosk resamples. Overruns are logged and recovered from.
+//! Finalized utterances are printed to stdout, one per line.
+//!
+//! Run with `cargo run --example alsa_capture --features alsa-example -- -m model -d hw:1,0`
+
+use alsa::pcm::{Access, Format, HwParams, PCM};
+use alsa::{Direction, ValueOr};
+use argh::FromArgs;
+use std::thread;
+use std::time::Duration;
+use vosk::overload::{OverloadOptions, RingFeeder};
+use vosk::ring::{AudioProducer, AudioRing};
+use vosk::{Event, Model, Recognizer};
+
+/// Length of each read from the device.
+const PERIOD: Duration = Duration::from_millis(20);
+
+/// Periods in the buffer of the device, before a slow reader causes an overrun.
+const PERIODS: i64 = 8;
+
+#[derive(FromArgs)]
+/// Print what is said into an ALSA capture device
+struct Args {
+    /// path to the model
+    #[argh(option, short = 'm', default = "String::from(\"model\")")]
+    model: String,
+    /// name of the capture PCM, such as hw:1,0 or plughw:1,0
+    #[argh(option, short = 'd', default = "String::from(\"default\")")]
+    device: String,
+    /// sample rate the model was trained on
+    #[argh(option, short = 's', default = "16000")]
+    sample_rate: u32,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Args = argh::from_env();
+    let model = Model::new(&args.model).map_err(|e| e.to_string())?;
+
+    let pcm = PCM::new(&args.device, Direction::Capture, false)?;
+    let (rate, period) = configure(&pcm, args.sample_rate)?;
+    if rate != args.sample_rate {
+        eprintln!(
+            "{} can't capture at {} Hz, using {} Hz",
+            args.device, args.sample_rate, rate
+        );
+    }
+
+    // Four seconds of audio, before falling behind loses any.
+    let (mut producer, consumer) = AudioRing::new(rate as usize * 4).split();
+    // Dropping the producer when capture fails ends the feeder below.
+    let capture = thread::spawn(move || {
+        if let Err(e) = capture(&pcm, period, &mut producer) {
+            eprintln!("Capture stopped: {}", e);
+        }
+    });
+
+    let mut recognizer = Recognizer::new(&model, rate as f32);
+    recognizer.set_words(true);
+    let mut feeder = RingFeeder::new(consumer, recognizer, OverloadOptions::default());
+    while let Some(event) = feeder.next_event() {
+        match event {
+            Event::Final(utterance) if !utterance.text.is_empty() => println!("{}", utterance.text),
+            Event::Overloaded { .. } => eprintln!("Recognition fell behind, skipping audio"),
+            _ => {}
+        }
+    }
+    let last = feeder.recognizer_mut().final_result();
+    if !last.text.is_empty() {
+        println!("{}", last.text);
+    }
+    let _ = capture.join();
+    Ok(())
+}
+
+/// Sets up 16-bit mono capture, returning the rate and the period in frames.
+fn configure(pcm: &PCM, rate: u32) -> alsa::Result<(u32, usize)> {
+    let hwp = HwParams::any(pcm)?;
+    hwp.set_access(Access::RWInterleaved)?;
+    hwp.set_format(Format::S16LE)?;
+    hwp.set_channels(1)?;
+    // Plain hw devices only have the rates of the hardware.
+    if hwp.set_rate(rate, ValueOr::Nearest).is_err() {
+        hwp.set_rate_near(rate, ValueOr::Nearest)?;
+    }
+    let rate = hwp.get_rate()?;
+    let frames = (rate as u128 * PERIOD.as_millis() / 1000) as i64;
+    hwp.set_period_size_near(frames, ValueOr::Nearest)?;
+    hwp.set_buffer_size_near(frames * PERIODS)?;
+    pcm.hw_params(&hwp)?;
+    let period = hwp.get_period_size()? as usize;
+    Ok((rate, period))
+}
+
+/// Reads periods from the device into the ring until an error can't be recovered from.
+fn capture(pcm: &PCM, period: usize, producer: &mut AudioProducer) -> alsa::Result<()> {
+    let io = pcm.io_i16()?;
+    let mut buf = vec![0i16; period];
+    // Capture starts with the first read and again after recovering.
+    loop {
+        match io.readi(&mut buf) {
+            Ok(frames) => {
+                let pushed = producer.push_slice(&buf[..frames]);
+                if pushed < frames {
+                    eprintln!("Ring full, dropped {} samples", frames - pushed);
+                }
+            }
+            Err(e) => recover(pcm, e)?,
+        }
+    }
+}
+
+/// Gets the device running again after an overrun or a suspend.
+fn recover(pcm: &PCM, e: alsa::Error) -> alsa::Result<()> {
+    match e.errno() {
+        libc::EPIPE => {
+            eprintln!("Overrun, audio was lost");
+            pcm.prepare()?;
+        }
+        libc::ESTRPIPE => {
+            eprintln!("Device suspended, resuming");
+            loop {
+                match pcm.resume() {
+                    Err(e) if e.errno() == libc::EAGAIN => thread::sleep(PERIOD),
+                    // Not every device can resume, starting over works for those.
+                    Err(_) => {
+                        pcm.prepare()?;
+                        break;
+                    }
+                    Ok(()) => break,
+                }
+            }
+        }
+        libc::EINTR => {}
+        _ => return Err(e),
+    }
+    Ok(())
+}
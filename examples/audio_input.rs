@@ -2,6 +2,9 @@ use argh::FromArgs;
 use portaudio_rs::device::DeviceInfo;
 use portaudio_rs::stream::{Stream, StreamCallbackResult, StreamFlags, StreamParameters};
 use std::collections::BTreeMap;
+use std::time::Duration;
+use vosk::partial::PartialTracker;
+use vosk::ring::AudioRing;
 use vosk::{Model, Recognizer};
 
 #[derive(FromArgs)]
@@ -38,7 +41,11 @@ fn main() {
 
     let model = Model::new(up.model).unwrap();
     let mut recognizer = Recognizer::new(&model, up.sample_rate);
-    let mut last_partial = String::new();
+    let mut partials = PartialTracker::default();
+
+    // Recognition takes too long to run in the audio callback, which only copies
+    // the samples into the ring. Two seconds of room leaves time to catch up.
+    let (mut producer, mut consumer) = AudioRing::new(up.sample_rate as usize * 2).split();
 
     let input_par = StreamParameters {
         device: i,
@@ -53,35 +60,49 @@ fn main() {
         portaudio_rs::stream::FRAMES_PER_BUFFER_UNSPECIFIED,
         StreamFlags::empty(),
         Some(Box::new(move |input, _out: &mut [i16], _time, _flags| {
-            let completed = recognizer.accept_waveform(input);
-            if completed {
-                let result = recognizer.final_result();
-                if !result.text.is_empty() {
-                    println!("{}", result.text);
-                }
-            } else {
-                let result = recognizer.partial_result();
-                if result.partial != last_partial {
-                    last_partial.clear();
-                    last_partial.insert_str(0, &result.partial);
-                    if !result.partial.is_empty() {
-                        println!("{}", result.partial);
-                    }
-                }
-            }
+            producer.push_slice(input);
             StreamCallbackResult::Continue
         })),
     )
     .unwrap();
     stream.start().expect("failed to start the stream");
-    std::thread::park();
+
+    // A tenth of a second at a time.
+    let mut chunk = vec![0; up.sample_rate as usize / 10];
+    let mut dropped = 0;
+    loop {
+        let n = consumer.read_chunk(&mut chunk, Duration::from_millis(10));
+        if n == 0 {
+            break;
+        }
+        let stats = consumer.stats();
+        if stats.dropped > dropped {
+            eprintln!(
+                "Recognition fell behind, {} samples dropped",
+                stats.dropped - dropped
+            );
+            dropped = stats.dropped;
+        }
+        if recognizer.accept_waveform(&chunk[..n]) {
+            partials.reset();
+            let result = recognizer.final_result();
+            if !result.text.is_empty() {
+                println!("{}", result.text);
+            }
+        } else {
+            let result = recognizer.partial_result();
+            match partials.changed(&result.partial) {
+                Some(partial) if !partial.is_empty() => println!("{}", partial),
+                _ => {}
+            }
+        }
+    }
 }
 
 fn list_devices() -> Result<BTreeMap<u32, DeviceInfo>, portaudio_rs::PaError> {
     portaudio_rs::initialize()?;
     let n = portaudio_rs::device::get_count()?;
     let inputs = (0..n)
-        .into_iter()
         .filter_map(|index| {
             let info = portaudio_rs::device::get_info(index)?;
             if info.max_input_channels > 0 {
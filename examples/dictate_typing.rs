@@ -0,0 +1,314 @@
+//! System-wide dictation: what you say is typed into the window that has focus.
+//!
+//! Listening starts paused. Press the hotkey, Ctrl+Alt+D by default, to start
+//! listening and again to stop; the utterance in progress is typed when stopping.
+//! Only finalized utterances are typed, never partial results: a partial result
+//! can still change, and typed text can't be taken back.
+//!
+//! Punctuation is said in words, "comma", "period", "question mark", "new line"
+//! and so on, and typed as symbols attached to the word before, with a capital
+//! letter after the end of a sentence.
+//!
+//! The kill switch, Ctrl+Alt+K by default, quits at once without typing anything
+//! more, even in the middle of an utterance.
+//!
+//! Keys are named as in `device_query`, such as `LControl+LShift+F9`. On Linux this
+//! needs X11; Wayland doesn't let programs read the keyboard or type into other windows.
+//!
+//! Run with `cargo run --example dictate_typing --features dictation-example -- -m model`
+
+use argh::FromArgs;
+use device_query::{DeviceQuery, DeviceState, Keycode};
+use enigo::{Direction, Enigo, Key, Keyboard, Settings};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use vosk::source::{AudioSource, Microphone};
+use vosk::{Model, Recognizer};
+
+/// How often the keyboard is checked for the hotkeys.
+const POLL: Duration = Duration::from_millis(20);
+
+/// Spoken punctuation and what's typed for it, longer phrases first.
+const SPOKEN: [(&str, &str); 10] = [
+    ("new paragraph", "\n\n"),
+    ("new line", "\n"),
+    ("question mark", "?"),
+    ("exclamation mark", "!"),
+    ("exclamation point", "!"),
+    ("full stop", "."),
+    ("period", "."),
+    ("comma", ","),
+    ("semicolon", ";"),
+    ("colon", ":"),
+];
+
+#[derive(FromArgs)]
+/// Type what you say into the focused window
+struct Args {
+    /// path to the model
+    #[argh(option, short = 'm', default = "String::from(\"model\")")]
+    model: String,
+    /// keys to press together to start and stop listening
+    #[argh(option, default = "Hotkey::from_str(\"LControl+LAlt+D\").unwrap()")]
+    hotkey: Hotkey,
+    /// keys to press together to quit at once
+    #[argh(option, default = "Hotkey::from_str(\"LControl+LAlt+K\").unwrap()")]
+    kill_switch: Hotkey,
+    /// what goes between utterances: "trailing" types a space after each one,
+    /// "leading" before the next one unless it starts with punctuation, "none" nothing
+    #[argh(option, default = "Spacing::Trailing")]
+    spacing: Spacing,
+}
+
+/// Keys that are pressed together.
+#[derive(Debug, Clone, PartialEq)]
+struct Hotkey(Vec<Keycode>);
+
+impl FromStr for Hotkey {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let keys = s
+            .split('+')
+            .map(|key| Keycode::from_str(key.trim()).map_err(|_| format!("unknown key {}", key)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Hotkey(keys))
+    }
+}
+
+impl Hotkey {
+    fn is_pressed(&self, pressed: &[Keycode]) -> bool {
+        self.0.iter().all(|key| pressed.contains(key))
+    }
+}
+
+/// How utterances are kept apart.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Spacing {
+    /// A space after each utterance, so that typing by hand can follow right away.
+    Trailing,
+    /// A space before each utterance but the first, so that nothing is left dangling.
+    /// Punctuation said on its own attaches to the text before it.
+    Leading,
+    None,
+}
+
+impl FromStr for Spacing {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "trailing" => Ok(Spacing::Trailing),
+            "leading" => Ok(Spacing::Leading),
+            "none" => Ok(Spacing::None),
+            _ => Err(format!("unknown spacing {}", s)),
+        }
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Args = argh::from_env();
+    let model = Model::new(&args.model).map_err(|e| e.to_string())?;
+    let mut enigo = Enigo::new(&Settings::default())?;
+    let keyboard = DeviceState::new();
+
+    let listening = Arc::new(AtomicBool::new(false));
+    let killed = Arc::new(AtomicBool::new(false));
+    let (sender, finals) = mpsc::channel();
+    // The stream of the microphone has to stay on the thread that opened it.
+    let recognition = {
+        let listening = listening.clone();
+        let killed = killed.clone();
+        thread::spawn(move || recognize(&model, &listening, &killed, sender))
+    };
+    eprintln!(
+        "Press {:?} to start and stop listening, {:?} to quit.",
+        args.hotkey.0, args.kill_switch.0
+    );
+
+    let mut typist = Typist::new(args.spacing);
+    let mut hotkey_down = false;
+    loop {
+        let pressed = keyboard.get_keys();
+        if killed.load(Ordering::Relaxed) || args.kill_switch.is_pressed(&pressed) {
+            eprintln!("Killed.");
+            break;
+        }
+        // Toggles once per press, however long the keys are held.
+        let down = args.hotkey.is_pressed(&pressed);
+        if down && !hotkey_down {
+            let now = !listening.fetch_xor(true, Ordering::Relaxed);
+            eprintln!("{}", if now { "Listening..." } else { "Paused." });
+        }
+        hotkey_down = down;
+        match finals.try_recv() {
+            Ok(text) => {
+                let keys = typist.keys_for(&text);
+                type_keys(&mut enigo, &keyboard, &args.kill_switch, &keys, &killed)?;
+            }
+            Err(TryRecvError::Empty) => thread::sleep(POLL),
+            Err(TryRecvError::Disconnected) => break,
+        }
+    }
+    killed.store(true, Ordering::Relaxed);
+    // The thread notices within a read of the microphone.
+    let _ = recognition.join();
+    Ok(())
+}
+
+/// Recognizes speech from the microphone while `listening`, sending the text of
+/// each finalized utterance, until `killed`.
+fn recognize(model: &Model, listening: &AtomicBool, killed: &AtomicBool, finals: Sender<String>) {
+    let mut microphone = match Microphone::open_default() {
+        Ok(microphone) => microphone,
+        Err(e) => {
+            eprintln!("Can't open the microphone: {}", e);
+            return;
+        }
+    };
+    let mut recognizer = Recognizer::new(model, microphone.sample_rate() as f32);
+    let mut samples = vec![0; microphone.sample_rate() as usize / 50];
+    let mut heard = false;
+    while !killed.load(Ordering::Relaxed) {
+        // Read even while paused, so that old audio doesn't pile up.
+        let n = match microphone.read(&mut samples) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+        let text = if listening.load(Ordering::Relaxed) {
+            heard = true;
+            if !recognizer.accept_waveform(&samples[..n]) {
+                continue;
+            }
+            recognizer.result().text.into_owned()
+        } else if heard {
+            // Stopped listening: what was said until then is final.
+            heard = false;
+            recognizer.final_result().text.into_owned()
+        } else {
+            continue;
+        };
+        if !text.is_empty() && finals.send(text).is_err() {
+            break;
+        }
+    }
+}
+
+/// What to type: text, or Enter for a line break.
+#[derive(Debug, Clone, PartialEq)]
+enum Keys {
+    Text(String),
+    Enter,
+}
+
+/// Turns utterances into keys, keeping track of sentences and spacing across them.
+struct Typist {
+    spacing: Spacing,
+    /// Whether the next word starts a sentence.
+    capitalize: bool,
+    /// Whether anything was typed yet, and the last of it wasn't a line break.
+    mid_line: bool,
+}
+
+impl Typist {
+    fn new(spacing: Spacing) -> Typist {
+        Typist {
+            spacing,
+            capitalize: true,
+            mid_line: false,
+        }
+    }
+
+    fn keys_for(&mut self, utterance: &str) -> Vec<Keys> {
+        let words: Vec<&str> = utterance.split_whitespace().collect();
+        let mut text = String::new();
+        let mut keys = Vec::new();
+        let mut first = true;
+        let mut i = 0;
+        while i < words.len() {
+            let mark = SPOKEN.iter().find(|(spoken, _)| {
+                let n = spoken.split(' ').count();
+                words.get(i..i + n).is_some_and(|w| w.join(" ") == *spoken)
+            });
+            if let Some(&(spoken, symbol)) = mark {
+                i += spoken.split(' ').count();
+                for (j, line) in symbol.split('\n').enumerate() {
+                    if j > 0 {
+                        keys.extend(take_text(&mut text));
+                        keys.push(Keys::Enter);
+                        self.mid_line = false;
+                        self.capitalize = true;
+                    }
+                    if !line.is_empty() {
+                        text.push_str(line);
+                        self.mid_line = true;
+                        self.capitalize = matches!(line, "." | "?" | "!");
+                    }
+                }
+            } else {
+                let space = if first {
+                    self.spacing == Spacing::Leading
+                } else {
+                    true
+                };
+                if space && self.mid_line {
+                    text.push(' ');
+                }
+                let word = words[i];
+                if self.capitalize {
+                    let mut chars = word.chars();
+                    text.extend(chars.next().map(|c| c.to_uppercase()).into_iter().flatten());
+                    text.push_str(chars.as_str());
+                } else {
+                    text.push_str(word);
+                }
+                self.capitalize = false;
+                self.mid_line = true;
+                i += 1;
+            }
+            first = false;
+        }
+        if self.spacing == Spacing::Trailing && self.mid_line {
+            text.push(' ');
+        }
+        keys.extend(take_text(&mut text));
+        keys
+    }
+}
+
+fn take_text(text: &mut String) -> Option<Keys> {
+    if text.is_empty() {
+        None
+    } else {
+        Some(Keys::Text(std::mem::take(text)))
+    }
+}
+
+/// Types `keys` a word at a time, stopping as soon as the kill switch is pressed.
+fn type_keys(
+    enigo: &mut Enigo,
+    keyboard: &DeviceState,
+    kill_switch: &Hotkey,
+    keys: &[Keys],
+    killed: &AtomicBool,
+) -> Result<(), enigo::InputError> {
+    for key in keys {
+        let chunks: Vec<&str> = match key {
+            Keys::Text(text) => text.split_inclusive(' ').collect(),
+            Keys::Enter => vec![],
+        };
+        if chunks.is_empty() {
+            enigo.key(Key::Return, Direction::Click)?;
+        }
+        for chunk in chunks {
+            if killed.load(Ordering::Relaxed) || kill_switch.is_pressed(&keyboard.get_keys()) {
+                killed.store(true, Ordering::Relaxed);
+                return Ok(());
+            }
+            enigo.text(chunk)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,247 @@
+//! A Discord bot that joins your voice channel when you type `!join`
+//! and posts what everyone says to the text channel.
+//!
+//! Run with `DISCORD_TOKEN=... cargo run --example discord_transcribe --features discord-example -- model`
+
+use serenity::async_trait;
+use serenity::client::{Client, Context, EventHandler};
+use serenity::http::Http;
+use serenity::model::channel::Message;
+use serenity::model::id::{ChannelId, UserId};
+use serenity::prelude::GatewayIntents;
+use songbird::driver::DecodeMode;
+use songbird::model::payload::Speaking;
+use songbird::{Config, CoreEvent, Event, EventContext, SerenityInit};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use vosk::{Model, Recognizer};
+
+/// Discord sends 48 kHz audio; Kaldi resamples it to the rate of the model.
+const DISCORD_RATE: f32 = 48000.0;
+/// A user's utterance is finalized after this long without packets.
+const SILENCE: Duration = Duration::from_secs(2);
+
+/// The recognizer of one user.
+struct Speaker {
+    recognizer: Recognizer,
+    last_packet: Instant,
+}
+
+/// One recognizer per speaking user.
+///
+/// A recognizer is created on the first packet of a user,
+/// and the utterance is finalized and the recognizer dropped
+/// once the user has been silent for `SILENCE`.
+struct Speakers {
+    model: Model,
+    /// Discord identifies audio streams by SSRC.
+    active: HashMap<u32, Speaker>,
+    users: HashMap<u32, UserId>,
+    mono: Vec<i16>,
+}
+
+impl Speakers {
+    fn new(model: Model) -> Speakers {
+        Speakers {
+            model,
+            active: HashMap::new(),
+            users: HashMap::new(),
+            mono: Vec::new(),
+        }
+    }
+
+    fn identify(&mut self, ssrc: u32, user: UserId) {
+        self.users.insert(ssrc, user);
+    }
+
+    /// Feeds interleaved stereo audio of a user, returning text when an utterance ends.
+    fn push(&mut self, ssrc: u32, stereo: &[i16], now: Instant) -> Option<String> {
+        self.mono.clear();
+        self.mono.extend(
+            stereo
+                .chunks_exact(2)
+                .map(|lr| ((lr[0] as i32 + lr[1] as i32) / 2) as i16),
+        );
+        let model = &self.model;
+        let speaker = self.active.entry(ssrc).or_insert_with(|| Speaker {
+            recognizer: Recognizer::new(model, DISCORD_RATE),
+            last_packet: now,
+        });
+        speaker.last_packet = now;
+        if speaker.recognizer.accept_waveform(&self.mono) {
+            non_empty(speaker.recognizer.result().text.into_owned())
+        } else {
+            None
+        }
+    }
+
+    /// Finalizes the utterances of users who stopped talking.
+    fn finish_idle(&mut self, now: Instant) -> Vec<(u32, String)> {
+        let idle: Vec<u32> = self
+            .active
+            .iter()
+            .filter(|(_, s)| now.duration_since(s.last_packet) >= SILENCE)
+            .map(|(&ssrc, _)| ssrc)
+            .collect();
+        idle.into_iter()
+            .filter_map(|ssrc| {
+                let mut speaker = self.active.remove(&ssrc)?;
+                let text = speaker.recognizer.final_result().text.into_owned();
+                non_empty(text).map(|text| (ssrc, text))
+            })
+            .collect()
+    }
+
+    fn user(&self, ssrc: u32) -> Option<UserId> {
+        self.users.get(&ssrc).copied()
+    }
+}
+
+fn non_empty(text: String) -> Option<String> {
+    if text.is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+/// Receives voice events for one call and posts utterances to a text channel.
+#[derive(Clone)]
+struct Receiver {
+    speakers: Arc<Mutex<Speakers>>,
+    http: Arc<Http>,
+    channel: ChannelId,
+}
+
+impl Receiver {
+    async fn post(&self, ssrc: u32, text: String) {
+        let user = self.speakers.lock().unwrap().user(ssrc);
+        let name = match user {
+            Some(user) => match user.to_user(&self.http).await {
+                Ok(user) => user.name,
+                Err(_) => format!("user {}", user.0),
+            },
+            None => format!("speaker {}", ssrc),
+        };
+        let _ = self
+            .channel
+            .say(&self.http, format!("{}: {}", name, text))
+            .await;
+    }
+}
+
+#[async_trait]
+impl songbird::EventHandler for Receiver {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        match ctx {
+            EventContext::SpeakingStateUpdate(Speaking {
+                ssrc,
+                user_id: Some(user),
+                ..
+            }) => {
+                let user = UserId(user.0);
+                self.speakers.lock().unwrap().identify(*ssrc, user);
+            }
+            EventContext::VoicePacket(data) => {
+                if let Some(audio) = data.audio {
+                    let ssrc = data.packet.ssrc;
+                    let text = self
+                        .speakers
+                        .lock()
+                        .unwrap()
+                        .push(ssrc, audio, Instant::now());
+                    if let Some(text) = text {
+                        self.post(ssrc, text).await;
+                    }
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+struct Handler {
+    model: Model,
+}
+
+#[async_trait]
+impl EventHandler for Handler {
+    async fn message(&self, ctx: Context, msg: Message) {
+        if msg.content != "!join" {
+            return;
+        }
+        let guild = match msg.guild(&ctx.cache) {
+            Some(guild) => guild,
+            None => return,
+        };
+        let voice_channel = guild
+            .voice_states
+            .get(&msg.author.id)
+            .and_then(|state| state.channel_id);
+        let voice_channel = match voice_channel {
+            Some(channel) => channel,
+            None => {
+                let _ = msg.reply(&ctx, "Join a voice channel first.").await;
+                return;
+            }
+        };
+        let manager = songbird::get(&ctx).await.expect("songbird not registered");
+        let (call, joined) = manager.join(guild.id, voice_channel).await;
+        if let Err(e) = joined {
+            let _ = msg.reply(&ctx, format!("Could not join: {}", e)).await;
+            return;
+        }
+        let receiver = Receiver {
+            speakers: Arc::new(Mutex::new(Speakers::new(self.model.clone()))),
+            http: ctx.http.clone(),
+            channel: msg.channel_id,
+        };
+        {
+            let mut call = call.lock().await;
+            call.add_global_event(CoreEvent::SpeakingStateUpdate.into(), receiver.clone());
+            call.add_global_event(CoreEvent::VoicePacket.into(), receiver.clone());
+        }
+        tokio::spawn(finish_idle_speakers(receiver));
+        let _ = msg.reply(&ctx, "Listening.").await;
+    }
+}
+
+/// Periodically finalizes the utterances of users who stopped talking.
+async fn finish_idle_speakers(receiver: Receiver) {
+    let mut interval = tokio::time::interval(Duration::from_millis(500));
+    loop {
+        interval.tick().await;
+        let finished = receiver
+            .speakers
+            .lock()
+            .unwrap()
+            .finish_idle(Instant::now());
+        for (ssrc, text) in finished {
+            receiver.post(ssrc, text).await;
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let model_path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "model".to_string());
+    let model = Model::new(&model_path).expect("could not load model");
+    let token = std::env::var("DISCORD_TOKEN").expect("DISCORD_TOKEN not set");
+    let intents = GatewayIntents::GUILDS
+        | GatewayIntents::GUILD_MESSAGES
+        | GatewayIntents::GUILD_VOICE_STATES
+        | GatewayIntents::MESSAGE_CONTENT;
+    let songbird_config = Config::default().decode_mode(DecodeMode::Decode);
+    let mut client = Client::builder(&token, intents)
+        .event_handler(Handler { model })
+        .register_songbird_from_config(songbird_config)
+        .await
+        .expect("could not create client");
+    if let Err(e) = client.start().await {
+        eprintln!("Client error: {}", e);
+    }
+}
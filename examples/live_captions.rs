@@ -0,0 +1,264 @@
+//! Live captions from the default microphone in the terminal.
+//!
+//! The partial result is redrawn in place on the bottom line, finalized utterances
+//! scroll up above it with the time they were said. The status line shows the input
+//! level and how fast recognition runs compared to real time.
+//! Press Ctrl-C, Esc or q to quit; the utterance in progress is finalized first.
+//!
+//! Run with `cargo run --example live_captions --features cpal -- -m model`
+
+use argh::FromArgs;
+use crossterm::event::{self, Event as TermEvent, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, Clear, ClearType};
+use crossterm::{cursor, execute, queue};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use vosk::activity::ActivityOptions;
+use vosk::overload::{OverloadOptions, OverloadPolicy, RingFeeder};
+use vosk::ring::{AudioProducer, AudioRing};
+use vosk::source::{AudioSource, Microphone};
+use vosk::{Event, Model, Recognizer, UtteranceOwned};
+
+/// How often the screen is redrawn while nothing happens, for the level meter.
+const REFRESH: Duration = Duration::from_millis(50);
+
+/// Width of the level meter in cells.
+const METER_WIDTH: usize = 20;
+
+#[derive(FromArgs)]
+/// Show live captions of the default microphone
+struct Args {
+    /// path to the model
+    #[argh(option, short = 'm', default = "String::from(\"model\")")]
+    model: String,
+}
+
+/// What the recognition thread tells the screen.
+enum Update {
+    Event(Event),
+    RealTimeFactor(f64),
+    /// The last utterance was finalized and the thread is done.
+    Finished(UtteranceOwned),
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Args = argh::from_env();
+    let model = Model::new(&args.model).map_err(|e| e.to_string())?;
+
+    let running = Arc::new(AtomicBool::new(true));
+    let level = Arc::new(AtomicU16::new(0));
+    let (rate_sender, rate) = mpsc::channel();
+    // Four seconds at the highest usual rate, before falling behind loses audio.
+    let (mut producer, consumer) = AudioRing::new(48000 * 4).split();
+    // The stream of the microphone has to stay on the thread that opened it.
+    let capture = {
+        let running = running.clone();
+        let level = level.clone();
+        thread::spawn(move || capture(&mut producer, &running, &level, rate_sender))
+    };
+    let sample_rate = rate.recv()?.map_err(|e| e.to_string())?;
+
+    let recognizer = Recognizer::new(&model, sample_rate as f32);
+    let opts = OverloadOptions {
+        policy: OverloadPolicy::SkipPartials,
+        ..OverloadOptions::default()
+    };
+    let feeder = RingFeeder::new(consumer, recognizer, opts)
+        .with_speech_activity(ActivityOptions::default());
+    let (sender, updates) = mpsc::channel();
+    let recognition = thread::spawn(move || recognize(feeder, sender));
+
+    terminal::enable_raw_mode()?;
+    execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+    let shown = show(updates, &running, &level);
+    execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+
+    let _ = capture.join();
+    let _ = recognition.join();
+    // What was said, now that the screen is gone.
+    for line in shown? {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// Copies samples from the microphone to the ring until `running` is false,
+/// keeping the peak level of the latest chunk in `level`.
+fn capture(
+    producer: &mut AudioProducer,
+    running: &AtomicBool,
+    level: &AtomicU16,
+    rate: Sender<Result<u32, vosk::Error>>,
+) {
+    let mut microphone = match Microphone::open_default() {
+        Ok(microphone) => microphone,
+        Err(e) => {
+            let _ = rate.send(Err(e));
+            return;
+        }
+    };
+    let _ = rate.send(Ok(microphone.sample_rate()));
+    let mut samples = vec![0; microphone.sample_rate() as usize / 50];
+    while running.load(Ordering::Relaxed) {
+        let n = match microphone.read(&mut samples) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+        let peak = samples[..n]
+            .iter()
+            .map(|s| s.unsigned_abs())
+            .max()
+            .unwrap_or(0);
+        level.store(peak, Ordering::Relaxed);
+        producer.push_slice(&samples[..n]);
+    }
+    // Dropping the producer ends the events of the feeder.
+}
+
+fn recognize(mut feeder: RingFeeder, updates: Sender<Update>) {
+    while let Some(event) = feeder.next_event() {
+        if let Some(rtf) = feeder.real_time_factor() {
+            let _ = updates.send(Update::RealTimeFactor(rtf));
+        }
+        if updates.send(Update::Event(event)).is_err() {
+            return;
+        }
+    }
+    let last = feeder.recognizer_mut().final_result().into_owned();
+    let _ = updates.send(Update::Finished(last));
+}
+
+/// The captions on screen.
+#[derive(Default)]
+struct Screen {
+    lines: Vec<String>,
+    partial: String,
+    rtf: Option<f64>,
+    overloaded: bool,
+    speaking: bool,
+}
+
+impl Screen {
+    fn finalize(&mut self, utterance: &UtteranceOwned) {
+        self.partial.clear();
+        self.overloaded = false;
+        if utterance.text.is_empty() {
+            return;
+        }
+        let start = utterance
+            .result
+            .as_ref()
+            .and_then(|words| words.first())
+            .map_or(0.0, |word| word.start());
+        let secs = start as u64;
+        self.lines.push(format!(
+            "[{:02}:{:02}] {}",
+            secs / 60,
+            secs % 60,
+            utterance.text
+        ));
+    }
+    fn update(&mut self, update: &Update) {
+        match update {
+            Update::Event(Event::Partial(text)) => self.partial = text.clone(),
+            Update::Event(Event::Final(utterance)) | Update::Finished(utterance) => {
+                self.finalize(utterance)
+            }
+            Update::Event(Event::Overloaded { .. }) => self.overloaded = true,
+            Update::Event(Event::SpeechStarted) => self.speaking = true,
+            Update::Event(Event::SpeechEnded { .. }) => self.speaking = false,
+            Update::Event(_) => {}
+            Update::RealTimeFactor(rtf) => self.rtf = Some(*rtf),
+        }
+    }
+    /// Redraws everything: the scrollback, the status line and the partial result.
+    fn draw(&self, out: &mut impl Write, level: u16) -> io::Result<()> {
+        let (width, height) = terminal::size()?;
+        let (width, height) = (width as usize, height as usize);
+        queue!(out, Clear(ClearType::All))?;
+        let room = height.saturating_sub(2);
+        let first = self.lines.len().saturating_sub(room);
+        for (row, line) in self.lines[first..].iter().enumerate() {
+            queue!(out, cursor::MoveTo(0, row as u16), Print(fit(line, width)))?;
+        }
+        let filled = level as usize * METER_WIDTH / i16::MAX as usize;
+        let mut status = format!(
+            "[{}{}]",
+            "#".repeat(filled.min(METER_WIDTH)),
+            " ".repeat(METER_WIDTH - filled.min(METER_WIDTH))
+        );
+        if self.speaking {
+            status.push_str(" speaking");
+        }
+        if let Some(rtf) = self.rtf {
+            status.push_str(&format!(" RTF {:.2}", rtf));
+        }
+        if self.overloaded {
+            status.push_str(" falling behind, partial results paused");
+        }
+        let status_row = height.saturating_sub(2) as u16;
+        queue!(
+            out,
+            cursor::MoveTo(0, status_row),
+            SetAttribute(Attribute::Reverse),
+            Print(fit(&status, width)),
+            SetAttribute(Attribute::Reset),
+            cursor::MoveTo(0, status_row + 1),
+            SetAttribute(Attribute::Dim),
+            Print(fit(&self.partial, width)),
+            SetAttribute(Attribute::Reset),
+        )?;
+        out.flush()
+    }
+}
+
+/// The end of `text` that fits in `width` columns, so the latest words of a long partial show.
+fn fit(text: &str, width: usize) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    chars[chars.len().saturating_sub(width)..].iter().collect()
+}
+
+/// Shows updates until the recognition thread is done, asking it to stop on Ctrl-C.
+/// Returns the finalized lines.
+fn show(
+    updates: Receiver<Update>,
+    running: &AtomicBool,
+    level: &AtomicU16,
+) -> io::Result<Vec<String>> {
+    let mut screen = Screen::default();
+    let mut out = io::stdout();
+    loop {
+        // Resizing needs nothing more than the redraw.
+        while event::poll(Duration::ZERO)? {
+            if let TermEvent::Key(key) = event::read()? {
+                if is_quit(key) {
+                    running.store(false, Ordering::Relaxed);
+                }
+            }
+        }
+        match updates.recv_timeout(REFRESH) {
+            Ok(Update::Finished(last)) => {
+                screen.finalize(&last);
+                return Ok(screen.lines);
+            }
+            Ok(update) => screen.update(&update),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(screen.lines),
+        }
+        screen.draw(&mut out, level.load(Ordering::Relaxed))?;
+    }
+}
+
+fn is_quit(key: KeyEvent) -> bool {
+    match key.code {
+        KeyCode::Char('c') => key.modifiers.contains(KeyModifiers::CONTROL),
+        KeyCode::Char('q') | KeyCode::Esc => true,
+        _ => false,
+    }
+}
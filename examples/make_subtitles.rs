@@ -0,0 +1,189 @@
+use argh::FromArgs;
+use riff_wave::WaveReader;
+use std::fs::File;
+use std::io::{BufReader, Write};
+use std::path::Path;
+use std::process::exit;
+use vosk::export::{to_srt, to_vtt, SubtitleOptions};
+use vosk::segment::SentenceOptions;
+use vosk::{Model, RecognizedTextOwned, Recognizer};
+
+/// Exit code when the input can't be opened or decoded.
+const EXIT_DECODE: i32 = 2;
+/// Exit code when the model can't be loaded.
+const EXIT_MODEL: i32 = 3;
+/// Exit code when the subtitles can't be written.
+const EXIT_OUTPUT: i32 = 4;
+
+#[derive(FromArgs)]
+/// Generate subtitles from a WAV file (mono, 16-bit PCM),
+/// or any audio file when built with the audio-decode feature
+struct MakeSubtitles {
+    /// path to the audio file
+    #[argh(positional)]
+    input: String,
+    /// path to the model
+    #[argh(option, short = 'm', default = "String::from(\"model\")")]
+    model: String,
+    /// where to write the subtitles, defaults to the input path with a new extension
+    #[argh(option, short = 'o')]
+    output: Option<String>,
+    /// write SubRip subtitles (the default)
+    #[argh(switch)]
+    srt: bool,
+    /// write WebVTT subtitles
+    #[argh(switch)]
+    vtt: bool,
+    /// maximum number of characters per line
+    #[argh(option, default = "42")]
+    max_line_len: usize,
+    /// maximum duration of one subtitle in seconds
+    #[argh(option, default = "7.0")]
+    max_cue_secs: f32,
+    /// start a new subtitle at every pause between sentences
+    #[argh(switch)]
+    sentences: bool,
+}
+
+fn main() {
+    let args: MakeSubtitles = argh::from_env();
+    if args.srt && args.vtt {
+        eprintln!("Choose one of --srt and --vtt.");
+        exit(1);
+    }
+    #[cfg(feature = "audio-decode")]
+    {
+        let is_wav = Path::new(&args.input)
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"));
+        if !is_wav {
+            let model = load_model(&args.model);
+            eprintln!("Decoding {}", args.input);
+            let progress =
+                vosk::ProgressReporter::new(|progress: vosk::Progress| match progress.fraction() {
+                    Some(fraction) => eprint!("\r{}%", (fraction * 100.0) as u32),
+                    None => eprint!("\r{:.0} s", progress.processed.as_secs_f32()),
+                });
+            let cancel = vosk::CancellationToken::new();
+            let transcribed =
+                vosk::decode::transcribe_file_with_progress(&model, &args.input, &cancel, progress);
+            eprintln!();
+            match transcribed {
+                Ok(utterances) => write_subtitles(&args, &utterances),
+                Err(e) => {
+                    eprintln!("Could not decode {}: {}", args.input, e);
+                    exit(EXIT_DECODE);
+                }
+            }
+            return;
+        }
+    }
+    let file = match File::open(&args.input) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("Could not open {}: {}", args.input, e);
+            exit(EXIT_DECODE);
+        }
+    };
+    // riff-wave doesn't expose the size of the data chunk,
+    // so progress is estimated from the file size.
+    let file_len = file.metadata().map(|m| m.len()).unwrap_or(0);
+    let mut wave_reader = match WaveReader::new(BufReader::new(file)) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("Could not decode {}: {:?}", args.input, e);
+            exit(EXIT_DECODE);
+        }
+    };
+    let fmt = &wave_reader.pcm_format;
+    if fmt.num_channels != 1 || fmt.bits_per_sample != 16 {
+        eprintln!("Audio file must be WAV format mono PCM.");
+        exit(EXIT_DECODE);
+    }
+    let sample_rate = fmt.sample_rate;
+    let total_samples = file_len.saturating_sub(44) / 2;
+
+    let model = load_model(&args.model);
+    let mut recognizer = Recognizer::new(&model, sample_rate as f32);
+    recognizer.set_words(true);
+
+    let mut utterances: Vec<RecognizedTextOwned> = Vec::new();
+    let mut buf = [0; 4096];
+    let mut processed = 0u64;
+    let mut last_percent = None;
+    loop {
+        let n = match read_samples(&mut wave_reader, &mut buf) {
+            Ok(n) => n,
+            Err(e) => {
+                eprintln!("Could not decode {}: {}", args.input, e);
+                exit(EXIT_DECODE);
+            }
+        };
+        if n == 0 {
+            utterances.push(recognizer.final_result().into_owned());
+            break;
+        }
+        if recognizer.accept_waveform(&buf[..n]) {
+            utterances.push(recognizer.result().into_owned());
+        }
+        processed += n as u64;
+        if let Some(percent) = (processed * 100).checked_div(total_samples) {
+            let percent = percent.min(100);
+            if last_percent != Some(percent) {
+                last_percent = Some(percent);
+                eprint!("\r{}%", percent);
+            }
+        }
+    }
+    eprintln!();
+    write_subtitles(&args, &utterances);
+}
+
+fn load_model(path: &str) -> Model {
+    match Model::new(path) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("Could not load model from {}: {}", path, e);
+            exit(EXIT_MODEL);
+        }
+    }
+}
+
+fn write_subtitles(args: &MakeSubtitles, utterances: &[RecognizedTextOwned]) {
+    let opts = SubtitleOptions {
+        max_line_len: args.max_line_len,
+        max_cue_secs: args.max_cue_secs,
+        sentences: args.sentences.then(SentenceOptions::default),
+        ..SubtitleOptions::default()
+    };
+    let (subtitles, extension) = if args.vtt {
+        (to_vtt(utterances, &opts), "vtt")
+    } else {
+        (to_srt(utterances, &opts), "srt")
+    };
+    let output = args.output.clone().unwrap_or_else(|| {
+        Path::new(&args.input)
+            .with_extension(extension)
+            .to_string_lossy()
+            .into_owned()
+    });
+    let written = File::create(&output).and_then(|mut f| f.write_all(subtitles.as_bytes()));
+    if let Err(e) = written {
+        eprintln!("Could not write {}: {}", output, e);
+        exit(EXIT_OUTPUT);
+    }
+    eprintln!("Wrote {}", output);
+}
+
+/// Fills `buf` with samples, returning how many were read.
+/// Running out of samples is not an error, anything else is.
+fn read_samples(r: &mut WaveReader<BufReader<File>>, buf: &mut [i16]) -> std::io::Result<usize> {
+    for (i, sample) in buf.iter_mut().enumerate() {
+        match r.read_sample_i16() {
+            Ok(s) => *sample = s,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(i),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(buf.len())
+}
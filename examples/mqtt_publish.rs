@@ -0,0 +1,125 @@
+//! Listens to the default microphone for voice commands and publishes them to MQTT,
+//! for home automation software such as Home Assistant or Node-RED.
+//!
+//! Commands are listed one per line in a file, `#` starting a comment.
+//! Recognition is restricted to them, so it's fast and accurate even with a small model.
+//! Each recognized command is published to the topic as
+//! `{"text": "lights on", "confidence": 0.93, "ts": 1700000000.5}`.
+//!
+//! Run with `cargo run --example mqtt_publish --features mqtt-example -- -c commands.txt`
+
+use argh::FromArgs;
+use rumqttc::{Client, MqttOptions, QoS};
+use std::error::Error;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use vosk::source::{AudioSource, Microphone};
+use vosk::{Model, Outcome, Recognizer};
+
+/// How long to wait before reconnecting after the connection to the broker failed.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(FromArgs)]
+/// Publish voice commands to MQTT
+struct Args {
+    /// path to the model
+    #[argh(option, short = 'm', default = "String::from(\"model\")")]
+    model: String,
+    /// file listing the commands, one per line
+    #[argh(option, short = 'c')]
+    commands: String,
+    /// host name of the MQTT broker
+    #[argh(option, default = "String::from(\"localhost\")")]
+    host: String,
+    /// port of the MQTT broker
+    #[argh(option, default = "1883")]
+    port: u16,
+    /// topic to publish the commands to
+    #[argh(option, short = 't', default = "String::from(\"voice/command\")")]
+    topic: String,
+    /// client id to connect with
+    #[argh(option, default = "String::from(\"vosk-commands\")")]
+    client_id: String,
+    /// mean word confidence below which commands are ignored
+    #[argh(option, default = "0.7")]
+    min_confidence: f32,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Args = argh::from_env();
+    let commands = read_commands(&args.commands)?;
+    if commands.is_empty() {
+        return Err(format!("no commands in {}", args.commands).into());
+    }
+
+    let model = Model::new(&args.model).map_err(describe)?;
+    let mut microphone = Microphone::open_default().map_err(describe)?;
+    let rate = microphone.sample_rate();
+    // With "[unk]" in the grammar, other speech is recognized as unknown
+    // rather than as the closest command.
+    let phrases = commands
+        .iter()
+        .map(|c| c.split_whitespace())
+        .chain(std::iter::once("[unk]".split_whitespace()));
+    let mut recognizer = Recognizer::with_grammar(&model, rate as f32, phrases);
+    recognizer.set_words(true);
+    recognizer.set_min_confidence(Some(args.min_confidence));
+
+    let mut options = MqttOptions::new(args.client_id, args.host, args.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    let (client, mut connection) = Client::new(options, 16);
+    // The connection has to be polled for messages to go out. After an error,
+    // polling again reconnects; messages published meanwhile wait in the queue.
+    thread::spawn(move || {
+        for notification in connection.iter() {
+            if let Err(e) = notification {
+                eprintln!("MQTT connection failed: {}", e);
+                thread::sleep(RECONNECT_DELAY);
+            }
+        }
+    });
+
+    println!("Listening for {} commands at {} Hz", commands.len(), rate);
+    let mut samples = vec![0; rate as usize / 10];
+    loop {
+        let n = microphone.read(&mut samples).map_err(describe)?;
+        if n == 0 {
+            return Ok(());
+        }
+        if !recognizer.accept_waveform(&samples[..n]) {
+            continue;
+        }
+        match recognizer.checked_result() {
+            Outcome::Accepted(result) => {
+                if !commands.iter().any(|c| *c == result.text) {
+                    continue;
+                }
+                let message = serde_json::json!({
+                    "text": result.text,
+                    "confidence": result.mean_confidence(),
+                    "ts": SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs_f64(),
+                });
+                println!("{}", message);
+                client.publish(&args.topic, QoS::AtLeastOnce, false, message.to_string())?;
+            }
+            Outcome::Rejected { text, confidence } => {
+                println!("Ignored \"{}\", confidence {:.2}", text, confidence)
+            }
+        }
+    }
+}
+
+fn describe(e: vosk::Error) -> Box<dyn Error> {
+    e.to_string().into()
+}
+
+/// Reads the commands, with words separated by single spaces as in results.
+fn read_commands(path: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let content = std::fs::read_to_string(path)?;
+    Ok(content
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|command| !command.is_empty())
+        .collect())
+}
@@ -0,0 +1,65 @@
+use argh::FromArgs;
+use std::io::Write;
+use vosk::partial::PartialTracker;
+use vosk::{Model, RecognizedText, Recognizer};
+
+#[derive(FromArgs)]
+/// Recognize raw little-endian 16-bit mono PCM from stdin, e.g.
+/// `ffmpeg -i input.mkv -f s16le -ac 1 -ar 16000 - | stdin_pcm`
+struct StdinPcm {
+    /// path to the model
+    #[argh(option, short = 'm', default = "String::from(\"model\")")]
+    model: String,
+    /// number of samples per second
+    #[argh(option, short = 's', default = "16000.0")]
+    sample_rate: f32,
+}
+
+fn main() {
+    let args: StdinPcm = argh::from_env();
+    let model = match Model::new(&args.model) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("Could not load model from {}: {}", args.model, e);
+            std::process::exit(1);
+        }
+    };
+    let mut recognizer = Recognizer::new(&model, args.sample_rate);
+    recognizer.set_words(true);
+    let mut partials = PartialTracker::default();
+    let stdin = std::io::stdin();
+    let read = recognizer.accept_reader(stdin.lock(), |recognizer, completed| {
+        if completed {
+            print_utterance(&recognizer.result());
+            partials.reset();
+        } else {
+            let result = recognizer.partial_result();
+            match partials.changed(&result.partial) {
+                Some(partial) if !partial.is_empty() => eprintln!("{}", partial),
+                _ => {}
+            }
+        }
+    });
+    if let Err(e) = read {
+        eprintln!("Could not read stdin: {}", e);
+    }
+    print_utterance(&recognizer.final_result());
+}
+
+/// Prints the utterance with the time range of its words.
+fn print_utterance(result: &RecognizedText) {
+    if result.text.is_empty() {
+        return;
+    }
+    let words = result.result.as_deref().unwrap_or_default();
+    match (words.first(), words.last()) {
+        (Some(first), Some(last)) => println!(
+            "[{:8.2} - {:8.2}] {}",
+            first.start(),
+            last.end(),
+            result.text
+        ),
+        _ => println!("{}", result.text),
+    }
+    let _ = std::io::stdout().flush();
+}
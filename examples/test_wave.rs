@@ -3,12 +3,12 @@ extern crate vosk;
 use riff_wave::WaveReader;
 use std::fs::File;
 use std::io::BufReader;
+use vosk::partial::PartialTracker;
 use vosk::{Model, Recognizer};
 
 fn main() {
     let file = std::env::args()
-        .skip(1)
-        .next()
+        .nth(1)
         .unwrap_or_else(|| "hello.wav".to_string());
     let file = match File::open(&file) {
         Ok(f) => f,
@@ -32,7 +32,7 @@ fn main() {
         fmt.sample_rate as f32,
         "o zero one two three four five six seven eight nine ten",
     );
-    let mut last_part = String::new();
+    let mut partials = PartialTracker::default();
     loop {
         let n = read_sample(&mut wave_reader, &mut buf);
         if n == 0 {
@@ -42,14 +42,13 @@ fn main() {
         } else {
             let completed = recognizer.accept_waveform(&buf[..n]);
             if completed {
+                partials.reset();
                 let result = recognizer.final_result();
                 println!("Result: {:?}", result);
             } else {
                 let result = recognizer.partial_result();
-                if result.partial != last_part {
-                    last_part.clear();
-                    last_part.insert_str(0, &result.partial);
-                    println!("Partial: {:?}", result.partial);
+                if let Some(partial) = partials.changed(&result.partial) {
+                    println!("Partial: {:?}", partial);
                 }
             }
         }
@@ -0,0 +1,392 @@
+//! An HTTP server that transcribes WAV files, as a starting point for internal tools.
+//!
+//! `POST /transcribe` takes a 16-bit mono WAV file, either as the body with
+//! `Content-Type: audio/wav` or as the `file` field of a multipart form, and returns
+//! the utterances with word timings. The JSON resembles the one of the Whisper API
+//! by default, `?format=native` returns the results of libvosk as they are.
+//! `GET /healthz` answers once a recognizer can be created from the model.
+//! `GET /metrics` returns metrics in the Prometheus text format: those of the
+//! `vosk::telemetry` module, and the requests being handled and handled so far,
+//! by status code.
+//!
+//! Recognition runs on blocking threads, with recognizers kept in a pool shared by
+//! all requests since creating one takes a while. The `--workers` option bounds how
+//! many files are recognized at once; further requests wait for a free worker.
+//!
+//! Run with `cargo run --example transcribe_server --features server-example -- -m model`
+//! and try `curl --data-binary @hello.wav -H 'Content-Type: audio/wav' localhost:8000/transcribe`
+
+use argh::FromArgs;
+use axum::body::Bytes;
+use axum::extract::multipart::MultipartError;
+use axum::extract::{DefaultBodyLimit, FromRequest, Multipart, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use hound::{SampleFormat, WavReader};
+use metrics::{counter, describe_counter, describe_gauge, gauge};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Semaphore;
+use vosk::{Model, RecognizedTextOwned, Recognizer};
+
+/// Samples given to the recognizer at a time.
+const CHUNK: usize = 4000;
+
+/// The rate the health check creates a recognizer at.
+const HEALTH_CHECK_RATE: f32 = 16000.0;
+
+/// Gauge of the transcription requests being handled.
+const REQUESTS_ACTIVE: &str = "vosk_server_requests_active";
+/// Counter of the transcription requests handled, labelled with the status code.
+const REQUESTS: &str = "vosk_server_requests_total";
+
+/// Buckets of the real-time factor histogram, around 1 where recognition
+/// stops keeping up.
+const RTF_BUCKETS: [f64; 9] = [0.05, 0.1, 0.2, 0.3, 0.5, 0.75, 1.0, 1.5, 2.0];
+
+#[derive(FromArgs)]
+/// Serve transcriptions of WAV files over HTTP
+struct Args {
+    /// path to the model
+    #[argh(option, short = 'm', default = "String::from(\"model\")")]
+    model: String,
+    /// address to listen on
+    #[argh(option, short = 'l', default = "String::from(\"127.0.0.1:8000\")")]
+    listen: String,
+    /// files recognized at the same time
+    #[argh(option, short = 'w', default = "2")]
+    workers: usize,
+    /// largest accepted request body in MiB
+    #[argh(option, default = "50")]
+    max_body_mb: usize,
+    /// longest accepted audio in seconds
+    #[argh(option, default = "600")]
+    max_seconds: u32,
+}
+
+/// Idle recognizers, reused across requests.
+///
+/// A recognizer only takes audio at the rate it was created with,
+/// so one for the rate of the file is looked for or created.
+struct Pool {
+    model: Model,
+    idle: Mutex<Vec<Recognizer>>,
+    workers: Arc<Semaphore>,
+    max_idle: usize,
+}
+
+impl Pool {
+    fn take(&self, sample_rate: f32) -> Result<Recognizer, vosk::Error> {
+        let mut idle = self.idle.lock().unwrap();
+        match idle.iter().position(|r| r.sample_rate() == sample_rate) {
+            Some(i) => Ok(idle.swap_remove(i)),
+            None => {
+                drop(idle);
+                let mut recognizer = Recognizer::try_new(&self.model, sample_rate)?;
+                recognizer.set_words(true);
+                Ok(recognizer)
+            }
+        }
+    }
+    fn put(&self, mut recognizer: Recognizer) {
+        recognizer.reset();
+        let mut idle = self.idle.lock().unwrap();
+        // Files at unusual rates shouldn't pile up recognizers.
+        if idle.len() == self.max_idle {
+            idle.remove(0);
+        }
+        idle.push(recognizer);
+    }
+}
+
+struct App {
+    pool: Pool,
+    max_seconds: u32,
+    metrics: PrometheusHandle,
+}
+
+/// Counts a request as active for as long as it's kept,
+/// including when the client goes away and the handler is dropped.
+struct ActiveRequest;
+
+impl ActiveRequest {
+    fn new() -> ActiveRequest {
+        gauge!(REQUESTS_ACTIVE).increment(1.0);
+        ActiveRequest
+    }
+}
+
+impl Drop for ActiveRequest {
+    fn drop(&mut self) {
+        gauge!(REQUESTS_ACTIVE).decrement(1.0);
+    }
+}
+
+/// An error response with a JSON body.
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = serde_json::json!({ "error": self.1 });
+        (self.0, Json(body)).into_response()
+    }
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum Format {
+    #[default]
+    Whisper,
+    Native,
+}
+
+#[derive(Deserialize)]
+struct Params {
+    #[serde(default)]
+    format: Format,
+}
+
+/// Audio checked to be 16-bit mono PCM.
+struct Audio {
+    sample_rate: u32,
+    samples: Vec<i16>,
+}
+
+#[derive(Serialize)]
+struct WhisperResponse {
+    text: String,
+    duration: f32,
+    segments: Vec<Segment>,
+}
+
+#[derive(Serialize)]
+struct Segment {
+    id: usize,
+    start: f32,
+    end: f32,
+    text: String,
+    words: Vec<Word>,
+}
+
+#[derive(Serialize)]
+struct Word {
+    word: String,
+    start: f32,
+    end: f32,
+    probability: f32,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Args = argh::from_env();
+    let workers = args.workers.max(1);
+    let metrics = PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full(vosk::telemetry::REAL_TIME_FACTOR.to_string()),
+            &RTF_BUCKETS,
+        )?
+        .install_recorder()?;
+    vosk::telemetry::describe();
+    describe_gauge!(REQUESTS_ACTIVE, "Transcription requests being handled");
+    describe_counter!(REQUESTS, "Transcription requests handled, by status code");
+    let model = Model::new(&args.model).map_err(|e| e.to_string())?;
+    let app = Arc::new(App {
+        pool: Pool {
+            model,
+            idle: Mutex::new(Vec::new()),
+            workers: Arc::new(Semaphore::new(workers)),
+            max_idle: workers,
+        },
+        max_seconds: args.max_seconds,
+        metrics,
+    });
+    let router = Router::new()
+        .route("/transcribe", post(transcribe))
+        .route("/healthz", get(healthz))
+        .route("/metrics", get(render_metrics))
+        .layer(DefaultBodyLimit::max(args.max_body_mb * 1024 * 1024))
+        .with_state(app);
+    let listener = tokio::net::TcpListener::bind(&args.listen).await?;
+    eprintln!("Listening on {}", listener.local_addr()?);
+    axum::serve(listener, router).await?;
+    Ok(())
+}
+
+async fn healthz(State(app): State<Arc<App>>) -> Result<Json<serde_json::Value>, ApiError> {
+    let recognizer = app
+        .pool
+        .take(HEALTH_CHECK_RATE)
+        .map_err(|e| ApiError(StatusCode::SERVICE_UNAVAILABLE, e.to_string()))?;
+    app.pool.put(recognizer);
+    Ok(Json(serde_json::json!({
+        "status": "ok",
+        "model": app.pool.model.path(),
+    })))
+}
+
+async fn render_metrics(State(app): State<Arc<App>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        app.metrics.render(),
+    )
+}
+
+async fn transcribe(
+    State(app): State<Arc<App>>,
+    Query(params): Query<Params>,
+    request: Request,
+) -> Response {
+    let _active = ActiveRequest::new();
+    let response = match transcribe_wav(app, params, request).await {
+        Ok(response) => response,
+        Err(e) => e.into_response(),
+    };
+    // Status codes are few, so they make a bounded label.
+    counter!(REQUESTS, "status" => response.status().as_u16().to_string()).increment(1);
+    response
+}
+
+async fn transcribe_wav(
+    app: Arc<App>,
+    params: Params,
+    request: Request,
+) -> Result<Response, ApiError> {
+    let wav = wav_body(request, &app).await?;
+    let audio = decode(&wav, app.max_seconds)?;
+    drop(wav);
+    let duration = audio.samples.len() as f32 / audio.sample_rate as f32;
+    // Waiting here rather than on a blocking thread keeps those free.
+    let permit = app.pool.workers.clone().acquire_owned().await.unwrap();
+    let utterances = tokio::task::spawn_blocking(move || {
+        let _permit = permit;
+        recognize(&app.pool, &audio)
+    })
+    .await
+    .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))??;
+    Ok(match params.format {
+        Format::Native => Json(utterances).into_response(),
+        Format::Whisper => Json(whisper(&utterances, duration)).into_response(),
+    })
+}
+
+/// The WAV file, from the body itself or the `file` field of a form.
+async fn wav_body(request: Request, app: &Arc<App>) -> Result<Bytes, ApiError> {
+    let content_type = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    // Too large bodies are only noticed while reading the field.
+    let rejected = |e: MultipartError| ApiError(e.status(), e.body_text());
+    if content_type.starts_with("multipart/form-data") {
+        let mut form = Multipart::from_request(request, app)
+            .await
+            .map_err(|e| ApiError(e.status(), e.body_text()))?;
+        while let Some(field) = form.next_field().await.map_err(rejected)? {
+            if field.name() == Some("file") {
+                return field.bytes().await.map_err(rejected);
+            }
+        }
+        Err(ApiError(
+            StatusCode::BAD_REQUEST,
+            "the form has no file field".to_string(),
+        ))
+    } else if ["audio/wav", "audio/x-wav", "audio/wave"]
+        .iter()
+        .any(|t| content_type.starts_with(t))
+    {
+        Bytes::from_request(request, app)
+            .await
+            .map_err(|e| ApiError(e.status(), e.body_text()))
+    } else {
+        Err(ApiError(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "send audio/wav or multipart/form-data".to_string(),
+        ))
+    }
+}
+
+fn decode(wav: &[u8], max_seconds: u32) -> Result<Audio, ApiError> {
+    let reader = WavReader::new(Cursor::new(wav))
+        .map_err(|e| ApiError(StatusCode::BAD_REQUEST, format!("not a WAV file: {}", e)))?;
+    let spec = reader.spec();
+    if spec.sample_format != SampleFormat::Int || spec.bits_per_sample != 16 || spec.channels != 1 {
+        return Err(ApiError(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            format!(
+                "expected 16-bit mono PCM, got {} channels of {} bits",
+                spec.channels, spec.bits_per_sample
+            ),
+        ));
+    }
+    if reader.duration() / spec.sample_rate.max(1) >= max_seconds {
+        return Err(ApiError(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("the audio is longer than {} seconds", max_seconds),
+        ));
+    }
+    let samples = reader
+        .into_samples::<i16>()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| ApiError(StatusCode::BAD_REQUEST, e.to_string()))?;
+    Ok(Audio {
+        sample_rate: spec.sample_rate,
+        samples,
+    })
+}
+
+/// Runs on a blocking thread.
+fn recognize(pool: &Pool, audio: &Audio) -> Result<Vec<RecognizedTextOwned>, ApiError> {
+    let mut recognizer = pool
+        .take(audio.sample_rate as f32)
+        .map_err(|e| ApiError(StatusCode::BAD_REQUEST, e.to_string()))?;
+    let mut utterances = Vec::new();
+    for chunk in audio.samples.chunks(CHUNK) {
+        if recognizer.accept_waveform(chunk) {
+            utterances.push(recognizer.result().into_owned());
+        }
+    }
+    utterances.push(recognizer.final_result().into_owned());
+    pool.put(recognizer);
+    utterances.retain(|u| !u.text.is_empty());
+    Ok(utterances)
+}
+
+fn whisper(utterances: &[RecognizedTextOwned], duration: f32) -> WhisperResponse {
+    let segments: Vec<Segment> = utterances
+        .iter()
+        .enumerate()
+        .map(|(id, utterance)| {
+            let words = utterance.words();
+            Segment {
+                id,
+                start: words.first().map_or(0.0, |w| w.start()),
+                end: words.last().map_or(0.0, |w| w.end()),
+                text: utterance.text.to_string(),
+                words: words
+                    .iter()
+                    .map(|w| Word {
+                        word: w.word().to_string(),
+                        start: w.start(),
+                        end: w.end(),
+                        probability: w.conf(),
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+    WhisperResponse {
+        text: utterances
+            .iter()
+            .map(|u| u.text.as_ref())
+            .collect::<Vec<_>>()
+            .join(" "),
+        duration,
+        segments,
+    }
+}
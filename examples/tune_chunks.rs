@@ -0,0 +1,162 @@
+use argh::FromArgs;
+use riff_wave::WaveReader;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::BufReader;
+use std::process::exit;
+use std::time::Duration;
+use vosk::latency::{measure_chunks, ChunkMeasurement};
+use vosk::{Model, Recognizer};
+
+/// Exit code when the input can't be opened or decoded.
+const EXIT_DECODE: i32 = 2;
+/// Exit code when the model can't be loaded.
+const EXIT_MODEL: i32 = 3;
+/// Exit code when the CSV file can't be written.
+const EXIT_OUTPUT: i32 = 4;
+
+#[derive(FromArgs)]
+/// Feed a WAV file (mono, 16-bit PCM) to a recognizer in chunks of different sizes,
+/// and compare the speed, the calls into libvosk and the partial results of each size
+struct TuneChunks {
+    /// path to the audio file
+    #[argh(positional)]
+    input: String,
+    /// path to the model
+    #[argh(option, short = 'm', default = "String::from(\"model\")")]
+    model: String,
+    /// chunk sizes to try in milliseconds, separated by commas
+    #[argh(option, default = "String::from(\"10,20,50,100,200,500,1000,2000\")")]
+    sizes: String,
+    /// chunk size in milliseconds of the run the transcripts are compared to
+    #[argh(option, default = "100")]
+    reference: u32,
+    /// also write the results to this CSV file
+    #[argh(option)]
+    csv: Option<String>,
+}
+
+fn main() {
+    let args: TuneChunks = argh::from_env();
+    let sizes: Vec<u32> = match args
+        .sizes
+        .split(',')
+        .map(|s| s.trim().parse())
+        .collect::<Result<_, _>>()
+    {
+        Ok(sizes) => sizes,
+        Err(e) => {
+            eprintln!("Could not read the sizes {:?}: {}", args.sizes, e);
+            exit(1);
+        }
+    };
+    let (samples, sample_rate) = read_wav(&args.input);
+    let model = match Model::new(&args.model) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("Could not load model from {}: {}", args.model, e);
+            exit(EXIT_MODEL);
+        }
+    };
+    let mut recognizer = Recognizer::new(&model, sample_rate as f32);
+    let chunk_len = |ms: u32| (sample_rate as u64 * ms as u64 / 1000).max(1) as usize;
+
+    // The reference run also warms up the caches, so that the first size isn't slower.
+    eprintln!("Reference run with {} ms chunks", args.reference);
+    let reference = measure_chunks(&mut recognizer, &samples, chunk_len(args.reference));
+    let mut rows = Vec::new();
+    for &ms in &sizes {
+        eprintln!("Chunks of {} ms", ms);
+        let measurement = measure_chunks(&mut recognizer, &samples, chunk_len(ms));
+        let same = measurement.text == reference.text;
+        rows.push((ms, measurement, same));
+    }
+
+    println!(
+        "{:>8} {:>7} {:>7} {:>10} {:>10} {:>10} {:>10}",
+        "chunk ms", "calls", "RTF", "mean ms", "p95 ms", "partials/s", "transcript"
+    );
+    for (ms, m, same) in &rows {
+        println!(
+            "{:>8} {:>7} {:>7.3} {:>10.3} {:>10.3} {:>10.2} {:>10}",
+            ms,
+            m.calls.count(),
+            m.real_time_factor(),
+            millis(m.calls.mean()),
+            millis(m.calls.percentile(95)),
+            m.partial_updates_per_sec(),
+            if *same { "same" } else { "changed" }
+        );
+    }
+    if rows.iter().any(|(_, _, same)| !same) {
+        println!("\nReference transcript:\n{}", reference.text);
+    }
+    if let Some(path) = &args.csv {
+        if let Err(e) = std::fs::write(path, csv(&rows)) {
+            eprintln!("Could not write {}: {}", path, e);
+            exit(EXIT_OUTPUT);
+        }
+        eprintln!("Wrote {}", path);
+    }
+}
+
+fn csv(rows: &[(u32, ChunkMeasurement, bool)]) -> String {
+    let mut out = String::from(
+        "chunk_ms,chunk_samples,calls,rtf,mean_call_ms,p95_call_ms,partials_per_sec,transcript_changed\n",
+    );
+    for (ms, m, same) in rows {
+        let _ = writeln!(
+            out,
+            "{},{},{},{:.4},{:.4},{:.4},{:.3},{}",
+            ms,
+            m.chunk_samples,
+            m.calls.count(),
+            m.real_time_factor(),
+            millis(m.calls.mean()),
+            millis(m.calls.percentile(95)),
+            m.partial_updates_per_sec(),
+            !same
+        );
+    }
+    out
+}
+
+fn millis(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+/// All the samples of a mono 16-bit WAV file, and its sample rate.
+fn read_wav(path: &str) -> (Vec<i16>, u32) {
+    let file = match File::open(path) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("Could not open {}: {}", path, e);
+            exit(EXIT_DECODE);
+        }
+    };
+    let mut reader = match WaveReader::new(BufReader::new(file)) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("Could not decode {}: {:?}", path, e);
+            exit(EXIT_DECODE);
+        }
+    };
+    let fmt = &reader.pcm_format;
+    if fmt.num_channels != 1 || fmt.bits_per_sample != 16 {
+        eprintln!("Audio file must be WAV format mono PCM.");
+        exit(EXIT_DECODE);
+    }
+    let sample_rate = fmt.sample_rate;
+    let mut samples = Vec::new();
+    loop {
+        match reader.read_sample_i16() {
+            Ok(s) => samples.push(s),
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => {
+                eprintln!("Could not decode {}: {}", path, e);
+                exit(EXIT_DECODE);
+            }
+        }
+    }
+    (samples, sample_rate)
+}
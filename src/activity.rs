@@ -0,0 +1,284 @@
+//! Telling when someone speaks from how the partial results change,
+//! such as to light up an indicator.
+//!
+//! A non-empty partial result means the recognizer hears words, and an utterance
+//! is over once it's finalized or the partial result has been empty for a while.
+//! Times are positions in the audio fed, not the time of day, so the same audio
+//! gives the same events however fast it's recognized.
+
+use crate::Event;
+use std::time::Duration;
+
+/// Debouncing of `SpeechActivity`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActivityOptions {
+    /// How long the partial result must stay non-empty before speech counts as started,
+    /// so that a cough or a click recognized as a short word doesn't flash the indicator.
+    pub min_speech: Duration,
+    /// How long the partial result must stay empty before speech counts as ended,
+    /// so that it doesn't end and start again in a short pause.
+    pub hangover: Duration,
+}
+
+impl Default for ActivityOptions {
+    fn default() -> Self {
+        ActivityOptions {
+            min_speech: Duration::from_millis(150),
+            hangover: Duration::from_millis(400),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Idle,
+    /// Words were heard, but not for `min_speech` yet.
+    Pending {
+        since: Duration,
+    },
+    Speaking {
+        since: Duration,
+        /// When the partial result became empty.
+        silent_since: Option<Duration>,
+    },
+}
+
+/// Turns partial results into `Event::SpeechStarted` and `Event::SpeechEnded`.
+///
+/// Call `partial` with every partial result, changed or not, since time passing
+/// is what ends a pause, and `finalized` whenever an utterance is.
+#[derive(Debug, Clone)]
+pub struct SpeechActivity {
+    opts: ActivityOptions,
+    state: State,
+}
+
+impl SpeechActivity {
+    pub fn new(opts: ActivityOptions) -> SpeechActivity {
+        SpeechActivity {
+            opts,
+            state: State::Idle,
+        }
+    }
+    /// Takes the partial result after the audio up to `at` was fed.
+    pub fn partial(&mut self, text: &str, at: Duration) -> Option<Event> {
+        match (self.state, text.trim().is_empty()) {
+            (State::Idle, true) => None,
+            (State::Idle, false) => {
+                self.state = State::Pending { since: at };
+                self.start_if_long_enough(at)
+            }
+            (State::Pending { .. }, false) => self.start_if_long_enough(at),
+            // Too short, it was noise.
+            (State::Pending { .. }, true) => {
+                self.state = State::Idle;
+                None
+            }
+            (State::Speaking { since, .. }, false) => {
+                self.state = State::Speaking {
+                    since,
+                    silent_since: None,
+                };
+                None
+            }
+            (
+                State::Speaking {
+                    since,
+                    silent_since,
+                },
+                true,
+            ) => {
+                let silent_since = silent_since.unwrap_or(at);
+                if at.saturating_sub(silent_since) >= self.opts.hangover {
+                    self.end(since, silent_since)
+                } else {
+                    self.state = State::Speaking {
+                        since,
+                        silent_since: Some(silent_since),
+                    };
+                    None
+                }
+            }
+        }
+    }
+    /// Ends the speech in progress, as the utterance was finalized at `at`.
+    ///
+    /// Words heard for less than `min_speech` are dropped as noise, even if
+    /// they made it into the result.
+    pub fn finalized(&mut self, at: Duration) -> Option<Event> {
+        match self.state {
+            State::Speaking {
+                since,
+                silent_since,
+            } => self.end(since, silent_since.unwrap_or(at)),
+            State::Idle | State::Pending { .. } => {
+                self.state = State::Idle;
+                None
+            }
+        }
+    }
+    /// Whether `SpeechStarted` was the last event.
+    pub fn is_speaking(&self) -> bool {
+        matches!(self.state, State::Speaking { .. })
+    }
+    /// Forgets the speech in progress without an event, such as when the recognizer is reset.
+    pub fn reset(&mut self) {
+        self.state = State::Idle;
+    }
+    fn start_if_long_enough(&mut self, at: Duration) -> Option<Event> {
+        let State::Pending { since } = self.state else {
+            return None;
+        };
+        if at.saturating_sub(since) < self.opts.min_speech {
+            return None;
+        }
+        self.state = State::Speaking {
+            since,
+            silent_since: None,
+        };
+        Some(Event::SpeechStarted)
+    }
+    fn end(&mut self, since: Duration, until: Duration) -> Option<Event> {
+        self.state = State::Idle;
+        Some(Event::SpeechEnded {
+            duration: until.saturating_sub(since),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What a recognizer reports after a chunk of 100 ms.
+    enum Step {
+        Partial(&'static str),
+        Final,
+    }
+    use Step::*;
+
+    /// Plays a script through a detector, as a feeder would, with the position in ms of each event.
+    fn run(opts: ActivityOptions, script: &[Step]) -> Vec<(u64, Event)> {
+        let mut activity = SpeechActivity::new(opts);
+        let mut events = Vec::new();
+        for (i, step) in script.iter().enumerate() {
+            let at = Duration::from_millis(100 * (i as u64 + 1));
+            let event = match step {
+                Partial(text) => activity.partial(text, at),
+                Final => activity.finalized(at),
+            };
+            events.extend(event.map(|e| (at.as_millis() as u64, e)));
+        }
+        events
+    }
+    fn opts() -> ActivityOptions {
+        ActivityOptions {
+            min_speech: Duration::from_millis(150),
+            hangover: Duration::from_millis(300),
+        }
+    }
+    fn ended(ms: u64) -> Event {
+        Event::SpeechEnded {
+            duration: Duration::from_millis(ms),
+        }
+    }
+
+    #[test]
+    fn utterance_ended_by_final() {
+        let script = [
+            Partial(""),
+            Partial("turn"),
+            Partial("turn on"),
+            Partial("turn on the"),
+            Partial("turn on the light"),
+            Final,
+            Partial(""),
+        ];
+        assert_eq!(
+            run(opts(), &script),
+            [(400, Event::SpeechStarted), (600, ended(400))]
+        );
+    }
+    #[test]
+    fn partial_emptied_for_the_hangover() {
+        let script = [
+            Partial("yes"),
+            Partial("yes"),
+            Partial("yes"),
+            Partial(""),
+            Partial(""),
+            Partial(""),
+            Partial(""),
+            Final,
+        ];
+        // Speech ends where the partial result became empty, reported once.
+        assert_eq!(
+            run(opts(), &script),
+            [(300, Event::SpeechStarted), (700, ended(300))]
+        );
+    }
+    #[test]
+    fn short_noise_is_ignored() {
+        let script = [
+            Partial("the"),
+            Partial(""),
+            Partial("huh"),
+            Final,
+            Partial(" "),
+        ];
+        assert!(run(opts(), &script).is_empty());
+        let eager = ActivityOptions {
+            min_speech: Duration::ZERO,
+            ..opts()
+        };
+        assert_eq!(
+            run(eager, &[Partial("the"), Final]),
+            [(100, Event::SpeechStarted), (200, ended(100))]
+        );
+    }
+    #[test]
+    fn short_pause_doesnt_flicker() {
+        let script = [
+            Partial("call"),
+            Partial("call"),
+            Partial("call"),
+            Partial(""),
+            Partial(""),
+            Partial("call mom"),
+            Partial("call mom"),
+            Final,
+        ];
+        assert_eq!(
+            run(opts(), &script),
+            [(300, Event::SpeechStarted), (800, ended(700))]
+        );
+    }
+    #[test]
+    fn pause_at_the_end_counts_until_final() {
+        // Finalized before the hangover was over: the speech still ended when it went quiet.
+        let script = [
+            Partial("stop"),
+            Partial("stop"),
+            Partial("stop"),
+            Partial(""),
+            Final,
+        ];
+        assert_eq!(
+            run(opts(), &script),
+            [(300, Event::SpeechStarted), (500, ended(300))]
+        );
+    }
+    #[test]
+    fn reset_forgets_speech() {
+        let mut activity = SpeechActivity::new(ActivityOptions {
+            min_speech: Duration::ZERO,
+            ..opts()
+        });
+        let at = Duration::from_millis(100);
+        assert_eq!(activity.partial("hi", at), Some(Event::SpeechStarted));
+        assert!(activity.is_speaking());
+        activity.reset();
+        assert!(!activity.is_speaking());
+        assert_eq!(activity.finalized(at * 2), None);
+    }
+}
@@ -0,0 +1,321 @@
+//! Forced alignment: finding when each word of a known transcript was spoken.
+//!
+//! The recognizer is restricted to a grammar built from the transcript,
+//! and its words are then matched back to the transcript with an edit-distance alignment,
+//! so words it skipped or inserted don't shift the timings of the rest.
+
+use crate::{Error, Model, RecognizedText, RecognizedWord, Recognizer, SampleRate};
+use std::convert::TryFrom;
+
+/// Number of samples fed to the recognizer at a time.
+/// Results are collected after each call, so this bounds how late an utterance is read.
+const FEED_SAMPLES: usize = 8000;
+
+#[derive(Debug, Clone)]
+pub struct AlignOptions {
+    /// Number of transcript words in each phrase of the grammar.
+    ///
+    /// Longer phrases constrain the recognizer more,
+    /// shorter ones let it recover sooner from a misrecognized word.
+    pub phrase_len: usize,
+}
+
+impl Default for AlignOptions {
+    fn default() -> Self {
+        AlignOptions { phrase_len: 8 }
+    }
+}
+
+/// A word of the transcript with its timing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlignedWord {
+    /// The word as written in the transcript, including punctuation.
+    pub word: String,
+    /// Start in seconds.
+    pub start: f32,
+    /// End in seconds.
+    pub end: f32,
+    /// Confidence of the recognizer, 0 for unmatched words.
+    pub conf: f32,
+    /// False if the recognizer didn't hear this word.
+    /// Its start and end then span the gap between the neighbouring matched words.
+    pub matched: bool,
+}
+
+/// Aligns `transcript` to the audio in `samples` with the default options.
+///
+/// Fails with `Error::OutOfVocabulary` if the model doesn't know some of the words.
+pub fn align(
+    model: &Model,
+    samples: &[i16],
+    sample_rate: f32,
+    transcript: &str,
+) -> Result<Vec<AlignedWord>, Error> {
+    align_with(
+        model,
+        samples,
+        sample_rate,
+        transcript,
+        &AlignOptions::default(),
+    )
+}
+
+/// Aligns `transcript` to the audio in `samples`.
+pub fn align_with(
+    model: &Model,
+    samples: &[i16],
+    sample_rate: f32,
+    transcript: &str,
+    opts: &AlignOptions,
+) -> Result<Vec<AlignedWord>, Error> {
+    let rate = SampleRate::try_from(sample_rate)?;
+    let tokens = tokenize(transcript);
+    let missing = model.missing_words_exact(tokens.iter().map(|t| t.normalized.as_str()));
+    if !missing.is_empty() {
+        return Err(Error::OutOfVocabulary(
+            missing.into_iter().map(String::from).collect(),
+        ));
+    }
+    let mut recognizer =
+        Recognizer::try_with_grammar(model, rate.hz(), grammar(&tokens, opts.phrase_len))?;
+    recognizer.set_words(true);
+    let mut recognized = Vec::new();
+    for chunk in samples.chunks(FEED_SAMPLES) {
+        if recognizer.try_accept_waveform(chunk)? {
+            push_words(&mut recognized, recognizer.result());
+        }
+    }
+    push_words(&mut recognized, recognizer.final_result());
+    Ok(merge(&tokens, &recognized))
+}
+
+fn push_words(words: &mut Vec<RecognizedWord<'static>>, utterance: RecognizedText) {
+    let utterance = utterance.into_owned();
+    words.extend(utterance.result.into_iter().flatten());
+}
+
+/// A transcript word and the form the model knows it by.
+struct Token<'t> {
+    text: &'t str,
+    normalized: String,
+}
+
+/// Splits on whitespace, lowercases and drops punctuation.
+/// Tokens that are only punctuation are left out.
+fn tokenize(transcript: &str) -> Vec<Token<'_>> {
+    transcript
+        .split_whitespace()
+        .filter_map(|text| {
+            let normalized: String = text
+                .chars()
+                .filter(|c| c.is_alphanumeric() || *c == '\'')
+                .flat_map(char::to_lowercase)
+                .collect();
+            if normalized.is_empty() {
+                None
+            } else {
+                Some(Token { text, normalized })
+            }
+        })
+        .collect()
+}
+
+/// Consecutive transcript words in phrases of `phrase_len`,
+/// and `[unk]` to absorb speech that isn't in the transcript.
+fn grammar<'a>(tokens: &'a [Token], phrase_len: usize) -> Vec<Vec<&'a str>> {
+    tokens
+        .chunks(phrase_len.max(1))
+        .map(|phrase| phrase.iter().map(|t| t.normalized.as_str()).collect())
+        .chain(std::iter::once(vec!["[unk]"]))
+        .collect()
+}
+
+/// Maps recognized words onto transcript tokens with a minimal edit script.
+///
+/// Recognized words that are inserted or substituted are ignored,
+/// transcript words that are deleted or substituted are unmatched.
+fn merge(tokens: &[Token], recognized: &[RecognizedWord]) -> Vec<AlignedWord> {
+    let n = tokens.len();
+    let m = recognized.len();
+    let same = |i: usize, j: usize| recognized[j].word().to_lowercase() == tokens[i].normalized;
+    // cost[i * (m + 1) + j] is the distance between the first i tokens and first j words.
+    let mut cost = vec![0u32; (n + 1) * (m + 1)];
+    let at = |i: usize, j: usize| i * (m + 1) + j;
+    for i in 0..=n {
+        for j in 0..=m {
+            cost[at(i, j)] = if i == 0 {
+                j as u32
+            } else if j == 0 {
+                i as u32
+            } else {
+                let diagonal = cost[at(i - 1, j - 1)] + if same(i - 1, j - 1) { 0 } else { 1 };
+                diagonal
+                    .min(cost[at(i - 1, j)] + 1)
+                    .min(cost[at(i, j - 1)] + 1)
+            };
+        }
+    }
+
+    let mut matches: Vec<Option<&RecognizedWord>> = vec![None; n];
+    let (mut i, mut j) = (n, m);
+    while i > 0 || j > 0 {
+        if i > 0 && j > 0 && same(i - 1, j - 1) && cost[at(i, j)] == cost[at(i - 1, j - 1)] {
+            matches[i - 1] = Some(&recognized[j - 1]);
+            i -= 1;
+            j -= 1;
+        } else if i > 0 && j > 0 && cost[at(i, j)] == cost[at(i - 1, j - 1)] + 1 {
+            i -= 1;
+            j -= 1;
+        } else if i > 0 && cost[at(i, j)] == cost[at(i - 1, j)] + 1 {
+            i -= 1;
+        } else {
+            j -= 1;
+        }
+    }
+
+    // Unmatched words span from the end of the previous match to the start of the next.
+    let mut next_start = vec![None; n];
+    let mut following = None;
+    for (k, matched) in matches.iter().enumerate().rev() {
+        next_start[k] = following;
+        if let Some(w) = matched {
+            following = Some(w.start());
+        }
+    }
+    let mut previous_end = 0.0;
+    tokens
+        .iter()
+        .zip(matches)
+        .zip(next_start)
+        .map(|((token, matched), next_start)| match matched {
+            Some(w) => {
+                previous_end = w.end();
+                AlignedWord {
+                    word: token.text.to_string(),
+                    start: w.start(),
+                    end: w.end(),
+                    conf: w.conf(),
+                    matched: true,
+                }
+            }
+            None => AlignedWord {
+                word: token.text.to_string(),
+                start: previous_end,
+                end: next_start.unwrap_or(previous_end),
+                conf: 0.0,
+                matched: false,
+            },
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::utterance;
+
+    fn aligned(transcript: &str, words: &[(&str, f32, f32)]) -> Vec<(String, f32, f32, bool)> {
+        let recognized = utterance(words).result.unwrap();
+        merge(&tokenize(transcript), &recognized)
+            .into_iter()
+            .map(|w| (w.word, w.start, w.end, w.matched))
+            .collect()
+    }
+
+    #[test]
+    fn tokens() {
+        let tokens = tokenize("Hello, world -- it's  me!");
+        let normalized: Vec<_> = tokens.iter().map(|t| t.normalized.as_str()).collect();
+        assert_eq!(normalized, vec!["hello", "world", "it's", "me"]);
+        assert_eq!(tokens[0].text, "Hello,");
+    }
+    #[test]
+    fn phrases() {
+        let tokens = tokenize("one two three four five");
+        assert_eq!(
+            grammar(&tokens, 2),
+            vec![
+                vec!["one", "two"],
+                vec!["three", "four"],
+                vec!["five"],
+                vec!["[unk]"]
+            ]
+        );
+        assert_eq!(grammar(&tokens, 0).len(), 6);
+    }
+    #[test]
+    fn exact() {
+        let result = aligned(
+            "Good morning.",
+            &[("good", 0.5, 0.8), ("morning", 0.8, 1.3)],
+        );
+        assert_eq!(
+            result,
+            vec![
+                ("Good".to_string(), 0.5, 0.8, true),
+                ("morning.".to_string(), 0.8, 1.3, true)
+            ]
+        );
+    }
+    #[test]
+    fn deleted_word() {
+        let result = aligned("one two three", &[("one", 0.0, 0.4), ("three", 1.0, 1.5)]);
+        assert_eq!(result[1], ("two".to_string(), 0.4, 1.0, false));
+        assert!(result[2].3);
+    }
+    #[test]
+    fn inserted_word() {
+        let result = aligned(
+            "one two",
+            &[("one", 0.0, 0.4), ("[unk]", 0.4, 0.9), ("two", 1.0, 1.5)],
+        );
+        assert_eq!(
+            result,
+            vec![
+                ("one".to_string(), 0.0, 0.4, true),
+                ("two".to_string(), 1.0, 1.5, true)
+            ]
+        );
+    }
+    #[test]
+    fn substituted_word() {
+        let result = aligned(
+            "one two three",
+            &[("one", 0.0, 0.4), ("too", 0.5, 0.8), ("three", 1.0, 1.5)],
+        );
+        assert_eq!(result[1], ("two".to_string(), 0.4, 1.0, false));
+    }
+    #[test]
+    fn repeated_words() {
+        // The second "the" was not heard; the first must keep its own timing.
+        let result = aligned(
+            "the cat and the dog",
+            &[
+                ("the", 0.0, 0.2),
+                ("cat", 0.2, 0.6),
+                ("and", 0.6, 0.8),
+                ("dog", 1.0, 1.4),
+            ],
+        );
+        let matched: Vec<_> = result.iter().map(|w| w.3).collect();
+        assert_eq!(matched, vec![true, true, true, false, true]);
+        assert_eq!((result[0].1, result[3].1, result[3].2), (0.0, 0.8, 1.0));
+    }
+    #[test]
+    fn nothing_recognized() {
+        let result = aligned("one two", &[]);
+        assert_eq!(
+            result,
+            vec![
+                ("one".to_string(), 0.0, 0.0, false),
+                ("two".to_string(), 0.0, 0.0, false)
+            ]
+        );
+    }
+    #[test]
+    fn invalid_sample_rate() {
+        let model = crate::test_util::fake_model("model");
+        let aligned = align_with(&model, &[], f32::NAN, "one", &AlignOptions::default());
+        assert!(matches!(aligned, Err(Error::InvalidSampleRate(hz)) if hz.is_nan()));
+    }
+}
@@ -0,0 +1,210 @@
+use crate::{RecognizedText, UtteranceOwned};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// One hypothesis for an utterance, see `Recognizer::set_max_alternatives`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Alternative<'a> {
+    /// Likelihood score from libvosk, higher is better. Only the differences between
+    /// the alternatives of one utterance mean anything.
+    pub confidence: f32,
+    #[serde(borrow)]
+    pub text: Cow<'a, str>,
+}
+
+/// The hypotheses for an utterance, likeliest first, as libvosk returns them
+/// when asked for more than one.
+///
+/// libvosk gives no word confidences for alternatives, and the words are left out here.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Alternatives<'a> {
+    #[serde(borrow, default)]
+    pub alternatives: Vec<Alternative<'a>>,
+}
+
+/// A distinct text among the alternatives, see `Alternatives::candidates`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub text: String,
+    /// The share of the likelihood of all the alternatives that have this text,
+    /// between 0 and 1.
+    pub confidence: f32,
+}
+
+/// Whether the speaker should be asked what they meant, see
+/// `Recognizer::finalize_with_candidates`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FinalOutcome {
+    /// The likeliest text was likely enough, or the only one.
+    Confident(UtteranceOwned),
+    /// The likeliest texts, none sure enough to go ahead with.
+    Ambiguous(Vec<Candidate>),
+}
+
+impl<'a> Alternatives<'a> {
+    /// The likeliest alternative as a result without word details, empty if there is none.
+    pub fn best(self) -> RecognizedText<'a> {
+        match self.alternatives.into_iter().next() {
+            Some(best) => RecognizedText {
+                text: best.text,
+                result: None,
+            },
+            None => RecognizedText::from_text(""),
+        }
+    }
+    /// The distinct texts of the alternatives, likeliest first.
+    ///
+    /// Texts that only differ in case or spacing count as the same, and add up their
+    /// likelihood. Empty texts, when nothing may have been said, are left out,
+    /// though their likelihood still lowers the confidence of the others.
+    pub fn candidates(&self) -> Vec<Candidate> {
+        let Some(top) = self
+            .alternatives
+            .iter()
+            .map(|a| a.confidence as f64)
+            .reduce(f64::max)
+        else {
+            return Vec::new();
+        };
+        // Relative to the best score, so that exp doesn't overflow.
+        let weights: Vec<f64> = self
+            .alternatives
+            .iter()
+            .map(|a| (a.confidence as f64 - top).exp())
+            .collect();
+        let total: f64 = weights.iter().sum();
+        let mut candidates: Vec<(String, f64)> = Vec::new();
+        let mut seen = HashMap::new();
+        for (alternative, weight) in self.alternatives.iter().zip(weights) {
+            let text = alternative
+                .text
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ");
+            if text.is_empty() {
+                continue;
+            }
+            let i = *seen.entry(text.to_lowercase()).or_insert_with(|| {
+                candidates.push((text, 0.0));
+                candidates.len() - 1
+            });
+            candidates[i].1 += weight;
+        }
+        candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+        candidates
+            .into_iter()
+            .map(|(text, weight)| Candidate {
+                text,
+                confidence: (weight / total) as f32,
+            })
+            .collect()
+    }
+    /// Goes ahead with the likeliest text if its confidence, as in `candidates`, is at
+    /// least `min_conf` or it's the only one, else returns up to `max_candidates` texts.
+    pub fn outcome(&self, min_conf: f32, max_candidates: usize) -> FinalOutcome {
+        let mut candidates = self.candidates();
+        match candidates.first() {
+            None => FinalOutcome::Confident(RecognizedText::from_text("")),
+            Some(top) if top.confidence >= min_conf || candidates.len() == 1 => {
+                FinalOutcome::Confident(RecognizedText::from_text(top.text.clone()))
+            }
+            Some(_) => {
+                candidates.truncate(max_candidates);
+                FinalOutcome::Ambiguous(candidates)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alternatives(scores: &[(&'static str, f32)]) -> Alternatives<'static> {
+        Alternatives {
+            alternatives: scores
+                .iter()
+                .map(|&(text, confidence)| Alternative {
+                    confidence,
+                    text: text.into(),
+                })
+                .collect(),
+        }
+    }
+    fn texts(outcome: &FinalOutcome) -> Vec<(&str, f32)> {
+        match outcome {
+            FinalOutcome::Ambiguous(candidates) => candidates
+                .iter()
+                .map(|c| (c.text.as_str(), (c.confidence * 100.0).round() / 100.0))
+                .collect(),
+            FinalOutcome::Confident(_) => panic!("not ambiguous: {:?}", outcome),
+        }
+    }
+
+    #[test]
+    fn parses_libvosk_json() {
+        let json = r#"{"alternatives" : [{
+            "confidence" : 225.6,
+            "result" : [{"end" : 0.9, "start" : 0.3, "word" : "one"}],
+            "text" : "one"
+          }, {
+            "confidence" : 222.1,
+            "text" : "won"
+          }]}"#;
+        let parsed: Alternatives = serde_json::from_str(json).unwrap();
+        assert_eq!(parsed.alternatives.len(), 2);
+        assert_eq!(parsed.alternatives[1].text, "won");
+        assert_eq!(parsed.best().text, "one");
+        // A plain result, when libvosk wasn't asked for alternatives.
+        let plain: Alternatives = serde_json::from_str(r#"{"text" : ""}"#).unwrap();
+        assert_eq!(plain.best(), RecognizedText::from_text(""));
+    }
+    #[test]
+    fn duplicates_add_up() {
+        // Each half as likely as the first, which has 0.4 of the likelihood on its own.
+        let set = alternatives(&[
+            ("call  Anna", 10.0),
+            ("call anna", 10.0 - 2f32.ln()),
+            ("", 10.0 - 2f32.ln()),
+            ("tall anna", 10.0 - 2f32.ln()),
+        ]);
+        let candidates = set.candidates();
+        assert_eq!(candidates.len(), 2);
+        assert_eq!(candidates[0].text, "call Anna");
+        assert!((candidates[0].confidence - 0.6).abs() < 1e-6);
+        assert!((candidates[1].confidence - 0.2).abs() < 1e-6);
+    }
+    #[test]
+    fn thresholds() {
+        let close = alternatives(&[
+            ("turn on the light", 100.0),
+            ("turn off the light", 99.5),
+            ("turn on the lights", 99.0),
+        ]);
+        assert_eq!(
+            texts(&close.outcome(0.8, 2)),
+            [("turn on the light", 0.51), ("turn off the light", 0.31)]
+        );
+        assert_eq!(close.outcome(0.8, 5), close.outcome(0.8, 3));
+        assert_eq!(
+            close.outcome(0.5, 2),
+            FinalOutcome::Confident(RecognizedText::from_text("turn on the light"))
+        );
+
+        let clear = alternatives(&[("yes", 50.0), ("yet", 40.0)]);
+        assert!(matches!(clear.outcome(0.9, 3), FinalOutcome::Confident(r) if r.text == "yes"));
+        // Only one text, however unsure.
+        let alone = alternatives(&[("yes", 1.0), ("", 5.0), ("YES", 1.0)]);
+        assert!(matches!(alone.outcome(0.9, 3), FinalOutcome::Confident(r) if r.text == "yes"));
+        let nothing = alternatives(&[("", 1.0)]);
+        assert_eq!(
+            nothing.outcome(0.9, 3),
+            FinalOutcome::Confident(RecognizedText::from_text(""))
+        );
+        assert_eq!(
+            Alternatives::default().outcome(0.9, 3),
+            FinalOutcome::Confident(RecognizedText::from_text(""))
+        );
+    }
+}
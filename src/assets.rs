@@ -0,0 +1,375 @@
+//! Loading models shipped inside an app package, such as the assets of an Android APK.
+//!
+//! libvosk reads models from a directory, so the files are extracted first.
+//! A marker written after the last file records a hash of what was extracted,
+//! so later launches skip the work, and an interrupted extraction is redone.
+
+use crate::{Error, Model};
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// Name of the marker file, written in the extracted directory.
+const MARKER: &str = ".vosk-extracted";
+
+/// Read access to packaged files, addressed by `/`-separated paths.
+pub trait AssetProvider {
+    /// Names of the files directly in `dir`.
+    fn files(&self, dir: &str) -> io::Result<Vec<String>>;
+    /// Names of the directories directly in `dir`.
+    fn dirs(&self, dir: &str) -> io::Result<Vec<String>>;
+    /// Size of the file at `path` in bytes.
+    fn size(&self, path: &str) -> io::Result<u64>;
+    fn open(&self, path: &str) -> io::Result<Box<dyn Read + '_>>;
+    /// A value that changes whenever any packaged file does, such as the version code
+    /// of the app or the digest of its archive.
+    ///
+    /// With `None`, the contents of the files are hashed to tell whether they changed,
+    /// reading all of them on every launch.
+    fn version(&self) -> Option<String> {
+        None
+    }
+}
+
+impl Model {
+    /// Extracts the model in the APK asset directory `asset_dir` into `cache_dir`,
+    /// unless that was done before, and loads it.
+    ///
+    /// `cache_dir` is usually `Context.getFilesDir()` or `getCacheDir()`.
+    /// The assets are read on every call to tell whether they changed; wrap `assets` in an
+    /// `AssetProvider` returning the app's version code to avoid that.
+    #[cfg(target_os = "android")]
+    pub fn from_android_assets<P: AsRef<Path>>(
+        assets: &ndk::asset::AssetManager,
+        asset_dir: &str,
+        cache_dir: P,
+    ) -> Result<Model, Error> {
+        Model::from_assets(assets, asset_dir, cache_dir)
+    }
+    /// Extracts the model in `asset_dir` into `cache_dir` with `extract_assets`, and loads it.
+    pub fn from_assets<A, P>(assets: &A, asset_dir: &str, cache_dir: P) -> Result<Model, Error>
+    where
+        A: AssetProvider + ?Sized,
+        P: AsRef<Path>,
+    {
+        let dir = extract_assets(assets, asset_dir, cache_dir.as_ref())?;
+        Model::new(dir)
+    }
+}
+
+/// Copies the files under `asset_dir` into a directory of the same name in `cache_dir`,
+/// returning that directory.
+///
+/// Nothing is copied if the marker of a previous extraction matches the paths and sizes
+/// of the assets, and `AssetProvider::version` or else their contents. Otherwise the directory is replaced, which also takes care of files
+/// left by an extraction that was interrupted.
+pub fn extract_assets<A>(assets: &A, asset_dir: &str, cache_dir: &Path) -> Result<PathBuf, Error>
+where
+    A: AssetProvider + ?Sized,
+{
+    let asset_dir = asset_dir.trim_matches('/');
+    let mut files = Vec::new();
+    list(assets, asset_dir, "", &mut files)?;
+    if files.is_empty() {
+        return Err(Error::Io(format!("no assets in {}", asset_dir)));
+    }
+    let target = cache_dir.join(asset_dir);
+    let marker = target.join(MARKER);
+    let hash = format!("{:016x}", content_hash(assets, asset_dir, &files)?);
+    if fs::read_to_string(&marker).is_ok_and(|found| found == hash) {
+        return Ok(target);
+    }
+    match fs::remove_dir_all(&target) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    for (path, _) in &files {
+        let dest = target.join(path);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut src = assets.open(&join(asset_dir, path))?;
+        io::copy(&mut src, &mut fs::File::create(dest)?)?;
+    }
+    // Renaming is atomic, a crash leaves either no marker or a complete one.
+    let partial = target.join(format!("{}.tmp", MARKER));
+    fs::write(&partial, &hash)?;
+    fs::rename(partial, marker)?;
+    Ok(target)
+}
+
+/// Collects the paths relative to `root` and sizes of all files under `root/dir`, sorted.
+fn list<A>(assets: &A, root: &str, dir: &str, out: &mut Vec<(String, u64)>) -> io::Result<()>
+where
+    A: AssetProvider + ?Sized,
+{
+    let full = join(root, dir);
+    for name in assets.files(&full)? {
+        let size = assets.size(&join(&full, &name))?;
+        out.push((join(dir, &name), size));
+    }
+    for name in assets.dirs(&full)? {
+        list(assets, root, &join(dir, &name), out)?;
+    }
+    out.sort();
+    Ok(())
+}
+
+fn join(dir: &str, name: &str) -> String {
+    if dir.is_empty() || name.is_empty() {
+        format!("{}{}", dir, name)
+    } else {
+        format!("{}/{}", dir, name)
+    }
+}
+
+/// FNV-1a over the paths and sizes, then the version of the assets if known,
+/// or else the contents of every file.
+fn content_hash<A>(assets: &A, root: &str, files: &[(String, u64)]) -> io::Result<u64>
+where
+    A: AssetProvider + ?Sized,
+{
+    let mut hash = Fnv(0xcbf2_9ce4_8422_2325);
+    for (path, size) in files {
+        hash.write(path.as_bytes());
+        hash.write(&[0]);
+        hash.write(&size.to_le_bytes());
+    }
+    if let Some(version) = assets.version() {
+        hash.write(version.as_bytes());
+        return Ok(hash.0);
+    }
+    let mut buf = vec![0; 64 * 1024];
+    for (path, _) in files {
+        let mut src = assets.open(&join(root, path))?;
+        loop {
+            match src.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => hash.write(&buf[..n]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    Ok(hash.0)
+}
+
+struct Fnv(u64);
+
+impl Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= b as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+/// Directories found in models, as AAssetDir only lists files.
+#[cfg(target_os = "android")]
+const MODEL_DIRS: [&str; 7] = [
+    "am", "conf", "graph", "phones", "ivector", "rescore", "rnnlm",
+];
+
+/// Only directories named like those of a model are found, and only if they hold files.
+#[cfg(target_os = "android")]
+impl AssetProvider for ndk::asset::AssetManager {
+    fn files(&self, dir: &str) -> io::Result<Vec<String>> {
+        let names = self.open_dir(&c_path(dir)?).into_iter().flatten();
+        Ok(names.map(|n| n.to_string_lossy().into_owned()).collect())
+    }
+    fn dirs(&self, dir: &str) -> io::Result<Vec<String>> {
+        let mut dirs = Vec::new();
+        for name in MODEL_DIRS {
+            if !self.files(&join(dir, name))?.is_empty() {
+                dirs.push(name.to_string());
+            }
+        }
+        Ok(dirs)
+    }
+    fn size(&self, path: &str) -> io::Result<u64> {
+        Ok(open_asset(self, path)?.length() as u64)
+    }
+    fn open(&self, path: &str) -> io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(open_asset(self, path)?))
+    }
+}
+
+#[cfg(target_os = "android")]
+fn open_asset(assets: &ndk::asset::AssetManager, path: &str) -> io::Result<ndk::asset::Asset> {
+    ndk::asset::AssetManager::open(assets, &c_path(path)?)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, path.to_string()))
+}
+
+#[cfg(target_os = "android")]
+fn c_path(path: &str) -> io::Result<std::ffi::CString> {
+    std::ffi::CString::new(path).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::collections::BTreeMap;
+
+    /// Assets kept in memory, counting how many files were opened.
+    #[derive(Default)]
+    struct FakeAssets {
+        files: BTreeMap<String, Vec<u8>>,
+        opened: Cell<usize>,
+        version: Option<String>,
+    }
+
+    impl FakeAssets {
+        fn with(files: &[(&str, &str)]) -> FakeAssets {
+            FakeAssets {
+                files: files
+                    .iter()
+                    .map(|(path, content)| (path.to_string(), content.as_bytes().to_vec()))
+                    .collect(),
+                opened: Cell::new(0),
+                version: None,
+            }
+        }
+        /// Entries directly in `dir`, as (name, is a directory).
+        fn entries(&self, dir: &str) -> Vec<(String, bool)> {
+            let prefix = format!("{}/", dir);
+            let mut entries: Vec<_> = self
+                .files
+                .keys()
+                .filter_map(|path| path.strip_prefix(&prefix))
+                .map(|rest| match rest.split_once('/') {
+                    Some((subdir, _)) => (subdir.to_string(), true),
+                    None => (rest.to_string(), false),
+                })
+                .collect();
+            entries.dedup();
+            entries
+        }
+    }
+
+    impl AssetProvider for FakeAssets {
+        fn files(&self, dir: &str) -> io::Result<Vec<String>> {
+            let entries = self.entries(dir).into_iter();
+            Ok(entries.filter(|e| !e.1).map(|e| e.0).collect())
+        }
+        fn dirs(&self, dir: &str) -> io::Result<Vec<String>> {
+            let entries = self.entries(dir).into_iter();
+            Ok(entries.filter(|e| e.1).map(|e| e.0).collect())
+        }
+        fn size(&self, path: &str) -> io::Result<u64> {
+            Ok(self.files[path].len() as u64)
+        }
+        fn open(&self, path: &str) -> io::Result<Box<dyn Read + '_>> {
+            self.opened.set(self.opened.get() + 1);
+            Ok(Box::new(&self.files[path][..]))
+        }
+        fn version(&self) -> Option<String> {
+            self.version.clone()
+        }
+    }
+
+    fn cache_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vosk-assets-{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+    fn model() -> FakeAssets {
+        FakeAssets::with(&[
+            ("models/small/am/final.mdl", "acoustic"),
+            ("models/small/conf/mfcc.conf", "--use-energy=false"),
+            ("models/small/graph/phones/word_boundary.int", "1 nonword"),
+            ("models/small/README", "a model"),
+        ])
+    }
+
+    #[test]
+    fn extract_tree() {
+        let cache = cache_dir("tree");
+        let assets = model();
+        let dir = extract_assets(&assets, "models/small/", &cache).unwrap();
+        assert_eq!(dir, cache.join("models/small"));
+        let read = |path: &str| fs::read_to_string(dir.join(path)).unwrap();
+        assert_eq!(read("am/final.mdl"), "acoustic");
+        assert_eq!(read("graph/phones/word_boundary.int"), "1 nonword");
+        assert_eq!(read("README"), "a model");
+        assert_eq!(read(MARKER).len(), 16);
+        // Once to hash, once to copy.
+        assert_eq!(assets.opened.get(), 8);
+    }
+    #[test]
+    fn skip_when_marked() {
+        let cache = cache_dir("marked");
+        let assets = model();
+        extract_assets(&assets, "models/small", &cache).unwrap();
+        extract_assets(&assets, "models/small", &cache).unwrap();
+        assert_eq!(assets.opened.get(), 12);
+    }
+    #[test]
+    fn version_skips_hashing() {
+        let cache = cache_dir("version");
+        let mut assets = model();
+        assets.version = Some("1".into());
+        extract_assets(&assets, "models/small", &cache).unwrap();
+        extract_assets(&assets, "models/small", &cache).unwrap();
+        assert_eq!(assets.opened.get(), 4);
+        // A new release with the same sizes.
+        assets
+            .files
+            .insert("models/small/am/final.mdl".into(), b"acoustiC".to_vec());
+        assets.version = Some("2".into());
+        extract_assets(&assets, "models/small", &cache).unwrap();
+        assert_eq!(assets.opened.get(), 8);
+        let mdl = cache.join("models/small/am/final.mdl");
+        assert_eq!(fs::read_to_string(mdl).unwrap(), "acoustiC");
+    }
+    #[test]
+    fn redo_interrupted() {
+        let cache = cache_dir("interrupted");
+        let assets = model();
+        let dir = extract_assets(&assets, "models/small", &cache).unwrap();
+        // As if the app was killed halfway through a first extraction.
+        fs::remove_file(dir.join(MARKER)).unwrap();
+        fs::write(dir.join("am/final.mdl"), "acou").unwrap();
+        fs::write(dir.join("leftover"), "").unwrap();
+        extract_assets(&assets, "models/small", &cache).unwrap();
+        assert_eq!(assets.opened.get(), 16);
+        assert_eq!(
+            fs::read_to_string(dir.join("am/final.mdl")).unwrap(),
+            "acoustic"
+        );
+        assert!(!dir.join("leftover").exists());
+    }
+    #[test]
+    fn redo_when_changed() {
+        let cache = cache_dir("changed");
+        extract_assets(&model(), "models/small", &cache).unwrap();
+        let mut updated = model();
+        updated
+            .files
+            .insert("models/small/am/final.mdl".into(), b"retrained".to_vec());
+        extract_assets(&updated, "models/small", &cache).unwrap();
+        assert_eq!(updated.opened.get(), 8);
+        let mdl = cache.join("models/small/am/final.mdl");
+        assert_eq!(fs::read_to_string(mdl).unwrap(), "retrained");
+    }
+    #[test]
+    fn redo_when_same_size_changed() {
+        let cache = cache_dir("same-size");
+        extract_assets(&model(), "models/small", &cache).unwrap();
+        let mut updated = model();
+        updated
+            .files
+            .insert("models/small/am/final.mdl".into(), b"acoustiC".to_vec());
+        extract_assets(&updated, "models/small", &cache).unwrap();
+        let mdl = cache.join("models/small/am/final.mdl");
+        assert_eq!(fs::read_to_string(mdl).unwrap(), "acoustiC");
+    }
+    #[test]
+    fn missing_dir() {
+        let cache = cache_dir("missing");
+        let result = extract_assets(&model(), "models/large", &cache);
+        assert!(matches!(result, Err(Error::Io(_))));
+        assert!(!cache.exists());
+    }
+}
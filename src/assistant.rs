@@ -0,0 +1,469 @@
+//! Listening for a command after a wake word, as voice assistants do.
+//!
+//! An `AssistantSession` feeds all the audio to a `WakeWordListener`. Once a wake
+//! phrase is heard, the audio after it goes to a second recognizer, restricted to a
+//! grammar of commands or not, for a limited window. The window is extended while
+//! someone speaks, and the session goes back to waiting for the wake phrase once the
+//! command ends or nothing was said in time.
+//!
+//! Time is counted in samples fed rather than by the clock,
+//! so that a file gives the same events however fast it's read.
+//!
+//! ```no_run
+//! # use vosk::assistant::{AssistantEvent, AssistantOptions, AssistantSession};
+//! # use vosk::wake::WakeWordListener;
+//! # use vosk::{Model, Recognizer};
+//! # fn main() -> Result<(), vosk::Error> {
+//! # let model = Model::new("model")?;
+//! # let chunks = std::iter::repeat(vec![0i16; 1600]);
+//! let listener = WakeWordListener::new(&model, 16000.0, &["hey computer"])?;
+//! let command = Recognizer::new(&model, 16000.0);
+//! let mut session = AssistantSession::new(listener, command, AssistantOptions::default());
+//! for chunk in chunks {
+//!     session.feed(&chunk);
+//!     while let Some(event) = session.next_event() {
+//!         match event {
+//!             AssistantEvent::Listening => println!("listening..."),
+//!             AssistantEvent::CommandFinalized(command) => println!("> {}", command.text),
+//!             AssistantEvent::TimedOut => println!("never mind"),
+//!             _ => {}
+//!         }
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::partial::PartialTracker;
+use crate::wake::{WakeEvent, WakeWordListener};
+use crate::{Recognizer, UtteranceOwned};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// How long an `AssistantSession` listens for a command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssistantOptions {
+    /// How long after the wake phrase a command may start.
+    pub window: Duration,
+    /// How long the window lasts after the latest speech, once it's longer than `window`.
+    pub silence: Duration,
+    /// The longest the session listens after the wake phrase, even if someone keeps speaking.
+    pub max_window: Duration,
+}
+
+impl Default for AssistantOptions {
+    fn default() -> Self {
+        AssistantOptions {
+            window: Duration::from_secs(8),
+            silence: Duration::from_millis(1500),
+            max_window: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Whether an `AssistantSession` waits for the wake phrase or for a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssistantState {
+    /// Only the wake word listener is fed.
+    Idle,
+    /// The command recognizer is fed as well.
+    Listening,
+}
+
+/// What happened in an `AssistantSession`, in the order of transitions.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum AssistantEvent {
+    /// A wake phrase was heard, also while listening, which drops the command in
+    /// progress. Its pre-roll after the phrase is fed to the command recognizer.
+    Triggered(WakeEvent),
+    /// The command recognizer started over, right after `Triggered`.
+    Listening,
+    /// The partial result of the command changed, which extends the window.
+    Partial(String),
+    /// The command ended, either at the end of an utterance or when the window
+    /// ran out after some speech.
+    CommandFinalized(UtteranceOwned),
+    /// The window ran out before anything was recognized.
+    TimedOut,
+    /// Back to waiting for the wake phrase, after `CommandFinalized` or `TimedOut`.
+    Idle,
+}
+
+/// Listens for a command after each wake phrase, see the module documentation.
+#[derive(Debug)]
+pub struct AssistantSession {
+    inner: Session<Recognizers>,
+}
+
+impl AssistantSession {
+    /// `command` should have the same sample rate as the recognizer of `listener`.
+    pub fn new(listener: WakeWordListener, command: Recognizer, opts: AssistantOptions) -> Self {
+        let sample_rate = command.sample_rate();
+        AssistantSession {
+            inner: Session::new(Recognizers { listener, command }, sample_rate, opts),
+        }
+    }
+    /// Feeds audio, queueing the events it leads to.
+    pub fn feed(&mut self, wave: &[i16]) {
+        self.inner.feed(wave)
+    }
+    /// Returns the oldest event not returned yet.
+    pub fn next_event(&mut self) -> Option<AssistantEvent> {
+        self.inner.events.pop_front()
+    }
+    pub fn state(&self) -> AssistantState {
+        match self.inner.window {
+            Some(_) => AssistantState::Listening,
+            None => AssistantState::Idle,
+        }
+    }
+    /// Stops listening for a command without finalizing it, as if the window had
+    /// run out with nothing said.
+    pub fn cancel(&mut self) {
+        self.inner.cancel()
+    }
+    pub fn listener(&self) -> &WakeWordListener {
+        &self.inner.backend.listener
+    }
+    pub fn listener_mut(&mut self) -> &mut WakeWordListener {
+        &mut self.inner.backend.listener
+    }
+    pub fn command_recognizer(&self) -> &Recognizer {
+        &self.inner.backend.command
+    }
+    pub fn command_recognizer_mut(&mut self) -> &mut Recognizer {
+        &mut self.inner.backend.command
+    }
+    pub fn into_inner(self) -> (WakeWordListener, Recognizer) {
+        let Recognizers { listener, command } = self.inner.backend;
+        (listener, command)
+    }
+}
+
+#[derive(Debug)]
+struct Recognizers {
+    listener: WakeWordListener,
+    command: Recognizer,
+}
+
+/// What the session needs of its recognizers, so that it can be tested without a model.
+trait Backend {
+    fn wake(&mut self, wave: &[i16]) -> Option<WakeEvent>;
+    fn accept(&mut self, wave: &[i16]) -> bool;
+    fn result(&mut self) -> UtteranceOwned;
+    fn partial(&mut self) -> String;
+    fn final_result(&mut self) -> UtteranceOwned;
+    fn reset(&mut self);
+}
+
+impl Backend for Recognizers {
+    fn wake(&mut self, wave: &[i16]) -> Option<WakeEvent> {
+        self.listener.feed(wave)
+    }
+    fn accept(&mut self, wave: &[i16]) -> bool {
+        self.command.accept_waveform(wave)
+    }
+    fn result(&mut self) -> UtteranceOwned {
+        self.command.result().into_owned()
+    }
+    fn partial(&mut self) -> String {
+        self.command.partial_result().partial.into_owned()
+    }
+    fn final_result(&mut self) -> UtteranceOwned {
+        self.command.final_result().into_owned()
+    }
+    fn reset(&mut self) {
+        self.command.reset()
+    }
+}
+
+/// When listening ends, in samples fed to the session.
+#[derive(Debug, Clone, Copy)]
+struct Window {
+    deadline: u64,
+    limit: u64,
+}
+
+#[derive(Debug)]
+struct Session<B> {
+    backend: B,
+    opts: AssistantOptions,
+    sample_rate: f32,
+    fed: u64,
+    /// Some while listening for a command.
+    window: Option<Window>,
+    partials: PartialTracker,
+    events: VecDeque<AssistantEvent>,
+}
+
+impl<B: Backend> Session<B> {
+    fn new(backend: B, sample_rate: f32, opts: AssistantOptions) -> Self {
+        Session {
+            backend,
+            opts,
+            sample_rate,
+            fed: 0,
+            window: None,
+            partials: PartialTracker::default(),
+            events: VecDeque::new(),
+        }
+    }
+    fn samples_in(&self, duration: Duration) -> u64 {
+        (duration.as_secs_f64() * self.sample_rate as f64) as u64
+    }
+    fn feed(&mut self, wave: &[i16]) {
+        self.fed += wave.len() as u64;
+        if let Some(wake) = self.backend.wake(wave) {
+            self.trigger(wake);
+        } else if self.window.is_some() {
+            let completed = self.backend.accept(wave);
+            self.recognized(completed);
+        }
+        if let Some(window) = self.window {
+            if self.fed >= window.deadline {
+                let last = self.backend.final_result();
+                self.end(last);
+            }
+        }
+    }
+    fn trigger(&mut self, wake: WakeEvent) {
+        // Whatever the command recognizer heard before doesn't belong to this command.
+        self.backend.reset();
+        self.partials.reset();
+        self.window = Some(Window {
+            deadline: self.fed + self.samples_in(self.opts.window),
+            limit: self.fed + self.samples_in(self.opts.max_window),
+        });
+        let completed = self.backend.accept(wake.after_phrase());
+        self.events.push_back(AssistantEvent::Triggered(wake));
+        self.events.push_back(AssistantEvent::Listening);
+        self.recognized(completed);
+    }
+    fn recognized(&mut self, completed: bool) {
+        if completed {
+            let utterance = self.backend.result();
+            self.partials.reset();
+            // Noise can end an utterance with nothing in it, the command may still come.
+            if !utterance.text.is_empty() {
+                self.end(utterance);
+            }
+            return;
+        }
+        let partial = self.backend.partial();
+        if let Some(changed) = self.partials.changed(&partial) {
+            if changed.is_empty() {
+                return;
+            }
+            self.events
+                .push_back(AssistantEvent::Partial(changed.to_string()));
+            let silence = self.samples_in(self.opts.silence);
+            if let Some(window) = &mut self.window {
+                window.deadline = window.deadline.max(self.fed + silence).min(window.limit);
+            }
+        }
+    }
+    fn end(&mut self, utterance: UtteranceOwned) {
+        self.window = None;
+        self.partials.reset();
+        let event = match utterance.text.is_empty() {
+            true => AssistantEvent::TimedOut,
+            false => AssistantEvent::CommandFinalized(utterance),
+        };
+        self.events.push_back(event);
+        self.events.push_back(AssistantEvent::Idle);
+    }
+    fn cancel(&mut self) {
+        if self.window.is_some() {
+            self.backend.reset();
+            self.end(crate::RecognizedText::from_text(""));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RecognizedText;
+
+    /// Reads samples as symbols: 1 is speech, 2 ends an utterance and 9 is the wake phrase.
+    #[derive(Debug, Default)]
+    struct Mock {
+        heard: usize,
+        resets: usize,
+    }
+
+    impl Mock {
+        fn take(&mut self) -> UtteranceOwned {
+            let text = match self.heard {
+                0 => String::new(),
+                n => format!("command {}", n),
+            };
+            self.heard = 0;
+            RecognizedText::from_text(text)
+        }
+    }
+
+    impl Backend for Mock {
+        fn wake(&mut self, wave: &[i16]) -> Option<WakeEvent> {
+            let at = wave.iter().position(|&s| s == 9)?;
+            Some(WakeEvent {
+                phrase: "jarvis".to_string(),
+                confidence: None,
+                pre_roll: wave.to_vec(),
+                phrase_end: Some(at + 1),
+            })
+        }
+        fn accept(&mut self, wave: &[i16]) -> bool {
+            self.heard += wave.iter().filter(|&&s| s == 1).count();
+            wave.contains(&2)
+        }
+        fn result(&mut self) -> UtteranceOwned {
+            self.take()
+        }
+        fn partial(&mut self) -> String {
+            match self.heard {
+                0 => String::new(),
+                n => format!("command {}", n),
+            }
+        }
+        fn final_result(&mut self) -> UtteranceOwned {
+            self.take()
+        }
+        fn reset(&mut self) {
+            self.heard = 0;
+            self.resets += 1;
+        }
+    }
+
+    /// At ten samples per second, a window of 8 seconds is 80 samples.
+    fn session() -> Session<Mock> {
+        Session::new(Mock::default(), 10.0, AssistantOptions::default())
+    }
+
+    fn events(session: &mut Session<Mock>) -> Vec<String> {
+        session
+            .events
+            .drain(..)
+            .map(|event| match event {
+                AssistantEvent::Triggered(wake) => format!("triggered {}", wake.phrase),
+                AssistantEvent::Partial(text) => format!("partial {}", text),
+                AssistantEvent::CommandFinalized(command) => format!("command {}", command.text),
+                other => format!("{:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn timeout_without_speech() {
+        let mut session = session();
+        session.feed(&[0; 10]);
+        assert!(events(&mut session).is_empty());
+        // The trigger ends at 20 samples, so the window lasts until 100.
+        session.feed(&[0, 0, 9, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(events(&mut session), ["triggered jarvis", "Listening"]);
+        for _ in 0..7 {
+            session.feed(&[0; 10]);
+        }
+        assert!(session.window.is_some());
+        assert!(events(&mut session).is_empty());
+        // An utterance of noise alone doesn't end listening either.
+        session.feed(&[0, 0, 2, 0, 0, 0, 0, 0, 0]);
+        assert!(events(&mut session).is_empty());
+        session.feed(&[0]);
+        assert_eq!(events(&mut session), ["TimedOut", "Idle"]);
+        assert!(session.window.is_none());
+        // Idle again, the command recognizer isn't fed.
+        session.feed(&[1; 10]);
+        assert!(events(&mut session).is_empty());
+        assert_eq!(session.backend.heard, 0);
+    }
+    #[test]
+    fn command_then_silence() {
+        let mut session = session();
+        session.feed(&[9, 0, 0, 0, 0]);
+        session.feed(&[1; 3]);
+        session.feed(&[1, 1, 2, 0]);
+        assert_eq!(
+            events(&mut session),
+            [
+                "triggered jarvis",
+                "Listening",
+                "partial command 3",
+                "command command 5",
+                "Idle"
+            ]
+        );
+        assert!(session.window.is_none());
+
+        // A command starting late in the window keeps it open until it's been quiet
+        // for 1.5 seconds, then it's finalized.
+        session.feed(&[9]);
+        session.feed(&[0; 75]);
+        session.feed(&[1; 5]);
+        assert_eq!(
+            events(&mut session),
+            ["triggered jarvis", "Listening", "partial command 5"]
+        );
+        // Listening until 93 + 15 samples rather than 13 + 80.
+        session.feed(&[0; 10]);
+        assert!(events(&mut session).is_empty());
+        session.feed(&[0; 5]);
+        assert_eq!(events(&mut session), ["command command 5", "Idle"]);
+    }
+    #[test]
+    fn retrigger_while_listening() {
+        let mut session = session();
+        session.feed(&[9]);
+        session.feed(&[1; 4]);
+        // The wake phrase again, the command so far is dropped.
+        session.feed(&[1, 9, 1, 1]);
+        assert_eq!(
+            events(&mut session),
+            [
+                "triggered jarvis",
+                "Listening",
+                "partial command 4",
+                "triggered jarvis",
+                "Listening",
+                "partial command 2",
+            ]
+        );
+        assert_eq!(session.backend.resets, 2);
+        // The window starts over from the second trigger, at 9 samples.
+        session.feed(&[0; 79]);
+        assert!(events(&mut session).is_empty());
+        session.feed(&[0]);
+        assert_eq!(events(&mut session), ["command command 2", "Idle"]);
+    }
+    #[test]
+    fn longest_window_and_cancel() {
+        let mut session = session();
+        session.feed(&[9]);
+        // Speech that keeps going is cut after 30 seconds.
+        for _ in 0..299 {
+            session.feed(&[1]);
+        }
+        assert_eq!(events(&mut session).len(), 301);
+        session.feed(&[1]);
+        assert_eq!(
+            events(&mut session),
+            ["partial command 300", "command command 300", "Idle"]
+        );
+
+        session.feed(&[9, 1]);
+        session.cancel();
+        assert_eq!(
+            events(&mut session),
+            [
+                "triggered jarvis",
+                "Listening",
+                "partial command 1",
+                "TimedOut",
+                "Idle"
+            ]
+        );
+        session.cancel();
+        assert!(events(&mut session).is_empty());
+    }
+}
@@ -0,0 +1,179 @@
+use crate::rate::{self, SampleRate};
+use crate::{Error, Model, Recognizer};
+use std::convert::TryFrom;
+
+/// Creates a recognizer with its options set, see `Recognizer::builder`.
+///
+/// ```no_run
+/// # use vosk::{Model, Recognizer};
+/// # fn main() -> Result<(), vosk::Error> {
+/// let model = Model::new("model")?;
+/// let recognizer = Recognizer::builder(&model, 44100.0)
+///     .strict_sample_rate(true)
+///     .words(true)
+///     .build();
+/// if let Err(vosk::Error::SampleRateMismatch { model, .. }) = recognizer {
+///     println!("resample the audio to {} Hz", model);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+#[must_use = "nothing is created until `build` is called"]
+pub struct RecognizerBuilder<'m> {
+    model: &'m Model,
+    sample_rate: f32,
+    strict_sample_rate: bool,
+    words: bool,
+    grammar: Option<Vec<Vec<String>>>,
+}
+
+impl Recognizer {
+    /// Starts building a recognizer for audio at `sample_rate`.
+    pub fn builder(model: &Model, sample_rate: f32) -> RecognizerBuilder<'_> {
+        RecognizerBuilder {
+            model,
+            sample_rate,
+            strict_sample_rate: false,
+            words: false,
+            grammar: None,
+        }
+    }
+}
+
+impl RecognizerBuilder<'_> {
+    /// Fails with `Error::SampleRateMismatch` if the model was trained on audio at
+    /// another rate, see `Model::sample_rate`.
+    ///
+    /// Otherwise a mismatch only logs a warning with the `tracing` feature, since
+    /// libvosk accepts the audio and recognizes it badly. Models that don't declare
+    /// their rate are accepted either way.
+    pub fn strict_sample_rate(mut self, strict: bool) -> Self {
+        self.strict_sample_rate = strict;
+        self
+    }
+    /// See `Recognizer::set_words`.
+    pub fn words(mut self, enable: bool) -> Self {
+        self.words = enable;
+        self
+    }
+    /// Restricts recognition to `phrases`, as `Recognizer::with_grammar`.
+    pub fn grammar<I, P, S>(mut self, phrases: I) -> Self
+    where
+        P: IntoIterator<Item = S>,
+        I: IntoIterator<Item = P>,
+        S: AsRef<str>,
+    {
+        let phrases = phrases
+            .into_iter()
+            .map(|phrase| {
+                phrase
+                    .into_iter()
+                    .map(|word| word.as_ref().to_string())
+                    .collect()
+            })
+            .collect();
+        self.grammar = Some(phrases);
+        self
+    }
+    /// Fails on an invalid sample rate, a mismatched one if strict, or a grammar
+    /// `Recognizer::try_with_grammar` rejects.
+    pub fn build(self) -> Result<Recognizer, Error> {
+        let rate = SampleRate::try_from(self.sample_rate)?;
+        if self.strict_sample_rate {
+            rate::check_model(self.model, rate.hz())?;
+        }
+        let mut recognizer = match self.grammar {
+            Some(phrases) => Recognizer::try_with_grammar(self.model, rate.hz(), phrases)?,
+            None => Recognizer::with_rate(self.model, rate),
+        };
+        recognizer.set_words(self.words);
+        Ok(recognizer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::fake_model;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// A model directory with only `conf/mfcc.conf`, holding `conf`.
+    fn model_dir(name: &str, conf: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vosk-builder-{}", name));
+        fs::create_dir_all(dir.join("conf")).unwrap();
+        fs::write(dir.join("conf/mfcc.conf"), conf).unwrap();
+        dir
+    }
+
+    #[test]
+    fn strict_rejects_mismatch() {
+        let dir = model_dir("strict", "--use-energy=false\n--sample-frequency=16000\n");
+        let model = fake_model(dir.to_str().unwrap());
+        assert_eq!(model.sample_rate(), Some(16000.0));
+        let built = Recognizer::builder(&model, 44100.0)
+            .strict_sample_rate(true)
+            .words(true)
+            .build();
+        let e = built.unwrap_err();
+        assert_eq!(
+            e,
+            Error::SampleRateMismatch {
+                model: 16000.0,
+                requested: 44100.0
+            }
+        );
+        assert_eq!(
+            e.to_string(),
+            "The model is for audio at 16000 Hz, not 44100 Hz"
+        );
+        // The rate itself is checked first.
+        let invalid = Recognizer::builder(&model, f32::NAN)
+            .strict_sample_rate(true)
+            .build();
+        assert!(matches!(invalid, Err(Error::InvalidSampleRate(_))));
+    }
+    #[test]
+    fn lenient_and_unknown() {
+        let dir = model_dir("lenient", "--sample-frequency=8000");
+        let model = fake_model(dir.to_str().unwrap());
+        // Without `strict_sample_rate` a mismatch only warns, when the recognizer is created.
+        assert_eq!(
+            rate::check_model(&model, 16000.0),
+            Err(Error::SampleRateMismatch {
+                model: 8000.0,
+                requested: 16000.0
+            })
+        );
+        assert_eq!(rate::check_model(&model, 8000.0), Ok(()));
+        let builder = Recognizer::builder(&model, 16000.0);
+        assert!(!builder.strict_sample_rate);
+
+        let undeclared = fake_model(
+            model_dir("undeclared", "--use-energy=false")
+                .to_str()
+                .unwrap(),
+        );
+        assert_eq!(undeclared.sample_rate(), None);
+        assert_eq!(rate::check_model(&undeclared, 44100.0), Ok(()));
+        let missing = fake_model("no/such/model");
+        assert_eq!(missing.sample_rate(), None);
+        assert_eq!(rate::check_model(&missing, 44100.0), Ok(()));
+    }
+    #[test]
+    fn grammar_words() {
+        let model = fake_model("model");
+        let builder = Recognizer::builder(&model, 16000.0)
+            .grammar(["yes", "no thanks"].iter().map(|p| p.split_whitespace()))
+            .words(true);
+        assert_eq!(
+            builder.grammar,
+            Some(vec![
+                vec!["yes".to_string()],
+                vec!["no".to_string(), "thanks".to_string()]
+            ])
+        );
+        assert!(builder.words);
+    }
+}
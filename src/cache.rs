@@ -0,0 +1,159 @@
+use crate::{Error, Model, ModelInner};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+
+/// Shares loaded models between independent parts of a program.
+///
+/// Models are looked up by canonical path. The cache doesn't keep models alive:
+/// once every clone of a model is dropped, the next request loads it again.
+#[derive(Debug)]
+pub struct ModelCache {
+    slots: Mutex<BTreeMap<PathBuf, Arc<Slot>>>,
+}
+
+/// The model of one path. Locked while it loads, so it's only loaded once.
+type Slot = Mutex<Weak<ModelInner>>;
+
+static GLOBAL: ModelCache = ModelCache::new();
+
+impl Model {
+    /// Returns the model at `path` if it's already loaded anywhere in the program
+    /// through this function, otherwise loads it.
+    pub fn cached<P: AsRef<Path>>(path: P) -> Result<Model, Error> {
+        GLOBAL.get(path)
+    }
+}
+
+impl ModelCache {
+    pub const fn new() -> ModelCache {
+        ModelCache {
+            slots: Mutex::new(BTreeMap::new()),
+        }
+    }
+    /// Returns a live model loaded from the same path, or loads it.
+    ///
+    /// If several threads ask for a model that isn't loaded,
+    /// one loads it and the others wait for it.
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Result<Model, Error> {
+        self.get_with(path.as_ref(), |path| Model::new(path))
+    }
+    fn get_with<F>(&self, path: &Path, load: F) -> Result<Model, Error>
+    where
+        F: FnOnce(&Path) -> Result<Model, Error>,
+    {
+        // Loading fails later if the path doesn't exist.
+        let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let slot = {
+            let mut slots = lock(&self.slots);
+            slots.retain(|_, slot| in_use(slot));
+            slots.entry(path.clone()).or_default().clone()
+        };
+        let mut model = lock(&slot);
+        if let Some(inner) = model.upgrade() {
+            return Ok(Model { inner });
+        }
+        let loaded = load(&path)?;
+        *model = Arc::downgrade(&loaded.inner);
+        Ok(loaded)
+    }
+}
+
+impl Default for ModelCache {
+    fn default() -> Self {
+        ModelCache::new()
+    }
+}
+
+/// A slot is kept while a thread holds it, or its model is alive.
+fn in_use(slot: &Arc<Slot>) -> bool {
+    Arc::strong_count(slot) > 1 || slot.try_lock().map_or(true, |m| m.strong_count() > 0)
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // A panic while loading leaves the slot empty, which is consistent.
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A model that doesn't need libvosk.
+    fn fake_model(path: &Path) -> Result<Model, Error> {
+        let inner = ModelInner {
+            ptr: std::ptr::null_mut(),
+            path: path.to_path_buf(),
+            memory_delta: None,
+            sample_rate: None,
+        };
+        Ok(Model {
+            inner: Arc::new(inner),
+        })
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "calls into libvosk")]
+    fn load_error() {
+        let cache = ModelCache::new();
+        assert!(matches!(
+            cache.get("not_existing"),
+            Err(Error::InvalidModel(_))
+        ));
+        assert!(matches!(
+            Model::cached("not_existing"),
+            Err(Error::InvalidModel(_))
+        ));
+    }
+    #[test]
+    fn reuse_live_model() {
+        let cache = ModelCache::new();
+        let loads = AtomicUsize::new(0);
+        let load = |path: &Path| {
+            loads.fetch_add(1, Ordering::SeqCst);
+            fake_model(path)
+        };
+        let a = cache.get_with(Path::new("model-a"), load).unwrap();
+        let b = cache.get_with(Path::new("model-a"), load).unwrap();
+        assert!(Arc::ptr_eq(&a.inner, &b.inner));
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+        drop((a, b));
+        let _a = cache.get_with(Path::new("model-a"), load).unwrap();
+        assert_eq!(loads.load(Ordering::SeqCst), 2);
+    }
+    #[test]
+    fn purge_dropped() {
+        let cache = ModelCache::new();
+        drop(cache.get_with(Path::new("model-a"), fake_model));
+        let _b = cache.get_with(Path::new("model-b"), fake_model).unwrap();
+        let slots = lock(&cache.slots);
+        assert_eq!(slots.keys().collect::<Vec<_>>(), vec![Path::new("model-b")]);
+    }
+    #[test]
+    fn one_load_per_path() {
+        let cache = ModelCache::new();
+        let loads = AtomicUsize::new(0);
+        let models: Vec<Model> = std::thread::scope(|s| {
+            let threads: Vec<_> = (0..16)
+                .map(|i| {
+                    let (cache, loads) = (&cache, &loads);
+                    s.spawn(move || {
+                        let path = format!("model-{}", i % 2);
+                        cache
+                            .get_with(Path::new(&path), |path| {
+                                loads.fetch_add(1, Ordering::SeqCst);
+                                std::thread::sleep(std::time::Duration::from_millis(20));
+                                fake_model(path)
+                            })
+                            .unwrap()
+                    })
+                })
+                .collect();
+            threads.into_iter().map(|t| t.join().unwrap()).collect()
+        });
+        assert_eq!(loads.load(Ordering::SeqCst), 2);
+        assert!(Arc::ptr_eq(&models[0].inner, &models[14].inner));
+        assert!(!Arc::ptr_eq(&models[0].inner, &models[1].inner));
+    }
+}
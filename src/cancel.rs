@@ -0,0 +1,154 @@
+use crate::source::AudioSource;
+use crate::Error;
+use std::fmt;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Stops a transcription that's taking too long, such as when the user gives up.
+///
+/// Clones share the same flag: keep one to call `cancel` on, from any thread,
+/// and give another to the helper running the transcription. Helpers check it
+/// between chunks of audio and fail with `Error::Cancelled`, dropping what was
+/// recognized so far.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Cancels the transcriptions using this token. There is no undoing it.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+    /// Fails with `Error::Cancelled` once cancelled.
+    pub fn check(&self) -> Result<(), Error> {
+        if self.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Two tokens are equal if they are clones of each other.
+impl PartialEq for CancellationToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.cancelled, &other.cancelled)
+    }
+}
+
+/// An audio source or reader that ends in an error once its token is cancelled.
+///
+/// The token is checked before and after each read, so a source that blocks
+/// for long in `read` delays cancellation by as much.
+#[derive(Debug)]
+pub struct Cancellable<S> {
+    inner: S,
+    token: CancellationToken,
+}
+
+impl<S> Cancellable<S> {
+    pub fn new(inner: S, token: CancellationToken) -> Self {
+        Cancellable { inner, token }
+    }
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: AudioSource> AudioSource for Cancellable<S> {
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+    /// Fails with `Error::Cancelled`, dropping samples read just as it was cancelled.
+    fn read(&mut self, buf: &mut [i16]) -> Result<usize, Error> {
+        self.token.check()?;
+        let n = self.inner.read(buf)?;
+        self.token.check()?;
+        Ok(n)
+    }
+    fn total_samples(&self) -> Option<u64> {
+        self.inner.total_samples()
+    }
+    fn bytes_read(&self) -> Option<u64> {
+        self.inner.bytes_read()
+    }
+}
+
+impl<R: Read> Read for Cancellable<R> {
+    /// Fails with an error that converts to `Error::Cancelled`.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.token.is_cancelled() {
+            return Err(cancelled());
+        }
+        let n = self.inner.read(buf)?;
+        if self.token.is_cancelled() {
+            return Err(cancelled());
+        }
+        Ok(n)
+    }
+}
+
+/// Marks the IO errors of cancelled reads, so that they convert back to `Error::Cancelled`.
+#[derive(Debug)]
+pub(crate) struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+fn cancelled() -> io::Error {
+    io::Error::other(Cancelled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::MemorySource;
+
+    #[test]
+    fn clones_share_the_flag() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert_eq!(token.check(), Ok(()));
+        clone.cancel();
+        assert!(token.is_cancelled());
+        assert_eq!(token.check(), Err(Error::Cancelled));
+        assert_eq!(token, clone);
+        assert_ne!(token, CancellationToken::new());
+    }
+    #[test]
+    fn cancel_source() {
+        let token = CancellationToken::new();
+        let mut source = Cancellable::new(MemorySource::new(vec![1; 10], 16000), token.clone());
+        let mut buf = [0; 4];
+        assert_eq!(source.read(&mut buf), Ok(4));
+        token.cancel();
+        assert_eq!(source.read(&mut buf), Err(Error::Cancelled));
+        assert_eq!(source.sample_rate(), 16000);
+    }
+    #[test]
+    fn cancel_reader() {
+        let token = CancellationToken::new();
+        let mut reader = Cancellable::new(&[1u8, 2, 3][..], token.clone());
+        let mut buf = [0; 2];
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        token.cancel();
+        let e = reader.read(&mut buf).unwrap_err();
+        assert_eq!(Error::from(e), Error::Cancelled);
+        // Other IO errors convert as before.
+        let other = io::Error::other("disk on fire");
+        assert_eq!(Error::from(other), Error::Io("disk on fire".into()));
+    }
+}
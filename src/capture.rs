@@ -0,0 +1,96 @@
+use crate::Recognizer;
+use std::collections::VecDeque;
+use std::ffi::CStr;
+
+/// Number of outputs kept unless set otherwise.
+pub const DEFAULT_CAPTURE_CAPACITY: usize = 32;
+
+/// The latest JSON strings returned by libvosk, oldest first.
+#[derive(Debug, Clone)]
+pub(crate) struct RawCapture {
+    entries: VecDeque<String>,
+    capacity: usize,
+}
+
+impl RawCapture {
+    pub(crate) fn new(capacity: usize) -> RawCapture {
+        RawCapture {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+    pub(crate) fn record(&mut self, raw: &CStr) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries
+            .push_back(String::from_utf8_lossy(raw.to_bytes()).into_owned());
+    }
+    fn set_capacity(&mut self, capacity: usize) {
+        let excess = self.entries.len().saturating_sub(capacity);
+        self.entries.drain(..excess);
+        self.capacity = capacity;
+    }
+}
+
+impl Recognizer {
+    /// The raw JSON of the latest results and partial results, oldest first,
+    /// to attach to bug reports about parsing.
+    ///
+    /// Invalid UTF-8 is replaced, see `result_bytes` for the exact bytes.
+    pub fn recent_raw_results(&self) -> Vec<String> {
+        self.raw_capture.entries.iter().cloned().collect()
+    }
+    /// Sets how many raw results are kept, [`DEFAULT_CAPTURE_CAPACITY`] by default.
+    /// With 0, nothing is kept.
+    pub fn set_raw_capture_capacity(&mut self, capacity: usize) {
+        self.raw_capture.set_capacity(capacity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    fn record(capture: &mut RawCapture, text: &str) {
+        capture.record(&CString::new(text).unwrap());
+    }
+    fn entries(capture: &RawCapture) -> Vec<&str> {
+        capture.entries.iter().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn keeps_latest() {
+        let mut capture = RawCapture::new(3);
+        for text in ["a", "b", "c", "d", "e"] {
+            record(&mut capture, text);
+        }
+        assert_eq!(entries(&capture), vec!["c", "d", "e"]);
+    }
+    #[test]
+    fn change_capacity() {
+        let mut capture = RawCapture::new(4);
+        for text in ["a", "b", "c", "d"] {
+            record(&mut capture, text);
+        }
+        capture.set_capacity(2);
+        assert_eq!(entries(&capture), vec!["c", "d"]);
+        capture.set_capacity(3);
+        record(&mut capture, "e");
+        record(&mut capture, "f");
+        assert_eq!(entries(&capture), vec!["d", "e", "f"]);
+        capture.set_capacity(0);
+        record(&mut capture, "g");
+        assert!(entries(&capture).is_empty());
+    }
+    #[test]
+    fn invalid_utf8() {
+        let mut capture = RawCapture::new(1);
+        capture.record(&CString::new(b"caf\xe9".to_vec()).unwrap());
+        assert_eq!(entries(&capture), vec!["caf\u{fffd}"]);
+    }
+}
@@ -0,0 +1,916 @@
+//! Voice commands: recognizing a fixed set of phrases and mapping them to values.
+//!
+//! ```no_run
+//! # use vosk::{command::CommandSet, Model};
+//! #[derive(Clone, Debug)]
+//! enum Action {
+//!     LightsOn,
+//!     VolumeUp,
+//! }
+//! # fn main() -> Result<(), vosk::Error> {
+//! # let model = Model::new("model")?;
+//! let mut commands = CommandSet::builder()
+//!     .command("lights on", Action::LightsOn)
+//!     .command_phrases(["volume up", "louder"], Action::VolumeUp)
+//!     .build(&model, 16000.0)?;
+//! # let samples = [0; 1600];
+//! if let Some(hit) = commands.feed(&samples) {
+//!     println!("{:?}", hit.value);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! A phrase can have slots for numbers, written `{number}`, or `{name:number}` to name
+//! the slot: "set temperature to {number}" matches "set temperature to twenty one",
+//! with 21 in the `slots` of the hit. See `intent` for output that home automation
+//! software understands.
+//!
+//! With the `derive` feature, the phrases can be written on the variants of an enum,
+//! and slots read into their fields:
+//!
+//! ```ignore
+//! use vosk::command::VoiceCommands;
+//!
+//! #[derive(VoiceCommands)]
+//! enum Cmd {
+//!     #[phrase("turn on the lights")]
+//!     LightsOn,
+//!     #[phrase("set volume to {n:number}")]
+//!     #[phrase("volume {n:number}")]
+//!     SetVolume { n: u32 },
+//! }
+//!
+//! let mut commands = Cmd::command_set().build(&model, 16000.0)?;
+//! if let Some(Cmd::SetVolume { n }) = commands.feed(&samples).and_then(|hit| Cmd::from_hit(&hit)) {
+//!     println!("volume {}", n);
+//! }
+//! ```
+
+use crate::presets::{numbers_grammar, parse_number, Lang};
+use crate::segment::TimeRange;
+use crate::{
+    Error, Grammar, Model, OovError, RecognizedText, RecognizedWord, Recognizer, SampleRate,
+};
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::Range;
+
+/// Collects the phrases of a `CommandSet`.
+#[derive(Debug, Clone)]
+pub struct CommandSetBuilder<T> {
+    table: CommandTable<T>,
+}
+
+impl<T: Clone> CommandSetBuilder<T> {
+    /// Maps `phrase` to `value`. A phrase added again maps to the latest value.
+    ///
+    /// Slots in the phrase are filled with what was said in their place. A word in
+    /// braces that isn't a slot fails to build as out of the vocabulary.
+    pub fn command(self, phrase: &str, value: T) -> Self {
+        self.command_phrases([phrase], value)
+    }
+    /// Maps each of `phrases` to `value`.
+    pub fn command_phrases<I, S>(mut self, phrases: I, value: T) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        for phrase in phrases {
+            let phrase = normalize(phrase.as_ref());
+            if !phrase.is_empty() {
+                self.table.commands.retain(|(p, _)| *p != phrase);
+                self.table.commands.push((phrase, value.clone()));
+            }
+        }
+        self
+    }
+    /// Also accepts results that are close to a phrase, as judged by `matcher`,
+    /// such as "turn [unk] the lights" for "turn off the lights".
+    pub fn fuzzy(mut self, matcher: FuzzyMatcher) -> Self {
+        self.table.fuzzy = Some(matcher);
+        self
+    }
+    /// Creates a recognizer restricted to the phrases.
+    ///
+    /// Fails with `Error::OutOfVocabulary` if the model doesn't know some of the words,
+    /// with `Error::EmptyGrammar` if no command has a phrase that isn't blank,
+    /// and with `Error::InvalidSampleRate` unless the rate is finite and positive.
+    pub fn build(self, model: &Model, sample_rate: f32) -> Result<CommandSet<T>, Error> {
+        if self.table.commands.is_empty() {
+            return Err(Error::EmptyGrammar);
+        }
+        let rate = SampleRate::try_from(sample_rate)?;
+        Ok(self.build_validated(model, rate)?)
+    }
+    /// Like `build`, but tells which phrase each unknown word is in.
+    ///
+    /// The rate is checked beforehand, with `SampleRate::try_from`.
+    pub fn build_validated(
+        self,
+        model: &Model,
+        sample_rate: SampleRate,
+    ) -> Result<CommandSet<T>, OovError> {
+        // With "[unk]", other speech is recognized as unknown
+        // instead of as the command that sounds closest.
+        let grammar = Grammar::new(self.table.grammar_phrases())
+            .build_validated(model)?
+            .with_unknown();
+        // The rate is valid, "[unk]" keeps the grammar from being empty,
+        // and a word with a NUL byte isn't known to the model.
+        let mut recognizer = Recognizer::try_with_grammar(model, sample_rate.hz(), &grammar)
+            .expect("a validated grammar is accepted");
+        recognizer.set_words(true);
+        Ok(CommandSet {
+            recognizer,
+            table: self.table,
+        })
+    }
+}
+
+/// A recognizer for a fixed set of phrases, each standing for a value of `T`.
+#[derive(Debug)]
+pub struct CommandSet<T> {
+    recognizer: Recognizer,
+    table: CommandTable<T>,
+}
+
+/// Phrases and their values, in the order they were added.
+#[derive(Debug, Clone)]
+struct CommandTable<T> {
+    commands: Vec<(String, T)>,
+    fuzzy: Option<FuzzyMatcher>,
+}
+
+/// A command that was spoken.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandHit<T> {
+    pub value: T,
+    /// The phrase of the command.
+    pub phrase: String,
+    /// Similarity of the result to the phrase, 1 for an exact match.
+    pub score: f32,
+    /// Mean confidence of the words.
+    pub confidence: Option<f32>,
+    /// When the phrase was spoken, in seconds since the recognizer started.
+    pub range: Option<TimeRange>,
+    /// What was recognized, with uniform whitespace.
+    pub text: String,
+    /// The slots of the phrase in order, filled in.
+    pub slots: Vec<Slot>,
+}
+
+/// A slot of a command phrase, as it was filled in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Slot {
+    /// "number" for `{number}`, "degrees" for `{degrees:number}`.
+    pub name: String,
+    /// The words said in its place.
+    pub raw_value: String,
+    pub value: SlotValue,
+    /// Mean confidence of the words.
+    pub confidence: Option<f32>,
+    /// When the words were spoken.
+    pub range: Option<TimeRange>,
+}
+
+/// The value of a filled-in slot.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum SlotValue {
+    /// A whole number, read with `presets::parse_number`.
+    Number(i64),
+}
+
+impl SlotValue {
+    /// The kind of slot, as written in the phrase, such as "number".
+    pub fn entity(&self) -> &'static str {
+        match self {
+            SlotValue::Number(_) => "number",
+        }
+    }
+}
+
+impl fmt::Display for SlotValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SlotValue::Number(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+#[cfg(feature = "derive")]
+pub use vosk_derive::VoiceCommands;
+
+/// Commands defined by the variants of an enum, see `#[derive(VoiceCommands)]`.
+///
+/// The set is an ordinary `CommandSet` mapping phrases to the index of their variant,
+/// from which `from_hit` builds the command.
+pub trait VoiceCommands: Sized {
+    /// Every phrase, with the index of its variant.
+    const PHRASES: &'static [(&'static str, usize)];
+    /// The variant at `index`, with its fields read from the slots of the same name.
+    ///
+    /// None if a slot is missing or doesn't fit its field.
+    fn from_slots(index: usize, slots: &[Slot]) -> Option<Self>;
+    /// A builder with all the phrases.
+    fn command_set() -> CommandSetBuilder<usize> {
+        Self::PHRASES
+            .iter()
+            .fold(CommandSet::builder(), |builder, &(phrase, index)| {
+                builder.command(phrase, index)
+            })
+    }
+    /// The command spoken, from a set built with `command_set`.
+    fn from_hit(hit: &CommandHit<usize>) -> Option<Self> {
+        Self::from_slots(hit.value, &hit.slots)
+    }
+}
+
+/// A type a filled-in slot can be read as, such as the fields of `VoiceCommands`.
+pub trait FromSlot: Sized {
+    fn from_slot(slot: &Slot) -> Option<Self>;
+}
+
+macro_rules! from_number_slot {
+    ($($t:ty),*) => {$(
+        impl FromSlot for $t {
+            /// None if the number doesn't fit.
+            fn from_slot(slot: &Slot) -> Option<Self> {
+                match slot.value {
+                    SlotValue::Number(n) => <$t>::try_from(n).ok(),
+                }
+            }
+        }
+    )*};
+}
+
+from_number_slot!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+impl FromSlot for f64 {
+    fn from_slot(slot: &Slot) -> Option<Self> {
+        match slot.value {
+            SlotValue::Number(n) => Some(n as f64),
+        }
+    }
+}
+
+impl FromSlot for SlotValue {
+    fn from_slot(slot: &Slot) -> Option<Self> {
+        Some(slot.value.clone())
+    }
+}
+
+impl FromSlot for String {
+    /// The words said, such as "twenty one".
+    fn from_slot(slot: &Slot) -> Option<Self> {
+        Some(slot.raw_value.clone())
+    }
+}
+
+impl FromSlot for Slot {
+    fn from_slot(slot: &Slot) -> Option<Self> {
+        Some(slot.clone())
+    }
+}
+
+impl<T> CommandSet<T> {
+    pub fn builder() -> CommandSetBuilder<T> {
+        CommandSetBuilder {
+            table: CommandTable {
+                commands: Vec::new(),
+                fuzzy: None,
+            },
+        }
+    }
+    pub fn recognizer(&self) -> &Recognizer {
+        &self.recognizer
+    }
+    pub fn recognizer_mut(&mut self) -> &mut Recognizer {
+        &mut self.recognizer
+    }
+}
+
+impl<T: Clone> CommandSet<T> {
+    /// Feeds audio, returning the command when an utterance ends with one.
+    ///
+    /// Utterances that aren't one of the phrases, such as unknown speech, are skipped.
+    /// Only exact matches count, unless a `FuzzyMatcher` was given to the builder.
+    pub fn feed(&mut self, wave: &[i16]) -> Option<CommandHit<T>> {
+        if !self.recognizer.accept_waveform(wave) {
+            return None;
+        }
+        self.table.lookup(&self.recognizer.result())
+    }
+    /// Returns the command at the end of the audio, if the last utterance is one.
+    pub fn finish(&mut self) -> Option<CommandHit<T>> {
+        self.table.lookup(&self.recognizer.final_result())
+    }
+}
+
+impl<T: Clone> CommandTable<T> {
+    /// The phrases without slots, with their positions in `commands`.
+    fn plain_phrases(&self) -> impl Iterator<Item = (usize, &str)> {
+        let phrases = self.commands.iter().map(|(p, _)| p.as_str()).enumerate();
+        phrases.filter(|(_, p)| !has_slots(p))
+    }
+    /// What the recognizer listens for. The words around slots are phrases of their own
+    /// next to the words of numbers, since a grammar allows any sequence of its phrases.
+    fn grammar_phrases(&self) -> Vec<String> {
+        let mut phrases = Vec::new();
+        let mut slots = false;
+        for (phrase, _) in &self.commands {
+            let mut run: Vec<&str> = Vec::new();
+            for part in parts(phrase) {
+                match part {
+                    Part::Word(word) => run.push(word),
+                    Part::Slot(_) => {
+                        slots = true;
+                        phrases.extend((!run.is_empty()).then(|| run.join(" ")));
+                        run.clear();
+                    }
+                }
+            }
+            phrases.extend((!run.is_empty()).then(|| run.join(" ")));
+        }
+        if slots {
+            phrases.extend(numbers_grammar(Lang::English).phrases().iter().cloned());
+        }
+        phrases
+    }
+    fn lookup(&self, result: &RecognizedText) -> Option<CommandHit<T>> {
+        let text = normalize(&result.text);
+        let words: Vec<&str> = text.split(' ').filter(|w| !w.is_empty()).collect();
+        let exact = self
+            .commands
+            .iter()
+            .enumerate()
+            .find_map(|(index, (phrase, _))| {
+                if *phrase == text {
+                    return Some((index, Vec::new()));
+                }
+                fill(&parts(phrase), &words).map(|filled| (index, filled))
+            });
+        let (index, score, filled) = match exact {
+            Some((index, filled)) => (index, 1.0, filled),
+            None => {
+                let (indices, phrases): (Vec<usize>, Vec<&str>) = self.plain_phrases().unzip();
+                let found = self.fuzzy.as_ref()?.find_result(result, phrases)?;
+                (indices[found.index], found.score, Vec::new())
+            }
+        };
+        let (phrase, value) = self.commands[index].clone();
+        let details = result.words();
+        // Times are only known if the words line up with the text.
+        let details = if details.len() == words.len() {
+            details
+        } else {
+            &[]
+        };
+        let slots = parts(&phrase)
+            .into_iter()
+            .filter_map(|part| match part {
+                Part::Slot(name) => Some(name),
+                Part::Word(_) => None,
+            })
+            .zip(filled)
+            .map(|(name, range)| {
+                let raw_value = words[range.clone()].join(" ");
+                let in_slot = details.get(range).unwrap_or_default();
+                Slot {
+                    name: name.to_string(),
+                    value: SlotValue::Number(parse_number(&raw_value).unwrap_or_default()),
+                    raw_value,
+                    confidence: mean_confidence(in_slot),
+                    range: time_range(in_slot),
+                }
+            })
+            .collect();
+        Some(CommandHit {
+            value,
+            phrase,
+            score,
+            confidence: result.mean_confidence(),
+            range: time_range(result.words()),
+            text,
+            slots,
+        })
+    }
+}
+
+/// A word of a command phrase, or a slot with its name.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Part<'a> {
+    Word(&'a str),
+    Slot(&'a str),
+}
+
+fn parts(phrase: &str) -> Vec<Part<'_>> {
+    let part = |word| match slot_name(word) {
+        Some(name) => Part::Slot(name),
+        None => Part::Word(word),
+    };
+    phrase.split_whitespace().map(part).collect()
+}
+
+fn has_slots(phrase: &str) -> bool {
+    phrase
+        .split_whitespace()
+        .any(|word| slot_name(word).is_some())
+}
+
+/// "number" for `{number}` and `{number:number}`, "degrees" for `{degrees:number}`.
+fn slot_name(word: &str) -> Option<&str> {
+    let inner = word.strip_prefix('{')?.strip_suffix('}')?;
+    match inner.split_once(':') {
+        None if inner == "number" => Some(inner),
+        Some((name, "number")) if !name.is_empty() => Some(name),
+        _ => None,
+    }
+}
+
+/// Which of `words` fill each slot of `phrase`, None if they don't match it.
+pub(crate) fn slot_words(phrase: &str, words: &[&str]) -> Option<Vec<Range<usize>>> {
+    fill(&parts(phrase), words)
+}
+
+/// Which of `words` fill each slot, if they match the phrase, trying longer
+/// numbers first. A phrase without slots matches itself only.
+fn fill(parts: &[Part], words: &[&str]) -> Option<Vec<Range<usize>>> {
+    let mut filled = Vec::new();
+    if parts.iter().any(|p| matches!(p, Part::Slot(_))) && fill_from(parts, words, 0, &mut filled) {
+        Some(filled)
+    } else {
+        None
+    }
+}
+
+fn fill_from(parts: &[Part], words: &[&str], start: usize, filled: &mut Vec<Range<usize>>) -> bool {
+    match parts.split_first() {
+        None => start == words.len(),
+        Some((Part::Word(word), rest)) => {
+            words.get(start) == Some(word) && fill_from(rest, words, start + 1, filled)
+        }
+        Some((Part::Slot(_), rest)) => {
+            for end in (start + 1..=words.len()).rev() {
+                if parse_number(&words[start..end].join(" ")).is_none() {
+                    continue;
+                }
+                filled.push(start..end);
+                if fill_from(rest, words, end, filled) {
+                    return true;
+                }
+                filled.pop();
+            }
+            false
+        }
+    }
+}
+
+fn time_range(words: &[RecognizedWord]) -> Option<TimeRange> {
+    match (words.first(), words.last()) {
+        (Some(first), Some(last)) => Some(TimeRange {
+            start: first.start(),
+            end: last.end(),
+        }),
+        _ => None,
+    }
+}
+
+fn mean_confidence(words: &[RecognizedWord]) -> Option<f32> {
+    if words.is_empty() {
+        return None;
+    }
+    Some(words.iter().map(|w| w.conf()).sum::<f32>() / words.len() as f32)
+}
+
+/// Finds the phrase closest to recognized text, comparing words.
+///
+/// The similarity of a text to a phrase is 1 minus their word-level edit distance
+/// divided by the number of words of the longer one: 1 for the same words, 0 for nothing
+/// in common. Words are compared as they are, so "[unk]" never matches.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FuzzyMatcher {
+    threshold: f32,
+}
+
+/// How close a text is to one of the phrases.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FuzzyMatch {
+    /// Position of the phrase in the list.
+    pub index: usize,
+    /// Number of words inserted, deleted or substituted.
+    pub distance: usize,
+    /// Similarity from 0 to 1.
+    pub score: f32,
+    /// Sum of the confidences of the recognized words that match the phrase.
+    kept_confidence: f32,
+}
+
+impl FuzzyMatcher {
+    /// Accepts phrases with a similarity of at least `threshold`, between 0 and 1.
+    ///
+    /// With 0.75, one word in four may be wrong.
+    pub fn new(threshold: f32) -> FuzzyMatcher {
+        FuzzyMatcher { threshold }
+    }
+    pub fn threshold(&self) -> f32 {
+        self.threshold
+    }
+    /// The phrase closest to `text`, if it's similar enough.
+    pub fn find<'p, I>(&self, text: &str, phrases: I) -> Option<FuzzyMatch>
+    where
+        I: IntoIterator<Item = &'p str>,
+    {
+        self.accept(closest(text, phrases))
+    }
+    /// Like `find`, breaking ties between equally similar phrases in favour of the one
+    /// that agrees with the words recognized with the most confidence.
+    pub fn find_result<'p, I>(&self, result: &RecognizedText, phrases: I) -> Option<FuzzyMatch>
+    where
+        I: IntoIterator<Item = &'p str>,
+    {
+        self.accept(closest_result(result, phrases))
+    }
+    fn accept(&self, found: Option<FuzzyMatch>) -> Option<FuzzyMatch> {
+        found.filter(|m| m.score >= self.threshold)
+    }
+}
+
+/// The phrase closest to `text` however far it is, to log near misses.
+/// Ties go to the phrase listed first; None without phrases.
+pub fn closest<'p, I>(text: &str, phrases: I) -> Option<FuzzyMatch>
+where
+    I: IntoIterator<Item = &'p str>,
+{
+    let words: Vec<(&str, f32)> = text.split_whitespace().map(|w| (w, 1.0)).collect();
+    best(&words, phrases)
+}
+
+/// Like `closest`, with the confidences of the words of `result` breaking ties.
+pub fn closest_result<'p, I>(result: &RecognizedText, phrases: I) -> Option<FuzzyMatch>
+where
+    I: IntoIterator<Item = &'p str>,
+{
+    let words: Vec<(&str, f32)> = match &result.result {
+        Some(words) => words.iter().map(|w| (w.word(), w.conf())).collect(),
+        None => result.text.split_whitespace().map(|w| (w, 1.0)).collect(),
+    };
+    best(&words, phrases)
+}
+
+fn best<'p, I>(words: &[(&str, f32)], phrases: I) -> Option<FuzzyMatch>
+where
+    I: IntoIterator<Item = &'p str>,
+{
+    let mut best: Option<FuzzyMatch> = None;
+    for (index, phrase) in phrases.into_iter().enumerate() {
+        let phrase: Vec<&str> = phrase.split_whitespace().collect();
+        let (distance, kept_confidence) = edit_distance(words, &phrase);
+        let longest = words.len().max(phrase.len());
+        let score = if longest == 0 {
+            1.0
+        } else {
+            1.0 - distance as f32 / longest as f32
+        };
+        let candidate = FuzzyMatch {
+            index,
+            distance,
+            score,
+            kept_confidence,
+        };
+        let better = best.is_none_or(|b| {
+            score > b.score || (score == b.score && kept_confidence > b.kept_confidence)
+        });
+        if better {
+            best = Some(candidate);
+        }
+    }
+    best
+}
+
+/// Number of words inserted, deleted or substituted to turn `a` into `b`.
+pub fn word_distance(a: &[&str], b: &[&str]) -> usize {
+    let a: Vec<(&str, f32)> = a.iter().map(|&w| (w, 0.0)).collect();
+    edit_distance(&a, b).0
+}
+
+/// Levenshtein distance over words, and among the edit scripts of that length,
+/// the highest sum of confidences of the words of `a` that are kept.
+fn edit_distance(a: &[(&str, f32)], b: &[&str]) -> (usize, f32) {
+    // Costs between the first i words of `a` and the first j words of `b`,
+    // keeping rows i - 1 and i.
+    let mut previous: Vec<(usize, f32)> = (0..=b.len()).map(|j| (j, 0.0)).collect();
+    let mut current = Vec::with_capacity(b.len() + 1);
+    for (i, &(word, conf)) in a.iter().enumerate() {
+        current.clear();
+        current.push((i + 1, 0.0));
+        for (j, other) in b.iter().enumerate() {
+            let (d, c) = previous[j];
+            let diagonal = if word == *other {
+                (d, c + conf)
+            } else {
+                (d + 1, c)
+            };
+            let deleted = (previous[j + 1].0 + 1, previous[j + 1].1);
+            let inserted = (current[j].0 + 1, current[j].1);
+            current.push(cheaper(cheaper(diagonal, deleted), inserted));
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// Fewer edits first, then more confidence kept; `a` on a tie.
+fn cheaper(a: (usize, f32), b: (usize, f32)) -> (usize, f32) {
+    if b.0 < a.0 || (b.0 == a.0 && b.1 > a.1) {
+        b
+    } else {
+        a
+    }
+}
+
+/// Makes whitespace uniform. Case is kept, as models differ in it.
+fn normalize(phrase: &str) -> String {
+    phrase.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::utterance;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Action {
+        LightsOn,
+        LightsOff,
+        VolumeUp,
+        Stop,
+    }
+
+    fn builder() -> CommandSetBuilder<Action> {
+        CommandSet::builder()
+            .command("turn  on the lights", Action::LightsOn)
+            .command("turn off the lights", Action::LightsOff)
+            .command_phrases(["volume up", "louder"], Action::VolumeUp)
+            .command("stop", Action::VolumeUp)
+            .command("stop", Action::Stop)
+            .command(" ", Action::Stop)
+    }
+    fn words(d: &str, t: &str) -> usize {
+        let a: Vec<&str> = d.split_whitespace().collect();
+        let b: Vec<&str> = t.split_whitespace().collect();
+        word_distance(&a, &b)
+    }
+    /// A result with the given words and confidences.
+    fn heard(words: &[(&str, f32)]) -> RecognizedText<'static> {
+        let words = words
+            .iter()
+            .enumerate()
+            .map(|(i, &(w, conf))| {
+                RecognizedWord::new(w.to_string(), conf, i as f32, i as f32 + 1.0)
+            })
+            .collect();
+        RecognizedText::from_words(words)
+    }
+
+    #[test]
+    fn mapped_phrases() {
+        let table = builder().table;
+        assert_eq!(table.commands.len(), 5);
+        let spoken = &[
+            ("turn", 1.0, 1.2),
+            ("on", 1.2, 1.3),
+            ("the", 1.3, 1.4),
+            ("lights", 1.4, 1.8),
+        ];
+        assert_eq!(
+            table.lookup(&utterance(spoken)),
+            Some(CommandHit {
+                value: Action::LightsOn,
+                phrase: "turn on the lights".to_string(),
+                score: 1.0,
+                confidence: Some(1.0),
+                range: Some(TimeRange {
+                    start: 1.0,
+                    end: 1.8
+                }),
+                text: "turn on the lights".to_string(),
+                slots: Vec::new(),
+            })
+        );
+        let hit = table.lookup(&utterance(&[("louder", 0.5, 0.9)])).unwrap();
+        assert_eq!(hit.value, Action::VolumeUp);
+        assert_eq!(hit.range.map(|r| (r.start, r.end)), Some((0.5, 0.9)));
+        let hit = table.lookup(&RecognizedText::from_text(" stop ")).unwrap();
+        assert_eq!((hit.value, hit.range), (Action::Stop, None));
+    }
+    #[test]
+    fn unknown_speech() {
+        let table = builder().table;
+        assert_eq!(table.lookup(&utterance(&[("[unk]", 0.0, 0.5)])), None);
+        let partly = utterance(&[("volume", 0.0, 0.3), ("[unk]", 0.3, 0.6)]);
+        assert_eq!(table.lookup(&partly), None);
+        assert_eq!(table.lookup(&RecognizedText::from_text("")), None);
+        assert_eq!(table.lookup(&RecognizedText::from_text("volume")), None);
+    }
+    #[test]
+    fn distances() {
+        assert_eq!(words("", ""), 0);
+        assert_eq!(words("a b c", ""), 3);
+        assert_eq!(words("", "a b"), 2);
+        assert_eq!(words("a b c", "a b c"), 0);
+        assert_eq!(words("a x c", "a b c"), 1);
+        assert_eq!(words("a c", "a b c"), 1);
+        assert_eq!(words("a b c d", "a c d"), 1);
+        assert_eq!(words("b a", "a b"), 2);
+        assert_eq!(words("kitten sitting", "sitting kitten on"), 2);
+        assert_eq!(words("turn of the lights", "turn off the lights"), 1);
+        // Words, not characters: "lights" and "light" differ entirely.
+        assert_eq!(words("the light", "the lights"), 1);
+    }
+    #[test]
+    fn closest_phrases() {
+        let phrases = ["turn on the lights", "turn off the lights", "stop"];
+        let found = closest("turn of the lights", phrases).unwrap();
+        // Equally far from both, the first is taken.
+        assert_eq!((found.index, found.distance, found.score), (0, 1, 0.75));
+        let found = closest("stop it", phrases).unwrap();
+        assert_eq!((found.index, found.score), (2, 0.5));
+        assert_eq!(closest("stop", []), None);
+        let matcher = FuzzyMatcher::new(0.75);
+        assert_eq!(matcher.find("stop it", phrases), None);
+        assert_eq!(
+            matcher
+                .find("please turn off the lights", phrases)
+                .map(|m| m.index),
+            Some(1)
+        );
+        assert_eq!(matcher.find("", phrases), None);
+    }
+    #[test]
+    fn ties_by_confidence() {
+        let phrases = ["turn on the lights", "turn off the lights"];
+        let on = heard(&[("turn", 0.9), ("on", 0.8), ("[unk]", 0.3), ("lights", 0.9)]);
+        let off = heard(&[("turn", 0.9), ("[unk]", 0.4), ("the", 0.2), ("lights", 0.9)]);
+        let matcher = FuzzyMatcher::new(0.7);
+        assert_eq!(matcher.find_result(&on, phrases).map(|m| m.index), Some(0));
+        // "on" and "off" both missing: a tie that favours neither.
+        assert_eq!(matcher.find_result(&off, phrases).map(|m| m.index), Some(0));
+        let unsure_on = heard(&[("turn", 0.9), ("on", 0.3), ("the", 0.9), ("lights", 0.9)]);
+        let sure_off = heard(&[("turn", 0.9), ("off", 0.9), ("the", 0.3), ("lights", 0.9)]);
+        assert_eq!(
+            closest_result(&unsure_on, phrases).map(|m| m.index),
+            Some(0)
+        );
+        assert_eq!(closest_result(&sure_off, phrases).map(|m| m.index), Some(1));
+    }
+    #[test]
+    fn ambiguous_input() {
+        // "the lights" is half of either phrase, too little for either.
+        let phrases = ["turn on the lights", "turn off the lights"];
+        let matcher = FuzzyMatcher::new(0.75);
+        assert_eq!(matcher.find("the lights", phrases), None);
+        let near = closest("the lights", phrases).unwrap();
+        assert_eq!((near.distance, near.score), (2, 0.5));
+        // Two competing words, one from each phrase, and confidence decides.
+        let both = heard(&[
+            ("turn", 0.9),
+            ("on", 0.4),
+            ("off", 0.8),
+            ("the", 0.9),
+            ("lights", 0.9),
+        ]);
+        let found = matcher.find_result(&both, phrases).unwrap();
+        assert_eq!((found.index, found.distance), (1, 1));
+        assert_eq!(found.score, 0.8);
+    }
+    #[test]
+    fn fuzzy_commands() {
+        let table = builder().fuzzy(FuzzyMatcher::new(0.75)).table;
+        let slipped = heard(&[("turn", 0.9), ("[unk]", 0.5), ("the", 0.9), ("lights", 0.9)]);
+        let hit = table.lookup(&slipped).unwrap();
+        assert_eq!((hit.value, hit.score), (Action::LightsOn, 0.75));
+        assert_eq!(hit.phrase, "turn on the lights");
+        assert_eq!(table.lookup(&heard(&[("[unk]", 0.5)])), None);
+    }
+    #[test]
+    fn number_slots() {
+        let table = CommandSet::builder()
+            .command("set temperature to {number}", "temperature")
+            .command("set a timer for {minutes:number} minutes", "timer")
+            .command("from {low:number} to {high:number}", "range")
+            .command("set temperature to warm", "warm")
+            .table;
+        let spoken = utterance(&[
+            ("set", 0.0, 0.2),
+            ("temperature", 0.2, 0.7),
+            ("to", 0.7, 0.8),
+            ("twenty", 0.8, 1.1),
+            ("one", 1.1, 1.4),
+        ]);
+        let hit = table.lookup(&spoken).unwrap();
+        assert_eq!(hit.value, "temperature");
+        assert_eq!(hit.text, "set temperature to twenty one");
+        assert_eq!(
+            hit.slots,
+            [Slot {
+                name: "number".to_string(),
+                raw_value: "twenty one".to_string(),
+                value: SlotValue::Number(21),
+                confidence: Some(1.0),
+                range: Some(TimeRange {
+                    start: 0.8,
+                    end: 1.4
+                }),
+            }]
+        );
+        let intent = crate::intent::Intent::from(hit);
+        assert_eq!(intent.text, "set temperature to 21");
+        let hit = table
+            .lookup(&RecognizedText::from_text(
+                "set a timer for one hundred and five minutes",
+            ))
+            .unwrap();
+        assert_eq!((hit.value, hit.slots.len()), ("timer", 1));
+        assert_eq!(hit.slots[0].name, "minutes");
+        assert_eq!(hit.slots[0].value, SlotValue::Number(105));
+        assert_eq!((hit.slots[0].range, hit.slots[0].confidence), (None, None));
+        let hit = table
+            .lookup(&RecognizedText::from_text("from two to twenty two"))
+            .unwrap();
+        let values: Vec<_> = hit
+            .slots
+            .iter()
+            .map(|s| (s.name.as_str(), &s.value))
+            .collect();
+        assert_eq!(
+            values,
+            [
+                ("low", &SlotValue::Number(2)),
+                ("high", &SlotValue::Number(22))
+            ]
+        );
+        // A phrase without slots still matches exactly.
+        let hit = table
+            .lookup(&RecognizedText::from_text("set temperature to warm"))
+            .unwrap();
+        assert_eq!((hit.value, hit.slots), ("warm", Vec::new()));
+    }
+    #[test]
+    fn unfilled_slots() {
+        let table = CommandSet::builder()
+            .command("set temperature to {number}", "temperature")
+            .command("stop", "stop")
+            .fuzzy(FuzzyMatcher::new(0.5))
+            .table;
+        for text in [
+            "set temperature to",
+            "set temperature to lights",
+            "set temperature to one twenty",
+            "set temperature to twenty one please",
+            "set temperature twenty one",
+        ] {
+            assert_eq!(
+                table.lookup(&RecognizedText::from_text(text)),
+                None,
+                "{}",
+                text
+            );
+        }
+        // Fuzzy matching only goes to phrases without slots.
+        let hit = table.lookup(&RecognizedText::from_text("stop it")).unwrap();
+        assert_eq!(hit.value, "stop");
+    }
+    #[test]
+    fn grammar_with_slots() {
+        let table = CommandSet::builder()
+            .command("set temperature to {number} degrees", 0)
+            .command("{number} percent", 1)
+            .command("play {color}", 2)
+            .table;
+        let phrases = table.grammar_phrases();
+        assert_eq!(
+            phrases[..4],
+            ["set temperature to", "degrees", "percent", "play {color}"]
+        );
+        assert!(phrases.iter().any(|p| p == "twenty"));
+        assert!(phrases.iter().any(|p| p == "hundred"));
+        assert_eq!(slot_name("{number}"), Some("number"));
+        assert_eq!(slot_name("{degrees:number}"), Some("degrees"));
+        assert_eq!(slot_name("{color}"), None);
+        assert_eq!(slot_name("{:number}"), None);
+        assert_eq!(slot_name("{number"), None);
+    }
+    #[test]
+    fn invalid_sample_rate() {
+        let model = crate::test_util::fake_model("model");
+        let built = builder().build(&model, f32::NAN);
+        assert!(matches!(built, Err(Error::InvalidSampleRate(hz)) if hz.is_nan()));
+        let blank = CommandSet::builder().command(" \t", Action::Stop);
+        assert_eq!(
+            blank.build(&model, 16000.0).unwrap_err(),
+            Error::EmptyGrammar
+        );
+    }
+}
@@ -0,0 +1,347 @@
+//! Recognizing speech in audio files of common formats,
+//! such as mp3, ogg, flac, m4a and wav.
+//!
+//! With the `audio-decode` feature, audio is decoded with symphonia and mixed down to mono.
+//! It's fed at its original sample rate; Kaldi resamples it to the rate of the model.
+//! With the `ffmpeg-cli` feature, `via_ffmpeg` has the ffmpeg program decode formats
+//! symphonia doesn't support. With the `http-source` feature, `transcribe_url` streams
+//! audio from a web server without saving it first.
+
+#[cfg(feature = "audio-decode")]
+use crate::progress::ignore_progress;
+#[cfg(feature = "audio-decode")]
+use crate::source::{transcribe_source_with_progress, AudioSource, FileSource};
+#[cfg(feature = "audio-decode")]
+use crate::{
+    CancellationToken, Error, Model, Progress, ProgressReporter, Recognizer, UtteranceOwned,
+};
+#[cfg(feature = "audio-decode")]
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+    sync::Arc,
+};
+#[cfg(feature = "audio-decode")]
+use symphonia::core::{
+    audio::SampleBuffer,
+    codecs::{Decoder, DecoderOptions, CODEC_TYPE_NULL},
+    errors::Error as SymphoniaError,
+    formats::{FormatOptions, FormatReader},
+    io::{MediaSource, MediaSourceStream},
+    meta::MetadataOptions,
+    probe::Hint,
+};
+
+#[cfg(feature = "ffmpeg-cli")]
+mod ffmpeg;
+#[cfg(feature = "http-source")]
+mod http;
+#[cfg(feature = "ffmpeg-cli")]
+pub use self::ffmpeg::{
+    via_ffmpeg, via_ffmpeg_cancellable, via_ffmpeg_with_progress, FfmpegOptions,
+};
+#[cfg(feature = "http-source")]
+pub(crate) use self::http::NetworkFailure;
+#[cfg(feature = "http-source")]
+pub use self::http::{
+    transcribe_url, transcribe_url_cancellable, transcribe_url_with_progress, HttpSource,
+    UrlOptions,
+};
+
+#[cfg(feature = "audio-decode")]
+/// Recognizes all speech in the audio file at `path`.
+///
+/// Returns the finalized utterances in order, with word details.
+pub fn transcribe_file<P: AsRef<Path>>(
+    model: &Model,
+    path: P,
+) -> Result<Vec<UtteranceOwned>, Error> {
+    transcribe_file_cancellable(model, path, &CancellationToken::new())
+}
+
+#[cfg(feature = "audio-decode")]
+/// Same as `transcribe_file`, but fails with `Error::Cancelled` once `cancel` is.
+///
+/// It's checked for every tenth of a second of audio, which takes
+/// a fraction of that to recognize.
+pub fn transcribe_file_cancellable<P: AsRef<Path>>(
+    model: &Model,
+    path: P,
+    cancel: &CancellationToken,
+) -> Result<Vec<UtteranceOwned>, Error> {
+    transcribe_file_with_progress(model, path, cancel, ignore_progress())
+}
+
+#[cfg(feature = "audio-decode")]
+/// Same as `transcribe_file_cancellable`, also reporting how far it got to `progress`,
+/// such as for a progress bar.
+///
+/// The total duration comes from the header of the file and is unknown for some formats.
+/// Bytes are counted in the file, a little ahead of the audio recognized.
+pub fn transcribe_file_with_progress<P, F>(
+    model: &Model,
+    path: P,
+    cancel: &CancellationToken,
+    progress: ProgressReporter<F>,
+) -> Result<Vec<UtteranceOwned>, Error>
+where
+    P: AsRef<Path>,
+    F: FnMut(Progress),
+{
+    cancel.check()?;
+    let source = FileSource::open(path)?;
+    let mut recognizer = Recognizer::new(model, source.sample_rate() as f32);
+    recognizer.set_words(true);
+    transcribe_source_with_progress(&mut recognizer, source, cancel, progress)
+}
+
+#[cfg(feature = "audio-decode")]
+/// Decodes the first audio track of a file into mono 16-bit samples.
+pub(crate) struct MonoDecoder {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    pub(crate) sample_rate: u32,
+    /// Length of the track from the header, if it has one.
+    pub(crate) total_samples: Option<u64>,
+    position: Arc<AtomicU64>,
+    interleaved: Option<SampleBuffer<i16>>,
+}
+
+#[cfg(feature = "audio-decode")]
+impl MonoDecoder {
+    pub(crate) fn open(path: &Path) -> Result<MonoDecoder, Error> {
+        let file = CountingFile::new(File::open(path)?);
+        let position = file.position.clone();
+        let mut hint = Hint::new();
+        if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+            hint.with_extension(extension);
+        }
+        MonoDecoder::from_source(Box::new(file), position, hint)
+    }
+
+    /// Decodes `source`, whose reads add up in `position`.
+    pub(crate) fn from_source(
+        source: Box<dyn MediaSource>,
+        position: Arc<AtomicU64>,
+        hint: Hint,
+    ) -> Result<MonoDecoder, Error> {
+        let stream = MediaSourceStream::new(source, Default::default());
+        let probed = symphonia::default::get_probe()
+            .format(
+                &hint,
+                stream,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .map_err(from_symphonia)?;
+        let format = probed.format;
+        let track = format
+            .tracks()
+            .iter()
+            .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+            .ok_or_else(|| Error::UnsupportedCodec("no audio track".to_string()))?;
+        let sample_rate = track
+            .codec_params
+            .sample_rate
+            .ok_or_else(|| Error::CorruptFile("unknown sample rate".to_string()))?;
+        let track_id = track.id;
+        let total_samples = track.codec_params.n_frames;
+        let decoder = symphonia::default::get_codecs()
+            .make(&track.codec_params, &DecoderOptions::default())
+            .map_err(from_symphonia)?;
+        Ok(MonoDecoder {
+            format,
+            decoder,
+            track_id,
+            sample_rate,
+            total_samples,
+            position,
+            interleaved: None,
+        })
+    }
+
+    /// How far into the file it has read, including some read ahead of decoding.
+    pub(crate) fn bytes_read(&self) -> u64 {
+        self.position.load(Ordering::Relaxed)
+    }
+
+    /// Replaces the content of `out` with the next decoded samples.
+    /// Returns false at the end of the file.
+    pub(crate) fn next_chunk(&mut self, out: &mut Vec<i16>) -> Result<bool, Error> {
+        out.clear();
+        while out.is_empty() {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(SymphoniaError::IoError(e))
+                    if e.kind() == std::io::ErrorKind::UnexpectedEof =>
+                {
+                    return Ok(false)
+                }
+                Err(e) => return Err(from_symphonia(e)),
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                // A damaged packet, skip it like players do.
+                Err(SymphoniaError::DecodeError(_)) => continue,
+                Err(e) => return Err(from_symphonia(e)),
+            };
+            let spec = *decoded.spec();
+            let capacity = decoded.capacity();
+            let interleaved = match &mut self.interleaved {
+                Some(buf) if buf.capacity() >= capacity * spec.channels.count() => buf,
+                buf => buf.insert(SampleBuffer::new(capacity as u64, spec)),
+            };
+            interleaved.copy_interleaved_ref(decoded);
+            crate::pcm::downmix(interleaved.samples(), spec.channels.count(), out);
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(feature = "audio-decode")]
+/// A file that keeps track of the position it was read to.
+struct CountingFile {
+    file: File,
+    position: Arc<AtomicU64>,
+}
+
+#[cfg(feature = "audio-decode")]
+impl CountingFile {
+    fn new(file: File) -> CountingFile {
+        CountingFile {
+            file,
+            position: Arc::new(AtomicU64::new(0)),
+        }
+    }
+}
+
+#[cfg(feature = "audio-decode")]
+impl Read for CountingFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.file.read(buf)?;
+        self.position.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+#[cfg(feature = "audio-decode")]
+impl Seek for CountingFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = self.file.seek(pos)?;
+        self.position.store(position, Ordering::Relaxed);
+        Ok(position)
+    }
+}
+
+#[cfg(feature = "audio-decode")]
+impl MediaSource for CountingFile {
+    fn is_seekable(&self) -> bool {
+        self.file.is_seekable()
+    }
+    fn byte_len(&self) -> Option<u64> {
+        self.file.byte_len()
+    }
+}
+
+#[cfg(feature = "audio-decode")]
+fn from_symphonia(e: SymphoniaError) -> Error {
+    match e {
+        SymphoniaError::Unsupported(what) => Error::UnsupportedCodec(what.to_string()),
+        SymphoniaError::IoError(e) => e.into(),
+        e => Error::CorruptFile(e.to_string()),
+    }
+}
+
+#[cfg(feature = "audio-decode")]
+#[cfg(test)]
+mod tests {
+    use super::MonoDecoder;
+    use crate::Error;
+    use std::path::PathBuf;
+
+    /// Writes a 16-bit PCM wav file to the temporary directory.
+    fn write_wav(name: &str, channels: u16, sample_rate: u32, samples: &[i16]) -> PathBuf {
+        let data_len = samples.len() as u32 * 2;
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&channels.to_le_bytes());
+        bytes.extend_from_slice(&sample_rate.to_le_bytes());
+        bytes.extend_from_slice(&(sample_rate * channels as u32 * 2).to_le_bytes());
+        bytes.extend_from_slice(&(channels * 2).to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_len.to_le_bytes());
+        for s in samples {
+            bytes.extend_from_slice(&s.to_le_bytes());
+        }
+        let path = std::env::temp_dir().join(format!("vosk-decode-{}.wav", name));
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn stereo_wav() {
+        let samples: Vec<i16> = (0..2000).map(|i| (i % 2 * 100) as i16).collect();
+        let path = write_wav("stereo", 2, 8000, &samples);
+        let mut decoder = MonoDecoder::open(&path).unwrap();
+        assert_eq!(decoder.sample_rate, 8000);
+        assert_eq!(decoder.total_samples, Some(1000));
+        let mut all = Vec::new();
+        let mut chunk = Vec::new();
+        while decoder.next_chunk(&mut chunk).unwrap() {
+            all.extend_from_slice(&chunk);
+        }
+        assert_eq!(all, vec![50; 1000]);
+        assert_eq!(decoder.bytes_read(), 44 + 4000);
+    }
+    /// Writes an MP3 file of `frames` silent MPEG-1 Layer III frames, mono at
+    /// 32 kHz and 32 kbps. Their side information is all zero, so each frame
+    /// holds no coded audio and decodes to 1152 samples of silence.
+    fn write_silent_mp3(name: &str, frames: usize) -> PathBuf {
+        let mut frame = [0; 144];
+        frame[..4].copy_from_slice(&[0xff, 0xfb, 0x18, 0xc0]);
+        let path = std::env::temp_dir().join(format!("vosk-decode-{}.mp3", name));
+        std::fs::write(&path, frame.repeat(frames)).unwrap();
+        path
+    }
+
+    #[test]
+    fn mp3() {
+        let path = write_silent_mp3("silence", 20);
+        let mut decoder = MonoDecoder::open(&path).unwrap();
+        assert_eq!(decoder.sample_rate, 32000);
+        let mut all = Vec::new();
+        let mut chunk = Vec::new();
+        while decoder.next_chunk(&mut chunk).unwrap() {
+            all.extend_from_slice(&chunk);
+        }
+        // Decoded by symphonia rather than read as PCM.
+        assert_eq!(decoder.total_samples, Some(20 * 1152));
+        assert_eq!(all, vec![0; 20 * 1152]);
+        assert_eq!(decoder.bytes_read(), 20 * 144);
+    }
+    #[test]
+    fn not_audio() {
+        let path = std::env::temp_dir().join("vosk-decode-text.txt");
+        std::fs::write(&path, "not audio at all").unwrap();
+        match MonoDecoder::open(&path) {
+            Err(Error::UnsupportedCodec(_)) => {}
+            other => panic!("unexpected {:?}", other.err()),
+        }
+    }
+    #[test]
+    fn missing_file() {
+        let path = std::env::temp_dir().join("vosk-decode-missing.wav");
+        assert!(matches!(MonoDecoder::open(&path), Err(Error::Io(_))));
+    }
+}
@@ -0,0 +1,267 @@
+use crate::progress::ignore_progress;
+use crate::{
+    Cancellable, CancellationToken, Error, Model, Progress, ProgressReporter, Recognizer,
+    UtteranceOwned,
+};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread::{self, JoinHandle};
+
+/// Most of the error output of ffmpeg kept for errors, from the end.
+const STDERR_LIMIT: usize = 16 * 1024;
+
+/// How `via_ffmpeg` runs ffmpeg and sets up the recognizer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FfmpegOptions {
+    /// The ffmpeg program, looked up in `PATH` unless it's a path.
+    pub program: PathBuf,
+    /// The rate ffmpeg resamples to, the one of the model is best.
+    pub sample_rate: u32,
+    /// Whether the utterances have word details.
+    pub words: bool,
+}
+
+impl Default for FfmpegOptions {
+    fn default() -> Self {
+        FfmpegOptions {
+            program: PathBuf::from("ffmpeg"),
+            sample_rate: 16000,
+            words: true,
+        }
+    }
+}
+
+/// Recognizes all speech in `input`, decoded by the ffmpeg program, which
+/// supports about any format, as well as URLs.
+///
+/// Returns the finalized utterances in order, leaving out empty ones.
+/// Fails with `Error::ProgramNotFound` if ffmpeg isn't installed, and with
+/// `Error::ProgramFailed` and the end of its error output if it fails.
+pub fn via_ffmpeg<P: AsRef<Path>>(
+    model: &Model,
+    input: P,
+    opts: &FfmpegOptions,
+) -> Result<Vec<UtteranceOwned>, Error> {
+    via_ffmpeg_cancellable(model, input, opts, &CancellationToken::new())
+}
+
+/// Same as `via_ffmpeg`, but kills ffmpeg and fails with `Error::Cancelled` once
+/// `cancel` is. It's checked whenever output of ffmpeg is read.
+pub fn via_ffmpeg_cancellable<P: AsRef<Path>>(
+    model: &Model,
+    input: P,
+    opts: &FfmpegOptions,
+    cancel: &CancellationToken,
+) -> Result<Vec<UtteranceOwned>, Error> {
+    via_ffmpeg_with_progress(model, input, opts, cancel, ignore_progress())
+}
+
+/// Same as `via_ffmpeg_cancellable`, also reporting how far it got to `progress`.
+///
+/// The total duration is never known, and the bytes are those of the samples
+/// ffmpeg has output.
+pub fn via_ffmpeg_with_progress<P, F>(
+    model: &Model,
+    input: P,
+    opts: &FfmpegOptions,
+    cancel: &CancellationToken,
+    mut progress: ProgressReporter<F>,
+) -> Result<Vec<UtteranceOwned>, Error>
+where
+    P: AsRef<Path>,
+    F: FnMut(Progress),
+{
+    cancel.check()?;
+    let mut recognizer = Recognizer::new(model, opts.sample_rate as f32);
+    recognizer.set_words(opts.words);
+    let mut utterances = Vec::new();
+    let mut samples = 0;
+    run(opts, input.as_ref(), |pcm| {
+        let pcm = Cancellable::new(pcm, cancel.clone());
+        recognizer.accept_reader(pcm, |recognizer, completed| {
+            if completed {
+                let utterance = recognizer.result().into_owned();
+                if !utterance.text.is_empty() {
+                    utterances.push(utterance);
+                }
+            }
+            samples = recognizer.samples_processed();
+            progress.update(samples, opts.sample_rate, samples * 2, None);
+        })
+    })?;
+    let last = recognizer.final_result().into_owned();
+    if !last.text.is_empty() {
+        utterances.push(last);
+    }
+    progress.finish(samples, opts.sample_rate, samples * 2, None);
+    Ok(utterances)
+}
+
+/// Runs ffmpeg on `input`, handing its output to `consume`.
+///
+/// If `consume` fails, ffmpeg is killed rather than left blocked on a full pipe.
+fn run<F>(opts: &FfmpegOptions, input: &Path, consume: F) -> Result<(), Error>
+where
+    F: FnOnce(&mut dyn Read) -> io::Result<()>,
+{
+    let mut child = Command::new(&opts.program)
+        .args(["-nostdin", "-hide_banner", "-loglevel", "error", "-i"])
+        .arg(input)
+        .args(["-f", "s16le", "-ac", "1", "-ar"])
+        .arg(opts.sample_rate.to_string())
+        .arg("-")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => Error::ProgramNotFound(opts.program.clone()),
+            _ => e.into(),
+        })?;
+    // Read on another thread, so that ffmpeg never waits for room in the pipe.
+    let stderr = child.stderr.take().map(read_tail);
+    let consumed = match child.stdout.take() {
+        Some(mut stdout) => consume(&mut stdout),
+        None => Ok(()),
+    };
+    if let Err(e) = consumed {
+        kill(&mut child);
+        return Err(e.into());
+    }
+    let status = child.wait()?;
+    let stderr = stderr
+        .and_then(|reader| reader.join().ok())
+        .unwrap_or_default();
+    if !status.success() {
+        return Err(Error::ProgramFailed {
+            program: opts.program.clone(),
+            code: status.code(),
+            stderr,
+        });
+    }
+    Ok(())
+}
+
+fn kill(child: &mut Child) {
+    // It may have exited already.
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Reads all of `pipe` on a new thread, keeping the end.
+fn read_tail<R: Read + Send + 'static>(mut pipe: R) -> JoinHandle<String> {
+    thread::spawn(move || {
+        let mut tail = Vec::new();
+        let mut buf = [0; 4096];
+        while let Ok(n) = pipe.read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+            tail.extend_from_slice(&buf[..n]);
+            let excess = tail.len().saturating_sub(STDERR_LIMIT);
+            tail.drain(..excess);
+        }
+        String::from_utf8_lossy(&tail).trim().to_string()
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// Writes a shell script standing in for ffmpeg.
+    fn fake_ffmpeg(name: &str, script: &str) -> FfmpegOptions {
+        let path = std::env::temp_dir().join(format!("vosk-fake-ffmpeg-{}", name));
+        fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        FfmpegOptions {
+            program: path,
+            ..FfmpegOptions::default()
+        }
+    }
+    fn read_all(opts: &FfmpegOptions) -> Result<Vec<u8>, Error> {
+        let mut out = Vec::new();
+        run(opts, Path::new("input.mp3"), |pcm| {
+            pcm.read_to_end(&mut out).map(drop)
+        })?;
+        Ok(out)
+    }
+
+    #[test]
+    fn arguments_and_output() {
+        let opts = fake_ffmpeg("args", r#"echo "$@""#);
+        let out = read_all(&opts).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "-nostdin -hide_banner -loglevel error -i input.mp3 -f s16le -ac 1 -ar 16000 -\n"
+        );
+    }
+    #[test]
+    fn missing_program() {
+        let opts = FfmpegOptions {
+            program: PathBuf::from("/nonexistent/ffmpeg"),
+            ..FfmpegOptions::default()
+        };
+        assert_eq!(
+            read_all(&opts),
+            Err(Error::ProgramNotFound(opts.program.clone()))
+        );
+    }
+    #[test]
+    fn failure_with_message() {
+        let opts = fake_ffmpeg("fails", "echo 'input.mp3: No such file' >&2; exit 1");
+        assert_eq!(
+            read_all(&opts),
+            Err(Error::ProgramFailed {
+                program: opts.program.clone(),
+                code: Some(1),
+                stderr: "input.mp3: No such file".to_string(),
+            })
+        );
+    }
+    #[test]
+    fn long_error_output() {
+        // More than fits in a pipe, which would block ffmpeg if not read.
+        let opts = fake_ffmpeg(
+            "chatty",
+            "i=0; while [ $i -lt 3000 ]; do echo 'warning: something odd' >&2; i=$((i+1)); done; echo done >&2; exit 2",
+        );
+        match read_all(&opts) {
+            Err(Error::ProgramFailed { code, stderr, .. }) => {
+                assert_eq!(code, Some(2));
+                assert!(stderr.len() <= STDERR_LIMIT);
+                assert!(stderr.ends_with("done"));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+    #[test]
+    fn consumer_gives_up() {
+        // Writes forever; has to be killed.
+        let opts = fake_ffmpeg("endless", "exec cat /dev/zero");
+        let result = run(&opts, Path::new("input.mp3"), |pcm| {
+            let mut buf = [0; 1024];
+            pcm.read_exact(&mut buf)?;
+            Err(io::Error::other("enough"))
+        });
+        assert_eq!(result, Err(Error::Io("enough".to_string())));
+    }
+    #[test]
+    fn cancelled_while_reading() {
+        let opts = fake_ffmpeg("cancelled", "exec cat /dev/zero");
+        let cancel = CancellationToken::new();
+        let result = run(&opts, Path::new("input.mp3"), |pcm| {
+            let mut pcm = Cancellable::new(pcm, cancel.clone());
+            let mut buf = [0; 1024];
+            pcm.read_exact(&mut buf)?;
+            cancel.cancel();
+            loop {
+                pcm.read_exact(&mut buf)?;
+            }
+        });
+        assert_eq!(result, Err(Error::Cancelled));
+    }
+}
@@ -0,0 +1,863 @@
+use crate::progress::ignore_progress;
+use crate::source::{transcribe_source_with_progress, AudioSource, Pending};
+use crate::{
+    CancellationToken, Error, Model, Progress, ProgressReporter, Recognizer, UtteranceOwned,
+};
+use std::fmt;
+use std::io::{self, Cursor, Read};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Bytes of the response read at a time, which bounds the memory used for any length.
+const READ_CHUNK_BYTES: usize = 8192;
+
+/// Most bytes of a WAV format chunk, which is 40 at most in practice.
+const MAX_FMT_BYTES: u32 = 1024;
+
+/// How `transcribe_url` downloads and decodes the audio.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UrlOptions {
+    /// The sample rate of raw 16-bit little-endian mono PCM, which has no header.
+    /// Responses that are neither WAV nor of a known compressed format are taken for
+    /// raw PCM only if this is set, or if their `Content-Type` is `audio/L16`.
+    pub raw_sample_rate: Option<u32>,
+    /// How many times in a row a failed request, or a download cut short, is tried again.
+    /// Downloads are resumed with a `Range` request where they stopped.
+    pub retries: u32,
+    /// The pause before trying again.
+    pub retry_delay: Duration,
+    /// How long connecting, and then each read, may take.
+    pub timeout: Duration,
+    /// Whether the utterances have word details.
+    pub words: bool,
+}
+
+impl Default for UrlOptions {
+    fn default() -> Self {
+        UrlOptions {
+            raw_sample_rate: None,
+            retries: 3,
+            retry_delay: Duration::from_millis(500),
+            timeout: Duration::from_secs(30),
+            words: true,
+        }
+    }
+}
+
+/// Recognizes all speech in the audio at `url`, streaming it rather than downloading
+/// it first.
+///
+/// The response can be WAV, raw PCM (see `UrlOptions::raw_sample_rate`), or, with the
+/// `audio-decode` feature, any format `transcribe_file` supports. Returns the finalized
+/// utterances in order, leaving out empty ones.
+///
+/// Failed requests and downloads fail with `Error::Network`, audio that can't be
+/// decoded with `Error::UnsupportedCodec` or `Error::CorruptFile`.
+pub fn transcribe_url(
+    model: &Model,
+    url: &str,
+    opts: &UrlOptions,
+) -> Result<Vec<UtteranceOwned>, Error> {
+    transcribe_url_cancellable(model, url, opts, &CancellationToken::new())
+}
+
+/// Same as `transcribe_url`, but fails with `Error::Cancelled` once `cancel` is.
+///
+/// A read blocked on a slow server delays it, up to `UrlOptions::timeout`.
+pub fn transcribe_url_cancellable(
+    model: &Model,
+    url: &str,
+    opts: &UrlOptions,
+    cancel: &CancellationToken,
+) -> Result<Vec<UtteranceOwned>, Error> {
+    transcribe_url_with_progress(model, url, opts, cancel, ignore_progress())
+}
+
+/// Same as `transcribe_url_cancellable`, also reporting how far it got to `progress`.
+///
+/// Bytes are those of the response body. The total duration is known for WAV with
+/// a length in its header, compressed formats that tell, and raw PCM with a
+/// `Content-Length`.
+pub fn transcribe_url_with_progress<F>(
+    model: &Model,
+    url: &str,
+    opts: &UrlOptions,
+    cancel: &CancellationToken,
+    progress: ProgressReporter<F>,
+) -> Result<Vec<UtteranceOwned>, Error>
+where
+    F: FnMut(Progress),
+{
+    cancel.check()?;
+    let source = HttpSource::open(url, opts)?;
+    let mut recognizer = Recognizer::try_new(model, source.sample_rate() as f32)?;
+    recognizer.set_words(opts.words);
+    transcribe_source_with_progress(&mut recognizer, source, cancel, progress)
+}
+
+/// The audio at a URL, downloaded as it's read and mixed down to mono.
+pub struct HttpSource {
+    body: Body,
+    sample_rate: u32,
+    total_samples: Option<u64>,
+    position: Arc<AtomicU64>,
+    pending: Pending,
+}
+
+/// The whole body, the bytes read to tell the format first.
+type Response = io::Chain<Cursor<Vec<u8>>, RangeReader>;
+
+enum Body {
+    Pcm(PcmStream<Response>),
+    #[cfg(feature = "audio-decode")]
+    Decoded(super::MonoDecoder),
+}
+
+impl HttpSource {
+    /// Requests `url` and reads enough of the response to tell its format.
+    pub fn open(url: &str, opts: &UrlOptions) -> Result<HttpSource, Error> {
+        let (mut reader, content_type) = RangeReader::open(url, opts)?;
+        let position = reader.position.clone();
+        let length = reader.length;
+        let mut peeked = Vec::new();
+        (&mut reader).take(12).read_to_end(&mut peeked)?;
+        let l16 = content_type.as_deref().and_then(L16::parse);
+        if peeked.starts_with(b"RIFF") && peeked.get(8..12) == Some(b"WAVE") {
+            let mut stream = Cursor::new(peeked).chain(reader);
+            let wav = WavFormat::read(&mut stream)?;
+            return Ok(HttpSource::pcm(
+                PcmStream::new(stream, wav.channels, false, wav.data_len),
+                wav.sample_rate,
+                position,
+            ));
+        }
+        if let Some(l16) = l16 {
+            let stream = Cursor::new(peeked).chain(reader);
+            return Ok(HttpSource::pcm(
+                PcmStream::new(stream, l16.channels, true, length),
+                l16.sample_rate,
+                position,
+            ));
+        }
+        if let (Some(rate), false) = (opts.raw_sample_rate, looks_compressed(&peeked)) {
+            let stream = Cursor::new(peeked).chain(reader);
+            return Ok(HttpSource::pcm(
+                PcmStream::new(stream, 1, false, length),
+                rate,
+                position,
+            ));
+        }
+        HttpSource::decoded(peeked, reader, url)
+    }
+    fn pcm(stream: PcmStream<Response>, sample_rate: u32, position: Arc<AtomicU64>) -> HttpSource {
+        HttpSource {
+            total_samples: stream.remaining.map(|bytes| bytes / stream.frame as u64),
+            body: Body::Pcm(stream),
+            sample_rate,
+            position,
+            pending: Pending::default(),
+        }
+    }
+    #[cfg(feature = "audio-decode")]
+    fn decoded(peeked: Vec<u8>, reader: RangeReader, url: &str) -> Result<HttpSource, Error> {
+        use symphonia::core::io::ReadOnlySource;
+        use symphonia::core::probe::Hint;
+
+        let position = reader.position.clone();
+        let mut hint = Hint::new();
+        let path = url.split(['?', '#']).next().unwrap_or_default();
+        let name = path.rsplit('/').next().unwrap_or_default();
+        if let Some((_, extension)) = name.rsplit_once('.') {
+            hint.with_extension(extension);
+        }
+        let source = ReadOnlySource::new(Cursor::new(peeked).chain(reader));
+        let decoder = super::MonoDecoder::from_source(Box::new(source), position.clone(), hint)?;
+        Ok(HttpSource {
+            sample_rate: decoder.sample_rate,
+            total_samples: decoder.total_samples,
+            body: Body::Decoded(decoder),
+            position,
+            pending: Pending::default(),
+        })
+    }
+    #[cfg(not(feature = "audio-decode"))]
+    fn decoded(peeked: Vec<u8>, _reader: RangeReader, _url: &str) -> Result<HttpSource, Error> {
+        Err(Error::UnsupportedCodec(match looks_compressed(&peeked) {
+            true => "compressed audio, which needs the audio-decode feature".to_string(),
+            false => "neither WAV nor a known format, set raw_sample_rate for raw PCM".to_string(),
+        }))
+    }
+}
+
+impl AudioSource for HttpSource {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+    fn read(&mut self, buf: &mut [i16]) -> Result<usize, Error> {
+        if self.pending.is_empty() {
+            let more = match &mut self.body {
+                Body::Pcm(stream) => stream.next_chunk(self.pending.refill())?,
+                #[cfg(feature = "audio-decode")]
+                Body::Decoded(decoder) => decoder.next_chunk(self.pending.refill())?,
+            };
+            if !more {
+                return Ok(0);
+            }
+        }
+        Ok(self.pending.take(buf))
+    }
+    fn total_samples(&self) -> Option<u64> {
+        self.total_samples
+    }
+    fn bytes_read(&self) -> Option<u64> {
+        Some(self.position.load(Ordering::Relaxed))
+    }
+}
+
+impl fmt::Debug for HttpSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpSource")
+            .field("sample_rate", &self.sample_rate)
+            .field("total_samples", &self.total_samples)
+            .field("bytes_read", &self.position.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+/// Whether the first bytes are those of a compressed format symphonia may know.
+fn looks_compressed(start: &[u8]) -> bool {
+    const MAGIC: [&[u8]; 5] = [b"ID3", b"fLaC", b"OggS", b"FORM", b"caff"];
+    MAGIC.iter().any(|magic| start.starts_with(magic))
+        || start.get(4..8) == Some(b"ftyp")
+        // The frame sync of MPEG audio and ADTS.
+        || matches!(start, [0xFF, second, ..] if second & 0xE0 == 0xE0)
+}
+
+/// The parameters of an `audio/L16` content type, which is big-endian.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct L16 {
+    sample_rate: u32,
+    channels: usize,
+}
+
+impl L16 {
+    fn parse(content_type: &str) -> Option<L16> {
+        let mut parts = content_type.split(';').map(str::trim);
+        if !parts.next()?.eq_ignore_ascii_case("audio/L16") {
+            return None;
+        }
+        let mut l16 = L16 {
+            sample_rate: 0,
+            channels: 1,
+        };
+        for (name, value) in parts.filter_map(|p| p.split_once('=')) {
+            match name.trim().to_ascii_lowercase().as_str() {
+                "rate" => l16.sample_rate = value.trim().parse().ok()?,
+                // As many as WAV allows, which keeps a frame within memory.
+                "channels" => l16.channels = value.trim().parse::<u16>().ok()?.into(),
+                _ => {}
+            }
+        }
+        (l16.sample_rate > 0 && l16.channels > 0).then_some(l16)
+    }
+}
+
+/// What the header of a 16-bit PCM WAV file tells.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct WavFormat {
+    channels: usize,
+    sample_rate: u32,
+    /// Bytes of samples, None when the header leaves it open, as streamed WAV does.
+    data_len: Option<u64>,
+}
+
+impl WavFormat {
+    /// Reads the header up to the start of the samples.
+    fn read<R: Read>(reader: &mut R) -> Result<WavFormat, Error> {
+        let mut riff = [0; 12];
+        read_header(reader, &mut riff)?;
+        let mut format = None;
+        loop {
+            let mut chunk = [0; 8];
+            read_header(reader, &mut chunk)?;
+            let size = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+            match &chunk[..4] {
+                b"fmt " => {
+                    if !(16..=MAX_FMT_BYTES).contains(&size) {
+                        return Err(corrupt("a WAV format chunk of an odd size"));
+                    }
+                    let mut fmt = vec![0; (size + size % 2) as usize];
+                    read_header(reader, &mut fmt)?;
+                    format = Some(WavFormat::parse_fmt(&fmt)?);
+                }
+                b"data" => {
+                    let mut format =
+                        format.ok_or_else(|| corrupt("WAV samples before their format"))?;
+                    format.data_len = match size {
+                        0 | u32::MAX => None,
+                        size => Some(size as u64),
+                    };
+                    return Ok(format);
+                }
+                _ => {
+                    let skip = size as u64 + size as u64 % 2;
+                    if io::copy(&mut reader.take(skip), &mut io::sink())? < skip {
+                        return Err(corrupt("the WAV header ends early"));
+                    }
+                }
+            }
+        }
+    }
+    fn parse_fmt(fmt: &[u8]) -> Result<WavFormat, Error> {
+        let u16_at = |i: usize| u16::from_le_bytes([fmt[i], fmt[i + 1]]);
+        let mut tag = u16_at(0);
+        // WAVE_FORMAT_EXTENSIBLE, whose sub-format starts with the actual tag.
+        if tag == 0xFFFE && fmt.len() >= 26 {
+            tag = u16_at(24);
+        }
+        let bits = u16_at(14);
+        if tag != 1 || bits != 16 {
+            return Err(Error::UnsupportedCodec(format!(
+                "WAV of format {} with {}-bit samples, only 16-bit PCM is streamed",
+                tag, bits
+            )));
+        }
+        let channels = u16_at(2) as usize;
+        let sample_rate = u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]);
+        if channels == 0 || sample_rate == 0 {
+            return Err(corrupt("a WAV header without channels or sample rate"));
+        }
+        Ok(WavFormat {
+            channels,
+            sample_rate,
+            data_len: None,
+        })
+    }
+}
+
+fn read_header<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<(), Error> {
+    reader.read_exact(buf).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => corrupt("the WAV header ends early"),
+        _ => e.into(),
+    })
+}
+
+fn corrupt(what: &str) -> Error {
+    Error::CorruptFile(what.to_string())
+}
+
+/// Interleaved 16-bit samples read from a stream and mixed down to mono.
+struct PcmStream<R> {
+    reader: R,
+    /// Bytes of one sample of every channel.
+    frame: usize,
+    big_endian: bool,
+    /// Bytes left to read, if known.
+    remaining: Option<u64>,
+    /// Bytes read, of which the complete frames are taken.
+    bytes: Vec<u8>,
+    interleaved: Vec<i16>,
+}
+
+impl<R: Read> PcmStream<R> {
+    fn new(reader: R, channels: usize, big_endian: bool, remaining: Option<u64>) -> Self {
+        PcmStream {
+            reader,
+            frame: channels * 2,
+            big_endian,
+            remaining,
+            bytes: Vec::new(),
+            interleaved: Vec::new(),
+        }
+    }
+    /// Replaces the content of `out` with the next samples, returning false at the end.
+    ///
+    /// An incomplete frame at the end is dropped.
+    fn next_chunk(&mut self, out: &mut Vec<i16>) -> Result<bool, Error> {
+        out.clear();
+        while out.is_empty() {
+            let want = match self.remaining {
+                Some(remaining) => remaining.min(READ_CHUNK_BYTES as u64) as usize,
+                None => READ_CHUNK_BYTES,
+            };
+            if want == 0 {
+                return Ok(false);
+            }
+            let start = self.bytes.len();
+            self.bytes.resize(start + want, 0);
+            let n = self.reader.read(&mut self.bytes[start..])?;
+            self.bytes.truncate(start + n);
+            if n == 0 {
+                return Ok(false);
+            }
+            if let Some(remaining) = &mut self.remaining {
+                *remaining -= n as u64;
+            }
+            let whole = self.bytes.len() / self.frame * self.frame;
+            let big_endian = self.big_endian;
+            self.interleaved.clear();
+            self.interleaved
+                .extend(
+                    self.bytes[..whole]
+                        .chunks_exact(2)
+                        .map(|b| match big_endian {
+                            true => i16::from_be_bytes([b[0], b[1]]),
+                            false => i16::from_le_bytes([b[0], b[1]]),
+                        }),
+                );
+            self.bytes.drain(..whole);
+            crate::pcm::downmix(&self.interleaved, self.frame / 2, out);
+        }
+        Ok(true)
+    }
+}
+
+/// The body of a response, requested again from where it stopped when the
+/// connection drops.
+struct RangeReader {
+    agent: ureq::Agent,
+    url: String,
+    body: Box<dyn Read + Send + Sync>,
+    /// Bytes of the body read so far.
+    position: Arc<AtomicU64>,
+    /// Length of the whole body, when the server tells.
+    length: Option<u64>,
+    retries: u32,
+    retry_delay: Duration,
+    /// Failures since the last successful read.
+    failures: u32,
+}
+
+impl RangeReader {
+    /// Also returns the content type of the response.
+    fn open(url: &str, opts: &UrlOptions) -> Result<(RangeReader, Option<String>), Error> {
+        let agent = ureq::AgentBuilder::new()
+            .timeout_connect(opts.timeout)
+            .timeout_read(opts.timeout)
+            .build();
+        let mut reader = RangeReader {
+            agent,
+            url: url.to_string(),
+            body: Box::new(io::empty()),
+            position: Arc::new(AtomicU64::new(0)),
+            length: None,
+            retries: opts.retries,
+            retry_delay: opts.retry_delay,
+            failures: 0,
+        };
+        let response = reader.with_retries(|reader| reader.request(None))?;
+        reader.failures = 0;
+        reader.length = response
+            .header("Content-Length")
+            .and_then(|length| length.trim().parse().ok());
+        let content_type = response.header("Content-Type").map(String::from);
+        reader.body = Box::new(response.into_reader());
+        Ok((reader, content_type))
+    }
+    fn request(&self, from: Option<u64>) -> Result<ureq::Response, Error> {
+        let mut request = self.agent.get(&self.url);
+        if let Some(from) = from {
+            request = request.set("Range", &format!("bytes={}-", from));
+        }
+        request.call().map_err(|e| match e {
+            ureq::Error::Status(status, _) => Error::Network {
+                url: self.url.clone(),
+                status: Some(status),
+                message: format!("the server answered with status {}", status),
+            },
+            ureq::Error::Transport(transport) => self.network(transport.to_string()),
+        })
+    }
+    fn network(&self, message: String) -> Error {
+        Error::Network {
+            url: self.url.clone(),
+            status: None,
+            message,
+        }
+    }
+    /// Calls `request` until it succeeds, fails for good, or too many times in a row.
+    fn with_retries<T, F>(&mut self, mut request: F) -> Result<T, Error>
+    where
+        F: FnMut(&mut RangeReader) -> Result<T, Error>,
+    {
+        loop {
+            match request(self) {
+                Err(e) if is_transient(&e) && self.failures < self.retries => {
+                    self.failures += 1;
+                    thread::sleep(self.retry_delay);
+                }
+                result => return result,
+            }
+        }
+    }
+    /// Requests the rest of the body, after it stopped with `failure`.
+    fn resume(&mut self, failure: io::Error) -> Result<(), Error> {
+        if self.failures >= self.retries {
+            return Err(self.network(format!("the download stopped: {}", failure)));
+        }
+        self.failures += 1;
+        thread::sleep(self.retry_delay);
+        let from = self.position.load(Ordering::Relaxed);
+        let response = self.with_retries(|reader| reader.request(Some(from)))?;
+        let start = response
+            .header("Content-Range")
+            .and_then(|range| range.trim().strip_prefix("bytes "))
+            .and_then(|range| range.split('-').next())
+            .and_then(|start| start.parse::<u64>().ok());
+        if response.status() != 206 || start != Some(from) {
+            return Err(self.network(format!(
+                "the download stopped ({}) and the server can't resume it",
+                failure
+            )));
+        }
+        self.body = Box::new(response.into_reader());
+        Ok(())
+    }
+}
+
+impl Read for RangeReader {
+    /// Fails with an error that converts to `Error::Network` once it can't go on.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let failure = match self.body.read(buf) {
+                Ok(0)
+                    if self
+                        .length
+                        .is_some_and(|length| self.position.load(Ordering::Relaxed) < length) =>
+                {
+                    io::Error::new(io::ErrorKind::UnexpectedEof, "the connection closed early")
+                }
+                Ok(n) => {
+                    if n > 0 {
+                        self.failures = 0;
+                    }
+                    self.position.fetch_add(n as u64, Ordering::Relaxed);
+                    return Ok(n);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => e,
+            };
+            self.resume(failure)
+                .map_err(|e| io::Error::other(NetworkFailure(e)))?;
+        }
+    }
+}
+
+/// Errors worth trying the request again for: the connection failing, and the server
+/// being busy or failing for the moment.
+fn is_transient(e: &Error) -> bool {
+    match *e {
+        Error::Network { status: None, .. } => true,
+        Error::Network {
+            status: Some(status),
+            ..
+        } => status == 408 || status == 429 || status >= 500,
+        _ => false,
+    }
+}
+
+/// Carries an `Error::Network` through IO errors, so that it converts back to it.
+#[derive(Debug)]
+pub(crate) struct NetworkFailure(pub(crate) Error);
+
+impl fmt::Display for NetworkFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for NetworkFailure {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::Mutex;
+
+    /// How the test server answers.
+    #[derive(Clone, Default)]
+    struct Serve {
+        body: Vec<u8>,
+        content_type: &'static str,
+        /// Closes the connection after this many bytes of the first response.
+        cut_after: Option<usize>,
+        /// Answers Range requests with the whole body, as servers without support do.
+        ignore_ranges: bool,
+        status: Option<u16>,
+    }
+
+    /// Serves over HTTP on a free port, returning its URL and the Range headers of
+    /// the requests, None for requests without one.
+    fn serve(serve: Serve) -> (String, Arc<Mutex<Vec<Option<String>>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/audio", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        thread::spawn(move || {
+            for (i, stream) in listener.incoming().enumerate() {
+                let mut stream = stream.unwrap();
+                let mut range = None;
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some(value) = line.strip_prefix("Range: bytes=") {
+                        range = Some(value.trim().to_string());
+                    }
+                }
+                seen.lock().unwrap().push(range.clone());
+                let len = serve.body.len();
+                let from = match (&range, serve.ignore_ranges) {
+                    (Some(range), false) => range.trim_end_matches('-').parse().unwrap(),
+                    _ => 0,
+                };
+                let status = match (serve.status, from) {
+                    (Some(status), _) => format!("{} Failing", status),
+                    (None, 0) => "200 OK".to_string(),
+                    (None, _) => "206 Partial Content".to_string(),
+                };
+                let mut head = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+                    status,
+                    serve.content_type,
+                    len - from
+                );
+                if from > 0 {
+                    head += &format!("Content-Range: bytes {}-{}/{}\r\n", from, len - 1, len);
+                }
+                head += "\r\n";
+                let mut body = &serve.body[from..];
+                if let (Some(cut), 0) = (serve.cut_after, i) {
+                    body = &body[..cut];
+                }
+                let _ = stream.write_all(head.as_bytes());
+                let _ = stream.write_all(body);
+            }
+        });
+        (url, requests)
+    }
+    fn wav(channels: u16, sample_rate: u32, samples: &[i16]) -> Vec<u8> {
+        let data_len = samples.len() as u32 * 2;
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + 12 + data_len).to_le_bytes());
+        bytes.extend_from_slice(b"WAVE");
+        // A chunk to skip, of an odd size.
+        bytes.extend_from_slice(b"LIST\x03\0\0\0abc\0");
+        bytes.extend_from_slice(b"fmt \x10\0\0\0\x01\0");
+        bytes.extend_from_slice(&channels.to_le_bytes());
+        bytes.extend_from_slice(&sample_rate.to_le_bytes());
+        bytes.extend_from_slice(&(sample_rate * channels as u32 * 2).to_le_bytes());
+        bytes.extend_from_slice(&(channels * 2).to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_len.to_le_bytes());
+        for s in samples {
+            bytes.extend_from_slice(&s.to_le_bytes());
+        }
+        bytes
+    }
+    fn opts() -> UrlOptions {
+        UrlOptions {
+            retries: 2,
+            retry_delay: Duration::ZERO,
+            timeout: Duration::from_secs(5),
+            ..UrlOptions::default()
+        }
+    }
+    fn read_all(mut source: HttpSource) -> Result<Vec<i16>, Error> {
+        let mut all = Vec::new();
+        let mut buf = [0; 1000];
+        loop {
+            let n = source.read(&mut buf)?;
+            if n == 0 {
+                return Ok(all);
+            }
+            all.extend_from_slice(&buf[..n]);
+        }
+    }
+
+    #[test]
+    fn stereo_wav() {
+        let samples: Vec<i16> = (0..20000)
+            .map(|i| (i % 2 * 100 + i / 2 % 7) as i16)
+            .collect();
+        let body = wav(2, 8000, &samples);
+        let len = body.len() as u64;
+        let (url, requests) = serve(Serve {
+            body,
+            content_type: "audio/wav",
+            ..Serve::default()
+        });
+        let source = HttpSource::open(&url, &opts()).unwrap();
+        assert_eq!(source.sample_rate(), 8000);
+        assert_eq!(source.total_samples(), Some(10000));
+        let position = source.position.clone();
+        let mono: Vec<i16> = (0..10000).map(|i| (50 + i % 7) as i16).collect();
+        assert_eq!(read_all(source).unwrap(), mono);
+        assert_eq!(position.load(Ordering::Relaxed), len);
+        assert_eq!(*requests.lock().unwrap(), [None]);
+    }
+    #[test]
+    fn resume_after_drop() {
+        let samples: Vec<i16> = (0..30000).map(|i| i as i16).collect();
+        let (url, requests) = serve(Serve {
+            body: wav(1, 16000, &samples),
+            content_type: "audio/wav",
+            // Within a sample.
+            cut_after: Some(20001),
+            ..Serve::default()
+        });
+        let source = HttpSource::open(&url, &opts()).unwrap();
+        assert_eq!(read_all(source).unwrap(), samples);
+        assert_eq!(
+            *requests.lock().unwrap(),
+            [None, Some("20001-".to_string())]
+        );
+    }
+    #[test]
+    fn network_errors() {
+        let (url, _) = serve(Serve {
+            body: wav(1, 16000, &[0; 30000]),
+            content_type: "audio/wav",
+            cut_after: Some(5000),
+            ignore_ranges: true,
+            ..Serve::default()
+        });
+        let source = HttpSource::open(&url, &opts()).unwrap();
+        match read_all(source) {
+            Err(Error::Network {
+                url: failed,
+                status: None,
+                message,
+            }) => {
+                assert_eq!(failed, url);
+                assert!(message.contains("can't resume"), "{}", message);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let (url, requests) = serve(Serve {
+            status: Some(404),
+            ..Serve::default()
+        });
+        // Not tried again, unlike server errors.
+        assert!(matches!(
+            HttpSource::open(&url, &opts()),
+            Err(Error::Network {
+                status: Some(404),
+                ..
+            })
+        ));
+        assert_eq!(requests.lock().unwrap().len(), 1);
+        let (url, requests) = serve(Serve {
+            status: Some(503),
+            ..Serve::default()
+        });
+        assert!(HttpSource::open(&url, &opts()).is_err());
+        assert_eq!(requests.lock().unwrap().len(), 3);
+
+        // Nothing listens on the port of a closed listener.
+        let closed = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let e = HttpSource::open(&format!("http://{}/", closed), &opts()).unwrap_err();
+        assert!(matches!(e, Error::Network { status: None, .. }), "{:?}", e);
+        assert!(e
+            .to_string()
+            .starts_with("Could not download http://127.0.0.1:"));
+    }
+    #[test]
+    fn raw_pcm() {
+        let samples: [i16; 4] = [1, -2, 300, -400];
+        let be: Vec<u8> = samples.iter().flat_map(|s| s.to_be_bytes()).collect();
+        let (url, _) = serve(Serve {
+            body: be,
+            content_type: "audio/L16; rate=8000; channels=2",
+            ..Serve::default()
+        });
+        let source = HttpSource::open(&url, &opts()).unwrap();
+        assert_eq!(source.sample_rate(), 8000);
+        assert_eq!(source.total_samples(), Some(2));
+        assert_eq!(read_all(source).unwrap(), [0, -50]);
+
+        let le: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        let (url, _) = serve(Serve {
+            body: le.clone(),
+            content_type: "application/octet-stream",
+            ..Serve::default()
+        });
+        let raw = UrlOptions {
+            raw_sample_rate: Some(16000),
+            ..opts()
+        };
+        let source = HttpSource::open(&url, &raw).unwrap();
+        assert_eq!(source.sample_rate(), 16000);
+        assert_eq!(read_all(source).unwrap(), samples);
+        // Without a rate it's not taken for audio.
+        assert!(matches!(
+            HttpSource::open(&url, &opts()),
+            Err(Error::UnsupportedCodec(_))
+        ));
+    }
+    #[test]
+    fn damaged_wav() {
+        let mut body = wav(1, 16000, &[0; 10]);
+        body.truncate(30);
+        let (url, _) = serve(Serve {
+            body,
+            content_type: "audio/wav",
+            ..Serve::default()
+        });
+        assert_eq!(
+            HttpSource::open(&url, &opts()).unwrap_err(),
+            Error::CorruptFile("the WAV header ends early".to_string())
+        );
+        let mut float = wav(1, 16000, &[0; 10]);
+        // The format tag of IEEE floats, in the chunk after the skipped one.
+        float[32] = 3;
+        let (url, _) = serve(Serve {
+            body: float,
+            content_type: "audio/wav",
+            ..Serve::default()
+        });
+        assert!(matches!(
+            HttpSource::open(&url, &opts()),
+            Err(Error::UnsupportedCodec(_))
+        ));
+    }
+    #[test]
+    fn content_types() {
+        assert_eq!(
+            L16::parse("audio/l16;rate=44100"),
+            Some(L16 {
+                sample_rate: 44100,
+                channels: 1
+            })
+        );
+        assert_eq!(L16::parse("audio/L16"), None);
+        assert_eq!(
+            L16::parse("audio/L16; rate=8000; channels=2"),
+            Some(L16 {
+                sample_rate: 8000,
+                channels: 2
+            })
+        );
+        assert_eq!(L16::parse("audio/L16;rate=8000;channels=65536"), None);
+        let overflow = format!("audio/L16;rate=8000;channels={}", usize::MAX);
+        assert_eq!(L16::parse(&overflow), None);
+        assert_eq!(L16::parse("audio/wav; rate=8000"), None);
+        assert!(looks_compressed(b"ID3\x04"));
+        assert!(looks_compressed(b"\0\0\0\x20ftypM4A "));
+        assert!(looks_compressed(&[0xFF, 0xFB, 0x90]));
+        assert!(!looks_compressed(&[0xFF, 0x0B, 0x90]));
+        assert!(!looks_compressed(b"RIFF"));
+    }
+}
@@ -0,0 +1,456 @@
+//! Spotting audio that can't be recognized well before blaming the model.
+//!
+//! Levels are on the 16-bit scale for both `i16` and `f32`, the scale
+//! `Recognizer::accept_waveform_f32` expects. Floats in [-1, 1], as most audio
+//! libraries produce them, are therefore almost silent to libvosk, one of the
+//! mistakes `InputDiagnostics` warns about.
+
+use crate::Recognizer;
+use std::fmt;
+use std::mem::discriminant;
+use std::time::Duration;
+
+/// Samples at least this loud are at full scale.
+const FULL_SCALE: f32 = 32767.0;
+/// The loudest valid f32 sample.
+const MAX_F32: f32 = 32768.0;
+
+/// Thresholds of `InputDiagnostics`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiagnosticsOptions {
+    /// A chunk with a larger fraction of its samples at full scale is clipped.
+    pub max_clipped: f32,
+    /// Input that stays below this peak level is near-silent.
+    pub quiet_peak: f32,
+    /// How much audio is needed to decide that the input is near-silent,
+    /// since a single chunk may fall into a pause.
+    pub quiet_after: Duration,
+}
+
+impl Default for DiagnosticsOptions {
+    fn default() -> Self {
+        DiagnosticsOptions {
+            max_clipped: 0.01,
+            // About -50 dBFS.
+            quiet_peak: 100.0,
+            quiet_after: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Peak, RMS and counts of suspicious samples, of a chunk or of everything so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Levels {
+    samples: u64,
+    peak: f32,
+    sum_squares: f64,
+    full_scale: u64,
+    out_of_range: u64,
+}
+
+impl Levels {
+    pub fn of_i16(samples: &[i16]) -> Levels {
+        let mut levels = Levels {
+            samples: samples.len() as u64,
+            ..Levels::default()
+        };
+        for &s in samples {
+            let s = f32::from(s).abs();
+            levels.peak = levels.peak.max(s);
+            levels.sum_squares += f64::from(s) * f64::from(s);
+            if s >= FULL_SCALE {
+                levels.full_scale += 1;
+            }
+        }
+        levels
+    }
+    /// Samples beyond the 16-bit range, or not numbers at all, are out of range.
+    /// They count towards the peak and RMS if they are finite.
+    pub fn of_f32(samples: &[f32]) -> Levels {
+        let mut levels = Levels {
+            samples: samples.len() as u64,
+            ..Levels::default()
+        };
+        for &s in samples {
+            let s = s.abs();
+            if !s.is_finite() {
+                levels.out_of_range += 1;
+                continue;
+            }
+            levels.peak = levels.peak.max(s);
+            levels.sum_squares += f64::from(s) * f64::from(s);
+            if s > MAX_F32 {
+                levels.out_of_range += 1;
+            } else if s >= FULL_SCALE {
+                levels.full_scale += 1;
+            }
+        }
+        levels
+    }
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+    /// The loudest absolute sample.
+    pub fn peak(&self) -> f32 {
+        self.peak
+    }
+    pub fn rms(&self) -> f32 {
+        (self.sum_squares / self.samples.max(1) as f64).sqrt() as f32
+    }
+    pub fn full_scale_fraction(&self) -> f32 {
+        fraction(self.full_scale, self.samples)
+    }
+    pub fn out_of_range_fraction(&self) -> f32 {
+        fraction(self.out_of_range, self.samples)
+    }
+    /// Adds the samples of another chunk.
+    pub fn merge(&mut self, other: &Levels) {
+        self.samples += other.samples;
+        self.peak = self.peak.max(other.peak);
+        self.sum_squares += other.sum_squares;
+        self.full_scale += other.full_scale;
+        self.out_of_range += other.out_of_range;
+    }
+    /// What's wrong with a chunk with these levels.
+    ///
+    /// Near-silence isn't reported here, a chunk being silent is normal.
+    pub fn warnings(&self, opts: &DiagnosticsOptions) -> Vec<InputWarning> {
+        let mut warnings = Vec::new();
+        if self.out_of_range > 0 {
+            warnings.push(InputWarning::OutOfRange {
+                fraction: self.out_of_range_fraction(),
+            });
+        }
+        if self.full_scale_fraction() > opts.max_clipped {
+            warnings.push(InputWarning::Clipping {
+                fraction: self.full_scale_fraction(),
+            });
+        }
+        if self.peak > 0.0 && self.peak <= 1.0 {
+            warnings.push(InputWarning::NormalizedFloat { peak: self.peak });
+        }
+        warnings
+    }
+}
+
+fn fraction(count: u64, total: u64) -> f32 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f32 / total as f32
+    }
+}
+
+/// A problem with the input, see `InputDiagnostics`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputWarning {
+    /// Samples no louder than 1, the scale of floats in [-1, 1] rather than the
+    /// 16-bit scale. Also happens to such floats converted to `i16` without scaling.
+    NormalizedFloat { peak: f32 },
+    /// f32 samples beyond the 16-bit range, or NaN or infinite.
+    OutOfRange { fraction: f32 },
+    /// Too many samples at full scale.
+    Clipping { fraction: f32 },
+    /// Nothing louder than `DiagnosticsOptions::quiet_peak` so far.
+    NearSilent { peak: f32 },
+}
+
+impl fmt::Display for InputWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputWarning::NormalizedFloat { peak } => write!(
+                f,
+                "input appears to be f32 in [-1, 1] (peak {}), samples should be in i16 range",
+                peak
+            ),
+            InputWarning::OutOfRange { fraction } => write!(
+                f,
+                "{:.1}% of samples are outside the i16 range or not numbers",
+                fraction * 100.0
+            ),
+            InputWarning::Clipping { fraction } => {
+                write!(f, "{:.1}% clipped samples", fraction * 100.0)
+            }
+            InputWarning::NearSilent { peak } => {
+                write!(f, "input is near-silent (peak {})", peak)
+            }
+        }
+    }
+}
+
+/// What `InputDiagnostics` found in the audio fed so far.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InputSummary {
+    pub chunks: u64,
+    /// Of all the chunks together.
+    pub levels: Levels,
+    /// Chunks with at least one warning.
+    pub flagged_chunks: u64,
+    /// Every kind of warning that came up, once, with the values of its first chunk.
+    pub warnings: Vec<InputWarning>,
+}
+
+/// A recognizer that measures the audio fed to it and warns about audio it can't
+/// make sense of: clipped, near-silent, or scaled wrongly.
+///
+/// Each kind of warning is logged once, with the `tracing` feature, and kept in the
+/// summary. The audio is passed on unchanged.
+pub struct InputDiagnostics {
+    recognizer: Recognizer,
+    monitor: Monitor,
+}
+
+impl InputDiagnostics {
+    pub fn new(recognizer: Recognizer) -> InputDiagnostics {
+        InputDiagnostics::with_options(recognizer, DiagnosticsOptions::default())
+    }
+    pub fn with_options(recognizer: Recognizer, opts: DiagnosticsOptions) -> InputDiagnostics {
+        let monitor = Monitor::new(opts, recognizer.sample_rate());
+        InputDiagnostics {
+            recognizer,
+            monitor,
+        }
+    }
+    /// Measures the samples and feeds them to the recognizer, like `Recognizer::accept_waveform`.
+    pub fn accept_waveform(&mut self, wave: &[i16]) -> bool {
+        self.monitor.observe(Levels::of_i16(wave));
+        self.recognizer.accept_waveform(wave)
+    }
+    /// Same as `accept_waveform`, for f32 samples.
+    pub fn accept_waveform_f32(&mut self, wave: &[f32]) -> bool {
+        self.monitor.observe(Levels::of_f32(wave));
+        self.recognizer.accept_waveform_f32(wave)
+    }
+    /// The levels of the last chunk.
+    pub fn last_levels(&self) -> &Levels {
+        &self.monitor.last
+    }
+    pub fn summary(&self) -> &InputSummary {
+        &self.monitor.summary
+    }
+    /// Starts a new summary, such as for a new stream, so that warnings are logged again.
+    pub fn reset_summary(&mut self) {
+        self.monitor.reset();
+    }
+    pub fn options(&self) -> &DiagnosticsOptions {
+        &self.monitor.opts
+    }
+    pub fn recognizer(&self) -> &Recognizer {
+        &self.recognizer
+    }
+    pub fn recognizer_mut(&mut self) -> &mut Recognizer {
+        &mut self.recognizer
+    }
+    pub fn into_inner(self) -> Recognizer {
+        self.recognizer
+    }
+}
+
+/// The bookkeeping of `InputDiagnostics`, apart from the recognizer.
+struct Monitor {
+    opts: DiagnosticsOptions,
+    sample_rate: f32,
+    last: Levels,
+    summary: InputSummary,
+}
+
+impl Monitor {
+    fn new(opts: DiagnosticsOptions, sample_rate: f32) -> Monitor {
+        Monitor {
+            opts,
+            sample_rate,
+            last: Levels::default(),
+            summary: InputSummary::default(),
+        }
+    }
+    fn reset(&mut self) {
+        self.last = Levels::default();
+        self.summary = InputSummary::default();
+    }
+    fn observe(&mut self, levels: Levels) {
+        self.last = levels;
+        self.summary.chunks += 1;
+        self.summary.levels.merge(&levels);
+        let warnings = levels.warnings(&self.opts);
+        if !warnings.is_empty() {
+            self.summary.flagged_chunks += 1;
+        }
+        for warning in warnings {
+            self.add(warning);
+        }
+        let total = self.summary.levels;
+        let heard = Duration::from_secs_f64(total.samples as f64 / f64::from(self.sample_rate));
+        // Floats in [-1, 1] are quiet as well, but already have a better explanation.
+        if heard >= self.opts.quiet_after
+            && total.peak < self.opts.quiet_peak
+            && !self.has(&InputWarning::NormalizedFloat { peak: 0.0 })
+        {
+            self.add(InputWarning::NearSilent { peak: total.peak });
+        }
+    }
+    fn has(&self, warning: &InputWarning) -> bool {
+        self.summary
+            .warnings
+            .iter()
+            .any(|w| discriminant(w) == discriminant(warning))
+    }
+    fn add(&mut self, warning: InputWarning) {
+        if self.has(&warning) {
+            return;
+        }
+        #[cfg(feature = "tracing")]
+        tracing::warn!(chunk = self.summary.chunks, "{}", warning);
+        self.summary.warnings.push(warning);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(amplitude: f32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| amplitude * (i as f32 * 2.0 * std::f32::consts::PI * 440.0 / 16000.0).sin())
+            .collect()
+    }
+    fn to_i16(samples: &[f32]) -> Vec<i16> {
+        samples.iter().map(|&s| s.round() as i16).collect()
+    }
+    fn opts() -> DiagnosticsOptions {
+        DiagnosticsOptions {
+            max_clipped: 0.01,
+            quiet_peak: 100.0,
+            quiet_after: Duration::from_secs(1),
+        }
+    }
+
+    #[test]
+    fn levels_of_a_sine() {
+        let levels = Levels::of_i16(&to_i16(&sine(10000.0, 1600)));
+        assert_eq!(levels.samples(), 1600);
+        assert!((levels.peak() - 10000.0).abs() < 1.0);
+        assert!((levels.rms() - 10000.0 / 2f32.sqrt()).abs() < 10.0);
+        assert_eq!(levels.full_scale_fraction(), 0.0);
+        assert!(levels.warnings(&opts()).is_empty());
+        let levels = Levels::of_f32(&sine(10000.0, 1600));
+        assert!((levels.rms() - 10000.0 / 2f32.sqrt()).abs() < 10.0);
+        assert!(levels.warnings(&opts()).is_empty());
+        assert_eq!(Levels::of_i16(&[]).rms(), 0.0);
+        assert_eq!(Levels::of_i16(&[]).full_scale_fraction(), 0.0);
+    }
+    #[test]
+    fn clipping_threshold() {
+        // A sine twice as loud as i16 allows, hard-clipped.
+        let clipped = to_i16(&sine(65536.0, 1600));
+        let levels = Levels::of_i16(&clipped);
+        assert!(levels.full_scale_fraction() > 0.5);
+        assert!(matches!(
+            levels.warnings(&opts())[..],
+            [InputWarning::Clipping { .. }]
+        ));
+        assert_eq!(
+            InputWarning::Clipping { fraction: 0.0312 }.to_string(),
+            "3.1% clipped samples"
+        );
+        // Exactly 1% at full scale is still fine, a bit more isn't.
+        let mut samples = vec![1000i16; 1000];
+        for s in &mut samples[..10] {
+            *s = i16::MIN;
+        }
+        assert!(Levels::of_i16(&samples).warnings(&opts()).is_empty());
+        samples[10] = i16::MAX;
+        assert_eq!(
+            Levels::of_i16(&samples).warnings(&opts()),
+            [InputWarning::Clipping { fraction: 0.011 }]
+        );
+    }
+    #[test]
+    fn out_of_range_f32() {
+        let mut samples = sine(20000.0, 1000);
+        samples[3] = 40000.0;
+        samples[4] = f32::NAN;
+        let levels = Levels::of_f32(&samples);
+        assert_eq!(levels.out_of_range_fraction(), 0.002);
+        assert_eq!(levels.peak(), 40000.0);
+        assert_eq!(
+            levels.warnings(&opts()),
+            [InputWarning::OutOfRange { fraction: 0.002 }]
+        );
+        // 32768 is the magnitude of i16::MIN, still in range.
+        let levels = Levels::of_f32(&[32768.0, -32768.0, 0.0]);
+        assert_eq!(levels.out_of_range_fraction(), 0.0);
+        assert!(levels.full_scale_fraction() > 0.6);
+    }
+    #[test]
+    fn normalized_floats() {
+        let levels = Levels::of_f32(&sine(0.8, 1600));
+        assert_eq!(
+            levels.warnings(&opts()),
+            [InputWarning::NormalizedFloat {
+                peak: levels.peak()
+            }]
+        );
+        assert!(levels.warnings(&opts())[0]
+            .to_string()
+            .starts_with("input appears to be f32 in [-1, 1]"));
+        // The same floats cast to i16 are only -1, 0 and 1.
+        let cast: Vec<i16> = sine(0.8, 1600).iter().map(|&s| s.round() as i16).collect();
+        assert!(matches!(
+            Levels::of_i16(&cast).warnings(&opts())[..],
+            [InputWarning::NormalizedFloat { peak }] if peak == 1.0
+        ));
+        // Digital silence could be anything.
+        assert!(Levels::of_f32(&[0.0; 100]).warnings(&opts()).is_empty());
+        assert!(Levels::of_f32(&sine(2.0, 1600))
+            .warnings(&opts())
+            .is_empty());
+    }
+    #[test]
+    fn near_silent_after_enough_audio() {
+        let mut monitor = Monitor::new(opts(), 16000.0);
+        let quiet = to_i16(&sine(50.0, 8000));
+        monitor.observe(Levels::of_i16(&quiet));
+        assert!(monitor.summary.warnings.is_empty());
+        monitor.observe(Levels::of_i16(&quiet));
+        assert!(matches!(
+            monitor.summary.warnings[..],
+            [InputWarning::NearSilent { peak }] if peak > 49.0 && peak <= 50.0
+        ));
+        // Quiet chunks aren't flagged one by one.
+        assert_eq!(monitor.summary.flagged_chunks, 0);
+
+        // A pause in speech is no reason to warn.
+        let mut monitor = Monitor::new(opts(), 16000.0);
+        monitor.observe(Levels::of_i16(&to_i16(&sine(5000.0, 1600))));
+        monitor.observe(Levels::of_i16(&[0; 32000]));
+        assert!(monitor.summary.warnings.is_empty());
+    }
+    #[test]
+    fn summary_keeps_each_warning_once() {
+        let mut monitor = Monitor::new(opts(), 16000.0);
+        let normalized = sine(0.5, 16000);
+        for _ in 0..3 {
+            monitor.observe(Levels::of_f32(&normalized));
+        }
+        let clipped = to_i16(&sine(65536.0, 1600));
+        monitor.observe(Levels::of_i16(&clipped));
+        monitor.observe(Levels::of_i16(&to_i16(&sine(1000.0, 1600))));
+        let summary = &monitor.summary;
+        assert_eq!(summary.chunks, 5);
+        assert_eq!(summary.flagged_chunks, 4);
+        assert_eq!(summary.levels.samples(), 3 * 16000 + 2 * 1600);
+        assert_eq!(summary.levels.peak(), 32768.0);
+        // The normalized floats explain the silence, there's no separate warning.
+        assert!(matches!(
+            summary.warnings[..],
+            [
+                InputWarning::NormalizedFloat { .. },
+                InputWarning::Clipping { .. }
+            ]
+        ));
+        assert_eq!(monitor.last, Levels::of_i16(&to_i16(&sine(1000.0, 1600))));
+
+        monitor.reset();
+        assert_eq!(monitor.summary, InputSummary::default());
+    }
+}
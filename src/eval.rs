@@ -0,0 +1,444 @@
+//! Word and character error rates of recognized text against reference transcripts,
+//! to compare models and settings.
+//!
+//! Rates are computed the standard way, from the fewest substitutions, insertions
+//! and deletions turning the reference into the hypothesis, divided by the length
+//! of the reference. They can exceed 1 when the hypothesis has many extra words.
+
+use serde::Serialize;
+
+/// How text is normalized before it's compared.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvalOptions {
+    /// Compares lowercased text.
+    pub case_fold: bool,
+    /// Leaves out characters other than letters, digits and apostrophes within words.
+    pub strip_punctuation: bool,
+}
+
+impl Default for EvalOptions {
+    fn default() -> Self {
+        EvalOptions {
+            case_fold: true,
+            strip_punctuation: true,
+        }
+    }
+}
+
+/// What happened to a token of the reference, or where one was inserted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EditOp {
+    Match,
+    Substitution,
+    Insertion,
+    Deletion,
+}
+
+/// A step of the alignment of the reference and the hypothesis.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AlignedToken {
+    pub op: EditOp,
+    /// None for insertions.
+    pub reference: Option<String>,
+    /// None for deletions.
+    pub hypothesis: Option<String>,
+}
+
+/// The errors of a hypothesis, see `wer` and `cer`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WerReport {
+    /// Errors per token of the reference. With an empty reference,
+    /// 0 for an empty hypothesis and 1 otherwise.
+    pub wer: f64,
+    pub substitutions: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+    /// Tokens in the normalized reference.
+    pub reference_len: usize,
+    /// The tokens in order, with how each was edited.
+    pub alignment: Vec<AlignedToken>,
+}
+
+impl WerReport {
+    pub fn errors(&self) -> usize {
+        self.substitutions + self.insertions + self.deletions
+    }
+    fn new(reference: &[String], hypothesis: &[String]) -> WerReport {
+        let alignment = align(reference, hypothesis);
+        let count = |op| alignment.iter().filter(|t| t.op == op).count();
+        let substitutions = count(EditOp::Substitution);
+        let insertions = count(EditOp::Insertion);
+        let deletions = count(EditOp::Deletion);
+        WerReport {
+            wer: rate(substitutions + insertions + deletions, reference.len()),
+            substitutions,
+            insertions,
+            deletions,
+            reference_len: reference.len(),
+            alignment,
+        }
+    }
+}
+
+/// The error rate of many hypotheses together, which weighs each by the length
+/// of its reference, unlike the mean of their rates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ErrorTotals {
+    pub substitutions: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+    pub reference_len: usize,
+}
+
+impl ErrorTotals {
+    pub fn add(&mut self, report: &WerReport) {
+        self.substitutions += report.substitutions;
+        self.insertions += report.insertions;
+        self.deletions += report.deletions;
+        self.reference_len += report.reference_len;
+    }
+    pub fn errors(&self) -> usize {
+        self.substitutions + self.insertions + self.deletions
+    }
+    /// Errors per token of all references, as `WerReport::wer`.
+    pub fn rate(&self) -> f64 {
+        rate(self.errors(), self.reference_len)
+    }
+}
+
+/// The word error rate of `hypothesis`, such as recognized text, against `reference`.
+///
+/// ```
+/// use vosk::eval::{wer, EvalOptions};
+/// let report = wer("The cat sat on the mat.", "the cat sit on mat", &EvalOptions::default());
+/// assert_eq!((report.substitutions, report.deletions), (1, 1));
+/// assert_eq!(report.wer, 2.0 / 6.0);
+/// ```
+pub fn wer(reference: &str, hypothesis: &str, opts: &EvalOptions) -> WerReport {
+    WerReport::new(&words(reference, opts), &words(hypothesis, opts))
+}
+
+/// The character error rate of `hypothesis` against `reference`, the measure of choice
+/// for languages written without spaces between words, such as Chinese and Japanese.
+///
+/// Whitespace isn't compared, so text with and without spaces between words matches.
+pub fn cer(reference: &str, hypothesis: &str, opts: &EvalOptions) -> WerReport {
+    WerReport::new(&chars(reference, opts), &chars(hypothesis, opts))
+}
+
+fn rate(errors: usize, reference_len: usize) -> f64 {
+    match reference_len {
+        0 => (errors > 0) as u8 as f64,
+        n => errors as f64 / n as f64,
+    }
+}
+
+fn normalize(token: &str, opts: &EvalOptions) -> String {
+    let mut token = if opts.strip_punctuation {
+        token
+            .chars()
+            .filter(|c| c.is_alphanumeric() || *c == '\'')
+            .collect::<String>()
+            .trim_matches('\'')
+            .to_string()
+    } else {
+        token.to_string()
+    };
+    if opts.case_fold {
+        token = token.to_lowercase();
+    }
+    token
+}
+
+fn words(text: &str, opts: &EvalOptions) -> Vec<String> {
+    text.split_whitespace()
+        .map(|word| normalize(word, opts))
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+fn chars(text: &str, opts: &EvalOptions) -> Vec<String> {
+    words(text, opts)
+        .iter()
+        .flat_map(|word| word.chars())
+        .map(String::from)
+        .collect()
+}
+
+/// Levenshtein alignment with a backtrace. Among the alignments with the fewest
+/// edits, substitutions are preferred over a deletion and an insertion.
+fn align(reference: &[String], hypothesis: &[String]) -> Vec<AlignedToken> {
+    let (n, m) = (reference.len(), hypothesis.len());
+    // cost[i][j] turns the first i tokens of the reference into the first j of the hypothesis.
+    let mut cost = vec![vec![0usize; m + 1]; n + 1];
+    cost[0] = (0..=m).collect();
+    for (i, row) in cost.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for i in 1..=n {
+        for j in 1..=m {
+            let diagonal = cost[i - 1][j - 1] + (reference[i - 1] != hypothesis[j - 1]) as usize;
+            let deleted = cost[i - 1][j] + 1;
+            let inserted = cost[i][j - 1] + 1;
+            cost[i][j] = diagonal.min(deleted).min(inserted);
+        }
+    }
+    let mut alignment = Vec::with_capacity(n.max(m));
+    let (mut i, mut j) = (n, m);
+    while i > 0 || j > 0 {
+        let token = if i > 0
+            && j > 0
+            && cost[i][j] == cost[i - 1][j - 1] + (reference[i - 1] != hypothesis[j - 1]) as usize
+        {
+            i -= 1;
+            j -= 1;
+            let op = if reference[i] == hypothesis[j] {
+                EditOp::Match
+            } else {
+                EditOp::Substitution
+            };
+            AlignedToken {
+                op,
+                reference: Some(reference[i].clone()),
+                hypothesis: Some(hypothesis[j].clone()),
+            }
+        } else if i > 0 && cost[i][j] == cost[i - 1][j] + 1 {
+            i -= 1;
+            AlignedToken {
+                op: EditOp::Deletion,
+                reference: Some(reference[i].clone()),
+                hypothesis: None,
+            }
+        } else {
+            j -= 1;
+            AlignedToken {
+                op: EditOp::Insertion,
+                reference: None,
+                hypothesis: Some(hypothesis[j].clone()),
+            }
+        };
+        alignment.push(token);
+    }
+    alignment.reverse();
+    alignment
+}
+
+/// The results of `evaluate_files`.
+#[cfg(feature = "audio-decode")]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Evaluation {
+    /// The report of each file, in order.
+    pub files: Vec<(std::path::PathBuf, WerReport)>,
+    pub totals: ErrorTotals,
+}
+
+/// Transcribes each audio file with `model` and compares the text to its transcript.
+///
+/// Fails on the first file that can't be decoded.
+#[cfg(feature = "audio-decode")]
+pub fn evaluate_files<P, S>(
+    model: &crate::Model,
+    pairs: &[(P, S)],
+    opts: &EvalOptions,
+) -> Result<Evaluation, crate::Error>
+where
+    P: AsRef<std::path::Path>,
+    S: AsRef<str>,
+{
+    let mut evaluation = Evaluation {
+        files: Vec::with_capacity(pairs.len()),
+        totals: ErrorTotals::default(),
+    };
+    for (path, transcript) in pairs {
+        let utterances = crate::decode::transcribe_file(model, path)?;
+        let texts: Vec<&str> = utterances.iter().map(|u| u.text.as_ref()).collect();
+        let report = wer(transcript.as_ref(), &texts.join(" "), opts);
+        evaluation.totals.add(&report);
+        evaluation.files.push((path.as_ref().to_path_buf(), report));
+    }
+    Ok(evaluation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::word_distance;
+
+    fn counts(report: &WerReport) -> (usize, usize, usize) {
+        (report.substitutions, report.insertions, report.deletions)
+    }
+
+    /// The alignment as "ref/hyp" pairs, with "-" for a missing side.
+    fn pairs(report: &WerReport) -> Vec<String> {
+        report
+            .alignment
+            .iter()
+            .map(|t| {
+                format!(
+                    "{}/{}",
+                    t.reference.as_deref().unwrap_or("-"),
+                    t.hypothesis.as_deref().unwrap_or("-")
+                )
+            })
+            .collect()
+    }
+
+    fn plain() -> EvalOptions {
+        EvalOptions {
+            case_fold: false,
+            strip_punctuation: false,
+        }
+    }
+
+    #[test]
+    fn identical() {
+        let report = wer("hello world", "hello world", &plain());
+        assert_eq!(report.wer, 0.0);
+        assert_eq!(counts(&report), (0, 0, 0));
+        assert!(report.alignment.iter().all(|t| t.op == EditOp::Match));
+    }
+    #[test]
+    fn substitution() {
+        let report = wer("hello world", "hello duck", &plain());
+        assert_eq!(report.wer, 0.5);
+        assert_eq!(counts(&report), (1, 0, 0));
+        assert_eq!(pairs(&report), ["hello/hello", "world/duck"]);
+    }
+    #[test]
+    fn substitution_and_deletion() {
+        let report = wer("the cat sat on the mat", "the cat sit on mat", &plain());
+        assert_eq!(counts(&report), (1, 0, 1));
+        assert_eq!(report.wer, 2.0 / 6.0);
+        assert_eq!(
+            pairs(&report),
+            ["the/the", "cat/cat", "sat/sit", "on/on", "the/-", "mat/mat"]
+        );
+    }
+    #[test]
+    fn insertions() {
+        let report = wer("a b", "a x b y", &plain());
+        assert_eq!(counts(&report), (0, 2, 0));
+        assert_eq!(report.wer, 1.0);
+        assert_eq!(pairs(&report), ["a/a", "-/x", "b/b", "-/y"]);
+    }
+    #[test]
+    fn more_errors_than_words() {
+        let report = wer("a b c", "x y z w", &plain());
+        assert_eq!(counts(&report), (3, 1, 0));
+        assert_eq!(report.wer, 4.0 / 3.0);
+    }
+    #[test]
+    fn empty_sides() {
+        let deleted = wer("a b c", "", &plain());
+        assert_eq!((counts(&deleted), deleted.wer), ((0, 0, 3), 1.0));
+        let inserted = wer("", "a", &plain());
+        assert_eq!((counts(&inserted), inserted.wer), ((0, 1, 0), 1.0));
+        let nothing = wer(" ", "", &plain());
+        assert_eq!((nothing.reference_len, nothing.wer), (0, 0.0));
+        assert!(nothing.alignment.is_empty());
+    }
+    #[test]
+    fn shifted_words() {
+        // A word moved to the end is a deletion and an insertion, not two substitutions.
+        let report = wer("one two three four", "two three four one", &plain());
+        assert_eq!(counts(&report), (0, 1, 1));
+        assert_eq!(
+            pairs(&report),
+            ["one/-", "two/two", "three/three", "four/four", "-/one"]
+        );
+    }
+    #[test]
+    fn repeated_words() {
+        let report = wer("no no no", "no", &plain());
+        assert_eq!(counts(&report), (0, 0, 2));
+        let report = wer("to be or not to be", "to be or to be", &plain());
+        assert_eq!(counts(&report), (0, 0, 1));
+        assert_eq!(report.alignment[3].reference.as_deref(), Some("not"));
+    }
+    #[test]
+    fn normalization() {
+        let opts = EvalOptions::default();
+        assert_eq!(wer("Hello, World!", "hello world", &opts).wer, 0.0);
+        assert_eq!(wer("It's 'quoted' - ok", "it's quoted ok", &opts).wer, 0.0);
+        let case_only = EvalOptions {
+            case_fold: false,
+            ..EvalOptions::default()
+        };
+        assert_eq!(
+            counts(&wer("Hello, World!", "hello world", &case_only)),
+            (2, 0, 0)
+        );
+        let punctuation_only = EvalOptions {
+            strip_punctuation: false,
+            ..EvalOptions::default()
+        };
+        assert_eq!(
+            counts(&wer("Hello, World!", "hello world", &punctuation_only)),
+            (2, 0, 0)
+        );
+        assert_eq!(wer("ÉCOLE", "école", &opts).wer, 0.0);
+    }
+    #[test]
+    fn characters() {
+        let report = cer("kitten", "sitting", &plain());
+        assert_eq!(counts(&report), (2, 1, 0));
+        assert_eq!(report.wer, 0.5);
+        let report = cer("今天天气很好", "今天天汽很好", &plain());
+        assert_eq!((report.substitutions, report.reference_len), (1, 6));
+        assert_eq!(cer("今天 天气", "今天天气", &plain()).wer, 0.0);
+        assert_eq!(cer("Hi!", "hi", &EvalOptions::default()).wer, 0.0);
+    }
+    #[test]
+    fn totals() {
+        let mut totals = ErrorTotals::default();
+        totals.add(&wer("a b c d", "a b c d", &plain()));
+        totals.add(&wer("a b", "x", &plain()));
+        assert_eq!(totals.errors(), 2);
+        assert_eq!(totals.reference_len, 6);
+        assert_eq!(totals.rate(), 2.0 / 6.0);
+        assert_eq!(ErrorTotals::default().rate(), 0.0);
+    }
+    #[test]
+    fn minimal_edits() {
+        let texts = [
+            "",
+            "a",
+            "a b c",
+            "a c b",
+            "b a",
+            "a a b b",
+            "c a b a c",
+            "x y z",
+            "a b c d e f",
+            "f e d c b a",
+        ];
+        for reference in texts {
+            for hypothesis in texts {
+                let report = wer(reference, hypothesis, &plain());
+                let r: Vec<&str> = reference.split_whitespace().collect();
+                let h: Vec<&str> = hypothesis.split_whitespace().collect();
+                assert_eq!(report.errors(), word_distance(&r, &h), "{:?}", (r, h));
+                // The alignment spells out both texts.
+                let side = |f: fn(&AlignedToken) -> Option<&String>| {
+                    report
+                        .alignment
+                        .iter()
+                        .filter_map(f)
+                        .map(String::as_str)
+                        .collect::<Vec<_>>()
+                };
+                assert_eq!(side(|t| t.reference.as_ref()), r);
+                assert_eq!(side(|t| t.hypothesis.as_ref()), h);
+            }
+        }
+    }
+    #[test]
+    fn report_json() {
+        let report = wer("a b", "a c", &plain());
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["substitutions"], 1);
+        assert_eq!(json["alignment"][1]["op"], "substitution");
+        assert_eq!(json["alignment"][1]["hypothesis"], "c");
+    }
+}
@@ -0,0 +1,908 @@
+//! Writing recognition results in subtitle formats, or as JSON Lines for other tools.
+//!
+//! Subtitle cues are built from word timings, so the recognizer should have word
+//! details enabled (see `Recognizer::set_words`).
+//! Utterances without word details are skipped.
+
+use crate::segment::{segment_sentences, SentenceOptions};
+use crate::{ConfStats, Error, RecognizedText, RecognizedWord, UtteranceOwned};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+
+/// Controls how words are grouped into subtitle cues.
+#[derive(Debug, Clone)]
+pub struct SubtitleOptions {
+    /// Maximum number of characters on one line of a cue.
+    /// A single word longer than this gets a line of its own.
+    pub max_line_len: usize,
+    /// Maximum number of lines in one cue.
+    pub max_lines: usize,
+    /// Maximum duration of a cue in seconds.
+    pub max_cue_secs: f32,
+    /// Split utterances into sentences first, so that a cue never spans two.
+    /// Off by default.
+    pub sentences: Option<SentenceOptions>,
+}
+
+impl Default for SubtitleOptions {
+    fn default() -> Self {
+        SubtitleOptions {
+            max_line_len: 42,
+            max_lines: 2,
+            max_cue_secs: 7.0,
+            sentences: None,
+        }
+    }
+}
+
+/// One subtitle entry, shown from `start` to `end` seconds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    pub start: f32,
+    pub end: f32,
+    pub lines: Vec<String>,
+    /// Confidence statistics of the words in the cue, for example to color uncertain text.
+    pub conf: ConfStats,
+}
+
+/// Groups the words of each utterance into cues.
+///
+/// A cue never spans two utterances.
+pub fn cues(utterances: &[RecognizedText], opts: &SubtitleOptions) -> Vec<Cue> {
+    let mut cues = Vec::new();
+    for utterance in utterances {
+        let words = utterance.words();
+        match &opts.sentences {
+            Some(sentence_opts) => {
+                for sentence in segment_sentences(words, sentence_opts) {
+                    push_cues(&mut cues, sentence.words, opts, None);
+                }
+            }
+            None => push_cues(&mut cues, words, opts, None),
+        }
+    }
+    cues
+}
+
+/// Groups consecutive words into cues, each starting with `label` if there is one.
+fn push_cues(
+    cues: &mut Vec<Cue>,
+    words: &[RecognizedWord],
+    opts: &SubtitleOptions,
+    label: Option<&str>,
+) {
+    // The current cue has words[first..i].
+    let mut first = 0;
+    for i in 0..=words.len() {
+        if i > first {
+            let complete = match words.get(i) {
+                Some(w) => {
+                    let text = texts(label, &words[first..=i]);
+                    w.end() - words[first].start() > opts.max_cue_secs
+                        || wrap(&text, opts.max_line_len).len() > opts.max_lines
+                }
+                None => true,
+            };
+            if complete {
+                cues.push(cue(&words[first..i], opts, label));
+                first = i;
+            }
+        }
+    }
+}
+
+fn cue(words: &[RecognizedWord], opts: &SubtitleOptions, label: Option<&str>) -> Cue {
+    Cue {
+        start: words[0].start(),
+        end: words[words.len() - 1].end(),
+        lines: wrap(&texts(label, words), opts.max_line_len),
+        conf: ConfStats::of(words).expect("cues are not empty"),
+    }
+}
+
+fn texts<'a>(label: Option<&'a str>, words: &'a [RecognizedWord]) -> Vec<&'a str> {
+    label
+        .into_iter()
+        .chain(words.iter().map(RecognizedWord::word))
+        .collect()
+}
+
+/// A stretch of time in which one speaker talks, as found by diarization.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpeakerTurn {
+    pub start: f32,
+    pub end: f32,
+    /// The cluster of the speaker, counted from 0.
+    pub speaker: usize,
+}
+
+/// An utterance with the turns of the speakers during it.
+///
+/// The turns may be those of the whole recording, only the ones overlapping the
+/// utterance matter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabelledUtterance<'a> {
+    pub utterance: RecognizedText<'a>,
+    pub turns: Vec<SpeakerTurn>,
+}
+
+/// How speakers are named at the start of cues.
+#[derive(Debug, Clone)]
+pub struct SpeakerLabels {
+    /// The label of a speaker without a name, `{}` being replaced by its cluster
+    /// counted from 1. `"SPEAKER {}:"` by default.
+    pub format: String,
+    /// Names by cluster, such as those of enrolled speakers, shown as `"Name:"`.
+    pub names: HashMap<usize, String>,
+}
+
+impl Default for SpeakerLabels {
+    fn default() -> Self {
+        SpeakerLabels {
+            format: "SPEAKER {}:".to_string(),
+            names: HashMap::new(),
+        }
+    }
+}
+
+impl SpeakerLabels {
+    /// Names the speaker of cluster `speaker`.
+    pub fn with_name(mut self, speaker: usize, name: impl Into<String>) -> Self {
+        self.names.insert(speaker, name.into());
+        self
+    }
+    pub fn label(&self, speaker: usize) -> String {
+        match self.names.get(&speaker) {
+            Some(name) => format!("{}:", name),
+            None => self.format.replace("{}", &(speaker + 1).to_string()),
+        }
+    }
+}
+
+/// Groups the words of each utterance into cues that start with the label of their speaker.
+///
+/// A cue never spans two speakers: an utterance is split where the speaker changes.
+/// A word belongs to the turn covering most of it, or the nearest turn if none covers it,
+/// so a word spoken over a change goes to the one who said more of it.
+/// Without turns the words get no label.
+pub fn speaker_cues(
+    utterances: &[LabelledUtterance],
+    opts: &SubtitleOptions,
+    labels: &SpeakerLabels,
+) -> Vec<Cue> {
+    let mut cues = Vec::new();
+    for labelled in utterances {
+        let words = labelled.utterance.words();
+        let sentences = match &opts.sentences {
+            Some(sentence_opts) => segment_sentences(words, sentence_opts)
+                .into_iter()
+                .map(|s| s.words)
+                .collect(),
+            None => vec![words],
+        };
+        for words in sentences {
+            let mut first = 0;
+            let mut speaker = None;
+            for i in 0..=words.len() {
+                let next = words.get(i).and_then(|w| speaker_of(w, &labelled.turns));
+                if i == words.len() || (i > first && next != speaker) {
+                    let label = speaker.map(|s| labels.label(s));
+                    push_cues(&mut cues, &words[first..i], opts, label.as_deref());
+                    first = i;
+                }
+                speaker = next;
+            }
+        }
+    }
+    cues
+}
+
+/// The turn with the largest part of the word, else the one closest to its middle.
+fn speaker_of(word: &RecognizedWord, turns: &[SpeakerTurn]) -> Option<usize> {
+    let overlap = |t: &SpeakerTurn| t.end.min(word.end()) - t.start.max(word.start());
+    let mut best: Option<&SpeakerTurn> = None;
+    for turn in turns {
+        if best.is_none_or(|b| overlap(turn) > overlap(b)) {
+            best = Some(turn);
+        }
+    }
+    let best = best?;
+    if overlap(best) > 0.0 {
+        return Some(best.speaker);
+    }
+    let middle = (word.start() + word.end()) / 2.0;
+    let distance = |t: &&SpeakerTurn| (t.start - middle).max(middle - t.end).max(0.0);
+    turns
+        .iter()
+        .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+        .map(|t| t.speaker)
+}
+
+/// Formats the utterances as SubRip (`.srt`) subtitles.
+pub fn to_srt(utterances: &[RecognizedText], opts: &SubtitleOptions) -> String {
+    srt(&cues(utterances, opts))
+}
+
+/// Formats the utterances as SubRip (`.srt`) subtitles, each cue starting with
+/// the label of its speaker, such as `SPEAKER 1: hello`. See `speaker_cues`.
+pub fn to_srt_with_speakers(
+    utterances: &[LabelledUtterance],
+    opts: &SubtitleOptions,
+    labels: &SpeakerLabels,
+) -> String {
+    srt(&speaker_cues(utterances, opts, labels))
+}
+
+fn srt(cues: &[Cue]) -> String {
+    let mut out = String::new();
+    for (i, cue) in cues.iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "{}\n{} --> {}",
+            i + 1,
+            timestamp(cue.start, ','),
+            timestamp(cue.end, ',')
+        );
+        for line in &cue.lines {
+            out.push_str(line);
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Formats the utterances as WebVTT (`.vtt`) subtitles.
+pub fn to_vtt(utterances: &[RecognizedText], opts: &SubtitleOptions) -> String {
+    let mut out = String::from("WEBVTT\n\n");
+    for cue in cues(utterances, opts) {
+        let _ = writeln!(
+            out,
+            "{} --> {}",
+            timestamp(cue.start, '.'),
+            timestamp(cue.end, '.')
+        );
+        for line in &cue.lines {
+            out.push_str(line);
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Greedily fills lines of at most `max_len` characters.
+fn wrap(words: &[&str], max_len: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut line_len = 0;
+    for word in words {
+        let len = word.chars().count();
+        if line_len > 0 && line_len + 1 + len > max_len {
+            lines.push(std::mem::take(&mut line));
+            line_len = 0;
+        }
+        if line_len > 0 {
+            line.push(' ');
+            line_len += 1;
+        }
+        line.push_str(word);
+        line_len += len;
+    }
+    if line_len > 0 {
+        lines.push(line);
+    }
+    lines
+}
+
+/// `HH:MM:SS,mmm` for SRT, `HH:MM:SS.mmm` for WebVTT.
+fn timestamp(secs: f32, separator: char) -> String {
+    let ms = (secs.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        separator,
+        ms % 1000
+    )
+}
+
+/// Settings for `to_kaldi_dir`.
+#[derive(Debug, Clone)]
+pub struct KaldiOptions {
+    /// The speaker of recordings not in `speakers`, `"speaker"` by default.
+    pub speaker: String,
+    /// Speakers by the path of the recording, as given to `to_kaldi_dir`.
+    pub speakers: HashMap<PathBuf, String>,
+}
+
+impl Default for KaldiOptions {
+    fn default() -> Self {
+        KaldiOptions {
+            speaker: "speaker".to_string(),
+            speakers: HashMap::new(),
+        }
+    }
+}
+
+/// Writes a Kaldi data directory with `wav.scp`, `text`, `utt2spk` and `segments`,
+/// for training on the transcriptions of the recordings in `items`.
+///
+/// Recordings are named `<speaker>-<file name>` and utterances
+/// `<recording>-<start>-<end>` in hundredths of a second, so that sorting by
+/// name keeps the utterances of a speaker together, as Kaldi wants.
+/// Characters other than letters, digits, `_` and `.` are replaced by `_` in names,
+/// and recordings with the same file name are told apart by a number.
+/// Paths with whitespace or quotes are read through `cat`.
+///
+/// Utterances without text or word details are left out.
+pub fn to_kaldi_dir(
+    output_dir: impl AsRef<Path>,
+    items: &[(PathBuf, Vec<UtteranceOwned>)],
+    opts: &KaldiOptions,
+) -> Result<(), Error> {
+    let mut recordings = Vec::new();
+    let mut utterances = Vec::new();
+    let mut used = HashMap::new();
+    for (path, results) in items {
+        let path_str = path
+            .to_str()
+            .ok_or_else(|| Error::InvalidPath(path.clone()))?;
+        let speaker = kaldi_name(opts.speakers.get(path).unwrap_or(&opts.speaker));
+        let stem = path.file_stem().map(|s| s.to_string_lossy());
+        let recording = unique(
+            &mut used,
+            format!(
+                "{}-{}",
+                speaker,
+                kaldi_name(stem.as_deref().unwrap_or("audio"))
+            ),
+        );
+        for utterance in results {
+            let words = utterance.words();
+            let (Some(first), Some(last)) = (words.first(), words.last()) else {
+                continue;
+            };
+            let text = utterance
+                .text
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ");
+            if text.is_empty() {
+                continue;
+            }
+            let (start, end) = (centisecs(first.start()), centisecs(last.end()));
+            let id = unique(&mut used, format!("{}-{:07}-{:07}", recording, start, end));
+            utterances.push((id, recording.clone(), speaker.clone(), start, end, text));
+        }
+        recordings.push((recording, kaldi_rxfilename(path_str)));
+    }
+    recordings.sort();
+    utterances.sort();
+
+    let output_dir = output_dir.as_ref();
+    fs::create_dir_all(output_dir)?;
+    let mut wav_scp = String::new();
+    for (recording, rxfilename) in &recordings {
+        let _ = writeln!(wav_scp, "{} {}", recording, rxfilename);
+    }
+    let (mut text, mut utt2spk, mut segments) = (String::new(), String::new(), String::new());
+    for (id, recording, speaker, start, end, words) in &utterances {
+        let _ = writeln!(text, "{} {}", id, words);
+        let _ = writeln!(utt2spk, "{} {}", id, speaker);
+        let _ = writeln!(
+            segments,
+            "{} {} {}.{:02} {}.{:02}",
+            id,
+            recording,
+            start / 100,
+            start % 100,
+            end / 100,
+            end % 100
+        );
+    }
+    fs::write(output_dir.join("wav.scp"), wav_scp)?;
+    fs::write(output_dir.join("text"), text)?;
+    fs::write(output_dir.join("utt2spk"), utt2spk)?;
+    fs::write(output_dir.join("segments"), segments)?;
+    Ok(())
+}
+
+fn kaldi_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect();
+    if name.is_empty() {
+        "_".to_string()
+    } else {
+        name
+    }
+}
+
+/// `name`, or `name_2`, `name_3` and so on if it was taken.
+fn unique(used: &mut HashMap<String, usize>, name: String) -> String {
+    let count = used.entry(name.clone()).or_insert(0);
+    *count += 1;
+    if *count == 1 {
+        return name;
+    }
+    let mut n = *count;
+    loop {
+        let candidate = format!("{}_{}", name, n);
+        if !used.contains_key(&candidate) {
+            used.insert(candidate.clone(), 1);
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+fn centisecs(secs: f32) -> u64 {
+    (secs.max(0.0) * 100.0).round() as u64
+}
+
+/// Kaldi takes the rest of the line as the file, which breaks at whitespace,
+/// so such paths become a pipe through `cat` with the path quoted for the shell.
+fn kaldi_rxfilename(path: &str) -> String {
+    if path
+        .chars()
+        .any(|c| c.is_whitespace() || matches!(c, '\'' | '"' | '|'))
+    {
+        format!("cat '{}' |", path.replace('\'', "'\\''"))
+    } else {
+        path.to_string()
+    }
+}
+
+/// Appends utterances to a file or other output as JSON Lines, one object per utterance.
+///
+/// Each line is flushed as it's written, so that a crash loses at most the utterance
+/// in progress. The objects look like this, with `confidence` and `words` null
+/// for utterances without word details:
+///
+/// ```json
+/// {"seq":0,"text":"hello world","start":0.5,"end":1.25,
+///  "confidence":{"min":0.8,"max":1.0,"mean":0.9},
+///  "words":[{"word":"hello","conf":0.8,"start":0.5,"end":0.9},
+///           {"word":"world","conf":1.0,"start":1.0,"end":1.25}]}
+/// ```
+///
+/// Read them back with [`read_jsonl`].
+#[derive(Debug)]
+pub struct JsonlWriter<W: io::Write> {
+    out: W,
+    seq: u64,
+    line: Vec<u8>,
+}
+
+/// The object written for each utterance. Changing it breaks the files of users.
+#[derive(Serialize, Deserialize)]
+struct JsonlRecord<'a> {
+    seq: u64,
+    #[serde(borrow)]
+    text: Cow<'a, str>,
+    start: Option<f32>,
+    end: Option<f32>,
+    confidence: Option<JsonlConfidence>,
+    #[serde(borrow)]
+    words: Option<Vec<RecognizedWord<'a>>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonlConfidence {
+    min: f32,
+    max: f32,
+    mean: f32,
+}
+
+impl<W: io::Write> JsonlWriter<W> {
+    pub fn new(out: W) -> JsonlWriter<W> {
+        JsonlWriter {
+            out,
+            seq: 0,
+            line: Vec::new(),
+        }
+    }
+    /// Writes the utterance as one line, numbered from 0 in the order of the calls.
+    pub fn write_utterance(&mut self, utterance: &RecognizedText) -> io::Result<()> {
+        let words = utterance.words();
+        let record = JsonlRecord {
+            seq: self.seq,
+            text: Cow::Borrowed(&utterance.text),
+            start: words.first().map(|w| w.start()),
+            end: words.last().map(|w| w.end()),
+            confidence: ConfStats::of(words).map(|c| JsonlConfidence {
+                min: c.min,
+                max: c.max,
+                mean: c.mean,
+            }),
+            // Absent rather than empty without word details.
+            words: utterance.result.as_deref().map(|words| {
+                words
+                    .iter()
+                    .map(|w| RecognizedWord::new(w.word(), w.conf(), w.start(), w.end()))
+                    .collect()
+            }),
+        };
+        self.line.clear();
+        serde_json::to_writer(&mut self.line, &record)?;
+        self.line.push(b'\n');
+        self.out.write_all(&self.line)?;
+        self.out.flush()?;
+        self.seq += 1;
+        Ok(())
+    }
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+/// Reads utterances written by a [`JsonlWriter`], skipping blank lines.
+///
+/// A line that can't be parsed gives an `Error::Json` naming its line number,
+/// reading goes on with the next one.
+pub fn read_jsonl<R: BufRead>(input: R) -> impl Iterator<Item = Result<UtteranceOwned, Error>> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|(i, line)| {
+            let line = line?;
+            let record: JsonlRecord = serde_json::from_str(&line).map_err(|e| Error::Json {
+                message: format!("line {}: {}", i + 1, e),
+                raw: None,
+            })?;
+            let utterance = RecognizedText {
+                text: record.text,
+                result: record.words,
+            };
+            Ok(utterance.into_owned())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::utterance;
+    use crate::RecognizedText;
+
+    /// The schema of JSON Lines files; a change here breaks files written before.
+    const GOLDEN_JSONL: &str = concat!(
+        r#"{"seq":0,"text":"hello world","start":0.5,"end":1.25,"confidence":{"min":0.75,"max":1.0,"mean":0.875},"words":[{"word":"hello","conf":0.75,"start":0.5,"end":0.9},{"word":"world","conf":1.0,"start":1.0,"end":1.25}]}"#,
+        "\n",
+        r#"{"seq":1,"text":"","start":null,"end":null,"confidence":null,"words":null}"#,
+        "\n",
+        r#"{"seq":2,"text":"ok","start":null,"end":null,"confidence":null,"words":null}"#,
+        "\n",
+    );
+
+    fn golden_utterances() -> Vec<UtteranceOwned> {
+        vec![
+            RecognizedText::from_words(vec![
+                RecognizedWord::new("hello", 0.75, 0.5, 0.9),
+                RecognizedWord::new("world", 1.0, 1.0, 1.25),
+            ]),
+            RecognizedText::from_text(""),
+            RecognizedText::from_text("ok"),
+        ]
+    }
+
+    #[test]
+    fn srt_format() {
+        let u = utterance(&[("hello", 0.5, 0.9), ("world", 1.0, 1.25)]);
+        let srt = to_srt(&[u], &SubtitleOptions::default());
+        assert_eq!(srt, "1\n00:00:00,500 --> 00:00:01,250\nhello world\n\n");
+    }
+    #[test]
+    fn vtt_format() {
+        let u = utterance(&[("hello", 3661.5, 3662.0)]);
+        let vtt = to_vtt(&[u], &SubtitleOptions::default());
+        assert_eq!(vtt, "WEBVTT\n\n01:01:01.500 --> 01:01:02.000\nhello\n\n");
+    }
+    #[test]
+    fn split_by_duration() {
+        let u = utterance(&[("one", 0.0, 1.0), ("two", 1.0, 2.0), ("three", 2.0, 3.5)]);
+        let opts = SubtitleOptions {
+            max_cue_secs: 3.0,
+            ..SubtitleOptions::default()
+        };
+        let cues = cues(&[u], &opts);
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].lines, vec!["one two"]);
+        assert_eq!((cues[1].start, cues[1].end), (2.0, 3.5));
+    }
+    #[test]
+    fn split_by_line_length() {
+        let u = utterance(&[("aaa", 0.0, 0.1), ("bbb", 0.1, 0.2), ("ccc", 0.2, 0.3)]);
+        let opts = SubtitleOptions {
+            max_line_len: 7,
+            max_lines: 1,
+            ..SubtitleOptions::default()
+        };
+        let cues = cues(&[u], &opts);
+        assert_eq!(cues[0].lines, vec!["aaa bbb"]);
+        assert_eq!(cues[1].lines, vec!["ccc"]);
+        assert_eq!(cues[1].conf.word_count, 1);
+    }
+    #[test]
+    fn split_by_sentence() {
+        let u = || {
+            utterance(&[
+                ("one", 0.0, 0.3),
+                ("two", 0.3, 0.6),
+                ("three", 0.6, 0.9),
+                ("four", 2.0, 2.3),
+            ])
+        };
+        let mut opts = SubtitleOptions::default();
+        assert_eq!(cues(&[u()], &opts).len(), 1);
+        opts.sentences = Some(SentenceOptions::default());
+        let cues = cues(&[u()], &opts);
+        assert_eq!(cues[0].lines, vec!["one two three"]);
+        assert_eq!(cues[1].lines, vec!["four"]);
+    }
+    fn turn(start: f32, end: f32, speaker: usize) -> SpeakerTurn {
+        SpeakerTurn {
+            start,
+            end,
+            speaker,
+        }
+    }
+    fn labelled(words: &[(&str, f32, f32)], turns: &[SpeakerTurn]) -> LabelledUtterance<'static> {
+        LabelledUtterance {
+            utterance: utterance(words),
+            turns: turns.to_vec(),
+        }
+    }
+    fn speaker_lines(utterances: &[LabelledUtterance], labels: &SpeakerLabels) -> Vec<String> {
+        speaker_cues(utterances, &SubtitleOptions::default(), labels)
+            .into_iter()
+            .map(|cue| cue.lines.join("|"))
+            .collect()
+    }
+
+    #[test]
+    fn speaker_change_mid_utterance() {
+        let u = labelled(
+            &[
+                ("are", 0.0, 0.3),
+                ("you", 0.3, 0.5),
+                ("there", 0.5, 0.9),
+                ("yes", 1.2, 1.5),
+                ("hi", 1.6, 1.8),
+            ],
+            &[turn(0.0, 1.0, 0), turn(1.0, 2.0, 1)],
+        );
+        let srt =
+            to_srt_with_speakers(&[u], &SubtitleOptions::default(), &SpeakerLabels::default());
+        assert_eq!(
+            srt,
+            "1\n00:00:00,000 --> 00:00:00,900\nSPEAKER 1: are you there\n\n\
+             2\n00:00:01,200 --> 00:00:01,800\nSPEAKER 2: yes hi\n\n"
+        );
+    }
+    #[test]
+    fn straddling_words() {
+        // "well" is mostly in the second turn, "so" exactly half in each.
+        let u = labelled(
+            &[
+                ("ok", 0.0, 0.4),
+                ("well", 0.8, 1.4),
+                ("so", 2.8, 3.2),
+                ("then", 3.2, 3.5),
+            ],
+            &[turn(0.0, 1.0, 0), turn(1.0, 3.0, 1), turn(3.0, 4.0, 0)],
+        );
+        assert_eq!(
+            speaker_lines(&[u], &SpeakerLabels::default()),
+            ["SPEAKER 1: ok", "SPEAKER 2: well so", "SPEAKER 1: then"]
+        );
+    }
+    #[test]
+    fn words_between_turns() {
+        // "um" is closer to the second turn, "and" after every turn.
+        let u = labelled(
+            &[
+                ("no", 0.0, 0.5),
+                ("um", 1.3, 1.5),
+                ("yes", 2.0, 2.5),
+                ("and", 4.0, 4.2),
+            ],
+            &[turn(0.0, 0.6, 3), turn(1.8, 2.6, 1), turn(10.0, 11.0, 3)],
+        );
+        assert_eq!(
+            speaker_lines(&[u], &SpeakerLabels::default()),
+            ["SPEAKER 4: no", "SPEAKER 2: um yes and"]
+        );
+        // Without turns, nothing is labelled.
+        let u = labelled(&[("hello", 0.0, 0.5)], &[]);
+        assert_eq!(speaker_lines(&[u], &SpeakerLabels::default()), ["hello"]);
+    }
+    #[test]
+    fn speaker_names_and_format() {
+        let turns = [turn(0.0, 1.0, 0), turn(1.0, 2.0, 1)];
+        let u = || labelled(&[("hi", 0.2, 0.4), ("hey", 1.2, 1.4)], &turns);
+        let labels = SpeakerLabels::default().with_name(1, "Anna");
+        assert_eq!(
+            speaker_lines(&[u()], &labels),
+            ["SPEAKER 1: hi", "Anna: hey"]
+        );
+        let labels = SpeakerLabels {
+            format: "[S{}]".to_string(),
+            ..labels
+        };
+        assert_eq!(speaker_lines(&[u()], &labels), ["[S1] hi", "Anna: hey"]);
+        // The same speaker in two utterances still gets two cues.
+        assert_eq!(speaker_lines(&[u(), u()], &labels).len(), 4);
+    }
+    #[test]
+    fn labels_count_towards_line_length() {
+        let u = labelled(
+            &[("aaa", 0.0, 0.1), ("bbb", 0.1, 0.2), ("ccc", 0.2, 0.3)],
+            &[turn(0.0, 1.0, 0)],
+        );
+        let opts = SubtitleOptions {
+            max_line_len: 14,
+            max_lines: 1,
+            ..SubtitleOptions::default()
+        };
+        let cues = speaker_cues(&[u], &opts, &SpeakerLabels::default());
+        let lines: Vec<_> = cues.iter().map(|c| c.lines.join("|")).collect();
+        assert_eq!(
+            lines,
+            ["SPEAKER 1: aaa", "SPEAKER 1: bbb", "SPEAKER 1: ccc"]
+        );
+        assert_eq!(cues[1].conf.word_count, 1);
+    }
+    fn kaldi_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vosk-kaldi-{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+    fn read(dir: &Path, file: &str) -> String {
+        fs::read_to_string(dir.join(file)).unwrap()
+    }
+
+    #[test]
+    fn kaldi_files() {
+        let dir = kaldi_dir("files");
+        let items = vec![
+            (
+                PathBuf::from("/data/calls/b.wav"),
+                vec![
+                    utterance(&[("later", 12.5, 13.0)]),
+                    RecognizedText::from_text("no words"),
+                    utterance(&[("hello", 0.5, 0.9), ("world", 1.0, 1.255)]),
+                ],
+            ),
+            (
+                PathBuf::from("/data/calls/a.wav"),
+                vec![utterance(&[("hi", 2.0, 2.25)]), utterance(&[])],
+            ),
+        ];
+        let opts = KaldiOptions {
+            speaker: "agent".to_string(),
+            ..KaldiOptions::default()
+        };
+        to_kaldi_dir(&dir, &items, &opts).unwrap();
+        assert_eq!(
+            read(&dir, "wav.scp"),
+            "agent-a /data/calls/a.wav\nagent-b /data/calls/b.wav\n"
+        );
+        assert_eq!(
+            read(&dir, "text"),
+            "agent-a-0000200-0000225 hi\n\
+             agent-b-0000050-0000126 hello world\n\
+             agent-b-0001250-0001300 later\n"
+        );
+        assert_eq!(
+            read(&dir, "utt2spk"),
+            "agent-a-0000200-0000225 agent\n\
+             agent-b-0000050-0000126 agent\n\
+             agent-b-0001250-0001300 agent\n"
+        );
+        assert_eq!(
+            read(&dir, "segments"),
+            "agent-a-0000200-0000225 agent-a 2.00 2.25\n\
+             agent-b-0000050-0000126 agent-b 0.50 1.26\n\
+             agent-b-0001250-0001300 agent-b 12.50 13.00\n"
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+    #[test]
+    fn kaldi_names() {
+        let dir = kaldi_dir("names");
+        let hi = || vec![utterance(&[("hi", 0.0, 0.5)])];
+        let items = vec![
+            (PathBuf::from("in/my call-1.wav"), hi()),
+            (PathBuf::from("out/my call-1.wav"), hi()),
+            (PathBuf::from("it's.wav"), hi()),
+            (PathBuf::from("plain.wav"), hi()),
+        ];
+        let mut opts = KaldiOptions::default();
+        opts.speakers
+            .insert(PathBuf::from("plain.wav"), "Dr. Who".to_string());
+        opts.speakers
+            .insert(PathBuf::from("it's.wav"), "Dr. Who".to_string());
+        to_kaldi_dir(&dir, &items, &opts).unwrap();
+        assert_eq!(
+            read(&dir, "wav.scp"),
+            "Dr._Who-it_s cat 'it'\\''s.wav' |\n\
+             Dr._Who-plain plain.wav\n\
+             speaker-my_call_1 cat 'in/my call-1.wav' |\n\
+             speaker-my_call_1_2 cat 'out/my call-1.wav' |\n"
+        );
+        assert_eq!(
+            read(&dir, "utt2spk"),
+            "Dr._Who-it_s-0000000-0000050 Dr._Who\n\
+             Dr._Who-plain-0000000-0000050 Dr._Who\n\
+             speaker-my_call_1-0000000-0000050 speaker\n\
+             speaker-my_call_1_2-0000000-0000050 speaker\n"
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+    #[test]
+    fn kaldi_duplicate_times() {
+        let dir = kaldi_dir("duplicates");
+        let same = utterance(&[("again", 1.0, 1.5)]);
+        let items = vec![(PathBuf::from("x.wav"), vec![same.clone(), same])];
+        to_kaldi_dir(&dir, &items, &KaldiOptions::default()).unwrap();
+        assert_eq!(
+            read(&dir, "text"),
+            "speaker-x-0000100-0000150 again\nspeaker-x-0000100-0000150_2 again\n"
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+    #[test]
+    fn no_word_details() {
+        let u = RecognizedText {
+            text: "hello".into(),
+            result: None,
+        };
+        assert!(cues(&[u], &SubtitleOptions::default()).is_empty());
+    }
+    #[test]
+    fn jsonl_schema() {
+        let mut writer = JsonlWriter::new(Vec::new());
+        for u in golden_utterances() {
+            writer.write_utterance(&u).unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(writer.into_inner()).unwrap(),
+            GOLDEN_JSONL
+        );
+    }
+    #[test]
+    fn jsonl_round_trip() {
+        let read: Vec<_> = read_jsonl(GOLDEN_JSONL.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let mut written = JsonlWriter::new(Vec::new());
+        for u in &read {
+            written.write_utterance(u).unwrap();
+        }
+        assert_eq!(written.into_inner(), GOLDEN_JSONL.as_bytes());
+        assert_eq!(read[0].result.as_ref().unwrap()[1].word(), "world");
+        assert!(read[2].result.is_none());
+    }
+    #[test]
+    fn jsonl_bad_line() {
+        let input = "{\"seq\":0,\"text\":\"a\"}\n\nnot json\n";
+        let read: Vec<_> = read_jsonl(input.as_bytes()).collect();
+        assert_eq!(read.len(), 2);
+        assert_eq!(read[0].as_ref().unwrap().text, "a");
+        assert!(
+            matches!(&read[1], Err(Error::Json { message, .. }) if message.starts_with("line 3:"))
+        );
+    }
+}
@@ -0,0 +1,118 @@
+//! What the feeders of packets and rings share: feeding audio to the recognizer and
+//! turning what it returns into events, measured by an optional `LatencyTracker`.
+
+use crate::activity::SpeechActivity;
+use crate::latency::{LatencyStats, LatencyTracker};
+use crate::partial::PartialTracker;
+use crate::{Event, Recognizer, UtteranceOwned};
+use std::collections::VecDeque;
+use std::time::Instant;
+
+/// A recognizer with the state of the events it produced.
+#[derive(Debug)]
+pub(crate) struct FeederCore {
+    pub(crate) recognizer: Recognizer,
+    partials: PartialTracker,
+    latency: Option<LatencyTracker>,
+    activity: Option<SpeechActivity>,
+    /// Events not taken by the feeder yet, oldest first.
+    pub(crate) events: VecDeque<Event>,
+}
+
+impl FeederCore {
+    pub(crate) fn new(recognizer: Recognizer) -> FeederCore {
+        FeederCore {
+            recognizer,
+            partials: PartialTracker::default(),
+            latency: None,
+            activity: None,
+            events: VecDeque::new(),
+        }
+    }
+    /// Feeds `samples`, queueing `Event::Final` if an utterance was completed,
+    /// or else `Event::Partial` if asked for `partials` and the partial result changed.
+    ///
+    /// Returns whether an utterance was completed.
+    pub(crate) fn feed(&mut self, samples: &[i16], partials: bool) -> bool {
+        if samples.is_empty() {
+            return false;
+        }
+        if let Some(latency) = &mut self.latency {
+            latency.submitted(Instant::now());
+        }
+        let completed = self.recognizer.accept_waveform(samples);
+        if let Some(latency) = &mut self.latency {
+            latency.processed();
+        }
+        if completed {
+            self.partials.reset();
+            self.speech_finalized();
+            let utterance = self.recognizer.result().into_owned();
+            self.push(Event::Final(utterance));
+        } else if partials {
+            let at = self.recognizer.audio_duration();
+            let partial = self.recognizer.partial_result();
+            if let Some(activity) = &mut self.activity {
+                self.events.extend(activity.partial(&partial.partial, at));
+            }
+            let changed = self.partials.changed(&partial.partial).map(str::to_string);
+            if let Some(text) = changed {
+                self.push(Event::Partial(text));
+            }
+        }
+        completed
+    }
+    /// Finalizes the utterance in progress.
+    ///
+    /// Speech in progress ends without an `Event::SpeechEnded`, see `speech_finalized`.
+    pub(crate) fn finish(&mut self) -> UtteranceOwned {
+        self.partials.reset();
+        if let Some(activity) = &mut self.activity {
+            activity.reset();
+        }
+        self.recognizer.final_result().into_owned()
+    }
+    /// Queues the end of the speech in progress, as the utterance is being finalized.
+    pub(crate) fn speech_finalized(&mut self) {
+        let at = self.recognizer.audio_duration();
+        if let Some(activity) = &mut self.activity {
+            self.events.extend(activity.finalized(at));
+        }
+    }
+    pub(crate) fn set_speech_activity(&mut self, activity: SpeechActivity) {
+        self.activity = Some(activity);
+    }
+    pub(crate) fn enable_latency(&mut self) {
+        self.latency.get_or_insert_with(LatencyTracker::default);
+    }
+    pub(crate) fn latency(&self) -> Option<LatencyStats> {
+        self.latency.as_ref().map(LatencyTracker::stats)
+    }
+    pub(crate) fn next_event(&mut self) -> Option<Event> {
+        self.events.pop_front()
+    }
+    fn push(&mut self, event: Event) {
+        if let Some(latency) = &mut self.latency {
+            latency.event(&event, Instant::now());
+        }
+        self.events.push_back(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::fake_model;
+
+    #[test]
+    fn empty_input_is_not_fed() {
+        let model = fake_model("model");
+        let recognizer = Recognizer::from_ptr(std::ptr::null_mut(), &model, 8000.0, None);
+        let mut core = FeederCore::new(recognizer);
+        core.enable_latency();
+        assert!(!core.feed(&[], true));
+        assert_eq!(core.recognizer.samples_processed(), 0);
+        assert_eq!(core.next_event(), None);
+        assert_eq!(core.latency().map(|stats| stats.count), Some(0));
+    }
+}
@@ -0,0 +1,210 @@
+use crate::Model;
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// How much a model takes on disk and, roughly, in memory, see `Model::footprint`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ModelFootprint {
+    pub disk_bytes: u64,
+    pub memory_bytes: u64,
+    /// Where `memory_bytes` comes from.
+    pub memory_estimate: MemoryEstimate,
+}
+
+/// How the memory of a model was estimated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryEstimate {
+    /// The growth of the resident memory of the process while the model loaded.
+    /// Other threads allocating or freeing meanwhile skew it.
+    ResidentDelta,
+    /// The size of the model files, where resident memory can't be measured.
+    /// Loaded models take about as much, as most of them is read into memory as is.
+    DiskSize,
+}
+
+impl Model {
+    /// The total size of the files in the model directory at `path`, such as
+    /// to check whether a device has room for a model before loading it.
+    ///
+    /// Symbolic links to files count with the size of the file;
+    /// links to directories aren't followed.
+    pub fn disk_size<P: AsRef<Path>>(path: P) -> io::Result<u64> {
+        let mut total = 0;
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            let mut kind = entry.file_type()?;
+            if kind.is_symlink() {
+                match fs::metadata(entry.path()) {
+                    Ok(target) if target.is_file() => kind = target.file_type(),
+                    _ => continue,
+                }
+            }
+            if kind.is_dir() {
+                total += Model::disk_size(entry.path())?;
+            } else if kind.is_file() {
+                total += fs::metadata(entry.path())?.len();
+            }
+        }
+        Ok(total)
+    }
+    /// About how much memory the model takes, measured while it loaded where
+    /// possible (on Linux and Android), otherwise the size of its files.
+    pub fn approx_memory_usage(&self) -> io::Result<u64> {
+        match self.inner.memory_delta {
+            Some(delta) => Ok(delta),
+            None => Model::disk_size(self.path()),
+        }
+    }
+    /// The size of the model on disk and its approximate memory usage.
+    pub fn footprint(&self) -> io::Result<ModelFootprint> {
+        let disk_bytes = Model::disk_size(self.path())?;
+        Ok(match self.inner.memory_delta {
+            Some(delta) => ModelFootprint {
+                disk_bytes,
+                memory_bytes: delta,
+                memory_estimate: MemoryEstimate::ResidentDelta,
+            },
+            None => ModelFootprint {
+                disk_bytes,
+                memory_bytes: disk_bytes,
+                memory_estimate: MemoryEstimate::DiskSize,
+            },
+        })
+    }
+}
+
+/// Tells how much memory the process has resident.
+pub(crate) trait MemoryProbe {
+    fn resident_bytes(&self) -> Option<u64>;
+}
+
+/// The memory of this process, as the operating system reports it.
+pub(crate) struct ProcessMemory;
+
+impl MemoryProbe for ProcessMemory {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn resident_bytes(&self) -> Option<u64> {
+        vm_rss(&fs::read_to_string("/proc/self/status").ok()?)
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn resident_bytes(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// Runs `load`, returning how much resident memory grew meanwhile if `probe` could tell.
+pub(crate) fn measure<T, F>(probe: &dyn MemoryProbe, load: F) -> (T, Option<u64>)
+where
+    F: FnOnce() -> T,
+{
+    let before = probe.resident_bytes();
+    let loaded = load();
+    let after = probe.resident_bytes();
+    let delta = before
+        .zip(after)
+        .map(|(before, after)| after.saturating_sub(before));
+    (loaded, delta)
+}
+
+/// The resident set size in `/proc/self/status`, given in kB.
+fn vm_rss(status: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let mut fields = line["VmRSS:".len()..].split_whitespace();
+    let kb: u64 = fields.next()?.parse().ok()?;
+    match fields.next() {
+        Some("kB") => Some(kb * 1024),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::fake_model;
+    use std::cell::RefCell;
+    use std::path::PathBuf;
+
+    /// Reports the given sizes in turn.
+    struct Readings(RefCell<Vec<Option<u64>>>);
+
+    impl MemoryProbe for Readings {
+        fn resident_bytes(&self) -> Option<u64> {
+            self.0.borrow_mut().remove(0)
+        }
+    }
+
+    fn model_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vosk-footprint-{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("am")).unwrap();
+        fs::create_dir_all(dir.join("graph/phones")).unwrap();
+        fs::write(dir.join("am/final.mdl"), [0; 1000]).unwrap();
+        fs::write(dir.join("graph/HCLr.fst"), [0; 300]).unwrap();
+        fs::write(dir.join("graph/phones/word_boundary.int"), [0; 20]).unwrap();
+        fs::write(dir.join("README"), "model\n").unwrap();
+        dir
+    }
+
+    #[test]
+    fn size_of_tree() {
+        let dir = model_dir("tree");
+        assert_eq!(Model::disk_size(&dir).unwrap(), 1326);
+        assert_eq!(Model::disk_size(dir.join("graph")).unwrap(), 320);
+        assert!(Model::disk_size(dir.join("missing")).is_err());
+    }
+    #[cfg(unix)]
+    #[test]
+    fn symbolic_links() {
+        let dir = model_dir("links");
+        std::os::unix::fs::symlink(dir.join("am/final.mdl"), dir.join("linked.mdl")).unwrap();
+        // A loop, which must not be followed.
+        std::os::unix::fs::symlink(&dir, dir.join("graph/self")).unwrap();
+        std::os::unix::fs::symlink(dir.join("gone"), dir.join("dangling")).unwrap();
+        assert_eq!(Model::disk_size(&dir).unwrap(), 2326);
+    }
+    #[test]
+    fn resident_delta() {
+        let probe = Readings(RefCell::new(vec![Some(100 << 20), Some(180 << 20)]));
+        let (value, delta) = measure(&probe, || 7);
+        assert_eq!((value, delta), (7, Some(80 << 20)));
+        // Memory freed by another thread meanwhile.
+        let shrunk = Readings(RefCell::new(vec![Some(100), Some(60)]));
+        assert_eq!(measure(&shrunk, || ()).1, Some(0));
+        let unknown = Readings(RefCell::new(vec![None, Some(60)]));
+        assert_eq!(measure(&unknown, || ()).1, None);
+    }
+    #[test]
+    fn status_parsing() {
+        let status = "Name:\tcargo\nVmPeak:\t  900 kB\nVmRSS:\t   51200 kB\nThreads:\t4\n";
+        assert_eq!(vm_rss(status), Some(50 << 20));
+        assert_eq!(vm_rss("VmRSS:\t12 MB\n"), None);
+        assert_eq!(vm_rss("Name:\tx\n"), None);
+    }
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn this_process() {
+        assert!(ProcessMemory.resident_bytes().unwrap() > 0);
+    }
+    #[test]
+    fn estimate_without_measurement() {
+        let dir = model_dir("estimate");
+        let model = fake_model(dir.to_str().unwrap());
+        let footprint = model.footprint().unwrap();
+        assert_eq!(
+            footprint,
+            ModelFootprint {
+                disk_bytes: 1326,
+                memory_bytes: 1326,
+                memory_estimate: MemoryEstimate::DiskSize,
+            }
+        );
+        assert_eq!(model.approx_memory_usage().unwrap(), 1326);
+        assert_eq!(
+            serde_json::to_string(&footprint).unwrap(),
+            r#"{"disk_bytes":1326,"memory_bytes":1326,"memory_estimate":"disk_size"}"#
+        );
+    }
+}
@@ -0,0 +1,378 @@
+use crate::{Error, Model, WordForm, WordMatch};
+use serde_json::to_writer;
+use std::collections::HashSet;
+use std::ffi::CString;
+use std::fmt;
+use std::iter::Map;
+use std::slice;
+use std::str::SplitWhitespace;
+
+/// Phrases a recognizer is restricted to, for `Recognizer::with_grammar` and `set_grammar`.
+///
+/// ```no_run
+/// # use vosk::{Grammar, Model, Recognizer};
+/// # let model = Model::new("model").unwrap();
+/// let grammar = Grammar::new(["yes", "no", "[unk]"]);
+/// let recognizer = Recognizer::with_grammar(&model, 16000.0, &grammar);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Grammar {
+    phrases: Vec<String>,
+}
+
+impl Grammar {
+    /// Phrases are made of words separated by whitespace.
+    pub fn new<I, S>(phrases: I) -> Grammar
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Grammar::default().with_phrases(phrases)
+    }
+    /// Adds more phrases.
+    pub fn with_phrases<I, S>(mut self, phrases: I) -> Grammar
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.phrases.extend(
+            phrases
+                .into_iter()
+                .map(|p| p.as_ref().split_whitespace().collect::<Vec<_>>().join(" ")),
+        );
+        self
+    }
+    /// Adds "[unk]", so that other speech is recognized as unknown
+    /// instead of as the phrase that sounds closest.
+    pub fn with_unknown(self) -> Grammar {
+        self.with_phrases(["[unk]"])
+    }
+    /// The phrases, with words separated by single spaces.
+    pub fn phrases(&self) -> &[String] {
+        &self.phrases
+    }
+    pub fn len(&self) -> usize {
+        self.phrases.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.phrases.is_empty()
+    }
+    /// Checks that the model knows every word, as libvosk silently leaves out
+    /// the phrases with words it doesn't know.
+    ///
+    /// Words are matched exactly, "[unk]" excepted. When a word is only known
+    /// in another case or Unicode form, that form is suggested.
+    pub fn validate(&self, model: &Model) -> Result<(), OovError> {
+        self.validate_with(|word| model.find_word_normalized(word))
+    }
+    /// Returns the grammar if it passes `validate`.
+    pub fn build_validated(self, model: &Model) -> Result<Grammar, OovError> {
+        self.validate(model)?;
+        Ok(self)
+    }
+    fn validate_with<F>(&self, find: F) -> Result<(), OovError>
+    where
+        F: Fn(&str) -> Option<WordMatch>,
+    {
+        let mut words = Vec::new();
+        for phrase in &self.phrases {
+            for word in phrase.split(' ').filter(|w| *w != UNKNOWN) {
+                if words
+                    .iter()
+                    .any(|w: &OovWord| w.word == word && w.phrase == *phrase)
+                {
+                    continue;
+                }
+                let suggestion = match find(word) {
+                    Some(found) if found.form == WordForm::Exact => continue,
+                    found => found.map(|found| found.word),
+                };
+                words.push(OovWord {
+                    word: word.to_string(),
+                    phrase: phrase.clone(),
+                    suggestion,
+                });
+            }
+        }
+        if words.is_empty() {
+            Ok(())
+        } else {
+            Err(OovError { words })
+        }
+    }
+}
+
+/// The word libvosk gives to speech outside of the grammar.
+const UNKNOWN: &str = "[unk]";
+
+/// Words of a grammar the model doesn't know, see `Grammar::validate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OovError {
+    /// In order of appearance, once per phrase.
+    pub words: Vec<OovWord>,
+}
+
+/// A word the model doesn't know.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OovWord {
+    pub word: String,
+    /// The phrase of the grammar it's in.
+    pub phrase: String,
+    /// The form of the word the model knows, if only the case or Unicode form differs.
+    pub suggestion: Option<String>,
+}
+
+impl fmt::Display for OovError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "words not known to the model:")?;
+        for (i, oov) in self.words.iter().enumerate() {
+            let sep = if i == 0 { " " } else { ", " };
+            write!(f, "{}\"{}\" in \"{}\"", sep, oov.word, oov.phrase)?;
+            if let Some(suggestion) = &oov.suggestion {
+                write!(f, " (did you mean \"{}\"?)", suggestion)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Lists each word once.
+impl From<OovError> for Error {
+    fn from(e: OovError) -> Self {
+        let mut words: Vec<String> = Vec::new();
+        for oov in e.words {
+            if !words.contains(&oov.word) {
+                words.push(oov.word);
+            }
+        }
+        Error::OutOfVocabulary(words)
+    }
+}
+
+/// The words of each phrase, the form `with_grammar` takes.
+impl<'g> IntoIterator for &'g Grammar {
+    type Item = SplitWhitespace<'g>;
+    type IntoIter = Map<slice::Iter<'g, String>, fn(&'g String) -> SplitWhitespace<'g>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.phrases.iter().map(|p| p.split_whitespace())
+    }
+}
+
+/// The phrases as the JSON list libvosk takes, and how many there are.
+///
+/// Words are separated by single spaces. Empty phrases are left out, as libvosk
+/// has no use for them, and repeated ones kept once.
+/// Fails with `Error::NulInInput` for a phrase with a NUL byte, giving its position
+/// in the phrase, as libvosk would cut the phrase short there.
+pub(crate) fn grammar_json<I, P, S>(phrases: I) -> Result<(CString, usize), Error>
+where
+    P: IntoIterator<Item = S>,
+    I: IntoIterator<Item = P>,
+    S: AsRef<str>,
+{
+    let mut phrase_list: Vec<String> = Vec::new();
+    let mut seen = HashSet::new();
+    let mut empty = 0;
+    for words in phrases {
+        let mut phrase = String::new();
+        // A word may itself hold several, as from splitting on lines only.
+        for word in words.into_iter() {
+            for word in word.as_ref().split_whitespace() {
+                if !phrase.is_empty() {
+                    phrase.push(' ');
+                }
+                phrase.push_str(word);
+            }
+        }
+        if let Some(pos) = phrase.find('\0') {
+            return Err(Error::NulInInput(pos));
+        }
+        if phrase.is_empty() {
+            empty += 1;
+        } else if seen.insert(phrase.clone()) {
+            phrase_list.push(phrase);
+        }
+    }
+    if empty > 0 {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(empty, "left empty phrases out of the grammar");
+    }
+    if phrase_list.is_empty() {
+        return Err(Error::EmptyGrammar);
+    }
+    let mut writer = Vec::with_capacity(128);
+    to_writer(&mut writer, &phrase_list).expect("strings serialize to a Vec");
+    // Other control characters are escaped.
+    let cstr = CString::new(writer).expect("JSON has no NUL bytes");
+    Ok((cstr, phrase_list.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use crate::vocabulary::find_normalized;
+    use proptest::prelude::*;
+
+    fn validate(grammar: &Grammar) -> Result<(), OovError> {
+        let words = ["hello", "world", "turn", "on", "the", "new-york"];
+        grammar.validate_with(|word| {
+            find_normalized(word, |w| {
+                words.iter().position(|k| *k == w).map(|i| i as u32)
+            })
+        })
+    }
+
+    #[test]
+    fn known_words() {
+        let grammar = Grammar::new(["hello  world", "turn on the", "new-york"]).with_unknown();
+        assert_eq!(validate(&grammar), Ok(()));
+    }
+    #[test]
+    fn out_of_vocabulary() {
+        let grammar = Grammar::new([
+            "Hello world",
+            "turn on the lights",
+            "lights on",
+            "lights lights",
+        ]);
+        let e = validate(&grammar).unwrap_err();
+        let found: Vec<_> = e
+            .words
+            .iter()
+            .map(|w| (w.word.as_str(), w.phrase.as_str(), w.suggestion.as_deref()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("Hello", "Hello world", Some("hello")),
+                ("lights", "turn on the lights", None),
+                ("lights", "lights on", None),
+                ("lights", "lights lights", None),
+            ]
+        );
+        assert_eq!(
+            Error::from(e),
+            Error::OutOfVocabulary(vec!["Hello".to_string(), "lights".to_string()])
+        );
+        // Punctuation is part of a word for libvosk.
+        let e = validate(&Grammar::new(["hello, world", "new york"])).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "words not known to the model: \"hello,\" in \"hello, world\", \"new\" in \"new york\", \"york\" in \"new york\""
+        );
+    }
+    fn json<I, P, S>(phrases: I) -> Result<String, Error>
+    where
+        P: IntoIterator<Item = S>,
+        I: IntoIterator<Item = P>,
+        S: AsRef<str>,
+    {
+        grammar_json(phrases).map(|(json, _)| json.into_string().unwrap())
+    }
+    #[test]
+    fn grammar_phrases() {
+        let grammar = Grammar::new(["turn  on", " lights "]).with_unknown();
+        assert_eq!(grammar.phrases(), ["turn on", "lights", "[unk]"]);
+        let (cstr, count) = grammar_json(&grammar).unwrap();
+        assert_eq!(cstr.to_str().unwrap(), r#"["turn on","lights","[unk]"]"#);
+        assert_eq!(count, 3);
+        let nested = vec![vec!["a", "b"], vec![]];
+        assert_eq!(json(nested).unwrap(), r#"["a b"]"#);
+    }
+    #[test]
+    fn messy_grammars() {
+        let lines = "yes\n\n  no \n\t\nyes\nno thanks\n";
+        assert_eq!(
+            json(lines.lines().map(Some)).unwrap(),
+            r#"["yes","no","no thanks"]"#
+        );
+        // Words with whitespace in them, or nothing but whitespace.
+        let words = vec![vec!["turn on ", "", "the\tlights"], vec![" ", "\n"]];
+        assert_eq!(json(words).unwrap(), r#"["turn on the lights"]"#);
+        let spaced = vec![
+            vec!["good", "morning"],
+            vec!["good morning"],
+            vec![" good  morning"],
+        ];
+        let (_, count) = grammar_json(spaced).unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(
+            json(vec![vec!["Yes"], vec!["yes"], vec!["say \"yes\""]]).unwrap(),
+            r#"["Yes","yes","say \"yes\""]"#
+        );
+        // Escaped by JSON, but libvosk would cut the phrase short.
+        assert_eq!(
+            json(vec![vec!["ok"], vec!["a", "n\0l"]]),
+            Err(Error::NulInInput(3))
+        );
+    }
+    #[test]
+    fn empty_grammars() {
+        assert_eq!(json(Vec::<Vec<String>>::new()), Err(Error::EmptyGrammar));
+        assert_eq!(json(vec![vec![""], vec!["  "]]), Err(Error::EmptyGrammar));
+        assert_eq!(json(&Grammar::new([" ", "\t"])), Err(Error::EmptyGrammar));
+        let model = test_util::fake_model("model");
+        let recognizer =
+            crate::Recognizer::try_with_grammar(&model, 16000.0, vec![Vec::<&str>::new()]);
+        assert_eq!(recognizer.unwrap_err(), Error::EmptyGrammar);
+        let mut recognizer =
+            crate::Recognizer::from_ptr(std::ptr::null_mut(), &model, 16000.0, Some(2));
+        assert_eq!(
+            recognizer.try_set_grammar(Vec::<Vec<&str>>::new()),
+            Err(Error::EmptyGrammar)
+        );
+        assert_eq!(recognizer.grammar, Some(2));
+    }
+    #[test]
+    #[should_panic(expected = "Invalid grammar: EmptyGrammar")]
+    fn empty_vocabulary() {
+        crate::Recognizer::with_vocabulary(&test_util::fake_model("model"), 16000.0, " \n ");
+    }
+
+    /// What `grammar_json` should make of `phrases`, done the obvious way.
+    fn normalized(phrases: &[Vec<String>]) -> Vec<String> {
+        let mut expected: Vec<String> = Vec::new();
+        for words in phrases {
+            let phrase = words
+                .iter()
+                .flat_map(|w| w.split_whitespace())
+                .collect::<Vec<_>>()
+                .join(" ");
+            if !phrase.is_empty() && !expected.contains(&phrase) {
+                expected.push(phrase);
+            }
+        }
+        expected
+    }
+    /// Words of any characters, often with whitespace, quotes, backslashes and NUL.
+    fn word() -> impl Strategy<Value = String> {
+        prop_oneof![
+            any::<String>(),
+            "\\PC{0,8}",
+            "[ a\"\\\n\t\u{0}\u{1}\u{7f}\u{a0}\u{2028}é😀]{0,8}",
+        ]
+    }
+    proptest! {
+        // Miri runs each case thousands of times slower.
+        #![proptest_config(ProptestConfig::with_cases(if cfg!(miri) { 4 } else { 256 }))]
+        #[test]
+        fn json_round_trip(phrases in prop::collection::vec(prop::collection::vec(word(), 0..4), 0..6)) {
+            let expected = normalized(&phrases);
+            match grammar_json(&phrases) {
+                Ok((cstr, count)) => {
+                    let parsed: Vec<String> = serde_json::from_slice(cstr.as_bytes()).unwrap();
+                    prop_assert_eq!(&parsed, &expected);
+                    prop_assert_eq!(count, expected.len());
+                }
+                Err(Error::NulInInput(pos)) => {
+                    let phrase = expected.iter().find(|p| p.contains('\0')).unwrap();
+                    prop_assert_eq!(phrase.find('\0'), Some(pos));
+                }
+                Err(Error::EmptyGrammar) => prop_assert!(expected.is_empty()),
+                Err(e) => prop_assert!(false, "unexpected error {:?}", e),
+            }
+        }
+    }
+}
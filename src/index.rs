@@ -0,0 +1,204 @@
+//! Searching accumulated transcripts for words and phrases.
+//!
+//! Only utterances with word details are searchable,
+//! see `Recognizer::set_words`.
+
+use crate::RecognizedText;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Where a word, or the first word of a phrase, was found.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Occurrence {
+    /// Position of the utterance in the order it was added to the index.
+    pub utterance_idx: usize,
+    /// Position of the word within the utterance.
+    pub word_idx: usize,
+    /// Start of the word in seconds.
+    pub start: f32,
+    /// End of the word in seconds; for phrases, the end of the last word.
+    pub end: f32,
+}
+
+/// A word → occurrences map over a growing list of utterances.
+///
+/// Words are compared in lowercase,
+/// and optionally reduced to a stem with a function given to `with_stemmer`.
+///
+/// The index can be saved and loaded with serde. The stemmer isn't saved,
+/// set it again with `set_stemmer` after loading.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct TranscriptIndex {
+    occurrences: HashMap<String, Vec<Occurrence>>,
+    /// Normalized words of every utterance, for matching phrases.
+    utterances: Vec<Vec<String>>,
+    #[serde(skip)]
+    stemmer: Option<fn(&str) -> String>,
+}
+
+impl TranscriptIndex {
+    pub fn new() -> TranscriptIndex {
+        TranscriptIndex::default()
+    }
+    /// Creates an index that looks up words by `stemmer(word)`.
+    /// The stemmer gets words already in lowercase.
+    pub fn with_stemmer(stemmer: fn(&str) -> String) -> TranscriptIndex {
+        TranscriptIndex {
+            stemmer: Some(stemmer),
+            ..TranscriptIndex::default()
+        }
+    }
+    /// Sets the stemmer of a loaded index.
+    ///
+    /// It should be the one the index was built with,
+    /// otherwise words added before and after won't match.
+    pub fn set_stemmer(&mut self, stemmer: Option<fn(&str) -> String>) {
+        self.stemmer = stemmer;
+    }
+    /// Adds the words of the next utterance.
+    ///
+    /// Every utterance takes a position, even if it has no word details.
+    pub fn push(&mut self, utterance: &RecognizedText) {
+        let utterance_idx = self.utterances.len();
+        let mut normalized = Vec::new();
+        for (word_idx, word) in utterance.words().iter().enumerate() {
+            let key = self.normalize(word.word());
+            self.occurrences
+                .entry(key.clone())
+                .or_default()
+                .push(Occurrence {
+                    utterance_idx,
+                    word_idx,
+                    start: word.start(),
+                    end: word.end(),
+                });
+            normalized.push(key);
+        }
+        self.utterances.push(normalized);
+    }
+    /// Number of utterances added.
+    pub fn len(&self) -> usize {
+        self.utterances.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.utterances.is_empty()
+    }
+    /// All occurrences of `word`, in order.
+    pub fn find(&self, word: &str) -> &[Occurrence] {
+        self.occurrences
+            .get(&self.normalize(word))
+            .map_or(&[], Vec::as_slice)
+    }
+    /// All occurrences of consecutive `words` within one utterance, in order.
+    ///
+    /// A phrase split between two utterances is not found.
+    pub fn find_phrase(&self, words: &[&str]) -> Vec<Occurrence> {
+        let (first, rest) = match words.split_first() {
+            Some(split) => split,
+            None => return Vec::new(),
+        };
+        let rest: Vec<String> = rest.iter().map(|w| self.normalize(w)).collect();
+        self.find(first)
+            .iter()
+            .filter_map(|first| {
+                let utterance = &self.utterances[first.utterance_idx];
+                let following =
+                    utterance.get(first.word_idx + 1..first.word_idx + 1 + rest.len())?;
+                if following != rest.as_slice() {
+                    return None;
+                }
+                let end = match rest.last() {
+                    Some(last) => {
+                        self.occurrences[last]
+                            .iter()
+                            .find(|o| {
+                                o.utterance_idx == first.utterance_idx
+                                    && o.word_idx == first.word_idx + rest.len()
+                            })?
+                            .end
+                    }
+                    None => first.end,
+                };
+                Some(Occurrence { end, ..*first })
+            })
+            .collect()
+    }
+    fn normalize(&self, word: &str) -> String {
+        let word = word.to_lowercase();
+        match self.stemmer {
+            Some(stem) => stem(&word),
+            None => word,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::utterance;
+
+    fn index() -> TranscriptIndex {
+        let mut index = TranscriptIndex::new();
+        index.push(&utterance(&[
+            ("send", 0.0, 0.3),
+            ("the", 0.3, 0.4),
+            ("purchase", 0.4, 0.9),
+            ("order", 0.9, 1.2),
+        ]));
+        index.push(&utterance(&[("purchase", 2.0, 2.5)]));
+        index.push(&utterance(&[("order", 3.0, 3.4), ("Purchase", 3.5, 4.0)]));
+        index
+    }
+
+    #[test]
+    fn repeated_word() {
+        let index = index();
+        let found: Vec<_> = index
+            .find("purchase")
+            .iter()
+            .map(|o| (o.utterance_idx, o.word_idx))
+            .collect();
+        assert_eq!(found, vec![(0, 2), (1, 0), (2, 1)]);
+        assert!(index.find("invoice").is_empty());
+    }
+    #[test]
+    fn phrase() {
+        let index = index();
+        let found = index.find_phrase(&["PURCHASE", "order"]);
+        assert_eq!(
+            found,
+            vec![Occurrence {
+                utterance_idx: 0,
+                word_idx: 2,
+                start: 0.4,
+                end: 1.2,
+            }]
+        );
+    }
+    #[test]
+    fn phrase_across_utterances() {
+        // "purchase" ends utterance 1 and "order" starts utterance 2.
+        let index = index();
+        assert_eq!(index.find_phrase(&["purchase", "order"]).len(), 1);
+        assert!(index
+            .find_phrase(&["purchase", "order", "purchase"])
+            .is_empty());
+        assert!(index.find_phrase(&[]).is_empty());
+    }
+    #[test]
+    fn stemmer() {
+        fn strip_s(word: &str) -> String {
+            word.trim_end_matches('s').to_string()
+        }
+        let mut index = TranscriptIndex::with_stemmer(strip_s);
+        index.push(&utterance(&[("orders", 0.0, 0.5)]));
+        assert_eq!(index.find("order").len(), 1);
+    }
+    #[test]
+    fn serde_round_trip() {
+        let json = serde_json::to_string(&index()).unwrap();
+        let loaded: TranscriptIndex = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.len(), 3);
+        assert_eq!(loaded.find("order"), index().find("order"));
+    }
+}
@@ -0,0 +1,368 @@
+//! Voice commands as intents, in the JSON that Rhasspy and home automation software
+//! speaking the Hermes protocol expect.
+//!
+//! ```no_run
+//! # use vosk::{intent::IntentSet, Model};
+//! # fn main() -> Result<(), vosk::Error> {
+//! # let model = Model::new("model")?;
+//! let mut intents = IntentSet::builder()
+//!     .intent("SetTemperature", "set temperature to {number}")
+//!     .intent("LightsOn", "turn on the lights")
+//!     .build(&model, 16000.0)?;
+//! # let samples = [0; 1600];
+//! if let Some(intent) = intents.feed(&samples) {
+//!     // {"input":"set temperature to 21","intent":{"name":"SetTemperature",...
+//!     println!("{}", serde_json::to_string(&intent).unwrap());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Slots are declared in the phrases as for a `CommandSet`.
+
+use crate::command::{
+    slot_words, CommandHit, CommandSet, CommandSetBuilder, FuzzyMatcher, Slot, SlotValue,
+};
+use crate::{Error, Model, OovError, Recognizer, SampleRate};
+use serde::{Serialize, Serializer};
+
+/// Collects the intents of an `IntentSet`.
+#[derive(Debug, Clone)]
+pub struct IntentSetBuilder {
+    commands: CommandSetBuilder<String>,
+}
+
+impl IntentSetBuilder {
+    /// Recognizes `phrase` as the intent `name`, such as "SetTemperature".
+    pub fn intent(self, name: &str, phrase: &str) -> Self {
+        self.intent_phrases(name, [phrase])
+    }
+    pub fn intent_phrases<I, S>(self, name: &str, phrases: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        IntentSetBuilder {
+            commands: self.commands.command_phrases(phrases, name.to_string()),
+        }
+    }
+    /// Accepts results close to a phrase without slots, see `CommandSetBuilder::fuzzy`.
+    pub fn fuzzy(self, matcher: FuzzyMatcher) -> Self {
+        IntentSetBuilder {
+            commands: self.commands.fuzzy(matcher),
+        }
+    }
+    /// Fails like `CommandSetBuilder::build`.
+    pub fn build(self, model: &Model, sample_rate: f32) -> Result<IntentSet, Error> {
+        Ok(IntentSet {
+            commands: self.commands.build(model, sample_rate)?,
+        })
+    }
+    /// Like `build`, but tells which phrase each unknown word is in.
+    pub fn build_validated(
+        self,
+        model: &Model,
+        sample_rate: SampleRate,
+    ) -> Result<IntentSet, OovError> {
+        Ok(IntentSet {
+            commands: self.commands.build_validated(model, sample_rate)?,
+        })
+    }
+}
+
+/// A recognizer for a fixed set of phrases, each standing for a named intent.
+#[derive(Debug)]
+pub struct IntentSet {
+    commands: CommandSet<String>,
+}
+
+impl IntentSet {
+    pub fn builder() -> IntentSetBuilder {
+        IntentSetBuilder {
+            commands: CommandSet::builder(),
+        }
+    }
+    /// Feeds audio, returning the intent when an utterance ends with one.
+    pub fn feed(&mut self, wave: &[i16]) -> Option<Intent> {
+        self.commands.feed(wave).map(Intent::from)
+    }
+    /// Returns the intent at the end of the audio, if the last utterance is one.
+    pub fn finish(&mut self) -> Option<Intent> {
+        self.commands.finish().map(Intent::from)
+    }
+    pub fn recognizer(&self) -> &Recognizer {
+        self.commands.recognizer()
+    }
+    pub fn recognizer_mut(&mut self) -> &mut Recognizer {
+        self.commands.recognizer_mut()
+    }
+}
+
+/// A recognized intent with its slots.
+///
+/// Serializes to JSON with the keys of both Rhasspy's intent JSON and Hermes
+/// `hermes/intent` messages: `input` and `text` hold the text with numbers in digits,
+/// `rawInput` and `raw_text` as recognized. The ranges of slots are character offsets
+/// in those.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Intent {
+    pub name: String,
+    pub slots: Vec<Slot>,
+    /// Mean confidence of the words, 1 without word details,
+    /// times the similarity for fuzzy matches.
+    pub confidence: f32,
+    /// The words as recognized, such as "set temperature to twenty one".
+    pub raw_text: String,
+    /// The words with slot values instead, such as "set temperature to 21".
+    pub text: String,
+}
+
+impl<T: AsRef<str>> From<CommandHit<T>> for Intent {
+    fn from(hit: CommandHit<T>) -> Intent {
+        Intent {
+            name: hit.value.as_ref().to_string(),
+            confidence: hit.confidence.unwrap_or(1.0) * hit.score,
+            text: text_with_values(&hit).unwrap_or_else(|| hit.text.clone()),
+            raw_text: hit.text,
+            slots: hit.slots,
+        }
+    }
+}
+
+/// The text of `hit` with slot values in place of their words.
+fn text_with_values<T>(hit: &CommandHit<T>) -> Option<String> {
+    let words: Vec<&str> = hit.text.split(' ').collect();
+    let filled = slot_words(&hit.phrase, &words)?;
+    let mut text: Vec<String> = Vec::new();
+    let mut next = 0;
+    for (slot, range) in hit.slots.iter().zip(filled) {
+        text.extend(words[next..range.start].iter().map(|w| w.to_string()));
+        text.push(slot.value.to_string());
+        next = range.end;
+    }
+    text.extend(words[next..].iter().map(|w| w.to_string()));
+    Some(text.join(" "))
+}
+
+impl Serialize for Intent {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let ranges =
+            slot_ranges(self).unwrap_or_else(|| vec![SlotRange::default(); self.slots.len()]);
+        let slots = self
+            .slots
+            .iter()
+            .zip(ranges)
+            .map(|(slot, range)| HermesSlot {
+                entity: slot.value.entity(),
+                slot_name: &slot.name,
+                raw_value: &slot.raw_value,
+                value: match slot.value {
+                    SlotValue::Number(value) => HermesValue {
+                        kind: "Number",
+                        value,
+                    },
+                },
+                confidence: slot.confidence.unwrap_or(1.0),
+                range,
+            })
+            .collect();
+        HermesIntent {
+            input: &self.text,
+            raw_input: &self.raw_text,
+            text: &self.text,
+            raw_text: &self.raw_text,
+            intent: IntentName {
+                name: &self.name,
+                intent_name: &self.name,
+                confidence: self.confidence,
+                confidence_score: self.confidence,
+            },
+            slots,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Where the slots of `intent` are in its texts, in characters.
+/// None if they aren't there, as when the intent was changed.
+fn slot_ranges(intent: &Intent) -> Option<Vec<SlotRange>> {
+    let chars = |text: &str, bytes: usize| text[..bytes].chars().count();
+    let mut ranges = Vec::new();
+    let (mut start, mut raw_start) = (0, 0);
+    for slot in &intent.slots {
+        let value = slot.value.to_string();
+        let at = start + find_words(&intent.text[start..], &value)?;
+        let raw_at = raw_start + find_words(&intent.raw_text[raw_start..], &slot.raw_value)?;
+        start = at + value.len();
+        raw_start = raw_at + slot.raw_value.len();
+        ranges.push(SlotRange {
+            start: chars(&intent.text, at),
+            end: chars(&intent.text, start),
+            raw_start: chars(&intent.raw_text, raw_at),
+            raw_end: chars(&intent.raw_text, raw_start),
+        });
+    }
+    Some(ranges)
+}
+
+/// Where `words` first appear in `text` as whole words.
+fn find_words(text: &str, words: &str) -> Option<usize> {
+    text.match_indices(words).map(|(i, _)| i).find(|&i| {
+        let before = text[..i].chars().next_back();
+        let after = text[i + words.len()..].chars().next();
+        before.is_none_or(|c| c == ' ') && after.is_none_or(|c| c == ' ')
+    })
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HermesIntent<'a> {
+    input: &'a str,
+    raw_input: &'a str,
+    text: &'a str,
+    #[serde(rename = "raw_text")]
+    raw_text: &'a str,
+    intent: IntentName<'a>,
+    slots: Vec<HermesSlot<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct IntentName<'a> {
+    name: &'a str,
+    intent_name: &'a str,
+    confidence: f32,
+    confidence_score: f32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HermesSlot<'a> {
+    entity: &'static str,
+    slot_name: &'a str,
+    raw_value: &'a str,
+    value: HermesValue,
+    confidence: f32,
+    range: SlotRange,
+}
+
+#[derive(Serialize)]
+struct HermesValue {
+    kind: &'static str,
+    value: i64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SlotRange {
+    start: usize,
+    end: usize,
+    raw_start: usize,
+    raw_end: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::segment::TimeRange;
+
+    fn number(name: &str, raw_value: &str, value: i64) -> Slot {
+        Slot {
+            name: name.to_string(),
+            raw_value: raw_value.to_string(),
+            value: SlotValue::Number(value),
+            confidence: Some(0.5),
+            range: None,
+        }
+    }
+    fn hit(phrase: &str, text: &str, slots: Vec<Slot>) -> CommandHit<&'static str> {
+        CommandHit {
+            value: "SetTemperature",
+            phrase: phrase.to_string(),
+            score: 1.0,
+            confidence: Some(0.75),
+            range: Some(TimeRange {
+                start: 0.0,
+                end: 1.5,
+            }),
+            text: text.to_string(),
+            slots,
+        }
+    }
+
+    #[test]
+    fn hermes_json() {
+        let slots = vec![number("number", "twenty one", 21)];
+        let hit = hit(
+            "set temperature to {number}",
+            "set temperature to twenty one",
+            slots,
+        );
+        let intent = Intent::from(hit);
+        assert_eq!(intent.text, "set temperature to 21");
+        assert_eq!(intent.raw_text, "set temperature to twenty one");
+        let json: serde_json::Value = serde_json::to_value(&intent).unwrap();
+        let expected = serde_json::json!({
+            "input": "set temperature to 21",
+            "rawInput": "set temperature to twenty one",
+            "text": "set temperature to 21",
+            "raw_text": "set temperature to twenty one",
+            "intent": {
+                "name": "SetTemperature",
+                "intentName": "SetTemperature",
+                "confidence": 0.75,
+                "confidenceScore": 0.75,
+            },
+            "slots": [{
+                "entity": "number",
+                "slotName": "number",
+                "rawValue": "twenty one",
+                "value": {"kind": "Number", "value": 21},
+                "confidence": 0.5,
+                "range": {"start": 19, "end": 21, "rawStart": 19, "rawEnd": 29},
+            }],
+        });
+        assert_eq!(json, expected);
+    }
+    #[test]
+    fn several_slots() {
+        let slots = vec![number("low", "one", 1), number("high", "one hundred", 100)];
+        let intent = Intent::from(hit(
+            "from {low:number} to {high:number} please",
+            "from one to one hundred please",
+            slots,
+        ));
+        assert_eq!(intent.text, "from 1 to 100 please");
+        let json = serde_json::to_value(&intent).unwrap();
+        let ranges: Vec<_> = json["slots"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| {
+                let r = &s["range"];
+                [&r["start"], &r["end"], &r["rawStart"], &r["rawEnd"]].map(|n| n.as_u64().unwrap())
+            })
+            .collect();
+        assert_eq!(ranges, [[5, 6, 5, 8], [10, 13, 12, 23]]);
+    }
+    #[test]
+    fn without_slots() {
+        let mut hit = hit("turn on the lights", "turn on [unk] lights", Vec::new());
+        hit.score = 0.75;
+        hit.confidence = None;
+        let intent = Intent::from(hit);
+        assert_eq!(intent.text, intent.raw_text);
+        assert_eq!(intent.confidence, 0.75);
+        let json = serde_json::to_value(&intent).unwrap();
+        assert_eq!(json["slots"], serde_json::json!([]));
+        assert_eq!(json["intent"]["name"], "SetTemperature");
+    }
+    #[test]
+    fn invalid_sample_rate() {
+        let model = crate::test_util::fake_model("model");
+        let built = IntentSet::builder()
+            .intent_phrases("Stop", ["stop"])
+            .build(&model, f32::INFINITY);
+        assert_eq!(built.err(), Some(Error::InvalidSampleRate(f32::INFINITY)));
+    }
+}
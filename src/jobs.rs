@@ -0,0 +1,788 @@
+//! A queue of transcriptions run by a few worker threads, for services where users
+//! submit files and come back for the text.
+//!
+//! Each worker keeps a recognizer, reusing it for jobs at the same sample rate.
+//! Jobs with a higher priority run first, and their status can be polled, waited
+//! for, or awaited:
+//!
+//! ```no_run
+//! # use vosk::jobs::{Job, JobQueue, JobStatus, QueueOptions};
+//! # use vosk::source::MemorySource;
+//! # use vosk::Model;
+//! # fn main() -> Result<(), vosk::Error> {
+//! # let (meeting, note) = (vec![0; 16000], vec![0; 1600]);
+//! let model = Model::new("model")?;
+//! let queue = JobQueue::new(&model, QueueOptions::default());
+//! queue.submit(Job::new("meeting", MemorySource::new(meeting, 16000)))?;
+//! queue.submit(Job::new("note", MemorySource::new(note, 16000)).with_priority(10))?;
+//! if let Some(JobStatus::Done(utterances)) = queue.wait("meeting") {
+//!     for utterance in utterances {
+//!         println!("{}", utterance.text);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Finished jobs are kept until `collect`ed, or dropped once they're older than
+//! `QueueOptions::result_ttl`. A job whose transcription panics fails with
+//! `Error::Panicked`, and its worker goes on with a new recognizer.
+
+use crate::source::{transcribe_source_with_progress, AudioSource};
+use crate::{
+    CancellationToken, Error, Model, Progress, ProgressReporter, Recognizer, UtteranceOwned,
+};
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How many workers a `JobQueue` runs and how long it keeps results.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueueOptions {
+    /// Jobs run at the same time, each with a recognizer of its own.
+    pub workers: usize,
+    /// How long a finished job is kept when nobody collects it.
+    pub result_ttl: Duration,
+}
+
+impl Default for QueueOptions {
+    fn default() -> Self {
+        QueueOptions {
+            workers: 2,
+            result_ttl: Duration::from_secs(600),
+        }
+    }
+}
+
+/// How the audio of a job is recognized.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JobOptions {
+    /// See `Recognizer::set_words`.
+    pub words: bool,
+    /// Restricts recognition to these phrases. The job then gets a recognizer
+    /// of its own rather than the worker's.
+    pub grammar: Option<Vec<String>>,
+}
+
+/// Audio to transcribe, see `JobQueue::submit`.
+pub struct Job {
+    /// Names the job in the queue, it must not be the same as another's that's still kept.
+    pub id: String,
+    pub audio_source: Box<dyn AudioSource + Send>,
+    pub options: JobOptions,
+    /// Jobs with higher priorities run first, jobs of the same priority in order.
+    pub priority: i32,
+}
+
+impl Job {
+    /// A job with the default options and priority 0.
+    pub fn new<S: AudioSource + Send + 'static>(id: impl Into<String>, audio_source: S) -> Self {
+        Job {
+            id: id.into(),
+            audio_source: Box::new(audio_source),
+            options: JobOptions::default(),
+            priority: 0,
+        }
+    }
+    pub fn with_options(mut self, options: JobOptions) -> Self {
+        self.options = options;
+        self
+    }
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+}
+
+impl fmt::Debug for Job {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Job")
+            .field("id", &self.id)
+            .field("sample_rate", &self.audio_source.sample_rate())
+            .field("options", &self.options)
+            .field("priority", &self.priority)
+            .finish()
+    }
+}
+
+/// Where a job is at.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobStatus {
+    /// Waiting for a worker, after `position` other jobs.
+    Queued { position: usize },
+    /// Being transcribed. The progress is None until the first report.
+    Running { progress: Option<Progress> },
+    /// The utterances of the audio, leaving out empty ones.
+    Done(Vec<UtteranceOwned>),
+    /// The job failed, or was cancelled with `Error::Cancelled`.
+    Failed(Error),
+}
+
+impl JobStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobStatus::Done(_) | JobStatus::Failed(_))
+    }
+}
+
+/// Runs transcription jobs on worker threads, see the module documentation.
+///
+/// Dropping the queue cancels the jobs still queued or running and waits for the
+/// workers to stop.
+pub struct JobQueue {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl JobQueue {
+    /// Starts the workers, which create recognizers for `model` as jobs need them.
+    pub fn new(model: &Model, opts: QueueOptions) -> Self {
+        let model = model.clone();
+        JobQueue::with_transcribers(opts, move || RecognizerWorker {
+            model: model.clone(),
+            recognizer: None,
+        })
+    }
+    fn with_transcribers<T, F>(opts: QueueOptions, transcriber: F) -> Self
+    where
+        T: Transcriber,
+        F: Fn() -> T,
+    {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State::default()),
+            changed: Condvar::new(),
+            result_ttl: opts.result_ttl,
+        });
+        let workers = (0..opts.workers.max(1))
+            .map(|i| {
+                let shared = shared.clone();
+                let mut transcriber = transcriber();
+                thread::Builder::new()
+                    .name(format!("vosk-job-worker-{}", i))
+                    .spawn(move || shared.work(&mut transcriber))
+                    .expect("failed to spawn a job worker")
+            })
+            .collect();
+        JobQueue { shared, workers }
+    }
+    /// Queues `job`, failing with `Error::DuplicateJob` if a job with its id is kept.
+    pub fn submit(&self, job: Job) -> Result<(), Error> {
+        let mut state = self.shared.lock();
+        state.expire(self.shared.result_ttl);
+        if state.jobs.contains_key(&job.id) {
+            return Err(Error::DuplicateJob(job.id));
+        }
+        // After the jobs of the same or higher priority.
+        let at = state
+            .queue
+            .partition_point(|queued| queued.priority >= job.priority);
+        state.queue.insert(
+            at,
+            Queued {
+                id: job.id.clone(),
+                priority: job.priority,
+            },
+        );
+        state.jobs.insert(job.id.clone(), Entry::new(job));
+        drop(state);
+        self.shared.changed.notify_all();
+        Ok(())
+    }
+    /// None if there's no such job, or it was collected or expired.
+    pub fn status(&self, id: &str) -> Option<JobStatus> {
+        let mut state = self.shared.lock();
+        state.expire(self.shared.result_ttl);
+        state.status(id)
+    }
+    /// Removes a finished job, returning its status. Returns None for jobs still
+    /// queued or running, which are kept.
+    pub fn collect(&self, id: &str) -> Option<JobStatus> {
+        let mut state = self.shared.lock();
+        state.expire(self.shared.result_ttl);
+        match state.jobs.get(id)?.stage {
+            Stage::Finished { .. } => match state.jobs.remove(id)?.stage {
+                Stage::Finished { status, .. } => Some(status),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+    /// Cancels a queued or running job, which then fails with `Error::Cancelled`.
+    /// Returns false if it had finished already or isn't known.
+    ///
+    /// A running job stops at its next read of audio.
+    pub fn cancel(&self, id: &str) -> bool {
+        let mut state = self.shared.lock();
+        let cancelled = state.cancel(id);
+        drop(state);
+        self.shared.changed.notify_all();
+        cancelled
+    }
+    /// Blocks until the job is finished, returning its status without collecting it.
+    /// None if there's no such job.
+    pub fn wait(&self, id: &str) -> Option<JobStatus> {
+        let mut state = self.shared.lock();
+        loop {
+            match state.status(id)? {
+                status if status.is_finished() => return Some(status),
+                _ => {}
+            }
+            state = self
+                .shared
+                .changed
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+    /// Like `wait`, for async code: the future is ready once the job is finished.
+    pub fn finished(&self, id: &str) -> JobFinished {
+        JobFinished {
+            shared: self.shared.clone(),
+            id: id.to_string(),
+        }
+    }
+    /// Numbers of jobs queued and running.
+    pub fn pending(&self) -> (usize, usize) {
+        let state = self.shared.lock();
+        let running = state
+            .jobs
+            .values()
+            .filter(|entry| matches!(entry.stage, Stage::Running { .. }))
+            .count();
+        (state.queue.len(), running)
+    }
+}
+
+impl fmt::Debug for JobQueue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (queued, running) = self.pending();
+        f.debug_struct("JobQueue")
+            .field("workers", &self.workers.len())
+            .field("queued", &queued)
+            .field("running", &running)
+            .finish()
+    }
+}
+
+impl Drop for JobQueue {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.closed = true;
+        let ids: Vec<String> = state.jobs.keys().cloned().collect();
+        for id in ids {
+            state.cancel(&id);
+        }
+        drop(state);
+        self.shared.changed.notify_all();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// The future of `JobQueue::finished`, with the status of the finished job,
+/// or None if there's no such job.
+#[must_use = "futures do nothing unless polled"]
+pub struct JobFinished {
+    shared: Arc<Shared>,
+    id: String,
+}
+
+impl fmt::Debug for JobFinished {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JobFinished").field("id", &self.id).finish()
+    }
+}
+
+impl Future for JobFinished {
+    type Output = Option<JobStatus>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.shared.lock();
+        match state.status(&self.id) {
+            Some(status) if !status.is_finished() => {
+                let entry = state.jobs.get_mut(&self.id).expect("the job has a status");
+                if !entry.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                    entry.wakers.push(cx.waker().clone());
+                }
+                Poll::Pending
+            }
+            status => Poll::Ready(status),
+        }
+    }
+}
+
+/// What a worker needs to transcribe a job, so that the queue can be tested without a model.
+trait Transcriber: Send + 'static {
+    fn transcribe(
+        &mut self,
+        job: Job,
+        cancel: &CancellationToken,
+        progress: &mut dyn FnMut(Progress),
+    ) -> Result<Vec<UtteranceOwned>, Error>;
+    /// Forgets what a panicking `transcribe` may have left half done.
+    fn recover(&mut self) {}
+}
+
+struct RecognizerWorker {
+    model: Model,
+    /// Kept for the next job at the same sample rate.
+    recognizer: Option<Recognizer>,
+}
+
+impl Transcriber for RecognizerWorker {
+    fn transcribe(
+        &mut self,
+        job: Job,
+        cancel: &CancellationToken,
+        progress: &mut dyn FnMut(Progress),
+    ) -> Result<Vec<UtteranceOwned>, Error> {
+        let rate = job.audio_source.sample_rate() as f32;
+        let mut own;
+        let recognizer = match job.options.grammar {
+            Some(phrases) => {
+                own = Recognizer::builder(&self.model, rate)
+                    .grammar(phrases.iter().map(|p| p.split_whitespace()))
+                    .build()?;
+                &mut own
+            }
+            None => {
+                if self.recognizer.as_ref().map(Recognizer::sample_rate) != Some(rate) {
+                    self.recognizer = Some(Recognizer::try_new(&self.model, rate)?);
+                }
+                self.recognizer.as_mut().expect("created just above")
+            }
+        };
+        recognizer.set_words(job.options.words);
+        let reporter = ProgressReporter::new(progress);
+        let result =
+            transcribe_source_with_progress(recognizer, job.audio_source, cancel, reporter);
+        // A failed or cancelled job leaves an utterance in progress.
+        recognizer.reset();
+        result
+    }
+    fn recover(&mut self) {
+        self.recognizer = None;
+    }
+}
+
+struct Shared {
+    state: Mutex<State>,
+    /// Notified when a job is queued, cancelled or finished.
+    changed: Condvar,
+    result_ttl: Duration,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+    fn work<T: Transcriber>(&self, transcriber: &mut T) {
+        while let Some((job, cancel)) = self.next_job() {
+            let id = job.id.clone();
+            let mut report = |progress: Progress| {
+                if let Some(entry) = self.lock().jobs.get_mut(&id) {
+                    if let Stage::Running { progress: last } = &mut entry.stage {
+                        *last = Some(progress);
+                    }
+                }
+            };
+            // A panic fails the job rather than killing the worker with it running.
+            let transcribed = panic::catch_unwind(AssertUnwindSafe(|| {
+                transcriber.transcribe(job, &cancel, &mut report)
+            }));
+            let status = match transcribed {
+                Ok(Ok(utterances)) => JobStatus::Done(utterances),
+                Ok(Err(e)) => JobStatus::Failed(e),
+                Err(payload) => {
+                    transcriber.recover();
+                    JobStatus::Failed(Error::Panicked(panic_message(payload.as_ref())))
+                }
+            };
+            let mut state = self.lock();
+            if let Some(entry) = state.jobs.get_mut(&id) {
+                entry.finish(status);
+            }
+            drop(state);
+            self.changed.notify_all();
+        }
+    }
+    /// Takes the first queued job, or returns None once the queue is dropped.
+    fn next_job(&self) -> Option<(Job, CancellationToken)> {
+        let mut state = self.lock();
+        loop {
+            if state.closed {
+                return None;
+            }
+            if !state.queue.is_empty() {
+                let queued = state.queue.remove(0);
+                let entry = state
+                    .jobs
+                    .get_mut(&queued.id)
+                    .expect("queued jobs are kept");
+                let stage = std::mem::replace(&mut entry.stage, Stage::Running { progress: None });
+                let Stage::Queued(job) = stage else {
+                    unreachable!("only queued jobs are in the queue")
+                };
+                return Some((job, entry.cancel.clone()));
+            }
+            state = self.changed.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+#[derive(Default)]
+struct State {
+    /// Ids of the jobs waiting for a worker, in the order they'll run.
+    queue: Vec<Queued>,
+    jobs: HashMap<String, Entry>,
+    closed: bool,
+}
+
+impl State {
+    fn status(&self, id: &str) -> Option<JobStatus> {
+        let status = match &self.jobs.get(id)?.stage {
+            Stage::Queued(_) => JobStatus::Queued {
+                position: self.queue.iter().position(|q| q.id == id)?,
+            },
+            Stage::Running { progress } => JobStatus::Running {
+                progress: *progress,
+            },
+            Stage::Finished { status, .. } => status.clone(),
+        };
+        Some(status)
+    }
+    fn cancel(&mut self, id: &str) -> bool {
+        let Some(entry) = self.jobs.get_mut(id) else {
+            return false;
+        };
+        match entry.stage {
+            Stage::Queued(_) => {
+                entry.finish(JobStatus::Failed(Error::Cancelled));
+                self.queue.retain(|q| q.id != id);
+                true
+            }
+            Stage::Running { .. } => {
+                entry.cancel.cancel();
+                true
+            }
+            Stage::Finished { .. } => false,
+        }
+    }
+    /// Drops the finished jobs kept longer than `ttl`.
+    fn expire(&mut self, ttl: Duration) {
+        self.jobs.retain(|_, entry| match entry.stage {
+            Stage::Finished { at, .. } => at.elapsed() <= ttl,
+            _ => true,
+        });
+    }
+}
+
+struct Queued {
+    id: String,
+    priority: i32,
+}
+
+struct Entry {
+    stage: Stage,
+    cancel: CancellationToken,
+    /// Of the `JobFinished` futures waiting for this job.
+    wakers: Vec<Waker>,
+}
+
+enum Stage {
+    Queued(Job),
+    Running { progress: Option<Progress> },
+    Finished { status: JobStatus, at: Instant },
+}
+
+impl Entry {
+    fn new(job: Job) -> Self {
+        Entry {
+            stage: Stage::Queued(job),
+            cancel: CancellationToken::new(),
+            wakers: Vec::new(),
+        }
+    }
+    fn finish(&mut self, status: JobStatus) {
+        self.stage = Stage::Finished {
+            status,
+            at: Instant::now(),
+        };
+        for waker in self.wakers.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::MemorySource;
+    use crate::{Cancellable, RecognizedText};
+    use std::sync::mpsc::{self, Receiver, Sender};
+
+    /// Counts the samples of a job, recording the order jobs finish in.
+    /// A job with the id `panic` panics, as a bug in libvosk could.
+    struct Mock {
+        finished: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Transcriber for Mock {
+        fn transcribe(
+            &mut self,
+            job: Job,
+            cancel: &CancellationToken,
+            progress: &mut dyn FnMut(Progress),
+        ) -> Result<Vec<UtteranceOwned>, Error> {
+            if job.id == "panic" {
+                panic!("the mock panicked");
+            }
+            // Cancelled like the sources of real jobs.
+            let mut source = Cancellable::new(job.audio_source, cancel.clone());
+            let mut buf = [0; 10];
+            let mut samples = 0;
+            loop {
+                let n = source.read(&mut buf)?;
+                if n == 0 {
+                    break;
+                }
+                samples += n;
+                progress(Progress {
+                    processed: Duration::from_secs(samples as u64),
+                    total: None,
+                    bytes: samples as u64 * 2,
+                    real_time_factor: 0.0,
+                });
+            }
+            self.finished.lock().unwrap().push(job.id.clone());
+            let text = format!("{} {}", job.id, samples);
+            Ok(vec![RecognizedText::from_text(text)])
+        }
+    }
+
+    /// A source whose samples only come when the test sends them, ending when the sender is dropped.
+    struct Gated(Receiver<Vec<i16>>);
+
+    impl AudioSource for Gated {
+        fn sample_rate(&self) -> u32 {
+            1
+        }
+        fn read(&mut self, buf: &mut [i16]) -> Result<usize, Error> {
+            match self.0.recv() {
+                Ok(samples) => {
+                    buf[..samples.len()].copy_from_slice(&samples);
+                    Ok(samples.len())
+                }
+                Err(_) => Ok(0),
+            }
+        }
+    }
+
+    fn gated(id: &str) -> (Job, Sender<Vec<i16>>) {
+        let (send, receive) = mpsc::channel();
+        (Job::new(id, Gated(receive)), send)
+    }
+    fn memory(id: &str, samples: usize) -> Job {
+        Job::new(id, MemorySource::new(vec![0; samples], 1))
+    }
+    fn queue(workers: usize, result_ttl: Duration) -> (JobQueue, Arc<Mutex<Vec<String>>>) {
+        let finished = Arc::new(Mutex::new(Vec::new()));
+        let opts = QueueOptions {
+            workers,
+            result_ttl,
+        };
+        let record = finished.clone();
+        let queue = JobQueue::with_transcribers(opts, move || Mock {
+            finished: record.clone(),
+        });
+        (queue, finished)
+    }
+    fn text(status: Option<JobStatus>) -> String {
+        match status {
+            Some(JobStatus::Done(utterances)) => utterances[0].text.to_string(),
+            other => panic!("not done: {:?}", other),
+        }
+    }
+    /// Waits until `id` is running, once the worker has taken it from the queue.
+    fn until_running(queue: &JobQueue, id: &str) {
+        while !matches!(queue.status(id), Some(JobStatus::Running { .. })) {
+            thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn priorities_reorder() {
+        let (queue, finished) = queue(1, Duration::from_secs(60));
+        let (first, gate) = gated("first");
+        queue.submit(first).unwrap();
+        until_running(&queue, "first");
+        queue.submit(memory("low", 5).with_priority(-1)).unwrap();
+        queue.submit(memory("normal", 6)).unwrap();
+        queue.submit(memory("urgent", 7).with_priority(5)).unwrap();
+        queue.submit(memory("also normal", 8)).unwrap();
+        let position = |id| match queue.status(id) {
+            Some(JobStatus::Queued { position }) => position,
+            other => panic!("not queued: {:?}", other),
+        };
+        assert_eq!(
+            ["urgent", "normal", "also normal", "low"].map(position),
+            [0, 1, 2, 3]
+        );
+        assert_eq!(queue.pending(), (4, 1));
+        assert_eq!(
+            queue.submit(memory("low", 1)).unwrap_err(),
+            Error::DuplicateJob("low".to_string())
+        );
+
+        gate.send(vec![1; 3]).unwrap();
+        drop(gate);
+        assert_eq!(text(queue.wait("low")), "low 5");
+        assert_eq!(
+            *finished.lock().unwrap(),
+            ["first", "urgent", "normal", "also normal", "low"]
+        );
+        assert_eq!(text(queue.collect("first")), "first 3");
+        assert_eq!(queue.status("first"), None);
+        assert_eq!(queue.wait("first"), None);
+    }
+    #[test]
+    fn workers_run_at_once() {
+        let (queue, _) = queue(2, Duration::from_secs(60));
+        let (a, gate_a) = gated("a");
+        let (b, gate_b) = gated("b");
+        let (c, gate_c) = gated("c");
+        for job in [a, b, c] {
+            queue.submit(job).unwrap();
+        }
+        until_running(&queue, "a");
+        until_running(&queue, "b");
+        assert_eq!(queue.status("c"), Some(JobStatus::Queued { position: 0 }));
+        assert_eq!(queue.pending(), (1, 2));
+
+        gate_b.send(vec![0; 4]).unwrap();
+        // The progress shows up once the worker reports it.
+        let progress = loop {
+            if let Some(JobStatus::Running {
+                progress: Some(progress),
+            }) = queue.status("b")
+            {
+                break progress;
+            }
+            thread::yield_now();
+        };
+        assert_eq!(progress.processed, Duration::from_secs(4));
+        // Not collected while running.
+        assert_eq!(queue.collect("b"), None);
+        drop(gate_b);
+        assert_eq!(text(queue.wait("b")), "b 4");
+        // c took the free worker.
+        until_running(&queue, "c");
+        drop(gate_a);
+        drop(gate_c);
+        assert_eq!(text(queue.wait("a")), "a 0");
+        assert_eq!(text(queue.wait("c")), "c 0");
+        assert_eq!(queue.pending(), (0, 0));
+    }
+    #[test]
+    fn panic_fails_the_job() {
+        let (queue, finished) = queue(1, Duration::from_secs(60));
+        queue.submit(memory("panic", 3)).unwrap();
+        queue.submit(memory("next", 4)).unwrap();
+        assert_eq!(
+            queue.wait("panic"),
+            Some(JobStatus::Failed(Error::Panicked(
+                "the mock panicked".to_string()
+            )))
+        );
+        // The worker goes on with the next job.
+        assert_eq!(text(queue.wait("next")), "next 4");
+        assert_eq!(*finished.lock().unwrap(), ["next"]);
+    }
+    #[test]
+    fn cancel_queued_and_running() {
+        let (queue, finished) = queue(1, Duration::from_secs(60));
+        let (running, gate) = gated("running");
+        queue.submit(running).unwrap();
+        queue.submit(memory("queued", 3)).unwrap();
+        queue.submit(memory("next", 4)).unwrap();
+        until_running(&queue, "running");
+        assert!(queue.cancel("queued"));
+        assert_eq!(
+            queue.status("queued"),
+            Some(JobStatus::Failed(Error::Cancelled))
+        );
+        assert_eq!(
+            queue.status("next"),
+            Some(JobStatus::Queued { position: 0 })
+        );
+        assert!(queue.cancel("running"));
+        // The worker notices at its next read.
+        gate.send(vec![0; 2]).unwrap();
+        assert_eq!(
+            queue.wait("running"),
+            Some(JobStatus::Failed(Error::Cancelled))
+        );
+        assert_eq!(text(queue.wait("next")), "next 4");
+        assert!(!queue.cancel("next"));
+        assert!(!queue.cancel("unknown"));
+        assert_eq!(*finished.lock().unwrap(), ["next"]);
+    }
+    #[test]
+    fn results_expire() {
+        let (queue, _) = queue(1, Duration::ZERO);
+        queue.submit(memory("short", 3)).unwrap();
+        assert_eq!(text(queue.wait("short")), "short 3");
+        thread::sleep(Duration::from_millis(5));
+        assert_eq!(queue.status("short"), None);
+        // The id can be used again.
+        queue.submit(memory("short", 4)).unwrap();
+        assert_eq!(text(queue.wait("short")), "short 4");
+    }
+    #[test]
+    fn await_finished() {
+        let (queue, _) = queue(1, Duration::from_secs(60));
+        let (job, gate) = gated("job");
+        queue.submit(job).unwrap();
+        let finished = queue.finished("job");
+        let feeder = thread::spawn(move || {
+            gate.send(vec![0; 7]).unwrap();
+        });
+        assert_eq!(text(futures::executor::block_on(finished)), "job 7");
+        feeder.join().unwrap();
+        assert_eq!(futures::executor::block_on(queue.finished("other")), None);
+    }
+    #[test]
+    fn drop_cancels() {
+        let (queue, finished) = queue(1, Duration::from_secs(60));
+        let (running, gate) = gated("running");
+        queue.submit(running).unwrap();
+        queue.submit(memory("queued", 3)).unwrap();
+        until_running(&queue, "running");
+        let shared = queue.shared.clone();
+        let dropping = thread::spawn(move || drop(queue));
+        while !shared.lock().closed {
+            thread::yield_now();
+        }
+        // The worker is blocked reading, it's cancelled once the read returns.
+        drop(gate);
+        dropping.join().unwrap();
+        assert!(finished.lock().unwrap().is_empty());
+    }
+}
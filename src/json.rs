@@ -0,0 +1,136 @@
+use crate::{telemetry, Alternatives, Error, RecognizedPartial, RecognizedText};
+use serde::Deserialize;
+use std::ffi::CStr;
+use std::fmt;
+
+/// Parses the JSON results of libvosk, such as ones relayed by a server.
+///
+/// Every recognizer has one of its own.
+#[derive(Default)]
+pub struct ResultParser {}
+
+impl ResultParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Parses the JSON of a final or complete result.
+    pub fn result<'a>(&'a mut self, json: &'a str) -> Result<RecognizedText<'a>, Error> {
+        self.parse(json, "result")
+    }
+    /// Parses the JSON of a result with alternatives, see `Recognizer::set_max_alternatives`.
+    pub fn alternatives<'a>(&'a mut self, json: &'a str) -> Result<Alternatives<'a>, Error> {
+        self.parse(json, "result")
+    }
+    /// Parses the JSON of a partial result.
+    pub fn partial<'a>(&'a mut self, json: &'a str) -> Result<RecognizedPartial<'a>, Error> {
+        self.parse(json, "partial")
+    }
+    /// Parses JSON returned by libvosk, which is valid until the next call.
+    ///
+    /// `kind` names the output in trace events.
+    pub(crate) fn parse_output<'a, T: Deserialize<'a>>(
+        &'a mut self,
+        c_str: &'a CStr,
+        kind: &str,
+    ) -> Result<T, Error> {
+        self.parse(c_str.to_str()?, kind)
+    }
+    fn parse<'a, T: Deserialize<'a>>(&'a mut self, json: &'a str, kind: &str) -> Result<T, Error> {
+        #[cfg(feature = "tracing")]
+        let started = std::time::Instant::now();
+        let parsed = serde_json::from_str(json).map_err(|e| {
+            telemetry::parse_error(kind);
+            Error::Json {
+                message: e.to_string(),
+                raw: Some(json.to_string()),
+            }
+        });
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            kind,
+            bytes = json.len(),
+            parse_us = started.elapsed().as_micros() as u64,
+            "parsed recognizer output"
+        );
+        parsed
+    }
+}
+
+impl fmt::Debug for ResultParser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResultParser").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RecognizedWord;
+
+    #[test]
+    fn parse_output() {
+        let mut parser = ResultParser::new();
+        let c_str = CStr::from_bytes_with_nul(b"{\"partial\": \"hi\"}\0").unwrap();
+        let partial: RecognizedPartial = parser.parse_output(c_str, "partial").unwrap();
+        assert_eq!(partial.partial, "hi");
+        let c_str = CStr::from_bytes_with_nul(b"{\"partial\": \"\xff\"}\0").unwrap();
+        let invalid = parser.parse_output::<RecognizedPartial>(c_str, "partial");
+        assert_eq!(invalid.err(), Some(Error::InvalidUtf8(13)));
+        let c_str = CStr::from_bytes_with_nul(b"{\"partial\": 1}\0").unwrap();
+        match parser.parse_output::<RecognizedPartial>(c_str, "partial") {
+            Err(Error::Json { raw, .. }) => assert_eq!(raw.as_deref(), Some("{\"partial\": 1}")),
+            other => panic!("unexpected {:?}", other.err()),
+        }
+    }
+    #[test]
+    fn parse_result() {
+        let json = r#"{
+  "result" : [{
+      "conf" : 0.834512,
+      "end" : 1.020000,
+      "start" : 0.630000,
+      "word" : "café"
+    }, {
+      "conf" : 1.000000,
+      "end" : 1.500000,
+      "start" : 1.020000,
+      "word" : "\"au\" lait"
+    }],
+  "text" : "café \"au\" lait"
+}"#;
+        let mut parser = ResultParser::new();
+        let result = parser.result(json).unwrap();
+        assert_eq!(result.text, "café \"au\" lait");
+        assert_eq!(
+            result.result.unwrap(),
+            vec![
+                RecognizedWord::new("café", 0.834512, 0.63, 1.02),
+                RecognizedWord::new("\"au\" lait", 1.0, 1.02, 1.5),
+            ]
+        );
+        // The parser is reused for the next one.
+        let result = parser.result(r#"{"text" : "again"}"#).unwrap();
+        assert_eq!(
+            (result.text.as_ref(), result.result.is_none()),
+            ("again", true)
+        );
+        assert_eq!(parser.partial(r#"{"partial" : ""}"#).unwrap().partial, "");
+        assert!(matches!(parser.partial("{"), Err(Error::Json { .. })));
+    }
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn parse_errors_counted() {
+        use metrics_util::debugging::DebugValue;
+        let metrics = crate::telemetry::tests::recorded(|| {
+            let mut parser = ResultParser::new();
+            assert!(parser.result("{").is_err());
+            assert!(parser.result(r#"{"text": 1}"#).is_err());
+            assert!(parser.partial(r#"{"partial": ""}"#).is_ok());
+        });
+        assert_eq!(
+            metrics["vosk_parse_errors_total{kind=result}"],
+            DebugValue::Counter(2)
+        );
+        assert!(!metrics.contains_key("vosk_parse_errors_total{kind=partial}"));
+    }
+}
@@ -0,0 +1,369 @@
+//! Measuring the delay from audio entering the recognizer to its text showing up in a partial.
+//!
+//! The feeders can keep a `LatencyTracker` with `enable_latency`. When the audio is handed
+//! to another thread, call the tracker yourself: `submitted` when a chunk is queued,
+//! `processed` after `accept_waveform` returned for it, and `event` with each event.
+//!
+//! `measure_chunks` times feeding a recording in chunks of a given size, to choose one;
+//! see the `tune_chunks` example.
+
+use crate::partial::PartialTracker;
+use crate::{Event, Recognizer};
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::time::{Duration, Instant};
+
+/// How many of the latest measurements are kept.
+pub const DEFAULT_LATENCY_WINDOW: usize = 1024;
+
+/// Summary of the latest measurements, all zero without any.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LatencyStats {
+    /// Number of measurements summarized.
+    pub count: usize,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub max: Duration,
+}
+
+/// Bookkeeping of chunks between submission and processing.
+///
+/// Chunks must be processed in the order they were submitted.
+/// Each time the partial result changes to a non-empty text, the age of the newest chunk
+/// that had been fully processed is recorded: it's the oldest that audio can be
+/// when it first shows up.
+#[derive(Debug, Clone)]
+pub struct LatencyTracker {
+    /// Submission times of the chunks not yet processed, oldest first.
+    queued: VecDeque<Instant>,
+    newest_processed: Option<Instant>,
+    latencies: VecDeque<Duration>,
+    window: usize,
+}
+
+impl Default for LatencyTracker {
+    fn default() -> Self {
+        LatencyTracker::new(DEFAULT_LATENCY_WINDOW)
+    }
+}
+
+impl LatencyTracker {
+    /// Keeps the latest `window` measurements.
+    pub fn new(window: usize) -> LatencyTracker {
+        LatencyTracker {
+            queued: VecDeque::new(),
+            newest_processed: None,
+            latencies: VecDeque::new(),
+            window: window.max(1),
+        }
+    }
+    /// A chunk was handed over for recognition at `at`.
+    pub fn submitted(&mut self, at: Instant) {
+        self.queued.push_back(at);
+        #[cfg(feature = "tracing")]
+        tracing::trace!(queued = self.queued.len(), "chunk submitted");
+    }
+    /// The oldest chunk not yet processed was fully processed.
+    ///
+    /// Calls without a submitted chunk are ignored.
+    pub fn processed(&mut self) {
+        if let Some(at) = self.queued.pop_front() {
+            self.newest_processed = Some(at);
+        }
+        #[cfg(feature = "tracing")]
+        tracing::trace!(queued = self.queued.len(), "chunk processed");
+    }
+    /// Takes an event seen at `now`, measuring a partial result.
+    pub fn event(&mut self, event: &Event, now: Instant) {
+        if let Event::Partial(text) = event {
+            self.partial_changed(text, now);
+        }
+    }
+    /// The partial result changed to `text` at `now`.
+    pub fn partial_changed(&mut self, text: &str, now: Instant) {
+        let at = match self.newest_processed {
+            Some(at) if !text.is_empty() => at,
+            _ => return,
+        };
+        if self.latencies.len() == self.window {
+            self.latencies.pop_front();
+        }
+        self.latencies.push_back(now.saturating_duration_since(at));
+    }
+    pub fn stats(&self) -> LatencyStats {
+        let mut sorted: Vec<Duration> = self.latencies.iter().copied().collect();
+        sorted.sort_unstable();
+        LatencyStats {
+            count: sorted.len(),
+            p50: nearest_rank(&sorted, 50),
+            p95: nearest_rank(&sorted, 95),
+            max: sorted.last().copied().unwrap_or_default(),
+        }
+    }
+    /// Forgets the measurements, keeping track of queued chunks.
+    pub fn clear(&mut self) {
+        self.latencies.clear();
+    }
+}
+
+/// The `p`th percentile of sorted durations, zero without any.
+fn nearest_rank(sorted: &[Duration], p: usize) -> Duration {
+    match sorted.len() {
+        0 => Duration::ZERO,
+        n => sorted[(n * p).div_ceil(100).max(1) - 1],
+    }
+}
+
+/// How long each of a number of calls took, such as those to `accept_waveform`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CallTimes {
+    times: Vec<Duration>,
+}
+
+impl CallTimes {
+    pub fn record(&mut self, took: Duration) {
+        self.times.push(took);
+    }
+    /// Calls `f` and records how long it took.
+    pub fn time<R>(&mut self, f: impl FnOnce() -> R) -> R {
+        let start = Instant::now();
+        let result = f();
+        self.record(start.elapsed());
+        result
+    }
+    pub fn count(&self) -> usize {
+        self.times.len()
+    }
+    pub fn total(&self) -> Duration {
+        self.times.iter().sum()
+    }
+    /// Zero without calls.
+    pub fn mean(&self) -> Duration {
+        match u32::try_from(self.times.len()) {
+            Ok(0) => Duration::ZERO,
+            Ok(n) => self.total() / n,
+            Err(_) => self.total().div_f64(self.times.len() as f64),
+        }
+    }
+    /// The time that `p` percent of the calls took at most.
+    pub fn percentile(&self, p: usize) -> Duration {
+        let mut sorted = self.times.clone();
+        sorted.sort_unstable();
+        nearest_rank(&sorted, p.min(100))
+    }
+}
+
+/// A recording fed to a recognizer in chunks of one size, see `measure_chunks`.
+#[derive(Debug, Clone)]
+pub struct ChunkMeasurement {
+    pub chunk_samples: usize,
+    /// The length of the recording.
+    pub audio: Duration,
+    /// The time taken for the whole recording, including getting the results.
+    pub elapsed: Duration,
+    /// Each call to `accept_waveform`.
+    pub calls: CallTimes,
+    /// How many times the partial result changed.
+    pub partial_updates: usize,
+    /// The text of every utterance, separated by spaces.
+    pub text: String,
+}
+
+impl ChunkMeasurement {
+    /// The time taken per second of audio; below 1 is faster than real time.
+    pub fn real_time_factor(&self) -> f64 {
+        match self.audio.as_secs_f64() {
+            secs if secs > 0.0 => self.elapsed.as_secs_f64() / secs,
+            _ => 0.0,
+        }
+    }
+    /// Partial result changes per second of audio.
+    pub fn partial_updates_per_sec(&self) -> f64 {
+        match self.audio.as_secs_f64() {
+            secs if secs > 0.0 => self.partial_updates as f64 / secs,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Feeds `samples` to the recognizer `chunk_samples` at a time, as a live source would,
+/// timing each call and asking for the partial result after each chunk.
+///
+/// The recognizer is reset first, and left after its final result.
+pub fn measure_chunks(
+    recognizer: &mut Recognizer,
+    samples: &[i16],
+    chunk_samples: usize,
+) -> ChunkMeasurement {
+    let chunk_samples = chunk_samples.max(1);
+    recognizer.reset();
+    let mut calls = CallTimes::default();
+    let mut partials = PartialTracker::default();
+    let mut partial_updates = 0;
+    let mut texts = Vec::new();
+    let start = Instant::now();
+    for chunk in samples.chunks(chunk_samples) {
+        if calls.time(|| recognizer.accept_waveform(chunk)) {
+            partials.reset();
+            texts.push(recognizer.result().text.into_owned());
+        } else if partials
+            .changed(&recognizer.partial_result().partial)
+            .is_some()
+        {
+            partial_updates += 1;
+        }
+    }
+    texts.push(recognizer.final_result().text.into_owned());
+    let elapsed = start.elapsed();
+    texts.retain(|t| !t.is_empty());
+    ChunkMeasurement {
+        chunk_samples,
+        audio: Duration::from_secs_f64(samples.len() as f64 / recognizer.sample_rate() as f64),
+        elapsed,
+        calls,
+        partial_updates,
+        text: texts.join(" "),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    /// Steps of a worker thread and the partials of a scripted recognizer.
+    enum Step {
+        Submit(u64),
+        Process,
+        Partial(u64, &'static str),
+        Final(u64),
+    }
+
+    fn run(tracker: &mut LatencyTracker, script: &[Step]) -> LatencyStats {
+        let start = Instant::now();
+        for step in script {
+            match *step {
+                Step::Submit(t) => tracker.submitted(start + ms(t)),
+                Step::Process => tracker.processed(),
+                Step::Partial(t, text) => {
+                    tracker.event(&Event::Partial(text.to_string()), start + ms(t))
+                }
+                Step::Final(t) => {
+                    let utterance = crate::RecognizedText::from_text("the cat");
+                    tracker.event(&Event::Final(utterance), start + ms(t))
+                }
+            }
+        }
+        tracker.stats()
+    }
+
+    #[test]
+    fn queued_chunks() {
+        use Step::*;
+        let mut tracker = LatencyTracker::default();
+        let stats = run(
+            &mut tracker,
+            &[
+                Submit(0),
+                Submit(100),
+                Submit(200),
+                Process,
+                // Only the first chunk was processed, the others are still queued.
+                Partial(230, "the"),
+                Process,
+                Process,
+                Partial(260, "the cat"),
+                Submit(300),
+                Process,
+                Final(330),
+                Submit(400),
+                Process,
+                Partial(450, "sat"),
+            ],
+        );
+        assert_eq!(
+            stats,
+            LatencyStats {
+                count: 3,
+                p50: ms(60),
+                p95: ms(230),
+                max: ms(230),
+            }
+        );
+    }
+    #[test]
+    fn empty_partials() {
+        use Step::*;
+        let mut tracker = LatencyTracker::default();
+        let stats = run(
+            &mut tracker,
+            &[Partial(10, "early"), Submit(0), Process, Partial(20, "")],
+        );
+        assert_eq!(stats, LatencyStats::default());
+    }
+    #[test]
+    fn percentiles() {
+        let mut tracker = LatencyTracker::new(100);
+        let start = Instant::now();
+        for i in 1..=200 {
+            tracker.submitted(start);
+            tracker.processed();
+            tracker.partial_changed("a", start + ms(i));
+        }
+        let stats = tracker.stats();
+        assert_eq!(stats.count, 100);
+        assert_eq!(stats.p50, ms(150));
+        assert_eq!(stats.p95, ms(195));
+        assert_eq!(stats.max, ms(200));
+        tracker.clear();
+        assert_eq!(tracker.stats().count, 0);
+    }
+    #[test]
+    fn unmatched_processing() {
+        let mut tracker = LatencyTracker::default();
+        let start = Instant::now();
+        tracker.processed();
+        tracker.submitted(start);
+        tracker.processed();
+        tracker.processed();
+        tracker.partial_changed("a", start + ms(5));
+        assert_eq!(tracker.stats().max, ms(5));
+    }
+    #[test]
+    fn call_times() {
+        let mut calls = CallTimes::default();
+        assert_eq!((calls.mean(), calls.percentile(95)), (ms(0), ms(0)));
+        for i in (1..=20).rev() {
+            calls.record(ms(i));
+        }
+        assert_eq!(calls.count(), 20);
+        assert_eq!(calls.total(), ms(210));
+        assert_eq!(calls.mean(), Duration::from_micros(10_500));
+        assert_eq!(calls.percentile(95), ms(19));
+        assert_eq!(calls.percentile(100), ms(20));
+        assert_eq!(calls.percentile(0), ms(1));
+        assert_eq!(calls.time(|| 7), 7);
+        assert_eq!(calls.count(), 21);
+    }
+    #[test]
+    fn measurement_rates() {
+        let measurement = ChunkMeasurement {
+            chunk_samples: 1600,
+            audio: Duration::from_secs(4),
+            elapsed: ms(500),
+            calls: CallTimes::default(),
+            partial_updates: 6,
+            text: String::new(),
+        };
+        assert_eq!(measurement.real_time_factor(), 0.125);
+        assert_eq!(measurement.partial_updates_per_sec(), 1.5);
+        let empty = ChunkMeasurement {
+            audio: Duration::ZERO,
+            ..measurement
+        };
+        assert_eq!(empty.real_time_factor(), 0.0);
+    }
+}
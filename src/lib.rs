@@ -1,93 +1,410 @@
+use crate::grammar::grammar_json;
 use core::fmt;
 use serde::{Deserialize, Serialize};
-use serde_json::to_writer;
+use std::borrow::Cow;
+use std::convert::TryFrom;
 use std::ffi::{CStr, CString};
-use std::os::raw::c_int;
-use std::path::Path;
+use std::io::{self, Read};
+use std::os::raw::{c_char, c_int};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use vosk_sys::{
     vosk_model_find_word, vosk_model_free, vosk_model_new_or_null,
     vosk_recognizer_accept_waveform_f, vosk_recognizer_accept_waveform_s,
     vosk_recognizer_final_result, vosk_recognizer_free, vosk_recognizer_new,
     vosk_recognizer_new_grm, vosk_recognizer_new_spk, vosk_recognizer_partial_result,
-    vosk_recognizer_result, vosk_set_log_level, vosk_spk_model_free, vosk_spk_model_new_or_null,
-    VoskModel, VoskRecognizer, VoskSpkModel,
+    vosk_recognizer_reset, vosk_recognizer_result, vosk_recognizer_set_grm,
+    vosk_recognizer_set_max_alternatives, vosk_recognizer_set_words, vosk_spk_model_free,
+    vosk_spk_model_new_or_null, VoskModel, VoskRecognizer, VoskSpkModel,
 };
 
+pub mod activity;
+pub mod align;
+mod alternatives;
+#[cfg(feature = "android")]
+pub mod assets;
+pub mod assistant;
+mod builder;
+mod cache;
+mod cancel;
+#[cfg(feature = "debug-capture")]
+mod capture;
+pub mod command;
+#[cfg(any(
+    feature = "audio-decode",
+    feature = "ffmpeg-cli",
+    feature = "http-source"
+))]
+pub mod decode;
+pub mod diagnostics;
+pub mod eval;
+pub mod export;
+mod feeder;
+mod footprint;
+mod grammar;
+pub mod index;
+pub mod intent;
+#[cfg(feature = "jobs")]
+pub mod jobs;
+mod json;
+pub mod latency;
+mod loading;
+pub mod log;
+#[cfg(feature = "normalization")]
+mod normalize;
+#[cfg(feature = "opus")]
+pub mod opus;
+pub mod overload;
+pub mod partial;
+mod pause;
+pub mod pcm;
+pub mod postprocess;
+pub mod preprocess;
+pub mod presets;
+pub mod profiles;
+mod progress;
+pub mod quality;
+mod rate;
+#[cfg(feature = "sys")]
+mod raw;
+pub mod redact;
+#[cfg(feature = "ansi")]
+pub mod render;
+pub mod replay;
+pub mod ring;
+pub mod router;
+pub mod rtp;
+pub mod segment;
+pub mod source;
+pub mod stats;
+#[cfg(feature = "async")]
+pub mod streaming;
+pub mod swap;
+pub mod tee;
+pub mod telemetry;
+pub mod telephony;
+mod text;
+pub mod transcript;
+mod validate;
+mod vocabulary;
+pub mod wake;
+
+pub use crate::alternatives::{Alternative, Alternatives, Candidate, FinalOutcome};
+pub use crate::builder::RecognizerBuilder;
+pub use crate::cache::ModelCache;
+pub use crate::cancel::{Cancellable, CancellationToken};
+#[cfg(feature = "debug-capture")]
+pub use crate::capture::DEFAULT_CAPTURE_CAPACITY;
+pub use crate::footprint::{MemoryEstimate, ModelFootprint};
+pub use crate::grammar::{Grammar, OovError, OovWord};
+pub use crate::json::ResultParser;
+pub use crate::loading::ModelLoading;
+pub use crate::log::{set_log_level, LogLevel};
+#[cfg(feature = "normalization")]
+pub use crate::normalize::Normalization;
+pub use crate::pause::{PauseControl, ResumePolicy};
+pub use crate::progress::{Progress, ProgressReporter};
+pub use crate::rate::SampleRate;
+pub use crate::validate::ModelValidationError;
+pub use crate::vocabulary::{WordForm, WordMatch};
+/// The raw bindings to libvosk, see `Recognizer::into_raw` and `Model::into_raw`.
+#[cfg(feature = "sys")]
+pub use vosk_sys as sys;
+
 /// Stores all the data required for recognition
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct Model {
     inner: Arc<ModelInner>,
 }
 
 /// Stores all the data required for speaker identification.
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct SpeakerModel {
     inner: Arc<SpeakerModelInner>,
 }
 
 /// The main object which processes data.
 /// Takes audio as input and returns decoded information - words, confidences, times, and so on */
-#[derive(Debug)]
+///
+/// # Results borrow the recognizer
+///
+/// The text of a result points into a buffer of libvosk that the next call overwrites,
+/// so a result keeps the recognizer mutably borrowed until it's dropped.
+/// Call `into_owned` to keep it while feeding more audio:
+///
+/// ```no_run
+/// # use vosk::{Model, Recognizer};
+/// # let model = Model::new("model").unwrap();
+/// # let mut recognizer = Recognizer::new(&model, 16000.0);
+/// let result = recognizer.result().into_owned();
+/// recognizer.accept_waveform(&[0; 1600]);
+/// println!("{}", result.text);
+/// ```
+///
+/// Using the borrowed result after that doesn't compile:
+///
+/// ```compile_fail,E0499
+/// # use vosk::{Model, Recognizer};
+/// # let model = Model::new("model").unwrap();
+/// # let mut recognizer = Recognizer::new(&model, 16000.0);
+/// let result = recognizer.result();
+/// recognizer.accept_waveform(&[0; 1600]);
+/// println!("{}", result.text);
+/// ```
+///
+/// The same holds for partial results and raw bytes:
+///
+/// ```compile_fail,E0499
+/// # use vosk::{Model, Recognizer};
+/// # let model = Model::new("model").unwrap();
+/// # let mut recognizer = Recognizer::new(&model, 16000.0);
+/// let partial = recognizer.partial_result();
+/// let json = recognizer.final_result_bytes();
+/// println!("{} {:?}", partial.partial, json);
+/// ```
 pub struct Recognizer {
     ptr: *mut VoskRecognizer,
+    /// Kept for `Debug`, like `grammar` and `words`.
+    model_path: PathBuf,
+    sample_rate: f32,
+    /// Number of phrases of the grammar.
+    grammar: Option<usize>,
+    words: bool,
+    max_alternatives: usize,
+    chunk_limit: Option<usize>,
+    samples_processed: u64,
+    keep_count_on_reset: bool,
+    min_confidence: Option<f32>,
+    #[cfg(feature = "normalization")]
+    normalization: Normalization,
+    /// Reused by `accept_waveform_be_bytes`.
+    be_samples: Vec<i16>,
+    parser: ResultParser,
+    #[cfg(feature = "debug-capture")]
+    raw_capture: capture::RawCapture,
 }
 
 /// The main object which processes data.
 /// Takes audio as input and returns decoded information - words, confidences, times, speaker, and so on */
-#[derive(Debug)]
 pub struct SpeakerRecognizer {
     ptr: *mut VoskRecognizer,
+    model_path: PathBuf,
+    speaker_model_path: PathBuf,
+    sample_rate: f32,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Error {
+    /// libvosk could not load the model, though the directory looks like one.
     NoValidModel,
+    /// The path doesn't hold a model.
+    InvalidModel(ModelValidationError),
+    /// The input has more samples than libvosk can take in one call.
+    InputTooLong(usize),
+    /// A sample rate that isn't finite and positive.
+    InvalidSampleRate(f32),
+    /// Reading a file or audio device failed.
+    Io(String),
+    /// The audio format or codec is not supported.
+    UnsupportedCodec(String),
+    /// The audio file is damaged or not what it claims to be.
+    CorruptFile(String),
+    /// A packet of streamed audio could not be decoded.
+    InvalidPacket(String),
+    /// 16-bit audio was given as an odd number of bytes.
+    OddLength(usize),
+    /// The grammar has no phrases, or only empty ones.
+    EmptyGrammar,
+    /// These words are not in the vocabulary of the model.
+    OutOfVocabulary(Vec<String>),
+    /// JSON from libvosk or another source could not be parsed.
+    /// `raw` is the JSON that failed, when it came from libvosk.
+    Json {
+        message: String,
+        raw: Option<String>,
+    },
+    /// The path contains a NUL byte, so it can't be passed to libvosk.
+    InvalidPath(PathBuf),
+    /// Text passed to libvosk contains a NUL byte at this position.
+    NulInInput(usize),
+    /// Output of libvosk is not valid UTF-8 after this many bytes,
+    /// which may be caused by the word list of the model.
+    InvalidUtf8(usize),
+    /// The model at this path doesn't include its list of words.
+    NoWordList(PathBuf),
+    /// An external program such as ffmpeg isn't installed.
+    ProgramNotFound(PathBuf),
+    /// An external program exited with an error, with the end of its error output.
+    /// `code` is None if it was killed by a signal.
+    ProgramFailed {
+        program: PathBuf,
+        code: Option<i32>,
+        stderr: String,
+    },
+    /// The transcription was stopped through a `CancellationToken`.
+    Cancelled,
+    /// A model other than libvosk's, such as for restoring punctuation,
+    /// failed to load or to run.
+    Inference(String),
+    /// The recognizer was requested at another sample rate than the `model` was trained on,
+    /// see `RecognizerBuilder::strict_sample_rate`.
+    SampleRateMismatch { model: f32, requested: f32 },
+    /// The audio is at `audio` Hz, but the recognizer was created for `recognizer` Hz,
+    /// see `source::transcribe_source` and `telephony::G711Feeder::new`.
+    AudioRateMismatch { recognizer: f32, audio: f32 },
+    /// A job with this id is already in the queue, see `jobs::JobQueue::submit`.
+    DuplicateJob(String),
+    /// No grammar profile has this name, see `profiles::GrammarSession::activate`.
+    UnknownProfile(String),
+    /// Downloading from `url` failed, with the HTTP status if the server answered
+    /// with an error, see `decode::transcribe_url`.
+    Network {
+        url: String,
+        status: Option<u16>,
+        message: String,
+    },
+    /// Transcribing panicked, with the panic message, see `jobs::JobQueue`.
+    Panicked(String),
 }
 
-/// Set log level for Kaldi messages
-///
-///   log_level the level
-///     0 - default value to print info and error messages but no debug
-///     less than 0 - don't print info messages
-///     greather than 0 - more verbose mode
-pub fn set_log_level(level: c_int) {
-    unsafe { vosk_set_log_level(level) }
-}
-
-#[derive(Debug)]
 struct ModelInner {
     ptr: *mut VoskModel,
+    path: PathBuf,
+    /// How much the resident memory grew while loading, if it could be measured.
+    memory_delta: Option<u64>,
+    sample_rate: Option<f32>,
 }
 unsafe impl Sync for ModelInner {}
 unsafe impl Send for ModelInner {}
 
-#[derive(Debug)]
 struct SpeakerModelInner {
     ptr: *mut VoskSpkModel,
+    path: PathBuf,
 }
 
 unsafe impl Send for SpeakerModelInner {}
 unsafe impl Sync for SpeakerModelInner {}
 
-#[derive(Serialize, Deserialize)]
+// SAFETY: libvosk keeps no thread-local state for a recognizer: a `VoskRecognizer`
+// is a plain heap object that holds its own references to the model (and speaker
+// model), which libvosk counts atomically. So it may be created, used and freed on
+// different threads. What it doesn't support is two threads calling into the same
+// recognizer at once, which is why it isn't `Sync`: every call that reaches libvosk
+// takes `&mut self`; `&self` methods only read fields kept on the Rust side, or hand
+// out the pointer with `as_raw` for code that is unsafe anyway.
+// The other fields are owned plain data.
+unsafe impl Send for Recognizer {}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct RecognizedPartial<'a> {
-    pub partial: &'a str,
+    #[serde(borrow)]
+    pub partial: Cow<'a, str>,
 }
 
 /// Speech recognition result
-#[derive(Serialize, Deserialize, Debug)]
+///
+/// Results are equal if their text and words are, see `RecognizedWord`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct RecognizedText<'a> {
     /// May be empty
-    pub text: &'a str,
+    #[serde(borrow)]
+    pub text: Cow<'a, str>,
     /// Contains more information about each word when text is not empty
+    ///
+    /// Parsed words are put in order of their start time, which some models
+    /// don't quite keep to.
+    #[serde(borrow, default, deserialize_with = "sorted_words")]
     pub result: Option<Vec<RecognizedWord<'a>>>,
 }
 
+/// A recognition result that doesn't borrow from the recognizer,
+/// so it can be kept around while more audio is processed.
+pub type RecognizedTextOwned = RecognizedText<'static>;
+
+/// A finalized utterance.
+pub type UtteranceOwned = RecognizedTextOwned;
+
+/// Summary of the word confidences of an utterance, or of a part of one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConfStats {
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    pub word_count: usize,
+}
+
+/// A finalized result that passed or failed the confidence check,
+/// see `Recognizer::set_min_confidence`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome<'a> {
+    Accepted(RecognizedText<'a>),
+    /// The mean word confidence was below the minimum.
+    /// The text is likely wrong, it may be best to ask the speaker to repeat.
+    Rejected {
+        text: Cow<'a, str>,
+        confidence: f32,
+    },
+}
+
+/// Something that happened while audio was fed to a recognizer.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Event {
+    /// The partial result changed.
+    Partial(String),
+    /// An utterance was completed.
+    Final(UtteranceOwned),
+    /// Recognition fell behind the audio, see `overload::RingFeeder`.
+    Overloaded {
+        policy: crate::overload::OverloadPolicy,
+        /// Audio waiting to be recognized.
+        backlog: Duration,
+        /// Audio discarded to catch up or because there was no room for it.
+        dropped: Duration,
+    },
+    /// A new recognizer took over, see `swap::SwappableRecognizer`.
+    Swapped {
+        /// Audio kept while the recognizer was created, which it was then fed.
+        buffered: Duration,
+        /// Audio lost because the buffer was full.
+        dropped: Duration,
+    },
+    /// The recognizer for a new model could not be created, see `swap::SwappableRecognizer`.
+    /// The old recognizer goes on and was fed the buffered audio.
+    SwapFailed {
+        error: Error,
+        buffered: Duration,
+        dropped: Duration,
+    },
+    /// Recognition was paused, see `PauseControl`.
+    /// The utterance in progress was finalized just before.
+    Paused,
+    /// Recognition went on after a pause.
+    Resumed {
+        /// Audio that arrived while paused and was thrown away.
+        discarded: Duration,
+    },
+    /// Another grammar took over, see `profiles::GrammarSession`.
+    /// The utterance in progress was finalized just before.
+    GrammarChanged { profile: String },
+    /// Someone started speaking, see `activity::SpeechActivity`.
+    SpeechStarted,
+    /// The speech that `SpeechStarted` reported is over.
+    SpeechEnded {
+        /// From the first words heard to the end of the utterance or the pause after it.
+        duration: Duration,
+    },
+}
+
 /// Information about a word including confidence and timing.
-#[derive(Serialize, Deserialize, Debug)]
+///
+/// Words are equal if their text is and their times and confidence compare equal
+/// with `==`, so exactly; a NaN confidence is never equal.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct RecognizedWord<'a> {
-    word: &'a str,
+    #[serde(borrow)]
+    word: Cow<'a, str>,
     /// Confidence, less than or equal to 1.0
     conf: f32,
     /// Start time of the word in seconds.
@@ -95,15 +412,186 @@ pub struct RecognizedWord<'a> {
     end: f32,
 }
 
+impl<'a> RecognizedPartial<'a> {
+    /// Builds a partial result, for tests and tools working with results.
+    pub fn new<S: Into<Cow<'a, str>>>(partial: S) -> RecognizedPartial<'a> {
+        RecognizedPartial {
+            partial: partial.into(),
+        }
+    }
+    /// Copies the text so that it no longer borrows from the recognizer.
+    pub fn into_owned(self) -> RecognizedPartial<'static> {
+        RecognizedPartial {
+            partial: Cow::Owned(self.partial.into_owned()),
+        }
+    }
+    /// Converts the text to the normalization form `n`.
+    #[cfg(feature = "normalization")]
+    pub fn normalize(self, n: Normalization) -> Self {
+        RecognizedPartial {
+            partial: n.apply(self.partial),
+        }
+    }
+}
+
+impl<'a> RecognizedText<'a> {
+    /// Builds a result from words, for tests and tools working with results.
+    ///
+    /// Words are put in order of their start time, as libvosk returns them.
+    /// The text is the words separated by spaces, except between Chinese or Japanese characters.
+    /// Pass owned strings to get a `RecognizedTextOwned`.
+    pub fn from_words(mut words: Vec<RecognizedWord<'a>>) -> RecognizedText<'a> {
+        sort_by_start(&mut words);
+        let (text, _) = text::join_words(&words.iter().map(|w| w.word()).collect::<Vec<_>>());
+        RecognizedText {
+            text: Cow::Owned(text),
+            result: Some(words),
+        }
+    }
+    /// Builds a result with text only, like libvosk returns without word details.
+    pub fn from_text<S: Into<Cow<'a, str>>>(text: S) -> RecognizedText<'a> {
+        RecognizedText {
+            text: text.into(),
+            result: None,
+        }
+    }
+    /// The word details, empty without them (see `Recognizer::set_words`).
+    ///
+    /// The words are in order of their start time, unless `result` was set by hand.
+    pub fn words(&self) -> &[RecognizedWord<'a>] {
+        self.result.as_deref().unwrap_or_default()
+    }
+    /// Copies the text and words so that they no longer borrow from the recognizer.
+    pub fn into_owned(self) -> RecognizedTextOwned {
+        RecognizedText {
+            text: Cow::Owned(self.text.into_owned()),
+            result: self
+                .result
+                .map(|words| words.into_iter().map(RecognizedWord::into_owned).collect()),
+        }
+    }
+    /// Converts the text and every word to the normalization form `n`.
+    #[cfg(feature = "normalization")]
+    pub fn normalize(self, n: Normalization) -> Self {
+        RecognizedText {
+            text: n.apply(self.text),
+            result: self.result.map(|words| {
+                words
+                    .into_iter()
+                    .map(|w| RecognizedWord {
+                        word: n.apply(w.word),
+                        ..w
+                    })
+                    .collect()
+            }),
+        }
+    }
+    /// Statistics of the word confidences.
+    ///
+    /// None if there are no words, or no word details (see `Recognizer::set_words`).
+    pub fn confidence_stats(&self) -> Option<ConfStats> {
+        ConfStats::of(self.words())
+    }
+    /// Average confidence of the words, a single number to decide whether to trust the text.
+    pub fn mean_confidence(&self) -> Option<f32> {
+        self.confidence_stats().map(|stats| stats.mean)
+    }
+}
+
+impl<'a> Outcome<'a> {
+    /// Rejects `result` if its mean word confidence is below `min`.
+    pub fn check(result: RecognizedText<'a>, min: Option<f32>) -> Outcome<'a> {
+        match (min, result.mean_confidence()) {
+            (Some(min), Some(confidence)) if confidence < min => Outcome::Rejected {
+                text: result.text,
+                confidence,
+            },
+            _ => Outcome::Accepted(result),
+        }
+    }
+}
+
+impl ConfStats {
+    /// Returns None for an empty list of words.
+    pub fn of(words: &[RecognizedWord]) -> Option<ConfStats> {
+        let first = words.first()?.conf;
+        let mut stats = ConfStats {
+            min: first,
+            max: first,
+            mean: 0.0,
+            word_count: words.len(),
+        };
+        let mut sum = 0.0;
+        for w in words {
+            stats.min = stats.min.min(w.conf);
+            stats.max = stats.max.max(w.conf);
+            sum += w.conf;
+        }
+        stats.mean = sum / words.len() as f32;
+        Some(stats)
+    }
+}
+
+impl<'a> RecognizedWord<'a> {
+    /// Builds a word from its text, confidence and start and end times in seconds,
+    /// for tests and tools working with results.
+    pub fn new<S: Into<Cow<'a, str>>>(word: S, conf: f32, start: f32, end: f32) -> Self {
+        RecognizedWord {
+            word: word.into(),
+            conf,
+            start,
+            end,
+        }
+    }
+    pub fn word(&self) -> &str {
+        &self.word
+    }
+    /// Confidence, less than or equal to 1.0
+    pub fn conf(&self) -> f32 {
+        self.conf
+    }
+    /// Start time of the word in seconds, counted from the first audio fed to the recognizer.
+    pub fn start(&self) -> f32 {
+        self.start
+    }
+    /// End time of the word in seconds.
+    pub fn end(&self) -> f32 {
+        self.end
+    }
+    /// Copies the word so that it no longer borrows from the recognizer.
+    pub fn into_owned(self) -> RecognizedWord<'static> {
+        RecognizedWord {
+            word: Cow::Owned(self.word.into_owned()),
+            conf: self.conf,
+            start: self.start,
+            end: self.end,
+        }
+    }
+}
+
 impl Model {
     // Loads model data from the path
+    //
+    // When loading fails, the error tells what's wrong with the directory if it can.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "Model::new", skip_all, fields(path = ?path.as_ref()))
+    )]
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Model, Error> {
-        let path = path_to_cstring(path);
-        let model = unsafe { vosk_model_new_or_null(path.as_ptr()) };
+        let cpath = path_to_cstring(&path)?;
+        let (model, memory_delta) = footprint::measure(&footprint::ProcessMemory, || unsafe {
+            vosk_model_new_or_null(cpath.as_ptr())
+        });
         if model.is_null() {
+            Model::validate(path).map_err(Error::InvalidModel)?;
             return Err(Error::NoValidModel);
         }
-        let inner = ModelInner { ptr: model };
+        let inner = ModelInner {
+            ptr: model,
+            path: path.as_ref().to_path_buf(),
+            memory_delta,
+            sample_rate: validate::declared_sample_rate(path.as_ref()),
+        };
         let inner = Arc::new(inner);
         Ok(Model { inner })
     }
@@ -114,13 +602,45 @@ impl Model {
     /// Note that symbol 0 is for `<epsilon>`
     // Would it be better to return an unsigned number?
     pub fn find_word(&self, word: &str) -> Option<i32> {
-        let cstr = CString::new(word).unwrap();
+        // No word of the model contains NUL.
+        let cstr = CString::new(word).ok()?;
         let sym = unsafe { vosk_model_find_word(self.ptr(), cstr.as_ptr()) };
         if sym == -1 {
             return None;
         }
         Some(sym)
     }
+    /// Returns the words the model can't recognize, each once, in order of appearance.
+    ///
+    /// Words are looked up with `find_word_normalized`, so "Hello" isn't missing
+    /// from a model that knows "hello". Grammars are matched exactly though:
+    /// check the words of one with `missing_words_exact`.
+    pub fn missing_words<'w, I>(&self, words: I) -> Vec<&'w str>
+    where
+        I: IntoIterator<Item = &'w str>,
+    {
+        missing_words_by(words, |word| self.find_word_normalized(word).is_some())
+    }
+    /// Returns the words `find_word` doesn't know, each once, in order of appearance.
+    ///
+    /// Check a word list or transcript with this before building a grammar from it.
+    pub fn missing_words_exact<'w, I>(&self, words: I) -> Vec<&'w str>
+    where
+        I: IntoIterator<Item = &'w str>,
+    {
+        missing_words_by(words, |word| self.find_word(word).is_some())
+    }
+    /// The path the model was loaded from.
+    pub fn path(&self) -> &Path {
+        &self.inner.path
+    }
+    /// The sample rate the model was trained on, as declared in its configuration.
+    ///
+    /// None if the model doesn't say. Recognizers for audio at another rate still
+    /// work, but recognize much worse, see `RecognizerBuilder::strict_sample_rate`.
+    pub fn sample_rate(&self) -> Option<f32> {
+        self.inner.sample_rate
+    }
     fn ptr(&self) -> *mut VoskModel {
         self.inner.as_ref().ptr
     }
@@ -128,13 +648,20 @@ impl Model {
 
 impl SpeakerModel {
     /// Loads speaker model data from the path
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "SpeakerModel::new", skip_all, fields(path = ?path.as_ref()))
+    )]
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
-        let path = path_to_cstring(path);
-        let model = unsafe { vosk_spk_model_new_or_null(path.as_ptr()) };
+        let cpath = path_to_cstring(&path)?;
+        let model = unsafe { vosk_spk_model_new_or_null(cpath.as_ptr()) };
         if model.is_null() {
             return Err(Error::NoValidModel);
         }
-        let inner = SpeakerModelInner { ptr: model };
+        let inner = SpeakerModelInner {
+            ptr: model,
+            path: path.as_ref().to_path_buf(),
+        };
         let inner = Arc::new(inner);
         Ok(SpeakerModel { inner })
     }
@@ -143,15 +670,83 @@ impl SpeakerModel {
     }
 }
 
-const INVALID_STR_MSG: &'static str =
-    "Invalid UTF-8 in output, which may be from the word list used by the model.";
+/// A reasonable limit for `Recognizer::set_chunk_limit`, about 4 seconds at 16 kHz.
+pub const DEFAULT_CHUNK_LIMIT: usize = 1 << 16;
+
+/// Number of samples of silence `warm_up` feeds at a time.
+const WARM_UP_CHUNK: usize = 4000;
+
+/// Alternatives asked of libvosk for each candidate in `finalize_with_candidates`.
+const ALTERNATIVES_PER_CANDIDATE: usize = 3;
+
+/// Size of the buffer `accept_reader` reads into.
+const READER_CHUNK_BYTES: usize = 8192;
 
 impl Recognizer {
+    fn from_ptr(
+        ptr: *mut VoskRecognizer,
+        model: &Model,
+        sample_rate: f32,
+        grammar: Option<usize>,
+    ) -> Recognizer {
+        #[cfg(feature = "tracing")]
+        if SampleRate::try_from(sample_rate).is_ok_and(|rate| !rate.is_common()) {
+            tracing::warn!(sample_rate, "uncommon sample rate");
+        }
+        #[cfg(feature = "tracing")]
+        if let Err(Error::SampleRateMismatch { model, requested }) =
+            rate::check_model(model, sample_rate)
+        {
+            tracing::warn!(
+                model,
+                requested,
+                "sample rate differs from the model's, recognition will be poor"
+            );
+        }
+        telemetry::recognizer_created();
+        Recognizer {
+            ptr,
+            model_path: model.path().to_path_buf(),
+            sample_rate,
+            grammar,
+            words: false,
+            max_alternatives: 0,
+            chunk_limit: None,
+            samples_processed: 0,
+            keep_count_on_reset: false,
+            min_confidence: None,
+            #[cfg(feature = "normalization")]
+            normalization: Normalization::None,
+            be_samples: Vec::new(),
+            parser: ResultParser::new(),
+            #[cfg(feature = "debug-capture")]
+            raw_capture: capture::RawCapture::new(capture::DEFAULT_CAPTURE_CAPACITY),
+        }
+    }
     /// Creates the recognizer object.
     /// `sample_rate`: The sample rate of the audio that will be fed into the recognizer
+    ///
+    /// # Panics
+    ///
+    /// If the sample rate isn't finite and positive, see `try_new`.
     pub fn new(model: &Model, sample_rate: f32) -> Recognizer {
-        let recognizer = unsafe { vosk_recognizer_new(model.ptr(), sample_rate) };
-        Recognizer { ptr: recognizer }
+        Recognizer::with_rate(model, rate::expect_valid(sample_rate))
+    }
+    /// Same as `new`, but fails with `Error::InvalidSampleRate` instead of panicking.
+    pub fn try_new(model: &Model, sample_rate: f32) -> Result<Recognizer, Error> {
+        Ok(Recognizer::with_rate(
+            model,
+            SampleRate::try_from(sample_rate)?,
+        ))
+    }
+    /// Creates the recognizer object for audio sampled at `rate`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "Recognizer::new", skip(model), fields(model = ?model.path()))
+    )]
+    pub fn with_rate(model: &Model, rate: SampleRate) -> Recognizer {
+        let recognizer = unsafe { vosk_recognizer_new(model.ptr(), rate.hz()) };
+        Recognizer::from_ptr(recognizer, model, rate.hz(), None)
     }
     ///  Creates the recognizer object with limited subset of words to improve accuracy.
     ///
@@ -159,12 +754,10 @@ impl Recognizer {
     ///
     /// Only recognizers with lookahead models support this type of quick configuration.
     ///  Precompiled HCLG graph models are not supported.
+    ///
+    /// Panics like `with_grammar` if the list has no words.
     pub fn with_vocabulary(model: &Model, sample_rate: f32, word_list: &str) -> Recognizer {
-        Recognizer::with_grammar(
-            model,
-            sample_rate,
-            word_list.split_whitespace().map(|w| Some(w)),
-        )
+        Recognizer::with_grammar(model, sample_rate, word_list.split_whitespace().map(Some))
     }
     ///  Creates the recognizer object with limited subset of phrases to improve accuracy.
     ///
@@ -183,32 +776,117 @@ impl Recognizer {
     /// ```
     /// Only recognizers with lookahead models support this type of quick configuration.
     ///  Precompiled HCLG graph models are not supported.
+    ///
+    /// Empty phrases are left out and repeated ones kept once.
+    ///
+    /// # Panics
+    ///
+    /// If no phrase is left, a phrase has a NUL byte, or the sample rate
+    /// isn't finite and positive; see `try_with_grammar`.
     pub fn with_grammar<I, P, S>(model: &Model, sample_rate: f32, phrases: I) -> Recognizer
     where
         P: IntoIterator<Item = S>,
         I: IntoIterator<Item = P>,
         S: AsRef<str>,
     {
-        let mut phrase = String::new();
-        let phrase_list: Vec<String> = phrases
-            .into_iter()
-            .map(|words| {
-                phrase.clear();
-                words.into_iter().for_each(|s| {
-                    let s = s.as_ref();
-                    phrase.push_str(s);
-                    phrase.push(' ');
-                });
-                let p = phrase.trim_end();
-                p.to_string()
-            })
-            .collect();
-        let mut writer = Vec::with_capacity(128);
-        to_writer(&mut writer, &phrase_list).unwrap();
-        let cstr = CString::new(writer).unwrap();
+        let sample_rate = rate::expect_valid(sample_rate);
+        Recognizer::try_with_grammar(model, sample_rate.hz(), phrases).expect(INVALID_GRAMMAR_MSG)
+    }
+    /// Same as `with_grammar`, but fails with `Error::EmptyGrammar`,
+    /// `Error::NulInInput` or `Error::InvalidSampleRate` instead of panicking.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "Recognizer::try_with_grammar",
+            skip(model, phrases),
+            fields(model = ?model.path())
+        )
+    )]
+    pub fn try_with_grammar<I, P, S>(
+        model: &Model,
+        sample_rate: f32,
+        phrases: I,
+    ) -> Result<Recognizer, Error>
+    where
+        P: IntoIterator<Item = S>,
+        I: IntoIterator<Item = P>,
+        S: AsRef<str>,
+    {
+        let sample_rate = SampleRate::try_from(sample_rate)?.hz();
+        let (cstr, count) = grammar_json(phrases)?;
         let recognizer =
             unsafe { vosk_recognizer_new_grm(model.ptr(), sample_rate, cstr.as_ptr()) };
-        Recognizer { ptr: recognizer }
+        Ok(Recognizer::from_ptr(
+            recognizer,
+            model,
+            sample_rate,
+            Some(count),
+        ))
+    }
+    /// Restricts recognition to `phrases`, which are given as to `with_grammar`.
+    ///
+    /// This is quicker than creating a new recognizer. Call it between utterances,
+    /// or `reset` afterwards, as audio already fed was decoded with the previous grammar.
+    /// Only recognizers with lookahead models support this.
+    ///
+    /// # Panics
+    ///
+    /// If no phrase is left after leaving out empty ones, or a phrase has a NUL byte;
+    /// see `try_set_grammar`.
+    pub fn set_grammar<I, P, S>(&mut self, phrases: I)
+    where
+        P: IntoIterator<Item = S>,
+        I: IntoIterator<Item = P>,
+        S: AsRef<str>,
+    {
+        self.try_set_grammar(phrases).expect(INVALID_GRAMMAR_MSG)
+    }
+    /// Same as `set_grammar`, but fails with `Error::EmptyGrammar` or `Error::NulInInput`
+    /// instead of panicking, keeping the previous grammar.
+    pub fn try_set_grammar<I, P, S>(&mut self, phrases: I) -> Result<(), Error>
+    where
+        P: IntoIterator<Item = S>,
+        I: IntoIterator<Item = P>,
+        S: AsRef<str>,
+    {
+        let (cstr, count) = grammar_json(phrases)?;
+        self.set_grammar_json(&cstr, count);
+        Ok(())
+    }
+    /// Sets a grammar already rendered by `grammar_json`, with `count` phrases.
+    pub(crate) fn set_grammar_json(&mut self, json: &CStr, count: usize) {
+        unsafe { vosk_recognizer_set_grm(self.ptr, json.as_ptr()) }
+        self.grammar = Some(count);
+    }
+    /// Enables or disables word details (timing and confidence) in `result` and `final_result`.
+    ///
+    /// Newer versions of libvosk leave them out unless asked for.
+    pub fn set_words(&mut self, enable: bool) {
+        self.words = enable;
+        unsafe { vosk_recognizer_set_words(self.ptr, enable as c_int) }
+    }
+    /// Asks libvosk for up to `max` hypotheses for each utterance, see `Alternatives`.
+    ///
+    /// `result` and `final_result` then return the likeliest one, without word details.
+    /// 0, the default, asks for the single best result.
+    pub fn set_max_alternatives(&mut self, max: usize) {
+        self.max_alternatives = max;
+        unsafe {
+            vosk_recognizer_set_max_alternatives(self.ptr, max.min(c_int::MAX as usize) as c_int)
+        }
+    }
+    /// Sets the maximum number of samples passed to libvosk in one call.
+    ///
+    /// Longer input to `accept_waveform` is split into chunks of this size,
+    /// which keeps partial results coming for long buffers.
+    /// `None`, the default, passes input through whole, unless it's longer than
+    /// `i32::MAX` samples, which libvosk can't take in one call.
+    ///
+    /// Only the result of the last utterance completed in a call is kept, so with a
+    /// limit an utterance ending in an earlier chunk is lost. Keep buffers short
+    /// enough to hold one utterance, or use a limit only where losing one is acceptable.
+    pub fn set_chunk_limit(&mut self, limit: Option<usize>) {
+        self.chunk_limit = limit;
     }
     /// Accept and process a new chunk of voice data.
     ///
@@ -216,11 +894,35 @@ impl Recognizer {
     ///
     ///  returns true if silence has occurred and you can retrieve a new utterance with `result`,
     ///  otherwise `partial_result` can be used to retrieve an incomplete sentence.
+    ///
+    /// When the input is split into several chunks and more than one utterance ends,
+    /// only the last one is available from `result`.
     pub fn accept_waveform(&mut self, wave: &[i16]) -> bool {
-        let completed = unsafe {
-            vosk_recognizer_accept_waveform_s(self.ptr, wave.as_ptr(), wave.len() as i32)
-        };
-        completed != 0
+        self.try_accept_waveform(wave).unwrap_or(false)
+    }
+    /// Same as `accept_waveform`.
+    ///
+    /// This doesn't fail anymore, as input too long for libvosk is split.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(samples = wave.len(), completed = tracing::field::Empty)
+        )
+    )]
+    pub fn try_accept_waveform(&mut self, wave: &[i16]) -> Result<bool, Error> {
+        let timer = telemetry::Timer::start();
+        let ptr = self.ptr;
+        let completed = accept_chunked(wave, self.chunk_limit, |chunk, len| unsafe {
+            vosk_recognizer_accept_waveform_s(ptr, chunk.as_ptr(), len) != 0
+        });
+        let before = self.samples_processed;
+        self.samples_processed += wave.len() as u64;
+        telemetry::audio_fed(timer, before, self.samples_processed, self.sample_rate);
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("completed", completed);
+        Ok(completed)
     }
     /// Alternative method for processing voice data using f32 instead of i16.
     ///
@@ -229,47 +931,262 @@ impl Recognizer {
     ///  returns true if silence has occurred and you can retrieve a new utterance with `result`,
     ///  otherwise `partial_result` can be used to retrieve an incomplete sentence.
     pub fn accept_waveform_f32(&mut self, wave: &[f32]) -> bool {
-        let completed = unsafe {
-            vosk_recognizer_accept_waveform_f(self.ptr, wave.as_ptr(), wave.len() as i32)
-        };
-        completed != 0
+        self.try_accept_waveform_f32(wave).unwrap_or(false)
+    }
+    /// Same as `accept_waveform_f32`.
+    ///
+    /// This doesn't fail anymore, as input too long for libvosk is split.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(samples = wave.len(), completed = tracing::field::Empty)
+        )
+    )]
+    pub fn try_accept_waveform_f32(&mut self, wave: &[f32]) -> Result<bool, Error> {
+        let timer = telemetry::Timer::start();
+        let ptr = self.ptr;
+        let completed = accept_chunked(wave, self.chunk_limit, |chunk, len| unsafe {
+            vosk_recognizer_accept_waveform_f(ptr, chunk.as_ptr(), len) != 0
+        });
+        let before = self.samples_processed;
+        self.samples_processed += wave.len() as u64;
+        telemetry::audio_fed(timer, before, self.samples_processed, self.sample_rate);
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("completed", completed);
+        Ok(completed)
+    }
+    /// Accepts big-endian 16-bit mono PCM, as carried by some network protocols.
+    ///
+    /// Returns true when an utterance was completed, like `accept_waveform`.
+    /// Fails on an odd number of bytes.
+    pub fn accept_waveform_be_bytes(&mut self, bytes: &[u8]) -> Result<bool, Error> {
+        let mut samples = std::mem::take(&mut self.be_samples);
+        let completed =
+            pcm::decode_be(bytes, &mut samples).and_then(|()| self.try_accept_waveform(&samples));
+        self.be_samples = samples;
+        completed
+    }
+    /// Reads little-endian 16-bit mono PCM from `reader` until the end of input
+    /// and feeds it to the recognizer.
+    ///
+    /// `on_chunk` is called after each chunk with the recognizer and
+    /// whether an utterance was completed, so that results can be retrieved.
+    /// A half sample at the end of input is ignored.
+    /// Call `final_result` afterwards to get the last utterance.
+    pub fn accept_reader<R, F>(&mut self, mut reader: R, mut on_chunk: F) -> io::Result<()>
+    where
+        R: Read,
+        F: FnMut(&mut Recognizer, bool),
+    {
+        let mut bytes = vec![0; READER_CHUNK_BYTES];
+        let mut samples = Vec::with_capacity(READER_CHUNK_BYTES / 2 + 1);
+        let mut decoder = pcm::LeDecoder::default();
+        loop {
+            let n = match reader.read(&mut bytes) {
+                Ok(0) => return Ok(()),
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            samples.clear();
+            decoder.decode(&bytes[..n], &mut samples);
+            if samples.is_empty() {
+                continue;
+            }
+            let completed = self.accept_waveform(&samples);
+            on_chunk(self, completed);
+        }
+    }
+    /// The sample rate of the audio the recognizer expects.
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+    /// Number of samples fed to the recognizer so far.
+    ///
+    /// Starts again from zero after `reset`, unless `set_keep_count_on_reset(true)` was called.
+    pub fn samples_processed(&self) -> u64 {
+        self.samples_processed
+    }
+    /// Duration of the audio fed to the recognizer so far,
+    /// derived from `samples_processed` and the sample rate.
+    pub fn audio_duration(&self) -> Duration {
+        duration_of(self.samples_processed, self.sample_rate)
+    }
+    /// Whether `reset` keeps the count of processed samples instead of zeroing it.
+    /// Keeping it is useful when the count is used as a position in a longer stream.
+    pub fn set_keep_count_on_reset(&mut self, keep: bool) {
+        self.keep_count_on_reset = keep;
+    }
+    /// Discards the current utterance and any pending audio,
+    /// so that recognition starts from scratch.
+    pub fn reset(&mut self) {
+        unsafe { vosk_recognizer_reset(self.ptr) }
+        if !self.keep_count_on_reset {
+            self.samples_processed = 0;
+        }
+    }
+    /// Feeds `duration` of silence and resets, so that the first real audio
+    /// isn't slowed down by allocations and feature extraction starting up.
+    ///
+    /// This moves the delay to startup: it takes about as long as
+    /// recognizing `duration` of audio, half a second is usually enough.
+    ///
+    /// Like `reset`, it discards the utterance in progress along with the audio
+    /// fed for it, so call it before feeding audio or right after a result.
+    /// The count of processed samples is restored afterwards: it doesn't include
+    /// the silence, but still includes any audio that was discarded.
+    pub fn warm_up(&mut self, duration: Duration) {
+        let processed = self.samples_processed;
+        let total = (duration.as_secs_f64() * self.sample_rate as f64) as usize;
+        let silence = vec![0i16; total.min(WARM_UP_CHUNK)];
+        let mut fed = 0;
+        while fed < total {
+            let n = (total - fed).min(silence.len());
+            self.accept_waveform(&silence[..n]);
+            fed += n;
+        }
+        self.reset();
+        self.samples_processed = processed;
     }
     /// Returns partial speech recognition text which is not yet finalized,
     /// may change as recognizer processes more data.
     /// Use this when `accept_waveform` returns false.
-    pub fn partial_result(&mut self) -> RecognizedPartial {
+    pub fn partial_result(&mut self) -> RecognizedPartial<'_> {
         let c_str = unsafe {
             let ptr = vosk_recognizer_partial_result(self.ptr);
             CStr::from_ptr(ptr)
         };
-        let str = c_str.to_str().expect(INVALID_STR_MSG);
-        serde_json::from_str(str).unwrap()
+        #[cfg(feature = "debug-capture")]
+        self.raw_capture.record(c_str);
+        let r: RecognizedPartial = self
+            .parser
+            .parse_output(c_str, "partial")
+            .unwrap_or_else(|e| panic!("{}", e));
+        #[cfg(feature = "normalization")]
+        let r = r.normalize(self.normalization);
+        r
     }
     /// Returns speech recognition result after `accept_waveform` returns true.
     /// Result contains decoded line, decoded words, times in seconds and confidences.
-    pub fn result(&mut self) -> RecognizedText {
+    pub fn result(&mut self) -> RecognizedText<'_> {
         let c_str = unsafe {
             let ptr = vosk_recognizer_result(self.ptr);
             CStr::from_ptr(ptr)
         };
-        let str = c_str.to_str().expect(INVALID_STR_MSG);
-        let r: RecognizedText = serde_json::from_str(str).unwrap();
-        r
+        self.parse_result(c_str, "result")
     }
     /// Returns speech recognition result.
     ///
     ///  Same as `result`, but doesn't wait for silence
     ///  You usually call it in the end of the stream to get final bits of audio. It
     ///  flushes the feature pipeline, so all remaining audio chunks got processed.
-    pub fn final_result(&mut self) -> RecognizedText {
+    pub fn final_result(&mut self) -> RecognizedText<'_> {
         let c_str = unsafe {
             let ptr = vosk_recognizer_final_result(self.ptr);
             CStr::from_ptr(ptr)
         };
-        let str = c_str.to_str().expect(INVALID_STR_MSG);
-        let r: RecognizedText = serde_json::from_str(str).unwrap();
+        self.parse_result(c_str, "final_result")
+    }
+    /// Parses a result, or the best of its alternatives if libvosk was asked for them.
+    fn parse_result<'a>(&'a mut self, c_str: &'a CStr, kind: &str) -> RecognizedText<'a> {
+        #[cfg(feature = "debug-capture")]
+        self.raw_capture.record(c_str);
+        let r: RecognizedText = if self.max_alternatives > 0 {
+            self.parser
+                .parse_output::<Alternatives>(c_str, kind)
+                .map(Alternatives::best)
+        } else {
+            self.parser.parse_output(c_str, kind)
+        }
+        .unwrap_or_else(|e| panic!("{}", e));
+        telemetry::utterance(&r.text);
+        #[cfg(feature = "normalization")]
+        let r = r.normalize(self.normalization);
         r
     }
+    /// Like `final_result`, but returns the likeliest texts instead when the best one is
+    /// unsure, so that the speaker can be asked which they meant.
+    ///
+    /// libvosk is asked for more alternatives than `max_candidates` for this call, as
+    /// several often have the same words. See `Alternatives::outcome` for how `min_conf`
+    /// applies. The text isn't normalized, and has no word details.
+    pub fn finalize_with_candidates(
+        &mut self,
+        min_conf: f32,
+        max_candidates: usize,
+    ) -> FinalOutcome {
+        let previous = self.max_alternatives;
+        self.set_max_alternatives(
+            previous.max(max_candidates.saturating_mul(ALTERNATIVES_PER_CANDIDATE)),
+        );
+        let c_str = unsafe { CStr::from_ptr(vosk_recognizer_final_result(self.ptr)) };
+        #[cfg(feature = "debug-capture")]
+        self.raw_capture.record(c_str);
+        let outcome = self
+            .parser
+            .parse_output::<Alternatives>(c_str, "final_result")
+            .unwrap_or_else(|e| panic!("{}", e))
+            .outcome(min_conf, max_candidates);
+        if let FinalOutcome::Confident(utterance) = &outcome {
+            telemetry::utterance(&utterance.text);
+        }
+        self.set_max_alternatives(previous);
+        outcome
+    }
+    /// The JSON of `partial_result` as bytes, for models whose words aren't UTF-8,
+    /// so that they can be transcoded.
+    ///
+    /// The bytes belong to libvosk and are only valid until the next call
+    /// to the recognizer, which the borrow enforces.
+    pub fn partial_result_bytes(&mut self) -> &[u8] {
+        unsafe { output_bytes(vosk_recognizer_partial_result(self.ptr)) }
+    }
+    /// The JSON of `result` as bytes, valid until the next call to the recognizer.
+    pub fn result_bytes(&mut self) -> &[u8] {
+        unsafe { output_bytes(vosk_recognizer_result(self.ptr)) }
+    }
+    /// The JSON of `final_result` as bytes, valid until the next call to the recognizer.
+    pub fn final_result_bytes(&mut self) -> &[u8] {
+        unsafe { output_bytes(vosk_recognizer_final_result(self.ptr)) }
+    }
+    /// Sets the Unicode normalization form of the text in results, by default unchanged.
+    #[cfg(feature = "normalization")]
+    pub fn set_normalization(&mut self, n: Normalization) {
+        self.normalization = n;
+    }
+    /// Sets the mean word confidence below which `checked_result`
+    /// and `checked_final_result` reject an utterance.
+    ///
+    /// Confidences come with word details, so this needs `set_words(true)`.
+    /// Results without word details are never rejected.
+    pub fn set_min_confidence(&mut self, min: Option<f32>) {
+        self.min_confidence = min;
+    }
+    /// Applies the settings of `other` that don't depend on its model,
+    /// leaving out the grammar.
+    pub(crate) fn copy_settings_from(&mut self, other: &Recognizer) {
+        self.set_words(other.words);
+        self.set_max_alternatives(other.max_alternatives);
+        self.chunk_limit = other.chunk_limit;
+        self.keep_count_on_reset = other.keep_count_on_reset;
+        self.min_confidence = other.min_confidence;
+        #[cfg(feature = "normalization")]
+        {
+            self.normalization = other.normalization;
+        }
+    }
+    /// Like `result`, checked against the minimum confidence.
+    pub fn checked_result(&mut self) -> Outcome<'_> {
+        let min = self.min_confidence;
+        Outcome::check(self.result(), min)
+    }
+    /// Like `final_result`, checked against the minimum confidence.
+    pub fn checked_final_result(&mut self) -> Outcome<'_> {
+        let min = self.min_confidence;
+        Outcome::check(self.final_result(), min)
+    }
 }
 
 impl SpeakerRecognizer {
@@ -281,17 +1198,36 @@ impl SpeakerRecognizer {
     ///   `speaker`: speaker model for speaker identification
     ///
     ///   `sample_rate`: The sample rate of the audio you going to feed into the recognizer
+    ///
+    /// Panics like `Recognizer::new` if the sample rate isn't finite and positive.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "SpeakerRecognizer::new",
+            skip(model, speaker),
+            fields(model = ?model.path(), speaker_model = ?speaker.inner.path)
+        )
+    )]
     pub fn new(model: &Model, speaker: &SpeakerModel, sample_rate: f32) -> SpeakerRecognizer {
+        let sample_rate = rate::expect_valid(sample_rate).hz();
         let recognizer =
             unsafe { vosk_recognizer_new_spk(model.ptr(), speaker.ptr(), sample_rate) };
-        SpeakerRecognizer { ptr: recognizer }
+        SpeakerRecognizer {
+            ptr: recognizer,
+            model_path: model.path().to_path_buf(),
+            speaker_model_path: speaker.inner.path.clone(),
+            sample_rate,
+        }
     }
 }
 
 impl Drop for ModelInner {
     fn drop(&mut self) {
-        unsafe {
-            vosk_model_free(self.ptr);
+        // Only null in tests that don't load a model.
+        if !self.ptr.is_null() {
+            unsafe {
+                vosk_model_free(self.ptr);
+            }
         }
     }
 }
@@ -306,7 +1242,11 @@ impl Drop for SpeakerModelInner {
 
 impl Drop for Recognizer {
     fn drop(&mut self) {
-        unsafe { vosk_recognizer_free(self.ptr) }
+        telemetry::recognizer_dropped();
+        // Only null in tests that don't load a model.
+        if !self.ptr.is_null() {
+            unsafe { vosk_recognizer_free(self.ptr) }
+        }
     }
 }
 
@@ -316,19 +1256,251 @@ impl Drop for SpeakerRecognizer {
     }
 }
 
+// Pointers mean nothing in logs, these show what the objects were created from.
+
+impl fmt::Debug for Model {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Model")
+            .field("path", &self.inner.path)
+            .finish()
+    }
+}
+
+impl fmt::Debug for SpeakerModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpeakerModel")
+            .field("path", &self.inner.path)
+            .finish()
+    }
+}
+
+impl fmt::Debug for Recognizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = f.debug_struct("Recognizer");
+        s.field("model", &self.model_path)
+            .field("sample_rate", &self.sample_rate)
+            .field("grammar", &self.grammar.map(Phrases))
+            .field("words", &self.words)
+            .field("max_alternatives", &self.max_alternatives)
+            .field("chunk_limit", &self.chunk_limit)
+            .field("min_confidence", &self.min_confidence);
+        #[cfg(feature = "normalization")]
+        s.field("normalization", &self.normalization);
+        s.finish()
+    }
+}
+
+impl fmt::Debug for SpeakerRecognizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpeakerRecognizer")
+            .field("model", &self.model_path)
+            .field("speaker_model", &self.speaker_model_path)
+            .field("sample_rate", &self.sample_rate)
+            .finish()
+    }
+}
+
+/// Shows the size of a grammar rather than all of it.
+struct Phrases(usize);
+
+impl fmt::Debug for Phrases {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} phrases", self.0)
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
             Error::NoValidModel => write!(f, "Could not find valid model at given pat")?,
+            Error::InvalidModel(ref e) => write!(f, "Invalid model: {}", e)?,
+            Error::InputTooLong(len) => write!(f, "Input of {} samples is too long", len)?,
+            Error::InvalidSampleRate(hz) => write!(f, "Invalid sample rate: {} Hz", hz)?,
+            Error::Io(ref e) => write!(f, "Could not read file: {}", e)?,
+            Error::UnsupportedCodec(ref e) => write!(f, "Unsupported audio format: {}", e)?,
+            Error::CorruptFile(ref e) => write!(f, "Could not decode audio: {}", e)?,
+            Error::InvalidPacket(ref e) => write!(f, "Invalid audio packet: {}", e)?,
+            Error::OddLength(len) => write!(f, "Odd number of bytes ({}) in 16-bit audio", len)?,
+            Error::EmptyGrammar => write!(f, "The grammar has no phrases")?,
+            Error::OutOfVocabulary(ref words) => {
+                write!(f, "Words not known to the model: {}", words.join(", "))?
+            }
+            Error::Json {
+                ref message,
+                ref raw,
+            } => {
+                write!(f, "Invalid JSON: {}", message)?;
+                if let Some(raw) = raw {
+                    write!(f, " in {}", raw)?;
+                }
+            }
+            Error::InvalidPath(ref path) => {
+                write!(f, "Path contains a NUL byte: {}", path.display())?
+            }
+            Error::NulInInput(pos) => write!(f, "NUL byte at position {} of input", pos)?,
+            Error::InvalidUtf8(valid) => write!(
+                f,
+                "Invalid UTF-8 after {} bytes of output, which may be from the word list used by the model",
+                valid
+            )?,
+            Error::NoWordList(ref path) => {
+                write!(f, "No word list in the model at {}", path.display())?
+            }
+            Error::ProgramNotFound(ref program) => {
+                write!(f, "Could not run {}, is it installed?", program.display())?
+            }
+            Error::ProgramFailed {
+                ref program,
+                code,
+                ref stderr,
+            } => {
+                match code {
+                    Some(code) => write!(f, "{} exited with code {}", program.display(), code)?,
+                    None => write!(f, "{} was killed", program.display())?,
+                }
+                if !stderr.is_empty() {
+                    write!(f, ": {}", stderr)?;
+                }
+            }
+            Error::Cancelled => write!(f, "Cancelled")?,
+            Error::Inference(ref message) => write!(f, "Inference failed: {}", message)?,
+            Error::SampleRateMismatch { model, requested } => write!(
+                f,
+                "The model is for audio at {} Hz, not {} Hz",
+                model, requested
+            )?,
+            Error::AudioRateMismatch { recognizer, audio } => write!(
+                f,
+                "The audio is at {} Hz, but the recognizer takes {} Hz",
+                audio, recognizer
+            )?,
+            Error::DuplicateJob(ref id) => write!(f, "A job with the id {} is already queued", id)?,
+            Error::UnknownProfile(ref name) => write!(f, "No grammar profile named {}", name)?,
+            Error::Network {
+                ref url,
+                ref message,
+                ..
+            } => write!(f, "Could not download {}: {}", url, message)?,
+            Error::Panicked(ref message) => write!(f, "Panicked: {}", message)?,
         }
         Ok(())
     }
 }
 
-fn path_to_cstring<P: AsRef<Path>>(path: P) -> CString {
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        if e.get_ref()
+            .is_some_and(|inner| inner.is::<cancel::Cancelled>())
+        {
+            return Error::Cancelled;
+        }
+        #[cfg(feature = "http-source")]
+        if let Some(failure) = e
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<decode::NetworkFailure>())
+        {
+            return failure.0.clone();
+        }
+        Error::Io(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json {
+            message: e.to_string(),
+            raw: None,
+        }
+    }
+}
+
+impl From<std::ffi::NulError> for Error {
+    fn from(e: std::ffi::NulError) -> Self {
+        Error::NulInInput(e.nul_position())
+    }
+}
+
+impl From<std::str::Utf8Error> for Error {
+    fn from(e: std::str::Utf8Error) -> Self {
+        Error::InvalidUtf8(e.valid_up_to())
+    }
+}
+
+const INVALID_GRAMMAR_MSG: &str = "Invalid grammar";
+
+/// Passes `wave` to `accept` in chunks of at most `limit` samples, or of as many as
+/// libvosk takes without a limit, together with the length of each chunk.
+/// Returns true if any chunk completed an utterance.
+fn accept_chunked<T>(
+    wave: &[T],
+    limit: Option<usize>,
+    mut accept: impl FnMut(&[T], c_int) -> bool,
+) -> bool {
+    let limit = limit.unwrap_or(usize::MAX).clamp(1, c_int::MAX as usize);
+    let mut completed = false;
+    for chunk in wave.chunks(limit) {
+        completed |= accept(chunk, chunk.len() as c_int);
+    }
+    completed
+}
+
+/// The words for which `known` is false, each once.
+fn missing_words_by<'w, I, F>(words: I, known: F) -> Vec<&'w str>
+where
+    I: IntoIterator<Item = &'w str>,
+    F: Fn(&str) -> bool,
+{
+    let mut missing = Vec::new();
+    for word in words {
+        if !missing.contains(&word) && !known(word) {
+            missing.push(word);
+        }
+    }
+    missing
+}
+
+pub(crate) fn duration_of(samples: u64, sample_rate: f32) -> Duration {
+    if sample_rate > 0.0 {
+        Duration::from_secs_f64(samples as f64 / sample_rate as f64)
+    } else {
+        Duration::from_secs(0)
+    }
+}
+
+fn path_to_cstring<P: AsRef<Path>>(path: P) -> Result<CString, Error> {
     let path = path.as_ref();
-    let path = path_to_bytes(path);
-    CString::new(path).unwrap()
+    CString::new(path_to_bytes(path)).map_err(|_| Error::InvalidPath(path.to_path_buf()))
+}
+
+/// Orders words by start time, keeping words that start together as they were.
+fn sort_by_start(words: &mut [RecognizedWord]) {
+    words.sort_by(|a, b| a.start.total_cmp(&b.start));
+}
+
+/// Deserializes word details, sorted by start time.
+fn sorted_words<'de: 'a, 'a, D>(
+    deserializer: D,
+) -> Result<Option<Vec<RecognizedWord<'a>>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let mut words: Option<Vec<RecognizedWord<'a>>> = Deserialize::deserialize(deserializer)?;
+    if let Some(words) = &mut words {
+        sort_by_start(words);
+    }
+    Ok(words)
+}
+
+/// The bytes of a string returned by libvosk, in whatever encoding they are.
+///
+/// # Safety
+///
+/// `ptr` must be null or point to a NUL-terminated string that outlives `'a`.
+unsafe fn output_bytes<'a>(ptr: *const c_char) -> &'a [u8] {
+    if ptr.is_null() {
+        return &[];
+    }
+    CStr::from_ptr(ptr).to_bytes()
 }
 
 #[cfg(unix)]
@@ -356,47 +1528,456 @@ fn path_to_bytes<P: AsRef<Path>>(path: P) -> Vec<u8> {
     buf
 }
 
+#[cfg(test)]
+pub(crate) mod test_util {
+    use crate::{Model, ModelInner, RecognizedText, RecognizedTextOwned, RecognizedWord};
+    use std::sync::Arc;
+
+    /// A model that doesn't need libvosk, for recognizers that are never fed.
+    pub(crate) fn fake_model(path: &str) -> Model {
+        let inner = ModelInner {
+            ptr: std::ptr::null_mut(),
+            path: path.into(),
+            memory_delta: None,
+            sample_rate: crate::validate::declared_sample_rate(std::path::Path::new(path)),
+        };
+        Model {
+            inner: Arc::new(inner),
+        }
+    }
+
+    /// Builds a result from `(word, start, end)` triples, with full confidence.
+    pub(crate) fn utterance(words: &[(&str, f32, f32)]) -> RecognizedTextOwned {
+        let words = words
+            .iter()
+            .map(|&(word, start, end)| RecognizedWord::new(word.to_string(), 1.0, start, end))
+            .collect();
+        RecognizedText::from_words(words)
+    }
+
+    /// Builds a result of half-second words `w0`, `w1`... with the given confidences.
+    pub(crate) fn with_confidences(confs: &[f32]) -> RecognizedTextOwned {
+        let names: Vec<String> = (0..confs.len()).map(|i| format!("w{}", i)).collect();
+        let words: Vec<_> = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.as_str(), i as f32 * 0.5, i as f32 * 0.5 + 0.5))
+            .collect();
+        let mut u = utterance(&words);
+        for (w, &conf) in u.result.as_mut().unwrap().iter_mut().zip(confs) {
+            w.conf = conf;
+        }
+        u
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{Error, Model, Recognizer};
+    use crate::test_util::with_confidences;
+    use crate::ModelValidationError;
+    use crate::{accept_chunked, duration_of, Error, Model, Recognizer};
+    use crate::{ConfStats, Outcome, RecognizedText, RecognizedTextOwned, RecognizedWord};
+    use std::time::Duration;
 
     #[test]
+    #[cfg_attr(miri, ignore = "calls into libvosk")]
     fn not_found() {
         let result = Model::new("not_existing");
-        assert_eq!(Error::NoValidModel, result.unwrap_err());
+        let missing = ModelValidationError::NotFound("not_existing".into());
+        assert_eq!(Error::InvalidModel(missing), result.unwrap_err());
+    }
+    #[test]
+    fn nul_in_path() {
+        let result = Model::new("mo\0del");
+        assert_eq!(result.unwrap_err(), Error::InvalidPath("mo\0del".into()));
+    }
+    /// A result with its words out of order, as some models return them.
+    const SHUFFLED: &str = r#"{
+  "result" : [{
+      "conf" : 1.0, "end" : 1.2, "start" : 0.9, "word" : "two"
+    }, {
+      "conf" : 0.9, "end" : 0.9, "start" : 0.4, "word" : "one"
+    }, {
+      "conf" : 0.8, "end" : 1.5, "start" : 1.2, "word" : "three"
+    }, {
+      "conf" : 0.7, "end" : 1.3, "start" : 1.2, "word" : "four"
+    }],
+  "text" : "two one three four"
+}"#;
+
+    #[test]
+    fn words_in_order() {
+        let expected = [
+            RecognizedWord::new("one", 0.9, 0.4, 0.9),
+            RecognizedWord::new("two", 1.0, 0.9, 1.2),
+            // Words starting together stay as they came.
+            RecognizedWord::new("three", 0.8, 1.2, 1.5),
+            RecognizedWord::new("four", 0.7, 1.2, 1.3),
+        ];
+        let parsed: RecognizedText = serde_json::from_str(SHUFFLED).unwrap();
+        assert_eq!(parsed.words(), expected);
+        // The text is left as libvosk returned it.
+        assert_eq!(parsed.text, "two one three four");
+        let mut parser = crate::ResultParser::new();
+        assert_eq!(parser.result(SHUFFLED).unwrap().words(), expected);
+        let text_only: RecognizedText = serde_json::from_str(r#"{"text" : "one"}"#).unwrap();
+        assert!(text_only.result.is_none() && text_only.words().is_empty());
+        let reordered = RecognizedText::from_words(parsed.words().to_vec());
+        assert_eq!(reordered.text, "one two three four");
+        assert_eq!(
+            crate::segment::gaps(&[parsed], 2.0, 0.1),
+            vec![
+                crate::segment::TimeRange {
+                    start: 0.0,
+                    end: 0.4
+                },
+                crate::segment::TimeRange {
+                    start: 1.5,
+                    end: 2.0
+                },
+            ]
+        );
     }
     #[test]
-    #[ignore]
-    fn one_drop_model() {
-        let m = Model::new("model").expect("no model");
-        let m1 = m.clone();
-        drop(m);
-        let _recognizer = Recognizer::new(&m1, 8000.0);
+    fn constructed_results() {
+        let words = vec![
+            RecognizedWord::new("world", 0.5, 0.6, 1.0),
+            RecognizedWord::new("hello".to_string(), 1.0, 0.0, 0.5),
+        ];
+        let owned: RecognizedTextOwned = RecognizedText::from_words(words.clone());
+        assert_eq!(owned.text, "hello world");
+        assert_eq!(owned.result, Some(vec![words[1].clone(), words[0].clone()]));
+        assert_eq!(owned.clone(), owned);
+        assert_ne!(owned, RecognizedText::from_text("hello world"));
+        let cjk = RecognizedText::from_words(vec![
+            RecognizedWord::new("你好", 1.0, 0.0, 0.5),
+            RecognizedWord::new("世界", 1.0, 0.5, 1.0),
+        ]);
+        assert_eq!(cjk.text, "你好世界");
+        assert!(RecognizedText::from_text("hi").result.is_none());
+        assert_eq!(crate::RecognizedPartial::new("hel").partial, "hel");
     }
     #[test]
-    #[ignore]
-    fn word_list() {
-        let m = Model::new("model").expect("no model");
-        let mut _recognizer = Recognizer::with_vocabulary(&m, 16000.0, "yes no");
+    fn debug_output() {
+        let model = crate::test_util::fake_model("models/en-us-0.22");
+        assert_eq!(
+            format!("{:?}", model),
+            r#"Model { path: "models/en-us-0.22" }"#
+        );
+        let mut recognizer = Recognizer::from_ptr(std::ptr::null_mut(), &model, 16000.0, Some(12));
+        recognizer.words = true;
+        recognizer.set_min_confidence(Some(0.5));
+        let debug = format!("{:?}", recognizer);
+        assert!(
+            debug.starts_with(
+                r#"Recognizer { model: "models/en-us-0.22", sample_rate: 16000.0, grammar: Some(12 phrases), words: true, max_alternatives: 0, chunk_limit: None, min_confidence: Some(0.5)"#
+            ),
+            "{}",
+            debug
+        );
+        let plain = Recognizer::from_ptr(std::ptr::null_mut(), &model, 8000.0, None);
+        assert!(format!("{:?}", plain).contains("grammar: None, words: false"));
+    }
+    #[test]
+    fn error_conversions() {
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "gone");
+        assert_eq!(Error::from(io), Error::Io("gone".into()));
+        let json = serde_json::from_str::<RecognizedText>("{").unwrap_err();
+        assert!(matches!(Error::from(json), Error::Json { raw: None, .. }));
+        let nul = std::ffi::CString::new("ab\0c").unwrap_err();
+        assert_eq!(Error::from(nul), Error::NulInInput(2));
+        let utf8 = String::from_utf8(b"caf\xe9".to_vec()).unwrap_err();
+        let utf8 = utf8.utf8_error();
+        assert_eq!(Error::from(utf8), Error::InvalidUtf8(3));
+    }
+    #[test]
+    fn raw_output() {
+        // "你好" in GBK, which is not valid UTF-8.
+        let gbk = b"{\"text\": \"\xc4\xe3\xba\xc3\"}\0";
+        let c_str = std::ffi::CStr::from_bytes_with_nul(gbk).unwrap();
+        let bytes = unsafe { crate::output_bytes(c_str.as_ptr()) };
+        assert_eq!(bytes, &gbk[..gbk.len() - 1]);
+        assert!(std::str::from_utf8(bytes).is_err());
+        assert!(unsafe { crate::output_bytes(std::ptr::null()) }.is_empty());
+    }
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn trace_spans() {
+        use std::fmt::Debug;
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+        /// Writes down spans, recorded fields and events as text, leaving out timings.
+        struct Trail(Arc<Mutex<Vec<String>>>);
+        struct Fields(String);
+        impl Visit for Fields {
+            fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+                if field.name() != "parse_us" {
+                    self.0 += &format!(" {}={:?}", field.name(), value);
+                }
+            }
+        }
+        impl<S: tracing::Subscriber> Layer<S> for Trail {
+            fn on_new_span(
+                &self,
+                attrs: &tracing::span::Attributes<'_>,
+                _: &tracing::span::Id,
+                _: Context<'_, S>,
+            ) {
+                let mut fields = Fields(format!("span {}", attrs.metadata().name()));
+                attrs.record(&mut fields);
+                self.0.lock().unwrap().push(fields.0);
+            }
+            fn on_record(
+                &self,
+                _: &tracing::span::Id,
+                values: &tracing::span::Record<'_>,
+                _: Context<'_, S>,
+            ) {
+                let mut fields = Fields("record".to_string());
+                values.record(&mut fields);
+                self.0.lock().unwrap().push(fields.0);
+            }
+            fn on_event(&self, event: &tracing::Event<'_>, _: Context<'_, S>) {
+                let mut fields = Fields("event".to_string());
+                event.record(&mut fields);
+                self.0.lock().unwrap().push(fields.0);
+            }
+        }
+
+        let trail = Arc::new(Mutex::new(Vec::new()));
+        let subscriber = tracing_subscriber::registry().with(Trail(trail.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            assert!(Model::new("mo\0del").is_err());
+            let model = crate::test_util::fake_model("model");
+            let mut recognizer = Recognizer::from_ptr(std::ptr::null_mut(), &model, 16000.0, None);
+            // Empty input doesn't reach libvosk.
+            assert!(!recognizer.accept_waveform(&[]));
+            let c_str = std::ffi::CStr::from_bytes_with_nul(b"{\"partial\": \"hi\"}\0").unwrap();
+            crate::ResultParser::new()
+                .parse_output::<crate::RecognizedPartial>(c_str, "partial")
+                .unwrap();
+        });
+        assert_eq!(
+            *trail.lock().unwrap(),
+            vec![
+                "span Model::new path=\"mo\\0del\"",
+                "span try_accept_waveform samples=0",
+                "record completed=false",
+                "event message=parsed recognizer output kind=\"partial\" bytes=17",
+            ]
+        );
     }
     #[test]
-    #[ignore]
-    fn phrase_list() {
-        let m = Model::new("model").expect("no model");
-        let v = vec![vec!["hello world"], vec!["initiate the process"]];
-        let mut _recognizer = Recognizer::with_grammar(&m, 16000.0, v);
+    fn chunked_lengths() {
+        let wave = [0i16; 10];
+        let mut lens = Vec::new();
+        let completed = accept_chunked(&wave, Some(4), |chunk, len| {
+            assert_eq!(chunk.len(), len as usize);
+            lens.push(len);
+            lens.len() == 2
+        });
+        assert!(completed);
+        assert_eq!(lens, vec![4, 4, 2]);
     }
     #[test]
-    #[ignore]
-    fn share_model() {
-        use ::std::thread;
-        let m = Model::new("model").expect("no model");
-        let m1 = m.clone();
-        drop(m);
-        thread::spawn(move || {
-            let _recognizer = Recognizer::new(&m1, 8000.0);
-        })
-        .join()
-        .unwrap();
+    fn unchunked() {
+        let wave = [0i16; 10];
+        let mut lens = Vec::new();
+        let completed = accept_chunked(&wave, None, |_, len| {
+            lens.push(len);
+            false
+        });
+        assert!(!completed);
+        assert_eq!(lens, vec![10]);
+    }
+    #[test]
+    fn two_utterances_in_one_buffer() {
+        // Speech, then a pause ending the first utterance, more speech and a second pause.
+        let mut wave = vec![1000i16; 100_000];
+        wave.extend_from_slice(&[0; 20_000]);
+        wave.extend_from_slice(&[1000; 100_000]);
+        wave.extend_from_slice(&[0; 20_000]);
+        // A pretend libvosk, returning the speech since the last result once a call
+        // contains a pause after speech. `result` only has the latest of them.
+        let speech_heard = |limit| {
+            let mut pending = 0;
+            let mut latest = None;
+            let completed = accept_chunked(&wave, limit, |chunk, _| {
+                pending += chunk.iter().filter(|&&s| s != 0).count();
+                let ended = pending > 0 && chunk.last() == Some(&0);
+                if ended {
+                    latest = Some(std::mem::take(&mut pending));
+                }
+                ended
+            });
+            assert!(completed);
+            latest
+        };
+        let model = crate::test_util::fake_model("model");
+        let recognizer = Recognizer::from_ptr(std::ptr::null_mut(), &model, 16000.0, None);
+        assert_eq!(recognizer.chunk_limit, None);
+        // Passed whole by default, the result has both utterances.
+        assert_eq!(speech_heard(recognizer.chunk_limit), Some(200_000));
+        // Split, the first one is overwritten by the second.
+        assert_eq!(speech_heard(Some(120_000)), Some(100_000));
+    }
+    #[test]
+    fn zero_limit() {
+        let wave = [0i16; 3];
+        let mut calls = 0;
+        let _ = accept_chunked(&wave, Some(0), |_, len| {
+            assert_eq!(len, 1);
+            calls += 1;
+            false
+        });
+        assert_eq!(calls, 3);
+    }
+    #[test]
+    fn too_long_is_split() {
+        // Zero-sized samples, as real ones would take gigabytes.
+        let wave = vec![(); i32::MAX as usize + 5];
+        let mut lens = Vec::new();
+        let completed = accept_chunked(&wave, None, |chunk, len| {
+            assert_eq!(chunk.len(), len as usize);
+            lens.push(len);
+            false
+        });
+        assert!(!completed);
+        assert_eq!(lens, vec![i32::MAX, 5]);
+    }
+    #[test]
+    fn durations() {
+        assert_eq!(duration_of(16000, 16000.0), Duration::from_secs(1));
+        assert_eq!(duration_of(4000, 8000.0), Duration::from_millis(500));
+        assert_eq!(duration_of(100, 0.0), Duration::from_secs(0));
+    }
+    #[test]
+    fn confidence_stats() {
+        let stats = with_confidences(&[0.5, 1.0, 0.75]).confidence_stats();
+        assert_eq!(
+            stats,
+            Some(ConfStats {
+                min: 0.5,
+                max: 1.0,
+                mean: 0.75,
+                word_count: 3
+            })
+        );
+        let single = with_confidences(&[0.25]);
+        assert_eq!(single.mean_confidence(), Some(0.25));
+        assert_eq!(single.confidence_stats().unwrap().min, 0.25);
+    }
+    #[test]
+    fn min_confidence() {
+        match Outcome::check(with_confidences(&[0.9, 0.3]), Some(0.7)) {
+            Outcome::Rejected { text, confidence } => {
+                assert_eq!(text, "w0 w1");
+                assert!((confidence - 0.6).abs() < 1e-6);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        let confident = Outcome::check(with_confidences(&[0.9, 0.8]), Some(0.7));
+        assert_eq!(confident, Outcome::Accepted(with_confidences(&[0.9, 0.8])));
+        let unchecked = Outcome::check(with_confidences(&[0.1]), None);
+        assert!(matches!(unchecked, Outcome::Accepted(_)));
+    }
+    #[test]
+    fn min_confidence_without_words() {
+        let no_details = RecognizedText {
+            text: "word".into(),
+            result: None,
+        };
+        assert!(matches!(
+            Outcome::check(no_details, Some(0.7)),
+            Outcome::Accepted(_)
+        ));
+    }
+    #[test]
+    fn no_confidence_stats() {
+        assert_eq!(with_confidences(&[]).confidence_stats(), None);
+        let no_details = RecognizedText {
+            text: "word".into(),
+            result: None,
+        };
+        assert_eq!(no_details.mean_confidence(), None);
+    }
+
+    // The drop_order tests have counterparts with libvosk in tests/leak_check.rs.
+    // They call no foreign functions, so they also run under Miri.
+
+    /// A recognizer that never touches libvosk, keeping only the path of `model`.
+    fn detached_recognizer(model: &Model) -> Recognizer {
+        Recognizer::from_ptr(std::ptr::null_mut(), model, 16000.0, None)
+    }
+    #[test]
+    fn drop_order_model_clones() {
+        let model = crate::test_util::fake_model("model");
+        let inner = std::sync::Arc::downgrade(&model.inner);
+        let clones = vec![model.clone(), model.clone(), model.clone()];
+        drop(model);
+        assert_eq!(inner.strong_count(), 3);
+        // Out of the order they were made in.
+        for clone in clones.into_iter().rev() {
+            assert!(inner.upgrade().is_some());
+            drop(clone);
+        }
+        assert!(inner.upgrade().is_none());
+    }
+    #[test]
+    fn drop_order_recognizer_outlives_model() {
+        let model = crate::test_util::fake_model("models/small");
+        let inner = std::sync::Arc::downgrade(&model.inner);
+        let recognizers = vec![
+            detached_recognizer(&model.clone()),
+            detached_recognizer(&model),
+        ];
+        drop(model);
+        // libvosk keeps its own reference, the wrapper only keeps the path.
+        assert!(inner.upgrade().is_none());
+        for recognizer in recognizers {
+            assert!(format!("{:?}", recognizer).contains("models/small"));
+        }
+    }
+    #[test]
+    fn drop_order_repeated() {
+        let model = crate::test_util::fake_model("model");
+        for _ in 0..100 {
+            let clone = model.clone();
+            let recognizer = detached_recognizer(&clone);
+            drop(clone);
+            drop(recognizer);
+        }
+        assert_eq!(std::sync::Arc::strong_count(&model.inner), 1);
+    }
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn active_recognizers() {
+        let model = crate::test_util::fake_model("model");
+        let mut kept = None;
+        let metrics = crate::telemetry::tests::recorded(|| {
+            kept = Some(detached_recognizer(&model));
+            drop(detached_recognizer(&model));
+        });
+        let active = &metrics[crate::telemetry::RECOGNIZERS_ACTIVE];
+        assert_eq!(format!("{:?}", active), "Gauge(1.0)");
+        drop(kept);
+    }
+    #[test]
+    fn drop_order_across_threads() {
+        let model = crate::test_util::fake_model("model");
+        let inner = std::sync::Arc::downgrade(&model.inner);
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let model = model.clone();
+                std::thread::spawn(move || detached_recognizer(&model))
+            })
+            .collect();
+        drop(model);
+        // Recognizers made on one thread are dropped on another.
+        let recognizers: Vec<Recognizer> = workers.into_iter().map(|w| w.join().unwrap()).collect();
+        assert!(inner.upgrade().is_none());
+        drop(recognizers);
     }
 }
@@ -0,0 +1,141 @@
+use crate::{Error, Model};
+use std::path::Path;
+
+#[cfg(not(feature = "tokio"))]
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+#[cfg(feature = "tokio")]
+use tokio::sync::oneshot::{channel, error::TryRecvError, Receiver};
+
+/// A model being loaded on another thread, see `Model::load_in_background`.
+///
+/// Dropping it before the model is ready doesn't stop the loading,
+/// the thread frees the model when it finds nobody is waiting for it.
+///
+/// With the `tokio` feature, it can also be awaited.
+#[derive(Debug)]
+pub struct ModelLoading {
+    receiver: Receiver<Result<Model, Error>>,
+    result: Option<Result<Model, Error>>,
+}
+
+impl Model {
+    /// Starts loading a model on a new thread, so that the calling thread,
+    /// such as the UI thread of an application, doesn't block for seconds.
+    pub fn load_in_background<P: AsRef<Path>>(path: P) -> ModelLoading {
+        let path = path.as_ref().to_path_buf();
+        let (sender, receiver) = channel();
+        std::thread::spawn(move || {
+            // Fails if the handle was dropped, the model is then freed here.
+            let _ = sender.send(Model::new(path));
+        });
+        ModelLoading {
+            receiver,
+            result: None,
+        }
+    }
+}
+
+impl ModelLoading {
+    /// Returns the result if loading has finished, without blocking.
+    pub fn try_get(&mut self) -> Option<Result<Model, Error>> {
+        if self.result.is_none() {
+            self.result = match self.receiver.try_recv() {
+                Ok(result) => Some(result),
+                Err(TryRecvError::Empty) => None,
+                Err(_) => Some(Err(thread_died())),
+            };
+        }
+        self.result.clone()
+    }
+    /// Blocks until the model is loaded.
+    ///
+    /// With the `tokio` feature this must not be called from async code, await instead.
+    pub fn wait(mut self) -> Result<Model, Error> {
+        if let Some(result) = self.result.take() {
+            return result;
+        }
+        #[cfg(not(feature = "tokio"))]
+        let received = self.receiver.recv();
+        #[cfg(feature = "tokio")]
+        let received = self.receiver.blocking_recv();
+        received.unwrap_or_else(|_| Err(thread_died()))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl std::future::Future for ModelLoading {
+    type Output = Result<Model, Error>;
+
+    fn poll(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        if let Some(result) = self.result.take() {
+            return std::task::Poll::Ready(result);
+        }
+        std::pin::Pin::new(&mut self.receiver)
+            .poll(cx)
+            .map(|received| received.unwrap_or_else(|_| Err(thread_died())))
+    }
+}
+
+/// The loading thread can only go away without sending if libvosk made it panic.
+fn thread_died() -> Error {
+    Error::NoValidModel
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Error, Model};
+    use std::time::Duration;
+
+    #[test]
+    #[cfg_attr(miri, ignore = "calls into libvosk")]
+    fn wait_for_error() {
+        let loading = Model::load_in_background("not_existing");
+        assert!(matches!(loading.wait(), Err(Error::InvalidModel(_))));
+    }
+    #[test]
+    #[cfg_attr(miri, ignore = "calls into libvosk")]
+    fn poll_for_error() {
+        let mut loading = Model::load_in_background("not_existing");
+        let result = loop {
+            if let Some(result) = loading.try_get() {
+                break result;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        };
+        assert!(matches!(result, Err(Error::InvalidModel(_))));
+        // The result stays available.
+        assert!(loading.try_get().is_some());
+        assert!(loading.wait().is_err());
+    }
+    #[test]
+    fn drop_early() {
+        drop(Model::load_in_background("not_existing"));
+    }
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn await_error() {
+        use std::future::Future;
+        use std::sync::Arc;
+        use std::task::{Context, Poll, Wake};
+
+        struct Unpark(std::thread::Thread);
+        impl Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+        let waker = Arc::new(Unpark(std::thread::current())).into();
+        let mut cx = Context::from_waker(&waker);
+        let mut loading = Model::load_in_background("not_existing");
+        let result = loop {
+            match std::pin::Pin::new(&mut loading).poll(&mut cx) {
+                Poll::Ready(result) => break result,
+                Poll::Pending => std::thread::park(),
+            }
+        };
+        assert!(matches!(result, Err(Error::InvalidModel(_))));
+    }
+}
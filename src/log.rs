@@ -0,0 +1,185 @@
+//! Controlling the log output of Kaldi and libvosk.
+//!
+//! libvosk can't report its current log level,
+//! so the level last set through this module is remembered here.
+
+use std::os::raw::c_int;
+use std::sync::Mutex;
+use vosk_sys::vosk_set_log_level;
+
+/// How much Kaldi prints to stderr.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogLevel {
+    /// Nothing except failed assertions, which abort anyway.
+    Silent,
+    /// Only errors.
+    Error,
+    /// Info and error messages, but no debug messages.
+    /// This is the default.
+    #[default]
+    Info,
+    /// Also print debug messages.
+    Debug,
+    /// A raw Kaldi verbosity level:
+    /// less than 0 hides info messages, greater than 0 is more verbose.
+    Verbose(i32),
+}
+
+impl LogLevel {
+    /// The verbosity level as understood by Kaldi.
+    pub fn as_raw(self) -> c_int {
+        match self {
+            LogLevel::Silent => -3,
+            LogLevel::Error => -2,
+            LogLevel::Info => 0,
+            LogLevel::Debug => 1,
+            LogLevel::Verbose(level) => level,
+        }
+    }
+}
+
+/// Silences Kaldi until dropped, see [`suppress`].
+#[derive(Debug)]
+#[must_use = "logging is restored as soon as the guard is dropped"]
+pub struct LogGuard {
+    _private: (),
+}
+
+static STATE: Mutex<State> = Mutex::new(State::new());
+
+/// Sets the log level for Kaldi messages.
+///
+/// While a [`LogGuard`] is alive the new level is remembered
+/// and applied once the last guard is dropped.
+pub fn set_log_level(level: LogLevel) {
+    let mut state = lock();
+    if let Some(apply) = state.set(level) {
+        unsafe { vosk_set_log_level(apply.as_raw()) }
+    }
+}
+
+/// Returns the log level currently in effect,
+/// as far as it has been set through this crate.
+pub fn log_level() -> LogLevel {
+    lock().effective()
+}
+
+/// Silences Kaldi, for example while loading a model,
+/// and restores the previous level when the returned guard is dropped.
+///
+/// Guards can be nested or overlap;
+/// logging comes back when the last one is dropped.
+pub fn suppress() -> LogGuard {
+    let mut state = lock();
+    if let Some(apply) = state.suppress() {
+        unsafe { vosk_set_log_level(apply.as_raw()) }
+    }
+    LogGuard { _private: () }
+}
+
+impl Drop for LogGuard {
+    fn drop(&mut self) {
+        let mut state = lock();
+        if let Some(apply) = state.release() {
+            unsafe { vosk_set_log_level(apply.as_raw()) }
+        }
+    }
+}
+
+fn lock() -> std::sync::MutexGuard<'static, State> {
+    // The state is always consistent between method calls.
+    STATE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Bookkeeping for the level and the number of live guards.
+/// Each method returns the level to pass to libvosk, if it changed.
+#[derive(Debug)]
+struct State {
+    level: LogLevel,
+    suppressed: usize,
+}
+
+impl State {
+    const fn new() -> State {
+        State {
+            level: LogLevel::Info,
+            suppressed: 0,
+        }
+    }
+    fn effective(&self) -> LogLevel {
+        if self.suppressed > 0 {
+            LogLevel::Silent
+        } else {
+            self.level
+        }
+    }
+    fn set(&mut self, level: LogLevel) -> Option<LogLevel> {
+        self.level = level;
+        if self.suppressed > 0 {
+            None
+        } else {
+            Some(level)
+        }
+    }
+    fn suppress(&mut self) -> Option<LogLevel> {
+        self.suppressed += 1;
+        if self.suppressed == 1 {
+            Some(LogLevel::Silent)
+        } else {
+            None
+        }
+    }
+    fn release(&mut self) -> Option<LogLevel> {
+        self.suppressed = self.suppressed.saturating_sub(1);
+        if self.suppressed == 0 {
+            Some(self.level)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LogLevel, State};
+
+    #[test]
+    fn set_applies_immediately() {
+        let mut s = State::new();
+        assert_eq!(s.effective(), LogLevel::Info);
+        assert_eq!(s.set(LogLevel::Debug), Some(LogLevel::Debug));
+        assert_eq!(s.effective(), LogLevel::Debug);
+    }
+    #[test]
+    fn suppress_and_restore() {
+        let mut s = State::new();
+        s.set(LogLevel::Verbose(2));
+        assert_eq!(s.suppress(), Some(LogLevel::Silent));
+        assert_eq!(s.effective(), LogLevel::Silent);
+        assert_eq!(s.release(), Some(LogLevel::Verbose(2)));
+        assert_eq!(s.effective(), LogLevel::Verbose(2));
+    }
+    #[test]
+    fn nested_guards() {
+        let mut s = State::new();
+        assert_eq!(s.suppress(), Some(LogLevel::Silent));
+        assert_eq!(s.suppress(), None);
+        assert_eq!(s.release(), None);
+        assert_eq!(s.effective(), LogLevel::Silent);
+        assert_eq!(s.release(), Some(LogLevel::Info));
+    }
+    #[test]
+    fn set_while_suppressed() {
+        let mut s = State::new();
+        s.suppress();
+        assert_eq!(s.set(LogLevel::Error), None);
+        assert_eq!(s.effective(), LogLevel::Silent);
+        assert_eq!(s.release(), Some(LogLevel::Error));
+    }
+    #[test]
+    fn raw_levels() {
+        assert_eq!(LogLevel::Info.as_raw(), 0);
+        assert!(LogLevel::Silent.as_raw() < LogLevel::Error.as_raw());
+        assert_eq!(LogLevel::Verbose(-1).as_raw(), -1);
+    }
+}
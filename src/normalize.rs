@@ -0,0 +1,88 @@
+//! Unicode normalization of recognized text.
+//!
+//! Depending on how its word list was built, a model may output decomposed characters,
+//! which don't compare equal to the precomposed text most programs use.
+
+use std::borrow::Cow;
+use unicode_normalization::{is_nfc_quick, is_nfkc_quick, IsNormalized, UnicodeNormalization};
+
+/// The Unicode normalization form of results, see `Recognizer::set_normalization`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Normalization {
+    /// Text is left as the model outputs it.
+    #[default]
+    None,
+    /// Canonical composition: `e` followed by a combining accent becomes `é`.
+    Nfc,
+    /// Compatibility composition, which also turns forms such as full-width letters
+    /// and ligatures into plain ones.
+    Nfkc,
+}
+
+impl Normalization {
+    /// Returns `text` in this form, only copying it when it changes.
+    pub fn apply<'a>(self, text: Cow<'a, str>) -> Cow<'a, str> {
+        match self {
+            Normalization::None => text,
+            Normalization::Nfc => {
+                if is_nfc_quick(text.chars()) == IsNormalized::Yes {
+                    text
+                } else {
+                    Cow::Owned(text.nfc().collect())
+                }
+            }
+            Normalization::Nfkc => {
+                if is_nfkc_quick(text.chars()) == IsNormalized::Yes {
+                    text
+                } else {
+                    Cow::Owned(text.nfkc().collect())
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Normalization;
+    use crate::{RecognizedText, RecognizedWord};
+    use std::borrow::Cow;
+
+    /// "café" with a combining acute accent, and "fine" with an "fi" ligature.
+    const DECOMPOSED: &str = r#"{
+        "text": "cafe\u0301 \ufb01ne",
+        "result": [
+            {"word": "cafe\u0301", "conf": 1.0, "start": 0.0, "end": 0.5},
+            {"word": "\ufb01ne", "conf": 1.0, "start": 0.5, "end": 0.9}
+        ]
+    }"#;
+
+    #[test]
+    fn text_and_words() {
+        let result: RecognizedText = serde_json::from_str(DECOMPOSED).unwrap();
+        let result = result.normalize(Normalization::Nfc);
+        assert_eq!(result.text, "caf\u{e9} \u{fb01}ne");
+        assert_eq!(result.result.unwrap()[0].word(), "caf\u{e9}");
+    }
+    #[test]
+    fn compatibility() {
+        let result: RecognizedText = serde_json::from_str(DECOMPOSED).unwrap();
+        let result = result.normalize(Normalization::Nfkc).into_owned();
+        assert_eq!(result.text, "caf\u{e9} fine");
+        assert_eq!(
+            result.result.unwrap(),
+            vec![
+                RecognizedWord::new("caf\u{e9}", 1.0, 0.0, 0.5),
+                RecognizedWord::new("fine", 1.0, 0.5, 0.9),
+            ]
+        );
+    }
+    #[test]
+    fn unchanged() {
+        let result: RecognizedText = serde_json::from_str(DECOMPOSED).unwrap();
+        let result = result.normalize(Normalization::None);
+        assert_eq!(result.text, "cafe\u{301} \u{fb01}ne");
+        let composed = Normalization::Nfc.apply(Cow::Borrowed("caf\u{e9}"));
+        assert!(matches!(composed, Cow::Borrowed(_)));
+    }
+}
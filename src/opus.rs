@@ -0,0 +1,205 @@
+//! Feeding Opus packets, as received from VoIP stacks, to a recognizer.
+//!
+//! libopus decodes directly at the rate of the recognizer,
+//! which must be one of 8, 12, 16, 24 or 48 kHz.
+
+use crate::feeder::FeederCore;
+use crate::latency::LatencyStats;
+use crate::{Error, Event, Recognizer, UtteranceOwned};
+use ::opus::{Channels, Decoder};
+use std::time::Duration;
+
+/// Sample rates libopus can decode at.
+const OPUS_RATES: [u32; 5] = [8000, 12000, 16000, 24000, 48000];
+
+/// Decodes Opus packets and feeds the audio to a recognizer.
+pub struct OpusFeeder {
+    core: FeederCore,
+    decoder: PacketDecoder,
+    samples: Vec<i16>,
+}
+
+impl OpusFeeder {
+    /// Creates a feeder for packets with 1 or 2 `channels`;
+    /// the audio is mixed down to mono.
+    pub fn new(recognizer: Recognizer, channels: usize) -> Result<OpusFeeder, Error> {
+        let rate = recognizer.sample_rate();
+        if rate.fract() != 0.0 || !OPUS_RATES.contains(&(rate as u32)) {
+            return Err(Error::UnsupportedCodec(format!(
+                "Opus can't be decoded at {} Hz",
+                rate
+            )));
+        }
+        let decoder = PacketDecoder::new(rate as u32, channels)?;
+        Ok(OpusFeeder {
+            core: FeederCore::new(recognizer),
+            decoder,
+            samples: Vec::new(),
+        })
+    }
+    /// Decodes one packet and feeds it to the recognizer.
+    ///
+    /// An empty packet stands for a lost one;
+    /// its audio is concealed by libopus so that timestamps stay aligned.
+    ///
+    /// Returns an event when an utterance was completed or the partial result changed.
+    pub fn push_packet(&mut self, packet: &[u8]) -> Result<Option<Event>, Error> {
+        self.samples.clear();
+        self.decoder.decode(packet, &mut self.samples)?;
+        Ok(self.feed())
+    }
+    /// Feeds silence for a period without packets, such as discontinuous transmission (DTX).
+    pub fn push_silence(&mut self, duration: Duration) -> Option<Event> {
+        self.samples.clear();
+        self.decoder.silence(duration, &mut self.samples);
+        self.feed()
+    }
+    /// Returns the last utterance at the end of the stream.
+    pub fn finish(&mut self) -> UtteranceOwned {
+        self.core.finish()
+    }
+    /// Starts measuring how long audio takes to show up in a partial result,
+    /// from the call that pushed it.
+    pub fn enable_latency(&mut self) {
+        self.core.enable_latency()
+    }
+    /// The measurements so far, if enabled.
+    pub fn latency(&self) -> Option<LatencyStats> {
+        self.core.latency()
+    }
+    pub fn recognizer(&self) -> &Recognizer {
+        &self.core.recognizer
+    }
+    pub fn recognizer_mut(&mut self) -> &mut Recognizer {
+        &mut self.core.recognizer
+    }
+    pub fn into_inner(self) -> Recognizer {
+        self.core.recognizer
+    }
+    fn feed(&mut self) -> Option<Event> {
+        self.core.feed(&self.samples, true);
+        self.core.next_event()
+    }
+}
+
+/// Turns Opus packets into mono samples.
+struct PacketDecoder {
+    decoder: Decoder,
+    channels: usize,
+    sample_rate: u32,
+    interleaved: Vec<i16>,
+}
+
+impl PacketDecoder {
+    fn new(sample_rate: u32, channels: usize) -> Result<PacketDecoder, Error> {
+        let layout = match channels {
+            1 => Channels::Mono,
+            2 => Channels::Stereo,
+            n => {
+                return Err(Error::UnsupportedCodec(format!(
+                    "Opus streams have 1 or 2 channels, not {}",
+                    n
+                )))
+            }
+        };
+        let decoder = Decoder::new(sample_rate, layout).map_err(from_opus)?;
+        Ok(PacketDecoder {
+            decoder,
+            channels,
+            sample_rate,
+            interleaved: Vec::new(),
+        })
+    }
+    /// Appends the decoded samples to `out`.
+    fn decode(&mut self, packet: &[u8], out: &mut Vec<i16>) -> Result<(), Error> {
+        let frame = if packet.is_empty() {
+            // Concealment must cover exactly the missing duration,
+            // assume it's the same as the previous packet.
+            match self.decoder.get_last_packet_duration().map_err(from_opus)? {
+                0 => self.sample_rate as usize / 50,
+                n => n as usize,
+            }
+        } else {
+            // The longest packet is 120 ms.
+            self.sample_rate as usize * 120 / 1000
+        };
+        self.interleaved.resize(frame * self.channels, 0);
+        let n = self
+            .decoder
+            .decode(packet, &mut self.interleaved, false)
+            .map_err(from_opus)?;
+        crate::pcm::downmix(&self.interleaved[..n * self.channels], self.channels, out);
+        Ok(())
+    }
+    fn silence(&mut self, duration: Duration, out: &mut Vec<i16>) {
+        let n = (duration.as_secs_f64() * self.sample_rate as f64).round() as usize;
+        out.resize(out.len() + n, 0);
+    }
+}
+
+fn from_opus(e: ::opus::Error) -> Error {
+    Error::InvalidPacket(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PacketDecoder;
+    use ::opus::{Application, Channels, Encoder};
+    use std::time::Duration;
+
+    /// 20 ms stereo frames of a tone, encoded at 48 kHz.
+    fn packets(count: usize) -> Vec<Vec<u8>> {
+        let mut encoder = Encoder::new(48000, Channels::Stereo, Application::Voip).unwrap();
+        (0..count)
+            .map(|i| {
+                let frame: Vec<i16> = (0..960 * 2)
+                    .map(|j| {
+                        let t = (i * 960 + j / 2) as f32 / 48000.0;
+                        ((t * 440.0 * std::f32::consts::TAU).sin() * 8000.0) as i16
+                    })
+                    .collect();
+                encoder.encode_vec(&frame, 4000).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn decode_at_model_rate() {
+        let mut d = PacketDecoder::new(16000, 2).unwrap();
+        let mut out = Vec::new();
+        for p in packets(5) {
+            d.decode(&p, &mut out).unwrap();
+        }
+        assert_eq!(out.len(), 5 * 320);
+        assert!(out.iter().any(|&s| s != 0));
+    }
+    #[test]
+    fn conceal_lost_packet() {
+        let mut d = PacketDecoder::new(16000, 2).unwrap();
+        let mut out = Vec::new();
+        let p = packets(2);
+        d.decode(&p[0], &mut out).unwrap();
+        d.decode(&[], &mut out).unwrap();
+        d.decode(&p[1], &mut out).unwrap();
+        assert_eq!(out.len(), 3 * 320);
+    }
+    #[test]
+    fn lost_before_first_packet() {
+        let mut d = PacketDecoder::new(8000, 1).unwrap();
+        let mut out = Vec::new();
+        d.decode(&[], &mut out).unwrap();
+        assert_eq!(out.len(), 160);
+    }
+    #[test]
+    fn dtx_silence() {
+        let mut d = PacketDecoder::new(16000, 1).unwrap();
+        let mut out = vec![1];
+        d.silence(Duration::from_millis(60), &mut out);
+        assert_eq!(out.len(), 1 + 960);
+        assert!(out[1..].iter().all(|&s| s == 0));
+    }
+    #[test]
+    fn bad_channels() {
+        assert!(PacketDecoder::new(16000, 3).is_err());
+    }
+}
@@ -0,0 +1,508 @@
+//! What to do when recognition can't keep up with live audio.
+//!
+//! On slow hardware a recognizer may process audio slower than real time,
+//! so samples pile up in the `AudioRing` until it's full and new audio is lost.
+//! A `RingFeeder` watches how much audio is waiting and, beyond a threshold,
+//! applies an `OverloadPolicy`, reporting it with `Event::Overloaded`.
+//!
+//! A `RingFeeder` can also be paused, like a mute button: the utterance in
+//! progress is finalized, then audio is discarded as it arrives until it's resumed.
+//!
+//! With `with_speech_activity` it also reports when someone starts and stops
+//! speaking, see the `activity` module.
+
+use crate::activity::{ActivityOptions, SpeechActivity};
+use crate::feeder::FeederCore;
+use crate::pause::{PauseControl, ResumePolicy};
+use crate::ring::AudioConsumer;
+use crate::tee::AudioTee;
+use crate::{duration_of, telemetry, CancellationToken, Event, Recognizer};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How to catch up when too much audio is waiting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverloadPolicy {
+    /// Discard the oldest audio down to half the threshold.
+    /// Speech in it is lost, but results stay close to real time.
+    DropOldest,
+    /// Stop asking for partial results, which take time to compute,
+    /// until the backlog is down to half the threshold.
+    SkipPartials,
+    /// Feed all the waiting audio at once, which has less overhead than
+    /// many small chunks. Partial results are skipped meanwhile.
+    Coalesce,
+}
+
+/// When and how a `RingFeeder` handles overload.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverloadOptions {
+    /// How much waiting audio counts as overload.
+    pub threshold: Duration,
+    pub policy: OverloadPolicy,
+    /// Audio fed to the recognizer at a time while not overloaded.
+    pub chunk: Duration,
+    /// How long to sleep while waiting for audio.
+    pub poll: Duration,
+}
+
+impl Default for OverloadOptions {
+    fn default() -> Self {
+        OverloadOptions {
+            threshold: Duration::from_secs(2),
+            policy: OverloadPolicy::DropOldest,
+            chunk: Duration::from_millis(100),
+            poll: Duration::from_millis(10),
+        }
+    }
+}
+
+/// Feeds the audio of a ring to a recognizer on the recognition thread,
+/// handling overload as configured.
+#[derive(Debug)]
+pub struct RingFeeder {
+    consumer: AudioConsumer,
+    core: FeederCore,
+    monitor: OverloadMonitor,
+    buf: Vec<i16>,
+    /// Time spent feeding the recognizer.
+    busy: Duration,
+    cancel: Option<CancellationToken>,
+    tee: Option<AudioTee>,
+    pause: PauseControl,
+    resume_policy: ResumePolicy,
+    /// Whether the last `Event::Paused` wasn't followed by `Event::Resumed` yet.
+    paused: bool,
+    /// Samples discarded since the last pause.
+    discarded: u64,
+    /// Whether audio was fed since the last utterance was finalized.
+    in_flight: bool,
+}
+
+impl RingFeeder {
+    pub fn new(consumer: AudioConsumer, recognizer: Recognizer, opts: OverloadOptions) -> Self {
+        let monitor = OverloadMonitor::new(opts, recognizer.sample_rate());
+        RingFeeder {
+            consumer,
+            core: FeederCore::new(recognizer),
+            monitor,
+            buf: Vec::new(),
+            busy: Duration::ZERO,
+            cancel: None,
+            tee: None,
+            pause: PauseControl::new(),
+            resume_policy: ResumePolicy::default(),
+            paused: false,
+            discarded: 0,
+            in_flight: false,
+        }
+    }
+    /// Stops feeding once `cancel` is cancelled, as if the producer was dropped.
+    ///
+    /// It's checked between chunks and while waiting for audio, every `poll` of the options.
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+    /// Also writes the audio fed to the recognizer to `tee`, leaving out what
+    /// was dropped to catch up, so that it matches the times of the results.
+    pub fn with_tee(mut self, tee: AudioTee) -> Self {
+        self.tee = Some(tee);
+        self
+    }
+    /// Pauses with `control` instead of a switch of its own, so that several
+    /// feeders can share a mute button.
+    pub fn with_pause_control(mut self, control: PauseControl) -> Self {
+        self.pause = control;
+        self
+    }
+    pub fn with_resume_policy(mut self, policy: ResumePolicy) -> Self {
+        self.resume_policy = policy;
+        self
+    }
+    /// Also reports `Event::SpeechStarted` and `Event::SpeechEnded`, judging from
+    /// the partial results. While overloaded without partial results, speech only
+    /// ends with its utterance.
+    pub fn with_speech_activity(mut self, opts: ActivityOptions) -> Self {
+        self.core.set_speech_activity(SpeechActivity::new(opts));
+        self
+    }
+    /// The switch that pauses this feeder, to keep on another thread.
+    pub fn pause_control(&self) -> PauseControl {
+        self.pause.clone()
+    }
+    /// Pauses recognition from the next call to `next_event`, which finalizes the
+    /// utterance in progress with `Event::Final`, then returns `Event::Paused`.
+    pub fn pause(&self) {
+        self.pause.pause();
+    }
+    /// Resumes recognition, which `next_event` reports with `Event::Resumed`.
+    pub fn resume(&self) {
+        self.pause.resume();
+    }
+    /// Waits for the next event, returning None once the producer was dropped
+    /// and all of its audio was fed, or once cancelled.
+    ///
+    /// The last utterance isn't finalized then, call `finish` for it.
+    pub fn next_event(&mut self) -> Option<Event> {
+        loop {
+            if let Some(event) = self.core.next_event() {
+                return Some(event);
+            }
+            // Audio still in the ring is left there.
+            if is_cancelled(&self.cancel) {
+                return None;
+            }
+            if self.pause.is_paused() != self.paused {
+                self.set_paused(!self.paused);
+                continue;
+            }
+            if self.paused {
+                self.discard();
+                if self.consumer.is_closed() {
+                    return None;
+                }
+                thread::sleep(self.monitor.opts.poll);
+                continue;
+            }
+            let stats = self.consumer.stats();
+            let plan = self.monitor.plan(self.consumer.available(), stats.dropped);
+            if plan.skip > 0 {
+                self.consumer.skip(plan.skip);
+            }
+            self.core.events.extend(plan.event);
+            self.buf.resize(plan.read, 0);
+            let cancel = &self.cancel;
+            let pause = &self.pause;
+            let n = self
+                .consumer
+                .read_chunk_until(&mut self.buf, self.monitor.opts.poll, || {
+                    is_cancelled(cancel) || pause.is_paused()
+                });
+            if n == 0 && self.pause.is_paused() {
+                continue;
+            }
+            // Audio read just as it was cancelled is dropped.
+            if n == 0 || is_cancelled(cancel) {
+                return self.core.next_event();
+            }
+            if let Some(tee) = &mut self.tee {
+                tee.write(&self.buf[..n]);
+            }
+            let start = Instant::now();
+            let completed = self.core.feed(&self.buf[..n], plan.partials);
+            self.in_flight = !completed;
+            self.busy += start.elapsed();
+        }
+    }
+    /// Time spent recognizing per second of audio fed so far, which must stay
+    /// below 1 for recognition to keep up. None before any audio was fed.
+    pub fn real_time_factor(&self) -> Option<f64> {
+        let audio = self.core.recognizer.audio_duration();
+        if audio.is_zero() {
+            return None;
+        }
+        Some(self.busy.as_secs_f64() / audio.as_secs_f64())
+    }
+    /// Finalizes the utterance in progress.
+    ///
+    /// Speech in progress ends without an `Event::SpeechEnded`, which `next_event`
+    /// would only return after this.
+    pub fn finish(&mut self) -> Event {
+        self.in_flight = false;
+        Event::Final(self.core.finish())
+    }
+    pub fn recognizer(&self) -> &Recognizer {
+        &self.core.recognizer
+    }
+    pub fn recognizer_mut(&mut self) -> &mut Recognizer {
+        &mut self.core.recognizer
+    }
+    pub fn into_inner(self) -> (AudioConsumer, Recognizer) {
+        (self.consumer, self.core.recognizer)
+    }
+    fn set_paused(&mut self, paused: bool) {
+        if paused {
+            // The partial results shown so far would be lost otherwise.
+            if self.in_flight {
+                self.core.speech_finalized();
+                let last = self.finish();
+                self.core.events.push_back(last);
+            }
+            self.discarded = 0;
+            self.discard();
+            self.core.events.push_back(Event::Paused);
+        } else {
+            self.discard();
+            if self.resume_policy == ResumePolicy::ResetOnResume {
+                self.core.recognizer.reset();
+            }
+            self.core.events.push_back(Event::Resumed {
+                discarded: duration_of(self.discarded, self.core.recognizer.sample_rate()),
+            });
+        }
+        self.paused = paused;
+    }
+    /// Skips the audio waiting in the ring.
+    fn discard(&mut self) {
+        let available = self.consumer.available();
+        self.discarded += self.consumer.skip(available) as u64;
+    }
+}
+
+fn is_cancelled(cancel: &Option<CancellationToken>) -> bool {
+    cancel.as_ref().is_some_and(|c| c.is_cancelled())
+}
+
+/// What to do before the next read.
+#[derive(Debug)]
+struct Plan {
+    /// Samples to discard.
+    skip: usize,
+    /// Samples to read.
+    read: usize,
+    /// Whether to get the partial result after feeding.
+    partials: bool,
+    event: Option<Event>,
+}
+
+/// Decides what to do from the number of samples waiting.
+#[derive(Debug, Clone)]
+struct OverloadMonitor {
+    opts: OverloadOptions,
+    sample_rate: f32,
+    overloaded: bool,
+    /// Samples dropped by the producer when last checked.
+    ring_dropped: u64,
+}
+
+impl OverloadMonitor {
+    fn new(opts: OverloadOptions, sample_rate: f32) -> OverloadMonitor {
+        OverloadMonitor {
+            opts,
+            sample_rate,
+            overloaded: false,
+            ring_dropped: 0,
+        }
+    }
+    fn samples(&self, duration: Duration) -> usize {
+        (duration.as_secs_f64() * self.sample_rate as f64) as usize
+    }
+    /// With `available` samples waiting and `ring_dropped` the total the producer dropped.
+    fn plan(&mut self, available: usize, ring_dropped: u64) -> Plan {
+        let threshold = self.samples(self.opts.threshold);
+        let chunk = self.samples(self.opts.chunk).max(1);
+        let newly_dropped = ring_dropped - self.ring_dropped;
+        self.ring_dropped = ring_dropped;
+        let entering = available > threshold && !self.overloaded;
+        if available > threshold {
+            self.overloaded = true;
+        } else if available <= threshold / 2 {
+            self.overloaded = false;
+        }
+        let mut plan = Plan {
+            skip: 0,
+            read: chunk,
+            partials: !self.overloaded,
+            event: None,
+        };
+        match self.opts.policy {
+            OverloadPolicy::DropOldest => {
+                plan.partials = true;
+                if available > threshold {
+                    plan.skip = available - threshold / 2;
+                }
+                // Dropping is over at once, so each drop is reported.
+                self.overloaded = false;
+            }
+            OverloadPolicy::SkipPartials => {}
+            OverloadPolicy::Coalesce if self.overloaded => plan.read = available.max(chunk),
+            OverloadPolicy::Coalesce => {}
+        }
+        if entering || plan.skip > 0 || newly_dropped > 0 {
+            let dropped = duration_of(plan.skip as u64 + newly_dropped, self.sample_rate);
+            telemetry::overloaded(self.opts.policy, dropped);
+            plan.event = Some(Event::Overloaded {
+                policy: self.opts.policy,
+                backlog: duration_of(available as u64, self.sample_rate),
+                dropped,
+            });
+        }
+        plan
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ring::AudioRing;
+
+    fn monitor(policy: OverloadPolicy) -> OverloadMonitor {
+        let opts = OverloadOptions {
+            threshold: Duration::from_secs(2),
+            policy,
+            chunk: Duration::from_millis(100),
+            poll: Duration::ZERO,
+        };
+        OverloadMonitor::new(opts, 100.0)
+    }
+    fn overloaded(plan: &Plan) -> Option<(Duration, Duration)> {
+        match plan.event {
+            Some(Event::Overloaded {
+                backlog, dropped, ..
+            }) => Some((backlog, dropped)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn slow_consumer_drops_oldest() {
+        let mut monitor = monitor(OverloadPolicy::DropOldest);
+        let (mut producer, mut consumer) = AudioRing::new(1000).split();
+        let samples: Vec<i16> = (0..250).collect();
+        producer.push_slice(&samples);
+        // The consumer only reads 10 samples for every 250 pushed.
+        let plan = monitor.plan(consumer.available(), consumer.stats().dropped);
+        assert_eq!(plan.skip, 150);
+        assert_eq!(
+            overloaded(&plan),
+            Some((Duration::from_millis(2500), Duration::from_millis(1500)))
+        );
+        consumer.skip(plan.skip);
+        let mut chunk = vec![0; plan.read];
+        assert_eq!(consumer.pop_slice(&mut chunk), 10);
+        assert_eq!(chunk[0], 150, "the oldest audio is gone");
+        let plan = monitor.plan(consumer.available(), consumer.stats().dropped);
+        assert_eq!((plan.skip, overloaded(&plan)), (0, None));
+        producer.push_slice(&samples);
+        let plan = monitor.plan(consumer.available(), consumer.stats().dropped);
+        assert_eq!(plan.skip, 240);
+        assert!(plan.partials);
+    }
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn dropped_audio_counted() {
+        use metrics_util::debugging::DebugValue;
+        let metrics = telemetry::tests::recorded(|| {
+            let mut monitor = monitor(OverloadPolicy::DropOldest);
+            monitor.plan(250, 0);
+            monitor.plan(90, 0);
+            monitor.plan(90, 40);
+        });
+        assert_eq!(
+            metrics["vosk_overloads_total{policy=drop_oldest}"],
+            DebugValue::Counter(2)
+        );
+        assert_eq!(
+            metrics[telemetry::OVERLOAD_DROPPED],
+            DebugValue::Counter(1900)
+        );
+    }
+    #[test]
+    fn skip_partials_until_caught_up() {
+        let mut monitor = monitor(OverloadPolicy::SkipPartials);
+        assert!(monitor.plan(150, 0).partials);
+        let plan = monitor.plan(250, 0);
+        assert!(!plan.partials);
+        assert_eq!(plan.skip, 0);
+        assert!(overloaded(&plan).is_some());
+        // Reported once until it's over.
+        assert_eq!(overloaded(&monitor.plan(300, 0)), None);
+        assert!(!monitor.plan(150, 0).partials);
+        assert!(monitor.plan(100, 0).partials);
+        assert!(overloaded(&monitor.plan(250, 0)).is_some());
+    }
+    #[test]
+    fn coalesce() {
+        let mut monitor = monitor(OverloadPolicy::Coalesce);
+        assert_eq!(monitor.plan(150, 0).read, 10);
+        let plan = monitor.plan(250, 0);
+        assert_eq!((plan.read, plan.partials), (250, false));
+        assert_eq!(monitor.plan(120, 0).read, 120);
+        assert_eq!(monitor.plan(5, 0).read, 10);
+    }
+    #[test]
+    fn full_ring_is_reported() {
+        let mut monitor = monitor(OverloadPolicy::SkipPartials);
+        let (mut producer, consumer) = AudioRing::new(50).split();
+        producer.push_slice(&[0; 80]);
+        let plan = monitor.plan(consumer.available(), consumer.stats().dropped);
+        assert_eq!(
+            overloaded(&plan),
+            Some((Duration::from_millis(500), Duration::from_millis(300)))
+        );
+        assert_eq!(overloaded(&monitor.plan(50, 30)), None);
+    }
+    #[test]
+    fn cancel_while_waiting() {
+        let model = crate::test_util::fake_model("model");
+        let recognizer = Recognizer::from_ptr(std::ptr::null_mut(), &model, 16000.0, None);
+        let (mut producer, consumer) = AudioRing::new(16000).split();
+        let cancel = CancellationToken::new();
+        let opts = OverloadOptions {
+            poll: Duration::from_millis(5),
+            ..OverloadOptions::default()
+        };
+        let mut feeder =
+            RingFeeder::new(consumer, recognizer, opts).with_cancellation(cancel.clone());
+        // Less than a chunk, so the feeder keeps waiting for more.
+        producer.push_slice(&[0; 100]);
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            cancel.cancel();
+        });
+        let start = Instant::now();
+        assert_eq!(feeder.next_event(), None);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        canceller.join().unwrap();
+        // The producer is still there.
+        producer.push_slice(&[0; 3200]);
+        assert_eq!(feeder.next_event(), None);
+    }
+    #[test]
+    fn paused_audio_is_discarded() {
+        let model = crate::test_util::fake_model("model");
+        let recognizer = Recognizer::from_ptr(std::ptr::null_mut(), &model, 16000.0, None);
+        let (mut producer, consumer) = AudioRing::new(16000).split();
+        let opts = OverloadOptions {
+            poll: Duration::from_millis(1),
+            ..OverloadOptions::default()
+        };
+        // Continue, since resetting would call into libvosk.
+        let mut feeder =
+            RingFeeder::new(consumer, recognizer, opts).with_resume_policy(ResumePolicy::Continue);
+        let mute = feeder.pause_control();
+        mute.pause();
+        producer.push_slice(&[1; 1600]);
+        // Nothing was fed, so there is no utterance to finalize.
+        assert_eq!(feeder.next_event(), Some(Event::Paused));
+        let device = std::thread::spawn(move || {
+            producer.push_slice(&[2; 800]);
+            std::thread::sleep(Duration::from_millis(20));
+            producer.push_slice(&[3; 800]);
+            mute.resume();
+        });
+        assert_eq!(
+            feeder.next_event(),
+            Some(Event::Resumed {
+                discarded: Duration::from_millis(200)
+            })
+        );
+        device.join().unwrap();
+        assert_eq!(feeder.next_event(), None);
+        assert_eq!(feeder.recognizer().samples_processed(), 0);
+    }
+    #[test]
+    fn closed_while_paused() {
+        let model = crate::test_util::fake_model("model");
+        let recognizer = Recognizer::from_ptr(std::ptr::null_mut(), &model, 8000.0, None);
+        let (mut producer, consumer) = AudioRing::new(8000).split();
+        let mut feeder = RingFeeder::new(consumer, recognizer, OverloadOptions::default());
+        feeder.pause();
+        producer.push_slice(&[0; 4000]);
+        drop(producer);
+        assert_eq!(feeder.next_event(), Some(Event::Paused));
+        assert_eq!(feeder.next_event(), None);
+        assert!(feeder.pause_control().is_paused());
+    }
+}
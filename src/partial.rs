@@ -0,0 +1,252 @@
+//! Helpers for showing partial results without flicker.
+
+use std::time::{Duration, Instant};
+
+/// Tells whether the partial result changed since the last call,
+/// to avoid showing the same text twice.
+#[derive(Debug, Default, Clone)]
+pub struct PartialTracker {
+    last: String,
+}
+
+impl PartialTracker {
+    /// Returns `partial` if it differs from the text of the previous call.
+    pub fn changed(&mut self, partial: &str) -> Option<&str> {
+        if partial == self.last {
+            return None;
+        }
+        self.last.clear();
+        self.last.push_str(partial);
+        Some(&self.last)
+    }
+    /// Returns the last text, leaving the tracker as if it had been reset.
+    pub fn take_last(&mut self) -> String {
+        std::mem::take(&mut self.last)
+    }
+    /// Forgets the last text. Call it when an utterance is finalized,
+    /// because the partial result of the next one starts empty.
+    pub fn reset(&mut self) {
+        self.last.clear();
+    }
+}
+
+/// Holds back partial results until they settle.
+///
+/// Partial results change every few tens of milliseconds while someone speaks.
+/// `update` only returns text once it has stayed the same for the hold time,
+/// or, with `with_prefix_words`, once enough leading words have, so that
+/// a long sentence shows up word by word rather than all at the end.
+#[derive(Debug, Clone)]
+pub struct StablePartial {
+    hold: Duration,
+    prefix_words: Option<usize>,
+    current: String,
+    /// End of each word in `current` and when it took its current form.
+    words: Vec<(usize, Instant)>,
+    changed_at: Option<Instant>,
+    emitted: String,
+}
+
+impl StablePartial {
+    /// Emits text that stayed unchanged for `hold`.
+    pub fn new(hold: Duration) -> StablePartial {
+        StablePartial {
+            hold,
+            prefix_words: None,
+            current: String::new(),
+            words: Vec::new(),
+            changed_at: None,
+            emitted: String::new(),
+        }
+    }
+    /// Also emits the leading words that stayed unchanged for the hold time,
+    /// once there are at least `n` of them.
+    pub fn with_prefix_words(mut self, n: usize) -> StablePartial {
+        self.prefix_words = Some(n);
+        self
+    }
+    /// Takes the latest partial result, returning what should be shown if it changed.
+    pub fn update(&mut self, partial: &str, now: Instant) -> Option<&str> {
+        if partial != self.current || self.changed_at.is_none() {
+            self.replace(partial, now);
+        }
+        let stable = self
+            .words
+            .iter()
+            .take_while(|(_, since)| now.duration_since(*since) >= self.hold)
+            .count();
+        let all_stable = self
+            .changed_at
+            .is_some_and(|at| now.duration_since(at) >= self.hold);
+        let end = if all_stable {
+            self.current.len()
+        } else {
+            match self.prefix_words {
+                Some(n) if stable >= n.max(1) => self.words[stable - 1].0,
+                _ => return None,
+            }
+        };
+        self.emit(end)
+    }
+    /// Takes a finalized result, which is shown right away and replaces the partial.
+    ///
+    /// Returns None if the text is what was shown last.
+    pub fn finalize(&mut self, text: &str) -> Option<&str> {
+        self.current.clear();
+        self.words.clear();
+        self.changed_at = None;
+        if text == self.emitted {
+            return None;
+        }
+        self.emitted.clear();
+        self.emitted.push_str(text);
+        Some(&self.emitted)
+    }
+    fn replace(&mut self, partial: &str, now: Instant) {
+        let kept = self
+            .current
+            .split_whitespace()
+            .zip(partial.split_whitespace())
+            .take_while(|(old, new)| old == new)
+            .count();
+        self.words.truncate(kept);
+        for (i, end) in word_ends(partial).enumerate() {
+            match self.words.get_mut(i) {
+                // A kept word, possibly moved by a change in whitespace.
+                Some(word) => word.0 = end,
+                None => self.words.push((end, now)),
+            }
+        }
+        self.current.clear();
+        self.current.push_str(partial);
+        self.changed_at = Some(now);
+    }
+    fn emit(&mut self, end: usize) -> Option<&str> {
+        let text = self.current[..end].trim_end();
+        if text == self.emitted {
+            return None;
+        }
+        self.emitted.clear();
+        self.emitted.push_str(text);
+        Some(&self.emitted)
+    }
+}
+
+/// Byte offsets just past each whitespace-separated word.
+fn word_ends(text: &str) -> impl Iterator<Item = usize> + '_ {
+    text.split_whitespace()
+        .map(move |word| word.as_ptr() as usize - text.as_ptr() as usize + word.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_partial() {
+        let mut tracker = PartialTracker::default();
+        assert_eq!(tracker.changed("the"), Some("the"));
+        assert_eq!(tracker.changed("the"), None);
+        assert_eq!(tracker.changed("the cat"), Some("the cat"));
+    }
+    #[test]
+    fn empty_partial() {
+        let mut tracker = PartialTracker::default();
+        assert_eq!(tracker.changed(""), None);
+        tracker.changed("the");
+        assert_eq!(tracker.changed(""), Some(""));
+    }
+    #[test]
+    fn reset_tracker() {
+        let mut tracker = PartialTracker::default();
+        tracker.changed("the cat");
+        tracker.reset();
+        assert_eq!(tracker.changed(""), None);
+        assert_eq!(tracker.changed("the cat"), Some("the cat"));
+        assert_eq!(tracker.take_last(), "the cat");
+        assert_eq!(tracker.changed("the cat"), Some("the cat"));
+    }
+
+    /// Feeds `(milliseconds, partial)` steps, collecting what's emitted at each.
+    fn run(tracker: &mut StablePartial, script: &[(u64, &str)]) -> Vec<Option<String>> {
+        let start = Instant::now();
+        script
+            .iter()
+            .map(|&(ms, text)| {
+                tracker
+                    .update(text, start + Duration::from_millis(ms))
+                    .map(String::from)
+            })
+            .collect()
+    }
+    fn some(text: &str) -> Option<String> {
+        Some(text.to_string())
+    }
+
+    #[test]
+    fn hold() {
+        let mut tracker = StablePartial::new(Duration::from_millis(200));
+        let emitted = run(
+            &mut tracker,
+            &[
+                (0, "the ca"),
+                (100, "the cat s"),
+                (200, "the cat sat"),
+                (300, "the cat sat"),
+                (400, "the cat sat"),
+                (500, "the cat sat"),
+            ],
+        );
+        assert_eq!(
+            emitted,
+            vec![None, None, None, None, some("the cat sat"), None]
+        );
+    }
+    #[test]
+    fn stable_prefix() {
+        let mut tracker = StablePartial::new(Duration::from_millis(200)).with_prefix_words(2);
+        let emitted = run(
+            &mut tracker,
+            &[
+                (0, "the cat"),
+                (100, "the cat sat"),
+                (200, "the cat sat on"),
+                (300, "the cat sat on the"),
+                (400, "the cat sat on the mat"),
+                (500, "the cat sat on the mat"),
+                (600, "the cat sat on the mat"),
+            ],
+        );
+        assert_eq!(
+            emitted,
+            vec![
+                None,
+                None,
+                some("the cat"),
+                some("the cat sat"),
+                some("the cat sat on"),
+                some("the cat sat on the"),
+                some("the cat sat on the mat"),
+            ]
+        );
+    }
+    #[test]
+    fn changed_word_restarts_hold() {
+        let mut tracker = StablePartial::new(Duration::from_millis(200)).with_prefix_words(1);
+        let emitted = run(
+            &mut tracker,
+            &[(0, "a cat"), (150, "a hat"), (250, "a hat"), (350, "a hat")],
+        );
+        assert_eq!(emitted, vec![None, None, some("a"), some("a hat")]);
+    }
+    #[test]
+    fn finalized_result() {
+        let mut tracker = StablePartial::new(Duration::from_millis(200));
+        run(&mut tracker, &[(0, "the cat"), (250, "the cat")]);
+        assert_eq!(tracker.finalize("the cat sat"), Some("the cat sat"));
+        assert_eq!(tracker.finalize("the cat sat"), None);
+        // The next utterance starts over.
+        let emitted = run(&mut tracker, &[(1000, ""), (1300, ""), (1400, "dog")]);
+        assert_eq!(emitted, vec![None, some(""), None]);
+    }
+}
@@ -0,0 +1,64 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A mute button for live recognition, see `RingFeeder::pause_control`.
+///
+/// Clones share the same switch, so the feeder can run on its own thread while
+/// another one, such as the UI thread, pauses and resumes it. Audio arriving while
+/// paused is discarded rather than kept for later.
+#[derive(Debug, Clone, Default)]
+pub struct PauseControl {
+    paused: Arc<AtomicBool>,
+}
+
+impl PauseControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Release);
+    }
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::Release);
+    }
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Acquire)
+    }
+}
+
+/// Two controls are equal if they are clones of each other.
+impl PartialEq for PauseControl {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.paused, &other.paused)
+    }
+}
+
+/// What happens to the recognizer when recognition resumes after a pause.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResumePolicy {
+    /// Start from scratch with `Recognizer::reset`, so nothing said before the
+    /// pause affects what's recognized after it.
+    #[default]
+    ResetOnResume,
+    /// Carry on with the same recognizer. Its times skip the paused audio,
+    /// which was never fed to it.
+    Continue,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clones_share_the_switch() {
+        let control = PauseControl::new();
+        let ui = control.clone();
+        assert!(!control.is_paused());
+        ui.pause();
+        assert!(control.is_paused());
+        control.resume();
+        assert!(!ui.is_paused());
+        assert_eq!(control, ui);
+        assert_ne!(control, PauseControl::new());
+    }
+}
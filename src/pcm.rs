@@ -0,0 +1,137 @@
+//! Conversion of raw PCM bytes into samples.
+
+use crate::Error;
+
+/// Turns little-endian 16-bit PCM bytes into samples,
+/// keeping an odd trailing byte until the rest of its sample arrives.
+#[derive(Debug, Default)]
+pub(crate) struct LeDecoder {
+    leftover: Option<u8>,
+}
+
+impl LeDecoder {
+    /// Appends the complete samples in `bytes` to `out`.
+    pub(crate) fn decode(&mut self, mut bytes: &[u8], out: &mut Vec<i16>) {
+        if let (Some(low), Some((&high, rest))) = (self.leftover, bytes.split_first()) {
+            out.push(i16::from_le_bytes([low, high]));
+            self.leftover = None;
+            bytes = rest;
+        }
+        let mut pairs = bytes.chunks_exact(2);
+        out.extend(pairs.by_ref().map(|b| i16::from_le_bytes([b[0], b[1]])));
+        if let [last] = pairs.remainder() {
+            self.leftover = Some(*last);
+        }
+    }
+}
+
+/// Replaces the content of `le` with big-endian 16-bit PCM `be` in little-endian order,
+/// as taken by `Recognizer::accept_reader`.
+///
+/// Network protocols such as AES67 and some RTP payloads carry big-endian audio.
+pub fn convert_be_to_le(be: &[u8], le: &mut Vec<u8>) -> Result<(), Error> {
+    check_even(be)?;
+    le.clear();
+    le.extend(be.chunks_exact(2).flat_map(|b| [b[1], b[0]]));
+    Ok(())
+}
+
+/// Replaces the content of `out` with the samples of big-endian 16-bit PCM.
+pub(crate) fn decode_be(bytes: &[u8], out: &mut Vec<i16>) -> Result<(), Error> {
+    check_even(bytes)?;
+    out.clear();
+    out.extend(
+        bytes
+            .chunks_exact(2)
+            .map(|b| i16::from_be_bytes([b[0], b[1]])),
+    );
+    Ok(())
+}
+
+fn check_even(bytes: &[u8]) -> Result<(), Error> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err(Error::OddLength(bytes.len()));
+    }
+    Ok(())
+}
+
+/// Averages the channels of interleaved samples into mono, appending to `out`.
+#[cfg_attr(
+    not(any(feature = "audio-decode", feature = "opus", feature = "http-source")),
+    allow(dead_code)
+)]
+pub(crate) fn downmix(interleaved: &[i16], channels: usize, out: &mut Vec<i16>) {
+    if channels <= 1 {
+        out.extend_from_slice(interleaved);
+        return;
+    }
+    out.extend(interleaved.chunks_exact(channels).map(|frame| {
+        let sum: i32 = frame.iter().map(|&s| s as i32).sum();
+        (sum / channels as i32) as i16
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{convert_be_to_le, decode_be, downmix, LeDecoder};
+    use crate::Error;
+
+    #[test]
+    fn whole_samples() {
+        let mut d = LeDecoder::default();
+        let mut out = Vec::new();
+        d.decode(&[0x01, 0x00, 0xff, 0xff], &mut out);
+        assert_eq!(out, vec![1, -1]);
+        assert_eq!(d.leftover, None);
+    }
+    #[test]
+    fn split_sample() {
+        let mut d = LeDecoder::default();
+        let mut out = Vec::new();
+        d.decode(&[0x01, 0x00, 0x34], &mut out);
+        assert_eq!(d.leftover, Some(0x34));
+        d.decode(&[0x12], &mut out);
+        d.decode(&[], &mut out);
+        d.decode(&[0x00, 0x80], &mut out);
+        assert_eq!(out, vec![1, 0x1234, i16::MIN]);
+        assert_eq!(d.leftover, None);
+    }
+    #[test]
+    fn downmix_stereo() {
+        let mut out = Vec::new();
+        downmix(&[100, 200, -4, 4, i16::MAX, i16::MAX], 2, &mut out);
+        assert_eq!(out, vec![150, 0, i16::MAX]);
+        downmix(&[1, 2, 3], 1, &mut out);
+        assert_eq!(out, vec![150, 0, i16::MAX, 1, 2, 3]);
+    }
+    #[test]
+    fn swap_bytes() {
+        let mut le = vec![0xaa];
+        convert_be_to_le(&[0x01, 0x02, 0xff, 0xfe], &mut le).unwrap();
+        assert_eq!(le, vec![0x02, 0x01, 0xfe, 0xff]);
+    }
+    #[test]
+    fn same_samples_either_order() {
+        let samples = [0i16, 1, -1, 0x1234, i16::MIN, i16::MAX];
+        let be: Vec<u8> = samples.iter().flat_map(|s| s.to_be_bytes()).collect();
+        let le: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        let mut from_be = Vec::new();
+        decode_be(&be, &mut from_be).unwrap();
+        let mut from_le = Vec::new();
+        LeDecoder::default().decode(&le, &mut from_le);
+        assert_eq!(from_be, samples);
+        assert_eq!(from_le, samples);
+        let mut converted = Vec::new();
+        convert_be_to_le(&be, &mut converted).unwrap();
+        assert_eq!(converted, le);
+    }
+    #[test]
+    fn odd_length() {
+        let mut out = Vec::new();
+        assert_eq!(decode_be(&[1, 2, 3], &mut out), Err(Error::OddLength(3)));
+        assert_eq!(
+            convert_be_to_le(&[1], &mut Vec::new()),
+            Err(Error::OddLength(1))
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! Rewriting finalized utterances, such as restoring punctuation, masking profanity
+//! or formatting dictation.
+//!
+//! Post-processors change how words are written, keeping one entry per word with
+//! its times and confidence, so captions made from the result stay in sync.
+//! The text is rebuilt from the words when they have details.
+//!
+//! ```
+//! # use vosk::postprocess::{PostProcessorChain, TextPostProcessor};
+//! # use vosk::{redact::redact_digits, RecognizedText};
+//! let chain = PostProcessorChain::new()
+//!     .then(|u: &mut vosk::UtteranceOwned| redact_digits(u, 4, "#"))
+//!     .then(|u: &mut vosk::UtteranceOwned| u.text = u.text.to_uppercase().into());
+//! let mut utterance = RecognizedText::from_text("pin one two three four");
+//! chain.process(&mut utterance);
+//! assert_eq!(utterance.text, "PIN # # # #");
+//! ```
+
+use crate::text::join_words;
+use crate::UtteranceOwned;
+use std::borrow::Cow;
+use std::fmt;
+
+mod denylist;
+mod dictation;
+#[cfg(feature = "punctuation")]
+mod punctuation;
+
+pub use self::denylist::{DenylistFilter, Mask};
+pub use self::dictation::{DictationCommands, DictationFormatter, Format};
+#[cfg(feature = "punctuation")]
+pub use self::punctuation::{
+    Casing, OnnxPunctuation, PunctuationModel, PunctuationRestorer, WordMarks,
+};
+
+/// Rewrites finalized utterances in place.
+pub trait TextPostProcessor {
+    fn process(&self, utterance: &mut UtteranceOwned);
+}
+
+impl<F: Fn(&mut UtteranceOwned)> TextPostProcessor for F {
+    fn process(&self, utterance: &mut UtteranceOwned) {
+        self(utterance)
+    }
+}
+
+/// Post-processors applied one after the other, in the order they were added.
+#[derive(Default)]
+pub struct PostProcessorChain {
+    stages: Vec<Box<dyn TextPostProcessor + Send + Sync>>,
+}
+
+impl PostProcessorChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn then<P>(mut self, stage: P) -> Self
+    where
+        P: TextPostProcessor + Send + Sync + 'static,
+    {
+        self.stages.push(Box::new(stage));
+        self
+    }
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+}
+
+impl TextPostProcessor for PostProcessorChain {
+    fn process(&self, utterance: &mut UtteranceOwned) {
+        for stage in &self.stages {
+            stage.process(utterance);
+        }
+    }
+}
+
+impl fmt::Debug for PostProcessorChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PostProcessorChain")
+            .field("stages", &self.stages.len())
+            .finish()
+    }
+}
+
+/// Replaces the words of `utterance` with what `rewrite` makes of them, one for one,
+/// and rebuilds the text. Without word details, the words of the text are rewritten.
+pub(crate) fn rewrite_words<F>(utterance: &mut UtteranceOwned, rewrite: F)
+where
+    F: FnOnce(&[&str]) -> Vec<String>,
+{
+    let words: Vec<&str> = match &utterance.result {
+        Some(words) => words.iter().map(|w| w.word()).collect(),
+        None => utterance.text.split_whitespace().collect(),
+    };
+    let rewritten = rewrite(&words);
+    debug_assert_eq!(rewritten.len(), words.len());
+    if rewritten.len() != words.len() || rewritten.iter().zip(&words).all(|(a, b)| a == b) {
+        return;
+    }
+    utterance.text = Cow::Owned(join_words(&rewritten).0);
+    if let Some(words) = &mut utterance.result {
+        for (word, rewritten) in words.iter_mut().zip(rewritten) {
+            word.word = Cow::Owned(rewritten);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::utterance;
+    use crate::RecognizedText;
+
+    #[test]
+    fn chain_in_order() {
+        let chain = PostProcessorChain::new()
+            .then(|u: &mut UtteranceOwned| u.text = format!("{} a", u.text).into())
+            .then(|u: &mut UtteranceOwned| u.text = format!("{} b", u.text).into());
+        assert_eq!(chain.len(), 2);
+        let mut u = RecognizedText::from_text("start");
+        chain.process(&mut u);
+        assert_eq!(u.text, "start a b");
+        let empty = PostProcessorChain::new();
+        assert!(empty.is_empty());
+        empty.process(&mut u);
+        assert_eq!(u.text, "start a b");
+    }
+    #[test]
+    fn rewritten_words_keep_times() {
+        let mut u = utterance(&[("hello", 0.0, 0.4), ("there", 0.5, 0.9)]);
+        rewrite_words(&mut u, |words| {
+            words.iter().map(|w| format!("{}!", w)).collect()
+        });
+        assert_eq!(u.text, "hello! there!");
+        let words: Vec<_> = u
+            .words()
+            .iter()
+            .map(|w| (w.word(), w.start(), w.end()))
+            .collect();
+        assert_eq!(words, [("hello!", 0.0, 0.4), ("there!", 0.5, 0.9)]);
+        let mut u = RecognizedText::from_text("no  details");
+        rewrite_words(&mut u, |words| {
+            words.iter().map(|w| w.to_uppercase()).collect()
+        });
+        assert_eq!(u.text, "NO DETAILS");
+    }
+}
@@ -0,0 +1,249 @@
+use super::{rewrite_words, TextPostProcessor};
+#[cfg(feature = "regex")]
+use crate::text::join_words;
+use crate::UtteranceOwned;
+use std::fmt;
+
+/// What a masked word is replaced with.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Mask {
+    /// "****", whatever the word.
+    #[default]
+    Stars,
+    /// The first letter, then an asterisk for each other letter: "d***".
+    FirstLetter,
+    /// Any placeholder, such as "[censored]".
+    Text(String),
+}
+
+impl Mask {
+    fn apply(&self, word: &str) -> String {
+        match self {
+            Mask::Stars => "****".to_string(),
+            Mask::FirstLetter => {
+                let mut chars = word.chars();
+                chars.next().into_iter().chain(chars.map(|_| '*')).collect()
+            }
+            Mask::Text(text) => text.clone(),
+        }
+    }
+}
+
+/// Masks words and phrases from a list, such as profanity in public captions.
+///
+/// Phrases match whole words regardless of case, so "Hell" is masked but not "hello",
+/// and every word of a matched phrase is masked on its own: the word list keeps its
+/// length, with the times and confidence of each word, so captions stay in time.
+///
+/// Punctuation around a word, as added by a `PunctuationRestorer` earlier in a chain,
+/// is ignored when matching and kept around the mask.
+///
+/// ```
+/// # use vosk::postprocess::{DenylistFilter, Mask, TextPostProcessor};
+/// # use vosk::RecognizedText;
+/// let filter = DenylistFilter::new(["darn", "what the heck"]).with_mask(Mask::FirstLetter);
+/// let mut utterance = RecognizedText::from_text("Darn, what the heck happened");
+/// filter.process(&mut utterance);
+/// assert_eq!(utterance.text, "D***, w*** t** h*** happened");
+/// ```
+#[derive(Clone, Default)]
+pub struct DenylistFilter {
+    /// Lower-cased words of each phrase.
+    phrases: Vec<Vec<String>>,
+    #[cfg(feature = "regex")]
+    patterns: Vec<regex::Regex>,
+    mask: Mask,
+}
+
+impl DenylistFilter {
+    /// Masks `phrases`, each one word or several separated by spaces.
+    pub fn new<I, S>(phrases: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let phrases = phrases
+            .into_iter()
+            .map(|phrase| {
+                phrase
+                    .as_ref()
+                    .split_whitespace()
+                    .map(str::to_lowercase)
+                    .collect::<Vec<_>>()
+            })
+            .filter(|words| !words.is_empty())
+            .collect();
+        DenylistFilter {
+            phrases,
+            ..Self::default()
+        }
+    }
+    pub fn with_mask(mut self, mask: Mask) -> Self {
+        self.mask = mask;
+        self
+    }
+    /// Also masks every word overlapping a match of `re` in the text, rebuilt from
+    /// the words with single spaces between them.
+    ///
+    /// Matching is as `re` says, build it with `(?i)` to ignore case.
+    /// Add `\b` around it to keep it from matching inside words.
+    #[cfg(feature = "regex")]
+    pub fn with_regex(mut self, re: regex::Regex) -> Self {
+        self.patterns.push(re);
+        self
+    }
+
+    /// Which of `words` are to be masked.
+    fn denied(&self, words: &[&str]) -> Vec<bool> {
+        let cores: Vec<String> = words.iter().map(|w| core(w).1.to_lowercase()).collect();
+        let mut denied = vec![false; words.len()];
+        for start in 0..cores.len() {
+            for phrase in &self.phrases {
+                let end = start + phrase.len();
+                if cores
+                    .get(start..end)
+                    .is_some_and(|words| words == &phrase[..])
+                {
+                    denied[start..end].fill(true);
+                }
+            }
+        }
+        #[cfg(feature = "regex")]
+        if !self.patterns.is_empty() {
+            let (text, spans) = join_words(words);
+            for m in self.patterns.iter().flat_map(|re| re.find_iter(&text)) {
+                for (denied, span) in denied.iter_mut().zip(&spans) {
+                    *denied |= m.start() < span.end && span.start < m.end();
+                }
+            }
+        }
+        denied
+    }
+}
+
+impl TextPostProcessor for DenylistFilter {
+    fn process(&self, utterance: &mut UtteranceOwned) {
+        rewrite_words(utterance, |words| {
+            let denied = self.denied(words);
+            words
+                .iter()
+                .zip(denied)
+                .map(|(word, denied)| {
+                    if !denied {
+                        return word.to_string();
+                    }
+                    let (before, core, after) = core(word);
+                    [before, &self.mask.apply(core), after].concat()
+                })
+                .collect()
+        });
+    }
+}
+
+impl fmt::Debug for DenylistFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("DenylistFilter");
+        debug.field("phrases", &self.phrases.len());
+        #[cfg(feature = "regex")]
+        debug.field("patterns", &self.patterns.len());
+        debug.field("mask", &self.mask).finish()
+    }
+}
+
+/// Splits `word` into punctuation before it, the word itself, and punctuation after.
+fn core(word: &str) -> (&str, &str, &str) {
+    let is_mark = |c: char| !c.is_alphanumeric();
+    let trimmed = word.trim_start_matches(is_mark);
+    let before = &word[..word.len() - trimmed.len()];
+    let core = trimmed.trim_end_matches(is_mark);
+    (before, core, &trimmed[core.len()..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postprocess::PostProcessorChain;
+    use crate::test_util::utterance;
+    use crate::RecognizedText;
+
+    fn filtered(filter: &DenylistFilter, text: &str) -> String {
+        let mut u = RecognizedText::from_text(text).into_owned();
+        filter.process(&mut u);
+        u.text.into_owned()
+    }
+
+    #[test]
+    fn whole_words_only() {
+        let filter = DenylistFilter::new(["ass", "hell", "cunt"]);
+        for text in [
+            "a class to assess the bass",
+            "hello shell hellish",
+            "scunthorpe united",
+            "ass-ociated",
+        ] {
+            assert_eq!(filtered(&filter, text), text);
+        }
+        assert_eq!(filtered(&filter, "what the hell"), "what the ****");
+        assert_eq!(filtered(&filter, "Hell, no."), "****, no.");
+        assert_eq!(filtered(&filter, "\"HELL!\""), "\"****!\"");
+    }
+    #[test]
+    fn phrases() {
+        let filter =
+            DenylistFilter::new(["  Go  To Hell ", "", "son of a"]).with_mask(Mask::FirstLetter);
+        assert_eq!(filtered(&filter, "go to hell"), "g* t* h***");
+        assert_eq!(filtered(&filter, "go to bed"), "go to bed");
+        // The whole phrase has to be there.
+        assert_eq!(filtered(&filter, "a son of"), "a son of");
+        assert_eq!(
+            filtered(&filter, "son of a son of a gun"),
+            "s** o* a s** o* a gun"
+        );
+        let none = DenylistFilter::new(Vec::<String>::new());
+        assert_eq!(filtered(&none, "anything goes"), "anything goes");
+    }
+    #[test]
+    fn masks() {
+        assert_eq!(Mask::Stars.apply("a"), "****");
+        assert_eq!(Mask::FirstLetter.apply("éclair"), "é*****");
+        assert_eq!(Mask::Text("[beep]".into()).apply("darn"), "[beep]");
+        assert_eq!(core("(darn!)"), ("(", "darn", "!)"));
+        assert_eq!(core("..."), ("...", "", ""));
+    }
+    #[test]
+    fn timings_kept() {
+        let filter = DenylistFilter::new(["darn it"]).with_mask(Mask::Text("[x]".into()));
+        let mut u = utterance(&[("oh", 0.0, 0.2), ("darn", 0.3, 0.6), ("it", 0.6, 0.8)]);
+        filter.process(&mut u);
+        assert_eq!(u.text, "oh [x] [x]");
+        let words: Vec<_> = u
+            .words()
+            .iter()
+            .map(|w| (w.word(), w.start(), w.end()))
+            .collect();
+        assert_eq!(
+            words,
+            [("oh", 0.0, 0.2), ("[x]", 0.3, 0.6), ("[x]", 0.6, 0.8)]
+        );
+    }
+    #[test]
+    fn in_a_chain() {
+        let chain = PostProcessorChain::new()
+            .then(|u: &mut UtteranceOwned| u.text = u.text.replace("heck", "Heck.").into())
+            .then(DenylistFilter::new(["heck"]).with_mask(Mask::FirstLetter))
+            .then(DenylistFilter::new(["flip"]));
+        let mut u = RecognizedText::from_text("flip heck");
+        chain.process(&mut u);
+        assert_eq!(u.text, "**** H***.");
+    }
+    #[cfg(feature = "regex")]
+    #[test]
+    fn regex() {
+        let re = regex::Regex::new(r"(?i)\bf+r+ick\w*|\bdang it\b").unwrap();
+        let filter = DenylistFilter::new(["darn"]).with_regex(re);
+        assert_eq!(
+            filtered(&filter, "Frrrick darn frickin dang it africk dang items"),
+            "**** **** **** **** **** africk dang items"
+        );
+    }
+}
@@ -0,0 +1,532 @@
+use super::TextPostProcessor;
+use crate::{RecognizedWord, UtteranceOwned};
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// What a spoken command is written as.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Format {
+    /// Attached to the word before, like a comma or a closing parenthesis.
+    Mark(String),
+    /// Attached to the word before, and the next word starts with a capital.
+    SentenceEnd(String),
+    /// Attached to the word after, like an opening parenthesis.
+    Opening(String),
+    /// Line breaks, such as "\n" or "\n\n". The next word starts on the new
+    /// line without a space, with a capital.
+    Break(String),
+}
+
+/// Spoken commands of a language and what they're written as, see `DictationFormatter`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DictationCommands {
+    /// Lower-cased words of each phrase, longest first.
+    commands: Vec<(Vec<String>, Format)>,
+    escape: Option<String>,
+}
+
+impl DictationCommands {
+    /// No commands, to build a table with `with`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// The commands of `language`, as an ISO 639-1 code such as "en",
+    /// or None for a language without a table.
+    pub fn for_language(language: &str) -> Option<Self> {
+        let table = match language.to_ascii_lowercase().as_str() {
+            "en" => ENGLISH,
+            "de" => GERMAN,
+            "fr" => FRENCH,
+            _ => return None,
+        };
+        Some(Self::from_table(table))
+    }
+    /// "period", "comma", "new line", "open paren" and so on; "literal" escapes them.
+    pub fn english() -> Self {
+        Self::from_table(ENGLISH)
+    }
+    /// "punkt", "komma", "neue zeile" and so on; "wörtlich" escapes them.
+    pub fn german() -> Self {
+        Self::from_table(GERMAN)
+    }
+    /// "point", "virgule", "à la ligne" and so on; "littéralement" escapes them.
+    pub fn french() -> Self {
+        Self::from_table(FRENCH)
+    }
+    fn from_table(table: Table) -> Self {
+        let commands = table
+            .commands
+            .iter()
+            .fold(Self::new(), |commands, (phrase, format)| {
+                commands.with(phrase, format.to_format())
+            });
+        commands.with_escape(table.escape)
+    }
+    /// Adds the command `phrase`, replacing what it was written as before.
+    pub fn with(mut self, phrase: &str, format: Format) -> Self {
+        let words: Vec<String> = phrase.split_whitespace().map(str::to_lowercase).collect();
+        if words.is_empty() {
+            return self;
+        }
+        self.commands.retain(|(known, _)| *known != words);
+        let at = self
+            .commands
+            .iter()
+            .position(|(known, _)| known.len() < words.len())
+            .unwrap_or(self.commands.len());
+        self.commands.insert(at, (words, format));
+        self
+    }
+    /// The word said before a command to write its words instead, as in
+    /// "literal period".
+    pub fn with_escape(mut self, word: &str) -> Self {
+        self.escape = Some(word.to_lowercase()).filter(|w| !w.is_empty());
+        self
+    }
+    pub fn without_escape(mut self) -> Self {
+        self.escape = None;
+        self
+    }
+    /// The longest command at the start of `words`, with its number of words.
+    fn find(&self, words: &[&str]) -> Option<(usize, &Format)> {
+        self.commands.iter().find_map(|(phrase, format)| {
+            let matches = words.len() >= phrase.len()
+                && phrase
+                    .iter()
+                    .zip(words)
+                    .all(|(command, word)| word.to_lowercase() == *command);
+            matches.then_some((phrase.len(), format))
+        })
+    }
+    fn is_escape(&self, word: &str) -> bool {
+        self.escape
+            .as_ref()
+            .is_some_and(|escape| word.to_lowercase() == *escape)
+    }
+}
+
+/// Turns spoken punctuation and formatting commands into symbols, for dictation.
+///
+/// Command words are consumed: "hello comma world period new paragraph thanks"
+/// becomes "Hello, world.\n\nThanks". The word after a sentence end or a line
+/// break starts with a capital. Saying the escape word first keeps the command
+/// as words, so "literal period" is written "period".
+///
+/// Each symbol takes the times of the word it's attached to, and the times of
+/// the command words are dropped. A symbol without a word to attach to, such as
+/// a "comma" said on its own, is a word of its own over the times of the command.
+///
+/// ```
+/// # use vosk::postprocess::{DictationFormatter, TextPostProcessor};
+/// # use vosk::RecognizedText;
+/// let formatter = DictationFormatter::english();
+/// let mut utterance = RecognizedText::from_text("dear sam comma new line how are you question mark");
+/// formatter.process(&mut utterance);
+/// assert_eq!(utterance.text, "Dear sam,\nHow are you?");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct DictationFormatter {
+    commands: DictationCommands,
+    capitalize_start: bool,
+}
+
+impl DictationFormatter {
+    pub fn new(commands: DictationCommands) -> Self {
+        DictationFormatter {
+            commands,
+            capitalize_start: true,
+        }
+    }
+    pub fn english() -> Self {
+        Self::new(DictationCommands::english())
+    }
+    /// Whether the first word of each utterance starts with a capital, as a new
+    /// sentence. On by default; turn it off when utterances often end mid-sentence.
+    pub fn with_capitalize_start(mut self, capitalize: bool) -> Self {
+        self.capitalize_start = capitalize;
+        self
+    }
+    pub fn commands(&self) -> &DictationCommands {
+        &self.commands
+    }
+
+    /// Splits `words` into the pieces of the formatted text.
+    fn format(&self, words: &[&str]) -> Vec<Piece> {
+        let mut state = State {
+            pieces: Vec::new(),
+            capitalize: self.capitalize_start,
+            opening: None,
+        };
+        let mut i = 0;
+        while i < words.len() {
+            if self.commands.is_escape(words[i]) {
+                if let Some((len, _)) = self.commands.find(&words[i + 1..]) {
+                    for (j, word) in words.iter().enumerate().skip(i + 1).take(len) {
+                        state.word(word, j);
+                    }
+                    i += 1 + len;
+                    continue;
+                }
+            }
+            let Some((len, format)) = self.commands.find(&words[i..]) else {
+                state.word(words[i], i);
+                i += 1;
+                continue;
+            };
+            let said = i..i + len;
+            match format {
+                Format::Mark(symbol) => state.attach(symbol, said),
+                Format::SentenceEnd(symbol) | Format::Break(symbol) => {
+                    state.attach(symbol, said);
+                    state.capitalize = true;
+                }
+                Format::Opening(symbol) => {
+                    let (opening, _) = state.opening.get_or_insert((String::new(), said));
+                    opening.push_str(symbol);
+                }
+            }
+            i += len;
+        }
+        state.flush_opening();
+        state.pieces
+    }
+}
+
+impl TextPostProcessor for DictationFormatter {
+    fn process(&self, utterance: &mut UtteranceOwned) {
+        let pieces = {
+            let words: Vec<&str> = match &utterance.result {
+                Some(words) => words.iter().map(|w| w.word()).collect(),
+                None => utterance.text.split_whitespace().collect(),
+            };
+            self.format(&words)
+        };
+        let text = join(&pieces);
+        if text == utterance.text {
+            return;
+        }
+        if let Some(words) = &utterance.result {
+            let formatted = pieces
+                .into_iter()
+                .map(|piece| {
+                    let said = &words[piece.said];
+                    let conf = said.iter().map(|w| w.conf()).fold(f32::INFINITY, f32::min);
+                    let start = said.first().map_or(0.0, |w| w.start());
+                    let end = said.last().map_or(0.0, |w| w.end());
+                    RecognizedWord::new(piece.text, conf, start, end)
+                })
+                .collect();
+            utterance.result = Some(formatted);
+        }
+        utterance.text = Cow::Owned(text);
+    }
+}
+
+/// A word of the formatted text.
+#[derive(Debug, Clone, PartialEq)]
+struct Piece {
+    text: String,
+    /// The words it was said as, for its times.
+    said: Range<usize>,
+}
+
+struct State {
+    pieces: Vec<Piece>,
+    /// Whether the next word starts with a capital.
+    capitalize: bool,
+    /// Openings waiting for the word after them, with the words they were said as.
+    opening: Option<(String, Range<usize>)>,
+}
+
+impl State {
+    fn word(&mut self, word: &str, at: usize) {
+        let mut text = self.opening.take().map(|(o, _)| o).unwrap_or_default();
+        if self.capitalize {
+            let mut chars = word.chars();
+            text.extend(chars.next().into_iter().flat_map(char::to_uppercase));
+            text.push_str(chars.as_str());
+        } else {
+            text.push_str(word);
+        }
+        self.capitalize = false;
+        self.pieces.push(Piece {
+            text,
+            said: at..at + 1,
+        });
+    }
+    fn attach(&mut self, symbol: &str, said: Range<usize>) {
+        self.flush_opening();
+        match self.pieces.last_mut() {
+            Some(last) => last.text.push_str(symbol),
+            None => self.pieces.push(Piece {
+                text: symbol.to_string(),
+                said,
+            }),
+        }
+    }
+    /// Openings with no word after them are pieces of their own.
+    fn flush_opening(&mut self) {
+        if let Some((text, said)) = self.opening.take() {
+            self.pieces.push(Piece { text, said });
+        }
+    }
+}
+
+/// The pieces with spaces between them, except after line breaks.
+fn join(pieces: &[Piece]) -> String {
+    let mut text = String::new();
+    for piece in pieces {
+        if !text.is_empty() && !text.ends_with('\n') {
+            text.push(' ');
+        }
+        text.push_str(&piece.text);
+    }
+    text
+}
+
+/// A built-in table of commands, in a form that can be a constant.
+struct Table {
+    commands: &'static [(&'static str, StaticFormat)],
+    escape: &'static str,
+}
+
+#[derive(Clone, Copy)]
+enum StaticFormat {
+    Mark(&'static str),
+    SentenceEnd(&'static str),
+    Opening(&'static str),
+    Break(&'static str),
+}
+
+impl StaticFormat {
+    fn to_format(self) -> Format {
+        match self {
+            StaticFormat::Mark(s) => Format::Mark(s.to_string()),
+            StaticFormat::SentenceEnd(s) => Format::SentenceEnd(s.to_string()),
+            StaticFormat::Opening(s) => Format::Opening(s.to_string()),
+            StaticFormat::Break(s) => Format::Break(s.to_string()),
+        }
+    }
+}
+
+use StaticFormat::{Break, Mark, Opening, SentenceEnd};
+
+const ENGLISH: Table = Table {
+    commands: &[
+        ("period", SentenceEnd(".")),
+        ("full stop", SentenceEnd(".")),
+        ("question mark", SentenceEnd("?")),
+        ("exclamation mark", SentenceEnd("!")),
+        ("exclamation point", SentenceEnd("!")),
+        ("comma", Mark(",")),
+        ("colon", Mark(":")),
+        ("semicolon", Mark(";")),
+        ("close paren", Mark(")")),
+        ("close parenthesis", Mark(")")),
+        ("close quote", Mark("\"")),
+        ("end quote", Mark("\"")),
+        ("open paren", Opening("(")),
+        ("open parenthesis", Opening("(")),
+        ("open quote", Opening("\"")),
+        ("new line", Break("\n")),
+        ("new paragraph", Break("\n\n")),
+    ],
+    escape: "literal",
+};
+
+const GERMAN: Table = Table {
+    commands: &[
+        ("punkt", SentenceEnd(".")),
+        ("fragezeichen", SentenceEnd("?")),
+        ("ausrufezeichen", SentenceEnd("!")),
+        ("komma", Mark(",")),
+        ("doppelpunkt", Mark(":")),
+        ("semikolon", Mark(";")),
+        ("klammer zu", Mark(")")),
+        ("anführungszeichen unten", Opening("„")),
+        ("anführungszeichen oben", Mark("“")),
+        ("klammer auf", Opening("(")),
+        ("neue zeile", Break("\n")),
+        ("neuer absatz", Break("\n\n")),
+    ],
+    escape: "wörtlich",
+};
+
+const FRENCH: Table = Table {
+    commands: &[
+        ("point", SentenceEnd(".")),
+        ("point d'interrogation", SentenceEnd(" ?")),
+        ("point d'exclamation", SentenceEnd(" !")),
+        ("virgule", Mark(",")),
+        ("deux points", Mark(" :")),
+        ("point virgule", Mark(" ;")),
+        ("fermer la parenthèse", Mark(")")),
+        ("ouvrir la parenthèse", Opening("(")),
+        ("à la ligne", Break("\n")),
+        ("nouveau paragraphe", Break("\n\n")),
+    ],
+    escape: "littéralement",
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::utterance;
+    use crate::RecognizedText;
+
+    fn formatted(formatter: &DictationFormatter, text: &str) -> String {
+        let mut u = RecognizedText::from_text(text).into_owned();
+        formatter.process(&mut u);
+        u.text.into_owned()
+    }
+
+    #[test]
+    fn english() {
+        let formatter = DictationFormatter::english();
+        for (said, written) in [
+            ("", ""),
+            ("hello world", "Hello world"),
+            ("hello world period", "Hello world."),
+            ("hello comma world", "Hello, world"),
+            ("yes period no period", "Yes. No."),
+            ("really question mark yes exclamation mark", "Really? Yes!"),
+            ("wow exclamation point", "Wow!"),
+            ("the end full stop", "The end."),
+            ("note colon buy milk", "Note: buy milk"),
+            ("one semicolon two", "One; two"),
+            // Consecutive commands compose.
+            ("hello period new paragraph next", "Hello.\n\nNext"),
+            ("end question mark new line", "End?\n"),
+            ("a comma comma b", "A,, b"),
+            ("first new line second", "First\nSecond"),
+            ("new line hello", "\nHello"),
+            ("new paragraph new paragraph hi", "\n\n\n\nHi"),
+            // Openings attach to the word after.
+            ("call me open paren maybe close paren", "Call me (maybe)"),
+            ("he said open quote hi close quote", "He said \"hi\""),
+            ("open quote open paren hi", "\"(Hi"),
+            ("the end open paren", "The end ("),
+            ("period open quote yes end quote", ". \"Yes\""),
+            // Commands said on their own.
+            ("period", "."),
+            ("comma", ","),
+            ("new line", "\n"),
+            ("open paren", "("),
+            // Escaped commands are written as words.
+            ("literal period", "Period"),
+            ("the word literal comma", "The word comma"),
+            ("a literal new line b", "A new line b"),
+            ("literal translation", "Literal translation"),
+            ("literal literal period", "Literal period"),
+            ("the end literal", "The end literal"),
+            // Commands are whole words, in any case.
+            ("periodic table", "Periodic table"),
+            ("commas are fine", "Commas are fine"),
+            ("new lines", "New lines"),
+            ("Hello PERIOD", "Hello."),
+            ("new Line x", "\nX"),
+            // Capitals after sentence ends only.
+            ("ok comma sure", "Ok, sure"),
+            ("ok period sure", "Ok. Sure"),
+            ("éclair period über", "Éclair. Über"),
+        ] {
+            assert_eq!(formatted(&formatter, said), written, "{:?}", said);
+        }
+    }
+    #[test]
+    fn without_capitals_at_start() {
+        let formatter = DictationFormatter::english().with_capitalize_start(false);
+        assert_eq!(formatted(&formatter, "and then period so"), "and then. So");
+        assert_eq!(formatted(&formatter, "new line so"), "\nSo");
+    }
+    #[test]
+    fn other_languages() {
+        let german = DictationFormatter::new(DictationCommands::for_language("DE").unwrap());
+        assert_eq!(
+            formatted(
+                &german,
+                "hallo komma welt punkt neuer absatz wie geht's fragezeichen"
+            ),
+            "Hallo, welt.\n\nWie geht's?"
+        );
+        assert_eq!(
+            formatted(
+                &german,
+                "er sagt anführungszeichen unten ja anführungszeichen oben"
+            ),
+            "Er sagt „ja“"
+        );
+        assert_eq!(formatted(&german, "wörtlich punkt"), "Punkt");
+        let french = DictationFormatter::new(DictationCommands::french());
+        assert_eq!(
+            formatted(&french, "bonjour virgule ça va point d'interrogation"),
+            "Bonjour, ça va ?"
+        );
+        assert_eq!(
+            formatted(&french, "oui point virgule non point"),
+            "Oui ; non."
+        );
+        assert_eq!(DictationCommands::for_language("xx"), None);
+    }
+    #[test]
+    fn custom_commands() {
+        let commands = DictationCommands::new()
+            .with("stop", Format::SentenceEnd(".".into()))
+            .with("smiley", Format::Mark(" :)".into()))
+            .with("  big   stop ", Format::SentenceEnd("!".into()))
+            .with("", Format::Mark("?".into()))
+            .with_escape("say");
+        let formatter = DictationFormatter::new(commands.clone());
+        assert_eq!(formatted(&formatter, "hi smiley stop"), "Hi :).");
+        assert_eq!(formatted(&formatter, "go big stop now"), "Go! Now");
+        assert_eq!(formatted(&formatter, "say big stop"), "Big stop");
+        assert_eq!(formatted(&formatter, "period"), "Period");
+        // Replacing a command.
+        let replaced = commands.with("STOP", Format::Mark(";".into()));
+        assert_eq!(
+            formatted(&DictationFormatter::new(replaced.clone()), "a stop b"),
+            "A; b"
+        );
+        let unescaped = DictationFormatter::new(replaced.without_escape());
+        assert_eq!(formatted(&unescaped, "say stop"), "Say;");
+    }
+    #[test]
+    fn timings() {
+        let formatter = DictationFormatter::english();
+        let mut u = utterance(&[
+            ("hello", 0.0, 0.4),
+            ("period", 0.5, 0.9),
+            ("new", 1.0, 1.1),
+            ("paragraph", 1.1, 1.5),
+            ("open", 1.6, 1.7),
+            ("quote", 1.7, 1.9),
+            ("bye", 2.0, 2.3),
+        ]);
+        formatter.process(&mut u);
+        assert_eq!(u.text, "Hello.\n\n\"Bye");
+        let words: Vec<_> = u
+            .words()
+            .iter()
+            .map(|w| (w.word(), w.start(), w.end()))
+            .collect();
+        assert_eq!(words, [("Hello.\n\n", 0.0, 0.4), ("\"Bye", 2.0, 2.3)]);
+
+        // On its own, a symbol spans the command.
+        let mut u = utterance(&[("question", 0.2, 0.5), ("mark", 0.5, 0.8)]);
+        u.result.as_mut().unwrap()[1] = RecognizedWord::new("mark", 0.5, 0.5, 0.8);
+        formatter.process(&mut u);
+        let word = &u.words()[0];
+        assert_eq!(
+            (word.word(), word.conf(), word.start(), word.end()),
+            ("?", 0.5, 0.2, 0.8)
+        );
+    }
+    #[test]
+    fn unchanged() {
+        let formatter = DictationFormatter::english().with_capitalize_start(false);
+        let mut u = utterance(&[("plain", 0.0, 0.3), ("words", 0.3, 0.6)]);
+        let before = u.clone();
+        formatter.process(&mut u);
+        assert_eq!(u, before);
+    }
+}
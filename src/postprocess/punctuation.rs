@@ -0,0 +1,537 @@
+use super::{rewrite_words, TextPostProcessor};
+use crate::{Error, UtteranceOwned};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use tract_onnx::prelude::*;
+
+/// Tokens per run of the model, the markers included. Longer utterances are split
+/// between words.
+const WINDOW: usize = 256;
+
+/// Longer words are unknown, as in BERT, so that a word always fits in a window.
+const MAX_WORD_CHARS: usize = 100;
+
+/// Labels of the punctuation output, in order, and what each adds after a word.
+const PUNCTUATION: [Option<char>; 5] = [None, Some(','), Some('.'), Some('?'), Some('!')];
+
+/// How a word is to be capitalized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Casing {
+    Lower,
+    Upper,
+    /// Only the first letter in upper case.
+    Capitalize,
+    /// Left as recognized, for mixed case such as "iPhone" that the model can't spell out.
+    Other,
+}
+
+impl Casing {
+    fn apply(self, word: &str) -> String {
+        match self {
+            Casing::Lower => word.to_lowercase(),
+            Casing::Upper => word.to_uppercase(),
+            Casing::Capitalize => {
+                let mut chars = word.chars();
+                match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars).collect(),
+                    None => String::new(),
+                }
+            }
+            Casing::Other => word.to_string(),
+        }
+    }
+}
+
+/// What a punctuation model predicts for one word.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WordMarks {
+    pub casing: Casing,
+    /// The mark that follows the word, if any.
+    pub punctuation: Option<char>,
+}
+
+impl WordMarks {
+    /// The word as it's to be written.
+    pub fn apply(&self, word: &str) -> String {
+        let mut written = self.casing.apply(word);
+        if let Some(mark) = self.punctuation {
+            if !written.ends_with(mark) {
+                written.push(mark);
+            }
+        }
+        written
+    }
+}
+
+/// Predicts casing and punctuation for a sequence of words.
+///
+/// Implemented by `OnnxPunctuation`, and by anything else that can, such as a stand-in
+/// in tests.
+pub trait PunctuationModel: Send + Sync {
+    /// Returns one `WordMarks` per word, in order.
+    fn predict(&self, words: &[&str]) -> Result<Vec<WordMarks>, Error>;
+}
+
+/// Restores punctuation and casing in finalized utterances, turning
+/// "how are you i'm fine" into "How are you? I'm fine."
+///
+/// Each word keeps its place in the word list along with its times and confidence,
+/// so "you" becomes "you?" rather than two words.
+///
+/// A restorer whose model couldn't be loaded leaves utterances as they are, so a
+/// missing model makes the output plainer without stopping recognition.
+pub struct PunctuationRestorer {
+    model: Option<Box<dyn PunctuationModel>>,
+    load_error: Option<Error>,
+}
+
+impl PunctuationRestorer {
+    /// Loads an `OnnxPunctuation` model from `dir`, doing nothing if that fails.
+    ///
+    /// The error is kept, see `load_error`, and logged as a warning with the
+    /// `tracing` feature.
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        match Self::try_new(dir) {
+            Ok(restorer) => restorer,
+            Err(e) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(error = %e, "punctuation model not loaded, leaving text as is");
+                PunctuationRestorer {
+                    model: None,
+                    load_error: Some(e),
+                }
+            }
+        }
+    }
+    /// Loads an `OnnxPunctuation` model from `dir`.
+    pub fn try_new<P: AsRef<Path>>(dir: P) -> Result<Self, Error> {
+        Ok(Self::with_model(OnnxPunctuation::load(dir)?))
+    }
+    pub fn with_model<M: PunctuationModel + 'static>(model: M) -> Self {
+        PunctuationRestorer {
+            model: Some(Box::new(model)),
+            load_error: None,
+        }
+    }
+    /// Whether there is a model, so that utterances get changed.
+    pub fn is_active(&self) -> bool {
+        self.model.is_some()
+    }
+    /// Why the model passed to `new` wasn't loaded.
+    pub fn load_error(&self) -> Option<&Error> {
+        self.load_error.as_ref()
+    }
+}
+
+impl TextPostProcessor for PunctuationRestorer {
+    /// Leaves the utterance as it is if the model fails on it.
+    fn process(&self, utterance: &mut UtteranceOwned) {
+        let model = match &self.model {
+            Some(model) => model,
+            None => return,
+        };
+        rewrite_words(utterance, |words| {
+            if words.is_empty() {
+                return Vec::new();
+            }
+            match model.predict(words) {
+                Ok(marks) if marks.len() == words.len() => words
+                    .iter()
+                    .zip(marks)
+                    .map(|(word, marks)| marks.apply(word))
+                    .collect(),
+                _result => {
+                    #[cfg(feature = "tracing")]
+                    if let Err(e) = _result {
+                        tracing::warn!(error = %e, "restoring punctuation failed");
+                    }
+                    words.iter().map(|w| w.to_string()).collect()
+                }
+            }
+        });
+    }
+}
+
+impl fmt::Debug for PunctuationRestorer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PunctuationRestorer")
+            .field("active", &self.is_active())
+            .field("load_error", &self.load_error)
+            .finish()
+    }
+}
+
+/// A recasepunc-style model exported to ONNX, run with tract.
+///
+/// The directory holds `model.onnx` and the `vocab.txt` of its BERT WordPiece
+/// tokenizer. The model takes token ids of shape `[1, n]` as `i64`, then optionally
+/// an attention mask and token type ids of the same shape, and returns two outputs
+/// of logits: punctuation of shape `[1, n, 5]`, labelled O, COMMA, PERIOD, QUESTION
+/// and EXCLAMATION, then casing of shape `[1, n, 4]`, labelled LOWER, UPPER,
+/// CAPITALIZE and OTHER.
+///
+/// The casing of a word is predicted from its first token, and the punctuation
+/// after it from its last.
+pub struct OnnxPunctuation {
+    plan: Arc<TypedRunnableModel>,
+    inputs: usize,
+    tokenizer: WordPiece,
+}
+
+impl OnnxPunctuation {
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Self, Error> {
+        let dir = dir.as_ref();
+        let vocab = fs::read_to_string(dir.join("vocab.txt"))?;
+        let tokenizer = WordPiece::new(&vocab)?;
+        let model = tract_onnx::onnx()
+            .model_for_path(dir.join("model.onnx"))
+            .map_err(inference)?;
+        let inputs = model.inputs.len();
+        if !(1..=3).contains(&inputs) || model.outputs.len() < 2 {
+            return Err(Error::Inference(format!(
+                "expected 1 to 3 inputs and 2 outputs, the model has {} and {}",
+                inputs,
+                model.outputs.len()
+            )));
+        }
+        let plan = model
+            .into_optimized()
+            .and_then(|model| model.into_runnable())
+            .map_err(inference)?;
+        Ok(OnnxPunctuation {
+            plan,
+            inputs,
+            tokenizer,
+        })
+    }
+
+    /// Runs the model on one window of words.
+    fn predict_window(&self, pieces: &[Vec<i64>]) -> Result<Vec<WordMarks>, Error> {
+        let mut ids = vec![self.tokenizer.cls];
+        for word in pieces {
+            ids.extend(word);
+        }
+        ids.push(self.tokenizer.sep);
+        let n = ids.len();
+        let mut inputs: TVec<TValue> = tvec![tensor(ids, n)?.into()];
+        if self.inputs > 1 {
+            inputs.push(tensor(vec![1; n], n)?.into());
+        }
+        if self.inputs > 2 {
+            inputs.push(tensor(vec![0; n], n)?.into());
+        }
+        let outputs = self.plan.run(inputs).map_err(inference)?;
+        let punctuation = outputs[0].to_plain_array_view::<f32>().map_err(inference)?;
+        let casing = outputs[1].to_plain_array_view::<f32>().map_err(inference)?;
+        if punctuation.shape() != [1, n, PUNCTUATION.len()] || casing.shape() != [1, n, 4] {
+            return Err(Error::Inference(format!(
+                "unexpected output shapes {:?} and {:?}",
+                punctuation.shape(),
+                casing.shape()
+            )));
+        }
+        let best = |row: &[f32]| {
+            (0..row.len())
+                .max_by(|&a, &b| row[a].total_cmp(&row[b]))
+                .unwrap_or(0)
+        };
+        let punctuation = punctuation
+            .into_shape_with_order((n, PUNCTUATION.len()))
+            .map_err(inference)?;
+        let casing = casing.into_shape_with_order((n, 4)).map_err(inference)?;
+        // Token 0 is [CLS].
+        let mut at = 1;
+        let marks = pieces
+            .iter()
+            .map(|word| {
+                let first = at;
+                at += word.len();
+                let last = at - 1;
+                let casing = match best(casing.row(first).as_slice().unwrap_or(&[])) {
+                    0 => Casing::Lower,
+                    1 => Casing::Upper,
+                    2 => Casing::Capitalize,
+                    _ => Casing::Other,
+                };
+                let punctuation =
+                    PUNCTUATION[best(punctuation.row(last).as_slice().unwrap_or(&[]))];
+                WordMarks {
+                    casing,
+                    punctuation,
+                }
+            })
+            .collect();
+        Ok(marks)
+    }
+}
+
+impl PunctuationModel for OnnxPunctuation {
+    fn predict(&self, words: &[&str]) -> Result<Vec<WordMarks>, Error> {
+        let pieces: Vec<Vec<i64>> = words.iter().map(|w| self.tokenizer.tokenize(w)).collect();
+        let mut marks = Vec::with_capacity(words.len());
+        for window in windows(&pieces, WINDOW - 2) {
+            marks.extend(self.predict_window(window)?);
+        }
+        Ok(marks)
+    }
+}
+
+impl fmt::Debug for OnnxPunctuation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OnnxPunctuation")
+            .field("inputs", &self.inputs)
+            .field("vocab", &self.tokenizer.vocab.len())
+            .finish()
+    }
+}
+
+fn inference<E: fmt::Display>(e: E) -> Error {
+    Error::Inference(e.to_string())
+}
+
+fn tensor(values: Vec<i64>, n: usize) -> Result<Tensor, Error> {
+    Tensor::from_shape(&[1, n], &values).map_err(inference)
+}
+
+/// Splits words, given as their tokens, into runs of at most `max` tokens,
+/// except for a single word with more.
+fn windows(pieces: &[Vec<i64>], max: usize) -> Vec<&[Vec<i64>]> {
+    let mut windows = Vec::new();
+    let (mut start, mut tokens) = (0, 0);
+    for (i, word) in pieces.iter().enumerate() {
+        if tokens > 0 && tokens + word.len() > max {
+            windows.push(&pieces[start..i]);
+            start = i;
+            tokens = 0;
+        }
+        tokens += word.len();
+    }
+    if start < pieces.len() {
+        windows.push(&pieces[start..]);
+    }
+    windows
+}
+
+/// The tokenizer of uncased BERT models: greedy longest match, with "##" marking
+/// tokens that continue a word.
+#[derive(Debug)]
+struct WordPiece {
+    vocab: HashMap<String, i64>,
+    cls: i64,
+    sep: i64,
+    unk: i64,
+}
+
+impl WordPiece {
+    /// Reads `vocab.txt`, one token per line, the line number being its id.
+    fn new(vocab: &str) -> Result<Self, Error> {
+        let vocab: HashMap<String, i64> = vocab
+            .lines()
+            .enumerate()
+            .map(|(id, token)| (token.trim_end().to_string(), id as i64))
+            .collect();
+        let id = |token: &str| {
+            vocab
+                .get(token)
+                .copied()
+                .ok_or_else(|| Error::Inference(format!("{} is not in vocab.txt", token)))
+        };
+        Ok(WordPiece {
+            cls: id("[CLS]")?,
+            sep: id("[SEP]")?,
+            unk: id("[UNK]")?,
+            vocab,
+        })
+    }
+
+    /// The tokens of one word, `[UNK]` alone if any part of it isn't in the vocabulary
+    /// or it's longer than BERT takes. Never empty, so that every word has a prediction.
+    fn tokenize(&self, word: &str) -> Vec<i64> {
+        let word = word.to_lowercase();
+        if word.chars().count() > MAX_WORD_CHARS {
+            return vec![self.unk];
+        }
+        let mut tokens = Vec::new();
+        let mut start = 0;
+        while start < word.len() {
+            let found = word[start..]
+                .char_indices()
+                .map(|(i, c)| start + i + c.len_utf8())
+                .rev()
+                .find_map(|end| {
+                    let piece = &word[start..end];
+                    let id = if start == 0 {
+                        self.vocab.get(piece)
+                    } else {
+                        self.vocab.get(&format!("##{}", piece))
+                    };
+                    id.map(|&id| (id, end))
+                });
+            match found {
+                Some((id, end)) => {
+                    tokens.push(id);
+                    start = end;
+                }
+                None => return vec![self.unk],
+            }
+        }
+        if tokens.is_empty() {
+            tokens.push(self.unk);
+        }
+        tokens
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postprocess::PostProcessorChain;
+    use crate::redact::redact_digits;
+    use crate::test_util::utterance;
+    use crate::RecognizedText;
+
+    /// Ends sentences at "you" and "fine", capitalizing the word after.
+    struct Mock;
+
+    impl PunctuationModel for Mock {
+        fn predict(&self, words: &[&str]) -> Result<Vec<WordMarks>, Error> {
+            let mut capitalize = true;
+            let marks = words
+                .iter()
+                .map(|&word| {
+                    let end = word == "you" || word == "fine";
+                    let casing = if word == "i'm" || capitalize {
+                        Casing::Capitalize
+                    } else {
+                        Casing::Lower
+                    };
+                    capitalize = end;
+                    WordMarks {
+                        casing,
+                        punctuation: match word {
+                            "you" => Some('?'),
+                            "fine" => Some('.'),
+                            _ => None,
+                        },
+                    }
+                })
+                .collect();
+            Ok(marks)
+        }
+    }
+
+    struct Failing;
+
+    impl PunctuationModel for Failing {
+        fn predict(&self, _: &[&str]) -> Result<Vec<WordMarks>, Error> {
+            Err(Error::Inference("out of memory".into()))
+        }
+    }
+
+    #[test]
+    fn words_keep_their_place() {
+        let restorer = PunctuationRestorer::with_model(Mock);
+        let mut u = utterance(&[
+            ("how", 0.0, 0.2),
+            ("are", 0.2, 0.4),
+            ("you", 0.4, 0.6),
+            ("i'm", 1.0, 1.2),
+            ("fine", 1.2, 1.5),
+        ]);
+        restorer.process(&mut u);
+        assert_eq!(u.text, "How are you? I'm fine.");
+        let words: Vec<_> = u
+            .words()
+            .iter()
+            .map(|w| (w.word(), w.start(), w.end()))
+            .collect();
+        assert_eq!(
+            words,
+            [
+                ("How", 0.0, 0.2),
+                ("are", 0.2, 0.4),
+                ("you?", 0.4, 0.6),
+                ("I'm", 1.0, 1.2),
+                ("fine.", 1.2, 1.5)
+            ]
+        );
+        let mut u = RecognizedText::from_text("how are you");
+        restorer.process(&mut u);
+        assert_eq!(u.text, "How are you?");
+        let mut u = RecognizedText::from_text("");
+        restorer.process(&mut u);
+        assert_eq!(u.text, "");
+    }
+    #[test]
+    fn failures_change_nothing() {
+        let dir = std::env::temp_dir().join("vosk-no-punctuation-model");
+        let restorer = PunctuationRestorer::new(&dir);
+        assert!(!restorer.is_active());
+        assert!(matches!(restorer.load_error(), Some(Error::Io(_))));
+        assert!(PunctuationRestorer::try_new(&dir).is_err());
+        let failing = PunctuationRestorer::with_model(Failing);
+        for restorer in [restorer, failing] {
+            let mut u = utterance(&[("how", 0.0, 0.2), ("are", 0.2, 0.4)]);
+            let before = u.clone();
+            restorer.process(&mut u);
+            assert_eq!(u, before);
+        }
+    }
+    #[test]
+    fn in_a_chain() {
+        let chain = PostProcessorChain::new()
+            .then(|u: &mut UtteranceOwned| redact_digits(u, 2, "#"))
+            .then(PunctuationRestorer::with_model(Mock));
+        let mut u = RecognizedText::from_text("pin one two are you");
+        chain.process(&mut u);
+        assert_eq!(u.text, "Pin # # are you?");
+    }
+    #[test]
+    fn marks() {
+        let marks = |casing, punctuation| WordMarks {
+            casing,
+            punctuation,
+        };
+        assert_eq!(marks(Casing::Upper, None).apply("nasa"), "NASA");
+        assert_eq!(
+            marks(Casing::Capitalize, Some('.')).apply("école"),
+            "École."
+        );
+        assert_eq!(marks(Casing::Other, Some('?')).apply("iPhone?"), "iPhone?");
+        assert_eq!(marks(Casing::Lower, Some(',')).apply("Well"), "well,");
+    }
+    #[test]
+    fn wordpiece() {
+        let vocab = "[PAD]\n[UNK]\n[CLS]\n[SEP]\nplay\n##ing\n##s\nthe\n##e\n";
+        let tokenizer = WordPiece::new(vocab).unwrap();
+        assert_eq!((tokenizer.cls, tokenizer.sep, tokenizer.unk), (2, 3, 1));
+        assert_eq!(tokenizer.tokenize("playing"), [4, 5]);
+        assert_eq!(tokenizer.tokenize("Plays"), [4, 6]);
+        assert_eq!(tokenizer.tokenize("thee"), [7, 8]);
+        // Any unknown part makes the whole word unknown.
+        assert_eq!(tokenizer.tokenize("played"), [1]);
+        assert_eq!(tokenizer.tokenize("ça"), [1]);
+        assert_eq!(tokenizer.tokenize(""), [1]);
+        assert_eq!(
+            tokenizer.tokenize(&format!("the{}", "e".repeat(96))).len(),
+            97
+        );
+        assert_eq!(tokenizer.tokenize(&format!("the{}", "e".repeat(98))), [1]);
+        assert!(matches!(
+            WordPiece::new("[CLS]\n[SEP]\n"),
+            Err(Error::Inference(_))
+        ));
+    }
+    #[test]
+    fn split_between_words() {
+        let pieces = vec![vec![1, 2], vec![3], vec![4, 5, 6], vec![7; 5], vec![8]];
+        let lengths =
+            |max| -> Vec<usize> { windows(&pieces, max).iter().map(|w| w.len()).collect() };
+        assert_eq!(lengths(3), [2, 1, 1, 1]);
+        assert_eq!(lengths(6), [3, 2]);
+        assert_eq!(lengths(100), [5]);
+        assert!(windows(&[], 3).is_empty());
+    }
+}
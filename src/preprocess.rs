@@ -0,0 +1,431 @@
+//! Audio processing in front of a recognizer.
+//!
+//! Preprocessors modify samples in place and keep their state from one chunk to the next,
+//! so audio can be passed through in chunks of any size.
+//! Samples are in the 16-bit range for both `i16` and `f32`,
+//! as `Recognizer::accept_waveform_f32` expects.
+
+use crate::Recognizer;
+use std::time::Duration;
+
+/// Modifies audio before it's recognized.
+pub trait Preprocessor {
+    fn process(&mut self, samples: &mut [i16], sample_rate: f32);
+    fn process_f32(&mut self, samples: &mut [f32], sample_rate: f32);
+    /// Forgets the state carried over from previous chunks, such as at the start of a new stream.
+    fn reset(&mut self);
+}
+
+/// A recognizer with a preprocessor in front of it.
+pub struct Preprocessed<P> {
+    recognizer: Recognizer,
+    preprocessor: P,
+    samples: Vec<i16>,
+    samples_f32: Vec<f32>,
+}
+
+impl<P: Preprocessor> Preprocessed<P> {
+    pub fn new(recognizer: Recognizer, preprocessor: P) -> Preprocessed<P> {
+        Preprocessed {
+            recognizer,
+            preprocessor,
+            samples: Vec::new(),
+            samples_f32: Vec::new(),
+        }
+    }
+    /// Processes a copy of the samples and feeds it to the recognizer,
+    /// returning true when an utterance was completed, like `Recognizer::accept_waveform`.
+    pub fn accept_waveform(&mut self, wave: &[i16]) -> bool {
+        self.samples.clear();
+        self.samples.extend_from_slice(wave);
+        self.preprocessor
+            .process(&mut self.samples, self.recognizer.sample_rate());
+        self.recognizer.accept_waveform(&self.samples)
+    }
+    /// Same as `accept_waveform`, for f32 samples.
+    pub fn accept_waveform_f32(&mut self, wave: &[f32]) -> bool {
+        self.samples_f32.clear();
+        self.samples_f32.extend_from_slice(wave);
+        self.preprocessor
+            .process_f32(&mut self.samples_f32, self.recognizer.sample_rate());
+        self.recognizer.accept_waveform_f32(&self.samples_f32)
+    }
+    /// Resets both the recognizer and the preprocessor.
+    pub fn reset(&mut self) {
+        self.recognizer.reset();
+        self.preprocessor.reset();
+    }
+    pub fn preprocessor(&self) -> &P {
+        &self.preprocessor
+    }
+    pub fn preprocessor_mut(&mut self) -> &mut P {
+        &mut self.preprocessor
+    }
+    pub fn recognizer(&self) -> &Recognizer {
+        &self.recognizer
+    }
+    pub fn recognizer_mut(&mut self) -> &mut Recognizer {
+        &mut self.recognizer
+    }
+    pub fn into_inner(self) -> (Recognizer, P) {
+        (self.recognizer, self.preprocessor)
+    }
+}
+
+/// Settings of `Agc`.
+#[derive(Debug, Clone)]
+pub struct AgcOptions {
+    /// The RMS level to bring speech to, in the 16-bit range.
+    pub target_rms: f32,
+    /// The gain is never raised above this, so that background noise isn't blown up.
+    pub max_gain: f32,
+    /// How quickly the gain drops when the input gets louder.
+    pub attack: Duration,
+    /// How quickly the gain rises when the input gets quieter.
+    pub release: Duration,
+}
+
+impl Default for AgcOptions {
+    fn default() -> Self {
+        AgcOptions {
+            // About -20 dBFS.
+            target_rms: 3000.0,
+            max_gain: 20.0,
+            attack: Duration::from_millis(50),
+            release: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Automatic gain control, for quiet microphones.
+///
+/// The gain follows the level of each chunk, smoothed by the attack and release times,
+/// and is lowered as much as needed to keep peaks from clipping.
+#[derive(Debug, Clone)]
+pub struct Agc {
+    opts: AgcOptions,
+    gain: f32,
+}
+
+/// Chunks quieter than this are taken as digital silence, which leaves the gain as it is.
+const SILENCE_RMS: f32 = 1.0;
+
+const MAX_SAMPLE: f32 = i16::MAX as f32;
+
+impl Agc {
+    /// AGC with the default options.
+    pub fn new() -> Agc {
+        Agc::with_options(AgcOptions::default())
+    }
+    pub fn with_options(opts: AgcOptions) -> Agc {
+        Agc { opts, gain: 1.0 }
+    }
+    /// The gain applied at the end of the last chunk, for displaying a level meter or debugging.
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+    /// Computes the gain of a chunk, returning the gains to ramp between over it.
+    fn update<I>(&mut self, samples: I, len: usize, sample_rate: f32) -> (f32, f32)
+    where
+        I: Iterator<Item = f32>,
+    {
+        let (sum, peak) = samples.fold((0.0f64, 0.0f32), |(sum, peak), s| {
+            (sum + s as f64 * s as f64, peak.max(s.abs()))
+        });
+        let rms = (sum / len.max(1) as f64).sqrt() as f32;
+        if rms < SILENCE_RMS {
+            return (self.gain, self.gain);
+        }
+        let wanted = (self.opts.target_rms / rms).min(self.opts.max_gain);
+        let time = if wanted < self.gain {
+            self.opts.attack
+        } else {
+            self.opts.release
+        };
+        let chunk_secs = len as f32 / sample_rate;
+        let smoothing = 1.0 - (-chunk_secs / time.as_secs_f32().max(f32::EPSILON)).exp();
+        // Whatever the smoothing, the loudest sample must fit.
+        let limit = MAX_SAMPLE / peak;
+        let start = self.gain.min(limit);
+        self.gain = (self.gain + (wanted - self.gain) * smoothing).min(limit);
+        (start, self.gain)
+    }
+}
+
+impl Default for Agc {
+    fn default() -> Self {
+        Agc::new()
+    }
+}
+
+impl Preprocessor for Agc {
+    fn process(&mut self, samples: &mut [i16], sample_rate: f32) {
+        let len = samples.len();
+        let (start, end) = self.update(samples.iter().map(|&s| s as f32), len, sample_rate);
+        for (i, s) in samples.iter_mut().enumerate() {
+            let scaled = *s as f32 * ramp(start, end, i, len);
+            *s = scaled.round().clamp(i16::MIN as f32, MAX_SAMPLE) as i16;
+        }
+    }
+    fn process_f32(&mut self, samples: &mut [f32], sample_rate: f32) {
+        let len = samples.len();
+        let (start, end) = self.update(samples.iter().copied(), len, sample_rate);
+        for (i, s) in samples.iter_mut().enumerate() {
+            *s = (*s * ramp(start, end, i, len)).clamp(i16::MIN as f32, MAX_SAMPLE);
+        }
+    }
+    fn reset(&mut self) {
+        self.gain = 1.0;
+    }
+}
+
+/// The gain for sample `i` of `len`, moving linearly from `start` to `end`
+/// so that gain changes don't cause clicks.
+fn ramp(start: f32, end: f32, i: usize, len: usize) -> f32 {
+    start + (end - start) * (i + 1) as f32 / len as f32
+}
+
+/// One-pole high-pass filter, removing DC offset and low-frequency rumble.
+#[derive(Debug, Clone)]
+pub struct HighPass {
+    alpha: f32,
+    last_input: f32,
+    last_output: f32,
+}
+
+impl HighPass {
+    /// Attenuates frequencies below `cutoff_hz`, by 3 dB at the cutoff.
+    /// Around 80 Hz removes rumble while leaving speech alone.
+    pub fn new(cutoff_hz: f32, sample_rate: f32) -> HighPass {
+        let rc = 1.0 / (2.0 * std::f32::consts::PI * cutoff_hz);
+        let dt = 1.0 / sample_rate;
+        HighPass {
+            alpha: rc / (rc + dt),
+            last_input: 0.0,
+            last_output: 0.0,
+        }
+    }
+    fn filter(&mut self, x: f32) -> f32 {
+        let y = self.alpha * (self.last_output + x - self.last_input);
+        self.last_input = x;
+        self.last_output = y;
+        y
+    }
+}
+
+/// The filter was set up for a sample rate when created,
+/// the rate passed to it is ignored.
+impl Preprocessor for HighPass {
+    fn process(&mut self, samples: &mut [i16], _: f32) {
+        for s in samples {
+            let y = self.filter(*s as f32);
+            *s = y.round().clamp(i16::MIN as f32, MAX_SAMPLE) as i16;
+        }
+    }
+    fn process_f32(&mut self, samples: &mut [f32], _: f32) {
+        for s in samples {
+            *s = self.filter(*s);
+        }
+    }
+    fn reset(&mut self) {
+        self.last_input = 0.0;
+        self.last_output = 0.0;
+    }
+}
+
+/// Two preprocessors, one after the other. Usually built with [`chain!`](crate::chain).
+#[derive(Debug, Clone)]
+pub struct Chain<A, B> {
+    pub first: A,
+    pub second: B,
+}
+
+impl<A: Preprocessor, B: Preprocessor> Preprocessor for Chain<A, B> {
+    fn process(&mut self, samples: &mut [i16], sample_rate: f32) {
+        self.first.process(samples, sample_rate);
+        self.second.process(samples, sample_rate);
+    }
+    fn process_f32(&mut self, samples: &mut [f32], sample_rate: f32) {
+        self.first.process_f32(samples, sample_rate);
+        self.second.process_f32(samples, sample_rate);
+    }
+    fn reset(&mut self) {
+        self.first.reset();
+        self.second.reset();
+    }
+}
+
+/// Composes preprocessors, which process audio in the order given.
+///
+/// ```
+/// use vosk::chain;
+/// use vosk::preprocess::{Agc, HighPass};
+///
+/// let preprocessor = chain!(HighPass::new(80.0, 16000.0), Agc::new());
+/// ```
+#[macro_export]
+macro_rules! chain {
+    ($only:expr $(,)?) => {
+        $only
+    };
+    ($first:expr, $($rest:expr),+ $(,)?) => {
+        $crate::preprocess::Chain {
+            first: $first,
+            second: $crate::chain!($($rest),+),
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: f32 = 16000.0;
+
+    /// A 440 Hz sine of `amplitude`, in chunks of 100 ms.
+    fn sine(amplitude: f32, chunks: usize) -> Vec<Vec<f32>> {
+        (0..chunks)
+            .map(|c| {
+                (0..1600)
+                    .map(|i| {
+                        let t = (c * 1600 + i) as f32 / RATE;
+                        amplitude * (2.0 * std::f32::consts::PI * 440.0 * t).sin()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn quiet_sine_converges() {
+        let mut agc = Agc::new();
+        let mut last = Vec::new();
+        for chunk in sine(300.0, 30) {
+            let mut samples: Vec<i16> = chunk.iter().map(|s| s.round() as i16).collect();
+            agc.process(&mut samples, RATE);
+            assert!(samples.iter().all(|&s| s > i16::MIN && s < i16::MAX));
+            last = samples.iter().map(|&s| s as f32).collect();
+        }
+        assert!((rms(&last) - 3000.0).abs() < 30.0, "rms {}", rms(&last));
+        assert!((agc.gain() - 3000.0 / rms(&sine(300.0, 1)[0])).abs() < 0.1);
+    }
+    #[test]
+    fn quiet_sine_converges_f32() {
+        let mut agc = Agc::new();
+        let mut last = Vec::new();
+        for mut chunk in sine(300.0, 30) {
+            agc.process_f32(&mut chunk, RATE);
+            assert!(chunk.iter().all(|s| s.abs() < MAX_SAMPLE));
+            last = chunk;
+        }
+        assert!((rms(&last) - 3000.0).abs() < 30.0, "rms {}", rms(&last));
+    }
+    #[test]
+    fn gain_limits() {
+        let opts = AgcOptions {
+            max_gain: 4.0,
+            ..AgcOptions::default()
+        };
+        let mut agc = Agc::with_options(opts);
+        for mut chunk in sine(10.0, 30) {
+            agc.process_f32(&mut chunk, RATE);
+        }
+        assert!((agc.gain() - 4.0).abs() < 0.01);
+        // A sudden loud chunk is scaled down without clipping.
+        let mut agc = Agc::new();
+        for mut chunk in sine(100.0, 30) {
+            agc.process_f32(&mut chunk, RATE);
+        }
+        let mut loud = sine(20000.0, 1).remove(0);
+        agc.process_f32(&mut loud, RATE);
+        assert!(loud.iter().all(|s| s.abs() <= MAX_SAMPLE));
+        assert!(agc.gain() < 1.7);
+    }
+    /// The filter computed in f64 from the textbook definition,
+    /// y[n] = a * (y[n-1] + x[n] - x[n-1]) with a = RC / (RC + 1 / rate).
+    fn reference_high_pass(input: &[f32], cutoff: f64, rate: f64) -> Vec<f32> {
+        let rc = 1.0 / (2.0 * std::f64::consts::PI * cutoff);
+        let a = rc / (rc + 1.0 / rate);
+        let (mut x1, mut y1) = (0.0, 0.0);
+        input
+            .iter()
+            .map(|&x| {
+                let y = a * (y1 + x as f64 - x1);
+                x1 = x as f64;
+                y1 = y;
+                y as f32
+            })
+            .collect()
+    }
+
+    #[test]
+    fn high_pass_coefficient() {
+        let filter = HighPass::new(100.0, 16000.0);
+        // RC = 1 / (200 pi) = 1.5915 ms, a = RC / (RC + 1 / 16000)
+        assert!((filter.alpha - 0.962_214).abs() < 1e-5, "{}", filter.alpha);
+    }
+    #[test]
+    fn high_pass_matches_reference() {
+        // A 440 Hz tone on a DC offset, filtered in uneven chunks.
+        let input: Vec<f32> = (0..8000)
+            .map(|i| 2000.0 + 1000.0 * (i as f32 * 440.0 / 16000.0 * std::f32::consts::TAU).sin())
+            .collect();
+        let expected = reference_high_pass(&input, 80.0, 16000.0);
+        let mut filter = HighPass::new(80.0, 16000.0);
+        let mut output = input.clone();
+        for chunk in output.chunks_mut(333) {
+            filter.process_f32(chunk, RATE);
+        }
+        for (out, exp) in output.iter().zip(&expected) {
+            assert!((out - exp).abs() < 0.05, "{} != {}", out, exp);
+        }
+        // The offset is gone and the tone passes.
+        let tail = &output[4000..];
+        let mean = tail.iter().sum::<f32>() / tail.len() as f32;
+        assert!(mean.abs() < 5.0, "mean {}", mean);
+        assert!((rms(tail) - 1000.0 / 2f32.sqrt()).abs() < 30.0);
+    }
+    #[test]
+    fn high_pass_removes_dc() {
+        let mut filter = HighPass::new(80.0, 16000.0);
+        let mut samples = [500i16; 16000];
+        filter.process(&mut samples, RATE);
+        // The first sample only loses a few percent.
+        assert_eq!(samples[0], 485);
+        assert_eq!(samples[15999], 0);
+        filter.reset();
+        let mut again = [500i16; 1];
+        filter.process(&mut again, RATE);
+        assert_eq!(again[0], 485);
+    }
+    #[test]
+    fn chained() {
+        let mut chained = crate::chain!(HighPass::new(80.0, 16000.0), Agc::new(),);
+        for chunk in sine(300.0, 30) {
+            let mut offset: Vec<f32> = chunk.iter().map(|s| s + 1000.0).collect();
+            chained.process_f32(&mut offset, RATE);
+        }
+        // Without the high-pass filter, the offset would keep the gain near 3.
+        let expected = 3000.0 / (300.0 / 2f32.sqrt());
+        assert!((chained.second.gain() - expected).abs() < 0.5);
+        let mut single = crate::chain!(Agc::new());
+        single.process(&mut [100; 160], RATE);
+    }
+    #[test]
+    fn silence_keeps_gain() {
+        let mut agc = Agc::new();
+        for mut chunk in sine(300.0, 10) {
+            agc.process_f32(&mut chunk, RATE);
+        }
+        let gain = agc.gain();
+        let mut silence = [0i16; 1600];
+        agc.process(&mut silence, RATE);
+        assert_eq!(agc.gain(), gain);
+        assert!(silence.iter().all(|&s| s == 0));
+        agc.reset();
+        assert_eq!(agc.gain(), 1.0);
+    }
+}
@@ -0,0 +1,838 @@
+//! Grammars for entering digits and spelling by voice, and parsers for their results.
+//!
+//! ```no_run
+//! # use vosk::presets::{digits_grammar, parse_digits, Lang};
+//! # use vosk::{Model, Recognizer};
+//! # let model = Model::new("model").unwrap();
+//! let grammar = digits_grammar(Lang::English).with_phrases(Lang::English.repeat_words());
+//! let mut recognizer = Recognizer::with_grammar(&model, 16000.0, &grammar);
+//! # let samples = [0; 1600];
+//! recognizer.accept_waveform(&samples);
+//! let digits = parse_digits(&recognizer.final_result().text);
+//! ```
+//!
+//! A grammar allows any sequence of its phrases, so a whole code can be said at once.
+//! `PhoneNumberCapture` puts this together for phone numbers said in groups.
+
+use crate::{Error, Grammar, Model, Recognizer, SampleRate};
+use std::convert::TryFrom;
+use std::ops::RangeInclusive;
+
+/// Language of the words in the grammars and parsers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Lang {
+    #[default]
+    English,
+}
+
+const ENGLISH_DIGITS: [&str; 10] = [
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+];
+
+const ENGLISH_TEENS: [&str; 10] = [
+    "ten",
+    "eleven",
+    "twelve",
+    "thirteen",
+    "fourteen",
+    "fifteen",
+    "sixteen",
+    "seventeen",
+    "eighteen",
+    "nineteen",
+];
+
+const ENGLISH_TENS: [&str; 8] = [
+    "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+];
+
+/// Words that multiply what comes before them, largest last.
+const ENGLISH_SCALES: [(&str, i64); 3] = [
+    ("hundred", 100),
+    ("thousand", 1_000),
+    ("million", 1_000_000),
+];
+
+/// Names of the letters, as English models write them, and other common spellings.
+const ENGLISH_LETTERS: [(char, &[&str]); 26] = [
+    ('a', &["a", "ay"]),
+    ('b', &["b", "bee", "be"]),
+    ('c', &["c", "see", "sea", "cee"]),
+    ('d', &["d", "dee"]),
+    ('e', &["e"]),
+    ('f', &["f", "ef", "eff"]),
+    ('g', &["g", "gee"]),
+    ('h', &["h", "aitch"]),
+    ('i', &["i", "eye"]),
+    ('j', &["j", "jay"]),
+    ('k', &["k", "kay"]),
+    ('l', &["l", "el", "ell"]),
+    ('m', &["m", "em"]),
+    ('n', &["n", "en"]),
+    ('o', &["o", "oh"]),
+    ('p', &["p", "pee"]),
+    ('q', &["q", "cue", "queue"]),
+    ('r', &["r", "ar", "are"]),
+    ('s', &["s", "ess"]),
+    ('t', &["t", "tee", "tea"]),
+    ('u', &["u", "you"]),
+    ('v', &["v", "vee"]),
+    ('w', &["w"]),
+    ('x', &["x", "ex"]),
+    ('y', &["y", "why", "wye"]),
+    ('z', &["z", "zee", "zed"]),
+];
+
+/// The ICAO spelling alphabet, spelled as in English models, with the official spellings.
+const NATO_ALPHABET: [(char, &[&str]); 26] = [
+    ('a', &["alpha", "alfa"]),
+    ('b', &["bravo"]),
+    ('c', &["charlie"]),
+    ('d', &["delta"]),
+    ('e', &["echo"]),
+    ('f', &["foxtrot"]),
+    ('g', &["golf"]),
+    ('h', &["hotel"]),
+    ('i', &["india"]),
+    ('j', &["juliet", "juliett"]),
+    ('k', &["kilo"]),
+    ('l', &["lima"]),
+    ('m', &["mike"]),
+    ('n', &["november"]),
+    ('o', &["oscar"]),
+    ('p', &["papa"]),
+    ('q', &["quebec"]),
+    ('r', &["romeo"]),
+    ('s', &["sierra"]),
+    ('t', &["tango"]),
+    ('u', &["uniform"]),
+    ('v', &["victor"]),
+    ('w', &["whiskey", "whisky"]),
+    ('x', &["x-ray", "xray"]),
+    ('y', &["yankee"]),
+    ('z', &["zulu"]),
+];
+
+impl Lang {
+    /// Words that repeat the next one, such as "double" in "double five".
+    pub fn repeat_words(self) -> &'static [&'static str] {
+        match self {
+            Lang::English => &["double", "triple"],
+        }
+    }
+    fn repeat_count(self, word: &str) -> Option<usize> {
+        match (self, word) {
+            (Lang::English, "double") => Some(2),
+            (Lang::English, "triple") => Some(3),
+            _ => None,
+        }
+    }
+    fn digit(self, word: &str) -> Option<char> {
+        match self {
+            Lang::English if word == "oh" => Some('0'),
+            Lang::English => {
+                let value = ENGLISH_DIGITS.iter().position(|&d| d == word)?;
+                char::from_digit(value as u32, 10)
+            }
+        }
+    }
+    fn letter(self, word: &str) -> Option<char> {
+        let letters = match self {
+            Lang::English => &ENGLISH_LETTERS,
+        };
+        letters
+            .iter()
+            .chain(&NATO_ALPHABET)
+            .find(|(_, names)| names.contains(&word))
+            .map(|&(letter, _)| letter)
+    }
+}
+
+/// The digits from zero to nine, and "oh" for zero in English.
+pub fn digits_grammar(lang: Lang) -> Grammar {
+    match lang {
+        Lang::English => Grammar::new(ENGLISH_DIGITS).with_phrases(["oh"]),
+    }
+}
+
+/// The words of whole numbers up to the millions, as `parse_number` reads them,
+/// including "minus" and the "and" of "one hundred and five".
+pub fn numbers_grammar(lang: Lang) -> Grammar {
+    match lang {
+        Lang::English => digits_grammar(lang)
+            .with_phrases(ENGLISH_TEENS)
+            .with_phrases(ENGLISH_TENS)
+            .with_phrases(ENGLISH_SCALES.iter().map(|&(word, _)| word))
+            .with_phrases(["and", "minus"]),
+    }
+}
+
+/// The ICAO (NATO) spelling alphabet, "alpha" to "zulu".
+pub fn nato_alphabet_grammar() -> Grammar {
+    Grammar::new(NATO_ALPHABET.iter().map(|(_, names)| names[0]))
+}
+
+/// The names of the letters, as the models of `lang` write them.
+pub fn spelling_grammar(lang: Lang) -> Grammar {
+    let letters = match lang {
+        Lang::English => &ENGLISH_LETTERS,
+    };
+    Grammar::new(letters.iter().map(|(_, names)| names[0]))
+}
+
+/// How recognized words are turned back into characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOptions {
+    pub lang: Lang,
+    /// Whether "double five" means "55". Add `Lang::repeat_words` to the grammar for this.
+    pub repeats: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            lang: Lang::English,
+            repeats: true,
+        }
+    }
+}
+
+/// The digits spoken in English, "four oh double two" giving "4022".
+/// Other words are skipped.
+pub fn parse_digits(text: &str) -> String {
+    parse_digits_with(text, &ParseOptions::default())
+}
+
+pub fn parse_digits_with(text: &str, opts: &ParseOptions) -> String {
+    parse(text, opts, |word, _| opts.lang.digit(word).map(|d| (d, 1)))
+}
+
+/// The letters spelled in English, by name or spelling alphabet, in upper case:
+/// "bravo o double b" giving "BOBB". Other words are skipped.
+///
+/// "double u" is read as "W" rather than "UU".
+pub fn parse_spelling(text: &str) -> String {
+    parse_spelling_with(text, &ParseOptions::default())
+}
+
+pub fn parse_spelling_with(text: &str, opts: &ParseOptions) -> String {
+    parse(text, opts, |word, next| {
+        // Models without "x-ray" recognize it as two words.
+        if word == "x" && next == Some("ray") {
+            return Some(('X', 2));
+        }
+        let u = matches!(next, Some("u" | "you"));
+        if opts.repeats && opts.lang == Lang::English && word == "double" && u {
+            return Some(('W', 2));
+        }
+        let letter = opts.lang.letter(word)?;
+        Some((letter.to_ascii_uppercase(), 1))
+    })
+}
+
+/// A whole number said in English, such as "twenty one", "nineteen hundred",
+/// "two thousand and five" or "minus four".
+///
+/// Digits said one by one, as in "four oh four", are read in a row, and a number
+/// already written in digits is taken as it is. None if the text is anything else,
+/// such as words that aren't numbers or numbers out of order like "one twenty".
+pub fn parse_number(text: &str) -> Option<i64> {
+    let words: Vec<String> = text.split_whitespace().map(str::to_lowercase).collect();
+    let mut words: Vec<&str> = words.iter().map(String::as_str).collect();
+    let sign = match words.first() {
+        Some(&"minus" | &"negative") => {
+            words.remove(0);
+            -1
+        }
+        _ => 1,
+    };
+    let value = match words[..] {
+        [] => return None,
+        [word] if word.bytes().all(|b| b.is_ascii_digit()) => word.parse().ok()?,
+        [_, _, ..] if words.iter().all(|w| Lang::English.digit(w).is_some()) => {
+            let digits: String = words
+                .iter()
+                .filter_map(|w| Lang::English.digit(w))
+                .collect();
+            digits.parse().ok()?
+        }
+        ["zero" | "oh"] => 0,
+        _ => parse_cardinal(&words)?,
+    };
+    Some(sign * value)
+}
+
+/// Reads number words such as "three hundred and twelve thousand four",
+/// rejecting those in an order nobody says them in.
+fn parse_cardinal(words: &[&str]) -> Option<i64> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Last {
+        Start,
+        Unit,
+        Teen,
+        Tens,
+        Hundred,
+        Scale,
+        And,
+    }
+    let position = |list: &[&str], word: &str| list.iter().position(|&w| w == word);
+    let mut total: i64 = 0;
+    // The part below the last scale word, such as 312 in "312 thousand".
+    let mut current: i64 = 0;
+    let mut last = Last::Start;
+    let mut smallest_scale = i64::MAX;
+    for &word in words {
+        let after_group = matches!(last, Last::Start | Last::Hundred | Last::Scale | Last::And);
+        if let Some(unit) = position(&ENGLISH_DIGITS[1..], word) {
+            if !after_group && last != Last::Tens {
+                return None;
+            }
+            current += unit as i64 + 1;
+            last = Last::Unit;
+        } else if let Some(teen) = position(&ENGLISH_TEENS, word) {
+            if !after_group {
+                return None;
+            }
+            current += teen as i64 + 10;
+            last = Last::Teen;
+        } else if let Some(tens) = position(&ENGLISH_TENS, word) {
+            if !after_group {
+                return None;
+            }
+            current += (tens as i64 + 2) * 10;
+            last = Last::Tens;
+        } else if word == "hundred" {
+            // "nineteen hundred" and "twenty one hundred" are fine, "one hundred hundred" isn't.
+            if !matches!(last, Last::Unit | Last::Teen | Last::Tens) || current >= 100 {
+                return None;
+            }
+            current *= 100;
+            last = Last::Hundred;
+        } else if let Some(&(_, scale)) = ENGLISH_SCALES[1..].iter().find(|(w, _)| *w == word) {
+            if current == 0 || scale >= smallest_scale || last == Last::And {
+                return None;
+            }
+            total += current * scale;
+            current = 0;
+            smallest_scale = scale;
+            last = Last::Scale;
+        } else if word == "and" {
+            if !matches!(last, Last::Hundred | Last::Scale) {
+                return None;
+            }
+            last = Last::And;
+        } else {
+            return None;
+        }
+    }
+    match last {
+        Last::Start | Last::And => None,
+        _ => Some(total + current),
+    }
+}
+
+/// Maps words to characters with `read`, which is given a word and the one after it,
+/// and returns the character and how many words it took.
+fn parse<F>(text: &str, opts: &ParseOptions, read: F) -> String
+where
+    F: Fn(&str, Option<&str>) -> Option<(char, usize)>,
+{
+    let words: Vec<String> = text.split_whitespace().map(str::to_lowercase).collect();
+    let word = |i: usize| words.get(i).map(String::as_str);
+    let mut parsed = String::new();
+    let mut i = 0;
+    while let Some(w) = word(i) {
+        if let Some((c, taken)) = read(w, word(i + 1)) {
+            parsed.push(c);
+            i += taken;
+            continue;
+        }
+        let count = opts.lang.repeat_count(w).filter(|_| opts.repeats);
+        match count.and_then(|n| Some((n, read(word(i + 1)?, word(i + 2))?))) {
+            Some((n, (c, taken))) => {
+                parsed.extend(std::iter::repeat_n(c, n));
+                i += 1 + taken;
+            }
+            // Skips other words, and repeat words not followed by something to repeat.
+            None => i += 1,
+        }
+    }
+    parsed
+}
+
+/// Words and limits of a `PhoneNumberCapture`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhoneNumberOptions {
+    /// Accepted numbers of digits.
+    pub len: RangeInclusive<usize>,
+    /// Phrases that erase the digits said last.
+    pub correction: Vec<String>,
+    /// Phrases that erase all digits.
+    pub restart: Vec<String>,
+    /// Phrases that end the number before the maximum length.
+    pub done: Vec<String>,
+    pub parse: ParseOptions,
+}
+
+impl Default for PhoneNumberOptions {
+    /// Numbers of 3 to 15 digits, the longest E.164 allows, with English commands.
+    fn default() -> Self {
+        PhoneNumberOptions {
+            len: 3..=15,
+            correction: vec!["correction".to_string()],
+            restart: vec!["start over".to_string()],
+            done: vec!["done".to_string()],
+            parse: ParseOptions::default(),
+        }
+    }
+}
+
+/// Takes down a phone number said in one go or in groups of digits,
+/// with spoken commands to correct it.
+///
+/// The number is complete when it reaches the maximum length,
+/// or when a done phrase is said once it has the minimum length.
+/// A group of digits that would make it longer than the maximum is dropped,
+/// as it was likely misheard.
+#[derive(Debug)]
+pub struct PhoneNumberCapture {
+    recognizer: Recognizer,
+    number: NumberState,
+}
+
+impl PhoneNumberCapture {
+    /// Expects between `len.start()` and `len.end()` digits, with the default commands.
+    pub fn new(model: &Model, sample_rate: f32, len: RangeInclusive<usize>) -> Result<Self, Error> {
+        let opts = PhoneNumberOptions {
+            len,
+            ..PhoneNumberOptions::default()
+        };
+        PhoneNumberCapture::with_options(model, sample_rate, opts)
+    }
+    /// Fails with `Error::OutOfVocabulary` if the model doesn't know some of the words.
+    pub fn with_options(
+        model: &Model,
+        sample_rate: f32,
+        opts: PhoneNumberOptions,
+    ) -> Result<Self, Error> {
+        let rate = SampleRate::try_from(sample_rate)?;
+        let lang = opts.parse.lang;
+        let mut grammar = digits_grammar(lang)
+            .with_phrases(&opts.correction)
+            .with_phrases(&opts.restart)
+            .with_phrases(&opts.done);
+        if opts.parse.repeats {
+            grammar = grammar.with_phrases(lang.repeat_words());
+        }
+        let words = grammar.phrases().iter().flat_map(|p| p.split(' '));
+        let missing = model.missing_words_exact(words);
+        if !missing.is_empty() {
+            return Err(Error::OutOfVocabulary(
+                missing.into_iter().map(String::from).collect(),
+            ));
+        }
+        let recognizer = Recognizer::try_with_grammar(model, rate.hz(), &grammar.with_unknown())?;
+        Ok(PhoneNumberCapture {
+            recognizer,
+            number: NumberState::new(opts),
+        })
+    }
+    /// Feeds audio, returning the number once it's complete.
+    pub fn feed(&mut self, wave: &[i16]) -> Option<String> {
+        if !self.recognizer.accept_waveform(wave) {
+            return None;
+        }
+        self.number.hear(&self.recognizer.result().text)
+    }
+    /// Takes the last utterance at the end of the audio, returning the number if complete.
+    pub fn finish(&mut self) -> Option<String> {
+        self.number.hear(&self.recognizer.final_result().text)
+    }
+    /// The digits taken down so far.
+    pub fn digits(&self) -> &str {
+        &self.number.digits
+    }
+    /// Starts over for another number.
+    pub fn reset(&mut self) {
+        self.recognizer.reset();
+        self.number.clear();
+    }
+    pub fn recognizer(&self) -> &Recognizer {
+        &self.recognizer
+    }
+    pub fn recognizer_mut(&mut self) -> &mut Recognizer {
+        &mut self.recognizer
+    }
+}
+
+/// The number being taken down, by groups of digits.
+#[derive(Debug, Clone)]
+struct NumberState {
+    opts: PhoneNumberOptions,
+    digits: String,
+    /// Length of each group of digits, in order.
+    groups: Vec<usize>,
+    complete: bool,
+}
+
+/// What a phrase of a `PhoneNumberOptions` asks for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Command {
+    Correction,
+    Restart,
+    Done,
+}
+
+impl NumberState {
+    fn new(opts: PhoneNumberOptions) -> NumberState {
+        NumberState {
+            opts,
+            digits: String::new(),
+            groups: Vec::new(),
+            complete: false,
+        }
+    }
+    fn clear(&mut self) {
+        self.digits.clear();
+        self.groups.clear();
+        self.complete = false;
+    }
+    /// Takes a recognized utterance, returning the number when it becomes complete.
+    ///
+    /// Digits between commands form a group. Nothing changes once the number is complete.
+    fn hear(&mut self, text: &str) -> Option<String> {
+        if self.complete {
+            return None;
+        }
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let mut group_start = 0;
+        let mut i = 0;
+        while i < words.len() {
+            let Some((command, len)) = self.command_at(&words[i..]) else {
+                i += 1;
+                continue;
+            };
+            self.add_group(&words[group_start..i]);
+            match command {
+                Command::Correction => {
+                    let last = self.groups.pop().unwrap_or(0);
+                    self.digits.truncate(self.digits.len() - last);
+                }
+                Command::Restart => {
+                    self.digits.clear();
+                    self.groups.clear();
+                }
+                Command::Done if self.opts.len.contains(&self.digits.len()) => {
+                    return self.completed();
+                }
+                // Too short to be done, wait for more digits.
+                Command::Done => {}
+            }
+            i += len;
+            group_start = i;
+        }
+        self.add_group(&words[group_start..]);
+        if self.digits.len() == *self.opts.len.end() {
+            return self.completed();
+        }
+        None
+    }
+    fn add_group(&mut self, words: &[&str]) {
+        let digits = parse_digits_with(&words.join(" "), &self.opts.parse);
+        if digits.is_empty() || self.digits.len() + digits.len() > *self.opts.len.end() {
+            return;
+        }
+        self.digits.push_str(&digits);
+        self.groups.push(digits.len());
+    }
+    fn completed(&mut self) -> Option<String> {
+        self.complete = true;
+        Some(self.digits.clone())
+    }
+    /// The command phrase `words` start with, and its number of words.
+    fn command_at(&self, words: &[&str]) -> Option<(Command, usize)> {
+        let commands = [
+            (Command::Correction, &self.opts.correction),
+            (Command::Restart, &self.opts.restart),
+            (Command::Done, &self.opts.done),
+        ];
+        commands.iter().find_map(|(command, phrases)| {
+            phrases.iter().find_map(|phrase| {
+                let phrase: Vec<&str> = phrase.split_whitespace().collect();
+                let matches = !phrase.is_empty() && words.starts_with(&phrase);
+                matches.then_some((*command, phrase.len()))
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_repeats() -> ParseOptions {
+        ParseOptions {
+            repeats: false,
+            ..ParseOptions::default()
+        }
+    }
+
+    #[test]
+    fn grammars() {
+        let digits = digits_grammar(Lang::English);
+        assert_eq!(digits.len(), 11);
+        assert_eq!(digits.phrases()[0], "zero");
+        assert_eq!(digits.phrases()[10], "oh");
+        let nato = nato_alphabet_grammar();
+        assert_eq!(nato.len(), 26);
+        assert_eq!(nato.phrases()[23], "x-ray");
+        let letters = spelling_grammar(Lang::English);
+        let letters: String = letters.phrases().concat();
+        assert_eq!(letters, "abcdefghijklmnopqrstuvwxyz");
+    }
+    #[test]
+    fn digits() {
+        assert_eq!(parse_digits("four one one"), "411");
+        assert_eq!(parse_digits("zero oh nine"), "009");
+        assert_eq!(parse_digits("  One   TWO "), "12");
+        assert_eq!(parse_digits(""), "");
+        assert_eq!(parse_digits("[unk] seven um eight"), "78");
+        assert_eq!(parse_digits("fifteen"), "");
+    }
+    #[test]
+    fn repeated_digits() {
+        assert_eq!(parse_digits("double five"), "55");
+        assert_eq!(parse_digits("triple nine one"), "9991");
+        assert_eq!(parse_digits("four double oh seven"), "4007");
+        assert_eq!(parse_digits("double double two"), "22");
+        assert_eq!(parse_digits("one double"), "1");
+        assert_eq!(parse_digits("double [unk] three"), "3");
+        assert_eq!(parse_digits_with("double five", &no_repeats()), "5");
+    }
+    #[test]
+    fn letters() {
+        assert_eq!(parse_spelling("s m i t h"), "SMITH");
+        assert_eq!(parse_spelling("bee oh bee"), "BOB");
+        assert_eq!(parse_spelling("see aitch are eye ess"), "CHRIS");
+        assert_eq!(parse_spelling("why zed"), "YZ");
+        assert_eq!(parse_spelling("[unk] k um"), "K");
+        assert_eq!(parse_spelling("seven"), "");
+    }
+    #[test]
+    fn spelling_alphabet() {
+        assert_eq!(parse_spelling("Charlie alfa romeo lima alpha"), "CARLA");
+        assert_eq!(parse_spelling("x-ray xray x ray"), "XXX");
+        assert_eq!(parse_spelling("x"), "X");
+        assert_eq!(parse_spelling("juliett echo whisky"), "JEW");
+        assert_eq!(parse_spelling("mike e l"), "MEL");
+    }
+    #[test]
+    fn numbers() {
+        let cases = [
+            ("twenty one", 21),
+            ("Twenty  One", 21),
+            ("seven", 7),
+            ("zero", 0),
+            ("thirteen", 13),
+            ("ninety", 90),
+            ("one hundred", 100),
+            ("one hundred and five", 105),
+            ("three hundred twelve", 312),
+            ("nineteen hundred", 1900),
+            ("twenty one hundred", 2100),
+            ("two thousand and five", 2005),
+            ("two thousand twenty four", 2024),
+            ("three hundred thousand", 300_000),
+            ("one million two hundred thousand six", 1_200_006),
+            ("minus four", -4),
+            ("negative twenty", -20),
+            ("four oh four", 404),
+            ("oh seven", 7),
+            ("21", 21),
+            ("minus 3", -3),
+        ];
+        for (text, number) in cases {
+            assert_eq!(parse_number(text), Some(number), "{:?}", text);
+        }
+    }
+    #[test]
+    fn not_numbers() {
+        let cases = [
+            "",
+            "minus",
+            "lights",
+            "twenty lights",
+            "one twenty",
+            "twenty thirty",
+            "twenty ten",
+            "eleven five",
+            "one hundred hundred",
+            "hundred",
+            "thousand",
+            "one thousand two thousand",
+            "and five",
+            "one hundred and",
+            "one and five",
+            "zero five hundred",
+            "2 1 fish",
+            "-5",
+        ];
+        for text in cases {
+            assert_eq!(parse_number(text), None, "{:?}", text);
+        }
+    }
+    #[test]
+    fn numbers_grammar_words() {
+        let grammar = numbers_grammar(Lang::English);
+        // Every word of the grammar is read as part of some number.
+        for word in grammar.phrases() {
+            let text = match word.as_str() {
+                "and" => "one hundred and one".to_string(),
+                "minus" => "minus one".to_string(),
+                "hundred" | "thousand" | "million" => format!("one {}", word),
+                _ => word.clone(),
+            };
+            assert!(parse_number(&text).is_some(), "{:?}", text);
+        }
+        assert_eq!(grammar.len(), 34);
+    }
+    #[test]
+    fn repeated_letters() {
+        assert_eq!(parse_spelling("a double n a"), "ANNA");
+        assert_eq!(parse_spelling("double papa"), "PP");
+        assert_eq!(parse_spelling("triple x ray"), "XXX");
+        assert_eq!(parse_spelling("double u"), "W");
+        assert_eq!(parse_spelling("double you"), "W");
+        assert_eq!(parse_spelling("triple u"), "UUU");
+        assert_eq!(parse_spelling("double uniform"), "UU");
+        assert_eq!(parse_spelling_with("double n", &no_repeats()), "N");
+        assert_eq!(parse_spelling_with("double u", &no_repeats()), "U");
+    }
+
+    /// Feeds scripted utterances, collecting what each returns and the digits after it.
+    fn take_down(opts: PhoneNumberOptions, utterances: &[&str]) -> Vec<(Option<String>, String)> {
+        let mut number = NumberState::new(opts);
+        utterances
+            .iter()
+            .map(|text| (number.hear(text), number.digits.clone()))
+            .collect()
+    }
+    fn len(len: RangeInclusive<usize>) -> PhoneNumberOptions {
+        PhoneNumberOptions {
+            len,
+            ..PhoneNumberOptions::default()
+        }
+    }
+    fn pending(digits: &str) -> (Option<String>, String) {
+        (None, digits.to_string())
+    }
+    fn complete(digits: &str) -> (Option<String>, String) {
+        (Some(digits.to_string()), digits.to_string())
+    }
+
+    #[test]
+    fn number_in_groups() {
+        let steps = take_down(
+            len(10..=10),
+            &[
+                "five five five",
+                "",
+                "one two [unk] three",
+                "double four five six",
+            ],
+        );
+        assert_eq!(
+            steps,
+            vec![
+                pending("555"),
+                pending("555"),
+                pending("555123"),
+                complete("5551234456"),
+            ]
+        );
+    }
+    #[test]
+    fn corrections() {
+        let steps = take_down(
+            len(7..=10),
+            &[
+                "five five five",
+                "one two three",
+                "correction",
+                "one two four correction one two five",
+                "start over",
+                "nine one one correction correction",
+                "eight six seven five three oh nine",
+            ],
+        );
+        assert_eq!(
+            steps,
+            vec![
+                pending("555"),
+                pending("555123"),
+                pending("555"),
+                pending("555125"),
+                pending(""),
+                pending(""),
+                pending("8675309"),
+            ]
+        );
+    }
+    #[test]
+    fn done_phrase() {
+        let steps = take_down(
+            len(4..=15),
+            &["one two", "done", "three four done five", "six"],
+        );
+        assert_eq!(
+            steps,
+            vec![
+                pending("12"),
+                pending("12"),
+                complete("1234"),
+                pending("1234")
+            ]
+        );
+    }
+    #[test]
+    fn overlong_group() {
+        let steps = take_down(len(5..=5), &["one two three", "four five six", "four five"]);
+        assert_eq!(
+            steps,
+            vec![pending("123"), pending("123"), complete("12345")]
+        );
+    }
+    #[test]
+    fn custom_commands() {
+        let opts = PhoneNumberOptions {
+            correction: vec!["no".to_string(), "wrong".to_string()],
+            restart: vec!["clear  all".to_string()],
+            done: vec!["that's it".to_string()],
+            ..len(3..=3)
+        };
+        let steps = take_down(
+            opts,
+            &[
+                "one two wrong",
+                "three clear all four",
+                "five no",
+                "six that's it",
+            ],
+        );
+        assert_eq!(
+            steps,
+            vec![pending(""), pending("4"), pending("4"), pending("46")]
+        );
+        let mut number = NumberState::new(len(3..=3));
+        assert_eq!(number.hear("one two three"), Some("123".to_string()));
+        number.clear();
+        assert_eq!(number.hear("done"), None);
+    }
+    #[test]
+    fn invalid_sample_rate() {
+        let model = crate::test_util::fake_model("model");
+        let capture = PhoneNumberCapture::new(&model, -8000.0, 10..=10);
+        assert_eq!(capture.err(), Some(Error::InvalidSampleRate(-8000.0)));
+    }
+}
@@ -0,0 +1,332 @@
+//! Switching a recognizer between named grammars, such as one per screen of an app.
+//!
+//! ```no_run
+//! # use vosk::{profiles::GrammarSession, Grammar, Model};
+//! # fn main() -> Result<(), vosk::Error> {
+//! # let model = Model::new("model")?;
+//! let mut session = GrammarSession::builder()
+//!     .profile("media", Grammar::new(["play", "pause", "next track"]).with_unknown())
+//!     .profile("navigation", Grammar::new(["go home", "go back"]).with_unknown())
+//!     .build(&model, 16000.0)?;
+//! session.activate("navigation")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::grammar::grammar_json;
+use crate::partial::PartialTracker;
+use crate::{Error, Event, Grammar, Model, Recognizer, SampleRate, UtteranceOwned};
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::ffi::CString;
+
+/// Collects the profiles of a `GrammarSession`.
+#[derive(Debug, Clone, Default)]
+pub struct GrammarSessionBuilder {
+    profiles: Vec<(String, Grammar)>,
+}
+
+impl GrammarSessionBuilder {
+    /// Adds a grammar under `name`. A name added again gets the latest grammar.
+    pub fn profile(mut self, name: &str, grammar: Grammar) -> Self {
+        self.profiles.retain(|(n, _)| n != name);
+        self.profiles.push((name.to_string(), grammar));
+        self
+    }
+    /// Checks every grammar against the model and creates a recognizer with the first.
+    ///
+    /// Fails with `Error::OutOfVocabulary` if the model doesn't know some of the words,
+    /// with `Error::EmptyGrammar` without profiles or for one without phrases,
+    /// and with `Error::InvalidSampleRate` unless the rate is finite and positive.
+    pub fn build(self, model: &Model, sample_rate: f32) -> Result<GrammarSession, Error> {
+        let rate = SampleRate::try_from(sample_rate)?;
+        let Some((_, first)) = self.profiles.first() else {
+            return Err(Error::EmptyGrammar);
+        };
+        let mut profiles = Vec::with_capacity(self.profiles.len());
+        for (name, grammar) in &self.profiles {
+            grammar.validate(model)?;
+            let (json, phrases) = grammar_json(grammar)?;
+            profiles.push(Profile {
+                name: name.clone(),
+                json,
+                phrases,
+            });
+        }
+        let recognizer = Recognizer::try_with_grammar(model, rate.hz(), first)?;
+        Ok(GrammarSession {
+            session: Session::new(recognizer, profiles),
+        })
+    }
+}
+
+/// A recognizer with several grammars to choose from, the first one active at the start.
+///
+/// The grammars are checked and turned into JSON once when the session is built,
+/// so switching only hands the prepared grammar to libvosk.
+pub struct GrammarSession {
+    session: Session<Recognizer>,
+}
+
+impl GrammarSession {
+    pub fn builder() -> GrammarSessionBuilder {
+        GrammarSessionBuilder::default()
+    }
+    /// Switches to the grammar of `name`, reported by `Event::GrammarChanged`.
+    ///
+    /// The utterance in progress is finalized with the old grammar first, as an
+    /// `Event::Final` unless empty. Activating the active profile does nothing,
+    /// and an unknown name fails with `Error::UnknownProfile`.
+    pub fn activate(&mut self, name: &str) -> Result<(), Error> {
+        self.session.activate(name)
+    }
+    /// The name of the active profile.
+    pub fn active(&self) -> &str {
+        self.session.active()
+    }
+    /// The names of the profiles, in the order they were added.
+    pub fn profiles(&self) -> impl Iterator<Item = &str> {
+        self.session.profiles.iter().map(|p| p.name.as_str())
+    }
+    /// Feeds audio to the recognizer.
+    pub fn feed(&mut self, samples: &[i16]) {
+        self.session.feed(samples)
+    }
+    /// The next event of the audio fed and the profiles activated so far.
+    pub fn next_event(&mut self) -> Option<Event> {
+        self.session.events.pop_front()
+    }
+    /// Finalizes the utterance in progress.
+    pub fn finish(&mut self) -> UtteranceOwned {
+        self.session.partials.reset();
+        Backend::final_result(&mut self.session.backend)
+    }
+    pub fn recognizer(&self) -> &Recognizer {
+        &self.session.backend
+    }
+    /// Setting another grammar directly leaves the session thinking the profile is active.
+    pub fn recognizer_mut(&mut self) -> &mut Recognizer {
+        &mut self.session.backend
+    }
+    pub fn into_inner(self) -> Recognizer {
+        self.session.backend
+    }
+}
+
+/// A grammar ready to be handed to libvosk.
+struct Profile {
+    name: String,
+    json: CString,
+    phrases: usize,
+}
+
+/// What a session needs of a recognizer, so that it can be tested without a model.
+trait Backend {
+    fn accept(&mut self, samples: &[i16]) -> bool;
+    fn result(&mut self) -> UtteranceOwned;
+    fn partial(&mut self) -> String;
+    fn final_result(&mut self) -> UtteranceOwned;
+    fn set_grammar(&mut self, profile: &Profile);
+}
+
+impl Backend for Recognizer {
+    fn accept(&mut self, samples: &[i16]) -> bool {
+        self.accept_waveform(samples)
+    }
+    fn result(&mut self) -> UtteranceOwned {
+        Recognizer::result(self).into_owned()
+    }
+    fn partial(&mut self) -> String {
+        self.partial_result().partial.into_owned()
+    }
+    fn final_result(&mut self) -> UtteranceOwned {
+        Recognizer::final_result(self).into_owned()
+    }
+    fn set_grammar(&mut self, profile: &Profile) {
+        self.set_grammar_json(&profile.json, profile.phrases)
+    }
+}
+
+struct Session<B> {
+    backend: B,
+    profiles: Vec<Profile>,
+    /// Index in `profiles`.
+    active: usize,
+    partials: PartialTracker,
+    events: VecDeque<Event>,
+}
+
+impl<B: Backend> Session<B> {
+    fn new(backend: B, profiles: Vec<Profile>) -> Self {
+        Session {
+            backend,
+            profiles,
+            active: 0,
+            partials: PartialTracker::default(),
+            events: VecDeque::new(),
+        }
+    }
+    fn active(&self) -> &str {
+        &self.profiles[self.active].name
+    }
+    fn activate(&mut self, name: &str) -> Result<(), Error> {
+        let index = self
+            .profiles
+            .iter()
+            .position(|p| p.name == name)
+            .ok_or_else(|| Error::UnknownProfile(name.to_string()))?;
+        if index == self.active {
+            return Ok(());
+        }
+        // libvosk only takes a new grammar between utterances.
+        let last = self.backend.final_result();
+        self.partials.reset();
+        if !last.text.is_empty() {
+            self.events.push_back(Event::Final(last));
+        }
+        self.backend.set_grammar(&self.profiles[index]);
+        self.active = index;
+        self.events.push_back(Event::GrammarChanged {
+            profile: name.to_string(),
+        });
+        Ok(())
+    }
+    fn feed(&mut self, samples: &[i16]) {
+        if self.backend.accept(samples) {
+            self.partials.reset();
+            self.events.push_back(Event::Final(self.backend.result()));
+        } else {
+            let partial = self.backend.partial();
+            if let Some(text) = self.partials.changed(&partial) {
+                self.events.push_back(Event::Partial(text.to_string()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RecognizedText;
+
+    /// Hears a word for each sample of 1, and ends the utterance at a 2.
+    #[derive(Default)]
+    struct Mock {
+        words: Vec<String>,
+        grammar: String,
+        calls: Vec<String>,
+    }
+
+    impl Backend for Mock {
+        fn accept(&mut self, samples: &[i16]) -> bool {
+            for &s in samples {
+                if s == 1 {
+                    self.words
+                        .push(format!("{}{}", self.grammar, self.words.len()));
+                }
+            }
+            samples.contains(&2)
+        }
+        fn result(&mut self) -> UtteranceOwned {
+            RecognizedText::from_text(self.words.drain(..).collect::<Vec<_>>().join(" "))
+        }
+        fn partial(&mut self) -> String {
+            self.words.join(" ")
+        }
+        fn final_result(&mut self) -> UtteranceOwned {
+            self.calls.push("final".to_string());
+            self.result()
+        }
+        fn set_grammar(&mut self, profile: &Profile) {
+            self.calls.push(format!("set {}", profile.name));
+            self.grammar = profile.name.clone();
+        }
+    }
+
+    fn session() -> Session<Mock> {
+        let profiles = [
+            ("media", Grammar::new(["play", "pause"])),
+            ("nav", Grammar::new(["go  home", "[unk]"])),
+        ]
+        .iter()
+        .map(|(name, grammar)| {
+            let (json, phrases) = grammar_json(grammar).unwrap();
+            Profile {
+                name: name.to_string(),
+                json,
+                phrases,
+            }
+        })
+        .collect();
+        Session::new(Mock::default(), profiles)
+    }
+    fn events(session: &mut Session<Mock>) -> Vec<Event> {
+        session.events.drain(..).collect()
+    }
+    fn changed(profile: &str) -> Event {
+        Event::GrammarChanged {
+            profile: profile.to_string(),
+        }
+    }
+
+    #[test]
+    fn prerendered_grammars() {
+        let session = session();
+        assert_eq!(session.active(), "media");
+        let nav = &session.profiles[1];
+        assert_eq!(nav.json.to_str().unwrap(), r#"["go home","[unk]"]"#);
+        assert_eq!(nav.phrases, 2);
+    }
+    #[test]
+    fn unknown_profile() {
+        let mut session = session();
+        assert_eq!(
+            session.activate("dictation"),
+            Err(Error::UnknownProfile("dictation".to_string()))
+        );
+        assert_eq!(session.active(), "media");
+        assert!(session.backend.calls.is_empty());
+        assert!(events(&mut session).is_empty());
+    }
+    #[test]
+    fn reactivating_does_nothing() {
+        let mut session = session();
+        session.feed(&[1]);
+        session.activate("media").unwrap();
+        assert!(session.backend.calls.is_empty());
+        assert_eq!(events(&mut session), [Event::Partial("0".to_string())]);
+        // The utterance goes on.
+        session.feed(&[1, 2]);
+        assert_eq!(
+            events(&mut session),
+            [Event::Final(RecognizedText::from_text("0 1"))]
+        );
+    }
+    #[test]
+    fn switch_mid_utterance() {
+        let mut session = session();
+        session.feed(&[1, 0, 1]);
+        session.activate("nav").unwrap();
+        assert_eq!(session.backend.calls, ["final", "set nav"]);
+        assert_eq!(session.active(), "nav");
+        assert_eq!(
+            events(&mut session),
+            [
+                Event::Partial("0 1".to_string()),
+                Event::Final(RecognizedText::from_text("0 1")),
+                changed("nav"),
+            ]
+        );
+        // The partial starts over with the new grammar.
+        session.feed(&[1]);
+        assert_eq!(events(&mut session), [Event::Partial("nav0".to_string())]);
+
+        // Between utterances nothing is finalized.
+        session.feed(&[2]);
+        events(&mut session);
+        session.activate("media").unwrap();
+        assert_eq!(events(&mut session), [changed("media")]);
+        session.feed(&[1]);
+        assert_eq!(events(&mut session), [Event::Partial("media0".to_string())]);
+    }
+}
@@ -0,0 +1,176 @@
+use crate::duration_of;
+use std::time::{Duration, Instant};
+
+/// How far a transcription got, as reported to a `ProgressReporter`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    /// Audio recognized so far.
+    pub processed: Duration,
+    /// Length of all of the audio, None when unknown, such as for streams
+    /// and files without a duration in their header.
+    pub total: Option<Duration>,
+    /// Bytes read from the input so far: of the file for `transcribe_file`,
+    /// of 16-bit samples otherwise.
+    pub bytes: u64,
+    /// Time taken so far, decoding included, per second of audio processed.
+    /// Below 1 when faster than real time.
+    pub real_time_factor: f64,
+}
+
+impl Progress {
+    /// Share of the audio processed, from 0 to 1, if the total is known.
+    pub fn fraction(&self) -> Option<f64> {
+        let total = self.total?.as_secs_f64();
+        if total == 0.0 {
+            return Some(1.0);
+        }
+        Some((self.processed.as_secs_f64() / total).min(1.0))
+    }
+}
+
+/// Calls a function with the `Progress` of a transcription at regular intervals of audio,
+/// and once more at the end.
+///
+/// The interval is half a second of audio by default. Recognition runs in chunks,
+/// so calls happen at the first chunk past each interval rather than exactly on it.
+pub struct ProgressReporter<F> {
+    on_progress: F,
+    interval: Duration,
+    next: Duration,
+    last: Option<Duration>,
+    start: Instant,
+}
+
+impl<F: FnMut(Progress)> ProgressReporter<F> {
+    pub fn new(on_progress: F) -> Self {
+        ProgressReporter {
+            on_progress,
+            interval: Duration::from_millis(500),
+            next: Duration::from_millis(500),
+            last: None,
+            start: Instant::now(),
+        }
+    }
+    /// Reports every `interval` of audio instead; zero reports after every chunk.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self.next = interval;
+        self
+    }
+    /// To be called after every chunk with the totals so far.
+    pub(crate) fn update(
+        &mut self,
+        samples: u64,
+        sample_rate: u32,
+        bytes: u64,
+        total: Option<u64>,
+    ) {
+        let processed = duration_of(samples, sample_rate as f32);
+        if processed < self.next {
+            return;
+        }
+        while self.next <= processed && !self.interval.is_zero() {
+            self.next += self.interval;
+        }
+        self.report(processed, sample_rate, bytes, total);
+    }
+    /// To be called at the end, reports unless the last call was for the same audio.
+    pub(crate) fn finish(
+        &mut self,
+        samples: u64,
+        sample_rate: u32,
+        bytes: u64,
+        total: Option<u64>,
+    ) {
+        let processed = duration_of(samples, sample_rate as f32);
+        if self.last != Some(processed) {
+            self.report(processed, sample_rate, bytes, total);
+        }
+    }
+    fn report(&mut self, processed: Duration, sample_rate: u32, bytes: u64, total: Option<u64>) {
+        self.last = Some(processed);
+        let real_time_factor = if processed.is_zero() {
+            0.0
+        } else {
+            self.start.elapsed().as_secs_f64() / processed.as_secs_f64()
+        };
+        (self.on_progress)(Progress {
+            processed,
+            total: total.map(|samples| duration_of(samples, sample_rate as f32)),
+            bytes,
+            real_time_factor,
+        });
+    }
+}
+
+/// Reports nothing, for the helpers called without a reporter.
+pub(crate) fn ignore_progress() -> ProgressReporter<fn(Progress)> {
+    ProgressReporter::new(|_| {})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds `chunks` samples at a time at 1 kHz, returning the reports.
+    fn report_all(interval: Option<Duration>, chunks: &[u64], total: Option<u64>) -> Vec<Progress> {
+        let mut reported = Vec::new();
+        let mut reporter = ProgressReporter::new(|p| reported.push(p));
+        if let Some(interval) = interval {
+            reporter = reporter.with_interval(interval);
+        }
+        let mut samples = 0;
+        for chunk in chunks {
+            samples += chunk;
+            reporter.update(samples, 1000, samples * 2, total);
+        }
+        reporter.finish(samples, 1000, samples * 2, total);
+        reported
+    }
+    fn processed_ms(reports: &[Progress]) -> Vec<u128> {
+        reports.iter().map(|p| p.processed.as_millis()).collect()
+    }
+
+    #[test]
+    fn every_half_second() {
+        let reports = report_all(None, &[100; 23], Some(2300));
+        assert_eq!(processed_ms(&reports), [500, 1000, 1500, 2000, 2300]);
+        let last = reports.last().unwrap();
+        assert_eq!(last.total, Some(Duration::from_millis(2300)));
+        assert_eq!((last.bytes, last.fraction()), (4600, Some(1.0)));
+        assert!((reports[0].fraction().unwrap() - 5.0 / 23.0).abs() < 1e-9);
+    }
+    #[test]
+    fn monotonic() {
+        let chunks: Vec<u64> = (0..200).map(|i| i * 7 % 13 + 1).collect();
+        let reports = report_all(Some(Duration::from_millis(50)), &chunks, None);
+        assert!(reports.len() > 10);
+        for pair in reports.windows(2) {
+            assert!(pair[0].processed < pair[1].processed);
+            assert!(pair[0].bytes < pair[1].bytes);
+        }
+        // The gaps are at least the interval, less than one more chunk.
+        for pair in reports[..reports.len() - 1].windows(2) {
+            let gap = pair[1].processed - pair[0].processed;
+            assert!(gap >= Duration::from_millis(37) && gap < Duration::from_millis(63));
+        }
+        assert!(reports
+            .iter()
+            .all(|p| p.total.is_none() && p.fraction().is_none()));
+    }
+    #[test]
+    fn large_chunks() {
+        // One report per chunk even when a chunk spans several intervals.
+        let reports = report_all(None, &[1200, 1200, 100], None);
+        assert_eq!(processed_ms(&reports), [1200, 2400, 2500]);
+        let reports = report_all(Some(Duration::ZERO), &[10, 10, 10], None);
+        assert_eq!(processed_ms(&reports), [10, 20, 30]);
+    }
+    #[test]
+    fn nothing_to_process() {
+        let reports = report_all(None, &[], Some(0));
+        assert_eq!(processed_ms(&reports), [0]);
+        assert_eq!(reports[0].fraction(), Some(1.0));
+        assert_eq!(reports[0].real_time_factor, 0.0);
+    }
+}
@@ -0,0 +1,251 @@
+//! Finding the parts of a transcript that likely need a human review.
+
+use crate::segment::TimeRange;
+use crate::stats::{speaking_rate_with, RateOptions};
+use crate::text::join_words;
+use crate::{ConfStats, RecognizedText, RecognizedWord};
+use serde::{Deserialize, Serialize};
+
+/// Thresholds of `quality_report`.
+#[derive(Debug, Clone)]
+pub struct QualityOptions {
+    /// Number of consecutive words the confidence and rate are computed over.
+    /// Utterances with fewer words are checked as a whole.
+    pub window_words: usize,
+    /// Windows with a lower mean word confidence are flagged.
+    pub min_confidence: f32,
+    /// Windows spoken faster than this many words per minute are flagged,
+    /// fast speech being recognized poorly.
+    pub max_words_per_minute: f32,
+}
+
+impl Default for QualityOptions {
+    fn default() -> Self {
+        QualityOptions {
+            window_words: 5,
+            min_confidence: 0.6,
+            max_words_per_minute: 250.0,
+        }
+    }
+}
+
+/// Everything flagged in a transcript, in the order of the utterances.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QualityReport {
+    pub issues: Vec<QualityIssue>,
+}
+
+/// A part of an utterance that is probably wrong.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QualityIssue {
+    /// Index of the utterance in the input.
+    pub utterance: usize,
+    /// None when the utterance has no word details.
+    pub range: Option<TimeRange>,
+    pub text: String,
+    #[serde(flatten)]
+    pub kind: IssueKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum IssueKind {
+    /// Overlapping windows with a low mean confidence, merged.
+    LowConfidence { mean_confidence: f32 },
+    /// Overlapping windows spoken too fast, merged, with the highest rate among them.
+    FastSpeech { words_per_minute: f32 },
+    /// Without word details nothing can be checked.
+    /// Enable them with `Recognizer::set_words`.
+    NoWordDetails,
+}
+
+impl QualityReport {
+    pub fn is_empty(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Checks each utterance with sliding windows of words.
+pub fn quality_report(utterances: &[RecognizedText], opts: &QualityOptions) -> QualityReport {
+    let mut issues = Vec::new();
+    for (i, utterance) in utterances.iter().enumerate() {
+        let words = match &utterance.result {
+            Some(words) => words,
+            None => {
+                issues.push(QualityIssue {
+                    utterance: i,
+                    range: None,
+                    text: utterance.text.to_string(),
+                    kind: IssueKind::NoWordDetails,
+                });
+                continue;
+            }
+        };
+        let low_confidence = flagged_runs(words, opts.window_words, |window| {
+            ConfStats::of(window).is_some_and(|stats| stats.mean < opts.min_confidence)
+        });
+        for run in low_confidence {
+            let mean = ConfStats::of(run).map_or(0.0, |stats| stats.mean);
+            issues.push(issue(
+                i,
+                run,
+                IssueKind::LowConfidence {
+                    mean_confidence: mean,
+                },
+            ));
+        }
+        // Pauses don't make speech slower.
+        let rate_opts = RateOptions {
+            max_pause: Some(0.0),
+        };
+        let rate = |window: &[RecognizedWord]| speaking_rate_with(window, &rate_opts);
+        let fast = flagged_runs(words, opts.window_words, |window| {
+            rate(window) > opts.max_words_per_minute
+        });
+        for run in fast {
+            let fastest = run
+                .windows(opts.window_words.clamp(1, run.len()))
+                .map(rate)
+                .fold(0.0, f32::max);
+            issues.push(issue(
+                i,
+                run,
+                IssueKind::FastSpeech {
+                    words_per_minute: fastest,
+                },
+            ));
+        }
+    }
+    QualityReport { issues }
+}
+
+/// The runs of words covered by flagged windows, overlapping windows merged.
+fn flagged_runs<'w, 'a, F>(
+    words: &'w [RecognizedWord<'a>],
+    window: usize,
+    flagged: F,
+) -> Vec<&'w [RecognizedWord<'a>]>
+where
+    F: Fn(&[RecognizedWord]) -> bool,
+{
+    if words.is_empty() {
+        return Vec::new();
+    }
+    let window = window.clamp(1, words.len());
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for start in 0..=words.len() - window {
+        if !flagged(&words[start..start + window]) {
+            continue;
+        }
+        match runs.last_mut() {
+            Some(run) if run.1 >= start => run.1 = start + window,
+            _ => runs.push((start, start + window)),
+        }
+    }
+    runs.into_iter()
+        .map(|(from, to)| &words[from..to])
+        .collect()
+}
+
+fn issue(utterance: usize, words: &[RecognizedWord], kind: IssueKind) -> QualityIssue {
+    let texts: Vec<&str> = words.iter().map(RecognizedWord::word).collect();
+    let (text, _) = join_words(&texts);
+    QualityIssue {
+        utterance,
+        range: Some(TimeRange {
+            start: words[0].start(),
+            end: words[words.len() - 1].end(),
+        }),
+        text,
+        kind,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{utterance, with_confidences};
+
+    fn opts(window_words: usize) -> QualityOptions {
+        QualityOptions {
+            window_words,
+            ..QualityOptions::default()
+        }
+    }
+
+    #[test]
+    fn clean_transcript() {
+        let u = with_confidences(&[1.0; 8]);
+        assert!(quality_report(&[u], &opts(3)).is_empty());
+    }
+    #[test]
+    fn low_confidence_segment() {
+        let u = with_confidences(&[1.0, 1.0, 1.0, 0.2, 0.3, 0.4, 1.0, 1.0, 1.0, 1.0]);
+        let report = quality_report(&[u], &opts(3));
+        assert_eq!(report.issues.len(), 1);
+        let issue = &report.issues[0];
+        // Windows starting at w2, w3 and w4 are below 0.6.
+        assert_eq!(issue.text, "w2 w3 w4 w5 w6");
+        assert_eq!(
+            issue.range,
+            Some(TimeRange {
+                start: 1.0,
+                end: 3.5
+            })
+        );
+        match issue.kind {
+            IssueKind::LowConfidence { mean_confidence } => {
+                assert!((mean_confidence - 0.58).abs() < 1e-5)
+            }
+            ref other => panic!("unexpected {:?}", other),
+        }
+    }
+    #[test]
+    fn fast_speech() {
+        // Five words in a second is 300 words per minute.
+        let u = utterance(&[
+            ("a", 0.0, 0.5),
+            ("b", 0.5, 1.0),
+            ("c", 2.0, 2.2),
+            ("d", 2.2, 2.4),
+            ("e", 2.4, 2.6),
+            ("f", 2.6, 2.8),
+            ("g", 2.8, 3.0),
+        ]);
+        let report = quality_report(&[u], &opts(5));
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].text, "c d e f g");
+        assert_eq!(
+            report.issues[0].kind,
+            IssueKind::FastSpeech {
+                words_per_minute: 300.0
+            }
+        );
+    }
+    #[test]
+    fn missing_details() {
+        let mut u = utterance(&[("hello", 0.0, 0.5)]);
+        u.result = None;
+        let short = with_confidences(&[0.1]);
+        let report = quality_report(&[u, short], &opts(5));
+        assert_eq!(report.issues.len(), 2);
+        assert_eq!(report.issues[0].kind, IssueKind::NoWordDetails);
+        assert_eq!(report.issues[0].range, None);
+        assert_eq!(report.issues[0].text, "hello");
+        // Shorter than a window, checked as a whole.
+        assert_eq!(report.issues[1].utterance, 1);
+    }
+    #[test]
+    fn json() {
+        let mut u = utterance(&[("hello", 0.0, 0.5)]);
+        u.result = None;
+        let report = quality_report(&[u], &opts(5));
+        let json = serde_json::to_string(&report).unwrap();
+        assert_eq!(
+            json,
+            r#"{"issues":[{"utterance":0,"range":null,"text":"hello","kind":"no_word_details"}]}"#
+        );
+        let parsed: QualityReport = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, report);
+    }
+}
@@ -0,0 +1,147 @@
+use crate::{Error, Model};
+use std::convert::TryFrom;
+use std::fmt;
+
+/// The sample rate of audio in Hz, always finite and positive.
+///
+/// libvosk takes any float and misbehaves on nonsense such as a negative rate,
+/// so the constructors check rates through this type.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct SampleRate(f32);
+
+impl SampleRate {
+    /// Telephony, such as G.711.
+    pub const HZ_8000: SampleRate = SampleRate(8000.0);
+    /// What most models are trained on.
+    pub const HZ_16000: SampleRate = SampleRate(16000.0);
+    pub const HZ_22050: SampleRate = SampleRate(22050.0);
+    /// CD audio.
+    pub const HZ_44100: SampleRate = SampleRate(44100.0);
+    /// Most sound cards and video.
+    pub const HZ_48000: SampleRate = SampleRate(48000.0);
+    /// The rates audio usually comes at.
+    pub const COMMON: [SampleRate; 5] = [
+        SampleRate::HZ_8000,
+        SampleRate::HZ_16000,
+        SampleRate::HZ_22050,
+        SampleRate::HZ_44100,
+        SampleRate::HZ_48000,
+    ];
+
+    pub const fn hz(self) -> f32 {
+        self.0
+    }
+    /// Whether this is one of `COMMON`. Other rates work, but are
+    /// often a mistake, such as samples per channel times the channels.
+    pub fn is_common(self) -> bool {
+        SampleRate::COMMON.contains(&self)
+    }
+}
+
+impl TryFrom<f32> for SampleRate {
+    type Error = Error;
+
+    /// Fails with `Error::InvalidSampleRate` for NaN, infinite, zero and negative rates.
+    fn try_from(hz: f32) -> Result<Self, Error> {
+        if hz.is_finite() && hz > 0.0 {
+            Ok(SampleRate(hz))
+        } else {
+            Err(Error::InvalidSampleRate(hz))
+        }
+    }
+}
+
+impl TryFrom<u32> for SampleRate {
+    type Error = Error;
+
+    /// Fails with `Error::InvalidSampleRate` for 0.
+    fn try_from(hz: u32) -> Result<Self, Error> {
+        SampleRate::try_from(hz as f32)
+    }
+}
+
+impl From<SampleRate> for f32 {
+    fn from(rate: SampleRate) -> f32 {
+        rate.0
+    }
+}
+
+impl fmt::Display for SampleRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} Hz", self.0)
+    }
+}
+
+/// Checks the rate given to a constructor that can't fail.
+#[track_caller]
+pub(crate) fn expect_valid(hz: f32) -> SampleRate {
+    match SampleRate::try_from(hz) {
+        Ok(rate) => rate,
+        Err(_) => panic!("invalid sample rate {}, it must be finite and positive", hz),
+    }
+}
+
+/// Fails with `Error::SampleRateMismatch` if the model declares another rate than
+/// `requested`. Models that don't declare one pass.
+pub(crate) fn check_model(model: &Model, requested: f32) -> Result<(), Error> {
+    match model.sample_rate() {
+        // Rates are whole numbers of Hz, anything closer is the same rate.
+        Some(declared) if (declared - requested).abs() >= 0.5 => Err(Error::SampleRateMismatch {
+            model: declared,
+            requested,
+        }),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_rates() {
+        for &hz in &[
+            0.0,
+            -0.0,
+            -16000.0,
+            -f32::MIN_POSITIVE,
+            f32::NAN,
+            -f32::NAN,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+        ] {
+            match SampleRate::try_from(hz) {
+                Err(Error::InvalidSampleRate(rejected)) => {
+                    assert_eq!(rejected.to_bits(), hz.to_bits())
+                }
+                other => panic!("{} accepted: {:?}", hz, other),
+            }
+        }
+        assert_eq!(
+            SampleRate::try_from(0u32),
+            Err(Error::InvalidSampleRate(0.0))
+        );
+        let model = crate::test_util::fake_model("model");
+        let recognizer = crate::Recognizer::try_new(&model, -16000.0);
+        assert_eq!(recognizer.unwrap_err(), Error::InvalidSampleRate(-16000.0));
+    }
+    #[test]
+    fn valid_rates() {
+        assert_eq!(SampleRate::try_from(16000u32), Ok(SampleRate::HZ_16000));
+        assert_eq!(SampleRate::try_from(8000.0).unwrap().hz(), 8000.0);
+        assert_eq!(
+            SampleRate::try_from(u32::MAX).unwrap().hz(),
+            u32::MAX as f32
+        );
+        let odd = SampleRate::try_from(f32::MIN_POSITIVE).unwrap();
+        assert!(!odd.is_common());
+        assert!(SampleRate::HZ_44100.is_common());
+        assert_eq!(f32::from(SampleRate::HZ_48000), 48000.0);
+        assert_eq!(SampleRate::HZ_22050.to_string(), "22050 Hz");
+    }
+    #[test]
+    #[should_panic(expected = "invalid sample rate -8000")]
+    fn constructors_panic() {
+        expect_valid(-8000.0);
+    }
+}
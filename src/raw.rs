@@ -0,0 +1,121 @@
+//! Converting models and recognizers to and from the pointers of libvosk,
+//! for calling functions of `vosk::sys` this crate doesn't wrap.
+
+use crate::{Model, ModelInner, Recognizer};
+use std::path::PathBuf;
+use std::ptr;
+use std::sync::Arc;
+use vosk_sys::{VoskModel, VoskRecognizer};
+
+impl Model {
+    /// The pointer to the model, which stays owned by this `Model`.
+    ///
+    /// It is valid until the last clone of this model is dropped.
+    /// Don't pass it to `vosk_model_free`.
+    pub fn as_raw(&self) -> *mut VoskModel {
+        self.ptr()
+    }
+    /// Gives up the model to the caller, who must free it with `vosk_model_free`.
+    ///
+    /// A `Model` is shared by its clones, so this only succeeds for the last one;
+    /// while others exist the model is given back unchanged.
+    /// Recognizers created from the model don't count: libvosk keeps
+    /// the model they use alive itself until they are freed.
+    pub fn into_raw(self) -> Result<*mut VoskModel, Model> {
+        let mut inner = Arc::try_unwrap(self.inner).map_err(|inner| Model { inner })?;
+        // Null keeps dropping `inner` from freeing the model.
+        Ok(std::mem::replace(&mut inner.ptr, ptr::null_mut()))
+    }
+    /// Takes ownership of a model created by libvosk, freeing it with
+    /// `vosk_model_free` once the last clone of the returned `Model` is dropped.
+    ///
+    /// `path` is what `path` returns and what `disk_size`, `sample_rate` and the like look at;
+    /// give the directory the model was loaded from.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be a model returned by `vosk_model_new` (or `Model::into_raw`)
+    /// that nothing else frees or uses after this call, except through the returned
+    /// `Model` and recognizers created from it.
+    pub unsafe fn from_raw<P: Into<PathBuf>>(ptr: *mut VoskModel, path: P) -> Model {
+        let path = path.into();
+        let inner = ModelInner {
+            ptr,
+            sample_rate: crate::validate::declared_sample_rate(&path),
+            path,
+            memory_delta: None,
+        };
+        Model {
+            inner: Arc::new(inner),
+        }
+    }
+}
+
+impl Recognizer {
+    /// The pointer to the recognizer, which stays owned by this `Recognizer`.
+    ///
+    /// It is valid as long as the recognizer; don't pass it to `vosk_recognizer_free`.
+    /// Calls through it bypass the bookkeeping of the wrapper: audio fed that way
+    /// doesn't count towards `samples_processed`, for instance.
+    pub fn as_raw(&self) -> *mut VoskRecognizer {
+        self.ptr
+    }
+    /// Gives up the recognizer to the caller, who must free it with `vosk_recognizer_free`.
+    ///
+    /// The model it was created from stays alive until then, whether or not
+    /// the `Model` is dropped before.
+    pub fn into_raw(mut self) -> *mut VoskRecognizer {
+        // Null keeps `Drop` from freeing the recognizer.
+        std::mem::replace(&mut self.ptr, ptr::null_mut())
+    }
+    /// Takes ownership of a recognizer created by libvosk, freeing it with
+    /// `vosk_recognizer_free` when the returned `Recognizer` is dropped.
+    ///
+    /// The settings of the wrapper start as those of `Recognizer::new`, whatever
+    /// was set on the pointer; call `set_words` and the like again as needed.
+    /// Audio fed before doesn't count towards `samples_processed`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be a recognizer returned by one of the `vosk_recognizer_new`
+    /// functions (or `Recognizer::into_raw`) that nothing else frees or uses after
+    /// this call. It must have been created from `model` with `sample_rate`,
+    /// which result times and durations are computed from.
+    pub unsafe fn from_raw(
+        ptr: *mut VoskRecognizer,
+        model: &Model,
+        sample_rate: f32,
+    ) -> Recognizer {
+        Recognizer::from_ptr(ptr, model, sample_rate, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::fake_model;
+
+    #[test]
+    fn model_shared_by_clones() {
+        let model = fake_model("models/en");
+        let clone = model.clone();
+        let model = model.into_raw().unwrap_err();
+        assert_eq!(model.path(), clone.path());
+        drop(clone);
+        assert!(model.into_raw().unwrap().is_null());
+    }
+    #[test]
+    fn model_from_raw() {
+        let model = unsafe { Model::from_raw(ptr::null_mut(), "models/de") };
+        assert_eq!(model.path(), std::path::Path::new("models/de"));
+        assert!(model.as_raw().is_null());
+    }
+    #[test]
+    fn recognizer_from_raw() {
+        let model = fake_model("models/en");
+        let recognizer = unsafe { Recognizer::from_raw(ptr::null_mut(), &model, 8000.0) };
+        assert!(recognizer.as_raw().is_null());
+        assert!(format!("{:?}", recognizer).contains("models/en"));
+        assert!(recognizer.into_raw().is_null());
+    }
+}
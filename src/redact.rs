@@ -0,0 +1,224 @@
+//! Hiding sensitive words in results, such as account numbers in support calls.
+//!
+//! Redacted words are replaced by a placeholder in both the text and the word list,
+//! and keep their times and confidence, so the timeline stays intact.
+
+use crate::text::join_words;
+use crate::{RecognizedTextOwned, RecognizedWord};
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// Spelled-out digits as recognized by English models.
+const DIGIT_WORDS: [&str; 11] = [
+    "zero", "oh", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+];
+
+/// Replaces every word for which `predicate` is true with `placeholder`.
+///
+/// Without word details, the whitespace-separated words of the text are checked.
+pub fn redact<F>(utterance: &mut RecognizedTextOwned, predicate: F, placeholder: &str)
+where
+    F: Fn(&str) -> bool,
+{
+    let mask: Vec<bool> = words(utterance).map(predicate).collect();
+    apply(utterance, &mask, placeholder);
+}
+
+/// Replaces runs of digits, spelled out or not, that are at least `min_digits` long,
+/// such as account and card numbers.
+///
+/// Each word of the run is replaced, "4111 1111" counting as 8 digits.
+pub fn redact_digits(utterance: &mut RecognizedTextOwned, min_digits: usize, placeholder: &str) {
+    let digits: Vec<usize> = words(utterance).map(digit_count).collect();
+    let mut mask = vec![false; digits.len()];
+    let mut run_start = 0;
+    for i in 0..=digits.len() {
+        if digits.get(i).is_some_and(|&n| n > 0) {
+            continue;
+        }
+        if digits[run_start..i].iter().sum::<usize>() >= min_digits.max(1) {
+            mask[run_start..i].fill(true);
+        }
+        run_start = i + 1;
+    }
+    apply(utterance, &mask, placeholder);
+}
+
+/// Replaces every word that overlaps a match of `re` in the text,
+/// so patterns can span several words.
+#[cfg(feature = "regex")]
+pub fn redact_regex(utterance: &mut RecognizedTextOwned, re: &regex::Regex, placeholder: &str) {
+    let words: Vec<&str> = words(utterance).collect();
+    let (text, spans) = layout(&utterance.text, &words);
+    let matches: Vec<_> = re.find_iter(&text).map(|m| m.range()).collect();
+    let mask: Vec<bool> = spans
+        .iter()
+        .map(|span| {
+            matches
+                .iter()
+                .any(|m| m.start < span.end && span.start < m.end)
+        })
+        .collect();
+    apply(utterance, &mask, placeholder);
+}
+
+/// The words of the word list, or of the text without word details.
+fn words(utterance: &RecognizedTextOwned) -> Box<dyn Iterator<Item = &str> + '_> {
+    match &utterance.result {
+        Some(words) => Box::new(words.iter().map(RecognizedWord::word)),
+        None => Box::new(utterance.text.split_whitespace()),
+    }
+}
+
+fn digit_count(word: &str) -> usize {
+    if DIGIT_WORDS.contains(&word) {
+        1
+    } else if !word.is_empty() && word.bytes().all(|b| b.is_ascii_digit()) {
+        word.len()
+    } else {
+        0
+    }
+}
+
+/// Replaces the words flagged in `mask` and rebuilds the text.
+fn apply(utterance: &mut RecognizedTextOwned, mask: &[bool], placeholder: &str) {
+    if !mask.contains(&true) {
+        return;
+    }
+    let words: Vec<&str> = words(utterance).collect();
+    let (text, spans) = layout(&utterance.text, &words);
+    let mut redacted = String::with_capacity(text.len());
+    let mut last = 0;
+    for (span, _) in spans.iter().zip(mask).filter(|(_, &masked)| masked) {
+        redacted.push_str(&text[last..span.start]);
+        redacted.push_str(placeholder);
+        last = span.end;
+    }
+    redacted.push_str(&text[last..]);
+    utterance.text = Cow::Owned(redacted);
+    if let Some(words) = &mut utterance.result {
+        for (word, _) in words.iter_mut().zip(mask).filter(|(_, &masked)| masked) {
+            word.word = Cow::Owned(placeholder.to_string());
+        }
+    }
+}
+
+/// Finds each word in the text, in order. The text is rebuilt from the words
+/// if they can't all be found, for example after the text was edited.
+fn layout<'t>(text: &'t str, words: &[&str]) -> (Cow<'t, str>, Vec<Range<usize>>) {
+    let mut spans = Vec::with_capacity(words.len());
+    let mut from = 0;
+    for word in words {
+        match text[from..].find(word) {
+            Some(i) => {
+                spans.push(from + i..from + i + word.len());
+                from += i + word.len();
+            }
+            None => {
+                let (text, spans) = join_words(words);
+                return (Cow::Owned(text), spans);
+            }
+        }
+    }
+    (Cow::Borrowed(text), spans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::utterance;
+    use crate::RecognizedText;
+
+    fn words(u: &RecognizedTextOwned) -> Vec<(&str, f32, f32)> {
+        u.result
+            .iter()
+            .flatten()
+            .map(|w| (w.word(), w.start(), w.end()))
+            .collect()
+    }
+
+    #[test]
+    fn repeated_word() {
+        let mut u = utterance(&[
+            ("call", 0.0, 0.3),
+            ("bob", 0.3, 0.6),
+            ("and", 0.6, 0.8),
+            ("bob", 0.8, 1.1),
+        ]);
+        redact(&mut u, |w| w == "bob", "[name]");
+        assert_eq!(u.text, "call [name] and [name]");
+        assert_eq!(
+            words(&u),
+            vec![
+                ("call", 0.0, 0.3),
+                ("[name]", 0.3, 0.6),
+                ("and", 0.6, 0.8),
+                ("[name]", 0.8, 1.1)
+            ]
+        );
+    }
+    #[test]
+    fn digit_runs() {
+        let mut u = utterance(&[
+            ("my", 0.0, 0.2),
+            ("number", 0.2, 0.5),
+            ("is", 0.5, 0.6),
+            ("four", 0.6, 0.9),
+            ("one", 0.9, 1.1),
+            ("one", 1.1, 1.3),
+            ("1234", 1.3, 2.0),
+            ("and", 2.0, 2.2),
+            ("one", 2.2, 2.4),
+            ("dog", 2.4, 2.6),
+        ]);
+        redact_digits(&mut u, 6, "#");
+        assert_eq!(u.text, "my number is # # # # and one dog");
+        assert_eq!(words(&u)[6], ("#", 1.3, 2.0));
+        assert_eq!(words(&u)[8], ("one", 2.2, 2.4));
+    }
+    #[test]
+    fn without_word_details() {
+        let mut u = RecognizedText {
+            text: "pin one two three four".into(),
+            result: None,
+        };
+        redact_digits(&mut u, 4, "*");
+        assert_eq!(u.text, "pin * * * *");
+        assert!(u.result.is_none());
+    }
+    #[test]
+    fn cjk_joining() {
+        // The text no longer matches the words, so it's rebuilt from them.
+        let mut u = utterance(&[("我", 0.0, 0.2), ("叫", 0.2, 0.4), ("李明", 0.4, 0.9)]);
+        u.text = "".into();
+        redact(&mut u, |w| w == "李明", "某某");
+        assert_eq!(u.text, "我叫某某");
+        let mut u = utterance(&[("call", 0.0, 0.2), ("李明", 0.2, 0.4), ("now", 0.4, 0.9)]);
+        u.text = "".into();
+        redact(&mut u, |w| w == "李明", "某某");
+        assert_eq!(u.text, "call 某某 now");
+    }
+    #[test]
+    fn nothing_to_redact() {
+        let mut u = utterance(&[("hello", 0.0, 0.5)]);
+        redact(&mut u, |_| false, "x");
+        assert_eq!(u.text, "hello");
+    }
+    #[cfg(feature = "regex")]
+    #[test]
+    fn regex_across_words() {
+        let mut u = utterance(&[
+            ("ask", 0.0, 0.3),
+            ("john", 0.3, 0.6),
+            ("smith", 0.6, 0.9),
+            ("or", 0.9, 1.0),
+            ("john", 1.0, 1.3),
+            ("smith", 1.3, 1.6),
+            ("junior", 1.6, 2.0),
+        ]);
+        let re = regex::Regex::new("john smith").unwrap();
+        redact_regex(&mut u, &re, "[name]");
+        assert_eq!(u.text, "ask [name] [name] or [name] [name] junior");
+        assert_eq!(words(&u)[5], ("[name]", 1.3, 1.6));
+    }
+}
@@ -0,0 +1,162 @@
+//! Showing results in a terminal, with words colored by confidence.
+
+use crate::text::join_words;
+use crate::RecognizedText;
+
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+/// Which confidence gets which color, see `ansi_colored`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorOptions {
+    /// Words at least this confident are green.
+    pub green: f32,
+    /// Words at least this confident, but not enough for green, are yellow.
+    /// Less confident ones are red.
+    pub yellow: f32,
+    /// Plain text without escape sequences, for output that isn't a terminal.
+    /// Set by default if the `NO_COLOR` environment variable isn't empty.
+    pub no_color: bool,
+}
+
+impl Default for ColorOptions {
+    fn default() -> Self {
+        ColorOptions {
+            green: 0.9,
+            yellow: 0.6,
+            no_color: std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()),
+        }
+    }
+}
+
+/// How sure the recognizer was of a word.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfidenceLevel {
+    High,
+    Medium,
+    Low,
+    /// The confidence isn't a number.
+    Unknown,
+}
+
+impl ColorOptions {
+    pub fn level(&self, conf: f32) -> ConfidenceLevel {
+        if conf.is_nan() {
+            ConfidenceLevel::Unknown
+        } else if conf >= self.green {
+            ConfidenceLevel::High
+        } else if conf >= self.yellow {
+            ConfidenceLevel::Medium
+        } else {
+            ConfidenceLevel::Low
+        }
+    }
+}
+
+/// The words of the utterance, each colored by its confidence with ANSI escape sequences.
+///
+/// Words are joined like the text of results, without spaces between Chinese or
+/// Japanese characters. Without word details the text is returned as it is,
+/// and so is everything with `no_color`.
+pub fn ansi_colored(utterance: &RecognizedText, opts: &ColorOptions) -> String {
+    let words = match utterance.result.as_deref() {
+        Some(words) if !words.is_empty() => words,
+        _ => return utterance.text.to_string(),
+    };
+    let (text, spans) = join_words(&words.iter().map(|w| w.word()).collect::<Vec<_>>());
+    if opts.no_color {
+        return text;
+    }
+    let mut out = String::with_capacity(text.len() + words.len() * (GREEN.len() + RESET.len()));
+    let mut end = 0;
+    for (word, span) in words.iter().zip(spans) {
+        out.push_str(&text[end..span.start]);
+        let color = match opts.level(word.conf()) {
+            ConfidenceLevel::High => Some(GREEN),
+            ConfidenceLevel::Medium => Some(YELLOW),
+            ConfidenceLevel::Low => Some(RED),
+            ConfidenceLevel::Unknown => None,
+        };
+        match color {
+            Some(color) => {
+                out.push_str(color);
+                out.push_str(&text[span.clone()]);
+                out.push_str(RESET);
+            }
+            None => out.push_str(&text[span.clone()]),
+        }
+        end = span.end;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RecognizedWord;
+
+    fn colors() -> ColorOptions {
+        ColorOptions {
+            green: 0.9,
+            yellow: 0.6,
+            no_color: false,
+        }
+    }
+    fn words(words: &[(&str, f32)]) -> RecognizedText<'static> {
+        RecognizedText::from_words(
+            words
+                .iter()
+                .enumerate()
+                .map(|(i, &(w, conf))| {
+                    RecognizedWord::new(w.to_string(), conf, i as f32, i as f32 + 0.5)
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn levels() {
+        let opts = colors();
+        assert_eq!(opts.level(1.0), ConfidenceLevel::High);
+        assert_eq!(opts.level(0.9), ConfidenceLevel::High);
+        assert_eq!(opts.level(0.89), ConfidenceLevel::Medium);
+        assert_eq!(opts.level(0.6), ConfidenceLevel::Medium);
+        assert_eq!(opts.level(0.59), ConfidenceLevel::Low);
+        assert_eq!(opts.level(0.0), ConfidenceLevel::Low);
+        assert_eq!(opts.level(f32::NAN), ConfidenceLevel::Unknown);
+    }
+    #[test]
+    fn escapes() {
+        let u = words(&[
+            ("turn", 1.0),
+            ("of", 0.7),
+            ("the", 0.2),
+            ("lights", f32::NAN),
+        ]);
+        assert_eq!(
+            ansi_colored(&u, &colors()),
+            "\x1b[32mturn\x1b[0m \x1b[33mof\x1b[0m \x1b[31mthe\x1b[0m lights"
+        );
+        let plain = ColorOptions {
+            no_color: true,
+            ..colors()
+        };
+        assert_eq!(ansi_colored(&u, &plain), "turn of the lights");
+    }
+    #[test]
+    fn cjk_words() {
+        let u = words(&[("我", 0.95), ("叫", 0.5), ("Li", 0.95)]);
+        assert_eq!(
+            ansi_colored(&u, &colors()),
+            "\x1b[32m我\x1b[0m\x1b[31m叫\x1b[0m \x1b[32mLi\x1b[0m"
+        );
+    }
+    #[test]
+    fn without_word_details() {
+        let u = RecognizedText::from_text("hello  there");
+        assert_eq!(ansi_colored(&u, &colors()), "hello  there");
+        assert_eq!(ansi_colored(&RecognizedText::from_text(""), &colors()), "");
+    }
+}
@@ -0,0 +1,577 @@
+//! Recording sessions with a recognizer and replaying them, to turn reports of
+//! "it behaves differently on my machine" into a file that reproduces them.
+//!
+//! A `RecordingRecognizer` keeps every call made to it, with the audio it was fed
+//! and the raw JSON libvosk returned. The `Recording` can be saved, attached to a
+//! bug report, and replayed against another recognizer, such as one built with a
+//! different version of libvosk, listing where the outputs differ.
+//!
+//! ```no_run
+//! # use vosk::replay::{Recording, RecordingRecognizer};
+//! # use vosk::{Model, Recognizer};
+//! # fn main() -> Result<(), vosk::Error> {
+//! # let model = Model::new("model")?;
+//! # let samples = [0; 16000];
+//! let mut recognizer = RecordingRecognizer::new(Recognizer::new(&model, 16000.0));
+//! recognizer.set_words(true);
+//! recognizer.accept_waveform(&samples);
+//! println!("{}", recognizer.final_result()?.text);
+//! recognizer.recording().save("session.vkreplay")?;
+//!
+//! // Elsewhere:
+//! let recording = Recording::load("session.vkreplay")?;
+//! let mut recognizer = recording.recognizer(&model)?;
+//! for difference in recording.replay(&mut recognizer) {
+//!     println!("{}", difference);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # Format
+//!
+//! Files start with the 8 bytes `VOSKREPL`, the format version as a little-endian
+//! `u16`, and the sample rate as a little-endian `f32`. Then come the steps, each a
+//! tag byte followed by its fields, until the end of the file. Integers are
+//! little-endian, strings are a `u32` length in bytes followed by UTF-8.
+//!
+//! | Tag | Step | Fields |
+//! |-----|------|--------|
+//! | 1 | `Accept` | `u32` sample count, the samples as `i16`, `u8` 1 if an utterance was completed |
+//! | 2 | `Partial` | JSON string |
+//! | 3 | `Result` | JSON string |
+//! | 4 | `FinalResult` | JSON string |
+//! | 5 | `Reset` | |
+//! | 6 | `SetWords` | `u8` 1 to enable |
+//! | 7 | `SetGrammar` | `u32` phrase count, each phrase a string |
+//!
+//! Readers reject files of a newer version. New steps get new tags and a new version.
+
+use crate::json::ResultParser;
+use crate::{
+    duration_of, Error, Model, RecognizedPartial, RecognizedText, RecognizedTextOwned, Recognizer,
+};
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::Duration;
+
+/// First bytes of a recording.
+const MAGIC: &[u8; 8] = b"VOSKREPL";
+
+/// Version of the format written, the newest one read.
+pub const FORMAT_VERSION: u16 = 1;
+
+/// A call made to a recognizer, with what it returned.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Step {
+    Accept {
+        samples: Vec<i16>,
+        completed: bool,
+    },
+    /// The JSON of `partial_result`.
+    Partial(String),
+    /// The JSON of `result`.
+    Result(String),
+    /// The JSON of `final_result`.
+    FinalResult(String),
+    Reset,
+    SetWords(bool),
+    SetGrammar(Vec<String>),
+}
+
+impl Step {
+    fn tag(&self) -> u8 {
+        match self {
+            Step::Accept { .. } => 1,
+            Step::Partial(_) => 2,
+            Step::Result(_) => 3,
+            Step::FinalResult(_) => 4,
+            Step::Reset => 5,
+            Step::SetWords(_) => 6,
+            Step::SetGrammar(_) => 7,
+        }
+    }
+}
+
+/// A session with a recognizer, as a list of steps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recording {
+    pub sample_rate: f32,
+    pub steps: Vec<Step>,
+}
+
+impl Recording {
+    pub fn new(sample_rate: f32) -> Self {
+        Recording {
+            sample_rate,
+            steps: Vec::new(),
+        }
+    }
+    /// Length of the audio fed.
+    pub fn audio_duration(&self) -> Duration {
+        let samples = self
+            .steps
+            .iter()
+            .map(|step| match step {
+                Step::Accept { samples, .. } => samples.len() as u64,
+                _ => 0,
+            })
+            .sum();
+        duration_of(samples, self.sample_rate)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let mut file = BufWriter::new(File::create(path)?);
+        self.write_to(&mut file)?;
+        file.flush()?;
+        Ok(())
+    }
+    /// Fails with `Error::CorruptFile` if `reader` doesn't hold a recording,
+    /// or one of a newer version.
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Self, Error> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic).map_err(truncated)?;
+        if &magic != MAGIC {
+            return Err(Error::CorruptFile("not a recording".to_string()));
+        }
+        let version = u16::from_le_bytes(read_array(&mut reader)?);
+        if version > FORMAT_VERSION {
+            return Err(Error::CorruptFile(format!(
+                "recording of format version {}, newer than {}",
+                version, FORMAT_VERSION
+            )));
+        }
+        let sample_rate = f32::from_le_bytes(read_array(&mut reader)?);
+        let mut steps = Vec::new();
+        loop {
+            let mut tag = [0];
+            if reader.read(&mut tag)? == 0 {
+                break;
+            }
+            let step = match tag[0] {
+                1 => {
+                    let count = read_u32(&mut reader)? as u64;
+                    let bytes = read_bytes(&mut reader, count * 2)?;
+                    let samples = bytes
+                        .chunks_exact(2)
+                        .map(|b| i16::from_le_bytes([b[0], b[1]]))
+                        .collect();
+                    let completed = read_array::<1, _>(&mut reader)?[0] != 0;
+                    Step::Accept { samples, completed }
+                }
+                2 => Step::Partial(read_string(&mut reader)?),
+                3 => Step::Result(read_string(&mut reader)?),
+                4 => Step::FinalResult(read_string(&mut reader)?),
+                5 => Step::Reset,
+                6 => Step::SetWords(read_array::<1, _>(&mut reader)?[0] != 0),
+                7 => {
+                    let count = read_u32(&mut reader)?;
+                    let phrases = (0..count)
+                        .map(|_| read_string(&mut reader))
+                        .collect::<Result<_, _>>()?;
+                    Step::SetGrammar(phrases)
+                }
+                tag => {
+                    return Err(Error::CorruptFile(format!(
+                        "unknown step {} in a recording",
+                        tag
+                    )))
+                }
+            };
+            steps.push(step);
+        }
+        Ok(Recording { sample_rate, steps })
+    }
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        writer.write_all(&self.sample_rate.to_le_bytes())?;
+        for step in &self.steps {
+            writer.write_all(&[step.tag()])?;
+            match step {
+                Step::Accept { samples, completed } => {
+                    write_len(&mut writer, samples.len())?;
+                    for sample in samples {
+                        writer.write_all(&sample.to_le_bytes())?;
+                    }
+                    writer.write_all(&[*completed as u8])?;
+                }
+                Step::Partial(json) | Step::Result(json) | Step::FinalResult(json) => {
+                    write_string(&mut writer, json)?
+                }
+                Step::Reset => {}
+                Step::SetWords(enable) => writer.write_all(&[*enable as u8])?,
+                Step::SetGrammar(phrases) => {
+                    write_len(&mut writer, phrases.len())?;
+                    for phrase in phrases {
+                        write_string(&mut writer, phrase)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// A recognizer for `model` at the sample rate of the recording.
+    pub fn recognizer(&self, model: &Model) -> Result<Recognizer, Error> {
+        Recognizer::try_new(model, self.sample_rate)
+    }
+    /// Makes the same calls to `recognizer`, returning where its outputs differ.
+    ///
+    /// JSON is compared by value, so differences in whitespace or the order of keys
+    /// don't count. The recognizer should be new, at the sample rate of the recording.
+    pub fn replay(&self, recognizer: &mut Recognizer) -> Vec<Difference> {
+        let mut differences = Vec::new();
+        for (i, expected) in self.steps.iter().enumerate() {
+            let actual = match expected {
+                Step::Accept { samples, .. } => Step::Accept {
+                    samples: samples.clone(),
+                    completed: recognizer.accept_waveform(samples),
+                },
+                Step::Partial(_) => Step::Partial(lossy(recognizer.partial_result_bytes())),
+                Step::Result(_) => Step::Result(lossy(recognizer.result_bytes())),
+                Step::FinalResult(_) => Step::FinalResult(lossy(recognizer.final_result_bytes())),
+                Step::Reset => {
+                    recognizer.reset();
+                    continue;
+                }
+                Step::SetWords(enable) => {
+                    recognizer.set_words(*enable);
+                    continue;
+                }
+                Step::SetGrammar(phrases) => {
+                    if let Err(e) = recognizer.try_set_grammar(phrases.iter().map(|p| [p])) {
+                        differences.push(Difference {
+                            step: i,
+                            expected: expected.clone(),
+                            actual: Err(e),
+                        });
+                    }
+                    continue;
+                }
+            };
+            if !same_output(expected, &actual) {
+                differences.push(Difference {
+                    step: i,
+                    expected: expected.clone(),
+                    actual: Ok(actual),
+                });
+            }
+        }
+        differences
+    }
+    /// Parses the recorded results, without a recognizer, for checking changes to
+    /// the parsing. Returns the number of each step with a final result or
+    /// a complete one.
+    pub fn results(&self) -> Vec<(usize, Result<RecognizedTextOwned, Error>)> {
+        let mut parser = ResultParser::new();
+        self.steps
+            .iter()
+            .enumerate()
+            .filter_map(|(i, step)| match step {
+                Step::Result(json) | Step::FinalResult(json) => {
+                    Some((i, parser.result(json).map(RecognizedText::into_owned)))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// A step whose output differs on replay.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+    /// Number of the step, from 0.
+    pub step: usize,
+    pub expected: Step,
+    /// The step as replayed, or the error replaying it.
+    pub actual: Result<Step, Error>,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let output = |step: &Step| match step {
+            Step::Accept { completed, .. } => format!("completed: {}", completed),
+            Step::Partial(json) | Step::Result(json) | Step::FinalResult(json) => {
+                json.trim().to_string()
+            }
+            other => format!("{:?}", other),
+        };
+        write!(
+            f,
+            "step {}: expected {}, got ",
+            self.step,
+            output(&self.expected)
+        )?;
+        match &self.actual {
+            Ok(actual) => write!(f, "{}", output(actual)),
+            Err(e) => write!(f, "error {}", e),
+        }
+    }
+}
+
+/// A recognizer that records the calls made to it, see the module documentation.
+///
+/// Results are parsed from the raw JSON, without the normalization or confidence
+/// checks that `Recognizer` can be set up with.
+#[derive(Debug)]
+pub struct RecordingRecognizer {
+    recognizer: Recognizer,
+    recording: Recording,
+    parser: ResultParser,
+}
+
+impl RecordingRecognizer {
+    pub fn new(recognizer: Recognizer) -> Self {
+        RecordingRecognizer {
+            recording: Recording::new(recognizer.sample_rate()),
+            recognizer,
+            parser: ResultParser::new(),
+        }
+    }
+    pub fn accept_waveform(&mut self, samples: &[i16]) -> bool {
+        let completed = self.recognizer.accept_waveform(samples);
+        self.recording.steps.push(Step::Accept {
+            samples: samples.to_vec(),
+            completed,
+        });
+        completed
+    }
+    pub fn partial_result(&mut self) -> Result<RecognizedPartial<'_>, Error> {
+        let json = lossy(self.recognizer.partial_result_bytes());
+        self.recording.steps.push(Step::Partial(json));
+        match self.recording.steps.last() {
+            Some(Step::Partial(json)) => self.parser.partial(json),
+            _ => unreachable!(),
+        }
+    }
+    pub fn result(&mut self) -> Result<RecognizedText<'_>, Error> {
+        let json = lossy(self.recognizer.result_bytes());
+        self.push_result(Step::Result(json))
+    }
+    pub fn final_result(&mut self) -> Result<RecognizedText<'_>, Error> {
+        let json = lossy(self.recognizer.final_result_bytes());
+        self.push_result(Step::FinalResult(json))
+    }
+    pub fn reset(&mut self) {
+        self.recognizer.reset();
+        self.recording.steps.push(Step::Reset);
+    }
+    pub fn set_words(&mut self, enable: bool) {
+        self.recognizer.set_words(enable);
+        self.recording.steps.push(Step::SetWords(enable));
+    }
+    /// Restricts recognition to `phrases`, each a phrase of words separated by spaces.
+    pub fn try_set_grammar<I, S>(&mut self, phrases: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let phrases: Vec<String> = phrases
+            .into_iter()
+            .map(|p| p.as_ref().to_string())
+            .collect();
+        self.recognizer
+            .try_set_grammar(phrases.iter().map(|p| [p]))?;
+        self.recording.steps.push(Step::SetGrammar(phrases));
+        Ok(())
+    }
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+    /// The recognizer, for calls that don't need recording.
+    pub fn recognizer(&self) -> &Recognizer {
+        &self.recognizer
+    }
+    pub fn into_inner(self) -> (Recognizer, Recording) {
+        (self.recognizer, self.recording)
+    }
+    fn push_result(&mut self, step: Step) -> Result<RecognizedText<'_>, Error> {
+        self.recording.steps.push(step);
+        match self.recording.steps.last() {
+            Some(Step::Result(json)) | Some(Step::FinalResult(json)) => self.parser.result(json),
+            _ => unreachable!(),
+        }
+    }
+}
+
+fn lossy(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// Whether the outputs of two steps are the same, JSON compared by value.
+fn same_output(expected: &Step, actual: &Step) -> bool {
+    let same_json = |a: &str, b: &str| match (
+        serde_json::from_str::<serde_json::Value>(a),
+        serde_json::from_str::<serde_json::Value>(b),
+    ) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    };
+    match (expected, actual) {
+        (Step::Accept { completed: a, .. }, Step::Accept { completed: b, .. }) => a == b,
+        (Step::Partial(a), Step::Partial(b))
+        | (Step::Result(a), Step::Result(b))
+        | (Step::FinalResult(a), Step::FinalResult(b)) => same_json(a, b),
+        (a, b) => a == b,
+    }
+}
+
+fn truncated(e: io::Error) -> Error {
+    if e.kind() == io::ErrorKind::UnexpectedEof {
+        Error::CorruptFile("the recording ends early".to_string())
+    } else {
+        e.into()
+    }
+}
+
+fn read_array<const N: usize, R: Read>(reader: &mut R) -> Result<[u8; N], Error> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes).map_err(truncated)?;
+    Ok(bytes)
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32, Error> {
+    Ok(u32::from_le_bytes(read_array(reader)?))
+}
+
+/// Reads `len` bytes, growing the buffer as they come, so that a damaged
+/// length doesn't allocate gigabytes.
+fn read_bytes<R: Read>(reader: &mut R, len: u64) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::new();
+    reader.take(len).read_to_end(&mut bytes)?;
+    if (bytes.len() as u64) < len {
+        return Err(truncated(io::ErrorKind::UnexpectedEof.into()));
+    }
+    Ok(bytes)
+}
+
+fn read_string<R: Read>(reader: &mut R) -> Result<String, Error> {
+    let len = read_u32(reader)?;
+    String::from_utf8(read_bytes(reader, len as u64)?)
+        .map_err(|e| Error::CorruptFile(format!("a string in the recording: {}", e)))
+}
+
+fn write_len<W: Write>(writer: &mut W, len: usize) -> Result<(), Error> {
+    let len = u32::try_from(len).map_err(|_| Error::InputTooLong(len))?;
+    writer.write_all(&len.to_le_bytes())?;
+    Ok(())
+}
+
+fn write_string<W: Write>(writer: &mut W, s: &str) -> Result<(), Error> {
+    write_len(writer, s.len())?;
+    writer.write_all(s.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Recording {
+        Recording {
+            sample_rate: 8000.0,
+            steps: vec![
+                Step::SetWords(true),
+                Step::SetGrammar(vec!["call mom".into(), "[unk]".into()]),
+                Step::Accept {
+                    samples: vec![0, -1, i16::MAX, i16::MIN],
+                    completed: false,
+                },
+                Step::Partial(r#"{"partial" : "call"}"#.into()),
+                Step::Accept {
+                    samples: vec![7; 800],
+                    completed: true,
+                },
+                Step::Result(r#"{"text" : "call mom"}"#.into()),
+                Step::Reset,
+                Step::FinalResult(r#"{"text" : "é"}"#.into()),
+            ],
+        }
+    }
+    fn encode(recording: &Recording) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        recording.write_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn round_trip() {
+        let recording = sample();
+        let bytes = encode(&recording);
+        assert_eq!(&bytes[..10], b"VOSKREPL\x01\x00");
+        assert_eq!(Recording::read_from(&bytes[..]).unwrap(), recording);
+        assert_eq!(recording.audio_duration(), Duration::from_micros(100_500));
+        let empty = Recording::new(16000.0);
+        assert_eq!(Recording::read_from(&encode(&empty)[..]).unwrap(), empty);
+    }
+    #[test]
+    fn damaged() {
+        let good = encode(&sample());
+        let corrupt = |bytes: &[u8]| match Recording::read_from(bytes) {
+            Err(Error::CorruptFile(message)) => message,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(corrupt(b"RIFF1234abcd"), "not a recording");
+        assert_eq!(corrupt(&good[..5]), "the recording ends early");
+        assert_eq!(corrupt(&good[..good.len() - 3]), "the recording ends early");
+        let mut newer = good.clone();
+        newer[8] = 2;
+        assert_eq!(
+            corrupt(&newer),
+            "recording of format version 2, newer than 1"
+        );
+        let mut unknown = good[..14].to_vec();
+        unknown.push(99);
+        assert_eq!(corrupt(&unknown), "unknown step 99 in a recording");
+        // A huge length with nothing behind it.
+        let mut long = good[..14].to_vec();
+        long.extend_from_slice(&[3, 0xff, 0xff, 0xff, 0xff, b'{']);
+        assert_eq!(corrupt(&long), "the recording ends early");
+    }
+    #[test]
+    fn parse_recorded_results() {
+        let results = sample().results();
+        let texts: Vec<(usize, String)> = results
+            .into_iter()
+            .map(|(i, r)| (i, r.unwrap().text.into_owned()))
+            .collect();
+        assert_eq!(texts, [(5, "call mom".to_string()), (7, "é".to_string())]);
+    }
+    #[test]
+    fn json_by_value() {
+        let a = Step::Result(r#"{"text" : "hi", "result" : []}"#.into());
+        let b = Step::Result(r#"{"result":[],"text":"hi"}"#.into());
+        assert!(same_output(&a, &b));
+        assert!(!same_output(
+            &a,
+            &Step::FinalResult(r#"{"text":"hi","result":[]}"#.into())
+        ));
+        assert!(!same_output(
+            &a,
+            &Step::Result(r#"{"text":"ho","result":[]}"#.into())
+        ));
+        let accept = |completed| Step::Accept {
+            samples: vec![],
+            completed,
+        };
+        assert!(same_output(&accept(true), &accept(true)));
+        assert!(!same_output(&accept(true), &accept(false)));
+        let difference = Difference {
+            step: 3,
+            expected: b,
+            actual: Ok(Step::Result("{\"text\":\"ho\"}\n".into())),
+        };
+        assert_eq!(
+            difference.to_string(),
+            r#"step 3: expected {"result":[],"text":"hi"}, got {"text":"ho"}"#
+        );
+    }
+}
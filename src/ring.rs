@@ -0,0 +1,290 @@
+//! Handing audio from a real-time callback to the recognition thread.
+//!
+//! Recognizing a chunk takes tens of milliseconds, far longer than an audio callback
+//! may block before the device drops input. The callback only copies samples into an
+//! `AudioRing` with an `AudioProducer`, which never blocks or allocates, and another
+//! thread takes them out in chunks with the `AudioConsumer` to feed the recognizer:
+//!
+//! ```no_run
+//! # use vosk::ring::AudioRing;
+//! # use vosk::{Model, Recognizer};
+//! # use std::time::Duration;
+//! # let model = Model::new("model").unwrap();
+//! let (mut producer, mut consumer) = AudioRing::new(16000 * 2).split();
+//! // Moved into the audio callback, which calls producer.push_slice(input).
+//! # let _ = &mut producer;
+//! let mut recognizer = Recognizer::new(&model, 16000.0);
+//! let mut chunk = vec![0; 1600];
+//! loop {
+//!     let n = consumer.read_chunk(&mut chunk, Duration::from_millis(10));
+//!     if n == 0 {
+//!         break; // The producer was dropped.
+//!     }
+//!     if recognizer.accept_waveform(&chunk[..n]) {
+//!         println!("{}", recognizer.result().text);
+//!     }
+//! }
+//! ```
+
+use std::sync::atomic::{AtomicBool, AtomicI16, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// A fixed number of samples shared by one producer and one consumer, without locks.
+#[derive(Debug)]
+pub struct AudioRing {
+    slots: Box<[AtomicI16]>,
+    /// Number of samples written so far, only changed by the producer.
+    written: AtomicUsize,
+    /// Number of samples read so far, only changed by the consumer.
+    read: AtomicUsize,
+    dropped: AtomicU64,
+    high_water: AtomicUsize,
+    closed: AtomicBool,
+}
+
+/// How full the ring got and what didn't fit in it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RingStats {
+    /// Samples the producer had to drop because the ring was full.
+    pub dropped: u64,
+    /// The most samples waiting in the ring at once.
+    pub high_water_mark: usize,
+    pub capacity: usize,
+}
+
+impl AudioRing {
+    /// A ring holding up to `capacity` samples.
+    pub fn new(capacity: usize) -> AudioRing {
+        AudioRing {
+            slots: (0..capacity).map(|_| AtomicI16::new(0)).collect(),
+            written: AtomicUsize::new(0),
+            read: AtomicUsize::new(0),
+            dropped: AtomicU64::new(0),
+            high_water: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+        }
+    }
+    /// The two ends, to move to their threads.
+    pub fn split(self) -> (AudioProducer, AudioConsumer) {
+        let ring = Arc::new(self);
+        (AudioProducer { ring: ring.clone() }, AudioConsumer { ring })
+    }
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+    fn len(&self) -> usize {
+        let read = self.read.load(Ordering::Acquire);
+        self.written.load(Ordering::Acquire).wrapping_sub(read)
+    }
+    fn stats(&self) -> RingStats {
+        RingStats {
+            dropped: self.dropped.load(Ordering::Relaxed),
+            high_water_mark: self.high_water.load(Ordering::Relaxed),
+            capacity: self.capacity(),
+        }
+    }
+    fn slot(&self, position: usize) -> &AtomicI16 {
+        &self.slots[position % self.slots.len()]
+    }
+}
+
+/// The writing end, safe to use in an audio callback.
+///
+/// The consumer is told the stream ended when this is dropped.
+#[derive(Debug)]
+pub struct AudioProducer {
+    ring: Arc<AudioRing>,
+}
+
+impl AudioProducer {
+    /// Copies as many samples as fit, returning how many.
+    /// The rest is dropped and counted in `stats().dropped`.
+    ///
+    /// Never blocks or allocates.
+    pub fn push_slice(&mut self, samples: &[i16]) -> usize {
+        let ring = &*self.ring;
+        let written = ring.written.load(Ordering::Relaxed);
+        let used = written.wrapping_sub(ring.read.load(Ordering::Acquire));
+        let n = samples.len().min(ring.capacity() - used);
+        for (i, &sample) in samples[..n].iter().enumerate() {
+            ring.slot(written.wrapping_add(i))
+                .store(sample, Ordering::Relaxed);
+        }
+        ring.written
+            .store(written.wrapping_add(n), Ordering::Release);
+        ring.high_water.fetch_max(used + n, Ordering::Relaxed);
+        if n < samples.len() {
+            ring.dropped
+                .fetch_add((samples.len() - n) as u64, Ordering::Relaxed);
+        }
+        n
+    }
+    /// Space left for samples.
+    pub fn free(&self) -> usize {
+        self.ring.capacity() - self.ring.len()
+    }
+    pub fn stats(&self) -> RingStats {
+        self.ring.stats()
+    }
+}
+
+impl Drop for AudioProducer {
+    fn drop(&mut self) {
+        self.ring.closed.store(true, Ordering::Release);
+    }
+}
+
+/// The reading end, for the thread running the recognizer.
+#[derive(Debug)]
+pub struct AudioConsumer {
+    ring: Arc<AudioRing>,
+}
+
+impl AudioConsumer {
+    /// Moves waiting samples to the start of `buf`, returning how many. Doesn't wait.
+    pub fn pop_slice(&mut self, buf: &mut [i16]) -> usize {
+        let ring = &*self.ring;
+        let read = ring.read.load(Ordering::Relaxed);
+        let available = ring.written.load(Ordering::Acquire).wrapping_sub(read);
+        let n = buf.len().min(available);
+        for (i, sample) in buf[..n].iter_mut().enumerate() {
+            *sample = ring.slot(read.wrapping_add(i)).load(Ordering::Relaxed);
+        }
+        ring.read.store(read.wrapping_add(n), Ordering::Release);
+        n
+    }
+    /// Discards up to `n` of the oldest waiting samples, returning how many.
+    pub fn skip(&mut self, n: usize) -> usize {
+        let ring = &*self.ring;
+        let read = ring.read.load(Ordering::Relaxed);
+        let n = n.min(ring.written.load(Ordering::Acquire).wrapping_sub(read));
+        ring.read.store(read.wrapping_add(n), Ordering::Release);
+        n
+    }
+    /// Fills `buf`, checking for new samples every `poll`, so the recognizer
+    /// is fed chunks of the same size.
+    ///
+    /// Once the producer is dropped, returns what's left, then 0.
+    pub fn read_chunk(&mut self, buf: &mut [i16], poll: Duration) -> usize {
+        self.read_chunk_until(buf, poll, || false)
+    }
+    /// Same as `read_chunk`, but gives up waiting once `stop` returns true,
+    /// returning what was read so far.
+    pub(crate) fn read_chunk_until<F>(&mut self, buf: &mut [i16], poll: Duration, stop: F) -> usize
+    where
+        F: Fn() -> bool,
+    {
+        let mut filled = 0;
+        while filled < buf.len() {
+            // Checked before popping, so that samples pushed before closing are all read.
+            let closed = self.ring.closed.load(Ordering::Acquire);
+            filled += self.pop_slice(&mut buf[filled..]);
+            if closed || filled == buf.len() || stop() {
+                break;
+            }
+            thread::sleep(poll);
+        }
+        filled
+    }
+    /// Number of samples waiting.
+    pub fn available(&self) -> usize {
+        self.ring.len()
+    }
+    /// Whether the producer was dropped; samples may still be waiting.
+    pub fn is_closed(&self) -> bool {
+        self.ring.closed.load(Ordering::Acquire)
+    }
+    pub fn stats(&self) -> RingStats {
+        self.ring.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_around() {
+        let (mut producer, mut consumer) = AudioRing::new(4).split();
+        let mut buf = [0; 4];
+        for round in 0..5 {
+            let samples = [round, round + 1, round + 2];
+            assert_eq!(producer.push_slice(&samples), 3);
+            assert_eq!(consumer.available(), 3);
+            assert_eq!(consumer.pop_slice(&mut buf), 3);
+            assert_eq!(&buf[..3], &samples);
+        }
+        assert_eq!(consumer.pop_slice(&mut buf), 0);
+    }
+    #[test]
+    fn drops_when_full() {
+        let (mut producer, mut consumer) = AudioRing::new(4).split();
+        assert_eq!(producer.push_slice(&[1, 2, 3]), 3);
+        assert_eq!(producer.push_slice(&[4, 5, 6]), 1);
+        assert_eq!(producer.free(), 0);
+        let mut buf = [0; 2];
+        assert_eq!(consumer.pop_slice(&mut buf), 2);
+        assert_eq!(producer.push_slice(&[7]), 1);
+        let mut rest = [0; 8];
+        assert_eq!(consumer.pop_slice(&mut rest), 3);
+        assert_eq!(&rest[..3], &[3, 4, 7]);
+        assert_eq!(
+            consumer.stats(),
+            RingStats {
+                dropped: 2,
+                high_water_mark: 4,
+                capacity: 4,
+            }
+        );
+        let (mut producer, _) = AudioRing::new(0).split();
+        assert_eq!(producer.push_slice(&[1]), 0);
+        assert_eq!(producer.stats().dropped, 1);
+    }
+    #[test]
+    fn chunks_until_closed() {
+        let (mut producer, mut consumer) = AudioRing::new(16).split();
+        producer.push_slice(&[1, 2, 3, 4, 5]);
+        let mut chunk = [0; 2];
+        assert_eq!(consumer.read_chunk(&mut chunk, Duration::ZERO), 2);
+        assert_eq!(consumer.read_chunk(&mut chunk, Duration::ZERO), 2);
+        drop(producer);
+        assert!(consumer.is_closed());
+        assert_eq!(consumer.read_chunk(&mut chunk, Duration::ZERO), 1);
+        assert_eq!(chunk[0], 5);
+        assert_eq!(consumer.read_chunk(&mut chunk, Duration::ZERO), 0);
+    }
+    #[test]
+    fn two_threads() {
+        // Enough to wrap around many times, and still finish under Miri.
+        const TOTAL: usize = if cfg!(miri) { 20_000 } else { 1_000_000 };
+        let (mut producer, mut consumer) = AudioRing::new(1000).split();
+        let writer = thread::spawn(move || {
+            let samples: Vec<i16> = (0..TOTAL).map(|i| i as i16).collect();
+            let mut sent = 0;
+            let mut size = 1;
+            while sent < TOTAL {
+                let end = (sent + size).min(TOTAL);
+                // Retry what didn't fit, so that nothing is lost.
+                sent += producer.push_slice(&samples[sent..end]);
+                size = size % 677 + 1;
+            }
+        });
+        let mut received = 0;
+        let mut chunk = vec![0; 333];
+        loop {
+            let n = consumer.read_chunk(&mut chunk, Duration::from_micros(50));
+            if n == 0 {
+                break;
+            }
+            for (i, &sample) in chunk[..n].iter().enumerate() {
+                assert_eq!(sample, (received + i) as i16, "at {}", received + i);
+            }
+            received += n;
+        }
+        writer.join().unwrap();
+        assert_eq!(received, TOTAL);
+        assert!(consumer.stats().high_water_mark <= 1000);
+    }
+}
@@ -0,0 +1,272 @@
+//! Recognizing speech of an unknown language, such as voicemails in one of a few.
+//!
+//! A `LanguageRouter` runs the audio through a recognizer per model and keeps
+//! the result the model was most confident about. A model of the wrong language
+//! still finds words, but usually with clearly lower confidence.
+
+use crate::text::join_words;
+use crate::{ConfStats, Model, RecognizedText, RecognizedWord, Recognizer, UtteranceOwned};
+use std::thread;
+use std::time::Duration;
+
+/// How a `LanguageRouter` runs its recognizers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouterOptions {
+    /// Runs each recognizer on a thread of its own rather than one after the other.
+    pub parallel: bool,
+    /// Decides on the language from this much audio at the start, then recognizes
+    /// the whole audio with the winning model only. The scores are then those of the start.
+    /// None decides on the whole audio.
+    pub decide_after: Option<Duration>,
+}
+
+impl Default for RouterOptions {
+    fn default() -> Self {
+        RouterOptions {
+            parallel: true,
+            decide_after: None,
+        }
+    }
+}
+
+/// How well the model of a language recognized the audio.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LanguageScore {
+    pub tag: String,
+    /// The mean confidence of all words it recognized, None if it recognized none.
+    pub mean_confidence: Option<f32>,
+    pub word_count: usize,
+}
+
+/// The result of the model of the language picked by `LanguageRouter::route`.
+#[derive(Debug)]
+pub struct Routed {
+    pub tag: String,
+    /// All the utterances in one, with word details.
+    pub utterance: UtteranceOwned,
+    /// The scores of all languages, in the order they were added.
+    pub scores: Vec<LanguageScore>,
+}
+
+/// Picks the best of several models, each tagged with its language, for each recording.
+#[derive(Debug, Clone)]
+pub struct LanguageRouter {
+    languages: Vec<(String, Model)>,
+    opts: RouterOptions,
+}
+
+impl LanguageRouter {
+    pub fn new() -> Self {
+        Self::with_options(RouterOptions::default())
+    }
+    pub fn with_options(opts: RouterOptions) -> Self {
+        LanguageRouter {
+            languages: Vec::new(),
+            opts,
+        }
+    }
+    /// Adds the model of a language, with a tag such as `"de"` to tell it by.
+    pub fn language<S: Into<String>>(mut self, tag: S, model: Model) -> Self {
+        self.languages.push((tag.into(), model));
+        self
+    }
+    /// Recognizes `samples` with the model of each language, returning the
+    /// result of the one with the highest mean word confidence.
+    ///
+    /// On a tie, or if no model recognized any word, the language added first wins.
+    /// Returns None if the router has no languages.
+    pub fn route(&self, samples: &[i16], sample_rate: f32) -> Option<Routed> {
+        let decide_on = match self.opts.decide_after {
+            Some(after) => {
+                let n = (after.as_secs_f64() * sample_rate as f64) as usize;
+                &samples[..n.min(samples.len())]
+            }
+            None => samples,
+        };
+        let results = self.recognize_all(decide_on, sample_rate);
+        let scores: Vec<LanguageScore> = self
+            .languages
+            .iter()
+            .zip(&results)
+            .map(|((tag, _), utterances)| score(tag, utterances))
+            .collect();
+        let best = select(&scores)?;
+        let utterances = if decide_on.len() < samples.len() {
+            recognize(&self.languages[best].1, samples, sample_rate)
+        } else {
+            results.into_iter().nth(best)?
+        };
+        Some(Routed {
+            tag: scores[best].tag.clone(),
+            utterance: merge(utterances),
+            scores,
+        })
+    }
+    fn recognize_all(&self, samples: &[i16], sample_rate: f32) -> Vec<Vec<UtteranceOwned>> {
+        if !self.opts.parallel {
+            return self
+                .languages
+                .iter()
+                .map(|(_, model)| recognize(model, samples, sample_rate))
+                .collect();
+        }
+        thread::scope(|scope| {
+            let threads: Vec<_> = self
+                .languages
+                .iter()
+                .map(|(_, model)| scope.spawn(move || recognize(model, samples, sample_rate)))
+                .collect();
+            threads
+                .into_iter()
+                .map(|thread| {
+                    thread
+                        .join()
+                        .unwrap_or_else(|e| std::panic::resume_unwind(e))
+                })
+                .collect()
+        })
+    }
+}
+
+impl Default for LanguageRouter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The non-empty utterances of a recognizer with word details, fed a tenth of a second at a time.
+fn recognize(model: &Model, samples: &[i16], sample_rate: f32) -> Vec<UtteranceOwned> {
+    let mut recognizer = Recognizer::new(model, sample_rate);
+    recognizer.set_words(true);
+    let mut utterances = Vec::new();
+    let chunk = (sample_rate as usize / 10).max(1);
+    for samples in samples.chunks(chunk) {
+        if recognizer.accept_waveform(samples) {
+            utterances.push(recognizer.result().into_owned());
+        }
+    }
+    utterances.push(recognizer.final_result().into_owned());
+    utterances.retain(|utterance| !utterance.text.is_empty());
+    utterances
+}
+
+fn score(tag: &str, utterances: &[UtteranceOwned]) -> LanguageScore {
+    let words: Vec<RecognizedWord> = utterances
+        .iter()
+        .flat_map(|utterance| utterance.words())
+        .map(|w| RecognizedWord::new(w.word(), w.conf(), w.start(), w.end()))
+        .collect();
+    let stats = ConfStats::of(&words);
+    LanguageScore {
+        tag: tag.to_string(),
+        mean_confidence: stats.map(|stats| stats.mean),
+        word_count: stats.map_or(0, |stats| stats.word_count),
+    }
+}
+
+/// The index of the best score, the first one among equals.
+fn select(scores: &[LanguageScore]) -> Option<usize> {
+    let mut best: Option<usize> = None;
+    for (i, score) in scores.iter().enumerate() {
+        let better =
+            best.is_none_or(
+                |b| match (score.mean_confidence, scores[b].mean_confidence) {
+                    (Some(new), Some(old)) => new > old,
+                    (new, old) => new.is_some() && old.is_none(),
+                },
+            );
+        if better {
+            best = Some(i);
+        }
+    }
+    best
+}
+
+/// Joins utterances into one, with word details if all of them have some.
+fn merge(utterances: Vec<UtteranceOwned>) -> UtteranceOwned {
+    if !utterances.is_empty() && utterances.iter().all(|u| u.result.is_some()) {
+        let words = utterances
+            .into_iter()
+            .flat_map(|u| u.result.unwrap_or_default())
+            .collect();
+        return RecognizedText::from_words(words);
+    }
+    let texts: Vec<&str> = utterances.iter().map(|u| u.text.as_ref()).collect();
+    RecognizedText::from_text(join_words(&texts).0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::utterance;
+
+    fn scores(confidences: &[(&str, Option<f32>)]) -> Vec<LanguageScore> {
+        confidences
+            .iter()
+            .map(|(tag, mean)| LanguageScore {
+                tag: tag.to_string(),
+                mean_confidence: *mean,
+                word_count: mean.map_or(0, |_| 1),
+            })
+            .collect()
+    }
+
+    fn with_confidence(words: &[&str], conf: f32) -> UtteranceOwned {
+        let words: Vec<_> = words
+            .iter()
+            .enumerate()
+            .map(|(i, w)| RecognizedWord::new(w.to_string(), conf, i as f32, i as f32 + 0.5))
+            .collect();
+        RecognizedText::from_words(words)
+    }
+
+    #[test]
+    fn highest_mean_confidence_wins() {
+        let de = vec![with_confidence(&["guten", "tag"], 0.9)];
+        let en = vec![
+            with_confidence(&["good"], 0.6),
+            with_confidence(&["tack"], 0.5),
+        ];
+        let scored = vec![score("en", &en), score("de", &de)];
+        assert_eq!(scored[0].word_count, 2);
+        assert!((scored[0].mean_confidence.unwrap() - 0.55).abs() < 1e-6);
+        assert_eq!(select(&scored), Some(1));
+    }
+    #[test]
+    fn ties_and_silence() {
+        assert_eq!(select(&[]), None);
+        assert_eq!(select(&scores(&[("en", None), ("de", None)])), Some(0));
+        assert_eq!(select(&scores(&[("en", None), ("de", Some(0.1))])), Some(1));
+        assert_eq!(
+            select(&scores(&[("en", Some(0.8)), ("de", Some(0.8))])),
+            Some(0)
+        );
+        assert_eq!(
+            select(&scores(&[
+                ("en", Some(0.4)),
+                ("de", Some(0.9)),
+                ("fr", Some(0.7))
+            ])),
+            Some(1)
+        );
+        let silent = score("en", &[]);
+        assert_eq!((silent.mean_confidence, silent.word_count), (None, 0));
+    }
+    #[test]
+    fn utterances_merged() {
+        let merged = merge(vec![
+            utterance(&[("hello", 0.0, 0.4)]),
+            utterance(&[("there", 1.0, 1.3), ("friend", 1.3, 1.8)]),
+        ]);
+        assert_eq!(merged.text, "hello there friend");
+        let words = merged.result.unwrap();
+        assert_eq!(words.len(), 3);
+        assert_eq!(words[2].start(), 1.3);
+        let plain = merge(vec![
+            RecognizedText::from_text("a"),
+            RecognizedText::from_text("b"),
+        ]);
+        assert_eq!((plain.text.as_ref(), plain.result.is_none()), ("a b", true));
+        assert_eq!(merge(Vec::new()).text, "");
+    }
+}
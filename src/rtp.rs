@@ -0,0 +1,527 @@
+//! Feeding the RTP packets of a live call to a recognizer.
+//!
+//! Packets arrive over UDP, so they can come out of order, twice or not at all.
+//! `RtpFeeder` puts them back in order within a small jitter window and fills
+//! what's missing with silence, so that word times match the call.
+
+use crate::feeder::FeederCore;
+use crate::telephony::{alaw_to_pcm_into, ulaw_to_pcm_into, G711Law, G711_RATE};
+use crate::{Error, Event, Recognizer, UtteranceOwned};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::time::Duration;
+
+/// The static payload type of comfort noise, sent during pauses instead of audio.
+const COMFORT_NOISE: u8 = 13;
+
+/// The fixed header of an RTP packet, as in RFC 3550.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RtpHeader {
+    pub marker: bool,
+    pub payload_type: u8,
+    pub sequence: u16,
+    /// In samples for the 8 kHz payloads here.
+    pub timestamp: u32,
+    pub ssrc: u32,
+}
+
+impl RtpHeader {
+    /// Splits a packet into its header and payload,
+    /// skipping contributing sources, the header extension and padding.
+    pub fn parse(packet: &[u8]) -> Result<(RtpHeader, &[u8]), Error> {
+        if packet.len() < 12 {
+            return Err(invalid(format!(
+                "{} bytes is too short for RTP",
+                packet.len()
+            )));
+        }
+        if packet[0] >> 6 != 2 {
+            return Err(invalid(format!("RTP version {}", packet[0] >> 6)));
+        }
+        let header = RtpHeader {
+            marker: packet[1] & 0x80 != 0,
+            payload_type: packet[1] & 0x7f,
+            sequence: u16::from_be_bytes([packet[2], packet[3]]),
+            timestamp: u32::from_be_bytes([packet[4], packet[5], packet[6], packet[7]]),
+            ssrc: u32::from_be_bytes([packet[8], packet[9], packet[10], packet[11]]),
+        };
+        let mut start = 12 + 4 * (packet[0] & 0x0f) as usize;
+        if packet[0] & 0x10 != 0 {
+            let words = packet
+                .get(start + 2..start + 4)
+                .ok_or_else(|| invalid("truncated RTP header extension".to_string()))?;
+            start += 4 + 4 * u16::from_be_bytes([words[0], words[1]]) as usize;
+        }
+        let mut end = packet.len();
+        if packet[0] & 0x20 != 0 {
+            end = end.saturating_sub(packet[end - 1] as usize);
+        }
+        if start > end {
+            return Err(invalid("RTP header longer than the packet".to_string()));
+        }
+        Ok((header, &packet[start..end]))
+    }
+}
+
+fn invalid(message: String) -> Error {
+    Error::InvalidPacket(message)
+}
+
+/// How the payload of an RTP packet is encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtpPayload {
+    G711(G711Law),
+    /// 16-bit big-endian mono samples at 8 kHz, usually under a dynamic payload type.
+    L16,
+}
+
+/// Settings for `RtpFeeder`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RtpOptions {
+    /// Payload types and their encoding, by default 0 for PCMU and 8 for PCMA.
+    /// Comfort noise (13) is always understood as silence.
+    pub payload_types: Vec<(u8, RtpPayload)>,
+    /// How many packets may wait for a missing one before it's given up as lost.
+    /// Each packet waiting delays the audio by its duration, usually 20 ms.
+    pub jitter_packets: usize,
+    /// The longest jump in timestamps that is filled with silence.
+    /// Longer ones are taken as a restarted clock rather than a pause.
+    pub max_gap: Duration,
+}
+
+impl Default for RtpOptions {
+    fn default() -> Self {
+        RtpOptions {
+            payload_types: vec![
+                (0, RtpPayload::G711(G711Law::MuLaw)),
+                (8, RtpPayload::G711(G711Law::ALaw)),
+            ],
+            jitter_packets: 5,
+            max_gap: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RtpOptions {
+    /// Decodes `payload_type` as `payload`, replacing what it was before.
+    pub fn with_payload_type(mut self, payload_type: u8, payload: RtpPayload) -> Self {
+        self.payload_types.retain(|&(pt, _)| pt != payload_type);
+        self.payload_types.push((payload_type, payload));
+        self
+    }
+}
+
+/// What happened to the packets so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RtpStats {
+    /// Every packet with a known payload type.
+    pub received: u64,
+    /// Packets that were already waiting in the jitter window.
+    pub duplicates: u64,
+    /// Packets that came after their turn, including duplicates of played ones.
+    pub late: u64,
+    /// Packets given up on.
+    pub lost: u64,
+    /// Samples of silence put in for lost packets and pauses.
+    pub concealed: u64,
+}
+
+/// Decodes the RTP packets of one call and feeds the audio in order to an 8 kHz recognizer.
+///
+/// When the sender changes (a new SSRC), the packets still waiting are played
+/// and ordering starts over.
+pub struct RtpFeeder {
+    core: FeederCore,
+    payload_types: Vec<(u8, RtpPayload)>,
+    jitter: JitterBuffer,
+    ssrc: Option<u32>,
+    chunks: Vec<Chunk>,
+    silence: Vec<i16>,
+}
+
+impl RtpFeeder {
+    /// Fails with `Error::AudioRateMismatch` unless the recognizer was created at 8000 Hz.
+    pub fn new(recognizer: Recognizer) -> Result<RtpFeeder, Error> {
+        RtpFeeder::with_options(recognizer, RtpOptions::default())
+    }
+    pub fn with_options(recognizer: Recognizer, options: RtpOptions) -> Result<RtpFeeder, Error> {
+        if recognizer.sample_rate() != G711_RATE {
+            return Err(Error::AudioRateMismatch {
+                recognizer: recognizer.sample_rate(),
+                audio: G711_RATE,
+            });
+        }
+        let max_gap = u32::try_from(options.max_gap.as_millis() * 8).unwrap_or(u32::MAX);
+        Ok(RtpFeeder {
+            core: FeederCore::new(recognizer),
+            payload_types: options.payload_types,
+            jitter: JitterBuffer::new(options.jitter_packets, max_gap),
+            ssrc: None,
+            chunks: Vec::new(),
+            silence: Vec::new(),
+        })
+    }
+    /// Takes one packet as received, and feeds the audio that is now in order.
+    ///
+    /// Fails for malformed packets and unknown payload types, which are otherwise ignored.
+    /// Returns the events of the audio fed, usually none or one.
+    pub fn push_packet(&mut self, packet: &[u8]) -> Result<Vec<Event>, Error> {
+        let (header, payload) = RtpHeader::parse(packet)?;
+        let samples = decode(&self.payload_types, header.payload_type, payload)?;
+        if self
+            .ssrc
+            .replace(header.ssrc)
+            .is_some_and(|s| s != header.ssrc)
+        {
+            self.jitter.flush(&mut self.chunks);
+            self.jitter.restart();
+        }
+        self.jitter
+            .push(header.sequence, header.timestamp, samples, &mut self.chunks);
+        Ok(self.feed_chunks())
+    }
+    /// Plays the packets still waiting and returns the last utterance at the end of the call,
+    /// along with the events of that audio.
+    pub fn finish(&mut self) -> (Vec<Event>, UtteranceOwned) {
+        self.jitter.flush(&mut self.chunks);
+        let events = self.feed_chunks();
+        self.jitter.restart();
+        self.ssrc = None;
+        (events, self.core.finish())
+    }
+    pub fn stats(&self) -> RtpStats {
+        self.jitter.stats
+    }
+    pub fn recognizer(&self) -> &Recognizer {
+        &self.core.recognizer
+    }
+    pub fn recognizer_mut(&mut self) -> &mut Recognizer {
+        &mut self.core.recognizer
+    }
+    pub fn into_inner(self) -> Recognizer {
+        self.core.recognizer
+    }
+
+    fn feed_chunks(&mut self) -> Vec<Event> {
+        for chunk in std::mem::take(&mut self.chunks) {
+            match chunk {
+                Chunk::Audio(samples) => {
+                    self.core.feed(&samples, true);
+                }
+                Chunk::Silence(len) => {
+                    self.silence.clear();
+                    self.silence.resize(len, 0);
+                    self.core.feed(&self.silence, true);
+                }
+            }
+        }
+        self.core.events.drain(..).collect()
+    }
+}
+
+fn decode(
+    payload_types: &[(u8, RtpPayload)],
+    payload_type: u8,
+    payload: &[u8],
+) -> Result<Vec<i16>, Error> {
+    let mut samples = Vec::with_capacity(payload.len());
+    match payload_types.iter().find(|&&(pt, _)| pt == payload_type) {
+        Some((_, RtpPayload::G711(G711Law::MuLaw))) => ulaw_to_pcm_into(payload, &mut samples),
+        Some((_, RtpPayload::G711(G711Law::ALaw))) => alaw_to_pcm_into(payload, &mut samples),
+        Some((_, RtpPayload::L16)) => {
+            if !payload.len().is_multiple_of(2) {
+                return Err(Error::OddLength(payload.len()));
+            }
+            samples.extend(
+                payload
+                    .chunks_exact(2)
+                    .map(|b| i16::from_be_bytes([b[0], b[1]])),
+            );
+        }
+        // The pause shows up as a jump in the timestamp of the next audio.
+        None if payload_type == COMFORT_NOISE => {}
+        None => {
+            return Err(Error::UnsupportedCodec(format!(
+                "RTP payload type {}",
+                payload_type
+            )))
+        }
+    }
+    Ok(samples)
+}
+
+/// Audio released by the jitter buffer, in order.
+#[derive(Debug, PartialEq)]
+enum Chunk {
+    Audio(Vec<i16>),
+    Silence(usize),
+}
+
+/// Puts packets back in order by sequence number, and fills gaps in the timestamps with silence.
+struct JitterBuffer {
+    depth: usize,
+    max_gap: u32,
+    /// The extended sequence number of the next packet to play.
+    next: Option<u64>,
+    /// The timestamp right after the audio played so far.
+    expected: Option<u32>,
+    waiting: BTreeMap<u64, (u32, Vec<i16>)>,
+    stats: RtpStats,
+}
+
+impl JitterBuffer {
+    fn new(depth: usize, max_gap: u32) -> Self {
+        JitterBuffer {
+            depth,
+            max_gap,
+            next: None,
+            expected: None,
+            waiting: BTreeMap::new(),
+            stats: RtpStats::default(),
+        }
+    }
+    fn push(&mut self, sequence: u16, timestamp: u32, samples: Vec<i16>, out: &mut Vec<Chunk>) {
+        self.stats.received += 1;
+        // Starting a wrap above zero leaves room for packets just before the first one.
+        let next = *self.next.get_or_insert(sequence as u64 + (1 << 16));
+        let extended = next as i64 + sequence.wrapping_sub(next as u16) as i16 as i64;
+        if extended < next as i64 {
+            self.stats.late += 1;
+            return;
+        }
+        let extended = extended as u64;
+        if self.waiting.contains_key(&extended) {
+            self.stats.duplicates += 1;
+            return;
+        }
+        self.waiting.insert(extended, (timestamp, samples));
+        self.release(out, false);
+    }
+    /// Plays every packet waiting, skipping the missing ones.
+    fn flush(&mut self, out: &mut Vec<Chunk>) {
+        self.release(out, true);
+    }
+    /// Forgets the order and timing of the stream, keeping the stats.
+    fn restart(&mut self) {
+        self.waiting.clear();
+        self.next = None;
+        self.expected = None;
+    }
+    fn release(&mut self, out: &mut Vec<Chunk>, flush: bool) {
+        while let Some(&first) = self.waiting.keys().next() {
+            let next = self.next.unwrap_or(first);
+            if first != next {
+                if !flush && self.waiting.len() <= self.depth {
+                    break;
+                }
+                self.stats.lost += first - next;
+            }
+            let (timestamp, samples) = self.waiting.remove(&first).unwrap_or_default();
+            self.play(timestamp, samples, out);
+            self.next = Some(first + 1);
+        }
+    }
+    fn play(&mut self, timestamp: u32, samples: Vec<i16>, out: &mut Vec<Chunk>) {
+        if let Some(expected) = self.expected {
+            let gap = timestamp.wrapping_sub(expected);
+            if (gap as i32) > 0 && gap <= self.max_gap {
+                self.stats.concealed += gap as u64;
+                out.push(Chunk::Silence(gap as usize));
+            }
+        }
+        self.expected = Some(timestamp.wrapping_add(samples.len() as u32));
+        if !samples.is_empty() {
+            out.push(Chunk::Audio(samples));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 20 ms at 8 kHz.
+    const FRAME: usize = 160;
+
+    fn packet(payload_type: u8, sequence: u16, timestamp: u32, payload: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0x80, payload_type];
+        packet.extend(sequence.to_be_bytes());
+        packet.extend(timestamp.to_be_bytes());
+        packet.extend(0x1234_5678u32.to_be_bytes());
+        packet.extend(payload);
+        packet
+    }
+    /// A frame whose samples all hold its sequence number, to follow it through the buffer.
+    fn frame(sequence: u16) -> Vec<i16> {
+        vec![sequence as i16; FRAME]
+    }
+    /// Pushes frames by sequence number, with timestamps following from it,
+    /// and describes the audio released as sequence numbers, with 0 for silence.
+    fn run(buffer: &mut JitterBuffer, sequences: &[u16]) -> Vec<i16> {
+        let mut out = Vec::new();
+        for &sequence in sequences {
+            let timestamp = (sequence as u32).wrapping_mul(FRAME as u32);
+            buffer.push(sequence, timestamp, frame(sequence), &mut out);
+        }
+        describe(out)
+    }
+    fn describe(chunks: Vec<Chunk>) -> Vec<i16> {
+        chunks
+            .into_iter()
+            .flat_map(|chunk| match chunk {
+                Chunk::Audio(samples) => samples.into_iter().step_by(FRAME).collect(),
+                Chunk::Silence(len) => {
+                    assert_eq!(len % FRAME, 0);
+                    vec![0; len / FRAME]
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parse_header() {
+        let plain = packet(8, 7, 1600, &[1, 2, 3]);
+        let (header, payload) = RtpHeader::parse(&plain).unwrap();
+        assert_eq!(
+            header,
+            RtpHeader {
+                marker: false,
+                payload_type: 8,
+                sequence: 7,
+                timestamp: 1600,
+                ssrc: 0x1234_5678,
+            }
+        );
+        assert_eq!(payload, [1, 2, 3]);
+
+        // Two contributing sources, a one-word extension and two bytes of padding.
+        let mut full = packet(0x80 | 96, 1, 2, &[]);
+        full[0] |= 0x20 | 0x10 | 2;
+        full.extend([0; 8]);
+        full.extend([0xbe, 0xde, 0, 1, 9, 9, 9, 9]);
+        full.extend([5, 6, 0, 2]);
+        let (header, payload) = RtpHeader::parse(&full).unwrap();
+        assert!(header.marker);
+        assert_eq!(header.payload_type, 96);
+        assert_eq!(payload, [5, 6]);
+    }
+    #[test]
+    fn malformed_packets() {
+        let short = RtpHeader::parse(&[0x80; 11]);
+        assert!(matches!(short, Err(Error::InvalidPacket(_))));
+        let mut version = packet(0, 1, 2, &[]);
+        version[0] = 0x40;
+        assert!(RtpHeader::parse(&version).is_err());
+        let mut padding = packet(0, 1, 2, &[1, 2, 40]);
+        padding[0] |= 0x20;
+        assert!(RtpHeader::parse(&padding).is_err());
+        let mut extension = packet(0, 1, 2, &[0, 0]);
+        extension[0] |= 0x10;
+        assert!(RtpHeader::parse(&extension).is_err());
+    }
+    #[test]
+    fn payload_types() {
+        let options = RtpOptions::default().with_payload_type(96, RtpPayload::L16);
+        let types = &options.payload_types;
+        assert_eq!(decode(types, 0, &[0xff, 0x80]).unwrap(), [0, 32124]);
+        assert_eq!(decode(types, 8, &[0xd5]).unwrap(), [8]);
+        assert_eq!(
+            decode(types, 96, &[0x01, 0x00, 0xff, 0xfe]).unwrap(),
+            [256, -2]
+        );
+        assert!(matches!(decode(types, 96, &[1]), Err(Error::OddLength(1))));
+        assert!(decode(types, COMFORT_NOISE, &[40]).unwrap().is_empty());
+        assert!(matches!(
+            decode(types, 18, &[0; 10]),
+            Err(Error::UnsupportedCodec(_))
+        ));
+        let pcma = RtpOptions::default().with_payload_type(0, RtpPayload::G711(G711Law::ALaw));
+        assert_eq!(decode(&pcma.payload_types, 0, &[0xd5]).unwrap(), [8]);
+    }
+    #[test]
+    fn reorders_within_window() {
+        let mut buffer = JitterBuffer::new(3, 8000);
+        assert_eq!(run(&mut buffer, &[1, 3, 2, 5, 4, 6]), [1, 2, 3, 4, 5, 6]);
+        assert_eq!(buffer.stats.concealed, 0);
+        assert_eq!(buffer.stats.lost, 0);
+        // Once the first packet is played, the ones before it are late.
+        let mut buffer = JitterBuffer::new(3, 8000);
+        assert_eq!(run(&mut buffer, &[11, 10]), [11]);
+        assert_eq!(buffer.stats.late, 1);
+    }
+    #[test]
+    fn duplicates_and_late_packets() {
+        let mut buffer = JitterBuffer::new(2, 8000);
+        assert_eq!(run(&mut buffer, &[1, 3, 3, 2, 2, 1]), [1, 2, 3]);
+        assert_eq!(buffer.stats.duplicates, 1);
+        assert_eq!(buffer.stats.late, 2);
+        assert_eq!(buffer.stats.received, 6);
+    }
+    #[test]
+    fn fills_lost_packets() {
+        let mut buffer = JitterBuffer::new(2, 8000);
+        assert_eq!(run(&mut buffer, &[1, 2, 4, 5]), [1, 2]);
+        // Three waiting is one more than the window.
+        assert_eq!(run(&mut buffer, &[7]), [0, 4, 5]);
+        // Too late for 3, but 6 is still awaited.
+        assert_eq!(run(&mut buffer, &[3, 6]), [6, 7]);
+        assert_eq!(buffer.stats.lost, 1);
+        assert_eq!(buffer.stats.late, 1);
+        assert_eq!(buffer.stats.concealed, FRAME as u64);
+
+        let mut out = Vec::new();
+        buffer.push(9, 9 * FRAME as u32, frame(9), &mut out);
+        buffer.push(12, 12 * FRAME as u32, frame(12), &mut out);
+        buffer.flush(&mut out);
+        assert_eq!(describe(out), [0, 9, 0, 0, 12]);
+        assert_eq!(buffer.stats.lost, 4);
+    }
+    #[test]
+    fn pauses_in_timestamps() {
+        let mut buffer = JitterBuffer::new(2, 800);
+        let mut out = Vec::new();
+        buffer.push(1, 0, frame(1), &mut out);
+        // Comfort noise, then audio after a pause of 0.1 s.
+        buffer.push(2, 160, Vec::new(), &mut out);
+        buffer.push(3, 960, frame(3), &mut out);
+        // A jump past max_gap is left alone.
+        buffer.push(4, 100_000, frame(4), &mut out);
+        // An overlap doesn't take anything away.
+        buffer.push(5, 100_100, frame(5), &mut out);
+        assert_eq!(describe(out), [1, 0, 0, 0, 0, 0, 3, 4, 5]);
+        assert_eq!(buffer.stats.lost, 0);
+        assert_eq!(buffer.stats.concealed, 800);
+    }
+    #[test]
+    fn sequence_wraps_around() {
+        let mut buffer = JitterBuffer::new(3, 8000);
+        let mut out = Vec::new();
+        for &(sequence, order) in &[(65534u16, 1i16), (0, 3), (65535, 2), (2, 5), (1, 4)] {
+            // The timestamps wrap too, between the second and third frames.
+            let timestamp = (order as u32).wrapping_sub(3).wrapping_mul(FRAME as u32);
+            buffer.push(sequence, timestamp, vec![order; FRAME], &mut out);
+        }
+        assert_eq!(describe(out), [1, 2, 3, 4, 5]);
+        assert_eq!(buffer.stats.concealed, 0);
+        // 65533 is from before the start.
+        assert!(run(&mut buffer, &[65533]).is_empty());
+        assert_eq!(buffer.stats.late, 1);
+    }
+    #[test]
+    fn needs_narrowband() {
+        let model = crate::test_util::fake_model("rtp");
+        let wideband = Recognizer::from_ptr(std::ptr::null_mut(), &model, 16000.0, None);
+        assert_eq!(
+            RtpFeeder::new(wideband).err(),
+            Some(Error::AudioRateMismatch {
+                recognizer: 16000.0,
+                audio: 8000.0
+            })
+        );
+        let narrowband = Recognizer::from_ptr(std::ptr::null_mut(), &model, 8000.0, None);
+        let mut feeder = RtpFeeder::new(narrowband).unwrap();
+        assert!(feeder.push_packet(&packet(18, 1, 0, &[0; 10])).is_err());
+        assert!(feeder.push_packet(&[0x80; 4]).is_err());
+        assert_eq!(feeder.stats(), RtpStats::default());
+    }
+}
@@ -0,0 +1,260 @@
+//! Splitting the timeline of recognized words into sentences and silences.
+//!
+//! Utterance boundaries come from silence detection in Kaldi,
+//! so one utterance often holds several sentences.
+//! Pauses between words are a good hint where a sentence ends.
+
+use crate::{RecognizedText, RecognizedWord};
+use serde::{Deserialize, Serialize};
+
+/// Controls where `segment_sentences` splits.
+#[derive(Debug, Clone)]
+pub struct SentenceOptions {
+    /// A pause between two words longer than this, in seconds, ends a sentence.
+    pub max_pause: f32,
+    /// A sentence is split once it has this many words.
+    pub max_words: usize,
+    /// A sentence is split before it gets longer than this, in seconds.
+    pub max_secs: f32,
+    /// A sentence with fewer words than this is never split,
+    /// so a short hesitation doesn't produce one-word sentences.
+    /// Only the last sentence can be shorter.
+    pub min_words: usize,
+}
+
+impl Default for SentenceOptions {
+    fn default() -> Self {
+        SentenceOptions {
+            max_pause: 0.8,
+            max_words: 30,
+            max_secs: 15.0,
+            min_words: 3,
+        }
+    }
+}
+
+/// Consecutive words forming a sentence, from `start` to `end` seconds.
+#[derive(Debug, Clone, Copy)]
+pub struct Sentence<'a> {
+    pub words: &'a [RecognizedWord<'a>],
+    pub start: f32,
+    pub end: f32,
+}
+
+/// Splits `words` at long pauses, and where sentences get too long.
+pub fn segment_sentences<'a>(
+    words: &'a [RecognizedWord<'a>],
+    opts: &SentenceOptions,
+) -> Vec<Sentence<'a>> {
+    let mut sentences = Vec::new();
+    let mut first = 0;
+    for i in 1..words.len() {
+        let count = i - first;
+        if count < opts.min_words.max(1) {
+            continue;
+        }
+        let pause = words[i].start() - words[i - 1].end();
+        if pause > opts.max_pause
+            || count >= opts.max_words
+            || words[i].end() - words[first].start() > opts.max_secs
+        {
+            sentences.push(sentence(&words[first..i]));
+            first = i;
+        }
+    }
+    if first < words.len() {
+        sentences.push(sentence(&words[first..]));
+    }
+    sentences
+}
+
+fn sentence<'a>(words: &'a [RecognizedWord<'a>]) -> Sentence<'a> {
+    Sentence {
+        words,
+        start: words[0].start(),
+        end: words[words.len() - 1].end(),
+    }
+}
+
+/// A span of time in seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TimeRange {
+    pub start: f32,
+    pub end: f32,
+}
+
+impl TimeRange {
+    pub fn duration(&self) -> f32 {
+        self.end - self.start
+    }
+}
+
+/// Finds the silences of at least `min_gap` seconds in audio of `total_duration` seconds,
+/// including any before the first word and after the last.
+///
+/// Silence is wherever no word was recognized,
+/// so utterances without word details don't count as speech.
+/// Word timings may overlap or be out of order. Words ending before they start
+/// are skipped, and times outside of the audio are taken as its start or end.
+pub fn gaps(utterances: &[RecognizedText], total_duration: f32, min_gap: f32) -> Vec<TimeRange> {
+    let within = |time: f32| time.max(0.0).min(total_duration);
+    let mut speech: Vec<TimeRange> = utterances
+        .iter()
+        .flat_map(|u| u.words())
+        .filter(|w| w.start() <= w.end())
+        .map(|w| TimeRange {
+            start: within(w.start()),
+            end: within(w.end()),
+        })
+        .collect();
+    speech.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+    let mut gaps = Vec::new();
+    let mut silent_since = 0.0f32;
+    for range in speech {
+        if range.start - silent_since >= min_gap {
+            gaps.push(TimeRange {
+                start: silent_since,
+                end: range.start,
+            });
+        }
+        silent_since = silent_since.max(range.end);
+    }
+    if total_duration - silent_since >= min_gap {
+        gaps.push(TimeRange {
+            start: silent_since,
+            end: total_duration,
+        });
+    }
+    gaps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::utterance;
+
+    /// Word counts of the sentences of words with the given (start, end) times.
+    fn split(times: &[(f32, f32)], opts: &SentenceOptions) -> Vec<usize> {
+        let words: Vec<_> = times.iter().map(|&(s, e)| ("w", s, e)).collect();
+        let words = utterance(&words).result.unwrap();
+        segment_sentences(&words, opts)
+            .iter()
+            .map(|s| s.words.len())
+            .collect()
+    }
+
+    #[test]
+    fn split_at_pause() {
+        let times = [
+            (0.0, 0.3),
+            (0.3, 0.6),
+            (0.6, 0.9),
+            (2.0, 2.3),
+            (2.3, 2.6),
+            (2.7, 3.0),
+        ];
+        assert_eq!(split(&times, &SentenceOptions::default()), vec![3, 3]);
+    }
+    #[test]
+    fn short_pause() {
+        let times = [(0.0, 0.3), (0.5, 0.8), (1.0, 1.3)];
+        assert_eq!(split(&times, &SentenceOptions::default()), vec![3]);
+    }
+    #[test]
+    fn min_words() {
+        // The pause after the first word is ignored, the one after the third isn't.
+        let times = [(0.0, 0.3), (2.0, 2.3), (2.3, 2.6), (4.0, 4.3)];
+        assert_eq!(split(&times, &SentenceOptions::default()), vec![3, 1]);
+    }
+    #[test]
+    fn max_words() {
+        let times: Vec<_> = (0..7).map(|i| (i as f32, i as f32 + 0.9)).collect();
+        let opts = SentenceOptions {
+            max_words: 3,
+            min_words: 1,
+            ..SentenceOptions::default()
+        };
+        assert_eq!(split(&times, &opts), vec![3, 3, 1]);
+    }
+    #[test]
+    fn max_duration() {
+        let times: Vec<_> = (0..6).map(|i| (i as f32, i as f32 + 1.0)).collect();
+        let opts = SentenceOptions {
+            max_secs: 4.0,
+            min_words: 1,
+            ..SentenceOptions::default()
+        };
+        assert_eq!(split(&times, &opts), vec![4, 2]);
+    }
+    #[test]
+    fn times() {
+        let words = utterance(&[("a", 0.5, 0.7), ("b", 0.7, 1.1)])
+            .result
+            .unwrap();
+        let sentences = segment_sentences(&words, &SentenceOptions::default());
+        assert_eq!((sentences[0].start, sentences[0].end), (0.5, 1.1));
+        assert!(segment_sentences(&[], &SentenceOptions::default()).is_empty());
+    }
+    #[test]
+    fn leading_and_trailing_gaps() {
+        let u = utterance(&[("a", 1.0, 1.5), ("b", 1.5, 2.0), ("c", 4.0, 4.5)]);
+        let found = gaps(&[u], 10.0, 0.5);
+        let expected = [(0.0, 1.0), (2.0, 4.0), (4.5, 10.0)];
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|&(start, end)| TimeRange { start, end })
+            .collect();
+        assert_eq!(found, expected);
+    }
+    #[test]
+    fn min_gap() {
+        let u = utterance(&[("a", 0.1, 1.0), ("b", 1.3, 2.0)]);
+        assert!(gaps(&[u], 2.2, 0.5).is_empty());
+    }
+    #[test]
+    fn unordered_and_overlapping() {
+        let later = utterance(&[("c", 5.0, 6.0)]);
+        let earlier = utterance(&[("a", 1.0, 3.0), ("b", 2.0, 2.5), ("x", 4.0, 3.5)]);
+        let found = gaps(&[later, earlier], 6.0, 0.1);
+        // x ends before it starts, so it doesn't split the gap.
+        assert_eq!(
+            found,
+            vec![
+                TimeRange {
+                    start: 0.0,
+                    end: 1.0
+                },
+                TimeRange {
+                    start: 3.0,
+                    end: 5.0
+                },
+            ]
+        );
+    }
+    #[test]
+    fn words_outside_the_audio() {
+        let u = utterance(&[("a", -0.5, 1.0), ("b", 2.0, 3.5), ("c", 4.0, 5.0)]);
+        let found = gaps(&[u], 3.0, 0.1);
+        assert_eq!(
+            found,
+            vec![TimeRange {
+                start: 1.0,
+                end: 2.0
+            }]
+        );
+    }
+    #[test]
+    fn all_silence() {
+        let no_words = RecognizedText {
+            text: "".into(),
+            result: None,
+        };
+        let expected = vec![TimeRange {
+            start: 0.0,
+            end: 3.0,
+        }];
+        assert_eq!(gaps(&[no_words], 3.0, 1.0), expected);
+        assert_eq!(gaps(&[], 3.0, 1.0), expected);
+    }
+}
@@ -0,0 +1,430 @@
+//! Where audio comes from, independent of the capture library.
+//!
+//! Implement `AudioSource` for your own capture path to use the helpers here.
+//! Included are an in-memory source, audio files with the `audio-decode` feature,
+//! and the default microphone through cpal with the `cpal` feature.
+
+use crate::progress::ignore_progress;
+use crate::{
+    Cancellable, CancellationToken, Error, Progress, ProgressReporter, Recognizer, SampleRate,
+    UtteranceOwned,
+};
+use std::convert::TryFrom;
+
+/// A stream of mono 16-bit samples.
+pub trait AudioSource {
+    fn sample_rate(&self) -> u32;
+    /// Fills the start of `buf` with the next samples, returning how many.
+    /// Returns 0 at the end of the stream; blocks until samples are available otherwise.
+    fn read(&mut self, buf: &mut [i16]) -> Result<usize, Error>;
+    /// Number of samples in the whole stream, if known.
+    fn total_samples(&self) -> Option<u64> {
+        None
+    }
+    /// Bytes of encoded input read so far, for sources that decode some.
+    fn bytes_read(&self) -> Option<u64> {
+        None
+    }
+}
+
+impl<S: AudioSource + ?Sized> AudioSource for &mut S {
+    fn sample_rate(&self) -> u32 {
+        (**self).sample_rate()
+    }
+    fn read(&mut self, buf: &mut [i16]) -> Result<usize, Error> {
+        (**self).read(buf)
+    }
+    fn total_samples(&self) -> Option<u64> {
+        (**self).total_samples()
+    }
+    fn bytes_read(&self) -> Option<u64> {
+        (**self).bytes_read()
+    }
+}
+
+impl<S: AudioSource + ?Sized> AudioSource for Box<S> {
+    fn sample_rate(&self) -> u32 {
+        (**self).sample_rate()
+    }
+    fn read(&mut self, buf: &mut [i16]) -> Result<usize, Error> {
+        (**self).read(buf)
+    }
+    fn total_samples(&self) -> Option<u64> {
+        (**self).total_samples()
+    }
+    fn bytes_read(&self) -> Option<u64> {
+        (**self).bytes_read()
+    }
+}
+
+/// Recognizes speech from `source` until it ends.
+///
+/// Returns the finalized utterances in order, leaving out empty ones.
+/// The recognizer must have been created at the sample rate of the source,
+/// which must not be 0, or this fails with `Error::AudioRateMismatch`.
+/// A live source such as a microphone never ends, so this never returns
+/// unless reading fails; see `transcribe_source_cancellable`.
+pub fn transcribe_source<S: AudioSource>(
+    recognizer: &mut Recognizer,
+    source: S,
+) -> Result<Vec<UtteranceOwned>, Error> {
+    transcribe_source_cancellable(recognizer, source, &CancellationToken::new())
+}
+
+/// Same as `transcribe_source`, but fails with `Error::Cancelled` once `cancel` is,
+/// checking it between reads. The utterances recognized until then are lost.
+pub fn transcribe_source_cancellable<S: AudioSource>(
+    recognizer: &mut Recognizer,
+    source: S,
+    cancel: &CancellationToken,
+) -> Result<Vec<UtteranceOwned>, Error> {
+    transcribe_source_with_progress(recognizer, source, cancel, ignore_progress())
+}
+
+/// Same as `transcribe_source_cancellable`, also reporting how far it got to `progress`.
+///
+/// The total is known if the source reports it. Bytes are those the source
+/// reports, or two per sample for sources that don't decode anything.
+pub fn transcribe_source_with_progress<S, F>(
+    recognizer: &mut Recognizer,
+    source: S,
+    cancel: &CancellationToken,
+    mut progress: ProgressReporter<F>,
+) -> Result<Vec<UtteranceOwned>, Error>
+where
+    S: AudioSource,
+    F: FnMut(Progress),
+{
+    let mut source = Cancellable::new(source, cancel.clone());
+    let rate = SampleRate::try_from(source.sample_rate())?;
+    if rate.hz() != recognizer.sample_rate() {
+        return Err(Error::AudioRateMismatch {
+            recognizer: recognizer.sample_rate(),
+            audio: rate.hz(),
+        });
+    }
+    let rate = source.sample_rate();
+    let total = source.total_samples();
+    let bytes = |source: &Cancellable<S>, samples: u64| source.bytes_read().unwrap_or(samples * 2);
+    // A tenth of a second at a time.
+    let mut buf = vec![0; (rate as usize / 10).max(1)];
+    let mut utterances = Vec::new();
+    let mut samples = 0;
+    loop {
+        let n = source.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        if recognizer.accept_waveform(&buf[..n]) {
+            push_utterance(&mut utterances, recognizer.result().into_owned());
+        }
+        samples += n as u64;
+        progress.update(samples, rate, bytes(&source, samples), total);
+    }
+    push_utterance(&mut utterances, recognizer.final_result().into_owned());
+    progress.finish(samples, rate, bytes(&source, samples), total);
+    Ok(utterances)
+}
+
+fn push_utterance(utterances: &mut Vec<UtteranceOwned>, utterance: UtteranceOwned) {
+    if !utterance.text.is_empty() {
+        utterances.push(utterance);
+    }
+}
+
+/// Samples already in memory, such as synthesized audio in tests.
+#[derive(Debug, Clone)]
+pub struct MemorySource {
+    pending: Pending,
+    sample_rate: u32,
+}
+
+impl MemorySource {
+    pub fn new(samples: Vec<i16>, sample_rate: u32) -> MemorySource {
+        MemorySource {
+            pending: Pending { samples, pos: 0 },
+            sample_rate,
+        }
+    }
+}
+
+impl AudioSource for MemorySource {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+    fn read(&mut self, buf: &mut [i16]) -> Result<usize, Error> {
+        Ok(self.pending.take(buf))
+    }
+    fn total_samples(&self) -> Option<u64> {
+        Some(self.pending.samples.len() as u64)
+    }
+}
+
+/// Samples received in larger pieces than were asked for.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Pending {
+    samples: Vec<i16>,
+    pos: usize,
+}
+
+impl Pending {
+    #[cfg(any(feature = "audio-decode", feature = "cpal", feature = "http-source"))]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pos == self.samples.len()
+    }
+    pub(crate) fn take(&mut self, buf: &mut [i16]) -> usize {
+        let n = buf.len().min(self.samples.len() - self.pos);
+        buf[..n].copy_from_slice(&self.samples[self.pos..self.pos + n]);
+        self.pos += n;
+        n
+    }
+    /// Refills with `samples` once all previous ones were taken.
+    #[cfg(any(feature = "audio-decode", feature = "cpal", feature = "http-source"))]
+    pub(crate) fn refill(&mut self) -> &mut Vec<i16> {
+        self.pos = 0;
+        &mut self.samples
+    }
+}
+
+/// An audio file of any format `decode` supports, including WAV, mixed down to mono.
+#[cfg(feature = "audio-decode")]
+pub struct FileSource {
+    decoder: crate::decode::MonoDecoder,
+    pending: Pending,
+}
+
+#[cfg(feature = "audio-decode")]
+impl FileSource {
+    pub fn open<P: AsRef<std::path::Path>>(path: P) -> Result<FileSource, Error> {
+        Ok(FileSource {
+            decoder: crate::decode::MonoDecoder::open(path.as_ref())?,
+            pending: Pending::default(),
+        })
+    }
+}
+
+#[cfg(feature = "audio-decode")]
+impl AudioSource for FileSource {
+    fn sample_rate(&self) -> u32 {
+        self.decoder.sample_rate
+    }
+    fn read(&mut self, buf: &mut [i16]) -> Result<usize, Error> {
+        if self.pending.is_empty() && !self.decoder.next_chunk(self.pending.refill())? {
+            return Ok(0);
+        }
+        Ok(self.pending.take(buf))
+    }
+    fn total_samples(&self) -> Option<u64> {
+        self.decoder.total_samples
+    }
+    fn bytes_read(&self) -> Option<u64> {
+        Some(self.decoder.bytes_read())
+    }
+}
+
+#[cfg(feature = "cpal")]
+pub use self::microphone::Microphone;
+
+#[cfg(feature = "cpal")]
+mod microphone {
+    use super::{AudioSource, Pending};
+    use crate::Error;
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+    use cpal::{SampleFormat, Stream, StreamConfig};
+    use std::sync::mpsc::{channel, Receiver, Sender};
+
+    type Chunk = Result<Vec<i16>, String>;
+
+    /// The default input device of the system, mixed down to mono,
+    /// at the sample rate the device prefers.
+    ///
+    /// Capture stops when it's dropped.
+    pub struct Microphone {
+        // Kept for capture to go on.
+        _stream: Stream,
+        receiver: Receiver<Chunk>,
+        pending: Pending,
+        sample_rate: u32,
+    }
+
+    impl Microphone {
+        pub fn open_default() -> Result<Microphone, Error> {
+            let device = cpal::default_host()
+                .default_input_device()
+                .ok_or_else(|| Error::Io("no input device".to_string()))?;
+            let supported = device.default_input_config().map_err(device_error)?;
+            let format = supported.sample_format();
+            let config: StreamConfig = supported.into();
+            let channels = config.channels as usize;
+            let (sender, receiver) = channel();
+            let stream = match format {
+                SampleFormat::I16 => build(&device, &config, sender, move |data: &[i16], out| {
+                    crate::pcm::downmix(data, channels, out)
+                })?,
+                SampleFormat::F32 => {
+                    let mut interleaved = Vec::new();
+                    build(&device, &config, sender, move |data: &[f32], out| {
+                        interleaved.clear();
+                        interleaved.extend(data.iter().map(|&s| {
+                            (s * i16::MAX as f32).clamp(i16::MIN as f32, i16::MAX as f32) as i16
+                        }));
+                        crate::pcm::downmix(&interleaved, channels, out)
+                    })?
+                }
+                other => {
+                    return Err(Error::UnsupportedCodec(format!(
+                        "input device format {:?}",
+                        other
+                    )))
+                }
+            };
+            stream.play().map_err(device_error)?;
+            Ok(Microphone {
+                _stream: stream,
+                receiver,
+                pending: Pending::default(),
+                sample_rate: config.sample_rate.0,
+            })
+        }
+    }
+
+    /// Starts capturing, with `convert` turning the samples of a callback into mono.
+    fn build<T, F>(
+        device: &cpal::Device,
+        config: &StreamConfig,
+        sender: Sender<Chunk>,
+        mut convert: F,
+    ) -> Result<Stream, Error>
+    where
+        T: cpal::SizedSample,
+        F: FnMut(&[T], &mut Vec<i16>) + Send + 'static,
+    {
+        let errors = sender.clone();
+        device
+            .build_input_stream(
+                config,
+                move |data: &[T], _: &cpal::InputCallbackInfo| {
+                    let mut mono = Vec::with_capacity(data.len());
+                    convert(data, &mut mono);
+                    let _ = sender.send(Ok(mono));
+                },
+                move |e| {
+                    let _ = errors.send(Err(e.to_string()));
+                },
+                None,
+            )
+            .map_err(device_error)
+    }
+
+    fn device_error<E: std::fmt::Display>(e: E) -> Error {
+        Error::Io(e.to_string())
+    }
+
+    impl AudioSource for Microphone {
+        fn sample_rate(&self) -> u32 {
+            self.sample_rate
+        }
+        fn read(&mut self, buf: &mut [i16]) -> Result<usize, Error> {
+            while self.pending.is_empty() {
+                match self.receiver.recv() {
+                    Ok(Ok(samples)) => *self.pending.refill() = samples,
+                    Ok(Err(e)) => return Err(Error::Io(e)),
+                    // The stream is kept alive, so this doesn't happen.
+                    Err(_) => return Ok(0),
+                }
+            }
+            Ok(self.pending.take(buf))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::fake_model;
+
+    #[test]
+    fn memory_reads() {
+        let mut source = MemorySource::new((0..10).collect(), 16000);
+        let mut buf = [0; 4];
+        let mut read = Vec::new();
+        loop {
+            let n = source.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            read.push(buf[..n].to_vec());
+        }
+        assert_eq!(read, vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7], vec![8, 9]]);
+        assert_eq!(source.read(&mut buf).unwrap(), 0);
+        assert_eq!(
+            (source.total_samples(), source.bytes_read()),
+            (Some(10), None)
+        );
+    }
+    #[test]
+    fn through_references() {
+        fn rate(source: impl AudioSource) -> u32 {
+            source.sample_rate()
+        }
+        let mut source = MemorySource::new(vec![1, 2], 8000);
+        assert_eq!(rate(&mut source), 8000);
+        let mut boxed: Box<dyn AudioSource> = Box::new(source);
+        let mut buf = [0; 4];
+        assert_eq!(boxed.read(&mut buf).unwrap(), 2);
+    }
+    #[test]
+    fn rate_mismatch() {
+        let model = fake_model("model");
+        let mut recognizer = Recognizer::from_ptr(std::ptr::null_mut(), &model, 16000.0, None);
+        let source = MemorySource::new(vec![0; 800], 8000);
+        let result = transcribe_source(&mut recognizer, source);
+        let Err(Error::AudioRateMismatch {
+            recognizer: expected,
+            audio,
+        }) = result
+        else {
+            panic!("{:?}", result);
+        };
+        assert_eq!(expected, 16000.0);
+        assert_eq!(audio, 8000.0);
+        let silent = MemorySource::new(Vec::new(), 0);
+        let result = transcribe_source(&mut recognizer, silent);
+        assert_eq!(result.unwrap_err(), Error::InvalidSampleRate(0.0));
+    }
+    #[test]
+    fn cancel_live_source() {
+        /// A microphone that hears nothing until the transcription is cancelled.
+        struct Waiting(CancellationToken);
+        impl AudioSource for Waiting {
+            fn sample_rate(&self) -> u32 {
+                16000
+            }
+            fn read(&mut self, buf: &mut [i16]) -> Result<usize, Error> {
+                while !self.0.is_cancelled() {
+                    std::thread::sleep(std::time::Duration::from_millis(5));
+                }
+                Ok(buf.len())
+            }
+        }
+        let model = fake_model("model");
+        let mut recognizer = Recognizer::from_ptr(std::ptr::null_mut(), &model, 16000.0, None);
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let source = MemorySource::new(vec![0; 800], 16000);
+        let result = transcribe_source_cancellable(&mut recognizer, source, &cancel);
+        assert_eq!(result.unwrap_err(), Error::Cancelled);
+
+        let cancel = CancellationToken::new();
+        let canceller = cancel.clone();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            canceller.cancel();
+        });
+        let source = Waiting(cancel.clone());
+        let result = transcribe_source_cancellable(&mut recognizer, source, &cancel);
+        // Samples read as it was cancelled never reach the recognizer.
+        assert_eq!(result.unwrap_err(), Error::Cancelled);
+        handle.join().unwrap();
+    }
+}
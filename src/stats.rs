@@ -0,0 +1,153 @@
+//! Speaking-rate metrics computed from word timings.
+
+use crate::RecognizedWord;
+use std::time::Duration;
+
+/// Controls which time counts as speaking.
+#[derive(Debug, Clone)]
+pub struct RateOptions {
+    /// Pauses between words longer than this many seconds don't count as speaking time.
+    /// With None, all time from the first word to the last counts.
+    pub max_pause: Option<f32>,
+}
+
+impl Default for RateOptions {
+    fn default() -> Self {
+        RateOptions {
+            max_pause: Some(1.0),
+        }
+    }
+}
+
+/// Words per minute of speaking time, leaving out pauses longer than a second.
+///
+/// Returns 0 when there are no words.
+pub fn speaking_rate(words: &[RecognizedWord]) -> f32 {
+    speaking_rate_with(words, &RateOptions::default())
+}
+
+/// Words per minute of speaking time, as defined by `opts`.
+pub fn speaking_rate_with(words: &[RecognizedWord], opts: &RateOptions) -> f32 {
+    let mut secs = 0.0;
+    for (i, w) in words.iter().enumerate() {
+        secs += (w.end() - w.start()).max(0.0);
+        if let Some(next) = words.get(i + 1) {
+            let pause = (next.start() - w.end()).max(0.0);
+            if opts.max_pause.is_none_or(|max| pause <= max) {
+                secs += pause;
+            }
+        }
+    }
+    if secs > 0.0 {
+        words.len() as f32 * 60.0 / secs
+    } else {
+        0.0
+    }
+}
+
+/// Speaking rate in windows of `window` length, starting every `step`,
+/// as `(time, words per minute)` with the time at the middle of the window.
+///
+/// A word belongs to the window its start is in, and pauses count as in `speaking_rate`.
+/// Windows without words have a rate of 0.
+/// Windows run from 0 until the last one that starts before the end of the last word.
+///
+/// # Panics
+///
+/// Panics if `step` is zero.
+pub fn speaking_rate_windows(
+    words: &[RecognizedWord],
+    window: Duration,
+    step: Duration,
+) -> Vec<(f32, f32)> {
+    assert!(!step.is_zero(), "step must not be zero");
+    let window = window.as_secs_f32();
+    let step = step.as_secs_f32();
+    let end = match words.last() {
+        Some(w) => w.end(),
+        None => return Vec::new(),
+    };
+    let opts = RateOptions::default();
+    let mut points = Vec::new();
+    let mut i = 0;
+    loop {
+        let start = i as f32 * step;
+        if start >= end {
+            break;
+        }
+        let first = words.partition_point(|w| w.start() < start);
+        let count = words[first..].partition_point(|w| w.start() < start + window);
+        let rate = speaking_rate_with(&words[first..first + count], &opts);
+        points.push((start + window / 2.0, rate));
+        i += 1;
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::utterance;
+
+    /// `count` words of `len` seconds with `pause` seconds between them.
+    fn evenly(count: usize, len: f32, pause: f32) -> Vec<RecognizedWord<'static>> {
+        let words: Vec<_> = (0..count)
+            .map(|i| {
+                let start = i as f32 * (len + pause);
+                ("w", start, start + len)
+            })
+            .collect();
+        utterance(&words).result.unwrap()
+    }
+
+    #[test]
+    fn steady_rate() {
+        // 0.4 s words with 0.1 s pauses, two words a second.
+        let words = evenly(10, 0.4, 0.1);
+        let rate = speaking_rate(&words);
+        assert!((rate - 10.0 * 60.0 / 4.9).abs() < 0.01);
+    }
+    #[test]
+    fn long_pause_excluded() {
+        let mut words = evenly(2, 0.5, 0.0);
+        words.extend(
+            utterance(&[("w", 11.0, 11.5), ("w", 11.5, 12.0)])
+                .result
+                .unwrap(),
+        );
+        assert!((speaking_rate(&words) - 120.0).abs() < 0.01);
+        let all_time = RateOptions { max_pause: None };
+        assert!((speaking_rate_with(&words, &all_time) - 20.0).abs() < 0.01);
+    }
+    #[test]
+    fn no_words() {
+        assert_eq!(speaking_rate(&[]), 0.0);
+        let instant = utterance(&[("w", 1.0, 1.0)]).result.unwrap();
+        assert_eq!(speaking_rate(&instant), 0.0);
+        let windows = speaking_rate_windows(&[], Duration::from_secs(1), Duration::from_secs(1));
+        assert!(windows.is_empty());
+    }
+    #[test]
+    fn windows() {
+        // Two words a second for 4 s, silence, then two more words.
+        let mut words = evenly(8, 0.5, 0.0);
+        words.extend(
+            utterance(&[("w", 7.0, 7.5), ("w", 7.5, 8.0)])
+                .result
+                .unwrap(),
+        );
+        let windows = speaking_rate_windows(&words, Duration::from_secs(2), Duration::from_secs(2));
+        assert_eq!(
+            windows,
+            vec![(1.0, 120.0), (3.0, 120.0), (5.0, 0.0), (7.0, 120.0)]
+        );
+    }
+    #[test]
+    fn overlapping_windows() {
+        let words = evenly(4, 0.5, 0.0);
+        let windows = speaking_rate_windows(&words, Duration::from_secs(2), Duration::from_secs(1));
+        let times: Vec<_> = windows.iter().map(|w| w.0).collect();
+        assert_eq!(times, vec![1.0, 2.0]);
+        assert_eq!(windows[1].1, 120.0);
+    }
+}
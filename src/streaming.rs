@@ -0,0 +1,525 @@
+//! Recognition for async code, on a thread of its own.
+//!
+//! Feeding audio to a recognizer blocks for tens of milliseconds, too long for an
+//! async task. A `RecognizerStream` runs the recognizer on a thread and yields
+//! the utterances as a `futures` `Stream`, while audio is pushed with an `AudioPusher`:
+//!
+//! ```no_run
+//! # use vosk::streaming::RecognizerStream;
+//! # use vosk::{Model, Recognizer};
+//! use futures::StreamExt;
+//! # async fn run() -> Result<(), vosk::Error> {
+//! # let model = Model::new("model")?;
+//! let (mut utterances, mut audio) = RecognizerStream::new(Recognizer::new(&model, 16000.0));
+//! audio.push_audio(vec![0; 16000]);
+//! audio.finish();
+//! while let Some(utterance) = utterances.next().await {
+//!     println!("{}", utterance?.text);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Audio coming from a `Stream` can be forwarded to a `RecognizerSink` instead,
+//! which holds up the source when recognition falls behind:
+//!
+//! ```no_run
+//! # use vosk::streaming::RecognizerSink;
+//! # use vosk::{Model, Recognizer};
+//! use futures::{stream, StreamExt, TryStreamExt};
+//! # async fn run() -> Result<(), vosk::Error> {
+//! # let model = Model::new("model")?;
+//! # let chunks = vec![vec![0i16; 1600]; 10];
+//! let (sink, utterances) = RecognizerSink::new(Recognizer::new(&model, 16000.0), 8);
+//! stream::iter(chunks).map(Ok).forward(sink).await?;
+//! let text: Vec<_> = utterances.map_ok(|u| u.text.into_owned()).try_collect().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{Error, Recognizer, UtteranceOwned};
+use futures_core::Stream;
+use futures_sink::Sink;
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+/// The utterances recognized from the audio of the paired `AudioPusher` or `RecognizerSink`,
+/// leaving out empty ones.
+///
+/// Ends once the last utterance is flushed after `AudioPusher::finish` or closing the sink.
+#[derive(Debug)]
+pub struct RecognizerStream {
+    shared: Arc<Mutex<Shared>>,
+}
+
+/// Sends audio to the thread of a `RecognizerStream`.
+///
+/// Dropping it has the same effect as `finish`.
+#[derive(Debug)]
+pub struct AudioPusher {
+    sender: Sender<Vec<i16>>,
+}
+
+/// What the recognition thread hands to the stream.
+#[derive(Debug, Default)]
+struct Shared {
+    results: VecDeque<Result<UtteranceOwned, Error>>,
+    done: bool,
+    /// The task waiting for a result.
+    waker: Option<Waker>,
+}
+
+impl RecognizerStream {
+    /// Moves `recognizer` to a new thread.
+    pub fn new(recognizer: Recognizer) -> (RecognizerStream, AudioPusher) {
+        RecognizerStream::spawn(recognizer)
+    }
+    fn spawn<D: Decode + Send + 'static>(decoder: D) -> (RecognizerStream, AudioPusher) {
+        let (sender, receiver) = channel();
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let publisher = Publisher {
+            shared: shared.clone(),
+        };
+        thread::spawn(move || recognize(decoder, receiver, |p, e| p.publish(Err(e)), publisher));
+        (RecognizerStream { shared }, AudioPusher { sender })
+    }
+}
+
+impl Stream for RecognizerStream {
+    type Item = Result<UtteranceOwned, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut shared = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(result) = shared.results.pop_front() {
+            return Poll::Ready(Some(result));
+        }
+        if shared.done {
+            return Poll::Ready(None);
+        }
+        // Registered while locked, so a result published meanwhile wakes this task.
+        shared.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl AudioPusher {
+    /// Queues samples for recognition, without waiting for them to be processed.
+    pub fn push_audio(&mut self, samples: Vec<i16>) {
+        // The thread only stops after the pusher is gone.
+        let _ = self.sender.send(samples);
+    }
+    /// Ends the audio: the last utterance is finalized, then the stream ends.
+    pub fn finish(self) {}
+}
+
+/// Feeds audio chunks to a recognizer on another thread, see the module documentation.
+///
+/// At most `capacity` chunks wait for recognition, `poll_ready` is pending when there are more.
+/// Closing the sink finalizes the last utterance and ends the paired `RecognizerStream`.
+/// Errors of the recognizer are returned by the next call to the sink, which then rejects
+/// further audio; the stream only yields utterances.
+#[derive(Debug)]
+pub struct RecognizerSink {
+    inbox: Arc<Inbox>,
+}
+
+/// The chunks waiting for the recognition thread of a sink.
+#[derive(Debug)]
+struct Inbox {
+    state: Mutex<InboxState>,
+    /// Notified when a chunk was added or the sink closed.
+    added: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct InboxState {
+    chunks: VecDeque<Vec<i16>>,
+    capacity: usize,
+    /// Whether the thread is feeding a chunk it took.
+    busy: bool,
+    closed: bool,
+    /// Whether the thread finished, after the final result.
+    stopped: bool,
+    error: Option<Error>,
+    /// The task waiting for room or for the thread.
+    waker: Option<Waker>,
+}
+
+impl RecognizerSink {
+    /// Moves `recognizer` to a new thread, with room for `capacity` chunks, at least one.
+    pub fn new(recognizer: Recognizer, capacity: usize) -> (RecognizerSink, RecognizerStream) {
+        RecognizerSink::spawn(recognizer, capacity)
+    }
+    fn spawn<D>(decoder: D, capacity: usize) -> (RecognizerSink, RecognizerStream)
+    where
+        D: Decode + Send + 'static,
+    {
+        let inbox = Arc::new(Inbox {
+            state: Mutex::new(InboxState {
+                capacity: capacity.max(1),
+                ..InboxState::default()
+            }),
+            added: Condvar::new(),
+        });
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let publisher = Publisher {
+            shared: shared.clone(),
+        };
+        let chunks = InboxChunks(inbox.clone());
+        let stopped = Stopped(inbox.clone());
+        thread::spawn(move || {
+            let _stopped = stopped;
+            let errors = chunks.0.clone();
+            let on_error = move |_: &Publisher, e| {
+                let mut state = errors.lock();
+                state.error.get_or_insert(e);
+                state.wake();
+            };
+            recognize(decoder, chunks, on_error, publisher)
+        });
+        (RecognizerSink { inbox }, RecognizerStream { shared })
+    }
+    /// Pending until `done` is true, failing with the error of the recognizer if there is one.
+    fn poll_until<F>(&self, cx: &mut Context<'_>, done: F) -> Poll<Result<(), Error>>
+    where
+        F: Fn(&InboxState) -> bool,
+    {
+        let mut state = self.inbox.lock();
+        if let Some(e) = state.error.clone() {
+            return Poll::Ready(Err(e));
+        }
+        if done(&state) {
+            return Poll::Ready(Ok(()));
+        }
+        if state.stopped {
+            return Poll::Ready(Err(Error::Io("the recognition thread stopped".to_string())));
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Sink<Vec<i16>> for RecognizerSink {
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        self.poll_until(cx, |state| state.chunks.len() < state.capacity)
+    }
+    fn start_send(self: Pin<&mut Self>, chunk: Vec<i16>) -> Result<(), Error> {
+        let mut state = self.inbox.lock();
+        if let Some(e) = state.error.clone() {
+            return Err(e);
+        }
+        state.chunks.push_back(chunk);
+        self.inbox.added.notify_one();
+        Ok(())
+    }
+    /// Ready once every chunk sent was fed to the recognizer.
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        self.poll_until(cx, |state| state.chunks.is_empty() && !state.busy)
+    }
+    /// Ready once the last utterance is finalized and available from the stream.
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        {
+            let mut state = self.inbox.lock();
+            state.closed = true;
+            self.inbox.added.notify_one();
+        }
+        self.poll_until(cx, |state| state.stopped)
+    }
+}
+
+impl Drop for RecognizerSink {
+    fn drop(&mut self) {
+        self.inbox.lock().closed = true;
+        self.inbox.added.notify_one();
+    }
+}
+
+impl Inbox {
+    fn lock(&self) -> MutexGuard<'_, InboxState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl InboxState {
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// The chunks of a sink as the recognition thread takes them, waiting for more
+/// until it's closed.
+struct InboxChunks(Arc<Inbox>);
+
+/// Tells the sink the recognition thread stopped when dropped, even if it panicked.
+struct Stopped(Arc<Inbox>);
+
+impl Iterator for InboxChunks {
+    type Item = Vec<i16>;
+
+    fn next(&mut self) -> Option<Vec<i16>> {
+        let mut state = self.0.lock();
+        // The previous chunk was fed.
+        state.busy = false;
+        state.wake();
+        loop {
+            if let Some(chunk) = state.chunks.pop_front() {
+                state.busy = true;
+                // There's room for another one.
+                state.wake();
+                return Some(chunk);
+            }
+            if state.closed {
+                return None;
+            }
+            state = self.0.added.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+impl Drop for Stopped {
+    fn drop(&mut self) {
+        let mut state = self.0.lock();
+        state.stopped = true;
+        state.wake();
+    }
+}
+
+/// Hands results to the stream, and ends it when dropped, even if recognition panics.
+struct Publisher {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Publisher {
+    fn publish(&self, result: Result<UtteranceOwned, Error>) {
+        if matches!(&result, Ok(utterance) if utterance.text.is_empty()) {
+            return;
+        }
+        let mut shared = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+        shared.results.push_back(result);
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Drop for Publisher {
+    fn drop(&mut self) {
+        let mut shared = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+        shared.done = true;
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+fn recognize<D, I, E>(mut decoder: D, audio: I, mut on_error: E, publisher: Publisher)
+where
+    D: Decode,
+    I: IntoIterator<Item = Vec<i16>>,
+    E: FnMut(&Publisher, Error),
+{
+    for samples in audio {
+        match decoder.accept(&samples) {
+            Ok(true) => publisher.publish(Ok(decoder.result())),
+            Ok(false) => {}
+            Err(e) => on_error(&publisher, e),
+        }
+    }
+    publisher.publish(Ok(decoder.final_result()));
+}
+
+/// What the recognition thread needs of a recognizer.
+pub(crate) trait Decode {
+    fn accept(&mut self, samples: &[i16]) -> Result<bool, Error>;
+    fn result(&mut self) -> UtteranceOwned;
+    fn final_result(&mut self) -> UtteranceOwned;
+}
+
+impl Decode for Recognizer {
+    fn accept(&mut self, samples: &[i16]) -> Result<bool, Error> {
+        self.try_accept_waveform(samples)
+    }
+    fn result(&mut self) -> UtteranceOwned {
+        Recognizer::result(self).into_owned()
+    }
+    fn final_result(&mut self) -> UtteranceOwned {
+        Recognizer::final_result(self).into_owned()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::RecognizedText;
+    use futures::executor::block_on;
+    use futures::{stream, SinkExt, StreamExt};
+    use std::time::Duration;
+
+    /// Completes an utterance whenever a chunk starts with a sample of 1,
+    /// its text being the number of chunks so far; fails on chunks starting with -1.
+    pub(crate) struct Script {
+        chunks: usize,
+    }
+
+    impl Script {
+        pub(crate) fn new() -> Script {
+            Script { chunks: 0 }
+        }
+        fn text(&self) -> UtteranceOwned {
+            RecognizedText::from_text(self.chunks.to_string())
+        }
+    }
+
+    impl Decode for Script {
+        fn accept(&mut self, samples: &[i16]) -> Result<bool, Error> {
+            self.chunks += 1;
+            match samples.first() {
+                Some(-1) => Err(Error::InputTooLong(samples.len())),
+                Some(1) => Ok(true),
+                _ => Ok(false),
+            }
+        }
+        fn result(&mut self) -> UtteranceOwned {
+            self.text()
+        }
+        fn final_result(&mut self) -> UtteranceOwned {
+            self.text()
+        }
+    }
+
+    fn texts(results: Vec<Result<UtteranceOwned, Error>>) -> Vec<Result<String, Error>> {
+        results
+            .into_iter()
+            .map(|r| r.map(|u| u.text.into_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn yields_utterances() {
+        let (stream, mut audio) = RecognizerStream::spawn(Script::new());
+        audio.push_audio(vec![0, 0]);
+        audio.push_audio(vec![1]);
+        audio.push_audio(vec![-1, 0]);
+        audio.push_audio(vec![0]);
+        audio.finish();
+        assert_eq!(
+            texts(block_on(stream.collect())),
+            vec![
+                Ok("2".to_string()),
+                Err(Error::InputTooLong(2)),
+                Ok("4".to_string())
+            ]
+        );
+    }
+    #[test]
+    fn wakes_on_results() {
+        let (mut stream, mut audio) = RecognizerStream::spawn(Script::new());
+        let feeder = thread::spawn(move || {
+            for chunk in [vec![0], vec![1], vec![0], vec![1]] {
+                thread::sleep(Duration::from_millis(20));
+                audio.push_audio(chunk);
+            }
+            // Dropped without finish, which also ends the stream.
+        });
+        assert_eq!(block_on(stream.next()).unwrap().unwrap().text, "2");
+        assert_eq!(block_on(stream.next()).unwrap().unwrap().text, "4");
+        assert_eq!(block_on(stream.next()).unwrap().unwrap().text, "4");
+        assert!(block_on(stream.next()).is_none());
+        feeder.join().unwrap();
+    }
+    #[test]
+    fn forward_chunks() {
+        let (sink, stream) = RecognizerSink::spawn(Script::new(), 2);
+        let chunks = vec![vec![0], vec![1], vec![0], vec![0], vec![1], vec![0]];
+        let forwarded = block_on(stream::iter(chunks).map(Ok).forward(sink));
+        assert_eq!(forwarded, Ok(()));
+        // Closing the sink flushed the last utterance.
+        assert_eq!(
+            texts(block_on(stream.collect())),
+            vec![
+                Ok("2".to_string()),
+                Ok("5".to_string()),
+                Ok("6".to_string())
+            ]
+        );
+    }
+    #[test]
+    fn backpressure() {
+        /// Blocks on every chunk until told to go on.
+        struct Gated(std::sync::mpsc::Receiver<()>);
+        impl Decode for Gated {
+            fn accept(&mut self, _: &[i16]) -> Result<bool, Error> {
+                self.0.recv().unwrap();
+                Ok(false)
+            }
+            fn result(&mut self) -> UtteranceOwned {
+                RecognizedText::from_text("")
+            }
+            fn final_result(&mut self) -> UtteranceOwned {
+                RecognizedText::from_text("done")
+            }
+        }
+        let (go, gate) = channel();
+        let (mut sink, stream) = RecognizerSink::spawn(Gated(gate), 2);
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut sent = 0;
+        while let Poll::Ready(ready) = Pin::new(&mut sink).poll_ready(&mut cx) {
+            ready.unwrap();
+            Pin::new(&mut sink).start_send(vec![0]).unwrap();
+            sent += 1;
+            assert!(sent <= 3, "the capacity is exceeded");
+        }
+        // One being fed, two waiting.
+        assert!(sent >= 2);
+        assert!(Pin::new(&mut sink).poll_flush(&mut cx).is_pending());
+        for _ in 0..sent {
+            go.send(()).unwrap();
+        }
+        block_on(sink.close()).unwrap();
+        assert_eq!(
+            texts(block_on(stream.collect())),
+            vec![Ok("done".to_string())]
+        );
+    }
+    #[test]
+    fn errors_reach_the_sink() {
+        let (mut sink, stream) = RecognizerSink::spawn(Script::new(), 4);
+        block_on(sink.send(vec![0])).unwrap();
+        // Sending flushes, so it waits for the chunk to be fed.
+        assert_eq!(
+            block_on(sink.send(vec![-1, 0, 0])),
+            Err(Error::InputTooLong(3))
+        );
+        assert_eq!(block_on(sink.send(vec![1])), Err(Error::InputTooLong(3)));
+        drop(sink);
+        assert_eq!(texts(block_on(stream.collect())), vec![Ok("2".to_string())]);
+    }
+    #[test]
+    fn empty_utterances_left_out() {
+        struct Silent;
+        impl Decode for Silent {
+            fn accept(&mut self, _: &[i16]) -> Result<bool, Error> {
+                Ok(true)
+            }
+            fn result(&mut self) -> UtteranceOwned {
+                RecognizedText::from_text("")
+            }
+            fn final_result(&mut self) -> UtteranceOwned {
+                RecognizedText::from_text("")
+            }
+        }
+        let (stream, mut audio) = RecognizerStream::spawn(Silent);
+        audio.push_audio(vec![0]);
+        drop(audio);
+        assert!(block_on(stream.collect::<Vec<_>>()).is_empty());
+    }
+}
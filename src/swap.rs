@@ -0,0 +1,686 @@
+//! Switching the model of a live recognizer, such as to another language,
+//! without losing what is said meanwhile.
+//!
+//! Creating a recognizer for a large model can take seconds. A `SwappableRecognizer`
+//! finalizes the utterance in progress and creates the new recognizer on another
+//! thread, buffering the audio fed in the meantime. Once the new recognizer is ready,
+//! the buffer is fed to it and `Event::Swapped` tells how much audio that was.
+//! If the recognizer can't be created, the old one gets the buffer instead and
+//! `Event::SwapFailed` tells why.
+
+use crate::partial::PartialTracker;
+use crate::{duration_of, Error, Event, Model, Recognizer, UtteranceOwned};
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+/// Which audio to lose when more arrives during a swap than the buffer holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferOverflow {
+    /// Keep the latest audio, losing the start of the swap.
+    DropOldest,
+    /// Keep the audio right after the swap started, losing the latest.
+    DropNewest,
+}
+
+/// How a `SwappableRecognizer` buffers audio during a swap.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapOptions {
+    /// The most audio kept while the new recognizer is created.
+    pub max_buffered: Duration,
+    pub overflow: BufferOverflow,
+    /// Audio fed to the new recognizer at a time when it catches up with the buffer.
+    pub chunk: Duration,
+}
+
+impl Default for SwapOptions {
+    fn default() -> Self {
+        SwapOptions {
+            max_buffered: Duration::from_secs(30),
+            overflow: BufferOverflow::DropOldest,
+            chunk: Duration::from_millis(100),
+        }
+    }
+}
+
+/// Where a `SwappableRecognizer` is in swapping models.
+///
+/// A swap goes from `Running` to `Draining` to `Swapping` and back to `Running`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapState {
+    /// Audio goes straight to the recognizer.
+    Running,
+    /// The utterance in progress is being finalized; audio is buffered.
+    Draining,
+    /// The recognizer for the new model is being created; audio is buffered.
+    Swapping,
+}
+
+/// A recognizer whose model can be replaced while audio keeps coming.
+///
+/// Dropping it during a swap doesn't wait for the swap; the thread frees
+/// the recognizers when it's done.
+pub struct SwappableRecognizer {
+    swapper: Swapper<Recognizer>,
+}
+
+impl SwappableRecognizer {
+    pub fn new(recognizer: Recognizer) -> Self {
+        Self::with_options(recognizer, SwapOptions::default())
+    }
+    pub fn with_options(recognizer: Recognizer, opts: SwapOptions) -> Self {
+        SwappableRecognizer {
+            swapper: Swapper::new(recognizer, opts),
+        }
+    }
+    /// Starts switching to `model`, returning right away.
+    ///
+    /// The utterance in progress is finalized, as an `Event::Final` unless empty.
+    /// The new recognizer has the same sample rate and settings, but no grammar.
+    /// Swapping again before a swap is done replaces its model; only the
+    /// recognizer for the latest one is used, and only its failure is reported.
+    pub fn swap_model(&mut self, model: Model) {
+        self.swapper.swap_model(model)
+    }
+    /// Feeds audio to the recognizer, or to the buffer during a swap.
+    pub fn feed(&mut self, samples: &[i16]) {
+        self.swapper.feed(samples)
+    }
+    /// The next event of the audio fed so far, without blocking.
+    pub fn next_event(&mut self) -> Option<Event> {
+        self.swapper.next_event()
+    }
+    pub fn state(&mut self) -> SwapState {
+        self.swapper.poll();
+        self.swapper.state
+    }
+    /// Blocks until an ongoing swap is done and its buffer was fed to the new recognizer.
+    pub fn wait_swapped(&mut self) {
+        self.swapper.wait()
+    }
+    /// Finalizes the utterance in progress, waiting for an ongoing swap first.
+    ///
+    /// Events of the buffered audio stay available from `next_event`.
+    pub fn finish(&mut self) -> UtteranceOwned {
+        self.swapper.finish()
+    }
+    /// The recognizer, unless it's being swapped.
+    pub fn recognizer(&self) -> Option<&Recognizer> {
+        self.swapper.current.as_ref()
+    }
+    pub fn recognizer_mut(&mut self) -> Option<&mut Recognizer> {
+        self.swapper.current.as_mut()
+    }
+}
+
+impl fmt::Debug for SwappableRecognizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SwappableRecognizer")
+            .field("recognizer", &self.swapper.current)
+            .field("state", &self.swapper.state)
+            .field("buffered", &self.swapper.buffer.len())
+            .finish()
+    }
+}
+
+/// What swapping needs of a recognizer, so that it can be tested without a model.
+trait Backend: Sized + Send + 'static {
+    type Model: Send + 'static;
+    /// Creates a recognizer for `model` like `old`, on the swapping thread.
+    fn replace(old: &Self, model: Self::Model) -> Result<Self, Error>;
+    fn sample_rate(&self) -> f32;
+    fn accept(&mut self, samples: &[i16]) -> bool;
+    fn result(&mut self) -> UtteranceOwned;
+    fn partial(&mut self) -> String;
+    fn final_result(&mut self) -> UtteranceOwned;
+}
+
+impl Backend for Recognizer {
+    type Model = Model;
+
+    fn replace(old: &Recognizer, model: Model) -> Result<Recognizer, Error> {
+        let mut recognizer = Recognizer::try_new(&model, old.sample_rate())?;
+        recognizer.copy_settings_from(old);
+        Ok(recognizer)
+    }
+    fn sample_rate(&self) -> f32 {
+        Recognizer::sample_rate(self)
+    }
+    fn accept(&mut self, samples: &[i16]) -> bool {
+        self.accept_waveform(samples)
+    }
+    fn result(&mut self) -> UtteranceOwned {
+        Recognizer::result(self).into_owned()
+    }
+    fn partial(&mut self) -> String {
+        self.partial_result().partial.into_owned()
+    }
+    fn final_result(&mut self) -> UtteranceOwned {
+        Recognizer::final_result(self).into_owned()
+    }
+}
+
+/// What the swapping thread sends back, in this order.
+enum Progress<B> {
+    Finalized(UtteranceOwned),
+    Ready(B),
+    /// The old recognizer, finalized, with why the new one couldn't be created.
+    Failed(B, Error),
+}
+
+struct Swapper<B: Backend> {
+    opts: SwapOptions,
+    sample_rate: f32,
+    /// None while the swapping thread has it.
+    current: Option<B>,
+    progress: Option<Receiver<Progress<B>>>,
+    state: SwapState,
+    /// The model of a swap requested during another.
+    pending: Option<B::Model>,
+    buffer: VecDeque<i16>,
+    /// Samples lost to overflow during the ongoing swap.
+    dropped: usize,
+    partials: PartialTracker,
+    events: VecDeque<Event>,
+}
+
+impl<B: Backend> Swapper<B> {
+    fn new(backend: B, opts: SwapOptions) -> Self {
+        Swapper {
+            opts,
+            sample_rate: backend.sample_rate(),
+            current: Some(backend),
+            progress: None,
+            state: SwapState::Running,
+            pending: None,
+            buffer: VecDeque::new(),
+            dropped: 0,
+            partials: PartialTracker::default(),
+            events: VecDeque::new(),
+        }
+    }
+    fn swap_model(&mut self, model: B::Model) {
+        self.poll();
+        match self.current.take() {
+            Some(old) => self.start(old, model),
+            None => self.pending = Some(model),
+        }
+    }
+    fn start(&mut self, old: B, model: B::Model) {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut old = old;
+            let last = old.final_result();
+            // Nobody is waiting if the swapper was dropped.
+            if sender.send(Progress::Finalized(last)).is_err() {
+                return;
+            }
+            let progress = match B::replace(&old, model) {
+                Ok(new) => {
+                    drop(old);
+                    Progress::Ready(new)
+                }
+                Err(error) => Progress::Failed(old, error),
+            };
+            let _ = sender.send(progress);
+        });
+        self.progress = Some(receiver);
+        self.state = SwapState::Draining;
+        self.partials.reset();
+    }
+    fn feed(&mut self, samples: &[i16]) {
+        // A recognizer that just became ready gets the buffer before this.
+        self.poll();
+        if self.current.is_some() {
+            self.accept(samples);
+        } else {
+            self.buffer(samples);
+        }
+    }
+    fn next_event(&mut self) -> Option<Event> {
+        self.poll();
+        self.events.pop_front()
+    }
+    fn poll(&mut self) {
+        self.advance(false)
+    }
+    fn wait(&mut self) {
+        self.advance(true)
+    }
+    fn finish(&mut self) -> UtteranceOwned {
+        self.wait();
+        self.partials.reset();
+        match &mut self.current {
+            Some(current) => current.final_result(),
+            None => unreachable!("no recognizer after a swap"),
+        }
+    }
+    /// Handles what the swapping thread sent, waiting for the swap to end if `block`.
+    fn advance(&mut self, block: bool) {
+        while let Some(progress) = &self.progress {
+            let received = if block {
+                progress.recv().map_err(|_| TryRecvError::Disconnected)
+            } else {
+                progress.try_recv()
+            };
+            match received {
+                Ok(Progress::Finalized(last)) => {
+                    if !last.text.is_empty() {
+                        self.events.push_back(Event::Final(last));
+                    }
+                    self.state = SwapState::Swapping;
+                }
+                Ok(Progress::Ready(new)) => match self.pending.take() {
+                    // It hasn't heard anything, its finalization is instant.
+                    Some(model) => self.start(new, model),
+                    None => self.resume(new, None),
+                },
+                Ok(Progress::Failed(old, error)) => match self.pending.take() {
+                    Some(model) => self.start(old, model),
+                    None => self.resume(old, Some(error)),
+                },
+                Err(TryRecvError::Empty) => return,
+                // Only a panic in libvosk ends the thread early.
+                Err(TryRecvError::Disconnected) => panic!("the swapping thread panicked"),
+            }
+        }
+    }
+    /// Ends the swap with `backend`, the new recognizer or the old one if `failed`,
+    /// and feeds it the buffer.
+    fn resume(&mut self, backend: B, failed: Option<Error>) {
+        self.progress = None;
+        self.current = Some(backend);
+        self.state = SwapState::Running;
+        let buffered = duration_of(self.buffer.len() as u64, self.sample_rate);
+        let dropped = duration_of(self.dropped as u64, self.sample_rate);
+        self.events.push_back(match failed {
+            None => Event::Swapped { buffered, dropped },
+            Some(error) => Event::SwapFailed {
+                error,
+                buffered,
+                dropped,
+            },
+        });
+        self.dropped = 0;
+        let chunk = samples_in(self.opts.chunk, self.sample_rate).max(1);
+        let buffer: Vec<i16> = self.buffer.drain(..).collect();
+        for samples in buffer.chunks(chunk) {
+            self.accept(samples);
+        }
+    }
+    fn accept(&mut self, samples: &[i16]) {
+        let Some(current) = &mut self.current else {
+            return;
+        };
+        if current.accept(samples) {
+            self.partials.reset();
+            self.events.push_back(Event::Final(current.result()));
+        } else {
+            let partial = current.partial();
+            if let Some(text) = self.partials.changed(&partial) {
+                self.events.push_back(Event::Partial(text.to_string()));
+            }
+        }
+    }
+    fn buffer(&mut self, samples: &[i16]) {
+        let max = samples_in(self.opts.max_buffered, self.sample_rate);
+        match self.opts.overflow {
+            BufferOverflow::DropOldest => {
+                self.buffer.extend(samples);
+                let excess = self.buffer.len().saturating_sub(max);
+                self.buffer.drain(..excess);
+                self.dropped += excess;
+            }
+            BufferOverflow::DropNewest => {
+                let room = max.saturating_sub(self.buffer.len()).min(samples.len());
+                self.buffer.extend(&samples[..room]);
+                self.dropped += samples.len() - room;
+            }
+        }
+    }
+}
+
+fn samples_in(duration: Duration, sample_rate: f32) -> usize {
+    (duration.as_secs_f64() * sample_rate as f64).round() as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RecognizedText;
+    use std::sync::mpsc::Sender;
+
+    /// Completes an utterance when a chunk starts with a sample of 1, its text
+    /// being the model and the number of samples heard. The partial result is
+    /// the model once anything was heard.
+    #[derive(Debug)]
+    struct Mock {
+        model: &'static str,
+        heard: usize,
+        /// Told the model when dropped.
+        dropped: Option<Sender<&'static str>>,
+    }
+
+    struct MockModel {
+        name: &'static str,
+        /// Creating a recognizer waits for it, if set.
+        gate: Option<Receiver<()>>,
+        /// Creating a recognizer fails with it, if set.
+        error: Option<Error>,
+        dropped: Option<Sender<&'static str>>,
+    }
+
+    fn model(name: &'static str) -> MockModel {
+        MockModel {
+            name,
+            gate: None,
+            error: None,
+            dropped: None,
+        }
+    }
+
+    /// A model whose recognizer can't be created.
+    fn broken(name: &'static str) -> MockModel {
+        MockModel {
+            error: Some(Error::NoValidModel),
+            ..model(name)
+        }
+    }
+
+    /// A model whose recognizer is only created once the sender is used or dropped.
+    fn gated(name: &'static str) -> (MockModel, Sender<()>) {
+        let (open, gate) = mpsc::channel();
+        let model = MockModel {
+            gate: Some(gate),
+            ..model(name)
+        };
+        (model, open)
+    }
+
+    impl Mock {
+        fn new(model: &'static str) -> Mock {
+            Mock {
+                model,
+                heard: 0,
+                dropped: None,
+            }
+        }
+        fn take(&mut self) -> UtteranceOwned {
+            if self.heard == 0 {
+                return RecognizedText::from_text("");
+            }
+            let text = format!("{}:{}", self.model, self.heard);
+            self.heard = 0;
+            RecognizedText::from_text(text)
+        }
+    }
+
+    impl Drop for Mock {
+        fn drop(&mut self) {
+            if let Some(dropped) = &self.dropped {
+                let _ = dropped.send(self.model);
+            }
+        }
+    }
+
+    impl Backend for Mock {
+        type Model = MockModel;
+
+        fn replace(_old: &Mock, model: MockModel) -> Result<Mock, Error> {
+            if let Some(gate) = &model.gate {
+                let _ = gate.recv();
+            }
+            if let Some(error) = model.error {
+                return Err(error);
+            }
+            Ok(Mock {
+                dropped: model.dropped,
+                ..Mock::new(model.name)
+            })
+        }
+        fn sample_rate(&self) -> f32 {
+            10.0
+        }
+        fn accept(&mut self, samples: &[i16]) -> bool {
+            self.heard += samples.len();
+            samples.first() == Some(&1)
+        }
+        fn result(&mut self) -> UtteranceOwned {
+            self.take()
+        }
+        fn partial(&mut self) -> String {
+            match self.heard {
+                0 => String::new(),
+                _ => self.model.to_string(),
+            }
+        }
+        fn final_result(&mut self) -> UtteranceOwned {
+            self.take()
+        }
+    }
+
+    /// Options feeding the buffer in chunks of ten samples.
+    fn opts() -> SwapOptions {
+        SwapOptions {
+            chunk: Duration::from_secs(1),
+            ..SwapOptions::default()
+        }
+    }
+
+    fn swapper(opts: SwapOptions) -> Swapper<Mock> {
+        Swapper::new(Mock::new("en"), opts)
+    }
+
+    fn events(swapper: &mut Swapper<Mock>) -> Vec<String> {
+        std::iter::from_fn(|| swapper.next_event())
+            .map(|event| match event {
+                Event::Partial(text) => format!("partial {}", text),
+                Event::Final(utterance) => format!("final {}", utterance.text),
+                Event::Swapped { buffered, dropped } => format!(
+                    "swapped {} {}",
+                    buffered.as_secs_f32(),
+                    dropped.as_secs_f32()
+                ),
+                Event::SwapFailed {
+                    error,
+                    buffered,
+                    dropped,
+                } => format!(
+                    "failed {:?} {} {}",
+                    error,
+                    buffered.as_secs_f32(),
+                    dropped.as_secs_f32()
+                ),
+                other => format!("{:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn swap_with_buffered_audio() {
+        let mut swapper = swapper(opts());
+        swapper.feed(&[0; 5]);
+        let (de, open) = gated("de");
+        swapper.swap_model(de);
+        assert_eq!(swapper.state, SwapState::Draining);
+        swapper.feed(&[0; 10]);
+        swapper.feed(&[1; 5]);
+        open.send(()).unwrap();
+        swapper.wait();
+        assert_eq!(swapper.state, SwapState::Running);
+        assert_eq!(
+            events(&mut swapper),
+            [
+                "partial en",
+                "final en:5",
+                "swapped 1.5 0",
+                "partial de",
+                "final de:15",
+            ]
+        );
+        swapper.feed(&[0; 3]);
+        assert_eq!(swapper.finish().text, "de:3");
+    }
+    #[test]
+    fn states_in_order() {
+        let mut swapper = swapper(opts());
+        assert_eq!(swapper.state, SwapState::Running);
+        let (de, open) = gated("de");
+        swapper.swap_model(de);
+        assert_eq!(swapper.state, SwapState::Draining);
+        // Finalizing comes before creating, which waits for the gate.
+        while swapper.state == SwapState::Draining {
+            swapper.poll();
+            thread::yield_now();
+        }
+        assert_eq!(swapper.state, SwapState::Swapping);
+        assert!(swapper.current.is_none());
+        drop(open);
+        swapper.wait();
+        assert_eq!(swapper.state, SwapState::Running);
+        assert_eq!(swapper.current.as_ref().unwrap().model, "de");
+    }
+    #[test]
+    fn overflow_drops_oldest() {
+        let opts = SwapOptions {
+            max_buffered: Duration::from_secs(1),
+            ..opts()
+        };
+        let mut swapper = swapper(opts);
+        let (de, open) = gated("de");
+        swapper.swap_model(de);
+        swapper.feed(&[1; 8]);
+        swapper.feed(&[0; 7]);
+        assert_eq!(swapper.buffer.len(), 10);
+        assert_eq!(swapper.buffer.front(), Some(&1));
+        drop(open);
+        swapper.wait();
+        // The buffer now starts with 1, which completes the utterance.
+        assert_eq!(events(&mut swapper), ["swapped 1 0.5", "final de:10"]);
+    }
+    #[test]
+    fn overflow_drops_newest() {
+        let opts = SwapOptions {
+            max_buffered: Duration::from_secs(1),
+            overflow: BufferOverflow::DropNewest,
+            chunk: Duration::from_secs(1),
+        };
+        let mut swapper = swapper(opts);
+        let (de, open) = gated("de");
+        swapper.swap_model(de);
+        swapper.feed(&[0; 8]);
+        swapper.feed(&[1; 7]);
+        drop(open);
+        swapper.wait();
+        assert_eq!(swapper.buffer.len(), 0);
+        assert_eq!(events(&mut swapper), ["swapped 1 0.5", "partial de"]);
+        assert_eq!(swapper.finish().text, "de:10");
+    }
+    #[test]
+    fn swap_during_swap() {
+        let mut swapper = swapper(opts());
+        let (de, open_de) = gated("de");
+        swapper.swap_model(de);
+        swapper.feed(&[0; 4]);
+        let (dropped, gone) = mpsc::channel();
+        let fr = MockModel {
+            dropped: Some(dropped),
+            ..model("fr")
+        };
+        swapper.swap_model(fr);
+        swapper.feed(&[0; 6]);
+        drop(open_de);
+        swapper.wait();
+        assert_eq!(swapper.current.as_ref().unwrap().model, "fr");
+        // All the audio went to the latest model, with a single swap reported.
+        assert_eq!(events(&mut swapper), ["swapped 1 0", "partial fr"]);
+        assert_eq!(swapper.finish().text, "fr:10");
+        drop(swapper);
+        assert_eq!(gone.recv(), Ok("fr"));
+    }
+    #[test]
+    fn swap_again_after_swap() {
+        let mut swapper = swapper(opts());
+        swapper.swap_model(model("de"));
+        swapper.wait();
+        swapper.feed(&[0; 2]);
+        swapper.swap_model(model("fr"));
+        swapper.wait();
+        assert_eq!(
+            events(&mut swapper),
+            ["swapped 0 0", "partial de", "final de:2", "swapped 0 0"]
+        );
+    }
+    #[test]
+    fn drop_during_swap() {
+        let mut swapper = swapper(opts());
+        let (dropped, gone) = mpsc::channel();
+        let (de, open) = gated("de");
+        let de = MockModel {
+            dropped: Some(dropped),
+            ..de
+        };
+        swapper.swap_model(de);
+        while swapper.state == SwapState::Draining {
+            swapper.poll();
+            thread::yield_now();
+        }
+        swapper.feed(&[0; 10]);
+        drop(swapper);
+        // The thread finishes creating the recognizer, then frees it.
+        open.send(()).unwrap();
+        assert_eq!(gone.recv(), Ok("de"));
+    }
+    #[test]
+    fn failed_swap_keeps_old_recognizer() {
+        let mut swapper = swapper(opts());
+        swapper.feed(&[0; 2]);
+        swapper.swap_model(broken("de"));
+        swapper.feed(&[0; 5]);
+        swapper.wait();
+        assert_eq!(swapper.state, SwapState::Running);
+        assert_eq!(swapper.current.as_ref().unwrap().model, "en");
+        // The old recognizer was finalized, then heard the buffer.
+        assert_eq!(
+            events(&mut swapper),
+            [
+                "partial en",
+                "final en:2",
+                "failed NoValidModel 0.5 0",
+                "partial en"
+            ]
+        );
+        assert_eq!(swapper.finish().text, "en:5");
+        swapper.swap_model(model("fr"));
+        swapper.wait();
+        assert_eq!(swapper.current.as_ref().unwrap().model, "fr");
+    }
+    #[test]
+    fn failed_swap_replaced_by_another() {
+        let mut swapper = swapper(opts());
+        let (de, open) = gated("de");
+        swapper.swap_model(MockModel {
+            error: Some(Error::NoValidModel),
+            ..de
+        });
+        swapper.swap_model(model("fr"));
+        swapper.feed(&[0; 3]);
+        drop(open);
+        swapper.wait();
+        // Only the swap that took place is reported.
+        assert_eq!(events(&mut swapper), ["swapped 0.3 0", "partial fr"]);
+    }
+    #[test]
+    fn finish_waits_for_swap() {
+        let mut swapper = swapper(opts());
+        let (de, open) = gated("de");
+        swapper.swap_model(de);
+        swapper.feed(&[0; 3]);
+        let opener = thread::spawn(move || open.send(()).unwrap());
+        assert_eq!(swapper.finish().text, "de:3");
+        opener.join().unwrap();
+        assert_eq!(events(&mut swapper), ["swapped 0.3 0", "partial de"]);
+    }
+}
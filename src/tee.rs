@@ -0,0 +1,647 @@
+//! Saving the audio fed to a recognizer, to attach to bug reports about recognition.
+//!
+//! An `AudioTee` writes chunks of audio to WAV files on a thread of its own, so
+//! feeding the recognizer doesn't wait for the disk. Alongside the files, an index
+//! records where each chunk went, so that the audio of an utterance can be cut out
+//! later with `extract`, from the times of its words.
+//!
+//! ```no_run
+//! # use vosk::tee::{AudioTee, TeeOptions, TeeSource};
+//! # use vosk::source::{transcribe_source, MemorySource};
+//! # fn main() -> Result<(), vosk::Error> {
+//! # let model = vosk::Model::new("model")?;
+//! # let mut recognizer = vosk::Recognizer::new(&model, 16000.0);
+//! # let source = MemorySource::new(vec![0; 16000], 16000);
+//! // Writes /tmp/session-0001.wav and so on, with the index in /tmp/session.chunks.
+//! let tee = AudioTee::create("/tmp/session", 16000, TeeOptions::default())?;
+//! let utterances = transcribe_source(&mut recognizer, TeeSource::new(source, tee))?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::source::AudioSource;
+use crate::Error;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Size of the header of the WAV files written.
+const HEADER_BYTES: u64 = 44;
+
+/// First line of the index, naming its columns: the position of the chunk in the
+/// stream in samples, its length, the number of its file, and where in that file
+/// it starts, in samples.
+const INDEX_HEADER: &str = "start\tsamples\tfile\toffset";
+
+/// How an `AudioTee` splits audio into files, and how much it keeps waiting.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TeeOptions {
+    /// Starts a new file before one would get larger, if it already has audio.
+    pub max_file_bytes: Option<u64>,
+    /// Starts a new file before one would get longer, if it already has audio.
+    pub max_file_duration: Option<Duration>,
+    /// Chunks waiting to be written. While the disk is too slow for that,
+    /// further chunks are dropped rather than holding up recognition.
+    pub queue: usize,
+}
+
+impl Default for TeeOptions {
+    /// One file, any size, and up to 256 chunks waiting.
+    fn default() -> Self {
+        TeeOptions {
+            max_file_bytes: None,
+            max_file_duration: None,
+            queue: 256,
+        }
+    }
+}
+
+/// Writes audio to WAV files in the background, see the module documentation.
+///
+/// Writing never fails or blocks: once a write fails, such as when the disk is full,
+/// the tee stops writing and keeps the error for `error` and `flush` to return,
+/// while recognition carries on. The headers of the files are updated by `flush`,
+/// when a file is full, and when the tee is dropped.
+pub struct AudioTee {
+    sender: Option<SyncSender<Message>>,
+    writer: Option<JoinHandle<()>>,
+    status: Arc<Mutex<Status>>,
+    sample_rate: u32,
+    /// Samples passed to `write`, dropped ones included.
+    position: u64,
+    dropped: u64,
+}
+
+enum Message {
+    Chunk { start: u64, samples: Vec<i16> },
+    Flush(mpsc::Sender<()>),
+}
+
+#[derive(Debug, Default)]
+struct Status {
+    error: Option<Error>,
+    files: Vec<PathBuf>,
+}
+
+impl AudioTee {
+    /// Writes to `prefix-0001.wav`, `prefix-0002.wav` and so on, with the index
+    /// in `prefix.chunks`. Fails if the first file or the index can't be created.
+    pub fn create<P: AsRef<Path>>(
+        prefix: P,
+        sample_rate: u32,
+        opts: TeeOptions,
+    ) -> Result<AudioTee, Error> {
+        let prefix = prefix.as_ref();
+        let status = Arc::new(Mutex::new(Status::default()));
+        let index = File::create(with_suffix(prefix, ".chunks"))?;
+        let mut writer = Writer {
+            prefix: prefix.to_path_buf(),
+            sample_rate,
+            max_samples: max_samples(&opts, sample_rate),
+            file: None,
+            files: 0,
+            file_samples: 0,
+            index: BufWriter::new(index),
+            status: status.clone(),
+            failed: false,
+        };
+        writeln!(writer.index, "{}", INDEX_HEADER)?;
+        writer.open_next()?;
+        let (sender, receiver) = mpsc::sync_channel(opts.queue.max(1));
+        let writer = thread::Builder::new()
+            .name("vosk-audio-tee".to_string())
+            .spawn(move || writer.run(receiver))?;
+        Ok(AudioTee {
+            sender: Some(sender),
+            writer: Some(writer),
+            status,
+            sample_rate,
+            position: 0,
+            dropped: 0,
+        })
+    }
+    /// Queues `samples` to be written, without waiting.
+    pub fn write(&mut self, samples: &[i16]) {
+        if samples.is_empty() {
+            return;
+        }
+        let start = self.position;
+        self.position += samples.len() as u64;
+        let chunk = Message::Chunk {
+            start,
+            samples: samples.to_vec(),
+        };
+        match self.sender.as_ref().map(|s| s.try_send(chunk)) {
+            Some(Ok(())) => {}
+            Some(Err(TrySendError::Full(_))) => {
+                self.dropped += 1;
+                #[cfg(feature = "tracing")]
+                tracing::warn!(start, "audio tee falling behind, chunk dropped");
+            }
+            Some(Err(TrySendError::Disconnected(_))) | None => self.dropped += 1,
+        }
+    }
+    /// Waits until the queued audio is written and the headers describe it,
+    /// failing with the first error writing met.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        let (ack, done) = mpsc::channel();
+        if let Some(sender) = &self.sender {
+            if sender.send(Message::Flush(ack)).is_ok() {
+                let _ = done.recv();
+            }
+        }
+        match self.error() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+    /// Writes the rest of the audio and closes the files, returning their paths.
+    pub fn finish(mut self) -> Result<Vec<PathBuf>, Error> {
+        self.close();
+        let status = self.status.lock().unwrap();
+        match &status.error {
+            Some(e) => Err(e.clone()),
+            None => Ok(status.files.clone()),
+        }
+    }
+    /// The first error writing met, after which nothing more was written.
+    pub fn error(&self) -> Option<Error> {
+        self.status.lock().unwrap().error.clone()
+    }
+    /// Chunks dropped because the writer was behind or had stopped.
+    pub fn dropped_chunks(&self) -> u64 {
+        self.dropped
+    }
+    /// The files created so far, the one being written last.
+    pub fn files(&self) -> Vec<PathBuf> {
+        self.status.lock().unwrap().files.clone()
+    }
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+    /// Duration of the audio passed to `write`, dropped chunks included,
+    /// so that it matches the times of a recognizer fed the same audio.
+    pub fn duration(&self) -> Duration {
+        crate::duration_of(self.position, self.sample_rate as f32)
+    }
+    fn close(&mut self) {
+        drop(self.sender.take());
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+impl Drop for AudioTee {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl fmt::Debug for AudioTee {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AudioTee")
+            .field("sample_rate", &self.sample_rate)
+            .field("position", &self.position)
+            .field("dropped", &self.dropped)
+            .field("status", &self.status.lock().unwrap())
+            .finish()
+    }
+}
+
+/// An audio source whose audio is also written to an `AudioTee`.
+#[derive(Debug)]
+pub struct TeeSource<S> {
+    inner: S,
+    tee: AudioTee,
+}
+
+impl<S> TeeSource<S> {
+    pub fn new(inner: S, tee: AudioTee) -> Self {
+        TeeSource { inner, tee }
+    }
+    pub fn tee(&self) -> &AudioTee {
+        &self.tee
+    }
+    pub fn into_inner(self) -> (S, AudioTee) {
+        (self.inner, self.tee)
+    }
+}
+
+impl<S: AudioSource> AudioSource for TeeSource<S> {
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+    fn read(&mut self, buf: &mut [i16]) -> Result<usize, Error> {
+        let n = self.inner.read(buf)?;
+        self.tee.write(&buf[..n]);
+        Ok(n)
+    }
+    fn total_samples(&self) -> Option<u64> {
+        self.inner.total_samples()
+    }
+    fn bytes_read(&self) -> Option<u64> {
+        self.inner.bytes_read()
+    }
+}
+
+/// Reads the audio from `start` to `end` back from the files of a tee with `prefix`,
+/// such as from the first to the last word of an utterance.
+///
+/// Times are from the start of the stream. Audio that wasn't written, such as
+/// dropped chunks, is silence; the result ends early if `end` is past the audio.
+pub fn extract<P: AsRef<Path>>(
+    prefix: P,
+    start: Duration,
+    end: Duration,
+) -> Result<Vec<i16>, Error> {
+    let prefix = prefix.as_ref();
+    let index = BufReader::new(File::open(with_suffix(prefix, ".chunks"))?);
+    let mut lines = index.lines();
+    if lines.next().transpose()?.as_deref() != Some(INDEX_HEADER) {
+        return Err(Error::CorruptFile("not an audio tee index".to_string()));
+    }
+    let chunks = lines
+        .map(|line| parse_chunk(&line?))
+        .collect::<Result<Vec<_>, Error>>()?;
+    let first = match chunks.first() {
+        Some(chunk) => chunk,
+        None => return Ok(Vec::new()),
+    };
+    let mut files: HashMap<u32, File> = HashMap::new();
+    let mut file = File::open(wav_path(prefix, first.file))?;
+    let sample_rate = read_sample_rate(&mut file)?;
+    files.insert(first.file, file);
+    let at = |time: Duration| (time.as_secs_f64() * sample_rate as f64) as u64;
+    let audio_end = chunks
+        .iter()
+        .map(|c| c.start + c.samples)
+        .max()
+        .unwrap_or(0);
+    let (from, to) = (at(start), at(end).min(audio_end));
+    if to <= from {
+        return Ok(Vec::new());
+    }
+    let mut samples = vec![0; (to - from) as usize];
+    for chunk in &chunks {
+        let (first, last) = (chunk.start.max(from), (chunk.start + chunk.samples).min(to));
+        if first >= last {
+            continue;
+        }
+        let file = match files.get_mut(&chunk.file) {
+            Some(file) => file,
+            None => {
+                let mut file = File::open(wav_path(prefix, chunk.file))?;
+                if read_sample_rate(&mut file)? != sample_rate {
+                    return Err(Error::CorruptFile("sample rates differ".to_string()));
+                }
+                files.entry(chunk.file).or_insert(file)
+            }
+        };
+        let offset = chunk.offset + first - chunk.start;
+        file.seek(SeekFrom::Start(HEADER_BYTES + offset * 2))?;
+        let mut bytes = vec![0; (last - first) as usize * 2];
+        file.read_exact(&mut bytes)?;
+        let range = (first - from) as usize..(last - from) as usize;
+        for (sample, bytes) in samples[range].iter_mut().zip(bytes.chunks_exact(2)) {
+            *sample = i16::from_le_bytes([bytes[0], bytes[1]]);
+        }
+    }
+    Ok(samples)
+}
+
+/// A line of the index.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Chunk {
+    start: u64,
+    samples: u64,
+    file: u32,
+    offset: u64,
+}
+
+fn parse_chunk(line: &str) -> Result<Chunk, Error> {
+    let fields: Vec<u64> = line
+        .split('\t')
+        .map(|field| field.parse().ok())
+        .collect::<Option<_>>()
+        .filter(|fields: &Vec<u64>| fields.len() == 4)
+        .ok_or_else(|| Error::CorruptFile(format!("bad line in audio tee index: {}", line)))?;
+    Ok(Chunk {
+        start: fields[0],
+        samples: fields[1],
+        file: fields[2] as u32,
+        offset: fields[3],
+    })
+}
+
+fn read_sample_rate(file: &mut File) -> Result<u32, Error> {
+    let mut header = [0; HEADER_BYTES as usize];
+    file.read_exact(&mut header)?;
+    if &header[..4] != b"RIFF" || &header[8..16] != b"WAVEfmt " {
+        return Err(Error::CorruptFile("not a WAV file".to_string()));
+    }
+    Ok(u32::from_le_bytes([
+        header[24], header[25], header[26], header[27],
+    ]))
+}
+
+fn with_suffix(prefix: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(prefix.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn wav_path(prefix: &Path, file: u32) -> PathBuf {
+    with_suffix(prefix, &format!("-{:04}.wav", file))
+}
+
+/// The most samples a file may hold.
+fn max_samples(opts: &TeeOptions, sample_rate: u32) -> u64 {
+    let by_size = opts.max_file_bytes.map(|bytes| bytes / 2);
+    let by_duration = opts
+        .max_file_duration
+        .map(|d| (d.as_secs_f64() * sample_rate as f64) as u64);
+    by_size
+        .into_iter()
+        .chain(by_duration)
+        .min()
+        .unwrap_or(u64::MAX)
+        // Room for the 4 GiB limit of WAV files.
+        .min((u32::MAX as u64 - HEADER_BYTES) / 2)
+}
+
+/// The header of a 16-bit mono WAV file with `samples` samples.
+fn header(sample_rate: u32, samples: u64) -> [u8; HEADER_BYTES as usize] {
+    let data = (samples * 2) as u32;
+    let mut header = [0; HEADER_BYTES as usize];
+    header[..4].copy_from_slice(b"RIFF");
+    header[4..8].copy_from_slice(&(36 + data).to_le_bytes());
+    header[8..16].copy_from_slice(b"WAVEfmt ");
+    header[16..20].copy_from_slice(&16u32.to_le_bytes());
+    // PCM, one channel.
+    header[20..22].copy_from_slice(&1u16.to_le_bytes());
+    header[22..24].copy_from_slice(&1u16.to_le_bytes());
+    header[24..28].copy_from_slice(&sample_rate.to_le_bytes());
+    header[28..32].copy_from_slice(&(sample_rate * 2).to_le_bytes());
+    header[32..34].copy_from_slice(&2u16.to_le_bytes());
+    header[34..36].copy_from_slice(&16u16.to_le_bytes());
+    header[36..40].copy_from_slice(b"data");
+    header[40..44].copy_from_slice(&data.to_le_bytes());
+    header
+}
+
+/// The end of the tee on the writing thread.
+struct Writer {
+    prefix: PathBuf,
+    sample_rate: u32,
+    max_samples: u64,
+    file: Option<BufWriter<File>>,
+    /// Files opened so far, the number of the current one.
+    files: u32,
+    file_samples: u64,
+    index: BufWriter<File>,
+    status: Arc<Mutex<Status>>,
+    failed: bool,
+}
+
+impl Writer {
+    fn run(mut self, receiver: Receiver<Message>) {
+        for message in receiver {
+            match message {
+                Message::Chunk { start, samples } => {
+                    let written = self.write_chunk(start, &samples);
+                    self.check(written);
+                }
+                Message::Flush(done) => {
+                    let flushed = self.flush();
+                    self.check(flushed);
+                    let _ = done.send(());
+                }
+            }
+        }
+        let flushed = self.flush();
+        self.check(flushed);
+    }
+    /// Stops writing after the first error.
+    fn check(&mut self, result: io::Result<()>) {
+        if let Err(e) = result {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(error = %e, "audio tee stopped writing");
+            self.failed = true;
+            self.file = None;
+            self.status.lock().unwrap().error.get_or_insert(e.into());
+        }
+    }
+    fn write_chunk(&mut self, start: u64, samples: &[i16]) -> io::Result<()> {
+        if self.failed {
+            return Ok(());
+        }
+        let len = samples.len() as u64;
+        if self.file_samples > 0 && self.file_samples + len > self.max_samples {
+            self.open_next()?;
+        }
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+        for sample in samples {
+            file.write_all(&sample.to_le_bytes())?;
+        }
+        writeln!(
+            self.index,
+            "{}\t{}\t{}\t{}",
+            start, len, self.files, self.file_samples
+        )?;
+        self.file_samples += len;
+        Ok(())
+    }
+    /// Finishes the current file, if any, and starts the next one.
+    fn open_next(&mut self) -> io::Result<()> {
+        self.flush()?;
+        self.file = None;
+        let path = wav_path(&self.prefix, self.files + 1);
+        let mut file = BufWriter::with_capacity(1 << 16, File::create(&path)?);
+        file.write_all(&header(self.sample_rate, 0))?;
+        self.files += 1;
+        self.file = Some(file);
+        self.file_samples = 0;
+        self.status.lock().unwrap().files.push(path);
+        Ok(())
+    }
+    /// Writes what's buffered and updates the header to match.
+    fn flush(&mut self) -> io::Result<()> {
+        if self.failed {
+            return Ok(());
+        }
+        if let Some(file) = &mut self.file {
+            file.flush()?;
+            let file = file.get_mut();
+            file.seek(SeekFrom::Start(0))?;
+            file.write_all(&header(self.sample_rate, self.file_samples))?;
+            file.seek(SeekFrom::End(0))?;
+        }
+        self.index.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::MemorySource;
+    use std::convert::TryInto;
+    use std::fs;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vosk-tee-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+    /// The sample rate, data length in the header, and samples of a WAV file.
+    fn read_wav(path: &Path) -> (u32, u32, Vec<i16>) {
+        let bytes = fs::read(path).unwrap();
+        assert_eq!(&bytes[..4], b"RIFF");
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        assert_eq!(u32_at(4) as usize, bytes.len() - 8);
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(&bytes[36..40], b"data");
+        let samples = bytes[44..]
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+            .collect();
+        (u32_at(24), u32_at(40), samples)
+    }
+
+    #[test]
+    fn headers_and_samples() {
+        let dir = temp_dir("single");
+        let prefix = dir.join("session");
+        let mut tee = AudioTee::create(&prefix, 8000, TeeOptions::default()).unwrap();
+        let samples: Vec<i16> = (0..8000).map(|i| (i * 7 % 2000) as i16 - 1000).collect();
+        for chunk in samples.chunks(300) {
+            tee.write(chunk);
+        }
+        tee.flush().unwrap();
+        // The header is right after a flush, before the file is closed.
+        let path = dir.join("session-0001.wav");
+        assert_eq!(read_wav(&path), (8000, 16000, samples.clone()));
+        tee.write(&[1, 2, 3]);
+        assert_eq!(tee.duration(), Duration::from_micros(1_000_375));
+        assert_eq!(tee.finish().unwrap(), vec![path.clone()]);
+        let (_, data, written) = read_wav(&path);
+        assert_eq!((data, written.len()), (16006, 8003));
+        let index = fs::read_to_string(dir.join("session.chunks")).unwrap();
+        assert_eq!(index.lines().count(), 1 + 27 + 1);
+        assert_eq!(index.lines().nth(2), Some("300\t300\t1\t300"));
+    }
+    #[test]
+    fn rotation() {
+        let dir = temp_dir("rotation");
+        let prefix = dir.join("r");
+        let opts = TeeOptions {
+            max_file_bytes: Some(1000),
+            ..TeeOptions::default()
+        };
+        let mut tee = AudioTee::create(&prefix, 16000, opts).unwrap();
+        // Each file takes two chunks of 200 samples, 800 bytes.
+        for i in 0..5 {
+            tee.write(&[i; 200]);
+        }
+        // A chunk over the limit gets a file of its own.
+        tee.write(&[9; 700]);
+        let files = tee.finish().unwrap();
+        let lengths: Vec<usize> = files.iter().map(|f| read_wav(f).2.len()).collect();
+        assert_eq!(lengths, [400, 400, 200, 700]);
+        assert!(files[3].ends_with("r-0004.wav"));
+
+        let opts = TeeOptions {
+            max_file_duration: Some(Duration::from_millis(50)),
+            ..TeeOptions::default()
+        };
+        let mut tee = AudioTee::create(dir.join("d"), 16000, opts).unwrap();
+        for _ in 0..10 {
+            tee.write(&[0; 320]);
+        }
+        // Two chunks of 20 ms to a file of at most 50 ms.
+        let files = tee.finish().unwrap();
+        assert_eq!(files.len(), 5);
+        assert!(files.iter().all(|f| read_wav(f).1 == 1280));
+    }
+    #[test]
+    fn extract_by_time() {
+        let dir = temp_dir("extract");
+        let prefix = dir.join("e");
+        let opts = TeeOptions {
+            max_file_bytes: Some(2000),
+            ..TeeOptions::default()
+        };
+        let mut tee = AudioTee::create(&prefix, 1000, opts).unwrap();
+        let samples: Vec<i16> = (0..3000).map(|i| i as i16).collect();
+        for chunk in samples.chunks(250) {
+            tee.write(chunk);
+        }
+        assert!(tee.finish().unwrap().len() > 1);
+        let ms = Duration::from_millis;
+        // Across files and chunks.
+        let cut = extract(&prefix, ms(900), ms(2100)).unwrap();
+        assert_eq!(cut, &samples[900..2100]);
+        // Past the end, and empty.
+        assert_eq!(
+            extract(&prefix, ms(2990), ms(5000)).unwrap(),
+            &samples[2990..]
+        );
+        assert!(extract(&prefix, ms(100), ms(100)).unwrap().is_empty());
+        assert!(extract(dir.join("none"), ms(0), ms(10)).is_err());
+    }
+    #[test]
+    fn write_errors_stop_the_tee() {
+        let dir = temp_dir("failing");
+        let prefix = dir.join("f");
+        let opts = TeeOptions {
+            max_file_bytes: Some(100),
+            ..TeeOptions::default()
+        };
+        let mut tee = AudioTee::create(&prefix, 16000, opts).unwrap();
+        tee.write(&[1; 50]);
+        tee.flush().unwrap();
+        // The next file can't be created.
+        fs::remove_dir_all(&dir).unwrap();
+        tee.write(&[2; 50]);
+        tee.write(&[3; 50]);
+        assert!(matches!(tee.flush(), Err(Error::Io(_))));
+        assert!(matches!(tee.error(), Some(Error::Io(_))));
+        // Writing goes on without complaint.
+        tee.write(&[4; 50]);
+        assert_eq!(tee.dropped_chunks(), 0);
+        assert!(tee.finish().is_err());
+        assert!(AudioTee::create(dir.join("f"), 16000, TeeOptions::default()).is_err());
+    }
+    #[test]
+    fn source_passes_through() {
+        let dir = temp_dir("source");
+        let tee = AudioTee::create(dir.join("s"), 16000, TeeOptions::default()).unwrap();
+        let samples: Vec<i16> = (0..1000).collect();
+        let mut source = TeeSource::new(MemorySource::new(samples.clone(), 16000), tee);
+        let mut read = Vec::new();
+        let mut buf = [0; 128];
+        loop {
+            let n = source.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            read.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(read, samples);
+        assert_eq!(source.total_samples(), Some(1000));
+        let (_, tee) = source.into_inner();
+        let files = tee.finish().unwrap();
+        assert_eq!(read_wav(&files[0]).2, samples);
+    }
+}
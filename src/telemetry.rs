@@ -0,0 +1,208 @@
+//! Metrics for server deployments, recorded through the `metrics` crate with the
+//! `metrics` feature.
+//!
+//! Nothing is recorded until the application installs a recorder, such as the
+//! Prometheus exporter of `metrics-exporter-prometheus`; see the `transcribe_server`
+//! example. The only labels are from short fixed lists, never per recognizer or
+//! session, so the number of series stays the same however many sessions there are.
+//!
+//! Without the feature, the crate records nothing and only the names remain.
+
+use crate::overload::OverloadPolicy;
+use std::time::Duration;
+#[cfg(feature = "metrics")]
+use std::time::Instant;
+
+/// Gauge of the recognizers that exist at the moment.
+pub const RECOGNIZERS_ACTIVE: &str = "vosk_recognizers_active";
+/// Counter of the audio fed to recognizers, in milliseconds.
+pub const AUDIO_PROCESSED: &str = "vosk_audio_processed_milliseconds_total";
+/// Histogram of the time taken per second of audio by each call feeding audio.
+pub const REAL_TIME_FACTOR: &str = "vosk_real_time_factor";
+/// Counter of results with text from `Recognizer::result` and `final_result`.
+pub const UTTERANCES: &str = "vosk_utterances_total";
+/// Counter of JSON that couldn't be parsed, labelled with `kind`:
+/// "result", "partial" or "final_result".
+pub const PARSE_ERRORS: &str = "vosk_parse_errors_total";
+/// Counter of `Event::Overloaded`, labelled with `policy`:
+/// "drop_oldest", "skip_partials" or "coalesce".
+pub const OVERLOADS: &str = "vosk_overloads_total";
+/// Counter of the audio dropped while overloaded, in milliseconds.
+pub const OVERLOAD_DROPPED: &str = "vosk_overload_dropped_milliseconds_total";
+
+/// Registers the descriptions and units of the metrics with the installed recorder,
+/// so that exporters can show them.
+#[cfg(feature = "metrics")]
+pub fn describe() {
+    use metrics::{describe_counter, describe_gauge, describe_histogram, Unit};
+    describe_gauge!(RECOGNIZERS_ACTIVE, "Recognizers that exist at the moment");
+    describe_counter!(
+        AUDIO_PROCESSED,
+        Unit::Milliseconds,
+        "Audio fed to recognizers"
+    );
+    describe_histogram!(
+        REAL_TIME_FACTOR,
+        "Time taken per second of audio, below 1 when faster than real time"
+    );
+    describe_counter!(UTTERANCES, "Utterances finalized with some text");
+    describe_counter!(PARSE_ERRORS, "Recognizer output that could not be parsed");
+    describe_counter!(OVERLOADS, "Times recognition fell behind the audio");
+    describe_counter!(
+        OVERLOAD_DROPPED,
+        Unit::Milliseconds,
+        "Audio dropped to catch up after falling behind"
+    );
+}
+
+/// Measures the time a call takes, when there are metrics to record it in.
+pub(crate) struct Timer {
+    #[cfg(feature = "metrics")]
+    started: Instant,
+}
+
+impl Timer {
+    pub(crate) fn start() -> Timer {
+        Timer {
+            #[cfg(feature = "metrics")]
+            started: Instant::now(),
+        }
+    }
+}
+
+pub(crate) fn recognizer_created() {
+    #[cfg(feature = "metrics")]
+    metrics::gauge!(RECOGNIZERS_ACTIVE).increment(1.0);
+}
+
+pub(crate) fn recognizer_dropped() {
+    #[cfg(feature = "metrics")]
+    metrics::gauge!(RECOGNIZERS_ACTIVE).decrement(1.0);
+}
+
+/// After a recognizer went from `before` to `after` samples processed.
+///
+/// Milliseconds are counted from the totals, so that rounding doesn't add up
+/// over many small chunks.
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn audio_fed(timer: Timer, before: u64, after: u64, sample_rate: f32) {
+    #[cfg(feature = "metrics")]
+    if after > before && sample_rate > 0.0 {
+        let millis = |samples: u64| (samples as f64 * 1000.0 / sample_rate as f64) as u64;
+        metrics::counter!(AUDIO_PROCESSED).increment(millis(after) - millis(before));
+        let audio = (after - before) as f64 / sample_rate as f64;
+        let taken = timer.started.elapsed().as_secs_f64();
+        metrics::histogram!(REAL_TIME_FACTOR).record(taken / audio);
+    }
+}
+
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn utterance(text: &str) {
+    #[cfg(feature = "metrics")]
+    if !text.is_empty() {
+        metrics::counter!(UTTERANCES).increment(1);
+    }
+}
+
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn parse_error(kind: &str) {
+    #[cfg(feature = "metrics")]
+    {
+        // Only known kinds, to keep the label bounded.
+        let kind = match kind {
+            "partial" => "partial",
+            "final_result" => "final_result",
+            _ => "result",
+        };
+        metrics::counter!(PARSE_ERRORS, "kind" => kind).increment(1);
+    }
+}
+
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn overloaded(policy: OverloadPolicy, dropped: Duration) {
+    #[cfg(feature = "metrics")]
+    {
+        let policy = match policy {
+            OverloadPolicy::DropOldest => "drop_oldest",
+            OverloadPolicy::SkipPartials => "skip_partials",
+            OverloadPolicy::Coalesce => "coalesce",
+        };
+        metrics::counter!(OVERLOADS, "policy" => policy).increment(1);
+        metrics::counter!(OVERLOAD_DROPPED).increment(dropped.as_millis() as u64);
+    }
+}
+
+#[cfg(all(test, feature = "metrics"))]
+pub(crate) mod tests {
+    use super::*;
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use std::collections::HashMap;
+
+    /// Runs `f` with a recorder of its own, returning the metrics it recorded
+    /// by name and labels, such as `vosk_parse_errors_total{kind=partial}`.
+    pub(crate) fn recorded<F: FnOnce()>(f: F) -> HashMap<String, DebugValue> {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, f);
+        snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| {
+                let key = key.key();
+                let labels: Vec<String> = key
+                    .labels()
+                    .map(|l| format!("{}={}", l.key(), l.value()))
+                    .collect();
+                let name = if labels.is_empty() {
+                    key.name().to_string()
+                } else {
+                    format!("{}{{{}}}", key.name(), labels.join(","))
+                };
+                (name, value)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn audio_and_rate() {
+        let metrics = recorded(|| {
+            // 1/3 ms each, adding up to 1 ms rather than nothing.
+            for i in 0..3 {
+                audio_fed(Timer::start(), i * 16, (i + 1) * 16, 48000.0);
+            }
+            audio_fed(Timer::start(), 0, 16000, 16000.0);
+            audio_fed(Timer::start(), 100, 100, 16000.0);
+        });
+        assert_eq!(metrics[AUDIO_PROCESSED], DebugValue::Counter(1001));
+        match &metrics[REAL_TIME_FACTOR] {
+            DebugValue::Histogram(values) => assert_eq!(values.len(), 4),
+            other => panic!("{:?}", other),
+        }
+    }
+    #[test]
+    fn bounded_labels() {
+        let metrics = recorded(|| {
+            parse_error("partial");
+            parse_error("something else");
+            parse_error("result");
+            overloaded(OverloadPolicy::Coalesce, Duration::from_millis(1500));
+            utterance("");
+            utterance("hello");
+        });
+        assert_eq!(
+            metrics["vosk_parse_errors_total{kind=partial}"],
+            DebugValue::Counter(1)
+        );
+        assert_eq!(
+            metrics["vosk_parse_errors_total{kind=result}"],
+            DebugValue::Counter(2)
+        );
+        assert_eq!(
+            metrics["vosk_overloads_total{policy=coalesce}"],
+            DebugValue::Counter(1)
+        );
+        assert_eq!(metrics[OVERLOAD_DROPPED], DebugValue::Counter(1500));
+        assert_eq!(metrics[UTTERANCES], DebugValue::Counter(1));
+    }
+}
@@ -0,0 +1,247 @@
+//! G.711 (µ-law and A-law) telephony audio.
+//!
+//! Calls are usually sampled at 8 kHz, so the recognizer should be created at 8000 Hz,
+//! ideally with a model trained on narrowband audio.
+
+use crate::feeder::FeederCore;
+use crate::latency::LatencyStats;
+use crate::{Error, Event, Recognizer, SampleRate, UtteranceOwned};
+
+/// The sample rate of G.711.
+pub const G711_RATE: f32 = SampleRate::HZ_8000.hz();
+
+static ULAW: [i16; 256] = ulaw_table();
+static ALAW: [i16; 256] = alaw_table();
+
+/// The two companding laws of G.711.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum G711Law {
+    /// µ-law, used in North America and Japan (PCMU).
+    MuLaw,
+    /// A-law, used in most other countries (PCMA).
+    ALaw,
+}
+
+/// Decodes µ-law bytes into 16-bit samples.
+pub fn ulaw_to_pcm(bytes: &[u8]) -> Vec<i16> {
+    let mut out = Vec::with_capacity(bytes.len());
+    ulaw_to_pcm_into(bytes, &mut out);
+    out
+}
+
+/// Decodes A-law bytes into 16-bit samples.
+pub fn alaw_to_pcm(bytes: &[u8]) -> Vec<i16> {
+    let mut out = Vec::with_capacity(bytes.len());
+    alaw_to_pcm_into(bytes, &mut out);
+    out
+}
+
+/// Appends the samples of µ-law bytes to `out`.
+pub fn ulaw_to_pcm_into(bytes: &[u8], out: &mut Vec<i16>) {
+    out.extend(bytes.iter().map(|&b| ULAW[b as usize]));
+}
+
+/// Appends the samples of A-law bytes to `out`.
+pub fn alaw_to_pcm_into(bytes: &[u8], out: &mut Vec<i16>) {
+    out.extend(bytes.iter().map(|&b| ALAW[b as usize]));
+}
+
+/// Feeds G.711 bytes, such as RTP payloads of PCMU or PCMA, to a recognizer.
+pub struct G711Feeder {
+    core: FeederCore,
+    law: G711Law,
+    samples: Vec<i16>,
+}
+
+impl G711Feeder {
+    /// Fails with `Error::AudioRateMismatch` unless the recognizer was created at 8000 Hz.
+    pub fn new(recognizer: Recognizer, law: G711Law) -> Result<G711Feeder, Error> {
+        if recognizer.sample_rate() != G711_RATE {
+            return Err(Error::AudioRateMismatch {
+                recognizer: recognizer.sample_rate(),
+                audio: G711_RATE,
+            });
+        }
+        Ok(G711Feeder {
+            core: FeederCore::new(recognizer),
+            law,
+            samples: Vec::new(),
+        })
+    }
+    /// Decodes the bytes and feeds them to the recognizer.
+    ///
+    /// Returns an event when an utterance was completed or the partial result changed.
+    pub fn push(&mut self, bytes: &[u8]) -> Option<Event> {
+        if bytes.is_empty() {
+            return None;
+        }
+        self.samples.clear();
+        match self.law {
+            G711Law::MuLaw => ulaw_to_pcm_into(bytes, &mut self.samples),
+            G711Law::ALaw => alaw_to_pcm_into(bytes, &mut self.samples),
+        }
+        self.core.feed(&self.samples, true);
+        self.core.next_event()
+    }
+    /// Returns the last utterance at the end of the call.
+    pub fn finish(&mut self) -> UtteranceOwned {
+        self.core.finish()
+    }
+    /// Starts measuring how long audio takes to show up in a partial result,
+    /// from the call that pushed it.
+    pub fn enable_latency(&mut self) {
+        self.core.enable_latency()
+    }
+    /// The measurements so far, if enabled.
+    pub fn latency(&self) -> Option<LatencyStats> {
+        self.core.latency()
+    }
+    pub fn recognizer(&self) -> &Recognizer {
+        &self.core.recognizer
+    }
+    pub fn recognizer_mut(&mut self) -> &mut Recognizer {
+        &mut self.core.recognizer
+    }
+    pub fn into_inner(self) -> Recognizer {
+        self.core.recognizer
+    }
+}
+
+/// Expands every µ-law code as in ITU-T G.711:
+/// the bits are inverted and hold a sign, a 3-bit segment and a 4-bit step.
+const fn ulaw_table() -> [i16; 256] {
+    let mut table = [0; 256];
+    let mut code = 0;
+    while code < 256 {
+        let u = !code & 0xff;
+        let segment = (u >> 4) & 7;
+        let magnitude = ((((u & 0x0f) << 3) + 0x84) << segment) - 0x84;
+        table[code] = if u & 0x80 != 0 {
+            -(magnitude as i16)
+        } else {
+            magnitude as i16
+        };
+        code += 1;
+    }
+    table
+}
+
+/// Expands every A-law code as in ITU-T G.711:
+/// even bits are inverted, and the sign bit is set for positive values.
+const fn alaw_table() -> [i16; 256] {
+    let mut table = [0; 256];
+    let mut code = 0;
+    while code < 256 {
+        let a = code ^ 0x55;
+        let segment = (a >> 4) & 7;
+        let step = (a & 0x0f) << 4;
+        let magnitude = if segment == 0 {
+            step + 8
+        } else {
+            (step + 0x108) << (segment - 1)
+        };
+        table[code] = if a & 0x80 != 0 {
+            magnitude as i16
+        } else {
+            -(magnitude as i16)
+        };
+        code += 1;
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Output of the widely used Sun Microsystems g711.c for every code, in order.
+    const ULAW_REFERENCE: [i16; 256] = [
+        -32124, -31100, -30076, -29052, -28028, -27004, -25980, -24956, -23932, -22908, -21884,
+        -20860, -19836, -18812, -17788, -16764, -15996, -15484, -14972, -14460, -13948, -13436,
+        -12924, -12412, -11900, -11388, -10876, -10364, -9852, -9340, -8828, -8316, -7932, -7676,
+        -7420, -7164, -6908, -6652, -6396, -6140, -5884, -5628, -5372, -5116, -4860, -4604, -4348,
+        -4092, -3900, -3772, -3644, -3516, -3388, -3260, -3132, -3004, -2876, -2748, -2620, -2492,
+        -2364, -2236, -2108, -1980, -1884, -1820, -1756, -1692, -1628, -1564, -1500, -1436, -1372,
+        -1308, -1244, -1180, -1116, -1052, -988, -924, -876, -844, -812, -780, -748, -716, -684,
+        -652, -620, -588, -556, -524, -492, -460, -428, -396, -372, -356, -340, -324, -308, -292,
+        -276, -260, -244, -228, -212, -196, -180, -164, -148, -132, -120, -112, -104, -96, -88,
+        -80, -72, -64, -56, -48, -40, -32, -24, -16, -8, 0, 32124, 31100, 30076, 29052, 28028,
+        27004, 25980, 24956, 23932, 22908, 21884, 20860, 19836, 18812, 17788, 16764, 15996, 15484,
+        14972, 14460, 13948, 13436, 12924, 12412, 11900, 11388, 10876, 10364, 9852, 9340, 8828,
+        8316, 7932, 7676, 7420, 7164, 6908, 6652, 6396, 6140, 5884, 5628, 5372, 5116, 4860, 4604,
+        4348, 4092, 3900, 3772, 3644, 3516, 3388, 3260, 3132, 3004, 2876, 2748, 2620, 2492, 2364,
+        2236, 2108, 1980, 1884, 1820, 1756, 1692, 1628, 1564, 1500, 1436, 1372, 1308, 1244, 1180,
+        1116, 1052, 988, 924, 876, 844, 812, 780, 748, 716, 684, 652, 620, 588, 556, 524, 492, 460,
+        428, 396, 372, 356, 340, 324, 308, 292, 276, 260, 244, 228, 212, 196, 180, 164, 148, 132,
+        120, 112, 104, 96, 88, 80, 72, 64, 56, 48, 40, 32, 24, 16, 8, 0,
+    ];
+
+    const ALAW_REFERENCE: [i16; 256] = [
+        -5504, -5248, -6016, -5760, -4480, -4224, -4992, -4736, -7552, -7296, -8064, -7808, -6528,
+        -6272, -7040, -6784, -2752, -2624, -3008, -2880, -2240, -2112, -2496, -2368, -3776, -3648,
+        -4032, -3904, -3264, -3136, -3520, -3392, -22016, -20992, -24064, -23040, -17920, -16896,
+        -19968, -18944, -30208, -29184, -32256, -31232, -26112, -25088, -28160, -27136, -11008,
+        -10496, -12032, -11520, -8960, -8448, -9984, -9472, -15104, -14592, -16128, -15616, -13056,
+        -12544, -14080, -13568, -344, -328, -376, -360, -280, -264, -312, -296, -472, -456, -504,
+        -488, -408, -392, -440, -424, -88, -72, -120, -104, -24, -8, -56, -40, -216, -200, -248,
+        -232, -152, -136, -184, -168, -1376, -1312, -1504, -1440, -1120, -1056, -1248, -1184,
+        -1888, -1824, -2016, -1952, -1632, -1568, -1760, -1696, -688, -656, -752, -720, -560, -528,
+        -624, -592, -944, -912, -1008, -976, -816, -784, -880, -848, 5504, 5248, 6016, 5760, 4480,
+        4224, 4992, 4736, 7552, 7296, 8064, 7808, 6528, 6272, 7040, 6784, 2752, 2624, 3008, 2880,
+        2240, 2112, 2496, 2368, 3776, 3648, 4032, 3904, 3264, 3136, 3520, 3392, 22016, 20992,
+        24064, 23040, 17920, 16896, 19968, 18944, 30208, 29184, 32256, 31232, 26112, 25088, 28160,
+        27136, 11008, 10496, 12032, 11520, 8960, 8448, 9984, 9472, 15104, 14592, 16128, 15616,
+        13056, 12544, 14080, 13568, 344, 328, 376, 360, 280, 264, 312, 296, 472, 456, 504, 488,
+        408, 392, 440, 424, 88, 72, 120, 104, 24, 8, 56, 40, 216, 200, 248, 232, 152, 136, 184,
+        168, 1376, 1312, 1504, 1440, 1120, 1056, 1248, 1184, 1888, 1824, 2016, 1952, 1632, 1568,
+        1760, 1696, 688, 656, 752, 720, 560, 528, 624, 592, 944, 912, 1008, 976, 816, 784, 880,
+        848,
+    ];
+
+    #[test]
+    fn ulaw_reference() {
+        let codes: Vec<u8> = (0..=255).collect();
+        assert_eq!(ulaw_to_pcm(&codes), ULAW_REFERENCE.to_vec());
+    }
+    #[test]
+    fn alaw_reference() {
+        let codes: Vec<u8> = (0..=255).collect();
+        assert_eq!(alaw_to_pcm(&codes), ALAW_REFERENCE.to_vec());
+    }
+    #[test]
+    fn known_codes() {
+        // Silence and full scale.
+        assert_eq!(
+            ulaw_to_pcm(&[0xff, 0x7f, 0x80, 0x00]),
+            vec![0, 0, 32124, -32124]
+        );
+        assert_eq!(
+            alaw_to_pcm(&[0xd5, 0x55, 0xaa, 0x2a]),
+            vec![8, -8, 32256, -32256]
+        );
+    }
+    #[test]
+    fn feeder_needs_8khz() {
+        let model = crate::test_util::fake_model("model");
+        let recognizer = Recognizer::from_ptr(std::ptr::null_mut(), &model, 16000.0, None);
+        let error = G711Feeder::new(recognizer, G711Law::MuLaw).err();
+        assert_eq!(
+            error,
+            Some(Error::AudioRateMismatch {
+                recognizer: 16000.0,
+                audio: 8000.0
+            })
+        );
+        assert_eq!(
+            error.unwrap().to_string(),
+            "The audio is at 8000 Hz, but the recognizer takes 16000 Hz"
+        );
+    }
+    #[test]
+    fn append_into_buffer() {
+        let mut out = vec![1];
+        ulaw_to_pcm_into(&[0xff], &mut out);
+        alaw_to_pcm_into(&[0xd5], &mut out);
+        assert_eq!(out, vec![1, 0, 8]);
+    }
+}
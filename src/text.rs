@@ -0,0 +1,51 @@
+//! Building text from words, shared by everything that has to.
+
+use std::ops::Range;
+
+/// Joins words with spaces, except between Chinese or Japanese characters,
+/// which are written without them.
+///
+/// Also returns where each word ended up in the text.
+pub(crate) fn join_words<S: AsRef<str>>(words: &[S]) -> (String, Vec<Range<usize>>) {
+    let mut text = String::new();
+    let mut spans = Vec::with_capacity(words.len());
+    for word in words {
+        let word = word.as_ref();
+        let between_cjk =
+            text.chars().next_back().is_some_and(is_cjk) && word.chars().next().is_some_and(is_cjk);
+        if !text.is_empty() && !between_cjk {
+            text.push(' ');
+        }
+        spans.push(text.len()..text.len() + word.len());
+        text.push_str(word);
+    }
+    (text, spans)
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3000}'..='\u{30ff}'
+        | '\u{3400}'..='\u{4dbf}'
+        | '\u{4e00}'..='\u{9fff}'
+        | '\u{f900}'..='\u{faff}'
+        | '\u{ff00}'..='\u{ffef}'
+        | '\u{20000}'..='\u{2fa1f}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spaces() {
+        let (text, spans) = join_words(&["hello", "world"]);
+        assert_eq!(text, "hello world");
+        assert_eq!(spans, vec![0..5, 6..11]);
+    }
+    #[test]
+    fn cjk() {
+        assert_eq!(join_words(&["我", "叫", "李明"]).0, "我叫李明");
+        assert_eq!(join_words(&["用", "iPhone", "打"]).0, "用 iPhone 打");
+        assert_eq!(join_words::<&str>(&[]).0, "");
+    }
+}
@@ -0,0 +1,674 @@
+//! Collecting the utterances of a stream into a transcript.
+//!
+//! For dictation, a transcript can take spoken edit commands such as "scratch that",
+//! see `EditCommands`.
+
+use crate::{Event, RecognizedText, RecognizedWord, UtteranceOwned};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::borrow::Cow;
+use std::time::Duration;
+
+/// Finalized utterances of a stream, in order, with the time they happened.
+///
+/// Word times reported by a recognizer count from the first audio it was fed,
+/// or from its last reset. When recognizers are replaced or reset during a stream,
+/// each utterance is pushed with the stream time its recognizer started at.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Transcript {
+    utterances: Vec<TranscriptEntry>,
+    /// The partial result of the utterance in progress, see `push_event`.
+    #[serde(skip)]
+    partial: String,
+    #[serde(skip)]
+    shown: TranscriptDiff,
+    #[serde(skip)]
+    commands: Option<EditCommands>,
+    /// How to take back each edit, the last one at the end.
+    #[serde(skip)]
+    history: Vec<Revert>,
+}
+
+/// An utterance and the stream time its word times are relative to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptEntry {
+    pub offset: Duration,
+    #[serde(deserialize_with = "deserialize_owned")]
+    pub utterance: UtteranceOwned,
+}
+
+impl Transcript {
+    pub fn new() -> Transcript {
+        Transcript::default()
+    }
+    /// Treats utterances that are only one of `commands` as edits, applied by
+    /// `push` and `push_event` instead of adding the utterance.
+    pub fn with_edit_commands(mut self, commands: EditCommands) -> Self {
+        self.commands = Some(commands);
+        self
+    }
+    /// Adds an utterance, whose word times count from `stream_offset`.
+    ///
+    /// With edit commands, an utterance that is a command is applied instead.
+    pub fn push(&mut self, utterance: UtteranceOwned, stream_offset: Duration) {
+        let command = self.commands.as_ref().and_then(|c| c.find(&utterance.text));
+        if let Some(command) = command {
+            self.apply(command);
+            return;
+        }
+        self.utterances.push(TranscriptEntry {
+            offset: stream_offset,
+            utterance,
+        });
+    }
+    /// Adds the utterance of a `Final` event, such as one returned by a feeder,
+    /// or keeps the text of a `Partial` one for `display_text`. Other events are ignored.
+    pub fn push_event(&mut self, event: Event, stream_offset: Duration) {
+        match event {
+            Event::Final(utterance) => {
+                self.partial.clear();
+                self.push(utterance, stream_offset);
+            }
+            Event::Partial(text) => self.partial = text,
+            Event::Overloaded { .. }
+            | Event::Swapped { .. }
+            | Event::SwapFailed { .. }
+            | Event::Paused
+            | Event::Resumed { .. }
+            | Event::GrammarChanged { .. }
+            | Event::SpeechStarted
+            | Event::SpeechEnded { .. } => {}
+        }
+    }
+    /// The finalized text followed by the partial result of the utterance in progress.
+    pub fn display_text(&self) -> String {
+        let mut text = self.full_text();
+        if !self.partial.is_empty() {
+            if !text.is_empty() {
+                text.push(' ');
+            }
+            text.push_str(&self.partial);
+        }
+        text
+    }
+    /// The edit that brings the text returned by the previous call up to date
+    /// with `display_text`, or None if it hasn't changed.
+    pub fn display_edit(&mut self) -> Option<Edit<'_>> {
+        let text = self.display_text();
+        self.shown.update(&text)
+    }
+    /// The text of all utterances, separated by spaces. Empty utterances are left out.
+    pub fn full_text(&self) -> String {
+        let texts: Vec<&str> = self
+            .utterances
+            .iter()
+            .map(|entry| entry.utterance.text.as_ref())
+            .filter(|text| !text.is_empty())
+            .collect();
+        texts.join(" ")
+    }
+    pub fn utterances(&self) -> &[TranscriptEntry] {
+        &self.utterances
+    }
+    /// All words with times counted from the start of the stream.
+    pub fn words(&self) -> impl Iterator<Item = RecognizedWord<'_>> + '_ {
+        self.utterances.iter().flat_map(|entry| {
+            let offset = entry.offset.as_secs_f32();
+            entry.utterance.words().iter().map(move |w| RecognizedWord {
+                word: Cow::Borrowed(w.word()),
+                conf: w.conf(),
+                start: w.start() + offset,
+                end: w.end() + offset,
+            })
+        })
+    }
+    /// Stream time at the end of the last word.
+    ///
+    /// Utterances without word details only count from their offset.
+    pub fn duration(&self) -> Duration {
+        let ends = self.utterances.iter().map(|entry| {
+            let last = entry.utterance.words().last();
+            entry.offset + last.map_or(Duration::ZERO, |w| secs(w.end()))
+        });
+        ends.max().unwrap_or(Duration::ZERO)
+    }
+    pub fn len(&self) -> usize {
+        self.utterances.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.utterances.is_empty()
+    }
+
+    /// Applies an edit, returning false if there was nothing to edit or undo.
+    pub fn apply(&mut self, command: EditCommand) -> bool {
+        match command {
+            EditCommand::ScratchThat => self.scratch_that(),
+            EditCommand::DeleteLastWord => self.delete_last_word(),
+            EditCommand::Undo => self.undo(),
+        }
+    }
+    /// Removes the last utterance with any text.
+    pub fn scratch_that(&mut self) -> bool {
+        let Some(index) = self.last_with_text() else {
+            return false;
+        };
+        let entry = self.utterances.remove(index);
+        self.history.push(Revert::Insert(index, entry));
+        true
+    }
+    /// Removes the last word of the last utterance with any text, from the text
+    /// and the word details.
+    pub fn delete_last_word(&mut self) -> bool {
+        let Some(index) = self.last_with_text() else {
+            return false;
+        };
+        let entry = &mut self.utterances[index];
+        let before = entry.clone();
+        drop_last_word(&mut entry.utterance);
+        self.history.push(Revert::Replace(index, before));
+        true
+    }
+    /// Takes back the last edit that wasn't taken back yet.
+    pub fn undo(&mut self) -> bool {
+        match self.history.pop() {
+            Some(Revert::Insert(index, entry)) => self.utterances.insert(index, entry),
+            Some(Revert::Replace(index, entry)) => self.utterances[index] = entry,
+            None => return false,
+        }
+        true
+    }
+    fn last_with_text(&self) -> Option<usize> {
+        self.utterances
+            .iter()
+            .rposition(|entry| !entry.utterance.text.trim().is_empty())
+    }
+}
+
+/// An edit of a transcript, see `EditCommands`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditCommand {
+    /// Remove the last utterance.
+    ScratchThat,
+    /// Remove the last word.
+    DeleteLastWord,
+    /// Take back the last edit.
+    Undo,
+}
+
+/// Spoken phrases that edit a transcript rather than being added to it,
+/// see `Transcript::with_edit_commands`.
+///
+/// An utterance is a command if it's one of the phrases and nothing else,
+/// regardless of case and punctuation, so "Scratch that." works after a
+/// `DictationFormatter`, but "don't scratch that" is added as it is.
+///
+/// ```
+/// # use vosk::transcript::{EditCommands, Transcript};
+/// # use vosk::RecognizedText;
+/// # use std::time::Duration;
+/// let mut transcript = Transcript::new().with_edit_commands(EditCommands::english());
+/// for said in ["dear sir", "scratch that", "dear madam", "hello there", "delete last word"] {
+///     transcript.push(RecognizedText::from_text(said), Duration::ZERO);
+/// }
+/// assert_eq!(transcript.full_text(), "dear madam hello");
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct EditCommands {
+    /// Lower-cased words of each phrase.
+    commands: Vec<(Vec<String>, EditCommand)>,
+}
+
+impl EditCommands {
+    /// No phrases, to add some with `with`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// "scratch that", "delete last word" and "undo".
+    pub fn english() -> Self {
+        Self::new()
+            .with("scratch that", EditCommand::ScratchThat)
+            .with("delete last word", EditCommand::DeleteLastWord)
+            .with("undo", EditCommand::Undo)
+    }
+    /// Adds `phrase` for `command`, keeping the phrases already there.
+    pub fn with(mut self, phrase: &str, command: EditCommand) -> Self {
+        let words = command_words(phrase);
+        if !words.is_empty() {
+            self.commands.retain(|(known, _)| *known != words);
+            self.commands.push((words, command));
+        }
+        self
+    }
+    /// The command `text` is, if it's one.
+    pub fn find(&self, text: &str) -> Option<EditCommand> {
+        let words = command_words(text);
+        self.commands
+            .iter()
+            .find(|(phrase, _)| *phrase == words)
+            .map(|&(_, command)| command)
+    }
+}
+
+/// How to take back an edit.
+#[derive(Debug)]
+enum Revert {
+    Insert(usize, TranscriptEntry),
+    Replace(usize, TranscriptEntry),
+}
+
+/// The words of `text`, lower-cased and without the punctuation around them.
+fn command_words(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .map(|word| {
+            word.trim_matches(|c: char| !c.is_alphanumeric())
+                .to_lowercase()
+        })
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// Removes the last word of `utterance`. The text is cut rather than rebuilt,
+/// to keep the formatting it may have, such as line breaks.
+fn drop_last_word(utterance: &mut UtteranceOwned) {
+    let text = utterance.text.trim_end();
+    let last = utterance
+        .result
+        .as_mut()
+        .and_then(|words| words.pop())
+        .map(|word| word.word.into_owned());
+    let cut = match last {
+        Some(word) if !word.is_empty() && text.ends_with(word.trim_end()) => {
+            text.len() - word.trim_end().len()
+        }
+        _ => text.rfind(char::is_whitespace).map_or(0, |i| i + 1),
+    };
+    utterance.text = Cow::Owned(text[..cut].trim_end().to_string());
+}
+
+/// A change to displayed text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Edit<'a> {
+    /// Add the text at the end.
+    Append(&'a str),
+    /// Replace everything from the byte `index` on with the text, which may be empty.
+    ReplaceFrom { index: usize, text: &'a str },
+}
+
+impl Edit<'_> {
+    pub fn apply(&self, shown: &mut String) {
+        match *self {
+            Edit::Append(text) => shown.push_str(text),
+            Edit::ReplaceFrom { index, text } => {
+                shown.truncate(index);
+                shown.push_str(text);
+            }
+        }
+    }
+}
+
+/// Computes minimal edits for a UI showing a changing transcript,
+/// so that only the end of the text is redrawn.
+///
+/// Edits never start inside a character, or between a character and the combining marks,
+/// variation selectors or joiners that follow it.
+#[derive(Debug, Default, Clone)]
+pub struct TranscriptDiff {
+    shown: String,
+}
+
+impl TranscriptDiff {
+    pub fn new() -> TranscriptDiff {
+        TranscriptDiff::default()
+    }
+    /// Returns the edit from the text of the previous call to `text`.
+    pub fn update(&mut self, text: &str) -> Option<Edit<'_>> {
+        let index = match diff(&self.shown, text)? {
+            Edit::Append(_) => self.shown.len(),
+            Edit::ReplaceFrom { index, .. } => index,
+        };
+        let append = index == self.shown.len();
+        self.shown.truncate(index);
+        self.shown.push_str(&text[index..]);
+        let text = &self.shown[index..];
+        Some(if append {
+            Edit::Append(text)
+        } else {
+            Edit::ReplaceFrom { index, text }
+        })
+    }
+    /// The text as it should be shown after the last edit.
+    pub fn shown(&self) -> &str {
+        &self.shown
+    }
+}
+
+/// The edit that turns `old` into `new`, or None if they are the same.
+pub fn diff<'a>(old: &str, new: &'a str) -> Option<Edit<'a>> {
+    if old == new {
+        return None;
+    }
+    let mut index = old
+        .char_indices()
+        .zip(new.chars())
+        .find(|((_, a), b)| a != b)
+        .map_or(old.len().min(new.len()), |((i, _), _)| i);
+    // Keep whole clusters together, in case the UI renders the edit separately.
+    while index > 0 && (starts_cluster(old, index) || starts_cluster(new, index)) {
+        index = old[..index].char_indices().last().map_or(0, |(i, _)| i);
+    }
+    if index == old.len() {
+        Some(Edit::Append(&new[index..]))
+    } else {
+        Some(Edit::ReplaceFrom {
+            index,
+            text: &new[index..],
+        })
+    }
+}
+
+/// Whether the character at `index` belongs to the one before it.
+fn starts_cluster(text: &str, index: usize) -> bool {
+    let joiner_before = text[..index].ends_with('\u{200d}');
+    joiner_before || text[index..].chars().next().is_some_and(extends_previous)
+}
+
+/// Combining marks, variation selectors, the zero-width joiner and emoji modifiers.
+fn extends_previous(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036f}'
+        | '\u{1ab0}'..='\u{1aff}'
+        | '\u{1dc0}'..='\u{1dff}'
+        | '\u{20d0}'..='\u{20ff}'
+        | '\u{fe20}'..='\u{fe2f}'
+        | '\u{fe00}'..='\u{fe0f}'
+        | '\u{200d}'
+        | '\u{1f3fb}'..='\u{1f3ff}'
+        | '\u{e0100}'..='\u{e01ef}')
+}
+
+fn secs(secs: f32) -> Duration {
+    Duration::from_secs_f32(secs.max(0.0))
+}
+
+/// Utterances can't borrow from the input, which is gone once the transcript is loaded.
+fn deserialize_owned<'de, D: Deserializer<'de>>(d: D) -> Result<UtteranceOwned, D::Error> {
+    RecognizedText::deserialize(d).map(RecognizedText::into_owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::utterance;
+
+    fn sample() -> Transcript {
+        let mut transcript = Transcript::new();
+        transcript.push(
+            utterance(&[("hello", 0.5, 0.9), ("world", 1.0, 1.4)]),
+            Duration::ZERO,
+        );
+        transcript.push(utterance(&[]), Duration::from_secs(3));
+        // A new recognizer, started 10 s into the stream.
+        transcript.push(utterance(&[("again", 0.25, 0.75)]), Duration::from_secs(10));
+        transcript
+    }
+
+    #[test]
+    fn offsets() {
+        let transcript = sample();
+        let words: Vec<_> = transcript
+            .words()
+            .map(|w| (w.word().to_string(), w.start(), w.end()))
+            .collect();
+        assert_eq!(
+            words,
+            vec![
+                ("hello".to_string(), 0.5, 0.9),
+                ("world".to_string(), 1.0, 1.4),
+                ("again".to_string(), 10.25, 10.75),
+            ]
+        );
+        assert_eq!(transcript.duration(), Duration::from_millis(10750));
+        assert_eq!(transcript.full_text(), "hello world again");
+        assert_eq!(transcript.len(), 3);
+    }
+    #[test]
+    fn empty() {
+        let transcript = Transcript::new();
+        assert_eq!(transcript.full_text(), "");
+        assert_eq!(transcript.duration(), Duration::ZERO);
+        assert_eq!(transcript.words().count(), 0);
+    }
+    #[test]
+    fn events() {
+        let mut transcript = Transcript::new();
+        transcript.push_event(Event::Partial("hel".into()), Duration::ZERO);
+        transcript.push_event(
+            Event::Final(utterance(&[("hello", 0.0, 0.5)])),
+            Duration::ZERO,
+        );
+        assert_eq!(transcript.full_text(), "hello");
+    }
+    #[test]
+    fn display_edits() {
+        let mut transcript = Transcript::new();
+        transcript.push_event(Event::Partial("hel".into()), Duration::ZERO);
+        assert_eq!(transcript.display_edit(), Some(Edit::Append("hel")));
+        assert_eq!(transcript.display_edit(), None);
+        transcript.push_event(Event::Partial("hello wor".into()), Duration::ZERO);
+        assert_eq!(transcript.display_edit(), Some(Edit::Append("lo wor")));
+        transcript.push_event(
+            Event::Final(utterance(&[("hello", 0.0, 0.5), ("world", 0.6, 1.0)])),
+            Duration::ZERO,
+        );
+        assert_eq!(transcript.display_edit(), Some(Edit::Append("ld")));
+        transcript.push_event(Event::Partial("the".into()), Duration::ZERO);
+        transcript.push_event(Event::Partial("a".into()), Duration::ZERO);
+        assert_eq!(transcript.display_edit(), Some(Edit::Append(" a")));
+        transcript.push_event(Event::Partial("an".into()), Duration::ZERO);
+        transcript.push_event(Event::Partial("the".into()), Duration::ZERO);
+        assert_eq!(
+            transcript.display_edit(),
+            Some(Edit::ReplaceFrom {
+                index: 12,
+                text: "the"
+            })
+        );
+    }
+    #[test]
+    fn edits_at_boundaries() {
+        // Same first byte, different characters.
+        assert_eq!(
+            diff("aé", "aè"),
+            Some(Edit::ReplaceFrom {
+                index: 1,
+                text: "è"
+            })
+        );
+        // A combining accent added to the last letter.
+        assert_eq!(
+            diff("cafe", "cafe\u{301}"),
+            Some(Edit::ReplaceFrom {
+                index: 3,
+                text: "e\u{301}"
+            })
+        );
+        // A joined emoji sequence growing.
+        assert_eq!(
+            diff("\u{1f469}\u{200d}", "\u{1f469}\u{200d}\u{1f4bb}"),
+            Some(Edit::ReplaceFrom {
+                index: 0,
+                text: "\u{1f469}\u{200d}\u{1f4bb}"
+            })
+        );
+        assert_eq!(
+            diff("abc", "ab"),
+            Some(Edit::ReplaceFrom { index: 2, text: "" })
+        );
+        assert_eq!(diff("", "ab"), Some(Edit::Append("ab")));
+        assert_eq!(diff("ab", "ab"), None);
+    }
+    #[test]
+    fn edits_reproduce_text() {
+        const PIECES: [&str; 9] = [
+            "a", "b", " ", "é", "e\u{301}", "日本", "\u{200d}", "\u{fe0f}", "🙂",
+        ];
+        // A fixed pseudo-random sequence of texts, which share prefixes often.
+        let mut seed = 0x2545_f491_u32;
+        let mut next = move |n: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed as usize % n
+        };
+        let mut tracker = TranscriptDiff::new();
+        let mut shown = String::new();
+        let mut text = String::new();
+        for _ in 0..2000 {
+            let keep = next(text.len() + 1);
+            while text.len() > keep {
+                text.pop();
+            }
+            for _ in 0..next(4) {
+                text.push_str(PIECES[next(PIECES.len())]);
+            }
+            let old = shown.clone();
+            match tracker.update(&text) {
+                Some(edit) => {
+                    assert_eq!(Some(edit.clone()), diff(&old, &text));
+                    if let Edit::ReplaceFrom { index, .. } = edit {
+                        assert!(old.is_char_boundary(index));
+                    }
+                    edit.apply(&mut shown);
+                }
+                None => assert_eq!(old, text),
+            }
+            assert_eq!(shown, text);
+            assert_eq!(tracker.shown(), text);
+        }
+    }
+    #[test]
+    fn json_round_trip() {
+        let json = serde_json::to_string(&sample()).unwrap();
+        let loaded: Transcript = serde_json::from_reader(json.as_bytes()).unwrap();
+        assert_eq!(loaded.full_text(), "hello world again");
+        assert_eq!(loaded.utterances()[2].offset, Duration::from_secs(10));
+        assert_eq!(loaded.duration(), sample().duration());
+        assert_eq!(serde_json::to_string(&loaded).unwrap(), json);
+    }
+    fn said(texts: &[&str]) -> Transcript {
+        let mut transcript = Transcript::new().with_edit_commands(EditCommands::english());
+        for text in texts {
+            transcript.push(
+                RecognizedText::from_text(*text).into_owned(),
+                Duration::ZERO,
+            );
+        }
+        transcript
+    }
+    #[test]
+    fn edit_commands() {
+        for (texts, expected) in [
+            (&["hello world", "scratch that"][..], ""),
+            (&["one", "two", "scratch that"], "one"),
+            (&["one", "two", "scratch that", "scratch that"], ""),
+            (&["scratch that", "one"], "one"),
+            (&["one two three", "delete last word"], "one two"),
+            (
+                &["one", "two three", "delete last word", "delete last word"],
+                "one",
+            ),
+            (&["one", "two", "delete last word", "delete last word"], ""),
+            (&["one", "two", "scratch that", "undo"], "one two"),
+            (&["one two", "delete last word", "undo"], "one two"),
+            (
+                &["a b", "c", "scratch that", "delete last word", "undo"],
+                "a b",
+            ),
+            (
+                &[
+                    "a b",
+                    "c",
+                    "scratch that",
+                    "delete last word",
+                    "undo",
+                    "undo",
+                ],
+                "a b c",
+            ),
+            (&["a", "undo"], "a"),
+            (&["a", "undo", "scratch that", "undo", "undo"], "a"),
+            // Empty utterances are skipped over.
+            (&["one", "two", "", "scratch that"], "one"),
+            // Commands only on their own, with any case and punctuation.
+            (&["one", "Scratch that."], ""),
+            (&["one", "DELETE LAST WORD!"], ""),
+            (&["one", "don't scratch that"], "one don't scratch that"),
+            (&["undo the knot"], "undo the knot"),
+        ] {
+            assert_eq!(said(texts).full_text(), expected, "{:?}", texts);
+        }
+        // Without commands, they are text like any other.
+        let mut plain = Transcript::new();
+        plain.push(RecognizedText::from_text("scratch that"), Duration::ZERO);
+        assert_eq!(plain.full_text(), "scratch that");
+    }
+    #[test]
+    fn delete_word_with_details() {
+        let mut transcript = Transcript::new().with_edit_commands(EditCommands::english());
+        transcript.push(
+            utterance(&[("hello", 0.5, 1.0), ("world", 1.0, 1.5)]),
+            Duration::from_secs(2),
+        );
+        transcript.push_event(
+            Event::Final(utterance(&[
+                ("delete", 0.0, 0.3),
+                ("last", 0.3, 0.5),
+                ("word", 0.5, 0.8),
+            ])),
+            Duration::from_secs(5),
+        );
+        assert_eq!(transcript.full_text(), "hello");
+        let words: Vec<_> = transcript
+            .words()
+            .map(|w| (w.word().to_string(), w.start()))
+            .collect();
+        assert_eq!(words, [("hello".to_string(), 2.5)]);
+        assert_eq!(transcript.duration(), Duration::from_secs(3));
+        assert!(transcript.undo());
+        assert_eq!(transcript.words().count(), 2);
+        assert!(!transcript.undo());
+    }
+    #[test]
+    fn delete_word_keeps_formatting() {
+        let mut u = RecognizedText::from_text("Dear sam,\nHow are you?  ").into_owned();
+        drop_last_word(&mut u);
+        assert_eq!(u.text, "Dear sam,\nHow are");
+        drop_last_word(&mut u);
+        drop_last_word(&mut u);
+        assert_eq!(u.text, "Dear sam,");
+        let mut cjk = utterance(&[("我", 0.0, 0.2), ("叫", 0.2, 0.4), ("李明", 0.4, 0.8)]);
+        assert_eq!(cjk.text, "我叫李明");
+        drop_last_word(&mut cjk);
+        assert_eq!((cjk.text.as_ref(), cjk.words().len()), ("我叫", 2));
+    }
+    #[test]
+    fn custom_edit_commands() {
+        let commands = EditCommands::new()
+            .with("oops", EditCommand::ScratchThat)
+            .with("  Back Space ", EditCommand::DeleteLastWord)
+            .with("", EditCommand::Undo);
+        assert_eq!(commands.find("Oops!"), Some(EditCommand::ScratchThat));
+        assert_eq!(
+            commands.find("back space"),
+            Some(EditCommand::DeleteLastWord)
+        );
+        assert_eq!(commands.find("undo"), None);
+        assert_eq!(commands.find(""), None);
+        let mut transcript = Transcript::new().with_edit_commands(commands);
+        for text in ["one two", "three", "oops", "back space", "scratch that"] {
+            transcript.push(RecognizedText::from_text(text), Duration::ZERO);
+        }
+        assert_eq!(transcript.full_text(), "one scratch that");
+        assert!(transcript.apply(EditCommand::Undo));
+        assert_eq!(transcript.full_text(), "one two scratch that");
+    }
+}
@@ -0,0 +1,248 @@
+use crate::Model;
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Files and directories every model has, relative to its directory.
+const REQUIRED: [&str; 3] = ["am/final.mdl", "conf/mfcc.conf", "graph"];
+
+/// Feature configurations that may declare the sample rate of a model, in order.
+const FEATURE_CONFS: [&str; 2] = ["conf/mfcc.conf", "conf/fbank.conf"];
+
+/// Extensions models are usually downloaded with.
+const ARCHIVE_EXTENSIONS: [&str; 6] = ["zip", "gz", "tgz", "tar", "bz2", "xz"];
+
+/// Why a path doesn't hold a model, see `Model::validate`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModelValidationError {
+    NotFound(PathBuf),
+    /// The path is a downloaded archive, which has to be extracted first.
+    Archive(PathBuf),
+    /// The path is a file, but not a known archive.
+    NotADirectory(PathBuf),
+    /// The directory holds a single directory that looks like a model,
+    /// use that one instead.
+    Nested(PathBuf),
+    /// The directory lacks these files or directories, relative to it.
+    /// It may be incomplete, for example after an interrupted download.
+    Missing(Vec<PathBuf>),
+}
+
+impl Model {
+    /// Checks that `path` looks like a model directory, without loading it.
+    ///
+    /// Only the layout is checked, a model that passes can still fail to load.
+    pub fn validate<P: AsRef<Path>>(path: P) -> Result<(), ModelValidationError> {
+        let path = path.as_ref();
+        let metadata =
+            fs::metadata(path).map_err(|_| ModelValidationError::NotFound(path.to_path_buf()))?;
+        if !metadata.is_dir() {
+            return Err(if is_archive(path) {
+                ModelValidationError::Archive(path.to_path_buf())
+            } else {
+                ModelValidationError::NotADirectory(path.to_path_buf())
+            });
+        }
+        let missing = missing(path);
+        if missing.is_empty() {
+            return Ok(());
+        }
+        if let Some(nested) = nested_model(path) {
+            return Err(ModelValidationError::Nested(nested));
+        }
+        Err(ModelValidationError::Missing(missing))
+    }
+}
+
+fn missing(dir: &Path) -> Vec<PathBuf> {
+    REQUIRED
+        .iter()
+        .map(PathBuf::from)
+        .filter(|required| !dir.join(required).exists())
+        .collect()
+}
+
+/// The sample rate declared in the feature configuration of the model in `dir`,
+/// as `--sample-frequency=16000`. None if there's no configuration or it leaves
+/// the rate out.
+pub(crate) fn declared_sample_rate(dir: &Path) -> Option<f32> {
+    FEATURE_CONFS.iter().find_map(|conf| {
+        let conf = fs::read_to_string(dir.join(conf)).ok()?;
+        conf.lines()
+            .filter_map(|line| {
+                line.split('#')
+                    .next()?
+                    .trim()
+                    .strip_prefix("--sample-frequency=")
+            })
+            .filter_map(|hz| hz.trim().parse::<f32>().ok())
+            // Kaldi takes the last of repeated options.
+            .rfind(|hz| hz.is_finite() && *hz > 0.0)
+    })
+}
+
+/// A model extracted into a directory of its own inside `dir`,
+/// which happens when an archive is extracted into a directory named after it.
+fn nested_model(dir: &Path) -> Option<PathBuf> {
+    let mut subdirs = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir());
+    let only = subdirs.next()?;
+    if subdirs.next().is_some() || !missing(&only).is_empty() {
+        return None;
+    }
+    Some(only)
+}
+
+fn is_archive(path: &Path) -> bool {
+    let known_extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| ARCHIVE_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()));
+    if known_extension {
+        return true;
+    }
+    // Zip and gzip files, whatever they are named.
+    let mut magic = [0; 4];
+    let read = fs::File::open(path).and_then(|mut f| f.read_exact(&mut magic));
+    read.is_ok() && (magic == *b"PK\x03\x04" || magic[..2] == [0x1f, 0x8b])
+}
+
+impl fmt::Display for ModelValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModelValidationError::NotFound(path) => {
+                write!(f, "{} does not exist", path.display())
+            }
+            ModelValidationError::Archive(path) => write!(
+                f,
+                "{} is an archive, extract it and use the directory inside",
+                path.display()
+            ),
+            ModelValidationError::NotADirectory(path) => {
+                write!(f, "{} is not a directory", path.display())
+            }
+            ModelValidationError::Nested(path) => {
+                write!(f, "the model seems to be in {}", path.display())
+            }
+            ModelValidationError::Missing(paths) => {
+                let paths: Vec<_> = paths.iter().map(|p| p.display().to_string()).collect();
+                write!(
+                    f,
+                    "missing {}, the model may be incomplete",
+                    paths.join(", ")
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates an empty directory in the temporary directory, with `files` in it.
+    fn layout(name: &str, files: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vosk-validate-{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for file in files {
+            let path = dir.join(file);
+            if file.ends_with('/') {
+                fs::create_dir_all(path).unwrap();
+            } else {
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, b"").unwrap();
+            }
+        }
+        dir
+    }
+    const COMPLETE: [&str; 4] = [
+        "am/final.mdl",
+        "conf/mfcc.conf",
+        "conf/model.conf",
+        "graph/",
+    ];
+
+    #[test]
+    fn complete() {
+        assert_eq!(Model::validate(layout("complete", &COMPLETE)), Ok(()));
+    }
+    #[test]
+    fn not_found() {
+        let path = std::env::temp_dir().join("vosk-validate-missing");
+        assert_eq!(
+            Model::validate(&path),
+            Err(ModelValidationError::NotFound(path))
+        );
+    }
+    #[test]
+    fn truncated() {
+        let dir = layout("truncated", &["conf/mfcc.conf", "graph/"]);
+        assert_eq!(
+            Model::validate(dir),
+            Err(ModelValidationError::Missing(vec![PathBuf::from(
+                "am/final.mdl"
+            )]))
+        );
+    }
+    #[test]
+    fn empty() {
+        match Model::validate(layout("empty", &[])) {
+            Err(ModelValidationError::Missing(missing)) => assert_eq!(missing.len(), 3),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+    #[test]
+    fn nested() {
+        let files: Vec<_> = COMPLETE
+            .iter()
+            .map(|f| format!("vosk-model-small/{}", f))
+            .collect();
+        let files: Vec<_> = files.iter().map(String::as_str).collect();
+        let dir = layout("nested", &files);
+        assert_eq!(
+            Model::validate(&dir),
+            Err(ModelValidationError::Nested(dir.join("vosk-model-small")))
+        );
+    }
+    #[test]
+    fn archive() {
+        let dir = layout("archive", &[]);
+        let zip = dir.join("model.zip");
+        fs::write(&zip, b"").unwrap();
+        assert_eq!(
+            Model::validate(&zip),
+            Err(ModelValidationError::Archive(zip))
+        );
+        let renamed = dir.join("model");
+        fs::write(&renamed, b"\x1f\x8b\x08\x00").unwrap();
+        assert_eq!(
+            Model::validate(&renamed),
+            Err(ModelValidationError::Archive(renamed))
+        );
+        let text = dir.join("README");
+        fs::write(&text, b"hello").unwrap();
+        assert_eq!(
+            Model::validate(&text),
+            Err(ModelValidationError::NotADirectory(text))
+        );
+    }
+    #[test]
+    fn sample_rate() {
+        let dir = layout("rate", &["conf/mfcc.conf", "conf/fbank.conf"]);
+        assert_eq!(declared_sample_rate(&dir), None);
+        fs::write(dir.join("conf/fbank.conf"), "--sample-frequency=8000\n").unwrap();
+        assert_eq!(declared_sample_rate(&dir), Some(8000.0));
+        let mfcc = "--use-energy=false\n# --sample-frequency=44100\n --sample-frequency=16000 # wideband\n";
+        fs::write(dir.join("conf/mfcc.conf"), mfcc).unwrap();
+        assert_eq!(declared_sample_rate(&dir), Some(16000.0));
+        // Models have one of the two, but if both are there, the first that declares a rate wins.
+        fs::write(dir.join("conf/mfcc.conf"), "--sample-frequency=fast").unwrap();
+        assert_eq!(declared_sample_rate(&dir), Some(8000.0));
+        assert_eq!(declared_sample_rate(&dir.join("nothing")), None);
+    }
+}
@@ -0,0 +1,221 @@
+use crate::{Error, Model};
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+/// How a word had to be changed to be found in a model, see `Model::find_word_normalized`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WordForm {
+    /// As given.
+    Exact,
+    Lowercase,
+    /// In Unicode canonical composition, then lowercase.
+    #[cfg(feature = "normalization")]
+    Nfc,
+    /// In Unicode compatibility composition, then lowercase.
+    #[cfg(feature = "normalization")]
+    Nfkc,
+}
+
+/// A word found by `Model::find_word_normalized`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WordMatch {
+    pub symbol: u32,
+    /// The form of the word the model knows, which is the one to use in grammars.
+    pub word: String,
+    pub form: WordForm,
+}
+
+/// Where models keep the list of words and their symbols, in order of preference.
+const WORD_LISTS: [&str; 2] = ["graph/words.txt", "words.txt"];
+
+impl Model {
+    /// The words the model can recognize, with their symbols as returned by `find_word`.
+    ///
+    /// They are read lazily from the word list of the model, usually `graph/words.txt`.
+    /// Epsilon, sentence boundaries and disambiguation symbols such as `#0` are left out,
+    /// as well as malformed lines. Reading stops early if the file can't be read to the end.
+    ///
+    /// Fails with `Error::NoWordList` if the model doesn't include the list.
+    pub fn vocabulary(&self) -> Result<impl Iterator<Item = (String, u32)>, Error> {
+        vocabulary_at(self.path())
+    }
+    /// The number of words `vocabulary` returns, which takes reading the whole list.
+    pub fn vocabulary_size(&self) -> Result<usize, Error> {
+        Ok(self.vocabulary()?.count())
+    }
+    /// Looks `word` up as given, then lowercased, then with the `normalization` feature,
+    /// in NFC and NFKC and lowercased, as models usually only have lowercase words.
+    ///
+    /// ```no_run
+    /// # use vosk::{Model, WordForm};
+    /// # let model = Model::new("model").unwrap();
+    /// let found = model.find_word_normalized("Hello").unwrap();
+    /// assert_eq!((found.word.as_str(), found.form), ("hello", WordForm::Lowercase));
+    /// ```
+    pub fn find_word_normalized(&self, word: &str) -> Option<WordMatch> {
+        find_normalized(word, |w| self.find_word(w).map(|symbol| symbol as u32))
+    }
+}
+
+/// Tries the forms of `word` in turn with `lookup`.
+pub(crate) fn find_normalized<F>(word: &str, lookup: F) -> Option<WordMatch>
+where
+    F: Fn(&str) -> Option<u32>,
+{
+    let mut forms = vec![(WordForm::Exact, Cow::Borrowed(word))];
+    forms.push((WordForm::Lowercase, Cow::Owned(word.to_lowercase())));
+    #[cfg(feature = "normalization")]
+    {
+        use crate::Normalization;
+        let nfc = Normalization::Nfc.apply(Cow::Borrowed(word));
+        forms.push((WordForm::Nfc, Cow::Owned(nfc.to_lowercase())));
+        let nfkc = Normalization::Nfkc.apply(Cow::Borrowed(word));
+        forms.push((WordForm::Nfkc, Cow::Owned(nfkc.to_lowercase())));
+    }
+    let mut tried: Vec<&str> = Vec::new();
+    for (form, candidate) in &forms {
+        if tried.contains(&candidate.as_ref()) {
+            continue;
+        }
+        tried.push(candidate);
+        if let Some(symbol) = lookup(candidate) {
+            return Some(WordMatch {
+                symbol,
+                word: candidate.to_string(),
+                form: *form,
+            });
+        }
+    }
+    None
+}
+
+fn vocabulary_at(model_dir: &Path) -> Result<impl Iterator<Item = (String, u32)>, Error> {
+    let path = word_list(model_dir).ok_or_else(|| Error::NoWordList(model_dir.into()))?;
+    let lines = BufReader::new(File::open(path)?).lines();
+    Ok(lines.map_while(Result::ok).filter_map(|line| {
+        let mut fields = line.split_whitespace();
+        let word = fields.next()?;
+        let symbol = fields.next()?.parse().ok()?;
+        if fields.next().is_some() || is_special(word) {
+            return None;
+        }
+        Some((word.to_string(), symbol))
+    }))
+}
+
+fn word_list(model_dir: &Path) -> Option<PathBuf> {
+    WORD_LISTS
+        .iter()
+        .map(|name| model_dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// Symbols of the decoding graph that aren't words.
+fn is_special(word: &str) -> bool {
+    let disambiguation = word
+        .strip_prefix('#')
+        .is_some_and(|d| !d.is_empty() && d.bytes().all(|b| b.is_ascii_digit()));
+    matches!(word, "<eps>" | "<s>" | "</s>") || disambiguation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::fake_model;
+    use std::collections::HashMap;
+    use std::fs;
+
+    fn model_dir(name: &str, list: &str, words: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vosk-vocabulary-{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join(list);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, words).unwrap();
+        dir
+    }
+
+    #[test]
+    fn words_with_symbols() {
+        let dir = model_dir(
+            "graph",
+            "graph/words.txt",
+            "<eps> 0\n!SIL 1\n<UNK> 2\na 3\nabout 4\nhello 5\n#0 6\n<s> 7\n</s> 8\n",
+        );
+        let model = fake_model(dir.to_str().unwrap());
+        let words: Vec<_> = model.vocabulary().unwrap().collect();
+        assert_eq!(
+            words,
+            vec![
+                ("!SIL".to_string(), 1),
+                ("<UNK>".to_string(), 2),
+                ("a".to_string(), 3),
+                ("about".to_string(), 4),
+                ("hello".to_string(), 5),
+            ]
+        );
+        assert_eq!(model.vocabulary_size().unwrap(), 5);
+    }
+    #[test]
+    fn top_level_list() {
+        let dir = model_dir("top", "words.txt", "#hashtag 1\nok 2\nbroken\nbad x\n\n");
+        let model = fake_model(dir.to_str().unwrap());
+        let words: Vec<_> = model.vocabulary().unwrap().collect();
+        assert_eq!(
+            words,
+            vec![("#hashtag".to_string(), 1), ("ok".to_string(), 2)]
+        );
+    }
+    #[test]
+    fn non_ascii_words() {
+        let dir = model_dir(
+            "non-ascii",
+            "graph/words.txt",
+            "<eps> 0\nпривет 1\n日本 2\nécole 3\n#1 4\n#١ 5\n",
+        );
+        let model = fake_model(dir.to_str().unwrap());
+        let words: Vec<_> = model.vocabulary().unwrap().collect();
+        assert_eq!(
+            words,
+            vec![
+                ("привет".to_string(), 1),
+                ("日本".to_string(), 2),
+                ("école".to_string(), 3),
+                ("#١".to_string(), 5),
+            ]
+        );
+        assert!(!is_special("#"));
+    }
+    #[test]
+    fn missing_list() {
+        let dir = model_dir("missing", "am/final.mdl", "");
+        let model = fake_model(dir.to_str().unwrap());
+        assert_eq!(model.vocabulary_size(), Err(Error::NoWordList(dir)));
+    }
+    #[test]
+    fn normalization_ladder() {
+        // "ﬁ" is a ligature, "é" is written as "e" and a combining accent.
+        let dir = model_dir(
+            "ladder",
+            "graph/words.txt",
+            "<eps> 0\nHELLO 1\nhello 2\ncafé 3\nfine 4\n",
+        );
+        let model = fake_model(dir.to_str().unwrap());
+        let words: HashMap<String, u32> = model.vocabulary().unwrap().collect();
+        let find = |word| {
+            find_normalized(word, |w| words.get(w).copied()).map(|found| (found.symbol, found.form))
+        };
+        assert_eq!(find("HELLO"), Some((1, WordForm::Exact)));
+        assert_eq!(find("Hello"), Some((2, WordForm::Lowercase)));
+        assert_eq!(find("Café"), Some((3, WordForm::Lowercase)));
+        #[cfg(feature = "normalization")]
+        {
+            assert_eq!(find("Cafe\u{301}"), Some((3, WordForm::Nfc)));
+            assert_eq!(find("\u{fb01}ne"), Some((4, WordForm::Nfkc)));
+        }
+        assert_eq!(find("goodbye"), None);
+        let found = find_normalized("Fine", |w| words.get(w).copied()).unwrap();
+        assert_eq!(found.word, "fine");
+    }
+}
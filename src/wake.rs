@@ -0,0 +1,385 @@
+//! Listening for a wake word, keeping the audio just before it.
+//!
+//! A `WakeWordListener` runs a recognizer restricted to the wake phrases.
+//! When one is heard, the recent audio is handed over in a `WakeEvent`,
+//! so that a full recognizer doesn't miss what was said right after it:
+//!
+//! ```no_run
+//! # use vosk::wake::WakeWordListener;
+//! # use vosk::{Model, Recognizer};
+//! # fn main() -> Result<(), vosk::Error> {
+//! # let model = Model::new("model")?;
+//! # let mut chunks = std::iter::repeat(vec![0i16; 1600]);
+//! let mut listener = WakeWordListener::new(&model, 16000.0, &["hey computer"])?;
+//! let mut full = Recognizer::new(&model, 16000.0);
+//! while let Some(chunk) = chunks.next() {
+//!     let Some(wake) = listener.feed(&chunk) else {
+//!         continue;
+//!     };
+//!     // Start with what followed the wake phrase, then keep feeding until the command ends.
+//!     let mut done = wake.feed_after_phrase(&mut full);
+//!     while !done {
+//!         done = full.accept_waveform(&chunks.next().unwrap());
+//!     }
+//!     println!("{}", full.result().text);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{Error, Grammar, Model, RecognizedText, Recognizer, SampleRate};
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::time::Duration;
+
+/// How a `WakeWordListener` decides that a wake phrase was said.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WakeOptions {
+    /// How much of the audio before a detection is kept.
+    pub pre_roll: Duration,
+    /// Mean confidence of the words of the phrase below which it's ignored.
+    pub min_confidence: Option<f32>,
+    /// How long after a detection other detections are ignored,
+    /// as the same phrase can be reported more than once.
+    pub refractory: Duration,
+    /// Whether to detect phrases in partial results, which is quicker but without
+    /// confidence, rather than waiting for the utterance to end.
+    pub partial: bool,
+}
+
+impl Default for WakeOptions {
+    fn default() -> Self {
+        WakeOptions {
+            pre_roll: Duration::from_millis(1500),
+            min_confidence: Some(0.7),
+            refractory: Duration::from_secs(2),
+            partial: false,
+        }
+    }
+}
+
+/// A wake phrase was heard.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WakeEvent {
+    pub phrase: String,
+    /// Mean confidence of the words of the phrase, None when detected in a partial result.
+    pub confidence: Option<f32>,
+    /// The latest audio, up to the end of the chunk the phrase was detected in.
+    pub pre_roll: Vec<i16>,
+    /// Where the phrase ends in `pre_roll`, when the recognizer told.
+    pub phrase_end: Option<usize>,
+}
+
+impl WakeEvent {
+    /// The audio of `pre_roll` after the phrase, or all of it when the end isn't known.
+    pub fn after_phrase(&self) -> &[i16] {
+        &self.pre_roll[self.phrase_end.unwrap_or(0)..]
+    }
+    /// Feeds the audio after the phrase to `recognizer`,
+    /// returning true if it completed an utterance.
+    pub fn feed_after_phrase(&self, recognizer: &mut Recognizer) -> bool {
+        recognizer.accept_waveform(self.after_phrase())
+    }
+}
+
+/// Listens for wake phrases, see the module documentation.
+#[derive(Debug)]
+pub struct WakeWordListener {
+    recognizer: Recognizer,
+    ring: PreRoll,
+    detector: Detector,
+}
+
+impl WakeWordListener {
+    /// Listens for `phrases` with the default options.
+    pub fn new<S: AsRef<str>>(
+        model: &Model,
+        sample_rate: f32,
+        phrases: &[S],
+    ) -> Result<Self, Error> {
+        WakeWordListener::with_options(model, sample_rate, phrases, WakeOptions::default())
+    }
+    /// Fails with `Error::OutOfVocabulary` if the model doesn't know some of the words,
+    /// and with `Error::InvalidSampleRate` unless the rate is finite and positive.
+    pub fn with_options<S: AsRef<str>>(
+        model: &Model,
+        sample_rate: f32,
+        phrases: &[S],
+        opts: WakeOptions,
+    ) -> Result<Self, Error> {
+        let rate = SampleRate::try_from(sample_rate)?;
+        let phrases: Vec<Vec<String>> = phrases
+            .iter()
+            .map(|p| p.as_ref().split_whitespace().map(String::from).collect())
+            .filter(|p: &Vec<String>| !p.is_empty())
+            .collect();
+        let missing = model.missing_words_exact(phrases.iter().flatten().map(String::as_str));
+        if !missing.is_empty() {
+            return Err(Error::OutOfVocabulary(
+                missing.into_iter().map(String::from).collect(),
+            ));
+        }
+        let grammar = Grammar::new(phrases.iter().map(|p| p.join(" "))).with_unknown();
+        let mut recognizer = Recognizer::try_with_grammar(model, rate.hz(), &grammar)?;
+        recognizer.set_words(true);
+        recognizer.set_keep_count_on_reset(true);
+        let ring = PreRoll::new((opts.pre_roll.as_secs_f64() * sample_rate as f64) as usize);
+        Ok(WakeWordListener {
+            recognizer,
+            ring,
+            detector: Detector::new(phrases, opts, sample_rate),
+        })
+    }
+    /// Feeds audio, returning an event when a wake phrase was heard in it.
+    pub fn feed(&mut self, wave: &[i16]) -> Option<WakeEvent> {
+        self.ring.push(wave);
+        let completed = self.recognizer.accept_waveform(wave);
+        let now = self.recognizer.samples_processed();
+        let detection = if completed {
+            self.detector.final_result(&self.recognizer.result(), now)
+        } else if self.detector.opts.partial {
+            self.detector
+                .partial_result(&self.recognizer.partial_result().partial, now)
+        } else {
+            None
+        };
+        let (phrase, confidence, end) = detection?;
+        if !completed {
+            // Don't detect the same words again when the utterance ends.
+            self.recognizer.reset();
+        }
+        let start = now - self.ring.len() as u64;
+        Some(WakeEvent {
+            phrase,
+            confidence,
+            phrase_end: end.map(|end| end.clamp(start, now).saturating_sub(start) as usize),
+            pre_roll: self.ring.to_vec(),
+        })
+    }
+    pub fn recognizer(&self) -> &Recognizer {
+        &self.recognizer
+    }
+    pub fn recognizer_mut(&mut self) -> &mut Recognizer {
+        &mut self.recognizer
+    }
+}
+
+/// The latest samples, up to a capacity.
+#[derive(Debug, Clone)]
+pub(crate) struct PreRoll {
+    samples: VecDeque<i16>,
+    capacity: usize,
+}
+
+impl PreRoll {
+    pub(crate) fn new(capacity: usize) -> PreRoll {
+        PreRoll {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+    pub(crate) fn push(&mut self, wave: &[i16]) {
+        let kept = &wave[wave.len().saturating_sub(self.capacity)..];
+        let overflow = (self.samples.len() + kept.len()).saturating_sub(self.capacity);
+        self.samples.drain(..overflow);
+        self.samples.extend(kept);
+    }
+    pub(crate) fn len(&self) -> usize {
+        self.samples.len()
+    }
+    pub(crate) fn to_vec(&self) -> Vec<i16> {
+        self.samples.iter().copied().collect()
+    }
+}
+
+/// Finds wake phrases in results, applying the confidence threshold and refractory period.
+#[derive(Debug, Clone)]
+struct Detector {
+    phrases: Vec<Vec<String>>,
+    opts: WakeOptions,
+    sample_rate: f32,
+    /// Position in samples of the last detection.
+    last: Option<u64>,
+}
+
+/// A phrase, the mean confidence of its words and the sample it ends at.
+type Detection = (String, Option<f32>, Option<u64>);
+
+impl Detector {
+    fn new(phrases: Vec<Vec<String>>, opts: WakeOptions, sample_rate: f32) -> Detector {
+        Detector {
+            phrases,
+            opts,
+            sample_rate,
+            last: None,
+        }
+    }
+    /// Looks for a phrase in a finalized result, with `now` the number of samples processed.
+    fn final_result(&mut self, result: &RecognizedText, now: u64) -> Option<Detection> {
+        let words = result.words();
+        let found = self.phrases.iter().find_map(|phrase| {
+            let at = words
+                .windows(phrase.len())
+                .position(|window| window.iter().zip(phrase).all(|(w, p)| w.word() == p))?;
+            let words = &words[at..at + phrase.len()];
+            let confidence = words.iter().map(|w| w.conf()).sum::<f32>() / words.len() as f32;
+            let end =
+                (words[words.len() - 1].end() as f64 * self.sample_rate as f64).round() as u64;
+            Some((phrase.join(" "), Some(confidence), Some(end)))
+        });
+        // Without word details, only the text can be searched.
+        let found = match found {
+            None if result.result.is_none() => self.in_text(&result.text),
+            found => found,
+        };
+        let (_, confidence, _) = found.as_ref()?;
+        if let (Some(min), Some(confidence)) = (self.opts.min_confidence, confidence) {
+            if *confidence < min {
+                return None;
+            }
+        }
+        self.trigger(now, found)
+    }
+    fn partial_result(&mut self, text: &str, now: u64) -> Option<Detection> {
+        let found = self.in_text(text);
+        self.trigger(now, found)
+    }
+    fn in_text(&self, text: &str) -> Option<Detection> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        self.phrases
+            .iter()
+            .find(|phrase| {
+                words
+                    .windows(phrase.len())
+                    .any(|window| window == phrase.as_slice())
+            })
+            .map(|phrase| (phrase.join(" "), None, None))
+    }
+    /// Lets `found` through unless it's within the refractory period of the last detection.
+    fn trigger(&mut self, now: u64, found: Option<Detection>) -> Option<Detection> {
+        let found = found?;
+        let refractory = (self.opts.refractory.as_secs_f64() * self.sample_rate as f64) as u64;
+        if self.last.is_some_and(|last| now < last + refractory) {
+            return None;
+        }
+        self.last = Some(now);
+        Some(found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RecognizedWord;
+
+    fn heard(words: &[(&str, f32, f32, f32)]) -> RecognizedText<'static> {
+        let words = words
+            .iter()
+            .map(|&(w, conf, start, end)| RecognizedWord::new(w.to_string(), conf, start, end))
+            .collect();
+        RecognizedText::from_words(words)
+    }
+    fn detector(opts: WakeOptions) -> Detector {
+        let phrases = vec![
+            vec!["hey".to_string(), "computer".to_string()],
+            vec!["jarvis".to_string()],
+        ];
+        Detector::new(phrases, opts, 100.0)
+    }
+
+    #[test]
+    fn ring_buffer() {
+        let mut ring = PreRoll::new(5);
+        ring.push(&[1, 2, 3]);
+        assert_eq!(ring.to_vec(), vec![1, 2, 3]);
+        ring.push(&[4, 5, 6]);
+        assert_eq!(ring.to_vec(), vec![2, 3, 4, 5, 6]);
+        ring.push(&[7, 8, 9, 10, 11, 12, 13]);
+        assert_eq!(ring.to_vec(), vec![9, 10, 11, 12, 13]);
+        ring.push(&[]);
+        assert_eq!(ring.len(), 5);
+        let mut empty = PreRoll::new(0);
+        empty.push(&[1, 2]);
+        assert_eq!(empty.to_vec(), Vec::<i16>::new());
+    }
+    #[test]
+    fn phrase_in_result() {
+        let mut detector = detector(WakeOptions::default());
+        let result = heard(&[
+            ("[unk]", 0.5, 0.0, 0.5),
+            ("hey", 0.9, 0.6, 0.8),
+            ("computer", 0.8, 0.8, 1.3),
+            ("[unk]", 0.4, 1.4, 1.6),
+        ]);
+        let (phrase, confidence, end) = detector.final_result(&result, 160).unwrap();
+        assert_eq!(phrase, "hey computer");
+        assert!((confidence.unwrap() - 0.85).abs() < 1e-6);
+        assert_eq!(end, Some(130));
+        let apart = heard(&[
+            ("hey", 0.9, 3.0, 3.2),
+            ("[unk]", 0.9, 3.2, 3.4),
+            ("computer", 0.9, 3.4, 3.8),
+        ]);
+        assert_eq!(detector.final_result(&apart, 400), None);
+    }
+    #[test]
+    fn low_confidence() {
+        let mut detector = detector(WakeOptions::default());
+        let unsure = heard(&[("jarvis", 0.5, 0.0, 0.5)]);
+        assert_eq!(detector.final_result(&unsure, 50), None);
+        // A rejected phrase doesn't start the refractory period.
+        let sure = heard(&[("jarvis", 0.9, 0.6, 1.0)]);
+        assert!(detector.final_result(&sure, 100).is_some());
+        let mut lenient = self::detector(WakeOptions {
+            min_confidence: None,
+            ..WakeOptions::default()
+        });
+        assert!(lenient.final_result(&unsure, 50).is_some());
+    }
+    #[test]
+    fn refractory_period() {
+        let mut detector = detector(WakeOptions::default());
+        let result = heard(&[("jarvis", 0.9, 0.0, 0.5)]);
+        assert!(detector.final_result(&result, 100).is_some());
+        // Within two seconds, at 100 samples per second.
+        assert_eq!(detector.final_result(&result, 250), None);
+        assert_eq!(detector.partial_result("jarvis", 299), None);
+        assert!(detector.final_result(&result, 300).is_some());
+    }
+    #[test]
+    fn partial_and_text_only() {
+        let mut detector = detector(WakeOptions::default());
+        assert_eq!(detector.partial_result("hey", 10), None);
+        assert_eq!(
+            detector.partial_result("[unk] hey computer", 20),
+            Some(("hey computer".to_string(), None, None))
+        );
+        let mut detector = self::detector(WakeOptions::default());
+        let text_only = RecognizedText::from_text("jarvis");
+        assert_eq!(
+            detector.final_result(&text_only, 10),
+            Some(("jarvis".to_string(), None, None))
+        );
+    }
+    #[test]
+    fn audio_after_phrase() {
+        let event = WakeEvent {
+            phrase: "jarvis".to_string(),
+            confidence: Some(0.9),
+            pre_roll: vec![1, 2, 3, 4],
+            phrase_end: Some(3),
+        };
+        assert_eq!(event.after_phrase(), &[4]);
+        let unknown = WakeEvent {
+            phrase_end: None,
+            ..event
+        };
+        assert_eq!(unknown.after_phrase(), &[1, 2, 3, 4]);
+    }
+    #[test]
+    fn invalid_sample_rate() {
+        let model = crate::test_util::fake_model("model");
+        let listener = WakeWordListener::new(&model, 0.0, &["hey computer"]);
+        assert_eq!(listener.err(), Some(Error::InvalidSampleRate(0.0)));
+    }
+}
@@ -0,0 +1,199 @@
+//! Recordings of speech with known transcripts, through the whole path from libvosk
+//! to parsed results. The fixtures are in `tests/fixtures/golden`, see its README.
+//!
+//! Each test with a model returns early when model tests are skipped. Without a
+//! grammar the text may be off by the word error rate allowed for the fixture;
+//! with the transcript as the grammar it has to match exactly.
+
+mod support;
+
+use riff_wave::WaveReader;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use vosk::eval::{wer, EvalOptions};
+use vosk::source::{transcribe_source, MemorySource};
+use vosk::{Model, Recognizer, UtteranceOwned};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/golden");
+
+/// Small enough to keep the repository small, long enough for a sentence or two.
+const MAX_FIXTURE_BYTES: u64 = 200 * 1024;
+
+struct Fixture {
+    file: String,
+    max_wer: f64,
+    transcript: String,
+}
+
+impl Fixture {
+    fn path(&self) -> PathBuf {
+        Path::new(FIXTURES).join(&self.file)
+    }
+    /// The sample rate and samples, which have to be 16-bit mono.
+    fn audio(&self) -> (u32, Vec<i16>) {
+        let file = File::open(self.path()).unwrap_or_else(|e| panic!("{}: {}", self.file, e));
+        let mut wave = WaveReader::new(BufReader::new(file))
+            .unwrap_or_else(|e| panic!("{}: {:?}", self.file, e));
+        let format = &wave.pcm_format;
+        assert_eq!(
+            (format.num_channels, format.bits_per_sample),
+            (1, 16),
+            "{} has to be 16-bit mono",
+            self.file
+        );
+        let rate = format.sample_rate;
+        let samples = std::iter::from_fn(|| wave.read_sample_i16().ok()).collect();
+        (rate, samples)
+    }
+}
+
+/// The fixtures listed in `transcripts.txt`, of which there has to be at least one.
+fn fixtures() -> Vec<Fixture> {
+    let manifest = Path::new(FIXTURES).join("transcripts.txt");
+    let manifest = fs::read_to_string(&manifest).unwrap();
+    let fixtures: Vec<Fixture> = manifest
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [file, max_wer, transcript] = fields[..] else {
+                panic!(
+                    "transcripts.txt:{}: expected 3 fields separated by tabs",
+                    i + 1
+                );
+            };
+            let max_wer = max_wer
+                .parse()
+                .ok()
+                .filter(|wer| (0.0..=1.0).contains(wer))
+                .unwrap_or_else(|| panic!("transcripts.txt:{}: bad max WER {}", i + 1, max_wer));
+            Fixture {
+                file: file.to_string(),
+                max_wer,
+                transcript: transcript.trim().to_string(),
+            }
+        })
+        .collect();
+    // Otherwise every golden test passes without checking anything.
+    assert!(!fixtures.is_empty(), "transcripts.txt lists no fixtures");
+    fixtures
+}
+
+fn text(utterances: &[UtteranceOwned]) -> String {
+    let texts: Vec<&str> = utterances.iter().map(|u| u.text.as_ref()).collect();
+    texts.join(" ")
+}
+
+/// Runs `recognize` over every fixture, failing with all the mismatches at once.
+fn check_all<F>(how: &str, exact: bool, mut recognize: F)
+where
+    F: FnMut(&Model, &Fixture) -> String,
+{
+    let Some(model) = support::model() else {
+        return;
+    };
+    let mut failures = Vec::new();
+    for fixture in fixtures() {
+        let hypothesis = recognize(&model, &fixture);
+        let report = wer(&fixture.transcript, &hypothesis, &EvalOptions::default());
+        let max_wer = if exact { 0.0 } else { fixture.max_wer };
+        if report.wer > max_wer {
+            failures.push(format!(
+                "{}: WER {:.2} is above {:.2}\n  expected: {}\n  got:      {}",
+                fixture.file, report.wer, max_wer, fixture.transcript, hypothesis
+            ));
+        }
+    }
+    assert!(failures.is_empty(), "{}:\n{}", how, failures.join("\n"));
+}
+
+fn transcribe(mut recognizer: Recognizer, rate: u32, samples: Vec<i16>) -> Vec<UtteranceOwned> {
+    recognizer.set_words(true);
+    transcribe_source(&mut recognizer, MemorySource::new(samples, rate)).unwrap()
+}
+
+#[test]
+fn fixtures_are_valid() {
+    let fixtures = fixtures();
+    for fixture in &fixtures {
+        let size = fs::metadata(fixture.path())
+            .unwrap_or_else(|e| panic!("{}: {}", fixture.file, e))
+            .len();
+        assert!(
+            size <= MAX_FIXTURE_BYTES,
+            "{} is {} bytes, more than {}",
+            fixture.file,
+            size,
+            MAX_FIXTURE_BYTES
+        );
+        let (_, samples) = fixture.audio();
+        assert!(!samples.is_empty(), "{} has no audio", fixture.file);
+        let spelled = fixture.transcript.to_lowercase();
+        assert!(
+            spelled == fixture.transcript
+                && spelled
+                    .chars()
+                    .all(|c| c.is_alphabetic() || c == ' ' || c == '\''),
+            "the transcript of {} isn't lowercase words",
+            fixture.file
+        );
+    }
+    for entry in fs::read_dir(FIXTURES).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|ext| ext == "wav") {
+            let name = path.file_name().unwrap().to_str().unwrap();
+            assert!(
+                fixtures.iter().any(|f| f.file == name),
+                "{} isn't in transcripts.txt",
+                name
+            );
+        }
+    }
+}
+
+#[test]
+fn golden_recognizer() {
+    check_all("Recognizer::new", false, |model, fixture| {
+        let (rate, samples) = fixture.audio();
+        let utterances = transcribe(Recognizer::new(model, rate as f32), rate, samples);
+        // The words and the text come from different parts of the JSON.
+        for utterance in &utterances {
+            let words: Vec<&str> = utterance.words().iter().map(|w| w.word()).collect();
+            assert_eq!(words.join(" "), utterance.text, "{}", fixture.file);
+        }
+        text(&utterances)
+    });
+}
+
+#[test]
+fn golden_vocabulary() {
+    check_all("Recognizer::with_vocabulary", false, |model, fixture| {
+        let (rate, samples) = fixture.audio();
+        let recognizer = Recognizer::with_vocabulary(model, rate as f32, &fixture.transcript);
+        text(&transcribe(recognizer, rate, samples))
+    });
+}
+
+#[test]
+fn golden_grammar() {
+    check_all("Recognizer::with_grammar", true, |model, fixture| {
+        let (rate, samples) = fixture.audio();
+        let phrases = [fixture.transcript.as_str(), "[unk]"];
+        let recognizer = Recognizer::with_grammar(
+            model,
+            rate as f32,
+            phrases.iter().map(|p| p.split_whitespace()),
+        );
+        text(&transcribe(recognizer, rate, samples))
+    });
+}
+
+#[cfg(feature = "audio-decode")]
+#[test]
+fn golden_wav_file() {
+    check_all("decode::transcribe_file", false, |model, fixture| {
+        text(&vosk::decode::transcribe_file(model, fixture.path()).unwrap())
+    });
+}
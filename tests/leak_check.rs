@@ -0,0 +1,164 @@
+//! Creating and dropping models and recognizers in every order, for running under
+//! valgrind or AddressSanitizer to find leaks and uses after free in the bindings.
+//!
+//! The tests only check that nothing crashes; the tools do the rest:
+//!
+//! ```text
+//! cargo test --test leak_check --no-run
+//! valgrind --leak-check=full --error-exitcode=1 target/debug/deps/leak_check-<hash> --test-threads=1
+//!
+//! RUSTFLAGS=-Zsanitizer=address cargo +nightly test --test leak_check --target x86_64-unknown-linux-gnu
+//! ```
+//!
+//! Each test returns early when model tests are skipped. The same orderings run
+//! without libvosk in the unit tests of the crate, named `drop_order_*`.
+//!
+//! `VOSK_LEAK_CHECK_ROUNDS` sets how many times the loops create and drop, 20 by default.
+
+mod support;
+
+use std::thread;
+use std::time::Duration;
+use vosk::overload::{OverloadOptions, RingFeeder};
+use vosk::ring::AudioRing;
+use vosk::swap::SwappableRecognizer;
+use vosk::{Model, Recognizer, SpeakerRecognizer};
+
+fn rounds() -> usize {
+    std::env::var("VOSK_LEAK_CHECK_ROUNDS")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(20)
+}
+
+/// Feeds a little audio and gets every kind of result, so that libvosk allocates.
+fn exercise(recognizer: &mut Recognizer) {
+    recognizer.set_words(true);
+    recognizer.accept_waveform(&[0; 1600]);
+    let _ = recognizer.partial_result();
+    let _ = recognizer.result();
+    let _ = recognizer.final_result();
+}
+
+#[test]
+fn drop_model_clones_in_any_order() {
+    let Some(model) = support::model() else {
+        return;
+    };
+    let clone = model.clone();
+    let mut recognizer = Recognizer::new(&clone, 16000.0);
+    drop(clone);
+    exercise(&mut recognizer);
+    let clone = model.clone();
+    drop(model);
+    exercise(&mut Recognizer::new(&clone, 16000.0));
+    drop(recognizer);
+}
+
+#[test]
+fn recognizer_outlives_model() {
+    let Some(dir) = support::model_dir() else {
+        return;
+    };
+    // A model of its own, so that no other test holds a handle to it.
+    let model = Model::new(&dir).unwrap();
+    let mut recognizers: Vec<Recognizer> = (0..3)
+        .map(|_| Recognizer::new(&model.clone(), 16000.0))
+        .collect();
+    drop(model);
+    for recognizer in &mut recognizers {
+        exercise(recognizer);
+    }
+    // The last recognizer frees the model.
+}
+
+#[test]
+fn speaker_recognizer_outlives_models() {
+    let (Some(dir), Some(speaker)) = (support::model_dir(), support::speaker_model()) else {
+        return;
+    };
+    let model = Model::new(&dir).unwrap();
+    let recognizer = SpeakerRecognizer::new(&model, &speaker, 16000.0);
+    drop(model);
+    drop(speaker);
+    // libvosk frees both models with the recognizer.
+    drop(recognizer);
+}
+
+#[test]
+fn create_and_drop_repeatedly() {
+    let Some(dir) = support::model_dir() else {
+        return;
+    };
+    let model = support::model().unwrap();
+    for _ in 0..rounds() {
+        exercise(&mut Recognizer::new(&model, 16000.0));
+        let mut grammar = Recognizer::with_grammar(&model, 16000.0, [["yes"], ["no"]]);
+        grammar.set_grammar([["maybe"]]);
+        exercise(&mut grammar);
+    }
+    // Loading is where most of the memory goes.
+    for _ in 0..rounds().min(3) {
+        drop(Model::new(&dir).unwrap());
+    }
+}
+
+#[test]
+fn model_shared_between_threads() {
+    let Some(model) = support::model() else {
+        return;
+    };
+    let workers: Vec<_> = (0..4)
+        .map(|_| {
+            let model = model.clone();
+            thread::spawn(move || exercise(&mut Recognizer::new(&model, 16000.0)))
+        })
+        .collect();
+    drop(model);
+    for worker in workers {
+        worker.join().unwrap();
+    }
+}
+
+#[test]
+fn drop_during_swap() {
+    let Some(model) = support::model() else {
+        return;
+    };
+    let mut swappable = SwappableRecognizer::new(Recognizer::new(&model, 16000.0));
+    swappable.feed(&[0; 1600]);
+    swappable.swap_model(model.clone());
+    drop(model);
+    // The swapping thread frees both recognizers on its own.
+    drop(swappable);
+    thread::sleep(Duration::from_millis(500));
+}
+
+#[test]
+fn drop_background_loading() {
+    let Some(dir) = support::model_dir() else {
+        return;
+    };
+    drop(Model::load_in_background(&dir));
+    let loading = Model::load_in_background(&dir);
+    drop(loading.wait().unwrap());
+    // Give the first thread time to free the model nobody waits for.
+    thread::sleep(Duration::from_secs(1));
+}
+
+#[test]
+fn feeder_shuts_down_with_producer() {
+    let Some(model) = support::model() else {
+        return;
+    };
+    let (mut producer, consumer) = AudioRing::new(16000).split();
+    let recognizer = Recognizer::new(&model, 16000.0);
+    let worker = thread::spawn(move || {
+        let mut feeder = RingFeeder::new(consumer, recognizer, OverloadOptions::default());
+        while feeder.next_event().is_some() {}
+        feeder.finish();
+    });
+    producer.push_slice(&[0; 8000]);
+    drop(producer);
+    worker.join().unwrap();
+}
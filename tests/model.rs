@@ -0,0 +1,578 @@
+//! Tests with a real model, see `support` for where it comes from.
+//!
+//! Each test returns early when model tests are skipped.
+
+mod support;
+
+use riff_wave::WaveReader;
+use std::fs::File;
+use std::io::BufReader;
+use std::time::Duration;
+use vosk::align::align;
+use vosk::command::CommandSet;
+use vosk::intent::IntentSet;
+use vosk::latency::measure_chunks;
+use vosk::presets::PhoneNumberCapture;
+use vosk::profiles::GrammarSession;
+use vosk::rtp::RtpFeeder;
+use vosk::source::{transcribe_source, transcribe_source_with_progress, MemorySource};
+use vosk::telephony::{G711Feeder, G711Law};
+use vosk::wake::WakeWordListener;
+use vosk::{
+    CancellationToken, Error, Event, FinalOutcome, Grammar, ProgressReporter, Recognizer,
+    SpeakerRecognizer,
+};
+
+#[test]
+fn count_samples() {
+    let Some(m) = support::model() else { return };
+    let mut recognizer = Recognizer::new(&m, 16000.0);
+    recognizer.set_chunk_limit(Some(100));
+    recognizer.accept_waveform(&[0; 8000]);
+    recognizer.accept_waveform_f32(&[0.0; 8000]);
+    assert_eq!(recognizer.samples_processed(), 16000);
+    assert_eq!(recognizer.audio_duration(), Duration::from_secs(1));
+    recognizer.set_keep_count_on_reset(true);
+    recognizer.reset();
+    assert_eq!(recognizer.samples_processed(), 16000);
+    recognizer.set_keep_count_on_reset(false);
+    recognizer.reset();
+    assert_eq!(recognizer.samples_processed(), 0);
+}
+
+#[test]
+fn sample_rate_mismatch() {
+    let Some(m) = support::model() else { return };
+    // The small English model declares 16 kHz, other models may not say.
+    let Some(declared) = m.sample_rate() else {
+        return;
+    };
+    let other = if declared == 8000.0 { 16000.0 } else { 8000.0 };
+    let lenient = Recognizer::builder(&m, other).words(true).build().unwrap();
+    assert_eq!(lenient.sample_rate(), other);
+    let strict = Recognizer::builder(&m, other)
+        .strict_sample_rate(true)
+        .build();
+    assert_eq!(
+        strict.unwrap_err(),
+        Error::SampleRateMismatch {
+            model: declared,
+            requested: other
+        }
+    );
+    let matching = Recognizer::builder(&m, declared)
+        .strict_sample_rate(true)
+        .build();
+    assert!(matching.is_ok());
+}
+
+#[test]
+fn pause_finalizes_and_resets() {
+    use vosk::overload::{OverloadOptions, RingFeeder};
+    use vosk::ring::AudioRing;
+    use vosk::Event;
+    let Some(m) = support::model() else { return };
+    let (mut producer, consumer) = AudioRing::new(32000).split();
+    let opts = OverloadOptions {
+        poll: Duration::from_millis(1),
+        ..OverloadOptions::default()
+    };
+    let mut feeder = RingFeeder::new(consumer, Recognizer::new(&m, 16000.0), opts);
+    producer.push_slice(&[0; 1600]);
+    let mute = feeder.pause_control();
+    let device = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(100));
+        mute.pause();
+        producer.push_slice(&[0; 3200]);
+        producer
+    });
+    // Silence gives no partial, so the first event is from the pause.
+    assert!(matches!(feeder.next_event(), Some(Event::Final(u)) if u.text.is_empty()));
+    assert_eq!(feeder.next_event(), Some(Event::Paused));
+    let _producer = device.join().unwrap();
+    feeder.resume();
+    assert_eq!(
+        feeder.next_event(),
+        Some(Event::Resumed {
+            discarded: Duration::from_millis(200)
+        })
+    );
+    assert_eq!(feeder.recognizer().samples_processed(), 0);
+}
+
+#[test]
+fn warm_up() {
+    let Some(m) = support::model() else { return };
+    let mut recognizer = Recognizer::new(&m, 16000.0);
+    recognizer.accept_waveform(&[0; 1600]);
+    recognizer.warm_up(Duration::from_millis(500));
+    assert_eq!(recognizer.samples_processed(), 1600);
+    assert_eq!(recognizer.partial_result().partial, "");
+    assert_eq!(recognizer.final_result().text, "");
+}
+
+#[test]
+fn one_drop_model() {
+    let Some(m) = support::model() else { return };
+    let m1 = m.clone();
+    // libvosk keeps the model alive for the recognizer.
+    drop(m);
+    let _recognizer = Recognizer::new(&m1, 8000.0);
+}
+
+#[test]
+fn word_list() {
+    let Some(m) = support::model() else { return };
+    let mut _recognizer = Recognizer::with_vocabulary(&m, 16000.0, "yes no");
+}
+
+#[test]
+fn phrase_list() {
+    let Some(m) = support::model() else { return };
+    let v = vec![vec!["hello world"], vec!["initiate the process"]];
+    let mut _recognizer = Recognizer::with_grammar(&m, 16000.0, v);
+}
+
+#[test]
+fn share_model() {
+    let Some(m) = support::model() else { return };
+    let m1 = m.clone();
+    // libvosk keeps the model alive for the recognizer.
+    drop(m);
+    std::thread::spawn(move || {
+        let _recognizer = Recognizer::new(&m1, 8000.0);
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+fn transcribe_silence() {
+    let Some(model) = support::model() else {
+        return;
+    };
+    let mut recognizer = Recognizer::new(&model, 16000.0);
+    let source = MemorySource::new(vec![0; 32000], 16000);
+    assert!(transcribe_source(&mut recognizer, source)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn intents_with_model() {
+    let Some(model) = support::model() else {
+        return;
+    };
+    let mut intents = IntentSet::builder()
+        .intent("SetTemperature", "set temperature to {number}")
+        .intent("LightsOn", "turn on the lights")
+        .build(&model, 16000.0)
+        .unwrap();
+    assert_eq!(intents.feed(&[0; 16000]), None);
+    assert_eq!(intents.finish(), None);
+    let unknown = IntentSet::builder().intent("Play", "play {song}");
+    assert!(matches!(
+        unknown.build(&model, 16000.0),
+        Err(Error::OutOfVocabulary(words)) if words == ["{song}"]
+    ));
+}
+
+#[test]
+fn transcribe_with_progress() {
+    let Some(model) = support::model() else {
+        return;
+    };
+    let mut recognizer = Recognizer::new(&model, 16000.0);
+    // 2.25 seconds, fed a tenth of a second at a time.
+    let source = MemorySource::new(vec![0; 36000], 16000);
+    let mut reports = Vec::new();
+    let progress = ProgressReporter::new(|p| reports.push(p));
+    let cancel = CancellationToken::new();
+    transcribe_source_with_progress(&mut recognizer, source, &cancel, progress).unwrap();
+    let processed: Vec<u128> = reports.iter().map(|p| p.processed.as_millis()).collect();
+    assert_eq!(processed, [500, 1000, 1500, 2000, 2250]);
+    for p in &reports {
+        assert_eq!(p.total, Some(Duration::from_millis(2250)));
+        assert_eq!(p.bytes, p.processed.as_millis() as u64 * 32);
+        assert!(p.real_time_factor > 0.0);
+    }
+    assert_eq!(reports.last().unwrap().fraction(), Some(1.0));
+}
+
+#[test]
+fn transcribe_wav_file() {
+    let Some(model) = support::model() else {
+        return;
+    };
+    // A quiet tone; nothing the model should take for words.
+    let samples: Vec<i16> = (0..48000)
+        .map(|i| ((i as f32 * 0.05).sin() * 200.0) as i16)
+        .collect();
+    let path = support::write_wav("tone", 16000, &samples);
+    // Read back the way applications usually do.
+    let mut wave = WaveReader::new(BufReader::new(File::open(&path).unwrap())).unwrap();
+    let rate = wave.pcm_format.sample_rate;
+    let read: Vec<i16> = std::iter::from_fn(|| wave.read_sample_i16().ok()).collect();
+    assert_eq!(read, samples);
+    let source = MemorySource::new(read, rate);
+    let mut recognizer = Recognizer::new(&model, 16000.0);
+    recognizer.set_words(true);
+    for utterance in transcribe_source(&mut recognizer, source).unwrap() {
+        assert!(utterance.text.is_empty() || utterance.result.is_some());
+    }
+    assert_eq!(recognizer.audio_duration(), Duration::from_secs(3));
+}
+
+#[test]
+#[ignore = "libvosk's alternatives depend on its version; run with --ignored"]
+fn candidates_with_model() {
+    let Some(model) = support::model() else {
+        return;
+    };
+    // Noise, so that the model has to guess.
+    let samples: Vec<i16> = (0..32000u32)
+        .map(|i| ((i.wrapping_mul(2654435761) >> 16) as i16) / 8)
+        .collect();
+    let mut recognizer = Recognizer::new(&model, 16000.0);
+    recognizer.accept_waveform(&samples);
+    match recognizer.finalize_with_candidates(0.9, 3) {
+        FinalOutcome::Confident(utterance) => assert!(utterance.result.is_none()),
+        FinalOutcome::Ambiguous(candidates) => {
+            assert!((2..=3).contains(&candidates.len()));
+            let total: f32 = candidates.iter().map(|c| c.confidence).sum();
+            assert!(total <= 1.0 + 1e-6);
+            assert!(candidates[0].confidence < 0.9);
+            assert!(candidates
+                .windows(2)
+                .all(|w| w[0].confidence >= w[1].confidence
+                    && w[0].text.to_lowercase() != w[1].text.to_lowercase()));
+        }
+    }
+    // Single results again afterwards, with their words.
+    recognizer.set_words(true);
+    recognizer.accept_waveform(&samples);
+    let result = recognizer.final_result();
+    assert!(result.text.is_empty() || result.result.is_some());
+}
+
+#[test]
+fn grammar_with_model() {
+    let Some(model) = support::model() else {
+        return;
+    };
+    let grammar = Grammar::new(["hello", "Hello xyzzyq"]);
+    let e = grammar.build_validated(&model).unwrap_err();
+    assert_eq!(e.words.len(), 2);
+    assert_eq!(e.words[0].suggestion.as_deref(), Some("hello"));
+
+    let grammar = Grammar::new(["yes", "no"]).with_unknown();
+    let phrases = grammar.build_validated(&model).unwrap();
+    let mut recognizer = Recognizer::with_grammar(&model, 16000.0, &phrases);
+    recognizer.accept_waveform(&[0; 16000]);
+    assert_eq!(recognizer.final_result().text, "");
+}
+
+#[test]
+fn spoken_commands() {
+    let Some(model) = support::model() else {
+        return;
+    };
+    let mut commands = CommandSet::builder()
+        .command("one", 1)
+        .command("two", 2)
+        .build(&model, 16000.0)
+        .unwrap();
+    assert_eq!(commands.feed(&[0; 16000]), None);
+    assert_eq!(commands.finish(), None);
+    let missing = CommandSet::builder()
+        .command("qwxzv", 0)
+        .build(&model, 16000.0);
+    assert!(matches!(missing, Err(Error::OutOfVocabulary(_))));
+}
+
+#[test]
+fn align_out_of_vocabulary() {
+    let Some(m) = support::model() else { return };
+    match align(&m, &[0; 16000], 16000.0, "hello qwxzv") {
+        Err(Error::OutOfVocabulary(words)) => assert_eq!(words, vec!["qwxzv"]),
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn phone_number_capture() {
+    let Some(model) = support::model() else {
+        return;
+    };
+    let mut capture = PhoneNumberCapture::new(&model, 16000.0, 7..=10).unwrap();
+    assert_eq!(capture.feed(&[0; 16000]), None);
+    assert_eq!(capture.finish(), None);
+    assert_eq!(capture.digits(), "");
+}
+
+#[test]
+fn g711_feeder_rate() {
+    let Some(m) = support::model() else { return };
+    let wideband = Recognizer::new(&m, 16000.0);
+    assert!(G711Feeder::new(wideband, G711Law::MuLaw).is_err());
+    let mut feeder = G711Feeder::new(Recognizer::new(&m, 8000.0), G711Law::ALaw).unwrap();
+    feeder.push(&[0xd5; 1600]);
+    assert_eq!(feeder.finish().text, "");
+}
+
+#[test]
+fn rtp_feeder_out_of_order() {
+    let Some(m) = support::model() else { return };
+    let mut feeder = RtpFeeder::new(Recognizer::new(&m, 8000.0)).unwrap();
+    for &sequence in &[1u16, 3, 2, 2, 6, 7, 8, 9, 10, 11, 12] {
+        let mut packet = vec![0x80, 0];
+        packet.extend(sequence.to_be_bytes());
+        packet.extend((sequence as u32 * 160).to_be_bytes());
+        packet.extend([0; 4]);
+        packet.extend([0xff; 160]);
+        feeder.push_packet(&packet).unwrap();
+    }
+    let (_, last) = feeder.finish();
+    assert_eq!(last.text, "");
+    assert_eq!(feeder.stats().duplicates, 1);
+    assert_eq!(feeder.stats().lost, 2);
+}
+
+#[test]
+fn chunk_measurements() {
+    let Some(m) = support::model() else { return };
+    let mut recognizer = Recognizer::new(&m, 16000.0);
+    let silence = [0; 16000];
+    let measurement = measure_chunks(&mut recognizer, &silence, 1600);
+    assert_eq!(measurement.calls.count(), 10);
+    assert_eq!(measurement.audio, Duration::from_secs(1));
+    assert_eq!(measurement.text, "");
+    // The last chunk is shorter.
+    assert_eq!(
+        measure_chunks(&mut recognizer, &silence, 3000)
+            .calls
+            .count(),
+        6
+    );
+}
+
+#[test]
+fn wake_word_in_silence() {
+    let Some(model) = support::model() else {
+        return;
+    };
+    let mut listener = WakeWordListener::new(&model, 16000.0, &["hey computer"]).unwrap();
+    assert_eq!(listener.feed(&[0; 32000]), None);
+}
+
+#[test]
+fn speaker_model() {
+    let Some(model) = support::model() else {
+        return;
+    };
+    let Some(speaker) = support::speaker_model() else {
+        return;
+    };
+    let _recognizer = SpeakerRecognizer::new(&model, &speaker, 16000.0);
+}
+
+#[cfg(feature = "audio-decode")]
+#[test]
+fn transcribe_silent_file() {
+    let Some(m) = support::model() else { return };
+    let path = support::write_wav("silence", 16000, &[0; 16000]);
+    let utterances = vosk::decode::transcribe_file(&m, path).unwrap();
+    assert!(utterances.is_empty());
+}
+
+#[cfg(feature = "ffmpeg-cli")]
+#[test]
+fn transcribe_with_ffmpeg() {
+    use vosk::decode::{via_ffmpeg, FfmpegOptions};
+    let Some(model) = support::model() else {
+        return;
+    };
+    let path = support::write_wav("ffmpeg-silence", 8000, &[0; 8000]);
+    match via_ffmpeg(&model, path, &FfmpegOptions::default()) {
+        Ok(utterances) => assert!(utterances.is_empty()),
+        // Not installed; nothing to test.
+        Err(Error::ProgramNotFound(_)) => {}
+        Err(e) => panic!("{}", e),
+    }
+}
+
+#[test]
+fn swap_model_keeps_audio() {
+    use vosk::swap::{SwapState, SwappableRecognizer};
+    use vosk::Event;
+    let Some(model) = support::model() else {
+        return;
+    };
+    let mut swappable = SwappableRecognizer::new(Recognizer::new(&model, 16000.0));
+    swappable.feed(&[0; 8000]);
+    swappable.swap_model(model.clone());
+    swappable.feed(&[0; 16000]);
+    swappable.wait_swapped();
+    assert_eq!(swappable.state(), SwapState::Running);
+    let buffered = std::iter::from_fn(|| swappable.next_event()).find_map(|event| match event {
+        Event::Swapped { buffered, .. } => Some(buffered),
+        _ => None,
+    });
+    // Nothing is buffered if the swap was done before the second feed.
+    assert!(buffered.is_some_and(|b| b <= Duration::from_secs(1)));
+    assert_eq!(swappable.finish().text, "");
+}
+
+#[test]
+fn route_between_languages() {
+    use vosk::router::{LanguageRouter, RouterOptions};
+    let Some(model) = support::model() else {
+        return;
+    };
+    let silence = [0; 32000];
+    let router = LanguageRouter::new()
+        .language("en", model.clone())
+        .language("en-again", model.clone());
+    let routed = router.route(&silence, 16000.0).unwrap();
+    assert_eq!(routed.tag, "en");
+    assert_eq!(routed.scores.len(), 2);
+    assert_eq!(routed.utterance.text, "");
+    let fast = LanguageRouter::with_options(RouterOptions {
+        parallel: false,
+        decide_after: Some(Duration::from_secs(1)),
+    })
+    .language("en", model);
+    assert_eq!(fast.route(&silence, 16000.0).unwrap().tag, "en");
+}
+
+#[test]
+fn model_footprint() {
+    let Some(dir) = support::model_dir() else {
+        return;
+    };
+    let model = support::model().unwrap();
+    let disk = vosk::Model::disk_size(&dir).unwrap();
+    // Even the small models take tens of megabytes.
+    assert!(disk > 10 << 20);
+    let footprint = model.footprint().unwrap();
+    assert_eq!(footprint.disk_bytes, disk);
+    assert_eq!(model.approx_memory_usage().unwrap(), footprint.memory_bytes);
+}
+
+#[cfg(feature = "audio-decode")]
+#[test]
+fn evaluate_silence() {
+    use vosk::eval::{evaluate_files, EvalOptions};
+    let Some(m) = support::model() else { return };
+    let path = support::write_wav("eval-silence", 16000, &[0; 16000]);
+    let evaluation = evaluate_files(&m, &[(path, "hello world")], &EvalOptions::default()).unwrap();
+    assert_eq!(evaluation.totals.deletions, 2);
+    assert_eq!(evaluation.totals.rate(), 1.0);
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn recognizer_through_raw_pointer() {
+    use vosk::sys;
+    let Some(m) = support::model() else { return };
+    let raw = Recognizer::new(&m, 16000.0).into_raw();
+    let samples = [0i16; 16000];
+    unsafe {
+        sys::vosk_recognizer_set_words(raw, 1);
+        sys::vosk_recognizer_accept_waveform_s(raw, samples.as_ptr(), samples.len() as i32);
+    }
+    let path = m.path().to_path_buf();
+    let mut recognizer = unsafe { Recognizer::from_raw(raw, &m, 16000.0) };
+    // libvosk keeps the model alive for the recognizer.
+    drop(m);
+    recognizer.accept_waveform(&samples);
+    assert_eq!(recognizer.final_result().text, "");
+    assert!(format!("{:?}", recognizer).contains(path.to_str().unwrap()));
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+fn metrics_after_session() {
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use vosk::telemetry;
+    let Some(model) = support::model() else {
+        return;
+    };
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    metrics::with_local_recorder(&recorder, || {
+        let mut recognizer = Recognizer::new(&model, 16000.0);
+        let source = MemorySource::new(vec![0; 32000], 16000);
+        transcribe_source(&mut recognizer, source).unwrap();
+    });
+    let metrics: std::collections::HashMap<String, DebugValue> = snapshotter
+        .snapshot()
+        .into_vec()
+        .into_iter()
+        .map(|(key, _, _, value)| (key.key().name().to_string(), value))
+        .collect();
+    assert_eq!(
+        metrics[telemetry::AUDIO_PROCESSED],
+        DebugValue::Counter(2000)
+    );
+    // Created and dropped again.
+    assert_eq!(
+        format!("{:?}", metrics[telemetry::RECOGNIZERS_ACTIVE]),
+        "Gauge(0.0)"
+    );
+    match &metrics[telemetry::REAL_TIME_FACTOR] {
+        DebugValue::Histogram(values) => assert!(!values.is_empty()),
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn grammar_profiles() {
+    let Some(model) = support::model() else {
+        return;
+    };
+    let mut session = GrammarSession::builder()
+        .profile("yes no", Grammar::new(["yes", "no"]).with_unknown())
+        .profile(
+            "numbers",
+            Grammar::new(["one", "two", "three"]).with_unknown(),
+        )
+        .build(&model, 16000.0)
+        .unwrap();
+    assert_eq!(session.active(), "yes no");
+    session.feed(&[0; 8000]);
+    session.activate("numbers").unwrap();
+    session.feed(&[0; 8000]);
+    let mut changed = false;
+    while let Some(event) = session.next_event() {
+        if let Event::GrammarChanged { profile } = event {
+            assert_eq!(profile, "numbers");
+            changed = true;
+        }
+    }
+    assert!(changed);
+    assert_eq!(session.finish().text, "");
+}
+
+#[test]
+fn no_speech_activity_in_silence() {
+    use vosk::activity::ActivityOptions;
+    use vosk::overload::{OverloadOptions, RingFeeder};
+    use vosk::ring::AudioRing;
+    let Some(m) = support::model() else { return };
+    let (mut producer, consumer) = AudioRing::new(32000).split();
+    let mut feeder = RingFeeder::new(
+        consumer,
+        Recognizer::new(&m, 16000.0),
+        OverloadOptions::default(),
+    )
+    .with_speech_activity(ActivityOptions::default());
+    producer.push_slice(&[0; 32000]);
+    drop(producer);
+    while let Some(event) = feeder.next_event() {
+        assert!(!matches!(
+            event,
+            Event::SpeechStarted | Event::SpeechEnded { .. }
+        ));
+    }
+}
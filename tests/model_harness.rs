@@ -0,0 +1,209 @@
+//! The downloading and caching of test models, against a local HTTP server.
+
+mod support;
+
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{BufRead, BufReader, Cursor, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use support::ModelFetcher;
+use zip::write::SimpleFileOptions;
+
+/// Serves `body` to every request, counting them.
+struct Server {
+    url: String,
+    requests: Arc<AtomicUsize>,
+}
+
+impl Server {
+    fn start(status: &'static str, body: Vec<u8>) -> Server {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/model.zip", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                counter.fetch_add(1, Ordering::SeqCst);
+                let head = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    body.len()
+                );
+                let _ = stream.write_all(head.as_bytes());
+                let _ = stream.write_all(&body);
+            }
+        });
+        Server { url, requests }
+    }
+    fn requests(&self) -> usize {
+        self.requests.load(Ordering::SeqCst)
+    }
+}
+
+/// A zip of the files of a fake model, by path in the archive.
+fn archive(files: &[(&str, &str)]) -> Vec<u8> {
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    for (path, contents) in files {
+        zip.start_file(*path, SimpleFileOptions::default()).unwrap();
+        zip.write_all(contents.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap().into_inner()
+}
+
+fn model_archive() -> Vec<u8> {
+    archive(&[
+        ("tiny-model/am/final.mdl", "acoustic"),
+        ("tiny-model/graph/words.txt", "<eps> 0\nhello 1\n"),
+    ])
+}
+
+fn fetcher(server: &Server, test: &str, sha256: String) -> ModelFetcher {
+    let cache_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("harness-{}", test));
+    ModelFetcher {
+        url: server.url.clone(),
+        name: "tiny-model".to_string(),
+        cache_dir,
+        sha256,
+    }
+}
+
+fn clean(fetcher: &ModelFetcher) {
+    let _ = fs::remove_dir_all(&fetcher.cache_dir);
+}
+
+fn sha256(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[test]
+fn download_and_extract() {
+    let body = model_archive();
+    let sha = sha256(&body);
+    let server = Server::start("200 OK", body);
+    let fetcher = fetcher(&server, "extract", sha);
+    clean(&fetcher);
+    let dir = fetcher.fetch().unwrap();
+    assert_eq!(dir, fetcher.cache_dir.join("tiny-model"));
+    assert_eq!(
+        fs::read_to_string(dir.join("graph/words.txt")).unwrap(),
+        "<eps> 0\nhello 1\n"
+    );
+    let mut left: Vec<_> = fs::read_dir(&fetcher.cache_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    left.sort();
+    assert_eq!(left, ["tiny-model", "tiny-model.lock", "tiny-model.sha256"]);
+}
+
+#[test]
+fn cached_after_first_fetch() {
+    let body = model_archive();
+    let sha = sha256(&body);
+    let server = Server::start("200 OK", body);
+    let fetcher = fetcher(&server, "cached", sha);
+    clean(&fetcher);
+    fetcher.fetch().unwrap();
+    fetcher.fetch().unwrap();
+    assert_eq!(server.requests(), 1);
+}
+
+#[test]
+fn checksum_verified() {
+    let body = model_archive();
+    let expected = sha256(&body);
+    let server = Server::start("200 OK", body);
+    let wrong = fetcher(&server, "checksum", "0".repeat(64));
+    clean(&wrong);
+    let e = wrong.fetch().unwrap_err();
+    assert!(e.contains(&format!("checksum of {} is {}", server.url, expected)));
+    assert!(!wrong.cache_dir.join("tiny-model").exists());
+    assert!(!wrong.cache_dir.join("tiny-model.zip.part").exists());
+
+    let right = fetcher(&server, "checksum", expected.clone());
+    right.fetch().unwrap();
+    // Cached with the checksum it was verified with.
+    right.fetch().unwrap();
+    assert_eq!(server.requests(), 2);
+    let recorded = fs::read_to_string(right.cache_dir.join("tiny-model/.complete")).unwrap();
+    assert_eq!(recorded, expected);
+}
+
+#[test]
+fn pinning_another_checksum_downloads_again() {
+    let body = model_archive();
+    let sha = sha256(&body);
+    let server = Server::start("200 OK", body);
+    let first = fetcher(&server, "repin", sha);
+    clean(&first);
+    first.fetch().unwrap();
+    let repinned = fetcher(&server, "repin", "f".repeat(64));
+    assert!(repinned.fetch().is_err());
+    assert_eq!(server.requests(), 2);
+}
+
+#[test]
+fn refuses_without_checksum() {
+    let server = Server::start("200 OK", model_archive());
+    let unpinned = fetcher(&server, "unpinned", String::new());
+    clean(&unpinned);
+    let e = unpinned.fetch().unwrap_err();
+    assert!(e.contains("VOSK_TEST_MODEL_SHA256"));
+    assert_eq!(server.requests(), 0);
+}
+
+#[test]
+fn parallel_fetches_download_once() {
+    let body = model_archive();
+    let sha = sha256(&body);
+    let server = Server::start("200 OK", body);
+    clean(&fetcher(&server, "parallel", sha.clone()));
+    let fetches: Vec<_> = (0..4)
+        .map(|_| {
+            let fetcher = fetcher(&server, "parallel", sha.clone());
+            thread::spawn(move || fetcher.fetch())
+        })
+        .collect();
+    for fetch in fetches {
+        let dir = fetch.join().unwrap().unwrap();
+        assert!(dir.join("am/final.mdl").is_file());
+    }
+    assert_eq!(server.requests(), 1);
+}
+
+#[test]
+fn failures_are_not_cached() {
+    let missing = Server::start("404 Not Found", Vec::new());
+    let fetcher_404 = fetcher(&missing, "not-found", sha256(b""));
+    clean(&fetcher_404);
+    assert!(fetcher_404.fetch().is_err());
+
+    let garbage = Server::start("200 OK", b"not a zip".to_vec());
+    let fetcher_garbage = fetcher(&garbage, "garbage", sha256(b"not a zip"));
+    clean(&fetcher_garbage);
+    assert!(fetcher_garbage.fetch().is_err());
+    assert!(fetcher_garbage.fetch().is_err());
+    assert_eq!(garbage.requests(), 2);
+
+    let other_archive = archive(&[("other-model/am/final.mdl", "")]);
+    let sha = sha256(&other_archive);
+    let other = Server::start("200 OK", other_archive);
+    let fetcher_other = fetcher(&other, "other", sha);
+    clean(&fetcher_other);
+    let e = fetcher_other.fetch().unwrap_err();
+    assert!(e.ends_with("has no directory tiny-model"));
+    assert!(!fetcher_other.cache_dir.join("tiny-model").exists());
+}
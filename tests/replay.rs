@@ -0,0 +1,118 @@
+//! Recorded sessions, from the fixtures in `tests/fixtures/replay` and from a model.
+
+mod support;
+
+use std::fs;
+use std::time::Duration;
+use vosk::replay::{Recording, RecordingRecognizer, Step, FORMAT_VERSION};
+use vosk::Recognizer;
+
+const SESSION_V1: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/replay/session-v1.vkreplay"
+);
+
+#[test]
+fn fixture_loads() {
+    let recording = Recording::load(SESSION_V1).unwrap();
+    assert_eq!(recording.sample_rate, 16000.0);
+    assert_eq!(recording.audio_duration(), Duration::from_millis(200));
+    let kinds: Vec<&str> = recording
+        .steps
+        .iter()
+        .map(|step| match step {
+            Step::Accept {
+                completed: true, ..
+            } => "accept completed",
+            Step::Accept { .. } => "accept",
+            Step::Partial(_) => "partial",
+            Step::Result(_) => "result",
+            Step::FinalResult(_) => "final",
+            Step::Reset => "reset",
+            Step::SetWords(_) => "words",
+            Step::SetGrammar(_) => "grammar",
+            _ => "other",
+        })
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            "words",
+            "accept",
+            "partial",
+            "accept completed",
+            "result",
+            "reset",
+            "grammar",
+            "accept",
+            "final"
+        ]
+    );
+    assert_eq!(
+        recording.steps[6],
+        Step::SetGrammar(vec!["hello world".into(), "[unk]".into()])
+    );
+}
+
+#[test]
+fn fixture_results_parse() {
+    let recording = Recording::load(SESSION_V1).unwrap();
+    let results: Vec<_> = recording
+        .results()
+        .into_iter()
+        .map(|(step, result)| (step, result.unwrap()))
+        .collect();
+    assert_eq!(results.len(), 2);
+    let (step, hello) = &results[0];
+    assert_eq!(*step, 4);
+    assert_eq!(hello.text, "hello");
+    assert_eq!(hello.words()[0].start(), 0.12);
+    assert_eq!(results[1].0, 8);
+    assert_eq!(results[1].1.text, "");
+}
+
+#[test]
+fn fixture_written_again_is_the_same() {
+    assert_eq!(FORMAT_VERSION, 1, "add a fixture of the new version");
+    let bytes = fs::read(SESSION_V1).unwrap();
+    let mut written = Vec::new();
+    Recording::read_from(&bytes[..])
+        .unwrap()
+        .write_to(&mut written)
+        .unwrap();
+    assert_eq!(written, bytes);
+}
+
+#[test]
+fn replay_of_own_recording() {
+    let Some(model) = support::model() else {
+        return;
+    };
+    let fixture = Recording::load(SESSION_V1).unwrap();
+    let mut recognizer = RecordingRecognizer::new(fixture.recognizer(&model).unwrap());
+    recognizer.set_words(true);
+    for step in &fixture.steps {
+        if let Step::Accept { samples, .. } = step {
+            recognizer.accept_waveform(samples);
+            recognizer.partial_result().unwrap();
+        }
+    }
+    recognizer.final_result().unwrap();
+    recognizer.reset();
+    recognizer
+        .try_set_grammar(["hello world", "[unk]"])
+        .unwrap();
+    let (_, recording) = recognizer.into_inner();
+
+    let mut path = std::env::temp_dir();
+    path.push(format!("vosk-replay-{}.vkreplay", std::process::id()));
+    recording.save(&path).unwrap();
+    let loaded = Recording::load(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(loaded, recording);
+
+    // Recognition is deterministic, so a fresh recognizer gives the same outputs.
+    let mut fresh = Recognizer::new(&model, loaded.sample_rate);
+    let differences = loaded.replay(&mut fresh);
+    assert!(differences.is_empty(), "{:?}", differences);
+}
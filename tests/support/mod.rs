@@ -0,0 +1,207 @@
+//! Models for tests that need one, downloaded once and cached under `target/`.
+//!
+//! - `VOSK_TEST_MODEL` is the path of a model to use instead of downloading one.
+//! - `VOSK_TEST_MODEL_SHA256` is the checksum the downloaded archive must have,
+//!   instead of `MODEL_SHA256`.
+//! - `VOSK_SKIP_MODEL_TESTS=1` skips the tests, for example when offline.
+//! - `VOSK_TEST_SPEAKER_MODEL` is the path of a speaker model, whose tests are skipped without it.
+
+// Each test binary uses a different part of this.
+#![allow(dead_code)]
+
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use vosk::{Model, SpeakerModel};
+
+/// The smallest English model, about 40 MB.
+pub const MODEL_NAME: &str = "vosk-model-small-en-us-0.15";
+pub const MODEL_URL: &str = "https://alphacephei.com/vosk/models/vosk-model-small-en-us-0.15.zip";
+/// SHA-256 of the archive at `MODEL_URL`, in lowercase hex, which every download
+/// is checked against. Empty until pinned from a trusted download; until then the
+/// model is only fetched with `VOSK_TEST_MODEL_SHA256` set.
+pub const MODEL_SHA256: &str = "";
+
+/// The model for tests, or None if they should be skipped.
+///
+/// Panics if the model can't be downloaded, unless tests with models are skipped.
+pub fn model() -> Option<Model> {
+    static MODEL: OnceLock<Option<Model>> = OnceLock::new();
+    MODEL
+        .get_or_init(|| {
+            let dir = model_dir()?;
+            Some(Model::new(&dir).unwrap_or_else(|e| panic!("{}: {}", dir.display(), e)))
+        })
+        .clone()
+}
+
+/// The directory of the model for tests, downloading it if needed.
+pub fn model_dir() -> Option<PathBuf> {
+    if skipped() {
+        return None;
+    }
+    if let Some(dir) = std::env::var_os("VOSK_TEST_MODEL") {
+        return Some(dir.into());
+    }
+    let fetcher = ModelFetcher {
+        url: MODEL_URL.to_string(),
+        name: MODEL_NAME.to_string(),
+        cache_dir: Path::new(env!("CARGO_TARGET_TMPDIR")).join("models"),
+        sha256: std::env::var("VOSK_TEST_MODEL_SHA256")
+            .unwrap_or_else(|_| MODEL_SHA256.to_string()),
+    };
+    match fetcher.fetch() {
+        Ok(dir) => Some(dir),
+        Err(e) => panic!(
+            "could not get the test model: {}\nset VOSK_SKIP_MODEL_TESTS=1 to skip these tests",
+            e
+        ),
+    }
+}
+
+/// The speaker model for tests, if one is set with `VOSK_TEST_SPEAKER_MODEL`.
+pub fn speaker_model() -> Option<SpeakerModel> {
+    let dir = std::env::var_os("VOSK_TEST_SPEAKER_MODEL").filter(|_| !skipped())?;
+    Some(SpeakerModel::new(&dir).expect("invalid speaker model"))
+}
+
+fn skipped() -> bool {
+    std::env::var("VOSK_SKIP_MODEL_TESTS").is_ok_and(|v| v == "1")
+}
+
+/// Downloads a zipped model and extracts it in a cache directory.
+///
+/// The archive holds a directory with the name of the model. A lock file in the
+/// cache directory keeps test binaries running at the same time from racing.
+pub struct ModelFetcher {
+    pub url: String,
+    pub name: String,
+    pub cache_dir: PathBuf,
+    /// Checksum of the archive, in lowercase hex. It's recorded with the extracted
+    /// model, which is downloaded again for another checksum.
+    pub sha256: String,
+}
+
+/// Marks a complete extraction, holding the checksum of the archive.
+const COMPLETE: &str = ".complete";
+
+impl ModelFetcher {
+    /// The model directory, downloaded and extracted unless it already was.
+    pub fn fetch(&self) -> Result<PathBuf, String> {
+        if self.sha256.is_empty() {
+            return Err(format!(
+                "no checksum to verify {} with, set VOSK_TEST_MODEL_SHA256",
+                self.url
+            ));
+        }
+        fs::create_dir_all(&self.cache_dir).map_err(|e| self.error("create", &e))?;
+        let lock_path = self.cache_dir.join(format!("{}.lock", self.name));
+        let lock = File::create(&lock_path).map_err(|e| self.error("create", &e))?;
+        // Released when the file is closed, even if this panics.
+        lock.lock().map_err(|e| self.error("lock", &e))?;
+        let dir = self.cache_dir.join(&self.name);
+        if fs::read_to_string(dir.join(COMPLETE))
+            .is_ok_and(|recorded| recorded.trim() == self.sha256)
+        {
+            return Ok(dir);
+        }
+        let archive = self.download()?;
+        let result = self.extract(&archive);
+        let _ = fs::remove_file(&archive);
+        result?;
+        Ok(dir)
+    }
+    fn download(&self) -> Result<PathBuf, String> {
+        let path = self.cache_dir.join(format!("{}.zip.part", self.name));
+        // The errors of ureq name the URL already.
+        let response = ureq::get(&self.url).call().map_err(|e| e.to_string())?;
+        let mut reader = response.into_reader();
+        let mut file = File::create(&path).map_err(|e| self.error("create", &e))?;
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            let n = reader
+                .read(&mut buf)
+                .map_err(|e| format!("{}: {}", self.url, e))?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+            file.write_all(&buf[..n])
+                .map_err(|e| self.error("write", &e))?;
+        }
+        let actual = hex(&hasher.finalize());
+        if actual != self.sha256 {
+            let _ = fs::remove_file(&path);
+            return Err(format!(
+                "checksum of {} is {}, expected {}",
+                self.url, actual, self.sha256
+            ));
+        }
+        fs::write(
+            self.cache_dir.join(format!("{}.sha256", self.name)),
+            &actual,
+        )
+        .map_err(|e| self.error("write", &e))?;
+        Ok(path)
+    }
+    /// Extracts next to the model directory, then moves the model in place.
+    fn extract(&self, archive: &Path) -> Result<(), String> {
+        let staging = self.cache_dir.join(format!("{}.tmp", self.name));
+        let _ = fs::remove_dir_all(&staging);
+        let file = File::open(archive).map_err(|e| self.error("open", &e))?;
+        let mut zip = zip::ZipArchive::new(file).map_err(|e| format!("{}: {}", self.url, e))?;
+        zip.extract(&staging)
+            .map_err(|e| format!("{}: {}", self.url, e))?;
+        let extracted = staging.join(&self.name);
+        if !extracted.is_dir() {
+            return Err(format!("{} has no directory {}", self.url, self.name));
+        }
+        let sha = fs::read_to_string(self.cache_dir.join(format!("{}.sha256", self.name)))
+            .map_err(|e| self.error("read", &e))?;
+        fs::write(extracted.join(COMPLETE), sha).map_err(|e| self.error("write", &e))?;
+        let dir = self.cache_dir.join(&self.name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::rename(&extracted, &dir).map_err(|e| self.error("move", &e))?;
+        let _ = fs::remove_dir_all(&staging);
+        Ok(())
+    }
+    fn error(&self, action: &str, e: &io::Error) -> String {
+        format!(
+            "could not {} in {}: {}",
+            action,
+            self.cache_dir.display(),
+            e
+        )
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Writes a 16-bit mono wav file to the temporary directory of the tests.
+pub fn write_wav(name: &str, sample_rate: u32, samples: &[i16]) -> PathBuf {
+    let data_len = samples.len() as u32 * 2;
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_len.to_le_bytes());
+    for s in samples {
+        bytes.extend_from_slice(&s.to_le_bytes());
+    }
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.wav", name));
+    fs::write(&path, bytes).unwrap();
+    path
+}
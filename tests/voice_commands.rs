@@ -0,0 +1,82 @@
+//! Commands defined with `#[derive(VoiceCommands)]`, run with `--features derive`.
+
+mod support;
+
+use vosk::command::{Slot, SlotValue, VoiceCommands};
+
+#[derive(VoiceCommands, Debug, PartialEq)]
+enum Cmd {
+    #[phrase("turn on the lights")]
+    LightsOn,
+    #[phrase("set volume to {n:number}")]
+    #[phrase("volume {n:number}")]
+    SetVolume { n: u8 },
+    #[phrase("from {from:number} to {to:number}")]
+    Range { to: i64, from: String },
+    #[phrase("timer {number} minutes")]
+    Timer { number: SlotValue },
+}
+
+fn slot(name: &str, raw_value: &str, n: i64) -> Slot {
+    Slot {
+        name: name.to_string(),
+        raw_value: raw_value.to_string(),
+        value: SlotValue::Number(n),
+        confidence: None,
+        range: None,
+    }
+}
+
+#[test]
+fn phrases_by_variant() {
+    assert_eq!(
+        Cmd::PHRASES,
+        [
+            ("turn on the lights", 0),
+            ("set volume to {n:number}", 1),
+            ("volume {n:number}", 1),
+            ("from {from:number} to {to:number}", 2),
+            ("timer {number} minutes", 3),
+        ]
+    );
+}
+
+#[test]
+fn fields_from_slots() {
+    assert_eq!(Cmd::from_slots(0, &[]), Some(Cmd::LightsOn));
+    let volume = [slot("n", "eleven", 11)];
+    assert_eq!(Cmd::from_slots(1, &volume), Some(Cmd::SetVolume { n: 11 }));
+    // Slots are found by name, whatever their order.
+    let range = [slot("from", "two", 2), slot("to", "three", 3)];
+    assert_eq!(
+        Cmd::from_slots(2, &range),
+        Some(Cmd::Range {
+            to: 3,
+            from: "two".to_string()
+        })
+    );
+    assert_eq!(
+        Cmd::from_slots(3, &[slot("number", "five", 5)]),
+        Some(Cmd::Timer {
+            number: SlotValue::Number(5)
+        })
+    );
+}
+
+#[test]
+fn slots_that_dont_fit() {
+    // Too big for a u8, or not there at all.
+    assert_eq!(Cmd::from_slots(1, &[slot("n", "three hundred", 300)]), None);
+    assert_eq!(Cmd::from_slots(1, &[slot("m", "one", 1)]), None);
+    assert_eq!(Cmd::from_slots(4, &[]), None);
+}
+
+#[test]
+fn command_set_with_model() {
+    let Some(model) = support::model() else {
+        return;
+    };
+    let mut commands = Cmd::command_set().build(&model, 16000.0).unwrap();
+    assert_eq!(commands.feed(&[0; 16000]), None);
+    assert_eq!(commands.finish().and_then(|hit| Cmd::from_hit(&hit)), None);
+}
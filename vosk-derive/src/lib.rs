@@ -0,0 +1,363 @@
+//! `#[derive(VoiceCommands)]`, re-exported by `vosk::command` with its `derive` feature.
+//!
+//! Each variant of the enum gets one or more `#[phrase("...")]` attributes. A phrase
+//! can have slots, written `{name:number}` or `{number}`, that fill the field of
+//! the variant with that name. The generated code only lists the phrases and reads
+//! the fields; recognition and slot parsing are those of `vosk::command::CommandSet`.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{quote, quote_spanned};
+use std::collections::HashMap;
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, LitStr};
+
+/// The kinds of slot `CommandSet` knows how to fill.
+const SLOT_TYPES: &[&str] = &["number"];
+
+#[proc_macro_derive(VoiceCommands, attributes(phrase))]
+pub fn derive_voice_commands(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let variants = match &input.data {
+        Data::Enum(data) => &data.variants,
+        _ => {
+            return Err(syn::Error::new(
+                input.ident.span(),
+                "VoiceCommands can only be derived for enums",
+            ))
+        }
+    };
+    let ident = &input.ident;
+    let mut phrases = Vec::new();
+    let mut arms = Vec::new();
+    // By the phrase with its slots unnamed, since "{a:number}" and "{b:number}"
+    // in the same place can't be told apart when spoken.
+    let mut seen: HashMap<String, (String, String)> = HashMap::new();
+    for (index, variant) in variants.iter().enumerate() {
+        let name = &variant.ident;
+        let fields: Vec<_> = match &variant.fields {
+            Fields::Unit => Vec::new(),
+            Fields::Named(named) => named.named.iter().collect(),
+            Fields::Unnamed(unnamed) => {
+                return Err(syn::Error::new(
+                    unnamed.span(),
+                    "slots fill fields by name, tuple variants aren't supported",
+                ))
+            }
+        };
+        let field_names: Vec<String> = fields
+            .iter()
+            .map(|f| {
+                f.ident
+                    .as_ref()
+                    .map(ToString::to_string)
+                    .unwrap_or_default()
+            })
+            .collect();
+        let mut found = false;
+        for attr in variant.attrs.iter().filter(|a| a.path().is_ident("phrase")) {
+            found = true;
+            let literal: LitStr = attr.parse_args()?;
+            let phrase = Phrase::parse(&literal)?;
+            for slot in &phrase.slots {
+                if !field_names.contains(slot) {
+                    return Err(syn::Error::new(
+                        literal.span(),
+                        format!("slot `{}` is not a field of `{}`", slot, name),
+                    ));
+                }
+            }
+            if let Some(missing) = field_names.iter().find(|f| !phrase.slots.contains(f)) {
+                return Err(syn::Error::new(
+                    literal.span(),
+                    format!("field `{}` has no slot in this phrase", missing),
+                ));
+            }
+            if let Some((earlier, other)) = seen.get(&phrase.key) {
+                return Err(syn::Error::new(
+                    literal.span(),
+                    format!(
+                        "duplicate phrase: \"{}\" can't be told apart from \"{}\" of `{}`",
+                        phrase.text, earlier, other
+                    ),
+                ));
+            }
+            seen.insert(phrase.key, (phrase.text.clone(), name.to_string()));
+            let text = phrase.text;
+            phrases.push(quote!((#text, #index)));
+        }
+        if !found {
+            return Err(syn::Error::new(
+                name.span(),
+                format!("`{}` needs at least one #[phrase(\"...\")]", name),
+            ));
+        }
+        let arm = if fields.is_empty() {
+            quote!(#index => ::std::option::Option::Some(#ident::#name))
+        } else {
+            let values = fields.iter().zip(&field_names).map(|(field, slot)| {
+                let field_ident = &field.ident;
+                let ty = &field.ty;
+                quote_spanned! {ty.span()=>
+                    #field_ident: <#ty as ::vosk::command::FromSlot>::from_slot(
+                        slots.iter().find(|slot| slot.name == #slot)?,
+                    )?
+                }
+            });
+            quote!(#index => ::std::option::Option::Some(#ident::#name { #(#values),* }))
+        };
+        arms.push(arm);
+    }
+    if phrases.is_empty() {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            "VoiceCommands needs an enum with at least one variant",
+        ));
+    }
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::vosk::command::VoiceCommands for #ident #ty_generics #where_clause {
+            const PHRASES: &'static [(&'static str, usize)] = &[#(#phrases),*];
+            fn from_slots(
+                index: usize,
+                slots: &[::vosk::command::Slot],
+            ) -> ::std::option::Option<Self> {
+                let _ = slots;
+                match index {
+                    #(#arms,)*
+                    _ => ::std::option::Option::None,
+                }
+            }
+        }
+    })
+}
+
+/// A phrase as `CommandSet` will see it.
+struct Phrase {
+    /// With uniform whitespace.
+    text: String,
+    /// The names of the slots, in order.
+    slots: Vec<String>,
+    /// The text with every slot written `{number}`.
+    key: String,
+}
+
+impl Phrase {
+    fn parse(literal: &LitStr) -> syn::Result<Phrase> {
+        let value = literal.value();
+        let error = |message: String| syn::Error::new(literal.span(), message);
+        let mut words = Vec::new();
+        let mut key = Vec::new();
+        let mut slots = Vec::new();
+        for word in value.split_whitespace() {
+            words.push(word);
+            if !word.starts_with('{') && !word.ends_with('}') {
+                key.push(word.to_string());
+                continue;
+            }
+            let inner = word
+                .strip_prefix('{')
+                .and_then(|w| w.strip_suffix('}'))
+                .ok_or_else(|| {
+                    error(format!("`{}` is not a slot, write `{{name:number}}`", word))
+                })?;
+            let (name, ty) = inner.split_once(':').unwrap_or((inner, inner));
+            if name.is_empty() {
+                return Err(error(format!("slot `{}` has no name", word)));
+            }
+            if !SLOT_TYPES.contains(&ty) {
+                return Err(error(format!(
+                    "unknown slot type `{}`, expected one of: {}",
+                    ty,
+                    SLOT_TYPES.join(", ")
+                )));
+            }
+            if slots.iter().any(|s| s == name) {
+                return Err(error(format!("slot `{}` appears twice", name)));
+            }
+            slots.push(name.to_string());
+            key.push(format!("{{{}}}", ty));
+        }
+        if words.is_empty() {
+            return Err(error("the phrase is empty".to_string()));
+        }
+        Ok(Phrase {
+            text: words.join(" "),
+            slots,
+            key: key.join(" "),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    fn error(input: DeriveInput) -> String {
+        expand(input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn phrases_and_slots() {
+        let expanded = expand(parse_quote! {
+            enum Cmd {
+                #[phrase("turn  on the lights")]
+                LightsOn,
+                #[phrase("set volume to {n:number}")]
+                #[phrase("volume {n:number}")]
+                SetVolume { n: u32 },
+                #[phrase("{number} minutes")]
+                Timer { number: u64 },
+            }
+        })
+        .unwrap()
+        .to_string();
+        assert!(expanded.contains(
+            r#"& [("turn on the lights" , 0usize) , ("set volume to {n:number}" , 1usize) , ("volume {n:number}" , 1usize) , ("{number} minutes" , 2usize)]"#
+        ));
+        assert!(expanded.contains(r#"slot . name == "n""#));
+        assert!(expanded.contains(r#"slot . name == "number""#));
+    }
+    #[test]
+    fn unknown_slot_types() {
+        let input = parse_quote! {
+            enum Cmd {
+                #[phrase("paint it {c:color}")]
+                Paint { c: String },
+            }
+        };
+        assert_eq!(
+            error(input),
+            "unknown slot type `color`, expected one of: number"
+        );
+        let input = parse_quote! {
+            enum Cmd {
+                #[phrase("wait {seconds}")]
+                Wait { seconds: u32 },
+            }
+        };
+        assert_eq!(
+            error(input),
+            "unknown slot type `seconds`, expected one of: number"
+        );
+        let input = parse_quote! {
+            enum Cmd {
+                #[phrase("wait {:number}")]
+                Wait { seconds: u32 },
+            }
+        };
+        assert_eq!(error(input), "slot `{:number}` has no name");
+        let input = parse_quote! {
+            enum Cmd {
+                #[phrase("wait {n:number")]
+                Wait { n: u32 },
+            }
+        };
+        assert_eq!(
+            error(input),
+            "`{n:number` is not a slot, write `{name:number}`"
+        );
+    }
+    #[test]
+    fn duplicate_phrases() {
+        let input = parse_quote! {
+            enum Cmd {
+                #[phrase("stop")]
+                Stop,
+                #[phrase("halt")]
+                #[phrase(" stop ")]
+                Halt,
+            }
+        };
+        assert_eq!(
+            error(input),
+            "duplicate phrase: \"stop\" can't be told apart from \"stop\" of `Stop`"
+        );
+        let input = parse_quote! {
+            enum Cmd {
+                #[phrase("go to {floor:number}")]
+                Floor { floor: u8 },
+                #[phrase("go to {room:number}")]
+                Room { room: u16 },
+            }
+        };
+        assert_eq!(
+            error(input),
+            "duplicate phrase: \"go to {room:number}\" can't be told apart from \"go to {floor:number}\" of `Floor`"
+        );
+    }
+    #[test]
+    fn slots_match_fields() {
+        let input = parse_quote! {
+            enum Cmd {
+                #[phrase("set volume to {level:number}")]
+                SetVolume { n: u32 },
+            }
+        };
+        assert_eq!(error(input), "slot `level` is not a field of `SetVolume`");
+        let input = parse_quote! {
+            enum Cmd {
+                #[phrase("set volume to {n:number}")]
+                #[phrase("louder")]
+                SetVolume { n: u32 },
+            }
+        };
+        assert_eq!(error(input), "field `n` has no slot in this phrase");
+        let input = parse_quote! {
+            enum Cmd {
+                #[phrase("from {a:number} to {a:number}")]
+                Range { a: u32 },
+            }
+        };
+        assert_eq!(error(input), "slot `a` appears twice");
+    }
+    #[test]
+    fn unsupported_shapes() {
+        let input = parse_quote! {
+            enum Cmd {
+                #[phrase("volume {n:number}")]
+                SetVolume(u32),
+            }
+        };
+        assert_eq!(
+            error(input),
+            "slots fill fields by name, tuple variants aren't supported"
+        );
+        let input = parse_quote! {
+            enum Cmd {
+                #[phrase("stop")]
+                Stop,
+                Pause,
+            }
+        };
+        assert_eq!(
+            error(input),
+            "`Pause` needs at least one #[phrase(\"...\")]"
+        );
+        let input = parse_quote! {
+            enum Cmd {
+                #[phrase("  ")]
+                Stop,
+            }
+        };
+        assert_eq!(error(input), "the phrase is empty");
+        let input = parse_quote! {
+            struct Cmd;
+        };
+        assert_eq!(error(input), "VoiceCommands can only be derived for enums");
+        let input = parse_quote! {
+            enum Cmd {}
+        };
+        assert_eq!(
+            error(input),
+            "VoiceCommands needs an enum with at least one variant"
+        );
+    }
+}